@@ -0,0 +1,82 @@
+// Hot paths behind /members-history, /growth and snapshot backfill, on synthetic guilds
+// large enough to show scaling problems: up to 100k members joining over five years.
+//
+// The bot is a binary crate, so the benches build the dependency-free modules directly.
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[allow(dead_code)]
+#[path = "../src/forecast.rs"]
+mod forecast;
+#[allow(dead_code)]
+#[path = "../src/stats.rs"]
+mod stats;
+
+const DAYS: i64 = 5 * 365;
+
+fn start() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()
+}
+
+/// `members` join times over five years, growing faster towards the end like most servers.
+fn join_dates(members: usize) -> Vec<NaiveDateTime> {
+    let mut rng = StdRng::seed_from_u64(members as u64);
+    let origin = start().and_hms_opt(0, 0, 0).unwrap();
+    let mut dates: Vec<NaiveDateTime> = (0..members)
+        .map(|_| origin + Duration::seconds((rng.gen::<f64>().sqrt() * (DAYS * 86400) as f64) as i64))
+        .collect();
+    dates.sort();
+    dates
+}
+
+/// Left members as (joined, left) timestamps, about one for every ten current members.
+fn departed(members: usize) -> Vec<(i64, i64)> {
+    let mut rng = StdRng::seed_from_u64(members as u64 + 1);
+    let origin = start().and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+    (0..members / 10).map(|_| {
+        let joined = origin + rng.gen_range(0..DAYS * 86400);
+        (joined, joined + rng.gen_range(86400..180 * 86400))
+    }).collect()
+}
+
+fn counting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("member_counts");
+    let end = start() + Duration::days(DAYS);
+    for members in [1_000, 10_000, 100_000] {
+        let dates = join_dates(members);
+        group.bench_with_input(BenchmarkId::from_parameter(members), &dates, |b, dates| {
+            b.iter(|| stats::member_counts(black_box(dates), start(), end))
+        });
+    }
+    group.finish();
+}
+
+fn snapshot_aggregation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reconstruct_counts");
+    let end = start() + Duration::days(DAYS);
+    for members in [1_000, 10_000, 100_000] {
+        let dates = join_dates(members);
+        let left = departed(members);
+        group.bench_with_input(BenchmarkId::from_parameter(members), &(dates, left), |b, (dates, left)| {
+            b.iter(|| stats::reconstruct_counts(black_box(dates), black_box(left), end))
+        });
+    }
+    group.finish();
+}
+
+fn fitting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fit");
+    group.sample_size(10);
+    let dates = join_dates(100_000);
+    let (x, y) = forecast::join_points(&dates);
+    for model in [forecast::Model::Polynomial, forecast::Model::Trend, forecast::Model::Exponential, forecast::Model::Logistic] {
+        group.bench_function(model.key(), |b| b.iter(|| forecast::fit(model, black_box(&x), black_box(&y)).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, counting, snapshot_aggregation, fitting);
+criterion_main!(benches);
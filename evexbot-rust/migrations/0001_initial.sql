@@ -0,0 +1,372 @@
+-- Schema as of the switch to versioned migrations, before the columns added by 0002-0005.
+
+CREATE TABLE IF NOT EXISTS welcome_settings (
+    guild_id INTEGER PRIMARY KEY,
+    is_enabled INTEGER DEFAULT 0,
+    member_increment INTEGER DEFAULT 100,
+    channel_id INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS leave_settings (
+    guild_id INTEGER PRIMARY KEY,
+    is_enabled INTEGER DEFAULT 0,
+    channel_id INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS modlog_settings (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS joingate_settings (
+    guild_id INTEGER PRIMARY KEY,
+    min_account_age_days INTEGER DEFAULT 0,
+    autorole_id INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS pending_autoroles (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    eligible_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS emoji_usage (
+    guild_id INTEGER NOT NULL,
+    emoji_id INTEGER NOT NULL,
+    uses INTEGER DEFAULT 0,
+    last_used INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, emoji_id)
+);
+
+CREATE TABLE IF NOT EXISTS tempvoice_settings (
+    guild_id INTEGER PRIMARY KEY,
+    hub_channel_id INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS temp_voice_channels (
+    channel_id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    owner_id INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS message_activity (
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    day TEXT NOT NULL,
+    hour INTEGER NOT NULL,
+    count INTEGER DEFAULT 0,
+    PRIMARY KEY (guild_id, channel_id, user_id, day, hour)
+);
+
+CREATE TABLE IF NOT EXISTS digest_settings (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER DEFAULT NULL,
+    last_sent_month TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS verify_settings (
+    guild_id INTEGER PRIMARY KEY,
+    role_id INTEGER DEFAULT NULL,
+    email_domain TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS verify_states (
+    state TEXT PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS guild_api_keys (
+    guild_id INTEGER NOT NULL,
+    provider TEXT NOT NULL,
+    nonce BLOB NOT NULL,
+    ciphertext BLOB NOT NULL,
+    updated_by INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, provider)
+);
+
+CREATE TABLE IF NOT EXISTS invite_filter_settings (
+    guild_id INTEGER PRIMARY KEY,
+    mode TEXT DEFAULT 'off',
+    allowlist TEXT DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS tracked_invites (
+    code TEXT PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    creator_id INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    max_uses INTEGER DEFAULT 0,
+    max_age INTEGER DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS invite_joins (
+    guild_id INTEGER NOT NULL,
+    code TEXT NOT NULL,
+    user_id INTEGER NOT NULL,
+    joined_at INTEGER NOT NULL,
+    left_at INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS milestone_announce_settings (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS dbquery_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    query TEXT NOT NULL,
+    row_count INTEGER,
+    error TEXT DEFAULT NULL,
+    executed_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS member_snapshots (
+    guild_id INTEGER NOT NULL,
+    day TEXT NOT NULL,
+    member_count INTEGER NOT NULL,
+    source TEXT NOT NULL DEFAULT 'live',
+    PRIMARY KEY (guild_id, day)
+);
+
+CREATE TABLE IF NOT EXISTS intro_settings (
+    guild_id INTEGER PRIMARY KEY,
+    auto_thread INTEGER DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS intro_templates (
+    guild_id INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    author_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, version)
+);
+
+CREATE TABLE IF NOT EXISTS intros (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    posted_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS privacy_settings (
+    user_id INTEGER PRIMARY KEY,
+    is_enabled INTEGER DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS channel_topics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    text TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS topic_rotation_state (
+    channel_id INTEGER PRIMARY KEY,
+    next_index INTEGER DEFAULT 0,
+    last_rotated TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    starts_at INTEGER NOT NULL,
+    ends_at INTEGER NOT NULL,
+    created_by INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS event_settings (
+    guild_id INTEGER PRIMARY KEY,
+    reminder_channel_id INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS event_voice_announcements (
+    event_id INTEGER PRIMARY KEY,
+    voice_channel_id INTEGER NOT NULL,
+    text_channel_id INTEGER NOT NULL,
+    speaker_role_id INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS batched_joins (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    joined_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS api_tokens (
+    guild_id INTEGER PRIMARY KEY,
+    token_hash TEXT NOT NULL,
+    created_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS external_metrics (
+    guild_id INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    day TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, source, day)
+);
+
+CREATE TABLE IF NOT EXISTS growth_notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    role_id INTEGER DEFAULT NULL,
+    target INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS member_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    flagged INTEGER DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_member_events_guild_time ON member_events (guild_id, created_at);
+
+CREATE TABLE IF NOT EXISTS daily_summary_settings (
+    guild_id INTEGER PRIMARY KEY,
+    is_enabled INTEGER DEFAULT 0,
+    last_sent_day TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS chart_settings (
+    guild_id INTEGER PRIMARY KEY,
+    size TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS automod_settings (
+    guild_id INTEGER PRIMARY KEY,
+    monitor_only INTEGER DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS mod_cases (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    moderator_id INTEGER,
+    target_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    reason TEXT,
+    created_at INTEGER NOT NULL,
+    appeal_status TEXT,
+    appeal_text TEXT,
+    resolved_by INTEGER,
+    resolved_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_mod_cases_target ON mod_cases (guild_id, target_id, created_at);
+
+CREATE TABLE IF NOT EXISTS retention_settings (
+    guild_id INTEGER NOT NULL,
+    data_type TEXT NOT NULL,
+    days INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, data_type)
+);
+
+CREATE TABLE IF NOT EXISTS leader_lease (
+    name TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS triage_channels (
+    channel_id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS triage_snippets (
+    guild_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY (guild_id, tag)
+);
+
+CREATE TABLE IF NOT EXISTS pastes (
+    id TEXT PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    author_id INTEGER NOT NULL,
+    language TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS status_subscriptions (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS milestone_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    member_count INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_milestone_log_guild ON milestone_log (guild_id, created_at);
+
+CREATE TABLE IF NOT EXISTS command_permissions (
+    guild_id INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    target_id INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, command, kind, target_id)
+);
+
+CREATE TABLE IF NOT EXISTS user_prefs (
+    user_id INTEGER PRIMARY KEY,
+    language TEXT,
+    dm_reminders INTEGER NOT NULL DEFAULT 0,
+    quote_privacy INTEGER NOT NULL DEFAULT 0,
+    ephemeral INTEGER NOT NULL DEFAULT 0
+);
+
+-- Quote privacy moved from privacy_settings into user_prefs; carry existing opt-outs over.
+INSERT OR IGNORE INTO user_prefs (user_id, quote_privacy) SELECT user_id, is_enabled FROM privacy_settings WHERE is_enabled != 0;
+
+CREATE TABLE IF NOT EXISTS onboarding_settings (
+    guild_id INTEGER PRIMARY KEY,
+    enabled INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS onboarding_templates (
+    guild_id INTEGER NOT NULL,
+    step INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY (guild_id, step)
+);
+
+CREATE TABLE IF NOT EXISTS onboarding_queue (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    step INTEGER NOT NULL,
+    due_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id, step)
+);
+
+CREATE TABLE IF NOT EXISTS admin_roles (
+    guild_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+CREATE TABLE IF NOT EXISTS guild_config (
+    guild_id INTEGER PRIMARY KEY,
+    language TEXT DEFAULT 'ja'
+);
@@ -0,0 +1,2 @@
+ALTER TABLE intro_settings ADD COLUMN react_emoji TEXT;
+ALTER TABLE intro_settings ADD COLUMN template_locked_by INTEGER;
@@ -0,0 +1,4 @@
+-- Events mirrored from Discord scheduled events carry its id; `reminded` marks the 1-hour reminder as sent.
+ALTER TABLE events ADD COLUMN scheduled_event_id INTEGER;
+ALTER TABLE events ADD COLUMN reminded INTEGER DEFAULT 0;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_events_scheduled ON events (scheduled_event_id);
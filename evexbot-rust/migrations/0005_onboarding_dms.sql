@@ -0,0 +1 @@
+ALTER TABLE user_prefs ADD COLUMN onboarding_dms INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,17 @@
+-- Rules panel per guild; `version` goes up with /rules reaccept.
+CREATE TABLE IF NOT EXISTS rules_settings (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER,
+    message_id INTEGER,
+    role_id INTEGER,
+    version INTEGER NOT NULL DEFAULT 1
+);
+
+-- Latest acceptance per member.
+CREATE TABLE IF NOT EXISTS rules_acceptances (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    accepted_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,12 @@
+-- Links served at /s/<slug>; slugs are global because the URL carries no guild.
+CREATE TABLE IF NOT EXISTS shortlinks (
+    slug TEXT PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    created_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    clicks INTEGER NOT NULL DEFAULT 0,
+    last_clicked_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_shortlinks_guild ON shortlinks (guild_id);
@@ -0,0 +1,24 @@
+-- Weekly "今週の人気投稿" digest; `last_sent` is the ISO week last posted.
+CREATE TABLE IF NOT EXISTS highlight_settings (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER,
+    threshold INTEGER NOT NULL DEFAULT 5,
+    last_sent TEXT
+);
+
+CREATE TABLE IF NOT EXISTS highlight_channels (
+    channel_id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL
+);
+
+-- ⭐/👍 reactions per message in tracked channels, excluding the author's own.
+CREATE TABLE IF NOT EXISTS highlight_scores (
+    message_id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    author_id INTEGER NOT NULL,
+    score INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_highlight_scores_guild_time ON highlight_scores (guild_id, created_at);
@@ -0,0 +1,16 @@
+-- First-message spotlight; `mode` is react, post or both.
+CREATE TABLE IF NOT EXISTS spotlight_settings (
+    guild_id INTEGER PRIMARY KEY,
+    is_enabled INTEGER NOT NULL DEFAULT 0,
+    mode TEXT NOT NULL DEFAULT 'react',
+    channel_id INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS first_messages (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    posted_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,8 @@
+-- The latest "次の目標到達予測" message per guild, re-forecast weekly until the target is reached.
+CREATE TABLE IF NOT EXISTS growth_forecasts (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    target INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
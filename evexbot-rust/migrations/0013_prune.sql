@@ -0,0 +1,20 @@
+-- Days a member was in voice, alongside message_activity, for /prune.
+CREATE TABLE IF NOT EXISTS voice_activity (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    day TEXT NOT NULL,
+    PRIMARY KEY (guild_id, user_id, day)
+);
+
+-- Every member affected by a confirmed /prune run; `run_id` is the preview's interaction id.
+CREATE TABLE IF NOT EXISTS prune_log (
+    run_id INTEGER NOT NULL,
+    guild_id INTEGER NOT NULL,
+    moderator_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    succeeded INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (run_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS prune_log_guild ON prune_log (guild_id, created_at);
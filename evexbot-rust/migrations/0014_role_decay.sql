@@ -0,0 +1,7 @@
+-- Roles taken back from members after `days` without messages or voice, set with /role-decay.
+CREATE TABLE IF NOT EXISTS role_decay_rules (
+    guild_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    days INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
@@ -0,0 +1,28 @@
+-- Contributions counted towards /contributor recognition. `ref_id` is the help thread, starred
+-- message or intro that was replied to, so each one counts once per member.
+CREATE TABLE IF NOT EXISTS contribution_events (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    ref_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id, kind, ref_id)
+);
+
+-- A threshold of 0 leaves that kind of contribution out.
+CREATE TABLE IF NOT EXISTS contributor_settings (
+    guild_id INTEGER PRIMARY KEY,
+    help_threshold INTEGER NOT NULL DEFAULT 0,
+    starboard_threshold INTEGER NOT NULL DEFAULT 0,
+    intro_threshold INTEGER NOT NULL DEFAULT 0,
+    role_id INTEGER,
+    channel_id INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS contributor_awards (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    awarded_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id, kind)
+);
@@ -0,0 +1,8 @@
+-- Message edit/delete logging. Without a row logging is off; edits and deletions can be
+-- turned off separately.
+CREATE TABLE IF NOT EXISTS message_log_settings (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL,
+    log_edits INTEGER NOT NULL DEFAULT 1,
+    log_deletes INTEGER NOT NULL DEFAULT 1
+);
@@ -0,0 +1,12 @@
+-- Help threads tracked for the 解決済み button and /helpstats. Times are unix seconds.
+CREATE TABLE IF NOT EXISTS help_threads (
+    thread_id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    asker_id INTEGER,
+    created_at INTEGER NOT NULL,
+    first_answer_at INTEGER,
+    resolved_at INTEGER,
+    resolved_by INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_help_threads_guild_created ON help_threads (guild_id, created_at);
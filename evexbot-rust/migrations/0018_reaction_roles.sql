@@ -0,0 +1,16 @@
+-- Role menus posted with /reactionrole create. `style` is 'buttons' or 'select'.
+CREATE TABLE IF NOT EXISTS reaction_role_menus (
+    message_id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    style TEXT NOT NULL,
+    title TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS reaction_role_options (
+    message_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (message_id, role_id)
+);
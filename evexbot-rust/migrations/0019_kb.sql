@@ -0,0 +1,16 @@
+-- Curated help answers for /kb. `kb_fts` mirrors title and body with the article id as rowid;
+-- the db accessors keep it in sync, since triggers don't survive the statement splitter.
+CREATE TABLE IF NOT EXISTS kb_articles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    author_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_kb_articles_guild ON kb_articles (guild_id);
+
+-- Trigrams match Japanese text, which has no spaces for a word tokenizer to split on.
+CREATE VIRTUAL TABLE IF NOT EXISTS kb_fts USING fts5(title, body, tokenize = 'trigram');
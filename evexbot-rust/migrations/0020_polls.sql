@@ -0,0 +1,25 @@
+-- Polls from /poll create. `options` holds the choices one per line; `message_id` is set once
+-- the poll message is posted.
+CREATE TABLE IF NOT EXISTS polls (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER,
+    creator_id INTEGER NOT NULL,
+    question TEXT NOT NULL,
+    options TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    closes_at INTEGER NOT NULL,
+    closed INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_polls_open ON polls (closed, closes_at);
+
+-- One vote per member; voting again moves it.
+CREATE TABLE IF NOT EXISTS poll_votes (
+    poll_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    option_index INTEGER NOT NULL,
+    voted_at INTEGER NOT NULL,
+    PRIMARY KEY (poll_id, user_id)
+);
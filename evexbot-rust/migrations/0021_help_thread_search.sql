@@ -0,0 +1,5 @@
+-- Question text of help threads, so new questions can be matched against earlier ones.
+-- rowid is the thread id; filled in by the helpdesk when a thread is tracked.
+ALTER TABLE help_threads ADD COLUMN title TEXT;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS help_thread_fts USING fts5(title, body, tokenize = 'trigram');
@@ -0,0 +1,33 @@
+-- Prompt of the day: where and when to post, the admin-curated prompt list, and each posted
+-- prompt with the thread that collects its submissions. `last_posted` is a JST date and
+-- `last_winner_week` the JST date of the Monday whose week was judged.
+CREATE TABLE IF NOT EXISTS prompt_settings (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL,
+    post_hour INTEGER NOT NULL DEFAULT 9,
+    last_posted TEXT,
+    last_winner_week TEXT
+);
+
+CREATE TABLE IF NOT EXISTS prompts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    used_count INTEGER NOT NULL DEFAULT 0,
+    last_used_at INTEGER,
+    created_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_prompts_guild ON prompts (guild_id);
+
+CREATE TABLE IF NOT EXISTS prompt_posts (
+    message_id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    thread_id INTEGER NOT NULL,
+    prompt TEXT NOT NULL,
+    posted_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_prompt_posts_guild ON prompt_posts (guild_id, posted_at);
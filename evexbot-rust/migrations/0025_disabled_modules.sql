@@ -0,0 +1,8 @@
+-- Modules turned off in a guild with `/module disable`. Every module is on until listed here.
+CREATE TABLE IF NOT EXISTS disabled_modules (
+    guild_id INTEGER NOT NULL,
+    module TEXT NOT NULL,
+    disabled_by INTEGER NOT NULL,
+    disabled_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, module)
+);
@@ -0,0 +1,14 @@
+-- Anti-raid: join burst detection per guild, and the raid mode it triggers. While raid_until
+-- is in the future welcome messages are paused; previous_verification is the verification
+-- level to restore when raid mode ends, if it was raised.
+CREATE TABLE IF NOT EXISTS antiraid_settings (
+    guild_id INTEGER PRIMARY KEY,
+    is_enabled INTEGER NOT NULL DEFAULT 0,
+    joins_per_minute INTEGER NOT NULL DEFAULT 10,
+    pause_minutes INTEGER NOT NULL DEFAULT 15,
+    raise_verification INTEGER NOT NULL DEFAULT 0,
+    kick_account_age_days INTEGER NOT NULL DEFAULT 0,
+    alert_channel_id INTEGER,
+    raid_until INTEGER,
+    previous_verification INTEGER
+);
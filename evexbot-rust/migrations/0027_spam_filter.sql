@@ -0,0 +1,12 @@
+-- Automod spam filter thresholds per guild. A limit of 0 turns that check off; action is
+-- delete, timeout (delete and time out for timeout_minutes) or warn (delete and open a case).
+CREATE TABLE IF NOT EXISTS spam_filter_settings (
+    guild_id INTEGER PRIMARY KEY,
+    is_enabled INTEGER NOT NULL DEFAULT 0,
+    action TEXT NOT NULL DEFAULT 'delete',
+    duplicate_limit INTEGER NOT NULL DEFAULT 4,
+    mention_limit INTEGER NOT NULL DEFAULT 5,
+    emoji_limit INTEGER NOT NULL DEFAULT 15,
+    caps_percent INTEGER NOT NULL DEFAULT 80,
+    timeout_minutes INTEGER NOT NULL DEFAULT 10
+);
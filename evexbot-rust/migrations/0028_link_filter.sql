@@ -0,0 +1,16 @@
+-- Link filter: domains from public anti-phishing feeds, cached so a restart or a failed
+-- fetch keeps the last good list, and the per-guild domains that are never flagged.
+CREATE TABLE IF NOT EXISTS phishing_domains (
+    domain TEXT NOT NULL,
+    source TEXT NOT NULL,
+    fetched_at INTEGER NOT NULL,
+    PRIMARY KEY (source, domain)
+);
+
+CREATE TABLE IF NOT EXISTS link_allowlist (
+    guild_id INTEGER NOT NULL,
+    domain TEXT NOT NULL,
+    added_by INTEGER NOT NULL,
+    added_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, domain)
+);
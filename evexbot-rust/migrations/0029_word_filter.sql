@@ -0,0 +1,12 @@
+-- Word filter: per-guild keyword or regex rules checked against every message. action is
+-- delete, warn (delete and record a warning case) or log (report to the mod-log only).
+CREATE TABLE IF NOT EXISTS word_filter_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    pattern TEXT NOT NULL,
+    is_regex INTEGER NOT NULL DEFAULT 0,
+    action TEXT NOT NULL DEFAULT 'delete',
+    created_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_word_filter_rules_guild ON word_filter_rules (guild_id);
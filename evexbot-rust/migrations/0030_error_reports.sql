@@ -0,0 +1,13 @@
+-- Failed command invocations. The id is the numeric part of the error code shown to the
+-- user (E-WEL-013), so owners can look up the full context with /errorlookup.
+CREATE TABLE IF NOT EXISTS error_reports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command TEXT NOT NULL,
+    guild_id INTEGER,
+    channel_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    options TEXT NOT NULL,
+    error TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_error_reports_created ON error_reports (created_at);
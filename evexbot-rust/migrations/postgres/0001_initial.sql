@@ -0,0 +1,372 @@
+-- Schema as of the switch to versioned migrations, before the columns added by 0002-0005.
+
+CREATE TABLE IF NOT EXISTS welcome_settings (
+    guild_id BIGINT PRIMARY KEY,
+    is_enabled BOOLEAN DEFAULT FALSE,
+    member_increment BIGINT DEFAULT 100,
+    channel_id BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS leave_settings (
+    guild_id BIGINT PRIMARY KEY,
+    is_enabled BOOLEAN DEFAULT FALSE,
+    channel_id BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS modlog_settings (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS joingate_settings (
+    guild_id BIGINT PRIMARY KEY,
+    min_account_age_days BIGINT DEFAULT 0,
+    autorole_id BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS pending_autoroles (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    eligible_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS emoji_usage (
+    guild_id BIGINT NOT NULL,
+    emoji_id BIGINT NOT NULL,
+    uses BIGINT DEFAULT 0,
+    last_used BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, emoji_id)
+);
+
+CREATE TABLE IF NOT EXISTS tempvoice_settings (
+    guild_id BIGINT PRIMARY KEY,
+    hub_channel_id BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS temp_voice_channels (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    owner_id BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS message_activity (
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    day TEXT NOT NULL,
+    hour BIGINT NOT NULL,
+    count BIGINT DEFAULT 0,
+    PRIMARY KEY (guild_id, channel_id, user_id, day, hour)
+);
+
+CREATE TABLE IF NOT EXISTS digest_settings (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT DEFAULT NULL,
+    last_sent_month TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS verify_settings (
+    guild_id BIGINT PRIMARY KEY,
+    role_id BIGINT DEFAULT NULL,
+    email_domain TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS verify_states (
+    state TEXT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS guild_api_keys (
+    guild_id BIGINT NOT NULL,
+    provider TEXT NOT NULL,
+    nonce BYTEA NOT NULL,
+    ciphertext BYTEA NOT NULL,
+    updated_by BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, provider)
+);
+
+CREATE TABLE IF NOT EXISTS invite_filter_settings (
+    guild_id BIGINT PRIMARY KEY,
+    mode TEXT DEFAULT 'off',
+    allowlist TEXT DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS tracked_invites (
+    code TEXT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    creator_id BIGINT NOT NULL,
+    reason TEXT NOT NULL,
+    max_uses BIGINT DEFAULT 0,
+    max_age BIGINT DEFAULT 0,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS invite_joins (
+    guild_id BIGINT NOT NULL,
+    code TEXT NOT NULL,
+    user_id BIGINT NOT NULL,
+    joined_at BIGINT NOT NULL,
+    left_at BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS milestone_announce_settings (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS dbquery_audit (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    query TEXT NOT NULL,
+    row_count BIGINT,
+    error TEXT DEFAULT NULL,
+    executed_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS member_snapshots (
+    guild_id BIGINT NOT NULL,
+    day TEXT NOT NULL,
+    member_count BIGINT NOT NULL,
+    source TEXT NOT NULL DEFAULT 'live',
+    PRIMARY KEY (guild_id, day)
+);
+
+CREATE TABLE IF NOT EXISTS intro_settings (
+    guild_id BIGINT PRIMARY KEY,
+    auto_thread BOOLEAN DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS intro_templates (
+    guild_id BIGINT NOT NULL,
+    version BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    author_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, version)
+);
+
+CREATE TABLE IF NOT EXISTS intros (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    posted_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS privacy_settings (
+    user_id BIGINT PRIMARY KEY,
+    is_enabled BOOLEAN DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS channel_topics (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    text TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS topic_rotation_state (
+    channel_id BIGINT PRIMARY KEY,
+    next_index BIGINT DEFAULT 0,
+    last_rotated TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS events (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    starts_at BIGINT NOT NULL,
+    ends_at BIGINT NOT NULL,
+    created_by BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS event_settings (
+    guild_id BIGINT PRIMARY KEY,
+    reminder_channel_id BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS event_voice_announcements (
+    event_id BIGINT PRIMARY KEY,
+    voice_channel_id BIGINT NOT NULL,
+    text_channel_id BIGINT NOT NULL,
+    speaker_role_id BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS batched_joins (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    joined_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS api_tokens (
+    guild_id BIGINT PRIMARY KEY,
+    token_hash TEXT NOT NULL,
+    created_by BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS external_metrics (
+    guild_id BIGINT NOT NULL,
+    source TEXT NOT NULL,
+    day TEXT NOT NULL,
+    value BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, source, day)
+);
+
+CREATE TABLE IF NOT EXISTS growth_notifications (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    role_id BIGINT DEFAULT NULL,
+    target BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS member_events (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    flagged BOOLEAN DEFAULT FALSE,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_member_events_guild_time ON member_events (guild_id, created_at);
+
+CREATE TABLE IF NOT EXISTS daily_summary_settings (
+    guild_id BIGINT PRIMARY KEY,
+    is_enabled BOOLEAN DEFAULT FALSE,
+    last_sent_day TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS chart_settings (
+    guild_id BIGINT PRIMARY KEY,
+    size TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS automod_settings (
+    guild_id BIGINT PRIMARY KEY,
+    monitor_only BOOLEAN DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS mod_cases (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    moderator_id BIGINT,
+    target_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    reason TEXT,
+    created_at BIGINT NOT NULL,
+    appeal_status TEXT,
+    appeal_text TEXT,
+    resolved_by BIGINT,
+    resolved_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_mod_cases_target ON mod_cases (guild_id, target_id, created_at);
+
+CREATE TABLE IF NOT EXISTS retention_settings (
+    guild_id BIGINT NOT NULL,
+    data_type TEXT NOT NULL,
+    days BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, data_type)
+);
+
+CREATE TABLE IF NOT EXISTS leader_lease (
+    name TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS triage_channels (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS triage_snippets (
+    guild_id BIGINT NOT NULL,
+    tag TEXT NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY (guild_id, tag)
+);
+
+CREATE TABLE IF NOT EXISTS pastes (
+    id TEXT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    language TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS status_subscriptions (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS milestone_log (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    member_count BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_milestone_log_guild ON milestone_log (guild_id, created_at);
+
+CREATE TABLE IF NOT EXISTS command_permissions (
+    guild_id BIGINT NOT NULL,
+    command TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    target_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, command, kind, target_id)
+);
+
+CREATE TABLE IF NOT EXISTS user_prefs (
+    user_id BIGINT PRIMARY KEY,
+    language TEXT,
+    dm_reminders BOOLEAN NOT NULL DEFAULT FALSE,
+    quote_privacy BOOLEAN NOT NULL DEFAULT FALSE,
+    ephemeral BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Quote privacy moved from privacy_settings into user_prefs; carry existing opt-outs over.
+INSERT INTO user_prefs (user_id, quote_privacy) SELECT user_id, is_enabled FROM privacy_settings WHERE is_enabled ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS onboarding_settings (
+    guild_id BIGINT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS onboarding_templates (
+    guild_id BIGINT NOT NULL,
+    step BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY (guild_id, step)
+);
+
+CREATE TABLE IF NOT EXISTS onboarding_queue (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    step BIGINT NOT NULL,
+    due_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id, step)
+);
+
+CREATE TABLE IF NOT EXISTS admin_roles (
+    guild_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+CREATE TABLE IF NOT EXISTS guild_config (
+    guild_id BIGINT PRIMARY KEY,
+    language TEXT DEFAULT 'ja'
+);
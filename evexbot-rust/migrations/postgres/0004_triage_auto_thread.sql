@@ -0,0 +1 @@
+ALTER TABLE triage_channels ADD COLUMN auto_thread BOOLEAN DEFAULT FALSE;
@@ -0,0 +1 @@
+ALTER TABLE user_prefs ADD COLUMN onboarding_dms BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,17 @@
+-- Rules panel per guild; `version` goes up with /rules reaccept.
+CREATE TABLE IF NOT EXISTS rules_settings (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT,
+    message_id BIGINT,
+    role_id BIGINT,
+    version BIGINT NOT NULL DEFAULT 1
+);
+
+-- Latest acceptance per member.
+CREATE TABLE IF NOT EXISTS rules_acceptances (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    version BIGINT NOT NULL,
+    accepted_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
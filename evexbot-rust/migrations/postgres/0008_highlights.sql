@@ -0,0 +1,24 @@
+-- Weekly "今週の人気投稿" digest; `last_sent` is the ISO week last posted.
+CREATE TABLE IF NOT EXISTS highlight_settings (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT,
+    threshold BIGINT NOT NULL DEFAULT 5,
+    last_sent TEXT
+);
+
+CREATE TABLE IF NOT EXISTS highlight_channels (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL
+);
+
+-- ⭐/👍 reactions per message in tracked channels, excluding the author's own.
+CREATE TABLE IF NOT EXISTS highlight_scores (
+    message_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    score BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_highlight_scores_guild_time ON highlight_scores (guild_id, created_at);
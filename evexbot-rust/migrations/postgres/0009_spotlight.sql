@@ -0,0 +1,16 @@
+-- First-message spotlight; `mode` is react, post or both.
+CREATE TABLE IF NOT EXISTS spotlight_settings (
+    guild_id BIGINT PRIMARY KEY,
+    is_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    mode TEXT NOT NULL DEFAULT 'react',
+    channel_id BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS first_messages (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    posted_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,2 @@
+-- Per-guild intro channel set with /intro-channel; NULL falls back to INTRO_CHANNEL_ID.
+ALTER TABLE intro_settings ADD COLUMN intro_channel_id BIGINT;
@@ -0,0 +1,3 @@
+-- Watermark stamped on charts and welcome cards, set with /config chart watermark.
+ALTER TABLE chart_settings ADD COLUMN watermark_text TEXT;
+ALTER TABLE chart_settings ADD COLUMN watermark_logo_url TEXT;
@@ -0,0 +1,8 @@
+-- The latest "次の目標到達予測" message per guild, re-forecast weekly until the target is reached.
+CREATE TABLE IF NOT EXISTS growth_forecasts (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    target BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
@@ -0,0 +1,20 @@
+-- Days a member was in voice, alongside message_activity, for /prune.
+CREATE TABLE IF NOT EXISTS voice_activity (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    day TEXT NOT NULL,
+    PRIMARY KEY (guild_id, user_id, day)
+);
+
+-- Every member affected by a confirmed /prune run; `run_id` is the preview's interaction id.
+CREATE TABLE IF NOT EXISTS prune_log (
+    run_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    moderator_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (run_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS prune_log_guild ON prune_log (guild_id, created_at);
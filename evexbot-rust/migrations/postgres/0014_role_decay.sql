@@ -0,0 +1,7 @@
+-- Roles taken back from members after `days` without messages or voice, set with /role-decay.
+CREATE TABLE IF NOT EXISTS role_decay_rules (
+    guild_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    days BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
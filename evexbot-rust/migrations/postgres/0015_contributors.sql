@@ -0,0 +1,28 @@
+-- Contributions counted towards /contributor recognition. `ref_id` is the help thread, starred
+-- message or intro that was replied to, so each one counts once per member.
+CREATE TABLE IF NOT EXISTS contribution_events (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    ref_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id, kind, ref_id)
+);
+
+-- A threshold of 0 leaves that kind of contribution out.
+CREATE TABLE IF NOT EXISTS contributor_settings (
+    guild_id BIGINT PRIMARY KEY,
+    help_threshold BIGINT NOT NULL DEFAULT 0,
+    starboard_threshold BIGINT NOT NULL DEFAULT 0,
+    intro_threshold BIGINT NOT NULL DEFAULT 0,
+    role_id BIGINT,
+    channel_id BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS contributor_awards (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    awarded_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id, kind)
+);
@@ -0,0 +1,8 @@
+-- Message edit/delete logging. Without a row logging is off; edits and deletions can be
+-- turned off separately.
+CREATE TABLE IF NOT EXISTS message_log_settings (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    log_edits BOOLEAN NOT NULL DEFAULT TRUE,
+    log_deletes BOOLEAN NOT NULL DEFAULT TRUE
+);
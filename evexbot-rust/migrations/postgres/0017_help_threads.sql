@@ -0,0 +1,12 @@
+-- Help threads tracked for the 解決済み button and /helpstats. Times are unix seconds.
+CREATE TABLE IF NOT EXISTS help_threads (
+    thread_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    asker_id BIGINT,
+    created_at BIGINT NOT NULL,
+    first_answer_at BIGINT,
+    resolved_at BIGINT,
+    resolved_by BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_help_threads_guild_created ON help_threads (guild_id, created_at);
@@ -0,0 +1,16 @@
+-- Role menus posted with /reactionrole create. `style` is 'buttons' or 'select'.
+CREATE TABLE IF NOT EXISTS reaction_role_menus (
+    message_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    style TEXT NOT NULL,
+    title TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS reaction_role_options (
+    message_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    PRIMARY KEY (message_id, role_id)
+);
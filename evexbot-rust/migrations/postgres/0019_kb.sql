@@ -0,0 +1,21 @@
+-- Curated help answers for /kb. `kb_fts` mirrors title and body with the article id as rowid;
+-- the db accessors keep it in sync, since triggers don't survive the statement splitter.
+CREATE TABLE IF NOT EXISTS kb_articles (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    author_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_kb_articles_guild ON kb_articles (guild_id);
+
+-- There is no FTS5 here; searches are substring matches on this copy, which keeps the
+-- accessors that write to `kb_fts` the same on both backends.
+CREATE TABLE IF NOT EXISTS kb_fts (
+    rowid BIGINT PRIMARY KEY,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL
+);
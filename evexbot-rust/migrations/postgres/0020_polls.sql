@@ -0,0 +1,25 @@
+-- Polls from /poll create. `options` holds the choices one per line; `message_id` is set once
+-- the poll message is posted.
+CREATE TABLE IF NOT EXISTS polls (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    creator_id BIGINT NOT NULL,
+    question TEXT NOT NULL,
+    options TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    closes_at BIGINT NOT NULL,
+    closed BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS idx_polls_open ON polls (closed, closes_at);
+
+-- One vote per member; voting again moves it.
+CREATE TABLE IF NOT EXISTS poll_votes (
+    poll_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    option_index BIGINT NOT NULL,
+    voted_at BIGINT NOT NULL,
+    PRIMARY KEY (poll_id, user_id)
+);
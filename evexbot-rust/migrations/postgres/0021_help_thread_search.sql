@@ -0,0 +1,9 @@
+-- Question text of help threads, so new questions can be matched against earlier ones.
+-- rowid is the thread id; filled in by the helpdesk when a thread is tracked.
+ALTER TABLE help_threads ADD COLUMN title TEXT;
+
+CREATE TABLE IF NOT EXISTS help_thread_fts (
+    rowid BIGINT PRIMARY KEY,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL
+);
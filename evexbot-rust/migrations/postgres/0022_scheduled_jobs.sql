@@ -0,0 +1,15 @@
+-- /remind reminders (kind 'reminder', run once) and /schedule announcements (kind 'announce',
+-- rescheduled from `cron` after each run). Times are unix seconds.
+CREATE TABLE IF NOT EXISTS scheduled_jobs (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    user_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message TEXT NOT NULL,
+    cron TEXT,
+    next_run_at BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_jobs_next_run ON scheduled_jobs (next_run_at);
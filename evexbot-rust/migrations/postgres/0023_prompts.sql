@@ -0,0 +1,33 @@
+-- Prompt of the day: where and when to post, the admin-curated prompt list, and each posted
+-- prompt with the thread that collects its submissions. `last_posted` is a JST date and
+-- `last_winner_week` the JST date of the Monday whose week was judged.
+CREATE TABLE IF NOT EXISTS prompt_settings (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    post_hour BIGINT NOT NULL DEFAULT 9,
+    last_posted TEXT,
+    last_winner_week TEXT
+);
+
+CREATE TABLE IF NOT EXISTS prompts (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    text TEXT NOT NULL,
+    used_count BIGINT NOT NULL DEFAULT 0,
+    last_used_at BIGINT,
+    created_by BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_prompts_guild ON prompts (guild_id);
+
+CREATE TABLE IF NOT EXISTS prompt_posts (
+    message_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    thread_id BIGINT NOT NULL,
+    prompt TEXT NOT NULL,
+    posted_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_prompt_posts_guild ON prompt_posts (guild_id, posted_at);
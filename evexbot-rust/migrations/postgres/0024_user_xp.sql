@@ -0,0 +1,18 @@
+-- Leveling: XP per member, earned at most once per cooldown from messages, and where
+-- level-ups are announced. A NULL channel announces in the channel the message was sent in.
+CREATE TABLE IF NOT EXISTS user_xp (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    xp BIGINT NOT NULL DEFAULT 0,
+    message_count BIGINT NOT NULL DEFAULT 0,
+    last_xp_at BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_xp_rank ON user_xp (guild_id, xp DESC);
+
+CREATE TABLE IF NOT EXISTS leveling_settings (
+    guild_id BIGINT PRIMARY KEY,
+    announce BOOLEAN NOT NULL DEFAULT TRUE,
+    channel_id BIGINT
+);
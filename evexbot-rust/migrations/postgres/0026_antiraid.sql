@@ -0,0 +1,14 @@
+-- Anti-raid: join burst detection per guild, and the raid mode it triggers. While raid_until
+-- is in the future welcome messages are paused; previous_verification is the verification
+-- level to restore when raid mode ends, if it was raised.
+CREATE TABLE IF NOT EXISTS antiraid_settings (
+    guild_id BIGINT PRIMARY KEY,
+    is_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    joins_per_minute BIGINT NOT NULL DEFAULT 10,
+    pause_minutes BIGINT NOT NULL DEFAULT 15,
+    raise_verification BOOLEAN NOT NULL DEFAULT FALSE,
+    kick_account_age_days BIGINT NOT NULL DEFAULT 0,
+    alert_channel_id BIGINT,
+    raid_until BIGINT,
+    previous_verification BIGINT
+);
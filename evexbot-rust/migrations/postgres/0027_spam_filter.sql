@@ -0,0 +1,12 @@
+-- Automod spam filter thresholds per guild. A limit of 0 turns that check off; action is
+-- delete, timeout (delete and time out for timeout_minutes) or warn (delete and open a case).
+CREATE TABLE IF NOT EXISTS spam_filter_settings (
+    guild_id BIGINT PRIMARY KEY,
+    is_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    action TEXT NOT NULL DEFAULT 'delete',
+    duplicate_limit BIGINT NOT NULL DEFAULT 4,
+    mention_limit BIGINT NOT NULL DEFAULT 5,
+    emoji_limit BIGINT NOT NULL DEFAULT 15,
+    caps_percent BIGINT NOT NULL DEFAULT 80,
+    timeout_minutes BIGINT NOT NULL DEFAULT 10
+);
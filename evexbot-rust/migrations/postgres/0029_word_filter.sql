@@ -0,0 +1,12 @@
+-- Word filter: per-guild keyword or regex rules checked against every message. action is
+-- delete, warn (delete and record a warning case) or log (report to the mod-log only).
+CREATE TABLE IF NOT EXISTS word_filter_rules (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    pattern TEXT NOT NULL,
+    is_regex BOOLEAN NOT NULL DEFAULT FALSE,
+    action TEXT NOT NULL DEFAULT 'delete',
+    created_by BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_word_filter_rules_guild ON word_filter_rules (guild_id);
@@ -0,0 +1,13 @@
+-- Failed command invocations. The id is the numeric part of the error code shown to the
+-- user (E-WEL-013), so owners can look up the full context with /errorlookup.
+CREATE TABLE IF NOT EXISTS error_reports (
+    id BIGSERIAL PRIMARY KEY,
+    command TEXT NOT NULL,
+    guild_id BIGINT,
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    options TEXT NOT NULL,
+    error TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_error_reports_created ON error_reports (created_at);
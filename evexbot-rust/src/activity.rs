@@ -0,0 +1,21 @@
+use anyhow::Result;
+use chrono::{Timelike, Utc};
+use serenity::model::channel::Message;
+use serenity::model::voice::VoiceState;
+
+use crate::db;
+
+/// Count a message towards per-channel, per-user, per-hour activity.
+pub async fn handle_message(message: &Message) -> Result<()> {
+    if message.author.bot { return Ok(()); }
+    let guild_id = match message.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    let now = Utc::now();
+    db::record_message_activity(guild_id, message.channel_id.0 as i64, message.author.id.0 as i64, &now.date_naive().to_string(), now.hour() as i64).await
+}
+
+/// Record the day a member joined or switched voice channels, so `/prune` sees voice-only members.
+pub async fn handle_voice_state_update(state: &VoiceState) -> Result<()> {
+    if state.channel_id.is_none() || state.member.as_ref().map(|m| m.user.bot).unwrap_or(false) { return Ok(()); }
+    let guild_id = match state.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    db::record_voice_activity(guild_id, state.user_id.0 as i64, &Utc::now().date_naive().to_string()).await
+}
@@ -0,0 +1,309 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serenity::builder::{CreateApplicationCommandOption, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::ChannelType;
+use serenity::model::guild::{Member, VerificationLevel};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db;
+use crate::modlog;
+use crate::modules;
+use crate::permissions;
+use crate::scheduler;
+
+/// Joins are counted over this many trailing seconds.
+const WINDOW_SECONDS: i64 = 60;
+const RAID_END_CHECK_INTERVAL_SECONDS: u64 = 60;
+const MIN_JOINS_PER_MINUTE: i64 = 2;
+const MAX_JOINS_PER_MINUTE: i64 = 500;
+const MAX_PAUSE_MINUTES: i64 = 24 * 60;
+const MAX_KICK_ACCOUNT_AGE_DAYS: i64 = 365;
+
+#[derive(Clone, Copy, Debug)]
+struct RecentJoin {
+    at: i64,
+    user_id: UserId,
+    created_at: i64,
+}
+
+/// Recent joins per guild. Only the leader sees joins, so a takeover starts counting afresh.
+static RECENT: Lazy<tokio::sync::Mutex<HashMap<u64, VecDeque<RecentJoin>>>> = Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
+
+/// Add `join` to `window` and drop joins older than the counting window.
+fn record(window: &mut VecDeque<RecentJoin>, join: RecentJoin) {
+    window.push_back(join);
+    while window.front().is_some_and(|j| j.at <= join.at - WINDOW_SECONDS) { window.pop_front(); }
+}
+
+/// Whether an account is young enough to be kicked during a raid. 0 days kicks no one.
+fn is_suspicious(created_at: i64, now: i64, max_age_days: i64) -> bool {
+    max_age_days > 0 && now - created_at < max_age_days * 86400
+}
+
+/// Whether welcome messages are paused because `guild_id` is in raid mode.
+pub async fn is_raid_active(guild_id: i64) -> Result<bool> {
+    let settings = db::get_antiraid_settings(guild_id).await?;
+    Ok(settings.raid_until.is_some_and(|until| until > Utc::now().timestamp()))
+}
+
+/// Count a join and react to a burst. Returns false when the member was kicked and nothing
+/// else should process the join.
+pub async fn handle_member_join(ctx: &Context, member: &Member) -> Result<bool> {
+    if member.user.bot { return Ok(true); }
+    let settings = db::get_antiraid_settings(member.guild_id.0 as i64).await?;
+    if !settings.is_enabled || !modules::is_enabled(member.guild_id, "antiraid").await { return Ok(true); }
+
+    let now = Utc::now().timestamp();
+    let join = RecentJoin { at: now, user_id: member.user.id, created_at: member.user.id.created_at().unix_timestamp() };
+    let recent: Vec<RecentJoin> = {
+        let mut all = RECENT.lock().await;
+        let window = all.entry(member.guild_id.0).or_default();
+        record(window, join);
+        window.iter().copied().collect()
+    };
+
+    if settings.raid_until.is_some_and(|until| until > now) {
+        return Ok(!kick_if_suspicious(&ctx.http, &settings, join, now).await);
+    }
+    if (recent.len() as i64) <= settings.joins_per_minute { return Ok(true); }
+    if !db::start_raid(settings.guild_id, now + settings.pause_minutes * 60, now).await? { return Ok(true); }
+
+    let verification = if settings.raise_verification { raise_verification(&ctx.http, member.guild_id).await } else { None };
+    let mut kicked = 0;
+    let mut kicked_joiner = false;
+    for j in &recent {
+        if kick_if_suspicious(&ctx.http, &settings, *j, now).await {
+            kicked += 1;
+            kicked_joiner |= j.user_id == member.user.id;
+        }
+    }
+
+    let mut embed = CreateEmbed::default();
+    embed.title("🚨 参加の急増を検知しました");
+    embed.description(format!("直近1分間に{}人が参加しました (しきい値: {}人)。\nウェルカムメッセージを <t:{}:R> まで停止しています。", recent.len(), settings.joins_per_minute, now + settings.pause_minutes * 60));
+    embed.field("認証レベル", verification.unwrap_or_else(|| "変更なし".to_string()), true);
+    let kick_summary = if settings.kick_account_age_days > 0 { format!("{}人 (アカウント作成から{}日未満)", kicked, settings.kick_account_age_days) } else { "無効".to_string() };
+    embed.field("キック", kick_summary, true);
+    embed.field("解除", "`/config antiraid end` で今すぐ解除できます", false);
+    embed.color(serenity::utils::Colour::RED);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Anti-Raid"));
+    alert(&ctx.http, &settings, embed).await;
+    log::info!("antiraid: raid mode in {} after {} joins in a minute", settings.guild_id, recent.len());
+    Ok(!kicked_joiner)
+}
+
+/// Kick `join` if raid mode kicks accounts of its age. True when the member was kicked.
+async fn kick_if_suspicious(http: &Http, settings: &db::AntiraidSettings, join: RecentJoin, now: i64) -> bool {
+    if !is_suspicious(join.created_at, now, settings.kick_account_age_days) { return false; }
+    let reason = format!("Anti-raid: account younger than {} days", settings.kick_account_age_days);
+    match GuildId(settings.guild_id as u64).kick_with_reason(http, join.user_id, &reason).await {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("antiraid: failed to kick {} from {}: {}", join.user_id.0, settings.guild_id, e);
+            false
+        }
+    }
+}
+
+/// The level stored by `raise_verification`.
+fn verification_level(num: i64) -> Option<VerificationLevel> {
+    match num {
+        0 => Some(VerificationLevel::None),
+        1 => Some(VerificationLevel::Low),
+        2 => Some(VerificationLevel::Medium),
+        3 => Some(VerificationLevel::High),
+        4 => Some(VerificationLevel::Higher),
+        _ => None,
+    }
+}
+
+/// Raise the verification level to High, remembering the old one. Returns a description
+/// for the alert, or None when nothing changed.
+async fn raise_verification(http: &Http, mut guild_id: GuildId) -> Option<String> {
+    let guild = match http.get_guild(guild_id.0).await {
+        Ok(g) => g,
+        Err(e) => {
+            log::warn!("antiraid: failed to read verification level of {}: {}", guild_id.0, e);
+            return Some("取得に失敗しました".to_string());
+        }
+    };
+    let previous = guild.verification_level;
+    if previous >= VerificationLevel::High { return None; }
+    if let Err(e) = guild_id.edit(http, |g| g.verification_level(VerificationLevel::High)).await {
+        log::warn!("antiraid: failed to raise verification level of {}: {}", guild_id.0, e);
+        return Some("引き上げに失敗しました (サーバー管理権限を確認してください)".to_string());
+    }
+    if let Err(e) = db::set_raid_previous_verification(guild_id.0 as i64, previous.num() as i64).await {
+        log::warn!("antiraid: failed to remember verification level of {}: {}", guild_id.0, e);
+    }
+    Some("「高」に引き上げました (解除時に元に戻します)".to_string())
+}
+
+async fn alert(http: &Http, settings: &db::AntiraidSettings, embed: CreateEmbed) {
+    let result = match settings.alert_channel_id {
+        Some(id) => ChannelId(id as u64).send_message(http, |m| m.embed(|e| { *e = embed; e })).await.map(|_| ()).map_err(anyhow::Error::from),
+        None => modlog::send(http, GuildId(settings.guild_id as u64), embed).await,
+    };
+    if let Err(e) = result { log::warn!("antiraid: failed to alert {}: {}", settings.guild_id, e); }
+}
+
+/// Leave raid mode: restore the verification level and say so. Returns false when the
+/// guild wasn't in raid mode.
+async fn end_raid(http: &Http, settings: &db::AntiraidSettings) -> Result<bool> {
+    if !db::end_raid(settings.guild_id).await? { return Ok(false); }
+    let mut guild_id = GuildId(settings.guild_id as u64);
+    let mut restored = "変更なし".to_string();
+    if let Some(level) = settings.previous_verification.and_then(verification_level) {
+        restored = match guild_id.edit(http, |g| g.verification_level(level)).await {
+            Ok(_) => "元に戻しました".to_string(),
+            Err(e) => {
+                log::warn!("antiraid: failed to restore verification level of {}: {}", settings.guild_id, e);
+                "元に戻せませんでした".to_string()
+            }
+        };
+    }
+    RECENT.lock().await.remove(&guild_id.0);
+    let mut embed = CreateEmbed::default();
+    embed.title("✅ レイド対策を解除しました");
+    embed.description("ウェルカムメッセージを再開しました。");
+    embed.field("認証レベル", restored, true);
+    embed.color(serenity::utils::Colour::DARK_GREEN);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Anti-Raid"));
+    alert(http, settings, embed).await;
+    Ok(true)
+}
+
+/// Start the background job that ends raid mode when its pause runs out.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("antiraid-end", Duration::from_secs(RAID_END_CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move {
+            for settings in db::get_expired_raids(Utc::now().timestamp()).await? {
+                if let Err(e) = end_raid(&http, &settings).await {
+                    log::warn!("antiraid: failed to end raid mode in {}: {}", settings.guild_id, e);
+                }
+            }
+            Ok(())
+        }
+    });
+}
+
+/// Adds the `antiraid` subcommand group to `/config`.
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("antiraid").description("参加の急増 (レイド) への対策").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| s.name("show").description("現在の設定を表示します").kind(CommandOptionType::SubCommand))
+        .create_sub_option(|s| {
+            s.name("detection").description("検知の条件を設定します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("enabled").description("検知する").kind(CommandOptionType::Boolean).required(true))
+                .create_sub_option(|o| o.name("joins-per-minute").description("1分間にこの人数を超えたらレイドとみなします (既定: 10)").kind(CommandOptionType::Integer)
+                    .min_int_value(MIN_JOINS_PER_MINUTE).max_int_value(MAX_JOINS_PER_MINUTE).required(false))
+                .create_sub_option(|o| o.name("pause-minutes").description("ウェルカムメッセージを止める時間 (分, 既定: 15)").kind(CommandOptionType::Integer)
+                    .min_int_value(1).max_int_value(MAX_PAUSE_MINUTES).required(false))
+        })
+        .create_sub_option(|s| {
+            s.name("response").description("レイド時の対応を設定します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("raise-verification").description("認証レベルを「高」に引き上げる").kind(CommandOptionType::Boolean).required(true))
+                .create_sub_option(|o| o.name("kick-account-age-days").description("作成からこの日数未満のアカウントをキック (0で無効)").kind(CommandOptionType::Integer)
+                    .min_int_value(0).max_int_value(MAX_KICK_ACCOUNT_AGE_DAYS).required(false))
+                .create_sub_option(|o| o.name("alert-channel").description("通知先 (省略でモデレーションログ)").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(false))
+        })
+        .create_sub_option(|s| s.name("end").description("レイド対策をすぐに解除します").kind(CommandOptionType::SubCommand))
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let option = |name: &str| sub.options.iter().find(|o| o.name == name);
+    let int = |name: &str| option(name).and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64());
+    let boolean = |name: &str| option(name).and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool());
+
+    let msg = if !permissions::is_admin(member).await {
+        permissions::DENIED_MESSAGE.to_string()
+    } else {
+        let settings = db::get_antiraid_settings(guild_id).await?;
+        match sub.name.as_str() {
+            "detection" => {
+                let enabled = boolean("enabled").unwrap_or(true);
+                let joins = int("joins-per-minute").unwrap_or(settings.joins_per_minute);
+                let pause = int("pause-minutes").unwrap_or(settings.pause_minutes);
+                db::set_antiraid_detection(guild_id, enabled, joins, pause).await?;
+                if enabled { format!("1分間に{}人を超える参加でレイド対策を始め、ウェルカムメッセージを{}分間止めます。", joins, pause) } else { "レイドの検知を停止しました。".to_string() }
+            }
+            "response" => {
+                let raise = boolean("raise-verification").unwrap_or(false);
+                let kick_days = int("kick-account-age-days").unwrap_or(settings.kick_account_age_days);
+                let channel = option("alert-channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Channel(c) => Some(c.id.0 as i64), _ => None });
+                db::set_antiraid_response(guild_id, raise, kick_days, channel).await?;
+                let mut lines = vec![format!("認証レベルの引き上げ: {}", if raise { "する" } else { "しない" })];
+                lines.push(if kick_days > 0 { format!("キック: アカウント作成から{}日未満", kick_days) } else { "キック: しない".to_string() });
+                lines.push(match channel { Some(c) => format!("通知先: <#{}>", c), None => "通知先: モデレーションログ".to_string() });
+                format!("レイド時の対応を設定しました。\n{}", lines.join("\n"))
+            }
+            "end" => {
+                if end_raid(&ctx.http, &settings).await? { "レイド対策を解除しました。".to_string() } else { "レイド対策は実行されていません。".to_string() }
+            }
+            _ => {
+                let state = match settings.raid_until {
+                    Some(until) if until > Utc::now().timestamp() => format!("🚨 レイド対策中 (<t:{}:R> まで)", until),
+                    _ if settings.is_enabled => "✅ 検知中".to_string(),
+                    _ => "⛔ 無効".to_string(),
+                };
+                format!("{}\nしきい値: 1分間に{}人\nウェルカムメッセージの停止: {}分\n認証レベルの引き上げ: {}\nキック: {}\n通知先: {}",
+                    state, settings.joins_per_minute, settings.pause_minutes,
+                    if settings.raise_verification { "する" } else { "しない" },
+                    if settings.kick_account_age_days > 0 { format!("アカウント作成から{}日未満", settings.kick_account_age_days) } else { "しない".to_string() },
+                    settings.alert_channel_id.map(|c| format!("<#{}>", c)).unwrap_or_else(|| "モデレーションログ".to_string()))
+            }
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(at: i64) -> RecentJoin {
+        RecentJoin { at, user_id: UserId(at as u64), created_at: 0 }
+    }
+
+    #[test]
+    fn window_keeps_only_the_last_minute() {
+        let mut window = VecDeque::new();
+        for at in [0, 10, 30, 59] { record(&mut window, join(at)); }
+        assert_eq!(window.len(), 4);
+        record(&mut window, join(60));
+        assert_eq!(window.iter().map(|j| j.at).collect::<Vec<_>>(), vec![10, 30, 59, 60]);
+        record(&mut window, join(200));
+        assert_eq!(window.len(), 1);
+    }
+
+    #[test]
+    fn only_young_accounts_are_suspicious() {
+        let now = 100 * 86400;
+        assert!(is_suspicious(now - 86400, now, 7));
+        assert!(!is_suspicious(now - 8 * 86400, now, 7));
+        assert!(!is_suspicious(now, now, 0));
+    }
+
+    #[test]
+    fn stored_levels_round_trip() {
+        for level in [VerificationLevel::None, VerificationLevel::Low, VerificationLevel::Medium, VerificationLevel::High, VerificationLevel::Higher] {
+            assert_eq!(verification_level(level.num() as i64), Some(level));
+        }
+        assert_eq!(verification_level(255), None);
+    }
+}
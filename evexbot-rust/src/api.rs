@@ -0,0 +1,157 @@
+use anyhow::Result;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{Duration, NaiveDate, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::db;
+use crate::member_cache;
+use crate::stats;
+use crate::web::{self, WebState};
+use crate::permissions;
+
+const DEFAULT_HISTORY_DAYS: i64 = 90;
+const MAX_HISTORY_DAYS: i64 = 730;
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    days: Option<i64>,
+    token: Option<String>,
+}
+
+pub fn routes() -> Router<WebState> {
+    Router::new()
+        .route("/api/guilds/:id/growth", get(growth))
+        .route("/api/guilds/:id/history", get(history))
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// API responses are token-gated, so any origin may fetch them (e.g. charts embedded on a website).
+pub fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    (status, [(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")], Json(body)).into_response()
+}
+
+pub fn error(status: StatusCode, message: &str) -> Response {
+    json_response(status, json!({ "error": message }))
+}
+
+/// Accepts `Authorization: Bearer <token>` or a `?token=` query parameter.
+pub async fn authorize(guild_id: i64, headers: &HeaderMap, query_token: Option<&str>) -> std::result::Result<(), Response> {
+    let token = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer ")).or(query_token);
+    let token = token.ok_or_else(|| error(StatusCode::UNAUTHORIZED, "missing token"))?;
+    let stored = db::get_api_token_hash(guild_id).await.map_err(|_| error(StatusCode::INTERNAL_SERVER_ERROR, "database error"))?;
+    match stored {
+        Some(h) if h == hash_token(token) => Ok(()),
+        _ => Err(error(StatusCode::UNAUTHORIZED, "invalid token")),
+    }
+}
+
+async fn growth(State(state): State<WebState>, Path(id): Path<u64>, Query(q): Query<HistoryQuery>, headers: HeaderMap) -> Response {
+    if let Err(r) = authorize(id as i64, &headers, q.token.as_deref()).await { return r; }
+    match growth_summary(&state, GuildId(id)).await {
+        Ok(body) => json_response(StatusCode::OK, body),
+        Err(e) => {
+            log::warn!("api: growth failed for guild {}: {}", id, e);
+            error(StatusCode::BAD_GATEWAY, "failed to fetch guild data")
+        }
+    }
+}
+
+async fn history(State(state): State<WebState>, Path(id): Path<u64>, Query(q): Query<HistoryQuery>, headers: HeaderMap) -> Response {
+    if let Err(r) = authorize(id as i64, &headers, q.token.as_deref()).await { return r; }
+    let days = q.days.unwrap_or(DEFAULT_HISTORY_DAYS).clamp(1, MAX_HISTORY_DAYS);
+    match history_points(&state, GuildId(id), days).await {
+        Ok(body) => json_response(StatusCode::OK, body),
+        Err(e) => {
+            log::warn!("api: history failed for guild {}: {}", id, e);
+            error(StatusCode::BAD_GATEWAY, "failed to fetch guild data")
+        }
+    }
+}
+
+async fn growth_summary(state: &WebState, guild_id: GuildId) -> Result<serde_json::Value> {
+    let members = member_cache::members(&state.http, guild_id).await?;
+    let now = Utc::now().timestamp();
+    let joined_since = |days: i64| members.iter().filter(|m| m.joined_at.map(|j| j.unix_timestamp() >= now - days * 86400).unwrap_or(false)).count();
+    let member_count = members.len() as i64;
+    let increment = db::get_welcome_settings(guild_id.0 as i64).await?.member_increment;
+    let next_milestone = if increment > 0 { (member_count / increment + 1) * increment } else { member_count };
+
+    Ok(json!({
+        "guild_id": guild_id.0.to_string(),
+        "member_count": member_count,
+        "joined_last_7_days": joined_since(7),
+        "joined_last_30_days": joined_since(30),
+        "next_milestone": next_milestone,
+        "generated_at": Utc::now().to_rfc3339(),
+    }))
+}
+
+/// Daily member counts from snapshots, falling back to counts reconstructed from join dates.
+async fn history_points(state: &WebState, guild_id: GuildId, days: i64) -> Result<serde_json::Value> {
+    let today = Utc::now().date_naive();
+    let since = today - Duration::days(days - 1);
+    let snapshots = db::get_member_snapshots(guild_id.0 as i64, &since.to_string(), &today.to_string()).await?;
+    let (source, points): (&str, Vec<(NaiveDate, i64)>) = if snapshots.is_empty() {
+        let join_dates: Vec<_> = member_cache::members(&state.http, guild_id).await?.iter()
+            .filter_map(|m| m.joined_at.and_then(|j| chrono::DateTime::from_timestamp(j.unix_timestamp(), 0).map(|d| d.naive_utc())))
+            .collect();
+        let (dates, counts) = stats::member_counts(&join_dates, since, today);
+        ("join_dates", dates.into_iter().zip(counts.into_iter().map(|c| c as i64)).collect())
+    } else {
+        ("snapshots", snapshots.into_iter().filter_map(|(d, c)| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok().map(|d| (d, c))).collect())
+    };
+
+    Ok(json!({
+        "guild_id": guild_id.0.to_string(),
+        "source": source,
+        "points": points.iter().map(|(d, c)| json!({ "date": d.to_string(), "member_count": c })).collect::<Vec<_>>(),
+    }))
+}
+
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("apitoken").description("成長データAPIのトークン").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| s.name("generate").description("トークンを発行します (既存のトークンは無効になります)").kind(CommandOptionType::SubCommand))
+        .create_sub_option(|s| s.name("revoke").description("トークンを無効にします").kind(CommandOptionType::SubCommand))
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    let msg = if !permissions::is_admin(member).await {
+        permissions::DENIED_MESSAGE.to_string()
+    } else {
+        match sub.name.as_str() {
+            "generate" => {
+                let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(40).map(char::from).collect();
+                db::set_api_token_hash(guild_id, &hash_token(&token), command.user.id.0 as i64, Utc::now().timestamp()).await?;
+                let base = web::public_base_url();
+                format!("APIトークンを発行しました。このトークンは再表示できないので安全な場所に保存してください。\n```\n{}\n```\nエンドポイント:\n`{}/api/guilds/{}/growth`\n`{}/api/guilds/{}/history?days=90`\n`Authorization: Bearer <token>` ヘッダーを付けてリクエストしてください。", token, base, guild_id, base, guild_id)
+            }
+            "revoke" => {
+                if db::delete_api_token(guild_id).await? { "APIトークンを無効にしました。".to_string() } else { "発行済みのAPIトークンはありません。".to_string() }
+            }
+            _ => return Ok(()),
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::builder::CreateEmbed;
+use serenity::http::Http;
+use serenity::model::application::component::InputTextStyle;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::modal::ModalSubmitInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::guild::Member;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::model::user::User;
+use serenity::prelude::*;
+
+use crate::components::modal_value;
+use crate::db;
+use crate::permissions;
+
+const AUDIT_MEMBER_BAN_ADD: u8 = 22;
+const AUDIT_MEMBER_UPDATE: u8 = 24;
+/// Audit log entries older than this are not tied to the event being handled.
+const AUDIT_MATCH_SECONDS: i64 = 30;
+const MAX_APPEAL_LENGTH: u64 = 1000;
+
+pub fn action_label(action: &str) -> &'static str {
+    match action { "ban" => "BAN", "timeout" => "タイムアウト", "kick" => "キック", "warn" => "警告", _ => "処分" }
+}
+
+/// Who performed `action_type` on `target` according to the audit log, and why.
+async fn find_audit_entry(http: &Http, guild_id: GuildId, action_type: u8, target: UserId) -> (Option<i64>, Option<String>) {
+    let now = Utc::now().timestamp();
+    match guild_id.audit_logs(http, Some(action_type), None, None, Some(5)).await {
+        Ok(logs) => logs.entries.iter()
+            .find(|e| e.target_id == Some(target.0) && now - e.id.created_at().unix_timestamp() <= AUDIT_MATCH_SECONDS)
+            .map(|e| (Some(e.user_id.0 as i64), e.reason.clone()))
+            .unwrap_or((None, None)),
+        Err(_) => (None, None),
+    }
+}
+
+/// Open a case for a ban or timeout and DM the member an appeal button.
+pub async fn open_case(http: &Http, guild_id: GuildId, target: UserId, action: &str, moderator: Option<i64>, reason: Option<&str>) -> Result<i64> {
+    let case_id = db::create_mod_case(guild_id.0 as i64, moderator, target.0 as i64, action, reason, Utc::now().timestamp()).await?;
+    let guild_name = guild_id.to_partial_guild(http).await.map(|g| g.name).unwrap_or_else(|_| guild_id.0.to_string());
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("{} で{}されました", guild_name, action_label(action)));
+    embed.description(format!("理由: {}\n\nこの処分に異議がある場合は、下のボタンから申し立てできます。申し立ては1回のみです。", reason.unwrap_or("指定なし")));
+    embed.color(serenity::utils::Colour::RED);
+    embed.footer(|f| f.text(format!("EvexBot | Case #{}", case_id)));
+    // Members who share no other server with the bot can't be reached once banned.
+    let sent = async {
+        let dm = target.create_dm_channel(http).await?;
+        dm.send_message(http, |m| m.embed(|e| { *e = embed; e }).components(|c| c.create_action_row(|ar| {
+            ar.create_button(|b| b.custom_id(format!("appeal_open:{}", case_id)).label("申し立てる").style(ButtonStyle::Primary))
+        }))).await?;
+        Ok::<_, anyhow::Error>(())
+    }.await;
+    if let Err(e) = sent { log::info!("appeal: could not DM user {} for case {}: {}", target.0, case_id, e); }
+    Ok(case_id)
+}
+
+/// Bans made outside the bot get a case from the audit log.
+pub async fn handle_ban_addition(http: &Http, guild_id: GuildId, user: &User) -> Result<()> {
+    if user.bot { return Ok(()); }
+    let now = Utc::now().timestamp();
+    if db::has_recent_mod_case(guild_id.0 as i64, user.id.0 as i64, "ban", now - AUDIT_MATCH_SECONDS).await? { return Ok(()); }
+    let (moderator, reason) = find_audit_entry(http, guild_id, AUDIT_MEMBER_BAN_ADD, user.id).await;
+    open_case(http, guild_id, user.id, "ban", moderator, reason.as_deref()).await?;
+    Ok(())
+}
+
+/// A member update that newly sets `communication_disabled_until` is a timeout.
+pub async fn handle_member_update(http: &Http, old: Option<&Member>, new: &Member) -> Result<()> {
+    if new.user.bot { return Ok(()); }
+    let now = Utc::now().timestamp();
+    let until = match new.communication_disabled_until { Some(t) if t.unix_timestamp() > now => t, _ => return Ok(()) };
+    if old.and_then(|o| o.communication_disabled_until) == Some(until) { return Ok(()); }
+    let gid = new.guild_id.0 as i64;
+    if db::has_recent_mod_case(gid, new.user.id.0 as i64, "timeout", now - AUDIT_MATCH_SECONDS).await? { return Ok(()); }
+    let (moderator, reason) = find_audit_entry(http, new.guild_id, AUDIT_MEMBER_UPDATE, new.user.id).await;
+    open_case(http, new.guild_id, new.user.id, "timeout", moderator, reason.as_deref()).await?;
+    Ok(())
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let (action, id) = comp.data.custom_id.split_once(':').ok_or_else(|| anyhow::anyhow!("malformed custom_id"))?;
+    let case_id: i64 = id.parse()?;
+    let case = db::get_mod_case(case_id).await?;
+    let db::ModCase { guild_id, target_id, action: kind, appeal_status: status, .. } = match case {
+        Some(c) => c,
+        None => {
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("ケースが見つかりません。").ephemeral(true))).await?;
+            return Ok(());
+        }
+    };
+
+    match action {
+        "appeal_open" => {
+            let error = if comp.user.id.0 as i64 != target_id {
+                Some("この申し立てはあなた宛てではありません。")
+            } else if status.is_some() {
+                Some("このケースはすでに申し立て済みです。")
+            } else { None };
+            if let Some(msg) = error {
+                comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+                return Ok(());
+            }
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::Modal).interaction_response_data(|d| {
+                d.custom_id(format!("appeal_submit:{}", case_id)).title(format!("Case #{} の申し立て", case_id)).components(|c| c.create_action_row(|row| {
+                    row.create_input_text(|t| t.custom_id("text").label("処分の解除を求める理由").style(InputTextStyle::Paragraph).max_length(MAX_APPEAL_LENGTH).required(true))
+                }))
+            })).await?;
+        }
+        "appeal_accept" | "appeal_deny" => {
+            let is_admin = comp.guild_id.map(|g| g.0 as i64) == Some(guild_id) && match comp.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+            if !is_admin {
+                comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(permissions::DENIED_MESSAGE).ephemeral(true))).await?;
+                return Ok(());
+            }
+            let accepted = action == "appeal_accept";
+            let now = Utc::now().timestamp();
+            if !db::resolve_mod_case_appeal(case_id, if accepted { "accepted" } else { "denied" }, comp.user.id.0 as i64, now).await? {
+                comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("この申し立てはすでに処理されています。").ephemeral(true))).await?;
+                return Ok(());
+            }
+
+            let guild = GuildId(guild_id as u64);
+            let target = UserId(target_id as u64);
+            let lifted = if accepted { lift(&ctx.http, guild, target, &kind, case_id).await } else { Ok(()) };
+            let outcome = match (&lifted, accepted) {
+                (Ok(_), true) => format!("✅ {} が承認し、{}を解除しました。", comp.user.mention(), action_label(&kind)),
+                (Err(e), true) => format!("⚠️ {} が承認しましたが、{}の解除に失敗しました: {}", comp.user.mention(), action_label(&kind), e),
+                (_, false) => format!("❌ {} が却下しました。", comp.user.mention()),
+            };
+
+            let mut embed = comp.message.embeds.first().cloned().map(CreateEmbed::from).unwrap_or_default();
+            embed.field("結果", &outcome, false);
+            embed.color(if accepted { serenity::utils::Colour::DARK_GREEN } else { serenity::utils::Colour::DARK_GREY });
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.set_embed(embed).components(|c| c))).await?;
+
+            let guild_name = ctx.cache.guild(guild).map(|g| g.name.clone()).unwrap_or_else(|| guild.0.to_string());
+            let notice = if accepted {
+                format!("{} での Case #{} の申し立ては承認されました。", guild_name, case_id)
+            } else {
+                format!("{} での Case #{} の申し立ては却下されました。", guild_name, case_id)
+            };
+            if let Ok(dm) = target.create_dm_channel(&ctx.http).await { let _ = dm.say(&ctx.http, notice).await; }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn lift(http: &Http, guild_id: GuildId, target: UserId, kind: &str, case_id: i64) -> Result<()> {
+    let reason = format!("appeal accepted for case #{}", case_id);
+    match kind {
+        "ban" => http.remove_ban(guild_id.0, target.0, Some(&reason)).await?,
+        "timeout" => { guild_id.edit_member(http, target, |m| m.enable_communication()).await?; }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub async fn handle_modal(ctx: &Context, modal: &ModalSubmitInteraction) -> Result<()> {
+    let case_id: i64 = modal.data.custom_id.trim_start_matches("appeal_submit:").parse()?;
+    let text = modal_value(modal, "text").unwrap_or_default();
+    let reply = |msg: &'static str| async move {
+        modal.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await
+    };
+
+    let db::ModCase { guild_id, target_id, action: kind, reason, .. } = match db::get_mod_case(case_id).await? {
+        Some(c) if c.target_id == modal.user.id.0 as i64 => c,
+        _ => { reply("ケースが見つかりません。").await?; return Ok(()); }
+    };
+    if text.trim().is_empty() { reply("理由を入力してください。").await?; return Ok(()); }
+    let channel_id = match db::get_modlog_channel(guild_id).await? {
+        Some(c) => ChannelId(c as u64),
+        None => { reply("このサーバーでは申し立てを受け付けていません。").await?; return Ok(()); }
+    };
+    if !db::submit_mod_case_appeal(case_id, text.trim()).await? { reply("このケースはすでに申し立て済みです。").await?; return Ok(()); }
+
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("申し立て: Case #{}", case_id));
+    embed.description(format!("対象: <@{}> ({})\n処分: {}\n処分理由: {}", target_id, modal.user.tag(), action_label(&kind), reason.as_deref().unwrap_or("指定なし")));
+    embed.field("申し立て内容", text.trim(), false);
+    embed.color(serenity::utils::Colour::ORANGE);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Appeal"));
+    let posted = channel_id.send_message(&ctx.http, |m| m.embed(|e| { *e = embed; e }).components(|c| c.create_action_row(|ar| {
+        ar.create_button(|b| b.custom_id(format!("appeal_accept:{}", case_id)).label("承認して解除").style(ButtonStyle::Success));
+        ar.create_button(|b| b.custom_id(format!("appeal_deny:{}", case_id)).label("却下").style(ButtonStyle::Danger))
+    }))).await;
+    if let Err(e) = posted { log::warn!("appeal: failed to post appeal for case {} to modlog: {}", case_id, e); }
+    reply("申し立てを送信しました。モデレーターの判断をお待ちください。").await?;
+    Ok(())
+}
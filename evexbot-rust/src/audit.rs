@@ -0,0 +1,196 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::PermissionOverwriteType;
+use serenity::model::id::{GuildId, RoleId};
+use serenity::model::permissions::Permissions;
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::commands::{Command, Invocation};
+use crate::permissions;
+
+const FINDINGS_PER_PAGE: usize = 8;
+const REPORT_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Reports are kept in memory so the page buttons can re-render without rescanning.
+type Reports = HashMap<u64, (Instant, Vec<String>)>;
+static REPORTS: Lazy<Mutex<Reports>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity { High, Medium, Low }
+
+impl Severity {
+    fn icon(self) -> &'static str {
+        match self { Severity::High => "🔴", Severity::Medium => "🟠", Severity::Low => "🟡" }
+    }
+}
+
+/// Permissions that should never be granted to @everyone.
+fn dangerous_for_everyone() -> Permissions {
+    Permissions::ADMINISTRATOR | Permissions::MANAGE_GUILD | Permissions::MANAGE_ROLES | Permissions::MANAGE_CHANNELS
+        | Permissions::MANAGE_WEBHOOKS | Permissions::BAN_MEMBERS | Permissions::KICK_MEMBERS | Permissions::MENTION_EVERYONE
+        | Permissions::MANAGE_MESSAGES | Permissions::MODERATE_MEMBERS
+}
+
+pub struct AuditCommand;
+
+#[async_trait]
+impl Command for AuditCommand {
+    fn name(&self) -> &'static str { "audit" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("サーバー設定の監査").create_option(|o| {
+            o.name("permissions").description("ロールとチャンネルの危険な権限設定を検出します").kind(CommandOptionType::SubCommand)
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_audit_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn scan_guild(http: &Http, guild_id: GuildId) -> Result<Vec<(Severity, String)>> {
+    let mut findings = Vec::new();
+    let everyone = RoleId(guild_id.0);
+    let roles = guild_id.roles(http).await?;
+
+    for role in roles.values() {
+        let is_everyone = role.id == everyone;
+        let name = if is_everyone { "@everyone".to_string() } else { format!("<@&{}>", role.id.0) };
+
+        if is_everyone {
+            let bad = role.permissions & dangerous_for_everyone();
+            if !bad.is_empty() {
+                findings.push((Severity::High, format!("{} に危険な権限があります: {}", name, bad)));
+            }
+            continue;
+        }
+
+        if role.permissions.administrator() {
+            if role.mentionable {
+                findings.push((Severity::High, format!("{} は管理者権限を持ち、誰でもメンションできます", name)));
+            } else if !role.managed {
+                findings.push((Severity::Low, format!("{} は管理者権限を持っています (付与対象を確認してください)", name)));
+            }
+        } else if role.permissions.mention_everyone() {
+            findings.push((Severity::Medium, format!("{} は @everyone / @here をメンションできます", name)));
+        }
+        if role.permissions.manage_webhooks() && !role.permissions.administrator() {
+            findings.push((Severity::Medium, format!("{} は Webhook を作成・編集できます", name)));
+        }
+    }
+
+    let channels = guild_id.channels(http).await?;
+    let mut open_webhook_channels = Vec::new();
+    for channel in channels.values() {
+        for ow in channel.permission_overwrites.iter() {
+            if ow.kind != PermissionOverwriteType::Role(everyone) { continue; }
+            let bad = ow.allow & dangerous_for_everyone();
+            if !bad.is_empty() {
+                findings.push((Severity::High, format!("<#{}> で @everyone に危険な権限が許可されています: {}", channel.id.0, bad)));
+            }
+            if ow.allow.manage_webhooks() {
+                open_webhook_channels.push(channel.id);
+            }
+        }
+    }
+
+    match guild_id.webhooks(http).await {
+        Ok(webhooks) => {
+            for wh in webhooks.iter() {
+                let channel_id = match wh.channel_id { Some(c) => c, None => continue };
+                if open_webhook_channels.contains(&channel_id) {
+                    findings.push((Severity::High, format!("Webhook `{}` (<#{}>) は誰でも管理可能なチャンネルにあります", wh.name.clone().unwrap_or_default(), channel_id.0)));
+                }
+            }
+            if webhooks.len() > 10 {
+                findings.push((Severity::Low, format!("Webhook が {} 個あります。不要なものを削除してください", webhooks.len())));
+            }
+        }
+        Err(_) => findings.push((Severity::Low, "Webhook 一覧を取得できませんでした (Webhookの管理権限が必要です)".to_string())),
+    }
+
+    findings.sort_by_key(|f| f.0);
+    Ok(findings)
+}
+
+fn build_page(lines: &[String], page: usize) -> CreateEmbed {
+    let pages = lines.len().div_ceil(FINDINGS_PER_PAGE).max(1);
+    let page = page.min(pages - 1);
+    let mut embed = CreateEmbed::default();
+    embed.title("権限監査レポート");
+    if lines.is_empty() {
+        embed.description("危険な設定は見つかりませんでした。");
+        embed.color(serenity::utils::Colour::DARK_GREEN);
+    } else {
+        let body: Vec<&str> = lines.iter().skip(page * FINDINGS_PER_PAGE).take(FINDINGS_PER_PAGE).map(|s| s.as_str()).collect();
+        embed.description(body.join("\n"));
+        embed.color(serenity::utils::Colour::RED);
+    }
+    embed.footer(|f| f.text(format!("EvexBot | Audit  {}/{} ページ ({}件)", page + 1, pages, lines.len())));
+    embed
+}
+
+async fn handle_audit_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+
+    let findings = scan_guild(&ctx.http, guild_id).await?;
+    let lines: Vec<String> = findings.into_iter().map(|(sev, text)| format!("{} {}", sev.icon(), text)).collect();
+    let report_id = command.id.0;
+    let embed = build_page(&lines, 0);
+    let pages = lines.len().div_ceil(FINDINGS_PER_PAGE).max(1);
+    {
+        let mut reports = REPORTS.lock().await;
+        reports.retain(|_, (created, _)| created.elapsed() < REPORT_TTL);
+        reports.insert(report_id, (Instant::now(), lines));
+    }
+
+    command.create_followup_message(&ctx.http, |m| {
+        m.ephemeral(true).embed(|e| { *e = embed; e });
+        if pages > 1 {
+            m.components(|c| page_buttons(c, report_id, 0, pages));
+        }
+        m
+    }).await?;
+    Ok(())
+}
+
+fn page_buttons(c: &mut serenity::builder::CreateComponents, report_id: u64, page: usize, pages: usize) -> &mut serenity::builder::CreateComponents {
+    c.create_action_row(|ar| {
+        ar.create_button(|b| b.custom_id(format!("audit_page:{}:{}", report_id, page.saturating_sub(1))).label("◀").style(ButtonStyle::Secondary).disabled(page == 0));
+        ar.create_button(|b| b.custom_id(format!("audit_page:{}:{}", report_id, page + 1)).label("▶").style(ButtonStyle::Secondary).disabled(page + 1 >= pages))
+    })
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let mut parts = comp.data.custom_id.splitn(3, ':').skip(1);
+    let report_id: u64 = parts.next().unwrap_or("").parse()?;
+    let page: usize = parts.next().unwrap_or("").parse()?;
+
+    let lines = REPORTS.lock().await.get(&report_id).map(|(_, l)| l.clone());
+    let lines = match lines {
+        Some(l) => l,
+        None => {
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("レポートの有効期限が切れました。もう一度 /audit permissions を実行してください。").ephemeral(true))).await?;
+            return Ok(());
+        }
+    };
+    let pages = lines.len().div_ceil(FINDINGS_PER_PAGE).max(1);
+    let page = page.min(pages - 1);
+    let embed = build_page(&lines, page);
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.set_embed(embed).components(|c| page_buttons(c, report_id, page, pages)))).await?;
+    Ok(())
+}
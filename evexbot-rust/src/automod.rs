@@ -0,0 +1,370 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::channel::Message;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use tokio::sync::Mutex;
+
+use crate::appeal;
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::modlog;
+use crate::permissions;
+
+static INVITE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?:https?://)?(?:www\.)?(?:discord\.gg|discord(?:app)?\.com/invite)/([A-Za-z0-9-]+)").unwrap());
+/// invite code -> guild id it points to (None when the invite is invalid or expired)
+static INVITE_CACHE: Lazy<Mutex<HashMap<String, Option<u64>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static CUSTOM_EMOJI_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<a?:\w+:\d+>").unwrap());
+/// (guild, author) -> (sent at, content hash) of their recent messages, for the repeat check.
+type RecentMessages = HashMap<(u64, u64), VecDeque<(i64, u64)>>;
+static RECENT_MESSAGES: Lazy<Mutex<RecentMessages>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Identical messages count as repeats within this many seconds of each other.
+const DUPLICATE_WINDOW_SECONDS: i64 = 30;
+/// Messages with fewer cased letters are too short for the caps check ("OK", "LOL").
+const CAPS_MIN_LETTERS: usize = 10;
+const SPAM_ACTIONS: [&str; 3] = ["delete", "timeout", "warn"];
+const MAX_SPAM_TIMEOUT_MINUTES: i64 = 28 * 24 * 60;
+const QUOTED_CONTENT_LENGTH: usize = 200;
+
+/// Run automod checks on a message. Returns true when the message was removed and
+/// later message handlers should not process it.
+pub async fn handle_message(ctx: &Context, message: &Message) -> Result<bool> {
+    if message.author.bot { return Ok(false); }
+    let guild_id = match message.guild_id { Some(g) => g, None => return Ok(false) };
+    if permissions::is_exempt(ctx, guild_id, message).await { return Ok(false); }
+
+    if check_invites(ctx, guild_id, message).await? { return Ok(true); }
+    check_spam(ctx, guild_id, message).await
+}
+
+/// In monitor-only mode automod reports what it would have done to the mod-log without
+/// touching the message, so rules can be tuned before they are enforced.
+pub async fn is_monitor_only(guild_id: GuildId) -> bool {
+    db::get_automod_monitor_only(guild_id.0 as i64).await.unwrap_or(false)
+}
+
+/// Mod-log title for an action, marked as not executed in monitor-only mode.
+pub fn action_title(title: &str, monitor_only: bool) -> String {
+    if monitor_only { format!("[監視モード] {} (未実行)", title) } else { title.to_string() }
+}
+
+async fn resolve_invite_guild(http: &Http, code: &str) -> Option<u64> {
+    if let Some(cached) = INVITE_CACHE.lock().await.get(code) {
+        return *cached;
+    }
+    let resolved = http.get_invite(code, false, false, None).await.ok().and_then(|inv| inv.guild.map(|g| g.id.0));
+    INVITE_CACHE.lock().await.insert(code.to_string(), resolved);
+    resolved
+}
+
+async fn check_invites(ctx: &Context, guild_id: GuildId, message: &Message) -> Result<bool> {
+    let codes: Vec<String> = INVITE_RE.captures_iter(&message.content).map(|c| c[1].to_string()).collect();
+    if codes.is_empty() { return Ok(false); }
+
+    let (mode, allowlist) = db::get_invite_filter_settings(guild_id.0 as i64).await?;
+    if mode == "off" { return Ok(false); }
+    let allowed: Vec<&str> = allowlist.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+
+    let mut foreign = Vec::new();
+    for code in codes.iter() {
+        if allowed.iter().any(|a| a.eq_ignore_ascii_case(code)) { continue; }
+        match resolve_invite_guild(&ctx.http, code).await {
+            Some(g) if g == guild_id.0 || allowed.contains(&g.to_string().as_str()) => {}
+            _ => foreign.push(code.clone()),
+        }
+    }
+    if foreign.is_empty() { return Ok(false); }
+
+    let monitor_only = is_monitor_only(guild_id).await;
+    let deleted = if monitor_only {
+        false
+    } else if mode == "delete" {
+        message.delete(&ctx.http).await?;
+        let _ = message.channel_id.say(&ctx.http, format!("{} 他サーバーの招待リンクは投稿できません。", message.author.mention())).await;
+        true
+    } else {
+        message.channel_id.edit_message(&ctx.http, message.id, |m| m.suppress_embeds(true)).await?;
+        false
+    };
+
+    let mut embed = CreateEmbed::default();
+    embed.title(action_title(if mode == "delete" { "招待リンクを削除しました" } else { "招待リンクの埋め込みを非表示にしました" }, monitor_only));
+    embed.description(format!("投稿者: {}\nチャンネル: <#{}>\n招待コード: {}", message.author.mention(), message.channel_id.0, foreign.join(", ")));
+    embed.color(serenity::utils::Colour::ORANGE);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | AutoMod"));
+    modlog::send(&ctx.http, guild_id, embed).await?;
+    Ok(deleted)
+}
+
+/// Why the spam filter flagged a message.
+enum Spam {
+    Repeated(usize),
+    Mentions(usize),
+    Emoji(usize),
+    Caps(i64),
+}
+
+impl Spam {
+    fn describe(&self) -> String {
+        match self {
+            Spam::Repeated(n) => format!("同じメッセージの連投 ({}回)", n),
+            Spam::Mentions(n) => format!("大量のメンション ({}件)", n),
+            Spam::Emoji(n) => format!("大量の絵文字 ({}個)", n),
+            Spam::Caps(p) => format!("大文字の多用 ({}%)", p),
+        }
+    }
+}
+
+/// Custom emoji plus pictographic characters; flags and keycaps count once per code point.
+fn emoji_count(content: &str) -> usize {
+    let custom = CUSTOM_EMOJI_RE.find_iter(content).count();
+    let unicode = CUSTOM_EMOJI_RE.replace_all(content, "").chars()
+        .filter(|c| matches!(*c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF))
+        .count();
+    custom + unicode
+}
+
+/// Share of cased letters that are upper case, or None when there are too few letters to tell.
+fn caps_percent(content: &str) -> Option<i64> {
+    let text = CUSTOM_EMOJI_RE.replace_all(content, "");
+    let letters: Vec<char> = text.chars().filter(|c| c.is_uppercase() || c.is_lowercase()).collect();
+    if letters.len() < CAPS_MIN_LETTERS { return None; }
+    Some((letters.iter().filter(|c| c.is_uppercase()).count() * 100 / letters.len()) as i64)
+}
+
+/// Add a message to its author's history and return how many of the recent ones, this one
+/// included, have the same content.
+fn record_message(history: &mut VecDeque<(i64, u64)>, at: i64, hash: u64) -> usize {
+    history.push_back((at, hash));
+    while history.front().is_some_and(|(t, _)| *t <= at - DUPLICATE_WINDOW_SECONDS) { history.pop_front(); }
+    history.iter().filter(|(_, h)| *h == hash).count()
+}
+
+/// Forget authors with nothing left inside the window, so the map only holds recent posters.
+fn sweep(recent: &mut RecentMessages, now: i64) {
+    recent.retain(|_, history| history.back().is_some_and(|(t, _)| *t > now - DUPLICATE_WINDOW_SECONDS));
+}
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.trim().to_lowercase().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The first limit `message` goes over. Limits of 0 are off.
+fn detect(settings: &db::SpamFilterSettings, message: &Message, repeats: usize) -> Option<Spam> {
+    let over = |value: i64, limit: i64| limit > 0 && value >= limit;
+    if over(repeats as i64, settings.duplicate_limit) { return Some(Spam::Repeated(repeats)); }
+    let mentions = message.mentions.len() + message.mention_roles.len() + usize::from(message.mention_everyone);
+    if over(mentions as i64, settings.mention_limit) { return Some(Spam::Mentions(mentions)); }
+    let emoji = emoji_count(&message.content);
+    if over(emoji as i64, settings.emoji_limit) { return Some(Spam::Emoji(emoji)); }
+    match caps_percent(&message.content) {
+        Some(p) if over(p, settings.caps_percent) => Some(Spam::Caps(p)),
+        _ => None,
+    }
+}
+
+async fn check_spam(ctx: &Context, guild_id: GuildId, message: &Message) -> Result<bool> {
+    let settings = db::get_spam_filter_settings(guild_id.0 as i64).await?;
+    if !settings.is_enabled { return Ok(false); }
+
+    let now = Utc::now().timestamp();
+    let repeats = if message.content.trim().is_empty() { 0 } else {
+        let mut recent = RECENT_MESSAGES.lock().await;
+        sweep(&mut recent, now);
+        record_message(recent.entry((guild_id.0, message.author.id.0)).or_default(), now, content_hash(&message.content))
+    };
+    let spam = match detect(&settings, message, repeats) { Some(s) => s, None => return Ok(false) };
+    let reason = format!("AutoMod: {}", spam.describe());
+
+    let monitor_only = is_monitor_only(guild_id).await;
+    let mut case_id = None;
+    if !monitor_only {
+        message.delete(&ctx.http).await?;
+        match settings.action.as_str() {
+            "timeout" => {
+                let id = appeal::open_case(&ctx.http, guild_id, message.author.id, "timeout", None, Some(&reason)).await?;
+                let until = Utc::now() + chrono::Duration::minutes(settings.timeout_minutes);
+                match guild_id.edit_member(&ctx.http, message.author.id, |m| m.disable_communication_until(until.to_rfc3339())).await {
+                    Ok(_) => case_id = Some(id),
+                    Err(e) => {
+                        log::warn!("automod: failed to time out {} in {}: {}", message.author.id.0, guild_id.0, e);
+                        db::delete_mod_case(id).await?;
+                    }
+                }
+            }
+            "warn" => case_id = Some(db::create_mod_case(guild_id.0 as i64, None, message.author.id.0 as i64, "warn", Some(&reason), now).await?),
+            _ => {}
+        }
+        let _ = message.channel_id.say(&ctx.http, format!("{} スパムと判断されたため、メッセージを削除しました。", message.author.mention())).await;
+    }
+
+    let title = match settings.action.as_str() {
+        "timeout" => format!("スパムを削除し、{}分間タイムアウトしました", settings.timeout_minutes),
+        "warn" => "スパムを削除し、警告しました".to_string(),
+        _ => "スパムを削除しました".to_string(),
+    };
+    let quoted: String = message.content.chars().take(QUOTED_CONTENT_LENGTH).collect();
+    let mut embed = CreateEmbed::default();
+    embed.title(action_title(&title, monitor_only));
+    embed.description(format!("投稿者: {}\nチャンネル: <#{}>\n理由: {}", message.author.mention(), message.channel_id.0, spam.describe()));
+    if !quoted.is_empty() { embed.field("内容", quoted, false); }
+    if let Some(id) = case_id { embed.field("ケース", format!("#{}", id), true); }
+    embed.color(serenity::utils::Colour::ORANGE);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | AutoMod"));
+    modlog::send(&ctx.http, guild_id, embed).await?;
+    Ok(!monitor_only)
+}
+
+pub struct AutomodCommand;
+
+#[async_trait]
+impl Command for AutomodCommand {
+    fn name(&self) -> &'static str { "automod" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("自動モデレーションの設定").create_option(|o| {
+            o.name("invites").description("他サーバーの招待リンクへの対応").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|s| {
+                    s.name("mode").description("off: 何もしない / suppress: 埋め込みを非表示 / delete: 削除").kind(CommandOptionType::String).required(true)
+                        .add_string_choice("off", "off")
+                        .add_string_choice("suppress", "suppress")
+                        .add_string_choice("delete", "delete")
+                })
+                .create_sub_option(|s| s.name("allowlist").description("許可する招待コードまたはサーバーID (カンマ区切り)").kind(CommandOptionType::String).required(false))
+        })
+        .create_option(|o| {
+            o.name("spam").description("連投・大量メンション・絵文字・大文字の多用への対応 (0でその項目を無効)").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|s| s.name("enabled").description("有効にする").kind(CommandOptionType::Boolean).required(true))
+                .create_sub_option(|s| {
+                    s.name("action").description("delete: 削除 / timeout: 削除してタイムアウト / warn: 削除して警告").kind(CommandOptionType::String).required(false)
+                        .add_string_choice("delete", "delete")
+                        .add_string_choice("timeout", "timeout")
+                        .add_string_choice("warn", "warn")
+                })
+                .create_sub_option(|s| s.name("repeats").description(format!("{}秒以内に同じメッセージをこの回数送ったら対応", DUPLICATE_WINDOW_SECONDS)).kind(CommandOptionType::Integer).min_int_value(0).max_int_value(20).required(false))
+                .create_sub_option(|s| s.name("mentions").description("1つのメッセージのメンション数の上限").kind(CommandOptionType::Integer).min_int_value(0).max_int_value(100).required(false))
+                .create_sub_option(|s| s.name("emoji").description("1つのメッセージの絵文字数の上限").kind(CommandOptionType::Integer).min_int_value(0).max_int_value(200).required(false))
+                .create_sub_option(|s| s.name("caps").description("大文字の割合の上限 (%)").kind(CommandOptionType::Integer).min_int_value(0).max_int_value(100).required(false))
+                .create_sub_option(|s| s.name("timeout-minutes").description("timeout のときの時間 (分)").kind(CommandOptionType::Integer).min_int_value(1).max_int_value(MAX_SPAM_TIMEOUT_MINUTES).required(false))
+        })
+        .create_option(|o| {
+            o.name("monitor").description("監視モード: 削除などを行わず、実行予定の内容をログに記録します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|s| s.name("enabled").description("有効にする").kind(CommandOptionType::Boolean).required(true))
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_automod_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_automod_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    match sub.name.as_str() {
+        "invites" => {
+            let mode = sub.options.iter().find(|o| o.name=="mode").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("off");
+            let allowlist = sub.options.iter().find(|o| o.name=="allowlist").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str());
+            if !["off", "suppress", "delete"].contains(&mode) { command.create_followup_message(&ctx.http, |m| m.content("off、suppress、deleteのいずれかを指定してください。").ephemeral(true)).await?; return Ok(()); }
+            db::update_invite_filter_settings(guild_id, mode, allowlist).await?;
+            let (_, current) = db::get_invite_filter_settings(guild_id).await?;
+            command.create_followup_message(&ctx.http, |m| m.content(format!("招待リンクフィルターを {} に設定しました。\n許可リスト: {}", mode, if current.is_empty() { "なし" } else { current.as_str() })).ephemeral(true)).await?;
+        }
+        "spam" => {
+            let int = |name: &str| sub.options.iter().find(|o| o.name == name).and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64());
+            let mut settings = db::get_spam_filter_settings(guild_id).await?;
+            settings.is_enabled = sub.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+            if let Some(action) = sub.options.iter().find(|o| o.name=="action").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()) {
+                if !SPAM_ACTIONS.contains(&action) { command.create_followup_message(&ctx.http, |m| m.content("delete、timeout、warnのいずれかを指定してください。").ephemeral(true)).await?; return Ok(()); }
+                settings.action = action.to_string();
+            }
+            settings.duplicate_limit = int("repeats").unwrap_or(settings.duplicate_limit);
+            settings.mention_limit = int("mentions").unwrap_or(settings.mention_limit);
+            settings.emoji_limit = int("emoji").unwrap_or(settings.emoji_limit);
+            settings.caps_percent = int("caps").unwrap_or(settings.caps_percent);
+            settings.timeout_minutes = int("timeout-minutes").unwrap_or(settings.timeout_minutes).clamp(1, MAX_SPAM_TIMEOUT_MINUTES);
+            db::set_spam_filter_settings(&settings).await?;
+            let limit = |v: i64, unit: &str| if v > 0 { format!("{}{}", v, unit) } else { "無効".to_string() };
+            let msg = if settings.is_enabled {
+                format!("スパムフィルターを有効にしました (対応: {})。\n連投: {}\nメンション: {}\n絵文字: {}\n大文字: {}",
+                    if settings.action == "timeout" { format!("timeout {}分", settings.timeout_minutes) } else { settings.action.clone() },
+                    limit(settings.duplicate_limit, "回"), limit(settings.mention_limit, "件"), limit(settings.emoji_limit, "個"), limit(settings.caps_percent, "%"))
+            } else {
+                "スパムフィルターを無効にしました。".to_string()
+            };
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+        }
+        "monitor" => {
+            let enabled = sub.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+            db::update_automod_monitor_only(guild_id, enabled).await?;
+            let msg = if enabled {
+                "監視モードを有効にしました。自動モデレーションはメッセージを削除・非表示にせず、実行予定の内容をモデレーションログに記録します。"
+            } else {
+                "監視モードを無効にしました。自動モデレーションのルールが適用されます。"
+            };
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_and_unicode_emoji_are_counted() {
+        assert_eq!(emoji_count("hi <:kekw:123456> <a:party:42> 🎉🎉 ☀"), 5);
+        assert_eq!(emoji_count("こんにちは、日本語です"), 0);
+    }
+
+    #[test]
+    fn caps_ignore_short_and_uncased_text() {
+        assert_eq!(caps_percent("OK LOL"), None);
+        assert_eq!(caps_percent("これは大文字のないメッセージです"), None);
+        assert_eq!(caps_percent("STOP SHOUTING please"), Some(66));
+        assert_eq!(caps_percent("<:KEKWKEKWKEKW:1> quiet message here"), Some(0));
+    }
+
+    #[test]
+    fn repeats_are_counted_within_the_window() {
+        let mut history = VecDeque::new();
+        let (spam, other) = (content_hash("Buy now!"), content_hash("hello"));
+        assert_eq!(record_message(&mut history, 0, spam), 1);
+        assert_eq!(record_message(&mut history, 5, other), 1);
+        assert_eq!(record_message(&mut history, 10, content_hash("  buy NOW!  ")), 2);
+        assert_eq!(record_message(&mut history, 35, spam), 2);
+        assert_eq!(record_message(&mut history, 100, spam), 1);
+    }
+
+    #[test]
+    fn quiet_authors_are_swept() {
+        let mut recent = RecentMessages::new();
+        record_message(recent.entry((1, 1)).or_default(), 0, content_hash("hello"));
+        record_message(recent.entry((1, 2)).or_default(), 20, content_hash("hello"));
+        sweep(&mut recent, 40);
+        assert_eq!(recent.keys().copied().collect::<Vec<_>>(), vec![(1, 2)]);
+        sweep(&mut recent, 50);
+        assert!(recent.is_empty());
+    }
+}
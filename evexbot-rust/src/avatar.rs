@@ -1,29 +1,35 @@
 use anyhow::Result;
-use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
-use serenity::prelude::*;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
 
-pub async fn handle_avatar(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
-    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
-    let user = command.data.options.get(0).and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::User(u, _member) => Some(u.clone()), _ => None }).unwrap_or(command.user.clone());
+use crate::commands::{Command, Defer, Invocation};
 
-    if let Some(avatar_url) = user.avatar_url() {
-        command.create_followup_message(&ctx.http, |m| {
-            m.embed(|e| {
-                e.title(format!("{}のアイコン", user.name));
-                e.image(&avatar_url);
-                e
-            })
-        }).await?;
-    } else {
-        let default_url = user.default_avatar_url();
-        command.create_followup_message(&ctx.http, |m| {
+pub struct AvatarCommand;
+
+#[async_trait]
+impl Command for AvatarCommand {
+    fn name(&self) -> &'static str { "avatar" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("ユーザーのアイコンを表示します").create_option(|o| o.name("user").description("対象ユーザー").kind(CommandOptionType::User).required(false))
+    }
+
+    fn defer(&self) -> Defer { Defer::UserPreference }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let user = inv.args.user("user").map(|(u, _)| u).unwrap_or(&inv.command.user);
+        let (title, url) = match user.avatar_url() {
+            Some(url) => (format!("{}のアイコン", user.name), url),
+            None => (format!("{}のデフォルトアイコン", user.name), user.default_avatar_url()),
+        };
+        inv.command.create_followup_message(&inv.ctx.http, |m| {
             m.embed(|e| {
-                e.title(format!("{}のデフォルトアイコン", user.name));
-                e.image(&default_url);
+                e.title(title);
+                e.image(&url);
                 e
-            })
+            }).ephemeral(inv.ephemeral)
         }).await?;
+        Ok(())
     }
-
-    Ok(())
 }
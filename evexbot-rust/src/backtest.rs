@@ -0,0 +1,98 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDateTime, Utc};
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+
+use crate::chart::{self, Locale};
+use crate::commands::{Command, Defer, Invocation};
+use crate::growth::{self, Model};
+use crate::member_cache;
+use crate::members_history;
+use crate::stats;
+
+const DEFAULT_DAYS: i64 = 30;
+const MIN_DAYS: i64 = 7;
+const MAX_DAYS: i64 = 180;
+/// Runaway fits would flatten the actual line, so the chart caps predictions at this multiple.
+const CHART_CAP: f64 = 3.0;
+
+/// One model's result over the held-out days.
+struct Score {
+    model: Model,
+    /// Mean absolute error in members per day; `None` when the model couldn't be fitted.
+    mae: Option<f64>,
+    /// Predicted minus actual count on the last day.
+    final_error: f64,
+    predictions: Vec<f64>,
+}
+
+pub struct BacktestCommand;
+
+#[async_trait]
+impl Command for BacktestCommand {
+    fn name(&self) -> &'static str { "growth-backtest" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("直近の参加データを伏せて各予測モデルの精度を比較します")
+            .create_option(|o| o.name("days").description(format!("伏せる日数 (デフォルト{}日)", DEFAULT_DAYS)).kind(CommandOptionType::Integer).min_int_value(MIN_DAYS).max_int_value(MAX_DAYS).required(false))
+            .create_option(|o| chart::size_option(o))
+    }
+
+    fn defer(&self) -> Defer { Defer::UserPreference }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let guild = command.guild_id.ok_or_else(|| anyhow::anyhow!("Guild only command"))?;
+        let gid = guild.0 as i64;
+        if !crate::intents::has(growth::REQUIRED_INTENTS) { return inv.say("このBotはメンバー一覧を取得できない設定のため、成長予測は利用できません。").await; }
+
+        let days = inv.args.int("days").unwrap_or(DEFAULT_DAYS).clamp(MIN_DAYS, MAX_DAYS);
+        let size = match chart::resolve(Some(gid), inv.args.str("size"), chart::WIDE).await {
+            Ok(s) => s,
+            Err(e) => return inv.say(e).await,
+        };
+        let today = Utc::now().date_naive();
+        let cutoff = today - Duration::days(days);
+        let join_dates = member_cache::join_dates(&ctx.http, guild).await?;
+        let train: Vec<NaiveDateTime> = join_dates.iter().copied().filter(|d| d.date() < cutoff).collect();
+        if train.len() < 2 { return inv.say(format!("{}日より前の参加データが不足しているため、検証できません。", days)).await; }
+
+        // The chart shows as much history before the cutoff as it holds out.
+        let (dates, actual) = stats::member_counts(&join_dates, cutoff - Duration::days(days), today);
+        let holdout = dates.iter().position(|d| *d >= cutoff).unwrap_or(0);
+        let (x, y) = growth::join_points(&train);
+        let mut scores = Vec::new();
+        for model in Model::ALL {
+            let fitted = match growth::fit(model, &x, &y) {
+                Ok(Some(f)) => f,
+                _ => { scores.push(Score { model, mae: None, final_error: 0.0, predictions: Vec::new() }); continue; }
+            };
+            let predictions = dates.iter().map(|d| fitted.predict(d.num_days_from_ce() as f64)).collect::<Result<Vec<f64>>>()?;
+            let errors: Vec<f64> = predictions[holdout..].iter().zip(&actual[holdout..]).map(|(p, a)| p - *a as f64).collect();
+            let mae = errors.iter().map(|e| e.abs()).sum::<f64>() / errors.len() as f64;
+            scores.push(Score { model, mae: Some(mae), final_error: errors.last().copied().unwrap_or(0.0), predictions });
+        }
+        scores.sort_by(|a, b| a.mae.unwrap_or(f64::INFINITY).total_cmp(&b.mae.unwrap_or(f64::INFINITY)));
+
+        let cap = actual.iter().copied().max().unwrap_or(0) as f64 * CHART_CAP;
+        let mut series = vec![("実績".to_string(), actual.iter().map(|c| *c as f64).collect::<Vec<f64>>())];
+        series.extend(scores.iter().filter(|s| s.mae.is_some()).map(|s| (s.model.key().to_string(), s.predictions.iter().map(|p| p.clamp(0.0, cap)).collect())));
+        let locale = Locale::for_user(command.user.id.0 as i64, Some(gid)).await;
+        let png = members_history::create_multi_line_chart(&format!("Backtest (last {} days held out)", days), &dates, &series, size, locale)?;
+        let png = chart::finish(Some(gid), png, "growth_backtest").await;
+
+        let lines: Vec<String> = scores.iter().enumerate().map(|(i, s)| match s.mae {
+            Some(mae) => format!("{} **{}** ({}): 平均誤差 {:.1}人, 最終日の誤差 {:+.0}人", if i == 0 { "🏆" } else { "・" }, s.model.key(), s.model.label(), mae, s.final_error),
+            None => format!("・ **{}** ({}): 当てはめられませんでした", s.model.key(), s.model.label()),
+        }).collect();
+        let mut embed = CreateEmbed::default();
+        embed.title("予測モデルのバックテスト");
+        embed.description(format!("{}より前の参加データで各モデルを当てはめ、直近{}日のメンバー数と比べました。\n\n{}", cutoff, days, lines.join("\n")));
+        embed.image(png.url());
+        embed.color(serenity::utils::Colour::BLUE);
+        embed.footer(|f| f.text("誤差の小さいモデルを /growth predict の model に指定してください"));
+        command.create_followup_message(&ctx.http, |m| m.add_file((png.bytes.as_slice(), png.filename().as_str())).embed(|e| { *e = embed; e })).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use serenity::model::channel::{Reaction, ReactionType};
+use serenity::prelude::*;
+
+use crate::db;
+
+pub const BOOKMARK_EMOJI: &str = "🔖";
+
+/// DM the reacting user a copy of the message with a jump link.
+pub async fn handle_reaction_add(ctx: &Context, reaction: &Reaction) -> Result<()> {
+    if !matches!(&reaction.emoji, ReactionType::Unicode(e) if e == BOOKMARK_EMOJI) { return Ok(()); }
+    let user_id = match reaction.user_id { Some(u) => u, None => return Ok(()) };
+    let user = user_id.to_user(&ctx.http).await?;
+    if user.bot { return Ok(()); }
+
+    let message = reaction.message(&ctx.http).await?;
+    let link = message.link();
+    // Authors with privacy mode on only get the link shared, not their content.
+    let private = db::get_privacy_enabled(message.author.id.0 as i64).await?;
+
+    let dm = user.create_dm_channel(&ctx.http).await?;
+    let sent = dm.send_message(&ctx.http, |m| m.embed(|e| {
+        e.title("🔖 ブックマーク");
+        if private {
+            e.description(format!("投稿者の設定により内容は保存されません。\n[メッセージへ移動]({})", link));
+        } else {
+            let content: String = message.content.chars().take(3900).collect();
+            e.description(format!("{}\n\n[メッセージへ移動]({})", content, link));
+            e.author(|a| a.name(&message.author.name).icon_url(message.author.avatar_url().unwrap_or_default()));
+            if let Some(img) = message.attachments.iter().find(|a| a.content_type.as_deref().map(|t| t.starts_with("image/")).unwrap_or(false)) {
+                e.image(&img.url);
+            }
+        }
+        e.timestamp(message.timestamp);
+        e.color(serenity::utils::Colour::BLUE);
+        e.footer(|f| f.text("EvexBot | Bookmark"))
+    })).await;
+    // DMs may be closed; there is nowhere else to report that privately.
+    if let Err(e) = sent { log::debug!("bookmark: could not DM {}: {}", user_id.0, e); }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use once_cell::sync::Lazy;
+use serenity::model::channel::Message;
+use serenity::model::guild::Member;
+use serenity::model::id::GuildId;
+use serenity::model::user::User;
+use tokio::sync::broadcast;
+
+/// Events published while a subscriber is this far behind are dropped for it (and logged).
+const CAPACITY: usize = 256;
+
+/// Something that happened in one module that others may react to.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// A member joined and passed the join gate.
+    MemberJoined(Member),
+    /// The welcome celebration for a member-count milestone was posted as `message`.
+    MilestoneReached { guild_id: i64, member_count: i64, message: Box<Message> },
+    /// A `/config` group was changed by `user`.
+    SettingsChanged { guild_id: GuildId, group: String, subcommand: String, user: User },
+    /// A moderator warned, kicked, banned or timed out a member; `action` is the case key.
+    ModAction { guild_id: GuildId, case_id: i64, action: &'static str, target: User, moderator: User, reason: Option<String>, minutes: Option<i64> },
+}
+
+impl Event {
+    pub fn guild_id(&self) -> GuildId {
+        match self {
+            Event::MemberJoined(member) => member.guild_id,
+            Event::MilestoneReached { guild_id, .. } => GuildId(*guild_id as u64),
+            Event::SettingsChanged { guild_id, .. } | Event::ModAction { guild_id, .. } => *guild_id,
+        }
+    }
+}
+
+static BUS: Lazy<broadcast::Sender<Event>> = Lazy::new(|| broadcast::channel(CAPACITY).0);
+
+/// Send `event` to every subscriber. Publishing with no subscribers is a no-op.
+pub fn publish(event: Event) {
+    let _ = BUS.send(event);
+}
+
+/// A queue of every event published from now on. `modules::init_all` gives each module
+/// its own, so a slow one doesn't hold up the rest.
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    BUS.subscribe()
+}
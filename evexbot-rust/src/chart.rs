@@ -0,0 +1,452 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::{ColorType, ImageEncoder};
+use once_cell::sync::Lazy;
+use plotters::coord::combinators::{BindKeyPoints, WithKeyPoints};
+use plotters::coord::types::RangedCoordusize;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use plotters_bitmap::BitMapBackend;
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::db;
+use crate::permissions;
+
+/// Upper bound on rendered pixels so a single chart can't exhaust memory or the upload limit.
+pub const MAX_PIXELS: u32 = 1920 * 1080;
+const MIN_WIDTH: u32 = 320;
+const MIN_HEIGHT: u32 = 240;
+const MAX_WATERMARK_LENGTH: usize = 40;
+/// The logo's longer side is this fraction of the chart's shorter side.
+const LOGO_FRACTION: u32 = 8;
+const WATERMARK_MARGIN: u32 = 8;
+/// Charts over this many bytes are re-encoded before upload (ATTACHMENT_SIZE_BUDGET).
+/// Discord rejects bot uploads over 10 MiB, so the default leaves room for the message.
+const DEFAULT_SIZE_BUDGET: usize = 8 * 1024 * 1024;
+/// Each downscale pass shrinks both sides to this share.
+const DOWNSCALE_STEP: f64 = 0.75;
+
+/// Decoded logos by URL; guild icons change rarely and every chart would otherwise refetch them.
+static LOGOS: Lazy<tokio::sync::Mutex<HashMap<String, Arc<image::RgbaImage>>>> = Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
+/// The font every chart draws "sans-serif" text with. It covers the Japanese labels, and
+/// charts look the same whatever fonts the host has installed.
+const CHART_FONT: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/fonts/NotoSansJP-Regular.ttf"));
+static FONTS: Lazy<bool> = Lazy::new(|| plotters::style::register_font("sans-serif", FontStyle::Normal, CHART_FONT).is_ok());
+
+/// Register the bundled chart font; charts can't draw text before this. Returns false when
+/// the font failed to load.
+pub fn register_fonts() -> bool {
+    *FONTS
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChartSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ChartSize {
+    pub const fn new(width: u32, height: u32) -> Self {
+        ChartSize { width, height }
+    }
+
+    /// Accepts a preset name or `WIDTHxHEIGHT`.
+    pub fn parse(s: &str) -> std::result::Result<Self, String> {
+        let s = s.trim().to_lowercase();
+        if let Some((_, size)) = PRESETS.iter().find(|(name, _)| *name == s) {
+            return Ok(*size);
+        }
+        let (w, h) = s.split_once('x').ok_or_else(|| format!("サイズは {} または 幅x高さ (例: 1000x600) で指定してください。", preset_names()))?;
+        let size = ChartSize::new(w.trim().parse().map_err(|_| "幅が数値ではありません。".to_string())?, h.trim().parse().map_err(|_| "高さが数値ではありません。".to_string())?);
+        size.validate()?;
+        Ok(size)
+    }
+
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.width < MIN_WIDTH || self.height < MIN_HEIGHT {
+            return Err(format!("サイズは{}x{}以上にしてください。", MIN_WIDTH, MIN_HEIGHT));
+        }
+        if self.width.saturating_mul(self.height) > MAX_PIXELS {
+            return Err(format!("サイズが大きすぎます (最大{}ピクセル)。", MAX_PIXELS));
+        }
+        Ok(())
+    }
+}
+
+pub const WIDE: ChartSize = ChartSize::new(1200, 400);
+pub const STANDARD: ChartSize = ChartSize::new(800, 450);
+/// Mobile-friendly: fills the width of a phone screen without shrinking the text.
+pub const SQUARE: ChartSize = ChartSize::new(800, 800);
+pub const HD: ChartSize = ChartSize::new(1920, 1080);
+
+pub const PRESETS: &[(&str, ChartSize)] = &[("wide", WIDE), ("standard", STANDARD), ("square", SQUARE), ("hd", HD)];
+
+fn preset_names() -> String {
+    PRESETS.iter().map(|(n, _)| *n).collect::<Vec<_>>().join("/")
+}
+
+/// Add the shared `size` option to a chart command or subcommand.
+pub fn size_option(o: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    o.name("size").description(format!("グラフのサイズ ({} または 幅x高さ)", preset_names())).kind(CommandOptionType::String).required(false)
+}
+
+/// Size for a chart: the command's `size` option, then the guild default, then `fallback`.
+pub async fn resolve(guild_id: Option<i64>, option: Option<&str>, fallback: ChartSize) -> std::result::Result<ChartSize, String> {
+    if let Some(s) = option {
+        return ChartSize::parse(s);
+    }
+    if let Some(g) = guild_id {
+        if let Ok(Some(s)) = db::get_chart_size(g).await {
+            if let Ok(size) = ChartSize::parse(&s) { return Ok(size); }
+        }
+    }
+    Ok(fallback)
+}
+
+/// Language used for chart labels, from the guild's `/config language` setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    Ja,
+    En,
+}
+
+impl Locale {
+    pub fn from_code(code: &str) -> Self {
+        match code { "en" => Locale::En, _ => Locale::Ja }
+    }
+
+    pub async fn for_guild(guild_id: Option<i64>) -> Self {
+        match guild_id {
+            Some(g) => db::get_guild_config(g).await.map(|c| Locale::from_code(&c.language)).unwrap_or(Locale::Ja),
+            None => Locale::Ja,
+        }
+    }
+
+    /// The user's `/preferences` language if set, otherwise the guild's.
+    pub async fn for_user(user_id: i64, guild_id: Option<i64>) -> Self {
+        match db::get_user_prefs(user_id).await.ok().and_then(|p| p.language) {
+            Some(code) => Locale::from_code(&code),
+            None => Locale::for_guild(guild_id).await,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TickUnit {
+    Day,
+    Month,
+    Quarter,
+    Year,
+}
+
+/// Aim for roughly this many labels regardless of the range.
+const TARGET_TICKS: usize = 8;
+
+fn tick_unit(days: usize) -> TickUnit {
+    match days {
+        0..=62 => TickUnit::Day,
+        63..=548 => TickUnit::Month,
+        549..=1460 => TickUnit::Quarter,
+        _ => TickUnit::Year,
+    }
+}
+
+fn is_boundary(d: NaiveDate, unit: TickUnit) -> bool {
+    match unit {
+        TickUnit::Day => true,
+        TickUnit::Month => d.day() == 1,
+        TickUnit::Quarter => d.day() == 1 && (d.month() - 1).is_multiple_of(3),
+        TickUnit::Year => d.day() == 1 && d.month() == 1,
+    }
+}
+
+/// Indices into `dates` to label: calendar boundaries (month/quarter/year starts) for long
+/// ranges, thinned so the axis stays readable.
+pub fn date_ticks(dates: &[NaiveDate]) -> Vec<usize> {
+    if dates.is_empty() { return Vec::new(); }
+    let unit = tick_unit(dates.len());
+    let mut ticks: Vec<usize> = dates.iter().enumerate().filter(|(_, d)| is_boundary(**d, unit)).map(|(i, _)| i).collect();
+    if ticks.len() > TARGET_TICKS {
+        let step = ticks.len().div_ceil(TARGET_TICKS);
+        ticks = ticks.into_iter().step_by(step).collect();
+    }
+    if ticks.is_empty() { ticks.push(0); }
+    ticks
+}
+
+/// Format a tick label at the granularity implied by the chart's range.
+pub fn format_tick(date: NaiveDate, range_days: usize, locale: Locale) -> String {
+    match (tick_unit(range_days), locale) {
+        (TickUnit::Day, Locale::Ja) => format!("{}/{}", date.month(), date.day()),
+        (TickUnit::Day, Locale::En) => date.format("%b %-d").to_string(),
+        (TickUnit::Month, Locale::Ja) | (TickUnit::Quarter, Locale::Ja) => format!("{}年{}月", date.year(), date.month()),
+        (TickUnit::Month, Locale::En) | (TickUnit::Quarter, Locale::En) => date.format("%b %Y").to_string(),
+        (TickUnit::Year, Locale::Ja) => format!("{}年", date.year()),
+        (TickUnit::Year, Locale::En) => date.year().to_string(),
+    }
+}
+
+/// X axis ranging over indices into `dates`, with key points at `date_ticks`.
+pub fn date_axis(dates: &[NaiveDate]) -> WithKeyPoints<RangedCoordusize> {
+    (0..dates.len()).with_key_points(date_ticks(dates))
+}
+
+/// Label formatter for `date_axis`.
+pub fn date_labels(dates: &[NaiveDate], locale: Locale) -> impl Fn(&usize) -> String + '_ {
+    move |i| dates.get(*i).map(|d| format_tick(*d, dates.len(), locale)).unwrap_or_default()
+}
+
+/// Encode an RGB buffer drawn by a BitMapBackend as PNG.
+pub fn encode_png(size: ChartSize, buf: Vec<u8>) -> Result<Vec<u8>> {
+    if buf.len() != (size.width * size.height * 3) as usize { anyhow::bail!("Failed to create image"); }
+    encode_rgb_png(size.width, size.height, &buf, CompressionType::Default)
+}
+
+/// Rows are filtered and compressed straight from `rgb`, without copying it into an image first.
+fn encode_rgb_png(width: u32, height: u32, rgb: &[u8], compression: CompressionType) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    PngEncoder::new_with_quality(&mut out, compression, PngFilter::Adaptive).write_image(rgb, width, height, ColorType::Rgb8)?;
+    Ok(out)
+}
+
+/// Lossless, so text and thin lines stay as sharp as in the PNG.
+fn encode_rgb_webp(width: u32, height: u32, rgb: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    WebPEncoder::new_lossless(&mut out).write_image(rgb, width, height, ColorType::Rgb8)?;
+    Ok(out)
+}
+
+fn size_budget() -> usize {
+    std::env::var("ATTACHMENT_SIZE_BUDGET").ok().and_then(|v| v.parse().ok()).filter(|n| *n > 0).unwrap_or(DEFAULT_SIZE_BUDGET)
+}
+
+/// A chart ready to attach, in whichever format fit the size budget.
+pub struct Rendered {
+    pub bytes: Vec<u8>,
+    stem: &'static str,
+    extension: &'static str,
+}
+
+impl Rendered {
+    pub fn filename(&self) -> String {
+        format!("{}.{}", self.stem, self.extension)
+    }
+
+    /// For `embed.image`, so the embed shows this attachment.
+    pub fn url(&self) -> String {
+        format!("attachment://{}", self.filename())
+    }
+}
+
+/// Shrink `png` until it is at most `budget` bytes: first re-encode it at full size with the
+/// best PNG compression, then as lossless WebP, then both again at smaller sizes. The
+/// smallest result is returned if even the minimum size doesn't fit.
+fn fit_budget(png: Vec<u8>, budget: usize) -> Result<(Vec<u8>, &'static str)> {
+    if png.len() <= budget { return Ok((png, "png")); }
+    let mut image = image::load_from_memory(&png)?.to_rgb8();
+    loop {
+        let (width, height) = image.dimensions();
+        let png = encode_rgb_png(width, height, image.as_raw(), CompressionType::Best)?;
+        if png.len() <= budget { return Ok((png, "png")); }
+        let webp = encode_rgb_webp(width, height, image.as_raw())?;
+        if webp.len() <= budget { return Ok((webp, "webp")); }
+
+        let next = ((width as f64 * DOWNSCALE_STEP) as u32, (height as f64 * DOWNSCALE_STEP) as u32);
+        if next.0 < MIN_WIDTH || next.1 < MIN_HEIGHT {
+            log::warn!("chart: {}x{} is still over the {} byte budget", width, height, budget);
+            return Ok(if webp.len() < png.len() { (webp, "webp") } else { (png, "png") });
+        }
+        image = image::imageops::resize(&image, next.0, next.1, image::imageops::FilterType::Triangle);
+    }
+}
+
+/// Watermark a rendered chart and fit it into the attachment size budget. It is named
+/// `<stem>.png`, or `<stem>.webp` when only WebP was small enough.
+pub async fn finish(guild_id: Option<i64>, png: Vec<u8>, stem: &'static str) -> Rendered {
+    let png = watermark(guild_id, png).await;
+    let budget = size_budget();
+    if png.len() <= budget { return Rendered { bytes: png, stem, extension: "png" }; }
+    let original = png.clone();
+    match tokio::task::spawn_blocking(move || fit_budget(png, budget)).await.map_err(anyhow::Error::from).and_then(|r| r) {
+        Ok((bytes, extension)) => {
+            log::info!("chart: re-encoded {} from {} to {} bytes as {}", stem, original.len(), bytes.len(), extension);
+            Rendered { bytes, stem, extension }
+        }
+        Err(e) => {
+            log::warn!("chart: failed to fit {} into the size budget: {}", stem, e);
+            Rendered { bytes: original, stem, extension: "png" }
+        }
+    }
+}
+
+async fn load_logo(url: &str) -> Option<Arc<image::RgbaImage>> {
+    if let Some(logo) = LOGOS.lock().await.get(url) { return Some(logo.clone()); }
+    let bytes = reqwest::get(url).await.ok()?.error_for_status().ok()?.bytes().await.ok()?;
+    let logo = Arc::new(image::load_from_memory(&bytes).ok()?.to_rgba8());
+    LOGOS.lock().await.insert(url.to_string(), logo.clone());
+    Some(logo)
+}
+
+/// Stamp the logo (bottom-right) and text (left of it) onto an encoded chart.
+fn apply_watermark(png: &[u8], text: Option<&str>, logo: Option<&image::RgbaImage>) -> Result<Vec<u8>> {
+    let mut base = image::load_from_memory(png)?.to_rgba8();
+    let (width, height) = base.dimensions();
+    let mut right = width.saturating_sub(WATERMARK_MARGIN);
+    if let Some(logo) = logo {
+        let side = width.min(height) / LOGO_FRACTION;
+        let scaled = image::imageops::resize(logo, side, side, image::imageops::FilterType::Triangle);
+        let x = right.saturating_sub(side);
+        image::imageops::overlay(&mut base, &scaled, x as i64, height.saturating_sub(WATERMARK_MARGIN + side) as i64);
+        right = x.saturating_sub(WATERMARK_MARGIN);
+    }
+    let mut rgb = image::DynamicImage::ImageRgba8(base).to_rgb8().into_raw();
+    if let Some(text) = text {
+        let area = BitMapBackend::with_buffer(&mut rgb, (width, height)).into_drawing_area();
+        let style = ("sans-serif", (height / 30).max(12)).into_font().color(&RGBColor(96, 96, 96).mix(0.7)).pos(Pos::new(HPos::Right, VPos::Bottom));
+        area.draw_text(text, &style, (right as i32, height.saturating_sub(WATERMARK_MARGIN) as i32))?;
+        area.present()?;
+    }
+    encode_png(ChartSize::new(width, height), rgb)
+}
+
+/// Apply the guild's `/config chart watermark` to a rendered chart or welcome card.
+/// The image is returned unchanged when no watermark is set or it can't be drawn.
+async fn watermark(guild_id: Option<i64>, png: Vec<u8>) -> Vec<u8> {
+    let (text, logo_url) = match guild_id {
+        Some(g) => db::get_chart_watermark(g).await.unwrap_or((None, None)),
+        None => return png,
+    };
+    if text.is_none() && logo_url.is_none() { return png; }
+    let logo = match logo_url { Some(url) => load_logo(&url).await, None => None };
+    match apply_watermark(&png, text.as_deref(), logo.as_deref()) {
+        Ok(out) => out,
+        Err(e) => {
+            log::warn!("chart: failed to apply watermark for guild {:?}: {}", guild_id, e);
+            png
+        }
+    }
+}
+
+pub fn build_language_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("language").description("サーバーの表示言語").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| {
+            s.name("set").description("グラフなどの表示言語を設定します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("value").description("言語").kind(CommandOptionType::String).required(true).add_string_choice("日本語", "ja").add_string_choice("English", "en"))
+        })
+}
+
+pub async fn handle_language_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let value = sub.options.iter().find(|o| o.name=="value").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("ja");
+
+    let msg = if !permissions::is_admin(member).await {
+        permissions::DENIED_MESSAGE.to_string()
+    } else if !["ja", "en"].contains(&value) {
+        "jaまたはenを指定してください。".to_string()
+    } else {
+        db::update_guild_language(guild_id, value).await?;
+        format!("表示言語を{}に設定しました。", if value == "en" { "English" } else { "日本語" })
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("chart").description("グラフの表示設定").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| {
+            s.name("size").description("このサーバーでのグラフの既定サイズ").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("value").description(format!("{} または 幅x高さ (reset で既定に戻す)", preset_names())).kind(CommandOptionType::String).required(true))
+        })
+        .create_sub_option(|s| {
+            s.name("watermark").description("グラフと参加カードに入れる透かし (両方省略で解除)").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("text").description(format!("透かしの文字 ({}文字まで)", MAX_WATERMARK_LENGTH)).kind(CommandOptionType::String).required(false))
+                .create_sub_option(|o| o.name("logo").description("サーバーアイコンを右下に入れる").kind(CommandOptionType::Boolean).required(false))
+        })
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let value = sub.options.iter().find(|o| o.name=="value").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").trim().to_lowercase();
+
+    let msg = if !permissions::is_admin(member).await {
+        permissions::DENIED_MESSAGE.to_string()
+    } else if sub.name == "watermark" {
+        let text = sub.options.iter().find(|o| o.name=="text").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).map(str::trim).filter(|t| !t.is_empty());
+        let logo = sub.options.iter().find(|o| o.name=="logo").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+        let logo_url = if logo { ctx.http.get_guild(guild_id as u64).await?.icon_url() } else { None };
+        if text.map(|t| t.chars().count() > MAX_WATERMARK_LENGTH).unwrap_or(false) {
+            format!("透かしの文字は{}文字以内にしてください。", MAX_WATERMARK_LENGTH)
+        } else if logo && logo_url.is_none() {
+            "このサーバーにはアイコンが設定されていません。".to_string()
+        } else {
+            db::update_chart_watermark(guild_id, text, logo_url.as_deref()).await?;
+            match (text, logo) {
+                (None, false) => "透かしを解除しました。".to_string(),
+                (Some(t), false) => format!("グラフに「{}」の透かしを入れます。", t),
+                (None, true) => "グラフにサーバーアイコンの透かしを入れます。".to_string(),
+                (Some(t), true) => format!("グラフに「{}」とサーバーアイコンの透かしを入れます。", t),
+            }
+        }
+    } else if value == "reset" {
+        db::update_chart_size(guild_id, None).await?;
+        "グラフのサイズを既定に戻しました。".to_string()
+    } else {
+        match ChartSize::parse(&value) {
+            Ok(size) => {
+                db::update_chart_size(guild_id, Some(&value)).await?;
+                format!("グラフの既定サイズを{}x{}に設定しました。", size.width, size.height)
+            }
+            Err(e) => e,
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noisy_png(width: u32, height: u32) -> Vec<u8> {
+        // Pseudo-random pixels barely compress, so the budget is easy to exceed.
+        let mut seed = 1u32;
+        let buf: Vec<u8> = (0..width * height * 3).map(|_| { seed = seed.wrapping_mul(1103515245).wrapping_add(12345); (seed >> 16) as u8 }).collect();
+        encode_png(ChartSize::new(width, height), buf).unwrap()
+    }
+
+    #[test]
+    fn charts_within_the_budget_are_untouched() {
+        let png = noisy_png(64, 64);
+        let (out, extension) = fit_budget(png.clone(), png.len()).unwrap();
+        assert_eq!((out, extension), (png, "png"));
+    }
+
+    #[test]
+    fn oversized_charts_are_downscaled_until_they_fit() {
+        let png = noisy_png(800, 600);
+        let budget = png.len() / 3;
+        let (out, _) = fit_budget(png, budget).unwrap();
+        assert!(out.len() <= budget, "{} > {}", out.len(), budget);
+        let (width, height) = image::load_from_memory(&out).unwrap().to_rgb8().dimensions();
+        assert!((MIN_WIDTH..800).contains(&width) && height >= MIN_HEIGHT, "{}x{}", width, height);
+    }
+
+    #[test]
+    fn the_smallest_attempt_is_kept_when_nothing_fits() {
+        let (out, _) = fit_budget(noisy_png(400, 300), 1).unwrap();
+        let (width, height) = image::load_from_memory(&out).unwrap().to_rgb8().dimensions();
+        assert_eq!((width, height), (400, 300));
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::Channel;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+
+use crate::db;
+use crate::permissions;
+
+/// `/config` itself can't be restricted, so admins can always undo a rule.
+const UNRESTRICTABLE: &[&str] = &["config"];
+
+pub const KIND_ROLE: &str = "role";
+pub const KIND_CHANNEL: &str = "channel";
+
+/// Adds the `command-permissions` subcommand group to `/config`.
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("command-permissions").description("コマンドを使えるロール・チャンネルの制限").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| {
+            s.name("add").description("許可するロールまたはチャンネルを追加します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("command").description("コマンド名 (例: imagegen)").kind(CommandOptionType::String).required(true))
+                .create_sub_option(|o| o.name("role").description("このロールを持つメンバーに限定").kind(CommandOptionType::Role).required(false))
+                .create_sub_option(|o| o.name("channel").description("このチャンネルでの使用に限定").kind(CommandOptionType::Channel).required(false))
+        })
+        .create_sub_option(|s| {
+            s.name("remove").description("許可したロールまたはチャンネルを外します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("command").description("コマンド名").kind(CommandOptionType::String).required(true))
+                .create_sub_option(|o| o.name("role").description("外すロール").kind(CommandOptionType::Role).required(false))
+                .create_sub_option(|o| o.name("channel").description("外すチャンネル").kind(CommandOptionType::Channel).required(false))
+        })
+        .create_sub_option(|s| {
+            s.name("reset").description("コマンドの制限をすべて解除します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("command").description("コマンド名").kind(CommandOptionType::String).required(true))
+        })
+        .create_sub_option(|s| s.name("list").description("設定済みの制限を表示します").kind(CommandOptionType::SubCommand))
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    let msg = if !permissions::is_admin(member).await {
+        permissions::DENIED_MESSAGE.to_string()
+    } else if sub.name == "list" {
+        let rules = db::get_command_permissions(guild_id).await?;
+        if rules.is_empty() { "制限されているコマンドはありません。".to_string() } else {
+            let mut by_command: Vec<(String, Vec<String>)> = Vec::new();
+            for rule in rules {
+                let label = if rule.kind == KIND_ROLE { format!("<@&{}>", rule.target_id) } else { format!("<#{}>", rule.target_id) };
+                match by_command.iter_mut().find(|(n, _)| *n == rule.command) {
+                    Some((_, v)) => v.push(label),
+                    None => by_command.push((rule.command, vec![label])),
+                }
+            }
+            by_command.iter().map(|(n, v)| format!("`/{}`: {}", n, v.join(" "))).collect::<Vec<_>>().join("\n")
+        }
+    } else {
+        let name = sub.options.iter().find(|o| o.name=="command").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").trim().trim_start_matches('/').to_lowercase();
+        let role = sub.options.iter().find(|o| o.name=="role").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Role(r) => Some(r.id.0 as i64), _ => None });
+        let channel = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Channel(c) => Some(c.id.0 as i64), _ => None });
+        let known = crate::commands::is_registered(&ctx.http, GuildId(guild_id as u64), &name).await.unwrap_or(true);
+
+        if UNRESTRICTABLE.contains(&name.as_str()) {
+            format!("`/{}` は制限できません。", name)
+        } else if !known {
+            format!("`/{}` というコマンドはありません。", name)
+        } else {
+            match sub.name.as_str() {
+                "add" | "remove" if role.is_none() && channel.is_none() => "ロールかチャンネルを指定してください。".to_string(),
+                "add" => {
+                    if let Some(r) = role { db::add_command_permission(guild_id, &name, KIND_ROLE, r).await?; }
+                    if let Some(c) = channel { db::add_command_permission(guild_id, &name, KIND_CHANNEL, c).await?; }
+                    format!("`/{}` の制限を更新しました。管理者は常に使用できます。", name)
+                }
+                "remove" => {
+                    if let Some(r) = role { db::remove_command_permission(guild_id, &name, KIND_ROLE, r).await?; }
+                    if let Some(c) = channel { db::remove_command_permission(guild_id, &name, KIND_CHANNEL, c).await?; }
+                    format!("`/{}` の制限を更新しました。", name)
+                }
+                "reset" => {
+                    db::clear_command_permissions(guild_id, &name).await?;
+                    format!("`/{}` の制限を解除しました。", name)
+                }
+                _ => return Ok(()),
+            }
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+/// Why `command` may not run here, if a guild rule forbids it. With role rules the member
+/// needs one of the roles; with channel rules it must be used in one of the channels (or a
+/// thread inside one). Admins are never restricted.
+async fn denial(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+    let guild_id = match command.guild_id { Some(g) => g.0 as i64, None => return Ok(None) };
+    let member = match command.member.as_ref() { Some(m) => m, None => return Ok(None) };
+    if permissions::is_admin(member).await { return Ok(None); }
+    let rules = db::get_command_rules(guild_id, &command.data.name).await?;
+    if rules.is_empty() { return Ok(None); }
+
+    let roles: Vec<i64> = rules.iter().filter(|r| r.kind == KIND_ROLE).map(|r| r.target_id).collect();
+    let channels: Vec<i64> = rules.iter().filter(|r| r.kind == KIND_CHANNEL).map(|r| r.target_id).collect();
+    if !roles.is_empty() && !member.roles.iter().any(|r| roles.contains(&(r.0 as i64))) {
+        let list = roles.iter().map(|r| format!("<@&{}>", r)).collect::<Vec<_>>().join(" ");
+        return Ok(Some(format!("このコマンドは {} のメンバーのみ使用できます。", list)));
+    }
+    if !channels.is_empty() && !channels.contains(&(command.channel_id.0 as i64)) {
+        let parent = match command.channel_id.to_channel(ctx).await {
+            Ok(Channel::Guild(c)) => c.parent_id.map(|p| p.0 as i64),
+            _ => None,
+        };
+        if !parent.map(|p| channels.contains(&p)).unwrap_or(false) {
+            let list = channels.iter().map(|c| format!("<#{}>", c)).collect::<Vec<_>>().join(" ");
+            return Ok(Some(format!("このコマンドは {} でのみ使用できます。", list)));
+        }
+    }
+    Ok(None)
+}
+
+/// Enforce guild rules before dispatch. Returns true when the command was refused.
+pub async fn intercept(ctx: &Context, command: &ApplicationCommandInteraction) -> bool {
+    let reason = match denial(ctx, command).await {
+        Ok(Some(r)) => r,
+        Ok(None) => return false,
+        Err(e) => {
+            // Fail open: a DB hiccup shouldn't take every command down.
+            log::warn!("command_access: failed to check rules for /{}: {}", command.data.name, e);
+            return false;
+        }
+    };
+    let _ = command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(reason).ephemeral(true))).await;
+    true
+}
@@ -0,0 +1,318 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::Command as ApiCommand;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::{Attachment, PartialChannel};
+use serenity::model::guild::{PartialMember, Role};
+use serenity::model::id::GuildId;
+use serenity::model::user::User;
+use serenity::prelude::*;
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::{avatar, growth, imagegen, members_history, sandbox};
+
+/// How the framework acknowledges a command before `run` is called.
+pub enum Defer {
+    /// The command answers by itself (modals, subcommands that reply differently);
+    /// `Invocation::say` and error reports are only shown to the invoker.
+    No,
+    /// Visible to everyone, e.g. slow external calls.
+    Public,
+    /// Always only visible to the invoker.
+    Ephemeral,
+    /// Public unless the invoker turned on `/preferences set ephemeral`.
+    UserPreference,
+}
+
+/// A slash command. Implement this and add the struct to `REGISTRY`; the framework
+/// registers the definition, defers, parses options and reports errors.
+#[async_trait]
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Description and options. The name is set by the framework.
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand;
+    fn defer(&self) -> Defer { Defer::No }
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()>;
+}
+
+/// Typed lookups over a command's (or a subcommand's) options.
+#[derive(Clone, Copy)]
+pub struct Args<'a> {
+    options: &'a [CommandDataOption],
+}
+
+impl<'a> Args<'a> {
+    pub fn new(options: &'a [CommandDataOption]) -> Self { Args { options } }
+
+    fn get(&self, name: &str) -> Option<&'a CommandDataOption> {
+        self.options.iter().find(|o| o.name == name)
+    }
+
+    pub fn str(&self, name: &str) -> Option<&'a str> { self.get(name)?.value.as_ref()?.as_str() }
+
+    pub fn int(&self, name: &str) -> Option<i64> { self.get(name)?.value.as_ref()?.as_i64() }
+
+    pub fn bool(&self, name: &str) -> Option<bool> { self.get(name)?.value.as_ref()?.as_bool() }
+
+    pub fn user(&self, name: &str) -> Option<(&'a User, Option<&'a PartialMember>)> {
+        match self.get(name)?.resolved.as_ref()? { CommandDataOptionValue::User(u, m) => Some((u, m.as_ref())), _ => None }
+    }
+
+    pub fn role(&self, name: &str) -> Option<&'a Role> {
+        match self.get(name)?.resolved.as_ref()? { CommandDataOptionValue::Role(r) => Some(r), _ => None }
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&'a PartialChannel> {
+        match self.get(name)?.resolved.as_ref()? { CommandDataOptionValue::Channel(c) => Some(c), _ => None }
+    }
+
+    pub fn attachment(&self, name: &str) -> Option<&'a Attachment> {
+        match self.get(name)?.resolved.as_ref()? { CommandDataOptionValue::Attachment(a) => Some(a), _ => None }
+    }
+}
+
+/// One command invocation, already acknowledged according to the command's `Defer`.
+pub struct Invocation<'a> {
+    pub ctx: &'a Context,
+    pub command: &'a ApplicationCommandInteraction,
+    pub args: Args<'a>,
+    /// Whether replies should only be visible to the invoker.
+    pub ephemeral: bool,
+    deferred: bool,
+}
+
+impl Invocation<'_> {
+    /// Send a plain text reply, as a followup once deferred.
+    pub async fn say(&self, content: impl ToString) -> Result<()> {
+        reply(&self.ctx.http, self.command, self.deferred, self.ephemeral, content.to_string()).await
+    }
+}
+
+async fn reply(http: &Http, command: &ApplicationCommandInteraction, deferred: bool, ephemeral: bool, content: String) -> Result<()> {
+    if deferred {
+        command.create_followup_message(http, |m| m.content(content).ephemeral(ephemeral)).await?;
+    } else {
+        command.create_interaction_response(http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(content).ephemeral(ephemeral))).await?;
+    }
+    Ok(())
+}
+
+struct Registry {
+    commands: HashMap<&'static str, Box<dyn Command>>,
+}
+
+impl Registry {
+    fn new() -> Self { Registry { commands: HashMap::new() } }
+
+    fn command(mut self, command: impl Command + 'static) -> Self {
+        self.commands.insert(command.name(), Box::new(command));
+        self
+    }
+}
+
+/// Every command the bot answers. New commands go here and nowhere else.
+static REGISTRY: Lazy<Registry> = Lazy::new(|| {
+    Registry::new()
+        .command(growth::GrowthCommand)
+        .command(members_history::MembersHistoryCommand)
+        .command(imagegen::ImagegenCommand)
+        .command(avatar::AvatarCommand)
+        .command(sandbox::SandboxCommand)
+        .command(SyncCommandsCommand)
+        .command(crate::onboarding::OnboardingCommand)
+        .command(crate::rules::RulesCommand)
+        .command(crate::crosspost::CrosspostCommand)
+        .command(crate::shortlink::ShortlinkCommand)
+        .command(crate::highlights::HighlightsCommand)
+        .command(crate::spotlight::SpotlightCommand)
+        .command(crate::zikosyokai::IntroChannelCommand)
+        .command(crate::snapshots::ImportInsightsCommand)
+        .command(crate::backtest::BacktestCommand)
+        .command(crate::prune::PruneCommand)
+        .command(crate::moderation::WarnCommand)
+        .command(crate::moderation::KickCommand)
+        .command(crate::moderation::BanCommand)
+        .command(crate::moderation::TimeoutCommand)
+        .command(crate::moderation::CaseCommand)
+        .command(crate::role_decay::RoleDecayCommand)
+        .command(crate::contributors::ContributorCommand)
+        .command(crate::logging::LogSettingsCommand)
+        .command(crate::helpdesk::HelpStatsCommand)
+        .command(crate::reactionroles::ReactionRoleCommand)
+        .command(crate::kb::KbCommand)
+        .command(crate::poll::PollCommand)
+        .command(crate::reminders::RemindCommand)
+        .command(crate::reminders::ScheduleCommand)
+        .command(crate::prompts::PromptCommand)
+        .command(crate::leveling::RankCommand)
+        .command(crate::leveling::LeaderboardCommand)
+        .command(crate::modules::ModuleCommand)
+        .command(crate::linkfilter::LinkFilterCommand)
+        .command(crate::diagnose::DiagnoseCommand)
+        .command(crate::wordfilter::FilterCommand)
+        .command(crate::errorcodes::ErrorLookupCommand)
+        .command(crate::welcome::WelcomeCommand)
+        .command(crate::welcome::LeaveMessageCommand)
+        .command(crate::welcome::MilestoneTestCommand)
+        .command(crate::modlog::ModlogCommand)
+        .command(crate::joingate::JoinGateCommand)
+        .command(crate::roles::RoleCommand)
+        .command(crate::audit::AuditCommand)
+        .command(crate::emojilog::EmojiStatsCommand)
+        .command(crate::tempvoice::TempVoiceCommand)
+        .command(crate::digest::DigestCommand)
+        .command(crate::verify::VerifyCommand)
+        .command(crate::settings::ConfigCommand)
+        .command(crate::automod::AutomodCommand)
+        .command(crate::owner::DbQueryCommand)
+        .command(crate::events::EventCommand)
+        .command(crate::topic::TopicCommand)
+        .command(crate::privacy::PrivacyCommand)
+        .command(crate::zikosyokai::IntroCommand)
+        .command(crate::zikosyokai::IntroTemplateCommand)
+        .command(crate::compare::CompareGuildsCommand)
+        .command(crate::snapshots::BackfillSnapshotsCommand)
+        .command(crate::invites::InviteCommand)
+        .command(crate::setup::SetupCommand)
+        .command(crate::maintenance::MaintenanceCommand)
+        .command(crate::preferences::PreferencesCommand)
+        .command(crate::milestones::MilestonesCommand)
+        .command(crate::metrics::PingCommand)
+        .command(crate::status::StatusCommand)
+        .command(crate::triage::TriageCommand)
+        .command(crate::ocr::OcrCommand)
+});
+
+#[cfg(test)]
+pub(crate) fn is_in_registry(name: &str) -> bool {
+    REGISTRY.commands.contains_key(name)
+}
+
+/// Where command definitions are created. Global commands can take a while to show up in
+/// clients, so development servers use `guild` mode for instant updates.
+enum Scope {
+    Global,
+    Guilds(Vec<GuildId>),
+}
+
+/// COMMAND_REGISTRATION=guild with COMMAND_GUILD_IDS (comma-separated) selects guild mode;
+/// `command_registration` / `command_guilds` in config.yml are used when the env is unset.
+static SCOPE: Lazy<Scope> = Lazy::new(|| {
+    let config = Config::load();
+    let mode = std::env::var("COMMAND_REGISTRATION").ok().filter(|s| !s.is_empty())
+        .or_else(|| config.as_ref().and_then(|c| c.command_registration.clone()))
+        .unwrap_or_default();
+    if mode.to_lowercase() != "guild" { return Scope::Global; }
+    let guilds: Vec<GuildId> = match std::env::var("COMMAND_GUILD_IDS") {
+        Ok(ids) if !ids.trim().is_empty() => ids.split(',').filter_map(|s| s.trim().parse().ok()).map(GuildId).collect(),
+        _ => config.map(|c| c.command_guilds.into_iter().map(GuildId).collect()).unwrap_or_default(),
+    };
+    if guilds.is_empty() {
+        log::warn!("commands: guild registration requested without guild ids; registering globally");
+        return Scope::Global;
+    }
+    Scope::Guilds(guilds)
+});
+
+/// Create a command definition in the configured scope. Use this instead of
+/// `Command::create_global_application_command` so guild mode covers every command.
+pub async fn create<F>(http: &Http, f: F) -> Result<()>
+where
+    F: Fn(&mut CreateApplicationCommand) -> &mut CreateApplicationCommand,
+{
+    match &*SCOPE {
+        Scope::Global => { ApiCommand::create_global_application_command(http, |c| f(c)).await?; }
+        Scope::Guilds(guilds) => {
+            for guild in guilds {
+                guild.create_application_command(http, |c| f(c)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a command called `name` is registered where `guild_id` can see it.
+pub async fn is_registered(http: &Http, guild_id: GuildId, name: &str) -> Result<bool> {
+    let commands = match &*SCOPE {
+        Scope::Global => ApiCommand::get_global_application_commands(http).await?,
+        Scope::Guilds(_) => guild_id.get_application_commands(http).await?,
+    };
+    Ok(commands.iter().any(|c| c.name == name))
+}
+
+/// Create the definition of every command in the registry.
+pub async fn register_all(http: &Http) {
+    for cmd in REGISTRY.commands.values() {
+        if let Err(e) = create(http, |c| cmd.define(c.name(cmd.name()))).await {
+            log::warn!("commands: failed to register /{}: {}", cmd.name(), e);
+        }
+    }
+}
+
+/// Re-create every definition without restarting, e.g. after editing options.
+struct SyncCommandsCommand;
+
+#[async_trait]
+impl Command for SyncCommandsCommand {
+    fn name(&self) -> &'static str { "sync-commands" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("スラッシュコマンドを再登録します")
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        // Definitions are shared by every guild, so only bot owners may push them.
+        if !crate::owner::is_owner(inv.command.user.id) { return inv.say("権限がありません。").await; }
+        register_all(&inv.ctx.http).await;
+        let scope = match &*SCOPE {
+            Scope::Global => "グローバル (反映まで時間がかかる場合があります)".to_string(),
+            Scope::Guilds(g) => format!("{}個のサーバー", g.len()),
+        };
+        log::info!("commands: re-registered by {}", inv.command.user.id.0);
+        inv.say(format!("コマンドを再登録しました: {}", scope)).await
+    }
+}
+
+/// Run the command named in `command`. Errors are recorded under an error code, which is
+/// all the invoker sees; `/errorlookup` shows the rest.
+pub async fn dispatch(ctx: &Context, command: &ApplicationCommandInteraction) {
+    let cmd = match REGISTRY.commands.get(command.data.name.as_str()) { Some(c) => c, None => return };
+    if let Some(guild_id) = command.guild_id {
+        if let Some(module) = crate::modules::disabled_for_command(guild_id, &command.data.name).await {
+            let _ = reply(&ctx.http, command, false, true, format!("このサーバーでは「{}」モジュールが無効になっています。", module)).await;
+            return;
+        }
+    }
+    let ephemeral = match cmd.defer() {
+        Defer::No | Defer::Ephemeral => true,
+        Defer::UserPreference => crate::preferences::wants_ephemeral(command.user.id.0 as i64).await,
+        Defer::Public => false,
+    };
+    let deferred = !matches!(cmd.defer(), Defer::No);
+    let result = if deferred {
+        command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(ephemeral))).await.map_err(anyhow::Error::from)
+    } else {
+        Ok(())
+    };
+    let result = match result {
+        Ok(()) => cmd.run(&Invocation { ctx, command, args: Args::new(&command.data.options), ephemeral, deferred }).await,
+        Err(e) => Err(e),
+    };
+    let e = match result { Ok(()) => return, Err(e) => e };
+    let msg = match crate::errorcodes::record(command, &e).await {
+        Some(code) => format!("エラーが発生しました (エラーコード: `{}`)。\n解決しない場合は、このコードを添えてお問い合わせください。", code),
+        None => format!("エラーが発生しました: {}", e),
+    };
+    // Commands that answer by themselves may or may not have answered before failing; try a
+    // response first, then a followup.
+    if deferred || reply(&ctx.http, command, false, ephemeral, msg.clone()).await.is_err() {
+        let _ = reply(&ctx.http, command, true, ephemeral, msg).await;
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime, Utc};
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+
+use crate::chart;
+use crate::commands::{Command, Invocation};
+use crate::member_cache;
+use crate::members_history;
+use crate::stats;
+use crate::owner;
+
+const DEFAULT_DAYS: i64 = 180;
+const MAX_DAYS: i64 = 730;
+const MAX_GUILDS: usize = 8;
+
+pub struct CompareGuildsCommand;
+
+#[async_trait]
+impl Command for CompareGuildsCommand {
+    fn name(&self) -> &'static str { "compare-guilds" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("オーナー用: 複数サーバーの成長曲線を比較します")
+            .create_option(|o| o.name("guilds").description(format!("サーバーID (カンマ区切り, 最大{}件, デフォルト: 参加中のサーバー)", MAX_GUILDS)).kind(CommandOptionType::String).required(false))
+            .create_option(|o| chart::size_option(o))
+            .create_option(|o| o.name("days").description(format!("比較する期間 (日, デフォルト{})", DEFAULT_DAYS)).kind(CommandOptionType::Integer).required(false))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_compare_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_compare_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    if !owner::is_owner(command.user.id) { command.create_followup_message(&ctx.http, |m| m.content("権限がありません。" ).ephemeral(true)).await?; return Ok(()); }
+
+    let days = command.data.options.iter().find(|o| o.name=="days").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(DEFAULT_DAYS).clamp(7, MAX_DAYS);
+    let size_opt = command.data.options.iter().find(|o| o.name=="size").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str());
+    let size = match chart::resolve(None, size_opt, chart::ChartSize::new(1200, 500)).await {
+        Ok(s) => s,
+        Err(e) => { command.create_followup_message(&ctx.http, |m| m.content(e).ephemeral(true)).await?; return Ok(()); }
+    };
+    let guilds: Vec<GuildId> = match command.data.options.iter().find(|o| o.name=="guilds").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()) {
+        Some(s) => s.split(',').filter_map(|id| id.trim().parse().ok()).map(GuildId).collect(),
+        None => ctx.cache.guilds(),
+    };
+    let guilds: Vec<GuildId> = guilds.into_iter().take(MAX_GUILDS).collect();
+    if guilds.len() < 2 { command.create_followup_message(&ctx.http, |m| m.content("比較するにはサーバーを2つ以上指定してください。" ).ephemeral(true)).await?; return Ok(()); }
+
+    let end = Utc::now().date_naive();
+    let start = end - Duration::days(days - 1);
+    let mut dates = Vec::new();
+    let mut series = Vec::new();
+    let mut lines = Vec::new();
+    for guild_id in guilds {
+        let members = match member_cache::members(&ctx.http, guild_id).await {
+            Ok(m) => m,
+            Err(e) => { lines.push(format!("{}: 取得できませんでした ({})", guild_id.0, e)); continue; }
+        };
+        let join_dates: Vec<NaiveDateTime> = members.iter().filter_map(|m| m.joined_at.and_then(|j| chrono::DateTime::from_timestamp(j.unix_timestamp(), 0).map(|d| d.naive_utc()))).collect();
+        let (d, counts) = stats::member_counts(&join_dates, start, end);
+        // Index each curve to 100 at its first non-zero day so different sizes share one axis.
+        let base = counts.iter().copied().find(|c| *c > 0).unwrap_or(1) as f64;
+        let normalized: Vec<f64> = counts.iter().map(|c| *c as f64 / base * 100.0).collect();
+        let name = ctx.cache.guild(guild_id).map(|g| g.name.clone()).unwrap_or_else(|| guild_id.0.to_string());
+        let first = counts.first().copied().unwrap_or(0);
+        let last = counts.last().copied().unwrap_or(0);
+        lines.push(format!("{}: {}人 → {}人 ({:+.1}%)", name, first, last, normalized.last().copied().unwrap_or(100.0) - 100.0));
+        dates = d;
+        series.push((name, normalized));
+    }
+    if series.len() < 2 { command.create_followup_message(&ctx.http, |m| m.content(format!("比較できるサーバーが不足しています。\n{}", lines.join("\n"))).ephemeral(true)).await?; return Ok(()); }
+
+    let chart = members_history::create_multi_line_chart("Normalized Member Growth (start = 100)", &dates, &series, size, chart::Locale::for_guild(command.guild_id.map(|g| g.0 as i64)).await)?;
+    let chart = chart::finish(command.guild_id.map(|g| g.0 as i64), chart, "compare_guilds").await;
+    let mut embed = serenity::builder::CreateEmbed::default();
+    embed.title(format!("サーバー成長比較 (直近{}日)", days));
+    embed.description(lines.join("\n"));
+    embed.image(chart.url());
+    embed.color(serenity::utils::Colour::BLURPLE);
+    embed.footer(|f| f.text("EvexBot | Guild Comparison"));
+    command.create_followup_message(&ctx.http, |m| m.add_file((chart.bytes.as_slice(), chart.filename().as_str())).embed(|e| { *e = embed; e }).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,10 @@
+use serenity::model::application::component::ActionRowComponent;
+use serenity::model::application::interaction::modal::ModalSubmitInteraction;
+
+/// Value of the text input with `custom_id` in a submitted modal.
+pub fn modal_value(modal: &ModalSubmitInteraction, custom_id: &str) -> Option<String> {
+    modal.data.components.iter().flat_map(|row| row.components.iter()).find_map(|c| match c {
+        ActionRowComponent::InputText(t) if t.custom_id == custom_id => Some(t.value.clone()),
+        _ => None,
+    })
+}
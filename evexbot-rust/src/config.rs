@@ -4,7 +4,15 @@ use anyhow::Result;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
+    /// Kept from the Python bot's config; slash commands don't use it.
+    #[allow(dead_code)]
     pub prefix: String,
+    /// `global` (default) or `guild`; overridden by COMMAND_REGISTRATION.
+    #[serde(default)]
+    pub command_registration: Option<String>,
+    /// Guilds that get the commands in `guild` mode; overridden by COMMAND_GUILD_IDS.
+    #[serde(default)]
+    pub command_guilds: Vec<u64>,
 }
 
 impl Config {
@@ -13,4 +21,10 @@ impl Config {
         let cfg: Config = serde_yaml::from_str(&s)?;
         Ok(cfg)
     }
+
+    /// The file at CONFIG_PATH (default `config.yml`), if it exists and parses.
+    pub fn load() -> Option<Self> {
+        let path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.yml".to_string());
+        Self::load_from_file(&path).ok()
+    }
 }
@@ -0,0 +1,209 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::{Channel, ChannelType, Message};
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+use crate::scheduler;
+use crate::triage;
+use crate::zikosyokai;
+
+const CHECK_INTERVAL_SECONDS: u64 = 3600;
+const MAX_THRESHOLD: i64 = 1000;
+const MAX_SHOUTOUTS: usize = 20;
+const RECENT_AWARDS_SHOWN: i64 = 10;
+
+/// Contributions that count towards recognition: (kind stored in the DB, `/contributor set` metric, label).
+const KINDS: [(&str, &str, &str); 3] = [
+    ("help_answer", "help", "ヘルプスレッドでの回答"),
+    ("starboard", "starboard", "ハイライト入りした投稿"),
+    ("intro_reply", "intro", "自己紹介への返信"),
+];
+
+fn label(kind: &str) -> &'static str {
+    KINDS.iter().find(|(k, _, _)| *k == kind).map(|(_, _, l)| *l).unwrap_or("貢献")
+}
+
+fn threshold(settings: &db::ContributorSettings, kind: &str) -> i64 {
+    match kind {
+        "help_answer" => settings.help_threshold,
+        "starboard" => settings.starboard_threshold,
+        "intro_reply" => settings.intro_threshold,
+        _ => 0,
+    }
+}
+
+/// Start the job that hands out recognition once members cross a threshold.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("contributors", Duration::from_secs(CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { award_all(&http).await }
+    });
+}
+
+/// Record help answers and intro replies. Only kinds with a threshold set are tracked.
+pub async fn handle_message(ctx: &Context, msg: &Message) -> Result<()> {
+    if msg.author.bot { return Ok(()); }
+    let guild_id = match msg.guild_id { Some(g) => g, None => return Ok(()) };
+    let settings = db::get_contributor_settings(guild_id.0 as i64).await?;
+    if settings.help_threshold > 0 { record_help_answer(ctx, msg, guild_id.0 as i64).await?; }
+    if settings.intro_threshold > 0 { record_intro_reply(msg, guild_id).await?; }
+    Ok(())
+}
+
+/// A message in someone else's help thread counts as an answer, once per thread. Replies in the
+/// help channel itself count once per question replied to.
+async fn record_help_answer(ctx: &Context, msg: &Message, guild_id: i64) -> Result<()> {
+    let channels = db::get_triage_channels(guild_id).await?;
+    if channels.is_empty() { return Ok(()); }
+    let author = msg.author.id.0;
+    let now = Utc::now().timestamp();
+
+    if channels.contains(&(msg.channel_id.0 as i64)) {
+        if let Some(question) = msg.referenced_message.as_ref().filter(|m| m.author.id.0 != author && !m.author.bot) {
+            db::record_contribution(guild_id, author as i64, "help_answer", question.id.0 as i64, now).await?;
+        }
+        return Ok(());
+    }
+    let thread = match msg.channel_id.to_channel(ctx).await {
+        Ok(Channel::Guild(c)) if matches!(c.kind, ChannelType::PublicThread | ChannelType::PrivateThread) => c,
+        _ => return Ok(()),
+    };
+    if !thread.parent_id.map(|p| channels.contains(&(p.0 as i64))).unwrap_or(false) { return Ok(()); }
+    if triage::asker(ctx, &thread).await.map(|a| a != author).unwrap_or(false) {
+        db::record_contribution(guild_id, author as i64, "help_answer", thread.id.0 as i64, now).await?;
+    }
+    Ok(())
+}
+
+/// A reply to another member's intro counts once per intro.
+async fn record_intro_reply(msg: &Message, guild_id: GuildId) -> Result<()> {
+    let intro = match msg.referenced_message.as_ref() {
+        Some(m) if m.author.id != msg.author.id && !m.author.bot => m,
+        _ => return Ok(()),
+    };
+    if zikosyokai::intro_channel(Some(guild_id)).await? != Some(msg.channel_id) { return Ok(()); }
+    db::record_contribution(guild_id.0 as i64, msg.author.id.0 as i64, "intro_reply", intro.id.0 as i64, Utc::now().timestamp()).await?;
+    Ok(())
+}
+
+async fn award_all(http: &Http) -> Result<()> {
+    for settings in db::get_enabled_contributor_settings().await? {
+        if let Err(e) = award(http, &settings).await {
+            log::warn!("contributors: failed to check guild {}: {}", settings.guild_id, e);
+        }
+    }
+    Ok(())
+}
+
+async fn award(http: &Http, settings: &db::ContributorSettings) -> Result<()> {
+    let guild_id = settings.guild_id;
+    // Digest scores are pruned after a few weeks, so entries are copied over before they go.
+    if settings.starboard_threshold > 0 {
+        let digest = db::get_highlight_settings(guild_id).await?;
+        db::sync_starboard_contributions(guild_id, digest.threshold).await?;
+    }
+
+    let now = Utc::now().timestamp();
+    let mut shoutouts = Vec::new();
+    for db::ContributionCount { user_id, kind, count } in db::get_contribution_counts(guild_id).await? {
+        let needed = threshold(settings, &kind);
+        if needed <= 0 || count < needed { continue; }
+        if !db::add_contributor_award(guild_id, user_id, &kind, now).await? { continue; }
+        if let Some(role) = settings.role_id {
+            if let Err(e) = http.add_member_role(guild_id as u64, user_id as u64, role as u64, Some("contributor recognition")).await {
+                log::warn!("contributors: failed to give role {} to {} in {}: {}", role, user_id, guild_id, e);
+            }
+        }
+        shoutouts.push(format!("🎖️ <@{}> さんが「{}」{}件を達成しました！", user_id, label(&kind), needed));
+    }
+
+    let channel = match settings.channel_id { Some(c) => ChannelId(c as u64), None => return Ok(()) };
+    if shoutouts.is_empty() { return Ok(()); }
+    let total = shoutouts.len();
+    shoutouts.truncate(MAX_SHOUTOUTS);
+    if total > MAX_SHOUTOUTS { shoutouts.push(format!("ほか{}件", total - MAX_SHOUTOUTS)); }
+    channel.send_message(http, |m| m.content(format!("コミュニティへの貢献ありがとうございます！\n{}", shoutouts.join("\n")))).await?;
+    Ok(())
+}
+
+pub struct ContributorCommand;
+
+#[async_trait]
+impl Command for ContributorCommand {
+    fn name(&self) -> &'static str { "contributor" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("貢献の多いメンバーにロールや称賛メッセージを自動で贈ります")
+            .create_option(|s| {
+                s.name("set").description("達成とみなす件数を設定します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| {
+                        o.name("metric").description("数える貢献").kind(CommandOptionType::String).required(true);
+                        for (_, metric, label) in KINDS { o.add_string_choice(label, metric); }
+                        o
+                    })
+                    .create_sub_option(|o| o.name("count").description("件数 (0で対象外)").kind(CommandOptionType::Integer).min_int_value(0).max_int_value(MAX_THRESHOLD).required(true))
+            })
+            .create_option(|s| {
+                s.name("reward").description("達成時に付与するロールと称賛を投稿するチャンネル").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("role").description("付与するロール (省略でなし)").kind(CommandOptionType::Role).required(false))
+                    .create_sub_option(|o| o.name("channel").description("称賛メッセージのチャンネル (省略でなし)").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(false))
+            })
+            .create_option(|s| s.name("settings").description("設定と最近の達成者を表示します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "set" => {
+                let metric = args.str("metric").unwrap_or("");
+                let (kind, label) = match KINDS.iter().find(|(_, m, _)| *m == metric) { Some((k, _, l)) => (*k, *l), None => return Ok(()) };
+                let count = args.int("count").unwrap_or(0).clamp(0, MAX_THRESHOLD);
+                db::set_contributor_threshold(gid, kind, count).await?;
+                if count == 0 { inv.say(format!("「{}」は対象外にしました。", label)).await } else { inv.say(format!("「{}」が{}件に達したメンバーを表彰します (1時間ごとに確認)。", label, count)).await }
+            }
+            "reward" => {
+                let role = args.role("role");
+                if role.map(|r| r.managed).unwrap_or(false) { return inv.say("連携ロールは付与できません。").await; }
+                let channel = args.channel("channel");
+                db::set_contributor_reward(gid, role.map(|r| r.id.0 as i64), channel.map(|c| c.id.0 as i64)).await?;
+                let role = role.map(|r| format!("<@&{}>", r.id.0)).unwrap_or_else(|| "なし".to_string());
+                let channel = channel.map(|c| format!("<#{}>", c.id.0)).unwrap_or_else(|| "なし".to_string());
+                inv.say(format!("達成時の付与ロール: {}\n称賛メッセージ: {}", role, channel)).await
+            }
+            "settings" => {
+                let settings = db::get_contributor_settings(gid).await?;
+                let mut lines: Vec<String> = KINDS.iter().map(|(kind, _, label)| match threshold(&settings, kind) {
+                    0 => format!("・{}: 対象外", label),
+                    n => format!("・{}: {}件", label, n),
+                }).collect();
+                lines.push(format!("付与ロール: {}", settings.role_id.map(|r| format!("<@&{}>", r)).unwrap_or_else(|| "なし".to_string())));
+                lines.push(format!("称賛メッセージ: {}", settings.channel_id.map(|c| format!("<#{}>", c)).unwrap_or_else(|| "なし".to_string())));
+                let awards = db::get_contributor_awards(gid, RECENT_AWARDS_SHOWN).await?;
+                if !awards.is_empty() {
+                    lines.push("\n最近の達成者:".to_string());
+                    lines.extend(awards.iter().map(|a| format!("<@{}> — {} (<t:{}:d>)", a.user_id, label(&a.kind), a.awarded_at)));
+                }
+                inv.say(lines.join("\n")).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike};
+
+/// Days searched for the next match before giving up, so impossible dates like `0 0 31 2 *` end.
+const SEARCH_DAYS: i64 = 366 * 5;
+
+/// A five-field cron expression: minute, hour, day of month, month, day of week (0 or 7 is
+/// Sunday). Fields take `*`, numbers, `a-b` ranges, `,` lists and `/n` steps.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Cron matches either day field when both are restricted, and only the other when one is `*`.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn field(s: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, n)) => (r, n.parse::<u32>().ok().filter(|n| *n > 0).ok_or_else(|| format!("{}の間隔 `{}` が不正です", name, n))?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else {
+            let parse = |v: &str| v.parse::<u32>().ok().filter(|v| (min..=max).contains(v)).ok_or_else(|| format!("{}は{}～{}で指定してください (`{}`)", name, min, max, v));
+            match range.split_once('-') {
+                Some((a, b)) => (parse(a)?, parse(b)?),
+                // `5/15` means from 5 to the end in steps of 15.
+                None if step > 1 => (parse(range)?, max),
+                None => { let v = parse(range)?; (v, v) }
+            }
+        };
+        if start > end { return Err(format!("{}の範囲 `{}` が逆順です", name, range)); }
+        for v in (start..=end).step_by(step as usize) { bits |= 1 << v; }
+    }
+    Ok(bits)
+}
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 { return Err("cron式は「分 時 日 月 曜日」の5項目で指定してください (例: `0 9 * * 1` で毎週月曜9:00)".to_string()); }
+        let mut weekdays = field(fields[4], 0, 7, "曜日")?;
+        if weekdays & (1 << 7) != 0 { weekdays |= 1; }
+        Ok(Schedule {
+            minutes: field(fields[0], 0, 59, "分")?,
+            hours: field(fields[1], 0, 23, "時")?,
+            days: field(fields[2], 1, 31, "日")?,
+            months: field(fields[3], 1, 12, "月")?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    fn matches_day<Tz: TimeZone>(&self, t: &DateTime<Tz>) -> bool {
+        if self.months & (1 << t.month()) == 0 { return false; }
+        let day = self.days & (1 << t.day()) != 0;
+        let weekday = self.weekdays & (1 << t.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first matching minute strictly after `after`, in `after`'s time zone.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut day = start.date();
+        for _ in 0..SEARCH_DAYS {
+            let midnight = tz.from_local_datetime(&day.and_hms_opt(0, 0, 0)?).earliest()?;
+            if self.matches_day(&midnight) {
+                for hour in (0..24).filter(|h| self.hours & (1 << h) != 0) {
+                    for minute in (0..60).filter(|m| self.minutes & (1 << m) != 0) {
+                        let candidate = day.and_hms_opt(hour, minute, 0)?;
+                        if candidate < start { continue; }
+                        if let Some(t) = tz.from_local_datetime(&candidate).earliest() { return Some(t); }
+                    }
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        None
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::{Embed, Message};
+use serenity::model::id::ChannelId;
+
+use crate::commands::{Command, Defer, Invocation};
+use crate::messagelink;
+use crate::permissions;
+use crate::report::Report;
+
+/// Keeps one run within a few seconds of Discord's per-route rate limits.
+const MAX_TARGETS: usize = 20;
+
+static CHANNEL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?:<#(\d+)>|(\d+))$").unwrap());
+static WEBHOOK: Lazy<Regex> = Lazy::new(|| Regex::new(r"^https://(?:canary\.|ptb\.)?discord(?:app)?\.com/api/webhooks/\d+/[\w-]+$").unwrap());
+
+enum Target {
+    Channel(ChannelId),
+    Webhook(String),
+}
+
+/// Channel mentions, channel ids and webhook URLs separated by spaces or commas.
+fn parse_targets(input: &str) -> std::result::Result<Vec<Target>, String> {
+    let mut targets = Vec::new();
+    for token in input.split(|c: char| c == ',' || c.is_whitespace()).filter(|t| !t.is_empty()) {
+        if let Some(cap) = CHANNEL.captures(token) {
+            let id = cap.get(1).or_else(|| cap.get(2)).map(|m| m.as_str()).unwrap_or("");
+            targets.push(Target::Channel(ChannelId(id.parse().map_err(|_| format!("`{}` はチャンネルではありません。", token))?)));
+        } else if WEBHOOK.is_match(token) {
+            targets.push(Target::Webhook(token.to_string()));
+        } else {
+            return Err(format!("`{}` はチャンネルでも Webhook URL でもありません。", token));
+        }
+    }
+    Ok(targets)
+}
+
+/// Content with attachment links appended, since files are not re-uploaded.
+fn content_of(message: &Message) -> String {
+    let mut content = message.content.clone();
+    for a in message.attachments.iter() {
+        content.push('\n');
+        content.push_str(&a.url);
+    }
+    content
+}
+
+async fn deliver(http: &Http, target: &Target, message: &Message) -> Result<()> {
+    let content = content_of(message);
+    match target {
+        Target::Channel(channel) => {
+            channel.send_message(http, |m| {
+                if !content.is_empty() { m.content(&content); }
+                for embed in message.embeds.iter() { m.add_embed(|e| { *e = CreateEmbed::from(embed.clone()); e }); }
+                m
+            }).await?;
+        }
+        Target::Webhook(url) => {
+            let webhook = http.get_webhook_from_url(url).await?;
+            let embeds = message.embeds.iter().map(|embed| Embed::fake(|e| { *e = CreateEmbed::from(embed.clone()); e })).collect();
+            let avatar = message.author.face();
+            webhook.execute(http, true, |w| {
+                if !content.is_empty() { w.content(&content); }
+                w.username(&message.author.name).avatar_url(&avatar).embeds(embeds)
+            }).await?;
+        }
+    }
+    Ok(())
+}
+
+pub struct CrosspostCommand;
+
+#[async_trait]
+impl Command for CrosspostCommand {
+    fn name(&self) -> &'static str { "crosspost" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("お知らせを複数のチャンネル・Webhookに再投稿します")
+            .create_option(|o| o.name("message_link").description("再投稿するメッセージのリンク").kind(CommandOptionType::String).required(true))
+            .create_option(|o| o.name("channels").description("投稿先のチャンネル (#チャンネル) や Webhook URL をスペース区切りで").kind(CommandOptionType::String).required(true))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+
+        let link = inv.args.str("message_link").unwrap_or("");
+        // Only this server's messages can be republished, so admins can't lift content from elsewhere.
+        let message = match messagelink::parse(link) {
+            Some((g, _, _)) if g == guild_id => match messagelink::fetch(&ctx.http, link).await {
+                Some(m) => m,
+                None => return inv.say("メッセージを取得できませんでした。").await,
+            },
+            Some(_) => return inv.say("このサーバーのメッセージのみ再投稿できます。").await,
+            None => return inv.say("メッセージリンクを指定してください。").await,
+        };
+        let targets = match parse_targets(inv.args.str("channels").unwrap_or("")) {
+            Ok(t) if t.is_empty() => return inv.say("投稿先を指定してください。").await,
+            Ok(t) if t.len() > MAX_TARGETS => return inv.say(format!("投稿先は最大{}件までです。", MAX_TARGETS)).await,
+            Ok(t) => t,
+            Err(e) => return inv.say(e).await,
+        };
+
+        let mut lines = Vec::new();
+        let mut failed = 0;
+        for target in targets.iter() {
+            // Channels outside this server would let the command post anywhere the bot is.
+            let result = match target {
+                Target::Channel(c) => match c.to_channel(&ctx.http).await.ok().and_then(|ch| ch.guild()) {
+                    Some(ch) if ch.guild_id == guild_id => deliver(&ctx.http, target, &message).await,
+                    _ => Err(anyhow::anyhow!("このサーバーのチャンネルではありません")),
+                },
+                Target::Webhook(_) => deliver(&ctx.http, target, &message).await,
+            };
+            let name = match target { Target::Channel(c) => format!("<#{}>", c.0), Target::Webhook(_) => "Webhook".to_string() };
+            match result {
+                Ok(()) => lines.push(format!("✅ {}", name)),
+                Err(e) => {
+                    failed += 1;
+                    lines.push(format!("❌ {}: {}", name, e));
+                }
+            }
+        }
+        Report::embed("crosspost.txt", "再投稿の結果", serenity::utils::Colour::BLURPLE)
+            .header(format!("{}件中{}件に投稿しました。", targets.len(), targets.len() - failed))
+            .lines(lines)
+            .ephemeral(true)
+            .send(&ctx.http, command)
+            .await
+    }
+}
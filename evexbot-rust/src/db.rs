@@ -1,106 +1,4216 @@
-use sqlx::{Sqlite, SqlitePool, Row};
-use serenity::http::Http;
+use sqlx::any::{AnyKind, AnyPool, AnyRow};
+use sqlx::{Any, Column, Connection, FromRow, Row, ValueRef};
 use std::sync::Arc;
 use anyhow::Result;
 use once_cell::sync::OnceCell;
 
-static POOL: OnceCell<Arc<SqlitePool>> = OnceCell::new();
+static POOL: OnceCell<Arc<AnyPool>> = OnceCell::new();
 
-pub async fn init_db(http: &Http) -> Result<()> {
-    // Use a data directory similar to the Python project
-    let db_path = std::path::Path::new("data/welcome.db");
-    if let Some(parent) = db_path.parent() {
-        std::fs::create_dir_all(parent)?;
+/// An unexecuted statement, for writes that run both alone and inside a transaction.
+type AnyQuery = sqlx::query::Query<'static, Any, sqlx::any::AnyArguments<'static>>;
+
+/// The database behind DATABASE_URL. Queries are written once in the SQL both accept (`$n`
+/// placeholders, `ON CONFLICT`, `RETURNING`, TRUE/FALSE); the few that can't be, like
+/// full-text search and the read-only `/dbquery`, match on this.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+// Typed rows for every table. Queries stay as runtime `query_as` rather than `query_as!`: the
+// compile-time macros (and `sqlx prepare` offline data) check against one concrete database, and
+// the pool here is `Any` over SQLite and PostgreSQL. The test suite runs against both instead.
+// New queries should get a struct here rather than returning tuples.
+// Structs carry every column of their table, so some fields aren't read anywhere yet.
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct WelcomeSettings {
+    pub guild_id: i64,
+    pub is_enabled: bool,
+    pub member_increment: i64,
+    pub channel_id: Option<i64>,
+}
+
+impl WelcomeSettings {
+    fn default_for(guild_id: i64) -> Self {
+        WelcomeSettings { guild_id, is_enabled: false, member_increment: 100, channel_id: None }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct LeaveSettings {
+    pub guild_id: i64,
+    pub is_enabled: bool,
+    pub channel_id: Option<i64>,
+}
+
+impl LeaveSettings {
+    fn default_for(guild_id: i64) -> Self {
+        LeaveSettings { guild_id, is_enabled: false, channel_id: None }
+    }
+}
+
+/// Guild-wide preferences that don't belong to a single feature.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct GuildConfig {
+    pub guild_id: i64,
+    pub language: String,
+}
+
+impl GuildConfig {
+    fn default_for(guild_id: i64) -> Self {
+        GuildConfig { guild_id, language: "ja".to_string() }
+    }
+}
+
+/// Per-user settings from `user_prefs`. `language` None follows the guild.
+#[derive(Clone, Debug, FromRow)]
+pub struct UserPrefs {
+    pub user_id: i64,
+    pub language: Option<String>,
+    pub dm_reminders: bool,
+    pub quote_privacy: bool,
+    pub ephemeral: bool,
+    pub onboarding_dms: bool,
+}
+
+impl UserPrefs {
+    pub fn default_for(user_id: i64) -> Self {
+        UserPrefs { user_id, language: None, dm_reminders: false, quote_privacy: false, ephemeral: false, onboarding_dms: true }
+    }
+}
+
+/// The guild's rules panel from `/rules panel`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct RulesSettings {
+    pub guild_id: i64,
+    pub channel_id: Option<i64>,
+    pub message_id: Option<i64>,
+    pub role_id: Option<i64>,
+    pub version: i64,
+}
+
+/// A `/shortlink` and its click count.
+#[derive(Clone, Debug, FromRow)]
+pub struct Shortlink {
+    pub slug: String,
+    pub url: String,
+    pub created_by: i64,
+    pub created_at: i64,
+    pub clicks: i64,
+    pub last_clicked_at: Option<i64>,
+}
+
+/// Weekly top-posts digest settings from `/highlights`.
+#[derive(Clone, Debug, FromRow)]
+pub struct HighlightSettings {
+    pub guild_id: i64,
+    pub channel_id: Option<i64>,
+    pub threshold: i64,
+    pub last_sent: Option<String>,
+}
+
+impl HighlightSettings {
+    fn default_for(guild_id: i64) -> Self {
+        HighlightSettings { guild_id, channel_id: None, threshold: 5, last_sent: None }
+    }
+}
+
+/// First-message spotlight settings from `/spotlight`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct SpotlightSettings {
+    pub guild_id: i64,
+    pub is_enabled: bool,
+    pub mode: String,
+    pub channel_id: Option<i64>,
+}
+
+impl SpotlightSettings {
+    fn default_for(guild_id: i64) -> Self {
+        SpotlightSettings { guild_id, is_enabled: false, mode: "react".to_string(), channel_id: None }
+    }
+}
+
+/// A posted "次の目標到達予測" message from `growth_forecasts`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct GrowthForecast {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub message_id: i64,
+    pub target: i64,
+    pub updated_at: i64,
+}
+
+/// A row of `mod_cases`, for `/case`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct ModCase {
+    pub id: i64,
+    pub guild_id: i64,
+    pub moderator_id: Option<i64>,
+    pub target_id: i64,
+    pub action: String,
+    pub reason: Option<String>,
+    pub created_at: i64,
+    pub appeal_status: Option<String>,
+}
+
+/// Where `/logsettings` sends message edit and deletion logs.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct MessageLogSettings {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub log_edits: bool,
+    pub log_deletes: bool,
+}
+
+/// A `/role-decay` rule: `role_id` is removed after `days` without activity.
+#[derive(Clone, Debug, FromRow)]
+pub struct RoleDecayRule {
+    pub guild_id: i64,
+    pub role_id: i64,
+    pub days: i64,
+}
+
+/// `/contributor` thresholds and rewards. A threshold of 0 disables that kind of contribution.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct ContributorSettings {
+    pub guild_id: i64,
+    pub help_threshold: i64,
+    pub starboard_threshold: i64,
+    pub intro_threshold: i64,
+    pub role_id: Option<i64>,
+    pub channel_id: Option<i64>,
+}
+
+/// A help thread tracked for the 解決済み button and `/helpstats`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct HelpThread {
+    pub thread_id: i64,
+    pub guild_id: i64,
+    pub asker_id: Option<i64>,
+    pub created_at: i64,
+    pub first_answer_at: Option<i64>,
+    pub resolved_at: Option<i64>,
+    pub resolved_by: Option<i64>,
+    pub title: Option<String>,
+}
+
+/// A role menu posted with `/reactionrole create`.
+#[derive(Clone, Debug, FromRow)]
+pub struct ReactionRoleMenu {
+    pub message_id: i64,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub style: String,
+    pub title: String,
+    pub created_at: i64,
+}
+
+/// A `/kb` article.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct KbArticle {
+    pub id: i64,
+    pub guild_id: i64,
+    pub title: String,
+    pub body: String,
+    pub author_id: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A `/poll`. `options` is newline-separated.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct Poll {
+    pub id: i64,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+    pub creator_id: i64,
+    pub question: String,
+    pub options: String,
+    pub created_at: i64,
+    pub closes_at: i64,
+    pub closed: bool,
+}
+
+impl Poll {
+    pub fn option_labels(&self) -> Vec<&str> { self.options.lines().collect() }
+}
+
+/// A `/remind` reminder or `/schedule` announcement waiting in `scheduled_jobs`.
+#[derive(Clone, Debug, FromRow)]
+pub struct ScheduledJob {
+    pub id: i64,
+    pub guild_id: i64,
+    pub kind: String,
+    pub user_id: i64,
+    pub channel_id: i64,
+    pub message: String,
+    pub cron: Option<String>,
+    pub next_run_at: i64,
+    pub created_at: i64,
+}
+
+/// Prompt-of-the-day settings from `prompt_settings`.
+#[derive(Clone, Debug, FromRow)]
+pub struct PromptSettings {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub post_hour: i64,
+    pub last_posted: Option<String>,
+    pub last_winner_week: Option<String>,
+}
+
+/// One admin-curated prompt from `prompts`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct Prompt {
+    pub id: i64,
+    pub guild_id: i64,
+    pub text: String,
+    pub used_count: i64,
+    pub last_used_at: Option<i64>,
+}
+
+/// One posted prompt from `prompt_posts`.
+#[derive(Clone, Debug, FromRow)]
+pub struct PromptPost {
+    pub message_id: i64,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub thread_id: i64,
+    pub prompt: String,
+    pub posted_at: i64,
+}
+
+/// A member's row from `user_xp`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct UserXp {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub xp: i64,
+    pub message_count: i64,
+    pub last_xp_at: i64,
+}
+
+/// Where level-ups are announced. Without a row they are announced where the message was sent.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct LevelingSettings {
+    pub guild_id: i64,
+    pub announce: bool,
+    pub channel_id: Option<i64>,
+}
+
+/// Join burst detection and the raid mode it triggers. Without a row detection is off.
+#[derive(Clone, Debug, FromRow)]
+pub struct AntiraidSettings {
+    pub guild_id: i64,
+    pub is_enabled: bool,
+    pub joins_per_minute: i64,
+    pub pause_minutes: i64,
+    pub raise_verification: bool,
+    /// Accounts younger than this are kicked during a raid; 0 kicks no one.
+    pub kick_account_age_days: i64,
+    /// Falls back to the mod-log channel.
+    pub alert_channel_id: Option<i64>,
+    pub raid_until: Option<i64>,
+    pub previous_verification: Option<i64>,
+}
+
+impl AntiraidSettings {
+    fn default_for(guild_id: i64) -> Self {
+        AntiraidSettings { guild_id, is_enabled: false, joins_per_minute: 10, pause_minutes: 15, raise_verification: false, kick_account_age_days: 0, alert_channel_id: None, raid_until: None, previous_verification: None }
+    }
+}
+
+/// Automod spam filter thresholds. Without a row the filter is off.
+#[derive(Clone, Debug, FromRow)]
+pub struct SpamFilterSettings {
+    pub guild_id: i64,
+    pub is_enabled: bool,
+    /// `delete`, `timeout` or `warn`.
+    pub action: String,
+    /// Identical messages from one member within the duplicate window; 0 turns the check off,
+    /// like the other limits.
+    pub duplicate_limit: i64,
+    pub mention_limit: i64,
+    pub emoji_limit: i64,
+    pub caps_percent: i64,
+    pub timeout_minutes: i64,
+}
+
+impl SpamFilterSettings {
+    fn default_for(guild_id: i64) -> Self {
+        SpamFilterSettings { guild_id, is_enabled: false, action: "delete".to_string(), duplicate_limit: 4, mention_limit: 5, emoji_limit: 15, caps_percent: 80, timeout_minutes: 10 }
+    }
+}
+
+/// A `/filter` rule: `pattern` is a keyword, or a regex when `is_regex` is set.
+#[derive(Clone, Debug, FromRow)]
+pub struct WordFilterRule {
+    pub id: i64,
+    pub guild_id: i64,
+    pub pattern: String,
+    pub is_regex: bool,
+    pub action: String,
+}
+
+/// A failed command invocation, looked up by its error code.
+#[derive(Clone, Debug, FromRow)]
+pub struct ErrorReport {
+    pub id: i64,
+    pub command: String,
+    pub guild_id: Option<i64>,
+    pub channel_id: i64,
+    pub user_id: i64,
+    /// Options as `name=value`, subcommands first.
+    pub options: String,
+    /// The full error chain.
+    pub error: String,
+    pub created_at: i64,
+}
+
+/// One milestone celebration from `milestone_log`.
+#[derive(Clone, Debug, FromRow)]
+pub struct MilestoneEntry {
+    pub member_count: i64,
+    pub channel_id: i64,
+    pub message_id: i64,
+    pub created_at: i64,
+}
+
+/// A `/config command-permissions` restriction: `kind` is `role` or `channel`.
+#[derive(Clone, Debug, FromRow)]
+pub struct CommandPermission {
+    pub command: String,
+    pub kind: String,
+    pub target_id: i64,
+}
+
+/// Voice join/leave announcements for an event that is running now.
+#[derive(Clone, Debug, FromRow)]
+pub struct VoiceAnnouncement {
+    pub name: String,
+    pub voice_channel_id: i64,
+    pub text_channel_id: i64,
+    pub speaker_role_id: i64,
+}
+
+/// An event starting soon, with the channel its guild posts reminders in.
+#[derive(Clone, Debug, FromRow)]
+pub struct EventReminder {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub name: String,
+    pub starts_at: i64,
+    pub scheduled_event_id: Option<i64>,
+}
+
+/// A queued onboarding DM that is due.
+#[derive(Clone, Debug, FromRow)]
+pub struct OnboardingDm {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub step: i64,
+}
+
+/// A member's latest acceptance from `rules_acceptances`.
+#[derive(Clone, Debug, FromRow)]
+pub struct RulesAcceptance {
+    pub user_id: i64,
+    pub version: i64,
+    pub accepted_at: i64,
+}
+
+/// A scored message from `highlight_scores`.
+#[derive(Clone, Debug, FromRow)]
+pub struct Highlight {
+    pub channel_id: i64,
+    pub message_id: i64,
+    pub author_id: i64,
+    pub score: i64,
+}
+
+/// One member acted on by a `/prune` run.
+#[allow(dead_code)]
+#[derive(Clone, Debug, FromRow)]
+pub struct PruneLogEntry {
+    pub run_id: i64,
+    pub moderator_id: i64,
+    pub user_id: i64,
+    /// `kick` or `role`.
+    pub action: String,
+    pub succeeded: bool,
+    pub created_at: i64,
+}
+
+/// How many contributions of one kind a member has made.
+#[derive(Clone, Debug, FromRow)]
+pub struct ContributionCount {
+    pub user_id: i64,
+    pub kind: String,
+    pub count: i64,
+}
+
+/// A contributor threshold a member reached, from `contributor_awards`.
+#[derive(Clone, Debug, FromRow)]
+pub struct ContributorAward {
+    pub user_id: i64,
+    pub kind: String,
+    pub awarded_at: i64,
+}
+
+/// A tracked invite with how many members joined through it and how many are still here.
+#[derive(Clone, Debug, FromRow)]
+pub struct InviteStats {
+    pub code: String,
+    pub reason: String,
+    pub creator_id: i64,
+    pub created_at: i64,
+    pub joins: i64,
+    pub still_present: i64,
+}
+
+/// A `/growth notify` request whose target has been reached.
+#[derive(Clone, Debug, FromRow)]
+pub struct GrowthNotification {
+    pub channel_id: i64,
+    pub user_id: i64,
+    pub role_id: Option<i64>,
+    pub target: i64,
+    pub created_at: i64,
+}
+
+/// `/joingate` settings; a minimum age of 0 turns the check off.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct JoinGateSettings {
+    pub min_account_age_days: i64,
+    pub autorole_id: Option<i64>,
+}
+
+/// A guild's retention period for one data type, overriding the default.
+#[derive(Clone, Debug, FromRow)]
+pub struct RetentionOverride {
+    pub guild_id: i64,
+    pub data_type: String,
+    pub days: i64,
+}
+
+/// The guidance `/triage snippet set` attached to a tag.
+#[derive(Clone, Debug, FromRow)]
+pub struct TriageSnippet {
+    pub tag: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, FromRow)]
+pub struct Paste {
+    pub language: String,
+    pub content: String,
+}
+
+/// A channel that gets `/status` outage and recovery notices.
+#[derive(Clone, Debug, FromRow)]
+pub struct StatusSubscription {
+    pub guild_id: i64,
+    pub channel_id: i64,
+}
+
+pub async fn init_db() -> Result<()> {
+    let url = database_url()?;
+    if url.starts_with("sqlite:") {
+        // Use a data directory similar to the Python project
+        let db_path = std::path::Path::new(url.trim_start_matches("sqlite:").trim_start_matches("//").split('?').next().unwrap_or(""));
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
     }
 
-    let url = format!("sqlite:{}", db_path.to_string_lossy());
-    let pool = SqlitePool::connect(&url).await?;
+    let pool = AnyPool::connect(&url).await?;
 
-    // create tables
-    sqlx::query("CREATE TABLE IF NOT EXISTS welcome_settings (
-        guild_id INTEGER PRIMARY KEY,
-        is_enabled INTEGER DEFAULT 0,
-        member_increment INTEGER DEFAULT 100,
-        channel_id INTEGER DEFAULT NULL
-    );")
-    .execute(&pool)
-    .await?;
+    crate::migrations::run(&pool).await?;
 
-    sqlx::query("CREATE TABLE IF NOT EXISTS leave_settings (
-        guild_id INTEGER PRIMARY KEY,
-        is_enabled INTEGER DEFAULT 0,
-        channel_id INTEGER DEFAULT NULL
-    );")
-    .execute(&pool)
-    .await?;
+    POOL.set(Arc::new(pool)).ok();
+    Ok(())
+}
 
+/// A migrated in-memory database for tests. One connection, kept forever, because each
+/// connection to `sqlite::memory:` would otherwise get a database of its own.
+///
+/// With TEST_DATABASE_URL set to a PostgreSQL database the tests run there instead, in a new
+/// schema each run: test ids start from the same number every time.
+#[cfg(test)]
+pub async fn init_test_pool() -> Result<()> {
+    let pool = match std::env::var("TEST_DATABASE_URL").ok().filter(|u| !u.trim().is_empty()) {
+        Some(url) => {
+            let schema = format!("evexbot_test_{}_{}", chrono::Utc::now().timestamp(), std::process::id());
+            let setup = AnyPool::connect(&url).await?;
+            sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&setup).await?;
+            setup.close().await;
+            sqlx::any::AnyPoolOptions::new()
+                .after_connect(move |conn, _| {
+                    let sql = format!("SET search_path TO {}", schema);
+                    Box::pin(async move { sqlx::query(&sql).execute(conn).await.map(|_| ()) })
+                })
+                .connect(&url).await?
+        }
+        None => sqlx::any::AnyPoolOptions::new().max_connections(1).idle_timeout(None).max_lifetime(None)
+            .connect("sqlite::memory:").await?,
+    };
+    crate::migrations::run(&pool).await?;
     POOL.set(Arc::new(pool)).ok();
     Ok(())
 }
 
-fn pool() -> Arc<SqlitePool> {
+/// DATABASE_URL, defaulting to the bundled SQLite file. A `postgres://` URL runs on PostgreSQL.
+fn database_url() -> Result<String> {
+    let url = std::env::var("DATABASE_URL").ok().filter(|u| !u.trim().is_empty()).unwrap_or_else(|| "sqlite:data/welcome.db".to_string());
+    if !["sqlite:", "postgres:", "postgresql:"].iter().any(|scheme| url.starts_with(scheme)) {
+        anyhow::bail!("DATABASE_URL must be a sqlite: or postgres: URL");
+    }
+    Ok(url)
+}
+
+fn pool() -> Arc<AnyPool> {
     POOL.get().expect("DB pool not initialized").clone()
 }
 
-pub async fn get_welcome_settings(guild_id: i64) -> Result<(bool, i64, Option<i64>)> {
+pub fn backend() -> Backend {
+    backend_of(&pool())
+}
+
+pub(crate) fn backend_of(pool: &AnyPool) -> Backend {
+    match pool.any_kind() {
+        AnyKind::Postgres => Backend::Postgres,
+        _ => Backend::Sqlite,
+    }
+}
+
+pub async fn get_welcome_settings(guild_id: i64) -> Result<WelcomeSettings> {
     let pool = pool();
-    let row = sqlx::query("SELECT is_enabled, member_increment, channel_id FROM welcome_settings WHERE guild_id = ?")
+    let row = sqlx::query_as::<_, WelcomeSettings>("SELECT guild_id, is_enabled, member_increment, channel_id FROM welcome_settings WHERE guild_id = $1")
         .bind(guild_id)
         .fetch_optional(&*pool)
         .await?;
-
-    if let Some(r) = row {
-        Ok((r.get::<i64, _>(0) != 0, r.get::<i64, _>(1), r.try_get::<i64, _>(2).ok()))
-    } else {
-        Ok((false, 100, None))
-    }
+    Ok(row.unwrap_or_else(|| WelcomeSettings::default_for(guild_id)))
 }
 
 pub async fn update_welcome_settings(guild_id: i64, is_enabled: bool, member_increment: Option<i64>, channel_id: Option<i64>) -> Result<()> {
     let pool = pool();
+    welcome_settings_upsert(guild_id, is_enabled, member_increment, channel_id).execute(&*pool).await?;
+    Ok(())
+}
+
+fn welcome_settings_upsert(guild_id: i64, is_enabled: bool, member_increment: Option<i64>, channel_id: Option<i64>) -> AnyQuery {
     sqlx::query("INSERT INTO welcome_settings (guild_id, is_enabled, member_increment, channel_id)
-        VALUES (?, ?, ?, ?)
+        VALUES ($1, $2, $3, $4)
         ON CONFLICT(guild_id) DO UPDATE SET
             is_enabled=excluded.is_enabled,
-            member_increment=COALESCE(?, welcome_settings.member_increment),
-            channel_id=COALESCE(?, welcome_settings.channel_id)")
+            member_increment=COALESCE($5, welcome_settings.member_increment),
+            channel_id=COALESCE($6, welcome_settings.channel_id)")
         .bind(guild_id)
-        .bind(is_enabled as i64)
+        .bind(is_enabled)
         .bind(member_increment.unwrap_or(100))
         .bind(channel_id)
         .bind(member_increment)
         .bind(channel_id)
-        .execute(&*pool)
-        .await?;
-    Ok(())
 }
 
-pub async fn get_leave_settings(guild_id: i64) -> Result<(bool, Option<i64>)> {
+pub async fn get_leave_settings(guild_id: i64) -> Result<LeaveSettings> {
     let pool = pool();
-    let row = sqlx::query("SELECT is_enabled, channel_id FROM leave_settings WHERE guild_id = ?")
+    let row = sqlx::query_as::<_, LeaveSettings>("SELECT guild_id, is_enabled, channel_id FROM leave_settings WHERE guild_id = $1")
         .bind(guild_id)
         .fetch_optional(&*pool)
         .await?;
-
-    if let Some(r) = row {
-        Ok((r.get::<i64, _>(0) != 0, r.try_get::<i64, _>(1).ok()))
-    } else {
-        Ok((false, None))
-    }
+    Ok(row.unwrap_or_else(|| LeaveSettings::default_for(guild_id)))
 }
 
 pub async fn update_leave_settings(guild_id: i64, is_enabled: bool, channel_id: Option<i64>) -> Result<()> {
     let pool = pool();
+    leave_settings_upsert(guild_id, is_enabled, channel_id).execute(&*pool).await?;
+    Ok(())
+}
+
+fn leave_settings_upsert(guild_id: i64, is_enabled: bool, channel_id: Option<i64>) -> AnyQuery {
     sqlx::query("INSERT INTO leave_settings (guild_id, is_enabled, channel_id)
-        VALUES (?, ?, ?)
+        VALUES ($1, $2, $3)
         ON CONFLICT(guild_id) DO UPDATE SET
             is_enabled=excluded.is_enabled,
-            channel_id=COALESCE(?, leave_settings.channel_id)")
+            channel_id=COALESCE($4, leave_settings.channel_id)")
+        .bind(guild_id)
+        .bind(is_enabled)
+        .bind(channel_id)
+        .bind(channel_id)
+}
+
+pub async fn get_modlog_channel(guild_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT channel_id FROM modlog_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.try_get::<i64, _>(0).ok()))
+}
+
+pub async fn update_modlog_channel(guild_id: i64, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    modlog_channel_upsert(guild_id, channel_id).execute(&*pool).await?;
+    Ok(())
+}
+
+fn modlog_channel_upsert(guild_id: i64, channel_id: Option<i64>) -> AnyQuery {
+    sqlx::query("INSERT INTO modlog_settings (guild_id, channel_id) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id")
+        .bind(guild_id)
+        .bind(channel_id)
+}
+
+pub async fn get_message_log_settings(guild_id: i64) -> Result<Option<MessageLogSettings>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, MessageLogSettings>("SELECT guild_id, channel_id, log_edits, log_deletes FROM message_log_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn set_message_log_settings(guild_id: i64, channel_id: i64, log_edits: bool, log_deletes: bool) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO message_log_settings (guild_id, channel_id, log_edits, log_deletes) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id, log_edits=excluded.log_edits, log_deletes=excluded.log_deletes")
         .bind(guild_id)
-        .bind(is_enabled as i64)
         .bind(channel_id)
+        .bind(log_edits)
+        .bind(log_deletes)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_message_log_settings(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM message_log_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_joingate_settings(guild_id: i64) -> Result<JoinGateSettings> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, JoinGateSettings>("SELECT min_account_age_days, autorole_id FROM joingate_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or_default())
+}
+
+pub async fn update_joingate_settings(guild_id: i64, min_account_age_days: Option<i64>, autorole_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO joingate_settings (guild_id, min_account_age_days, autorole_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id) DO UPDATE SET
+            min_account_age_days=COALESCE($4, joingate_settings.min_account_age_days),
+            autorole_id=COALESCE($5, joingate_settings.autorole_id)")
+        .bind(guild_id)
+        .bind(min_account_age_days.unwrap_or(0))
+        .bind(autorole_id)
+        .bind(min_account_age_days)
+        .bind(autorole_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn clear_joingate_autorole(guild_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE joingate_settings SET autorole_id = NULL WHERE guild_id = $1")
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_pending_autorole(guild_id: i64, user_id: i64, eligible_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO pending_autoroles (guild_id, user_id, eligible_at) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id, user_id) DO UPDATE SET eligible_at=excluded.eligible_at")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(eligible_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_pending_autorole(guild_id: i64, user_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("DELETE FROM pending_autoroles WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id)
+        .bind(user_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (guild_id, user_id) pairs whose account age now satisfies the gate.
+pub async fn get_due_pending_autoroles(now: i64) -> Result<Vec<(i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT guild_id, user_id FROM pending_autoroles WHERE eligible_at <= $1")
+        .bind(now)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+pub async fn record_emoji_use(guild_id: i64, emoji_id: i64, used_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO emoji_usage (guild_id, emoji_id, uses, last_used) VALUES ($1, $2, 1, $3)
+        ON CONFLICT(guild_id, emoji_id) DO UPDATE SET uses=emoji_usage.uses + 1, last_used=excluded.last_used")
+        .bind(guild_id)
+        .bind(emoji_id)
+        .bind(used_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (emoji_id, uses, last_used) for every tracked emoji in the guild.
+pub async fn get_emoji_usage(guild_id: i64) -> Result<Vec<(i64, i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT emoji_id, uses, last_used FROM emoji_usage WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1), r.get::<i64, _>(2))).collect())
+}
+
+pub async fn get_tempvoice_hub(guild_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT hub_channel_id FROM tempvoice_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.try_get::<i64, _>(0).ok()))
+}
+
+pub async fn update_tempvoice_hub(guild_id: i64, hub_channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO tempvoice_settings (guild_id, hub_channel_id) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET hub_channel_id=excluded.hub_channel_id")
+        .bind(guild_id)
+        .bind(hub_channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_temp_voice_channel(channel_id: i64, guild_id: i64, owner_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO temp_voice_channels (channel_id, guild_id, owner_id) VALUES ($1, $2, $3)
+        ON CONFLICT(channel_id) DO UPDATE SET guild_id=excluded.guild_id, owner_id=excluded.owner_id")
         .bind(channel_id)
+        .bind(guild_id)
+        .bind(owner_id)
         .execute(&*pool)
         .await?;
     Ok(())
 }
+
+/// Returns the owner of a temporary voice channel, or None if the channel is not temporary.
+pub async fn get_temp_voice_owner(channel_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT owner_id FROM temp_voice_channels WHERE channel_id = $1")
+        .bind(channel_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<i64, _>(0)))
+}
+
+pub async fn remove_temp_voice_channel(channel_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("DELETE FROM temp_voice_channels WHERE channel_id = $1")
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn record_message_activity(guild_id: i64, channel_id: i64, user_id: i64, day: &str, hour: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO message_activity (guild_id, channel_id, user_id, day, hour, count) VALUES ($1, $2, $3, $4, $5, 1)
+        ON CONFLICT(guild_id, channel_id, user_id, day, hour) DO UPDATE SET count=message_activity.count + 1")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(user_id)
+        .bind(day)
+        .bind(hour)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (channel_id, messages) for the busiest channels between two days (inclusive, YYYY-MM-DD).
+pub async fn get_top_channels(guild_id: i64, since: &str, until: &str, limit: i64) -> Result<Vec<(i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT channel_id, CAST(SUM(count) AS BIGINT) AS total FROM message_activity
+        WHERE guild_id = $1 AND day >= $2 AND day <= $3 GROUP BY channel_id ORDER BY total DESC LIMIT $4")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .bind(limit)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Returns (user_id, messages) for the most active members between two days (inclusive).
+pub async fn get_top_users(guild_id: i64, since: &str, until: &str, limit: i64) -> Result<Vec<(i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT user_id, CAST(SUM(count) AS BIGINT) AS total FROM message_activity
+        WHERE guild_id = $1 AND day >= $2 AND day <= $3 GROUP BY user_id ORDER BY total DESC LIMIT $4")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .bind(limit)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Returns (UTC hour, messages) between two days (inclusive), for hours with any activity.
+pub async fn get_hourly_activity(guild_id: i64, since: &str, until: &str) -> Result<Vec<(i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT hour, CAST(SUM(count) AS BIGINT) FROM message_activity
+        WHERE guild_id = $1 AND day >= $2 AND day <= $3 GROUP BY hour")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Number of distinct members who posted in a channel between two days (inclusive).
+pub async fn count_channel_posters(guild_id: i64, channel_id: i64, since: &str, until: &str) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("SELECT COUNT(DISTINCT user_id) FROM message_activity
+        WHERE guild_id = $1 AND channel_id = $2 AND day >= $3 AND day <= $4")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(since)
+        .bind(until)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn get_digest_settings(guild_id: i64) -> Result<(Option<i64>, Option<String>)> {
+    let pool = pool();
+    let row = sqlx::query("SELECT channel_id, last_sent_month FROM digest_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+
+    if let Some(r) = row {
+        Ok((r.try_get::<i64, _>(0).ok(), r.try_get::<String, _>(1).ok()))
+    } else {
+        Ok((None, None))
+    }
+}
+
+/// Returns every guild with a digest channel configured as (guild_id, channel_id, last_sent_month).
+pub async fn get_enabled_digests() -> Result<Vec<(i64, i64, Option<String>)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT guild_id, channel_id, last_sent_month FROM digest_settings WHERE channel_id IS NOT NULL")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1), r.try_get::<String, _>(2).ok())).collect())
+}
+
+pub async fn update_digest_channel(guild_id: i64, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO digest_settings (guild_id, channel_id) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id")
+        .bind(guild_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn mark_digest_sent(guild_id: i64, month: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE digest_settings SET last_sent_month = $1 WHERE guild_id = $2")
+        .bind(month)
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_verify_settings(guild_id: i64) -> Result<(Option<i64>, Option<String>)> {
+    let pool = pool();
+    let row = sqlx::query("SELECT role_id, email_domain FROM verify_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+
+    if let Some(r) = row {
+        Ok((r.try_get::<i64, _>(0).ok(), r.try_get::<String, _>(1).ok()))
+    } else {
+        Ok((None, None))
+    }
+}
+
+pub async fn update_verify_settings(guild_id: i64, role_id: Option<i64>, email_domain: Option<String>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO verify_settings (guild_id, role_id, email_domain) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id) DO UPDATE SET role_id=excluded.role_id, email_domain=excluded.email_domain")
+        .bind(guild_id)
+        .bind(role_id)
+        .bind(email_domain)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn create_verify_state(state: &str, guild_id: i64, user_id: i64, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO verify_states (state, guild_id, user_id, created_at) VALUES ($1, $2, $3, $4)")
+        .bind(state)
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Consume a verification state, returning (guild_id, user_id, created_at). Each state is single-use.
+pub async fn take_verify_state(state: &str) -> Result<Option<(i64, i64, i64)>> {
+    let pool = pool();
+    let row = sqlx::query("DELETE FROM verify_states WHERE state = $1 RETURNING guild_id, user_id, created_at")
+        .bind(state)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1), r.get::<i64, _>(2))))
+}
+
+pub async fn upsert_api_key(guild_id: i64, provider: &str, nonce: &[u8], ciphertext: &[u8], updated_by: i64, updated_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO guild_api_keys (guild_id, provider, nonce, ciphertext, updated_by, updated_at) VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT(guild_id, provider) DO UPDATE SET
+            nonce=excluded.nonce, ciphertext=excluded.ciphertext, updated_by=excluded.updated_by, updated_at=excluded.updated_at")
+        .bind(guild_id)
+        .bind(provider)
+        .bind(nonce)
+        .bind(ciphertext)
+        .bind(updated_by)
+        .bind(updated_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (nonce, ciphertext) for a stored key.
+pub async fn get_api_key(guild_id: i64, provider: &str) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT nonce, ciphertext FROM guild_api_keys WHERE guild_id = $1 AND provider = $2")
+        .bind(guild_id)
+        .bind(provider)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| (r.get::<Vec<u8>, _>(0), r.get::<Vec<u8>, _>(1))))
+}
+
+/// Returns (provider, updated_by, updated_at) for every key stored for the guild.
+pub async fn list_api_keys(guild_id: i64) -> Result<Vec<(String, i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT provider, updated_by, updated_at FROM guild_api_keys WHERE guild_id = $1 ORDER BY provider")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<String, _>(0), r.get::<i64, _>(1), r.get::<i64, _>(2))).collect())
+}
+
+pub async fn delete_api_key(guild_id: i64, provider: &str) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM guild_api_keys WHERE guild_id = $1 AND provider = $2")
+        .bind(guild_id)
+        .bind(provider)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Returns (mode, allowlist) where allowlist is a comma-separated list of invite codes or guild IDs.
+pub async fn get_invite_filter_settings(guild_id: i64) -> Result<(String, String)> {
+    let pool = pool();
+    let row = sqlx::query("SELECT mode, allowlist FROM invite_filter_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+
+    if let Some(r) = row {
+        Ok((r.get::<String, _>(0), r.get::<String, _>(1)))
+    } else {
+        Ok(("off".to_string(), String::new()))
+    }
+}
+
+pub async fn update_invite_filter_settings(guild_id: i64, mode: &str, allowlist: Option<&str>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO invite_filter_settings (guild_id, mode, allowlist) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id) DO UPDATE SET
+            mode=excluded.mode,
+            allowlist=COALESCE($4, invite_filter_settings.allowlist)")
+        .bind(guild_id)
+        .bind(mode)
+        .bind(allowlist.unwrap_or(""))
+        .bind(allowlist)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_tracked_invite(code: &str, guild_id: i64, channel_id: i64, creator_id: i64, reason: &str, max_uses: i64, max_age: i64, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO tracked_invites (code, guild_id, channel_id, creator_id, reason, max_uses, max_age, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
+        .bind(code)
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(creator_id)
+        .bind(reason)
+        .bind(max_uses)
+        .bind(max_age)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_tracked_invite_codes(guild_id: i64) -> Result<Vec<String>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT code FROM tracked_invites WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<String, _>(0)).collect())
+}
+
+pub async fn record_invite_join(guild_id: i64, code: &str, user_id: i64, joined_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO invite_joins (guild_id, code, user_id, joined_at) VALUES ($1, $2, $3, $4)")
+        .bind(guild_id)
+        .bind(code)
+        .bind(user_id)
+        .bind(joined_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn mark_invite_member_left(guild_id: i64, user_id: i64, left_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE invite_joins SET left_at = $1 WHERE guild_id = $2 AND user_id = $3 AND left_at IS NULL")
+        .bind(left_at)
+        .bind(guild_id)
+        .bind(user_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Every tracked invite with its join counts, newest first.
+pub async fn get_invite_stats(guild_id: i64) -> Result<Vec<InviteStats>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, InviteStats>("SELECT t.code, t.reason, t.creator_id, t.created_at,
+            COUNT(j.user_id) AS joins, COALESCE(SUM(CASE WHEN j.user_id IS NOT NULL AND j.left_at IS NULL THEN 1 ELSE 0 END), 0) AS still_present
+        FROM tracked_invites t LEFT JOIN invite_joins j ON j.code = t.code AND j.guild_id = t.guild_id
+        WHERE t.guild_id = $1 GROUP BY t.code ORDER BY t.created_at DESC")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_milestone_announce_channel(guild_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT channel_id FROM milestone_announce_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.try_get::<i64, _>(0).ok()))
+}
+
+pub async fn update_milestone_announce_channel(guild_id: i64, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO milestone_announce_settings (guild_id, channel_id) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id")
+        .bind(guild_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+fn cell_to_string(row: &AnyRow, idx: usize) -> String {
+    if row.try_get_raw(idx).map(|v| v.is_null()).unwrap_or(false) { return "NULL".to_string(); }
+    if let Ok(v) = row.try_get::<i64, _>(idx) { return v.to_string(); }
+    if let Ok(v) = row.try_get::<i32, _>(idx) { return v.to_string(); }
+    if let Ok(v) = row.try_get::<f64, _>(idx) { return v.to_string(); }
+    if let Ok(v) = row.try_get::<bool, _>(idx) { return v.to_string(); }
+    if let Ok(v) = row.try_get::<String, _>(idx) { return v; }
+    if let Ok(v) = row.try_get::<Vec<u8>, _>(idx) { return format!("<{} bytes>", v.len()); }
+    "?".to_string()
+}
+
+/// Run a single statement so that writes fail in the database itself: with `PRAGMA query_only`
+/// on SQLite, in a read-only transaction that is rolled back on PostgreSQL.
+/// Returns (column names, rows as strings), limited to `limit` rows.
+pub async fn run_readonly_query(sql: &str, limit: i64) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let pool = pool();
+    let wrapped = format!("SELECT * FROM ({}) AS q LIMIT $1", sql);
+    let rows = match backend_of(&pool) {
+        Backend::Sqlite => {
+            let mut conn = pool.acquire().await?;
+            sqlx::query("PRAGMA query_only = ON").execute(&mut *conn).await?;
+            let result = sqlx::query(&wrapped).bind(limit).fetch_all(&mut *conn).await;
+            sqlx::query("PRAGMA query_only = OFF").execute(&mut *conn).await?;
+            result?
+        }
+        Backend::Postgres => {
+            let mut tx = pool.begin().await?;
+            sqlx::query("SET TRANSACTION READ ONLY").execute(&mut tx).await?;
+            let result = sqlx::query(&wrapped).bind(limit).fetch_all(&mut tx).await;
+            tx.rollback().await?;
+            result?
+        }
+    };
+
+    let columns = rows.first().map(|r| r.columns().iter().map(|c| c.name().to_string()).collect()).unwrap_or_default();
+    let values = rows.iter().map(|r| (0..r.len()).map(|i| cell_to_string(r, i)).collect()).collect();
+    Ok((columns, values))
+}
+
+pub async fn record_dbquery_audit(user_id: i64, query: &str, row_count: Option<i64>, error: Option<&str>, executed_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO dbquery_audit (user_id, query, row_count, error, executed_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(user_id)
+        .bind(query)
+        .bind(row_count)
+        .bind(error)
+        .bind(executed_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Insert daily member counts. Existing rows are kept unless `overwrite` is set.
+/// Returns the number of rows written.
+pub async fn insert_member_snapshots(guild_id: i64, snapshots: &[(String, i64)], source: &str, overwrite: bool) -> Result<u64> {
+    let pool = pool();
+    let sql = if overwrite {
+        "INSERT INTO member_snapshots (guild_id, day, member_count, source) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id, day) DO UPDATE SET member_count = excluded.member_count, source = excluded.source"
+    } else {
+        "INSERT INTO member_snapshots (guild_id, day, member_count, source) VALUES ($1, $2, $3, $4) ON CONFLICT(guild_id, day) DO NOTHING"
+    };
+    let mut tx = pool.begin().await?;
+    let mut written = 0;
+    for (day, count) in snapshots {
+        written += sqlx::query(sql)
+            .bind(guild_id)
+            .bind(day)
+            .bind(count)
+            .bind(source)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(written)
+}
+
+/// Returns (joined_at, left_at) for tracked-invite joins whose member has since left.
+pub async fn get_departed_invite_joins(guild_id: i64) -> Result<Vec<(i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT joined_at, left_at FROM invite_joins WHERE guild_id = $1 AND left_at IS NOT NULL")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+pub async fn get_intro_auto_thread(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT auto_thread FROM intro_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<bool, _>(0)).unwrap_or(false))
+}
+
+pub async fn update_intro_auto_thread(guild_id: i64, enabled: bool) -> Result<()> {
+    let pool = pool();
+    intro_auto_thread_upsert(guild_id, enabled).execute(&*pool).await?;
+    Ok(())
+}
+
+fn intro_auto_thread_upsert(guild_id: i64, enabled: bool) -> AnyQuery {
+    sqlx::query("INSERT INTO intro_settings (guild_id, auto_thread) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET auto_thread=excluded.auto_thread")
+        .bind(guild_id)
+        .bind(enabled)
+}
+
+pub async fn get_intro_channel(guild_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT intro_channel_id FROM intro_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.try_get::<Option<i64>, _>(0).ok().flatten()))
+}
+
+pub async fn update_intro_channel(guild_id: i64, channel_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO intro_settings (guild_id, intro_channel_id) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET intro_channel_id=excluded.intro_channel_id")
+        .bind(guild_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// NULL means the default ✅, an empty string means no reaction.
+pub async fn get_intro_react_emoji(guild_id: i64) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT react_emoji FROM intro_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.try_get::<Option<String>, _>(0).ok().flatten()))
+}
+
+pub async fn update_intro_react_emoji(guild_id: i64, emoji: Option<&str>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO intro_settings (guild_id, react_emoji) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET react_emoji=excluded.react_emoji")
+        .bind(guild_id)
+        .bind(emoji)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_intro_template_lock(guild_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT template_locked_by FROM intro_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.try_get::<i64, _>(0).ok()))
+}
+
+pub async fn update_intro_template_lock(guild_id: i64, locked_by: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO intro_settings (guild_id, template_locked_by) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET template_locked_by=excluded.template_locked_by")
+        .bind(guild_id)
+        .bind(locked_by)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Store a new template version and return its number.
+pub async fn add_intro_template_version(guild_id: i64, content: &str, author_id: i64, created_at: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO intro_templates (guild_id, version, content, author_id, created_at)
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4 FROM intro_templates WHERE guild_id = $5
+        RETURNING version")
+        .bind(guild_id)
+        .bind(content)
+        .bind(author_id)
+        .bind(created_at)
+        .bind(guild_id)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn get_current_intro_template(guild_id: i64) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT content FROM intro_templates WHERE guild_id = $1 ORDER BY version DESC LIMIT 1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<String, _>(0)))
+}
+
+pub async fn get_intro_template_version(guild_id: i64, version: i64) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT content FROM intro_templates WHERE guild_id = $1 AND version = $2")
+        .bind(guild_id)
+        .bind(version)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<String, _>(0)))
+}
+
+/// Returns (version, content, author_id, created_at), newest first.
+pub async fn get_intro_template_versions(guild_id: i64) -> Result<Vec<(i64, String, i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT version, content, author_id, created_at FROM intro_templates WHERE guild_id = $1 ORDER BY version DESC")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<String, _>(1), r.get::<i64, _>(2), r.get::<i64, _>(3))).collect())
+}
+
+/// Record a member's first intro; later posts by the same member are ignored.
+pub async fn record_intro(guild_id: i64, user_id: i64, message_id: i64, posted_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO intros (guild_id, user_id, message_id, posted_at) VALUES ($1, $2, $3, $4) ON CONFLICT(guild_id, user_id) DO NOTHING")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(message_id)
+        .bind(posted_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (user_id, posted_at) of every recorded intro, oldest first.
+pub async fn get_intros(guild_id: i64) -> Result<Vec<(i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT user_id, posted_at FROM intros WHERE guild_id = $1 ORDER BY posted_at")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Insert or replace imported daily counts; days the bot recorded live are kept.
+/// Returns the number of rows written.
+pub async fn import_member_snapshots(guild_id: i64, snapshots: &[(String, i64)], source: &str) -> Result<u64> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    let mut written = 0;
+    for (day, count) in snapshots {
+        written += sqlx::query("INSERT INTO member_snapshots (guild_id, day, member_count, source) VALUES ($1, $2, $3, $4)
+            ON CONFLICT(guild_id, day) DO UPDATE SET member_count = excluded.member_count, source = excluded.source
+            WHERE member_snapshots.source != 'live'")
+            .bind(guild_id)
+            .bind(day)
+            .bind(count)
+            .bind(source)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(written)
+}
+
+/// Returns (day, member_count) between two days (inclusive), oldest first.
+pub async fn get_member_snapshots(guild_id: i64, since: &str, until: &str) -> Result<Vec<(String, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT day, member_count FROM member_snapshots WHERE guild_id = $1 AND day >= $2 AND day <= $3 ORDER BY day")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<String, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Whether the user opted out of having their messages copied (link expansion, bookmarks).
+pub async fn get_privacy_enabled(user_id: i64) -> Result<bool> {
+    Ok(get_user_prefs(user_id).await?.quote_privacy)
+}
+
+pub async fn update_privacy_enabled(user_id: i64, enabled: bool) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO user_prefs (user_id, quote_privacy) VALUES ($1, $2)
+        ON CONFLICT(user_id) DO UPDATE SET quote_privacy=excluded.quote_privacy")
+        .bind(user_id)
+        .bind(enabled)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_channel_topic(guild_id: i64, channel_id: i64, text: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO channel_topics (guild_id, channel_id, text) VALUES ($1, $2, $3)")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(text)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (id, text) of a channel's topics in insertion order.
+pub async fn get_channel_topics(channel_id: i64) -> Result<Vec<(i64, String)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT id, text FROM channel_topics WHERE channel_id = $1 ORDER BY id")
+        .bind(channel_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<String, _>(1))).collect())
+}
+
+pub async fn remove_channel_topic(channel_id: i64, id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM channel_topics WHERE channel_id = $1 AND id = $2")
+        .bind(channel_id)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Returns (channel_id, next_index, last_rotated) for every channel with at least one topic.
+pub async fn get_topic_rotations() -> Result<Vec<(i64, i64, Option<String>)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT t.channel_id, COALESCE(s.next_index, 0), s.last_rotated
+        FROM (SELECT DISTINCT channel_id FROM channel_topics) t LEFT JOIN topic_rotation_state s ON s.channel_id = t.channel_id")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1), r.try_get::<String, _>(2).ok())).collect())
+}
+
+pub async fn mark_topic_rotated(channel_id: i64, next_index: i64, day: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO topic_rotation_state (channel_id, next_index, last_rotated) VALUES ($1, $2, $3)
+        ON CONFLICT(channel_id) DO UPDATE SET next_index=excluded.next_index, last_rotated=excluded.last_rotated")
+        .bind(channel_id)
+        .bind(next_index)
+        .bind(day)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_event(guild_id: i64, name: &str, starts_at: i64, ends_at: i64, created_by: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO events (guild_id, name, starts_at, ends_at, created_by) VALUES ($1, $2, $3, $4, $5) RETURNING id")
+        .bind(guild_id)
+        .bind(name)
+        .bind(starts_at)
+        .bind(ends_at)
+        .bind(created_by)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+/// Returns (id, name, starts_at, ends_at) of events that have not ended yet.
+pub async fn get_upcoming_events(guild_id: i64, now: i64) -> Result<Vec<(i64, String, i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT id, name, starts_at, ends_at FROM events WHERE guild_id = $1 AND ends_at >= $2 ORDER BY starts_at")
+        .bind(guild_id)
+        .bind(now)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<String, _>(1), r.get::<i64, _>(2), r.get::<i64, _>(3))).collect())
+}
+
+pub async fn remove_event(guild_id: i64, id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM events WHERE guild_id = $1 AND id = $2")
+        .bind(guild_id)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    if res.rows_affected() > 0 {
+        sqlx::query("DELETE FROM event_voice_announcements WHERE event_id = $1").bind(id).execute(&*pool).await?;
+    }
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn is_event_active(guild_id: i64, now: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT 1 FROM events WHERE guild_id = $1 AND starts_at <= $2 AND ends_at >= $3 LIMIT 1")
+        .bind(guild_id)
+        .bind(now)
+        .bind(now)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+pub async fn add_batched_join(guild_id: i64, user_id: i64, joined_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO batched_joins (guild_id, user_id, joined_at) VALUES ($1, $2, $3)")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(joined_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove and return every batched join as (guild_id, user_id).
+pub async fn take_batched_joins() -> Result<Vec<(i64, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("DELETE FROM batched_joins RETURNING guild_id, user_id")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Replace the guild's API token. Only the SHA-256 hash is stored.
+pub async fn set_api_token_hash(guild_id: i64, token_hash: &str, created_by: i64, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO api_tokens (guild_id, token_hash, created_by, created_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id) DO UPDATE SET token_hash=excluded.token_hash, created_by=excluded.created_by, created_at=excluded.created_at")
+        .bind(guild_id)
+        .bind(token_hash)
+        .bind(created_by)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_api_token_hash(guild_id: i64) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT token_hash FROM api_tokens WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<String, _>(0)))
+}
+
+pub async fn delete_api_token(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM api_tokens WHERE guild_id = $1")
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn upsert_external_metric(guild_id: i64, source: &str, day: &str, value: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO external_metrics (guild_id, source, day, value) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id, source, day) DO UPDATE SET value=excluded.value")
+        .bind(guild_id)
+        .bind(source)
+        .bind(day)
+        .bind(value)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (source, day, value) recorded between two days (inclusive), ordered by source and day.
+pub async fn get_external_metrics(guild_id: i64, since: &str, until: &str) -> Result<Vec<(String, String, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT source, day, value FROM external_metrics WHERE guild_id = $1 AND day >= $2 AND day <= $3 ORDER BY source, day")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<String, _>(0), r.get::<String, _>(1), r.get::<i64, _>(2))).collect())
+}
+
+pub async fn add_growth_notification(guild_id: i64, channel_id: i64, user_id: i64, role_id: Option<i64>, target: i64, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO growth_notifications (guild_id, channel_id, user_id, role_id, target, created_at) VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(user_id)
+        .bind(role_id)
+        .bind(target)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove and return the notifications whose target was reached.
+pub async fn take_reached_growth_notifications(guild_id: i64, member_count: i64) -> Result<Vec<GrowthNotification>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, GrowthNotification>("DELETE FROM growth_notifications WHERE guild_id = $1 AND target <= $2
+        RETURNING channel_id, user_id, role_id, target, created_at")
+        .bind(guild_id)
+        .bind(member_count)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Record a join, leave, kick or ban. `flagged` marks joins from notably young accounts.
+pub async fn record_member_event(guild_id: i64, user_id: i64, kind: &str, flagged: bool, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO member_events (guild_id, user_id, kind, flagged, created_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(kind)
+        .bind(flagged)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (kind, count) of member events in [since, until). Leaves caused by a kick or ban are not counted as leaves.
+pub async fn count_member_events(guild_id: i64, since: i64, until: i64) -> Result<Vec<(String, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT kind, COUNT(*) FROM member_events e
+        WHERE guild_id = $1 AND created_at >= $2 AND created_at < $3
+        AND NOT (kind = 'leave' AND EXISTS (SELECT 1 FROM member_events m WHERE m.guild_id = e.guild_id AND m.user_id = e.user_id
+            AND m.kind IN ('kick', 'ban') AND m.created_at BETWEEN e.created_at - 60 AND e.created_at + 60))
+        GROUP BY kind")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<String, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Returns user ids of flagged joins in [since, until).
+pub async fn get_flagged_joins(guild_id: i64, since: i64, until: i64) -> Result<Vec<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT user_id FROM member_events WHERE guild_id = $1 AND kind = 'join' AND flagged = TRUE AND created_at >= $2 AND created_at < $3 ORDER BY created_at")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<i64, _>(0)).collect())
+}
+
+pub async fn get_daily_summary_enabled(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT is_enabled FROM daily_summary_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<bool, _>(0)).unwrap_or(false))
+}
+
+pub async fn update_daily_summary_enabled(guild_id: i64, enabled: bool) -> Result<()> {
+    let pool = pool();
+    daily_summary_enabled_upsert(guild_id, enabled).execute(&*pool).await?;
+    Ok(())
+}
+
+fn daily_summary_enabled_upsert(guild_id: i64, enabled: bool) -> AnyQuery {
+    sqlx::query("INSERT INTO daily_summary_settings (guild_id, is_enabled) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET is_enabled=excluded.is_enabled")
+        .bind(guild_id)
+        .bind(enabled)
+}
+
+/// Write everything `/setup` collects in one transaction, so a failed save leaves the old
+/// settings in place rather than half of the new ones.
+pub async fn save_setup(guild_id: i64, welcome_channel: Option<i64>, member_increment: i64, leave_channel: Option<i64>, intro_auto_thread: bool, modlog_channel: Option<i64>, daily_summary: bool) -> Result<()> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    welcome_settings_upsert(guild_id, welcome_channel.is_some(), Some(member_increment), welcome_channel).execute(&mut tx).await?;
+    leave_settings_upsert(guild_id, leave_channel.is_some(), leave_channel).execute(&mut tx).await?;
+    intro_auto_thread_upsert(guild_id, intro_auto_thread).execute(&mut tx).await?;
+    modlog_channel_upsert(guild_id, modlog_channel).execute(&mut tx).await?;
+    daily_summary_enabled_upsert(guild_id, daily_summary).execute(&mut tx).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Returns (guild_id, last_sent_day) for guilds with the daily summary enabled.
+pub async fn get_enabled_daily_summaries() -> Result<Vec<(i64, Option<String>)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT guild_id, last_sent_day FROM daily_summary_settings WHERE is_enabled = TRUE")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<i64, _>(0), r.try_get::<String, _>(1).ok())).collect())
+}
+
+pub async fn mark_daily_summary_sent(guild_id: i64, day: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE daily_summary_settings SET last_sent_day = $1 WHERE guild_id = $2")
+        .bind(day)
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_chart_size(guild_id: i64) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT size FROM chart_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.try_get::<String, _>(0).ok()))
+}
+
+pub async fn update_chart_size(guild_id: i64, size: Option<&str>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO chart_settings (guild_id, size) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET size=excluded.size")
+        .bind(guild_id)
+        .bind(size)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (text, logo URL) of the guild's chart watermark.
+pub async fn get_chart_watermark(guild_id: i64) -> Result<(Option<String>, Option<String>)> {
+    let pool = pool();
+    let row = sqlx::query("SELECT watermark_text, watermark_logo_url FROM chart_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| (r.try_get::<Option<String>, _>(0).ok().flatten(), r.try_get::<Option<String>, _>(1).ok().flatten())).unwrap_or((None, None)))
+}
+
+pub async fn update_chart_watermark(guild_id: i64, text: Option<&str>, logo_url: Option<&str>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO chart_settings (guild_id, watermark_text, watermark_logo_url) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id) DO UPDATE SET watermark_text=excluded.watermark_text, watermark_logo_url=excluded.watermark_logo_url")
+        .bind(guild_id)
+        .bind(text)
+        .bind(logo_url)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_guild_config(guild_id: i64) -> Result<GuildConfig> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, GuildConfig>("SELECT guild_id, COALESCE(language, 'ja') AS language FROM guild_config WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or_else(|| GuildConfig::default_for(guild_id)))
+}
+
+pub async fn update_guild_language(guild_id: i64, language: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO guild_config (guild_id, language) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET language=excluded.language")
+        .bind(guild_id)
+        .bind(language)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_automod_monitor_only(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT monitor_only FROM automod_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<bool, _>(0)).unwrap_or(false))
+}
+
+pub async fn update_automod_monitor_only(guild_id: i64, enabled: bool) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO automod_settings (guild_id, monitor_only) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET monitor_only=excluded.monitor_only")
+        .bind(guild_id)
+        .bind(enabled)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Open a moderation case. `moderator_id` is None when the audit log didn't say who acted.
+pub async fn create_mod_case(guild_id: i64, moderator_id: Option<i64>, target_id: i64, action: &str, reason: Option<&str>, created_at: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO mod_cases (guild_id, moderator_id, target_id, action, reason, created_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id")
+        .bind(guild_id)
+        .bind(moderator_id)
+        .bind(target_id)
+        .bind(action)
+        .bind(reason)
+        .bind(created_at)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn has_recent_mod_case(guild_id: i64, target_id: i64, action: &str, since: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT 1 FROM mod_cases WHERE guild_id = $1 AND target_id = $2 AND action = $3 AND created_at >= $4 LIMIT 1")
+        .bind(guild_id)
+        .bind(target_id)
+        .bind(action)
+        .bind(since)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+/// A case by id, from any guild; appeal buttons and modals only carry the id.
+pub async fn get_mod_case(id: i64) -> Result<Option<ModCase>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, ModCase>("SELECT id, guild_id, moderator_id, target_id, action, reason, created_at, appeal_status FROM mod_cases WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+/// Every column `/case` shows, for a case in `guild_id`.
+pub async fn get_mod_case_detail(guild_id: i64, id: i64) -> Result<Option<ModCase>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, ModCase>("SELECT id, guild_id, moderator_id, target_id, action, reason, created_at, appeal_status FROM mod_cases WHERE guild_id = $1 AND id = $2")
+        .bind(guild_id)
+        .bind(id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+/// A member's cases in a guild, newest first.
+pub async fn get_mod_cases_for(guild_id: i64, target_id: i64) -> Result<Vec<ModCase>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, ModCase>("SELECT id, guild_id, moderator_id, target_id, action, reason, created_at, appeal_status FROM mod_cases WHERE guild_id = $1 AND target_id = $2 ORDER BY id DESC")
+        .bind(guild_id)
+        .bind(target_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Returns (action, count) for cases opened in [since, until).
+pub async fn count_mod_cases(guild_id: i64, since: i64, until: i64) -> Result<Vec<(String, i64)>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT action, COUNT(*) FROM mod_cases WHERE guild_id = $1 AND created_at >= $2 AND created_at < $3 GROUP BY action")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| (r.get::<String, _>(0), r.get::<i64, _>(1))).collect())
+}
+
+/// Drop a case whose action failed, so the log only holds what actually happened.
+pub async fn delete_mod_case(id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM mod_cases WHERE id = $1")
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Record a submitted appeal; returns false when the case already has one.
+pub async fn submit_mod_case_appeal(id: i64, text: &str) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("UPDATE mod_cases SET appeal_status = 'pending', appeal_text = $1 WHERE id = $2 AND appeal_status IS NULL")
+        .bind(text)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Resolve a pending appeal as 'accepted' or 'denied'; returns false when it wasn't pending.
+pub async fn resolve_mod_case_appeal(id: i64, status: &str, resolved_by: i64, resolved_at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("UPDATE mod_cases SET appeal_status = $1, resolved_by = $2, resolved_at = $3 WHERE id = $4 AND appeal_status = 'pending'")
+        .bind(status)
+        .bind(resolved_by)
+        .bind(resolved_at)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Delete rows of `table` whose `column` is older than `cutoff`, for one guild or, with
+/// `guild_id` None, for every guild without a retention override for `data_type`.
+/// Table and column names come from retention's static policies, never from user input.
+pub async fn purge_before<T>(table: &str, column: &str, data_type: &str, guild_id: Option<i64>, cutoff: T) -> Result<u64>
+where
+    T: for<'q> sqlx::Encode<'q, Any> + sqlx::Type<Any> + Send,
+{
+    let pool = pool();
+    let res = match guild_id {
+        Some(g) => {
+            let sql = format!("DELETE FROM {} WHERE guild_id = $1 AND {} < $2", table, column);
+            sqlx::query(&sql).bind(g).bind(cutoff).execute(&*pool).await?
+        }
+        None => {
+            let sql = format!("DELETE FROM {} WHERE {} < $1 AND guild_id NOT IN (SELECT guild_id FROM retention_settings WHERE data_type = $2)", table, column);
+            sqlx::query(&sql).bind(cutoff).bind(data_type).execute(&*pool).await?
+        }
+    };
+    Ok(res.rows_affected())
+}
+
+/// Delete rows older than `cutoff` (unix seconds) from a table that isn't per guild.
+pub async fn purge_global_before(table: &str, column: &str, cutoff: i64) -> Result<u64> {
+    let pool = pool();
+    let sql = format!("DELETE FROM {} WHERE {} < $1", table, column);
+    let res = sqlx::query(&sql).bind(cutoff).execute(&*pool).await?;
+    Ok(res.rows_affected())
+}
+
+/// Guilds that override the default for `data_type`.
+pub async fn get_retention_overrides(data_type: &str) -> Result<Vec<RetentionOverride>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, RetentionOverride>("SELECT guild_id, data_type, days FROM retention_settings WHERE data_type = $1")
+        .bind(data_type)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Every override for one guild.
+pub async fn get_guild_retention(guild_id: i64) -> Result<Vec<RetentionOverride>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, RetentionOverride>("SELECT guild_id, data_type, days FROM retention_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// `days` None removes the override.
+pub async fn set_guild_retention(guild_id: i64, data_type: &str, days: Option<i64>) -> Result<()> {
+    let pool = pool();
+    match days {
+        Some(d) => {
+            sqlx::query("INSERT INTO retention_settings (guild_id, data_type, days) VALUES ($1, $2, $3)
+                ON CONFLICT(guild_id, data_type) DO UPDATE SET days=excluded.days")
+                .bind(guild_id)
+                .bind(data_type)
+                .bind(d)
+                .execute(&*pool)
+                .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM retention_settings WHERE guild_id = $1 AND data_type = $2")
+                .bind(guild_id)
+                .bind(data_type)
+                .execute(&*pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Take or renew the lease `name` for `holder`. Succeeds when the lease is free, expired,
+/// or already held by `holder`; returns whether `holder` owns it afterwards.
+pub async fn try_acquire_lease(name: &str, holder: &str, now: i64, ttl_seconds: i64) -> Result<bool> {
+    let pool = pool();
+    sqlx::query("INSERT INTO leader_lease (name, holder, expires_at) VALUES ($1, $2, $3)
+        ON CONFLICT(name) DO UPDATE SET holder=excluded.holder, expires_at=excluded.expires_at
+        WHERE leader_lease.holder = excluded.holder OR leader_lease.expires_at < $4")
+        .bind(name)
+        .bind(holder)
+        .bind(now + ttl_seconds)
+        .bind(now)
+        .execute(&*pool)
+        .await?;
+    let row = sqlx::query("SELECT holder FROM leader_lease WHERE name = $1")
+        .bind(name)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<String, _>(0) == holder).unwrap_or(false))
+}
+
+/// Give up the lease so a follower can take over without waiting for it to expire.
+pub async fn release_lease(name: &str, holder: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("DELETE FROM leader_lease WHERE name = $1 AND holder = $2")
+        .bind(name)
+        .bind(holder)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// A PostgreSQL session advisory lock. The lock belongs to the connection held here, which is
+/// detached from the pool so it can't be handed to another query; it goes away when the
+/// connection does, so a crashed holder frees it as soon as the server drops the session.
+pub struct AdvisoryLock {
+    name: String,
+    conn: sqlx::AnyConnection,
+}
+
+/// Try to take the advisory lock `name` without waiting. `None` means another session holds it.
+pub async fn try_advisory_lock(name: &str) -> Result<Option<AdvisoryLock>> {
+    let mut conn = pool().acquire().await?.detach();
+    let held: bool = sqlx::query("SELECT pg_try_advisory_lock(hashtext($1))")
+        .bind(name)
+        .fetch_one(&mut conn)
+        .await?
+        .get(0);
+    if !held {
+        conn.close().await?;
+        return Ok(None);
+    }
+    Ok(Some(AdvisoryLock { name: name.to_string(), conn }))
+}
+
+impl AdvisoryLock {
+    /// Confirm the session holding the lock is still alive.
+    pub async fn check(&mut self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&mut self.conn).await?;
+        Ok(())
+    }
+
+    pub async fn release(mut self) -> Result<()> {
+        sqlx::query("SELECT pg_advisory_unlock(hashtext($1))")
+            .bind(&self.name)
+            .execute(&mut self.conn)
+            .await?;
+        self.conn.close().await?;
+        Ok(())
+    }
+}
+
+pub async fn add_triage_channel(guild_id: i64, channel_id: i64, auto_thread: bool) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO triage_channels (channel_id, guild_id, auto_thread) VALUES ($1, $2, $3)
+        ON CONFLICT(channel_id) DO UPDATE SET auto_thread=excluded.auto_thread")
+        .bind(channel_id)
+        .bind(guild_id)
+        .bind(auto_thread)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_triage_channel(guild_id: i64, channel_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM triage_channels WHERE guild_id = $1 AND channel_id = $2")
+        .bind(guild_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_triage_channels(guild_id: i64) -> Result<Vec<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT channel_id FROM triage_channels WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<i64, _>(0)).collect())
+}
+
+pub async fn get_triage_auto_thread(channel_id: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT auto_thread FROM triage_channels WHERE channel_id = $1")
+        .bind(channel_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<bool, _>(0)).unwrap_or(false))
+}
+
+pub async fn set_triage_snippet(guild_id: i64, tag: &str, content: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO triage_snippets (guild_id, tag, content) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id, tag) DO UPDATE SET content=excluded.content")
+        .bind(guild_id)
+        .bind(tag)
+        .bind(content)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_triage_snippet(guild_id: i64, tag: &str) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM triage_snippets WHERE guild_id = $1 AND tag = $2")
+        .bind(guild_id)
+        .bind(tag)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// A guild's snippets, one per tag.
+pub async fn get_triage_snippets(guild_id: i64) -> Result<Vec<TriageSnippet>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, TriageSnippet>("SELECT tag, content FROM triage_snippets WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn create_paste(id: &str, guild_id: i64, author_id: i64, language: &str, content: &str, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO pastes (id, guild_id, author_id, language, content, created_at) VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(id)
+        .bind(guild_id)
+        .bind(author_id)
+        .bind(language)
+        .bind(content)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// A paste by its id.
+pub async fn get_paste(id: &str) -> Result<Option<Paste>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, Paste>("SELECT language, content FROM pastes WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+/// Every channel subscribed to backend status notices.
+pub async fn get_status_subscriptions() -> Result<Vec<StatusSubscription>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, StatusSubscription>("SELECT guild_id, channel_id FROM status_subscriptions")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// `channel_id` None unsubscribes the guild.
+pub async fn set_status_subscription(guild_id: i64, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    match channel_id {
+        Some(c) => {
+            sqlx::query("INSERT INTO status_subscriptions (guild_id, channel_id) VALUES ($1, $2)
+                ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id")
+                .bind(guild_id)
+                .bind(c)
+                .execute(&*pool)
+                .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM status_subscriptions WHERE guild_id = $1")
+                .bind(guild_id)
+                .execute(&*pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Cheapest possible round trip, for latency checks.
+pub async fn ping() -> Result<()> {
+    let pool = pool();
+    sqlx::query("SELECT 1").execute(&*pool).await?;
+    Ok(())
+}
+
+pub async fn add_milestone_log(guild_id: i64, member_count: i64, channel_id: i64, message_id: i64, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO milestone_log (guild_id, member_count, channel_id, message_id, created_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(guild_id)
+        .bind(member_count)
+        .bind(channel_id)
+        .bind(message_id)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Every celebration in a guild, oldest first.
+pub async fn get_milestone_log(guild_id: i64) -> Result<Vec<MilestoneEntry>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, MilestoneEntry>("SELECT member_count, channel_id, message_id, created_at FROM milestone_log WHERE guild_id = $1 ORDER BY created_at")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// `kind` is "role" or "channel".
+pub async fn add_command_permission(guild_id: i64, command: &str, kind: &str, target_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO command_permissions (guild_id, command, kind, target_id) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id, command, kind, target_id) DO NOTHING")
+        .bind(guild_id)
+        .bind(command)
+        .bind(kind)
+        .bind(target_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_command_permission(guild_id: i64, command: &str, kind: &str, target_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("DELETE FROM command_permissions WHERE guild_id = $1 AND command = $2 AND kind = $3 AND target_id = $4")
+        .bind(guild_id)
+        .bind(command)
+        .bind(kind)
+        .bind(target_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn clear_command_permissions(guild_id: i64, command: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("DELETE FROM command_permissions WHERE guild_id = $1 AND command = $2")
+        .bind(guild_id)
+        .bind(command)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// The rules for one command.
+pub async fn get_command_rules(guild_id: i64, command: &str) -> Result<Vec<CommandPermission>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, CommandPermission>("SELECT command, kind, target_id FROM command_permissions WHERE guild_id = $1 AND command = $2")
+        .bind(guild_id)
+        .bind(command)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// (command, kind, target_id) for every rule in a guild.
+pub async fn get_command_permissions(guild_id: i64) -> Result<Vec<CommandPermission>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, CommandPermission>("SELECT command, kind, target_id FROM command_permissions WHERE guild_id = $1 ORDER BY command")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_user_prefs(user_id: i64) -> Result<UserPrefs> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, UserPrefs>("SELECT user_id, language, dm_reminders, quote_privacy, ephemeral, onboarding_dms FROM user_prefs WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or_else(|| UserPrefs::default_for(user_id)))
+}
+
+pub async fn update_user_prefs(prefs: &UserPrefs) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO user_prefs (user_id, language, dm_reminders, quote_privacy, ephemeral, onboarding_dms) VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT(user_id) DO UPDATE SET language=excluded.language, dm_reminders=excluded.dm_reminders, quote_privacy=excluded.quote_privacy, ephemeral=excluded.ephemeral, onboarding_dms=excluded.onboarding_dms")
+        .bind(prefs.user_id)
+        .bind(&prefs.language)
+        .bind(prefs.dm_reminders)
+        .bind(prefs.quote_privacy)
+        .bind(prefs.ephemeral)
+        .bind(prefs.onboarding_dms)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_user_prefs(user_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("DELETE FROM user_prefs WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Configure voice announcements for one of the guild's events. Returns false when the event doesn't exist.
+pub async fn set_event_voice_announcement(guild_id: i64, event_id: i64, voice_channel_id: i64, text_channel_id: i64, speaker_role_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO event_voice_announcements (event_id, voice_channel_id, text_channel_id, speaker_role_id)
+        SELECT id, $1, $2, $3 FROM events WHERE guild_id = $4 AND id = $5
+        ON CONFLICT(event_id) DO UPDATE SET voice_channel_id=excluded.voice_channel_id, text_channel_id=excluded.text_channel_id, speaker_role_id=excluded.speaker_role_id")
+        .bind(voice_channel_id)
+        .bind(text_channel_id)
+        .bind(speaker_role_id)
+        .bind(guild_id)
+        .bind(event_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn remove_event_voice_announcement(guild_id: i64, event_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM event_voice_announcements WHERE event_id IN (SELECT id FROM events WHERE guild_id = $1 AND id = $2)")
+        .bind(guild_id)
+        .bind(event_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Returns (event name, voice_channel_id, text_channel_id, speaker_role_id) for events running at `now`.
+pub async fn get_active_voice_announcements(guild_id: i64, now: i64) -> Result<Vec<VoiceAnnouncement>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, VoiceAnnouncement>("SELECT e.name, a.voice_channel_id, a.text_channel_id, a.speaker_role_id FROM event_voice_announcements a
+        JOIN events e ON e.id = a.event_id
+        WHERE e.guild_id = $1 AND e.starts_at <= $2 AND e.ends_at >= $3")
+        .bind(guild_id)
+        .bind(now)
+        .bind(now)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Event ids of the guild that have voice announcements configured.
+pub async fn get_voice_announcement_events(guild_id: i64) -> Result<Vec<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT a.event_id FROM event_voice_announcements a JOIN events e ON e.id = a.event_id WHERE e.guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<i64, _>(0)).collect())
+}
+
+/// Insert or update the mirror of a Discord scheduled event. A changed start time re-arms the reminder.
+pub async fn upsert_scheduled_event(guild_id: i64, scheduled_event_id: i64, name: &str, starts_at: i64, ends_at: i64, created_by: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO events (guild_id, name, starts_at, ends_at, created_by, scheduled_event_id) VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT(scheduled_event_id) DO UPDATE SET name=excluded.name, starts_at=excluded.starts_at, ends_at=excluded.ends_at,
+        reminded=CASE WHEN events.starts_at = excluded.starts_at THEN events.reminded ELSE FALSE END")
+        .bind(guild_id)
+        .bind(name)
+        .bind(starts_at)
+        .bind(ends_at)
+        .bind(created_by)
+        .bind(scheduled_event_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_scheduled_event(scheduled_event_id: i64) -> Result<bool> {
+    let pool = pool();
+    sqlx::query("DELETE FROM event_voice_announcements WHERE event_id IN (SELECT id FROM events WHERE scheduled_event_id = $1)")
+        .bind(scheduled_event_id)
+        .execute(&*pool)
+        .await?;
+    let res = sqlx::query("DELETE FROM events WHERE scheduled_event_id = $1")
+        .bind(scheduled_event_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Mark events starting in (now, until] as reminded. Returns (guild_id, reminder_channel_id, name,
+/// starts_at, scheduled_event_id) for those whose guild has a reminder channel.
+pub async fn take_due_event_reminders(now: i64, until: i64) -> Result<Vec<EventReminder>> {
+    let pool = pool();
+    let rows = sqlx::query("UPDATE events SET reminded = TRUE WHERE reminded = FALSE AND starts_at > $1 AND starts_at <= $2
+        RETURNING guild_id, name, starts_at, scheduled_event_id")
+        .bind(now)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    let mut due = Vec::new();
+    for r in rows.iter() {
+        let guild_id = r.get::<i64, _>(0);
+        if let Some(channel_id) = get_event_reminder_channel(guild_id).await? {
+            due.push(EventReminder { guild_id, channel_id, name: r.get(1), starts_at: r.get(2), scheduled_event_id: r.get(3) });
+        }
+    }
+    Ok(due)
+}
+
+pub async fn get_event_reminder_channel(guild_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT reminder_channel_id FROM event_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.and_then(|r| r.get::<Option<i64>, _>(0)))
+}
+
+pub async fn set_event_reminder_channel(guild_id: i64, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO event_settings (guild_id, reminder_channel_id) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET reminder_channel_id=excluded.reminder_channel_id")
+        .bind(guild_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_admin_role(guild_id: i64, role_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO admin_roles (guild_id, role_id) VALUES ($1, $2) ON CONFLICT(guild_id, role_id) DO NOTHING")
+        .bind(guild_id)
+        .bind(role_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_admin_role(guild_id: i64, role_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM admin_roles WHERE guild_id = $1 AND role_id = $2")
+        .bind(guild_id)
+        .bind(role_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_admin_roles(guild_id: i64) -> Result<Vec<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT role_id FROM admin_roles WHERE guild_id = $1 ORDER BY role_id")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<i64, _>(0)).collect())
+}
+
+pub async fn get_onboarding_enabled(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT enabled FROM onboarding_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<bool, _>(0)).unwrap_or(false))
+}
+
+pub async fn set_onboarding_enabled(guild_id: i64, enabled: bool) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO onboarding_settings (guild_id, enabled) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET enabled=excluded.enabled")
+        .bind(guild_id)
+        .bind(enabled)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_onboarding_template(guild_id: i64, step: i64) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT content FROM onboarding_templates WHERE guild_id = $1 AND step = $2")
+        .bind(guild_id)
+        .bind(step)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<String, _>(0)))
+}
+
+/// Store a custom template for `step`, or drop it (back to the default) with None.
+pub async fn set_onboarding_template(guild_id: i64, step: i64, content: Option<&str>) -> Result<()> {
+    let pool = pool();
+    match content {
+        Some(c) => {
+            sqlx::query("INSERT INTO onboarding_templates (guild_id, step, content) VALUES ($1, $2, $3)
+                ON CONFLICT(guild_id, step) DO UPDATE SET content=excluded.content")
+                .bind(guild_id)
+                .bind(step)
+                .bind(c)
+                .execute(&*pool)
+                .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM onboarding_templates WHERE guild_id = $1 AND step = $2")
+                .bind(guild_id)
+                .bind(step)
+                .execute(&*pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn queue_onboarding_dm(guild_id: i64, user_id: i64, step: i64, due_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO onboarding_queue (guild_id, user_id, step, due_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id, user_id, step) DO UPDATE SET due_at=excluded.due_at")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(step)
+        .bind(due_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn clear_onboarding_queue(guild_id: i64, user_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("DELETE FROM onboarding_queue WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id)
+        .bind(user_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove and return (guild_id, user_id, step) of drip DMs due at `now`.
+pub async fn take_due_onboarding_dms(now: i64) -> Result<Vec<OnboardingDm>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, OnboardingDm>("DELETE FROM onboarding_queue WHERE due_at <= $1 RETURNING guild_id, user_id, step")
+        .bind(now)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Id of the member's recorded intro message, if they posted one.
+pub async fn get_intro_message(guild_id: i64, user_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT message_id FROM intros WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id)
+        .bind(user_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<i64, _>(0)))
+}
+
+pub async fn get_rules_settings(guild_id: i64) -> Result<Option<RulesSettings>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, RulesSettings>("SELECT guild_id, channel_id, message_id, role_id, version FROM rules_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn set_rules_panel(guild_id: i64, channel_id: i64, message_id: i64, role_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO rules_settings (guild_id, channel_id, message_id, role_id) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id, message_id=excluded.message_id, role_id=excluded.role_id")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(message_id)
+        .bind(role_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Start a new rules version so earlier acceptances no longer count; returns the new version.
+pub async fn bump_rules_version(guild_id: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("UPDATE rules_settings SET version = version + 1 WHERE guild_id = $1 RETURNING version")
+        .bind(guild_id)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn accept_rules(guild_id: i64, user_id: i64, version: i64, accepted_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO rules_acceptances (guild_id, user_id, version, accepted_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id, user_id) DO UPDATE SET version=excluded.version, accepted_at=excluded.accepted_at")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(version)
+        .bind(accepted_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Version of the rules the member last accepted.
+pub async fn get_rules_acceptance(guild_id: i64, user_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT version FROM rules_acceptances WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id)
+        .bind(user_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<i64, _>(0)))
+}
+
+/// Returns (user_id, version, accepted_at) of every acceptance in the guild.
+pub async fn get_rules_acceptances(guild_id: i64) -> Result<Vec<RulesAcceptance>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, RulesAcceptance>("SELECT user_id, version, accepted_at FROM rules_acceptances WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Returns false when the slug is already taken.
+pub async fn create_shortlink(slug: &str, guild_id: i64, url: &str, created_by: i64, created_at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO shortlinks (slug, guild_id, url, created_by, created_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT(slug) DO NOTHING")
+        .bind(slug)
+        .bind(guild_id)
+        .bind(url)
+        .bind(created_by)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn delete_shortlink(guild_id: i64, slug: &str) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM shortlinks WHERE guild_id = $1 AND slug = $2")
+        .bind(guild_id)
+        .bind(slug)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Count a click and return the target URL.
+pub async fn click_shortlink(slug: &str, clicked_at: i64) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("UPDATE shortlinks SET clicks = clicks + 1, last_clicked_at = $1 WHERE slug = $2 RETURNING url")
+        .bind(clicked_at)
+        .bind(slug)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<String, _>(0)))
+}
+
+/// Target URL without counting a click.
+pub async fn get_shortlink_url(slug: &str) -> Result<Option<String>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT url FROM shortlinks WHERE slug = $1")
+        .bind(slug)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<String, _>(0)))
+}
+
+/// The guild's links, most clicked first.
+pub async fn get_shortlinks(guild_id: i64) -> Result<Vec<Shortlink>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, Shortlink>("SELECT slug, url, created_by, created_at, clicks, last_clicked_at FROM shortlinks WHERE guild_id = $1 ORDER BY clicks DESC, created_at DESC")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_highlight_settings(guild_id: i64) -> Result<HighlightSettings> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, HighlightSettings>("SELECT guild_id, channel_id, threshold, last_sent FROM highlight_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or_else(|| HighlightSettings::default_for(guild_id)))
+}
+
+pub async fn update_highlight_channel(guild_id: i64, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO highlight_settings (guild_id, channel_id) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id")
+        .bind(guild_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn update_highlight_threshold(guild_id: i64, threshold: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO highlight_settings (guild_id, threshold) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET threshold=excluded.threshold")
+        .bind(guild_id)
+        .bind(threshold)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Settings of every guild with a digest channel set.
+pub async fn get_enabled_highlights() -> Result<Vec<HighlightSettings>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, HighlightSettings>("SELECT guild_id, channel_id, threshold, last_sent FROM highlight_settings WHERE channel_id IS NOT NULL")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn mark_highlights_sent(guild_id: i64, week: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE highlight_settings SET last_sent = $1 WHERE guild_id = $2")
+        .bind(week)
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_highlight_channel(guild_id: i64, channel_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO highlight_channels (channel_id, guild_id) VALUES ($1, $2) ON CONFLICT(channel_id) DO NOTHING")
+        .bind(channel_id)
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_highlight_channel(guild_id: i64, channel_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM highlight_channels WHERE guild_id = $1 AND channel_id = $2")
+        .bind(guild_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_highlight_channels(guild_id: i64) -> Result<Vec<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT channel_id FROM highlight_channels WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<i64, _>(0)).collect())
+}
+
+pub async fn is_highlight_channel(channel_id: i64) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT 1 FROM highlight_channels WHERE channel_id = $1")
+        .bind(channel_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+/// Author of a message already being scored.
+pub async fn get_highlight_author(message_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT author_id FROM highlight_scores WHERE message_id = $1")
+        .bind(message_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.map(|r| r.get::<i64, _>(0)))
+}
+
+pub async fn track_highlight_message(message_id: i64, guild_id: i64, channel_id: i64, author_id: i64, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO highlight_scores (message_id, guild_id, channel_id, author_id, created_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT(message_id) DO NOTHING")
+        .bind(message_id)
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(author_id)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn adjust_highlight_score(message_id: i64, delta: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE highlight_scores SET score = CASE WHEN score + $1 > 0 THEN score + $1 ELSE 0 END WHERE message_id = $2")
+        .bind(delta)
+        .bind(message_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (channel_id, message_id, author_id, score) of messages posted since `since`
+/// with at least `threshold` reactions, highest first.
+pub async fn get_top_highlights(guild_id: i64, since: i64, threshold: i64, limit: i64) -> Result<Vec<Highlight>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, Highlight>("SELECT channel_id, message_id, author_id, score FROM highlight_scores
+        WHERE guild_id = $1 AND created_at >= $2 AND score >= $3 ORDER BY score DESC, created_at LIMIT $4")
+        .bind(guild_id)
+        .bind(since)
+        .bind(threshold)
+        .bind(limit)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Forget scores of messages too old to appear in a digest again.
+pub async fn prune_highlight_scores(before: i64) -> Result<u64> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM highlight_scores WHERE created_at < $1")
+        .bind(before)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+pub async fn get_spotlight_settings(guild_id: i64) -> Result<SpotlightSettings> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, SpotlightSettings>("SELECT guild_id, is_enabled, mode, channel_id FROM spotlight_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or_else(|| SpotlightSettings::default_for(guild_id)))
+}
+
+pub async fn update_spotlight_settings(guild_id: i64, is_enabled: bool, mode: &str, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO spotlight_settings (guild_id, is_enabled, mode, channel_id) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id) DO UPDATE SET is_enabled=excluded.is_enabled, mode=excluded.mode, channel_id=excluded.channel_id")
+        .bind(guild_id)
+        .bind(is_enabled)
+        .bind(mode)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Time of the member's most recent join, from `member_events`.
+pub async fn get_last_join(guild_id: i64, user_id: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let row = sqlx::query("SELECT MAX(created_at) FROM member_events WHERE guild_id = $1 AND user_id = $2 AND kind = 'join'")
+        .bind(guild_id)
+        .bind(user_id)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.try_get::<i64, _>(0).ok())
+}
+
+/// Whether `message_activity` has any message from the user on or after `since_day` (YYYY-MM-DD).
+pub async fn has_message_activity(guild_id: i64, user_id: i64, since_day: &str) -> Result<bool> {
+    let pool = pool();
+    let row = sqlx::query("SELECT 1 FROM message_activity WHERE guild_id = $1 AND user_id = $2 AND day >= $3 LIMIT 1")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(since_day)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+/// Returns false when the member's first message was already recorded.
+pub async fn record_first_message(guild_id: i64, user_id: i64, channel_id: i64, message_id: i64, posted_at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO first_messages (guild_id, user_id, channel_id, message_id, posted_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT(guild_id, user_id) DO NOTHING")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(channel_id)
+        .bind(message_id)
+        .bind(posted_at)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Returns (joins, joins followed by a first message) for members who joined in [since, now).
+pub async fn count_first_message_conversion(guild_id: i64, since: i64) -> Result<(i64, i64)> {
+    let pool = pool();
+    let row = sqlx::query("SELECT COUNT(DISTINCT e.user_id), COUNT(DISTINCT f.user_id) FROM member_events e
+        LEFT JOIN first_messages f ON f.guild_id = e.guild_id AND f.user_id = e.user_id AND f.posted_at >= e.created_at
+        WHERE e.guild_id = $1 AND e.kind = 'join' AND e.created_at >= $2")
+        .bind(guild_id)
+        .bind(since)
+        .fetch_one(&*pool)
+        .await?;
+    Ok((row.get::<i64, _>(0), row.get::<i64, _>(1)))
+}
+
+/// Track the guild's latest prediction message, replacing the previous one.
+pub async fn set_growth_forecast(guild_id: i64, channel_id: i64, message_id: i64, target: i64, updated_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO growth_forecasts (guild_id, channel_id, message_id, target, updated_at) VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id, message_id=excluded.message_id, target=excluded.target, updated_at=excluded.updated_at")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(message_id)
+        .bind(target)
+        .bind(updated_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Predictions last updated before `before`.
+pub async fn get_due_growth_forecasts(before: i64) -> Result<Vec<GrowthForecast>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, GrowthForecast>("SELECT guild_id, channel_id, message_id, target, updated_at FROM growth_forecasts WHERE updated_at < $1")
+        .bind(before)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn remove_growth_forecast(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM growth_forecasts WHERE guild_id = $1")
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn record_voice_activity(guild_id: i64, user_id: i64, day: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO voice_activity (guild_id, user_id, day) VALUES ($1, $2, $3) ON CONFLICT(guild_id, user_id, day) DO NOTHING")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(day)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Users with a message or a voice session on or after `since_day` (YYYY-MM-DD).
+pub async fn get_active_user_ids(guild_id: i64, since_day: &str) -> Result<std::collections::HashSet<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT user_id FROM message_activity WHERE guild_id = $1 AND day >= $2
+        UNION SELECT user_id FROM voice_activity WHERE guild_id = $3 AND day >= $4")
+        .bind(guild_id)
+        .bind(since_day)
+        .bind(guild_id)
+        .bind(since_day)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<i64, _>(0)).collect())
+}
+
+pub async fn add_prune_log_entry(run_id: i64, guild_id: i64, moderator_id: i64, user_id: i64, action: &str, succeeded: bool, created_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO prune_log (run_id, guild_id, moderator_id, user_id, action, succeeded, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT(run_id, user_id) DO UPDATE SET guild_id=excluded.guild_id, moderator_id=excluded.moderator_id, action=excluded.action, succeeded=excluded.succeeded, created_at=excluded.created_at")
+        .bind(run_id)
+        .bind(guild_id)
+        .bind(moderator_id)
+        .bind(user_id)
+        .bind(action)
+        .bind(succeeded)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns (run_id, moderator_id, user_id, action, succeeded, created_at) since `since`, newest run first.
+pub async fn get_prune_log(guild_id: i64, since: i64) -> Result<Vec<PruneLogEntry>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, PruneLogEntry>("SELECT run_id, moderator_id, user_id, action, succeeded, created_at FROM prune_log WHERE guild_id = $1 AND created_at >= $2 ORDER BY created_at DESC, run_id DESC, user_id")
+        .bind(guild_id)
+        .bind(since)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn set_role_decay_rule(guild_id: i64, role_id: i64, days: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO role_decay_rules (guild_id, role_id, days) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id, role_id) DO UPDATE SET days=excluded.days")
+        .bind(guild_id)
+        .bind(role_id)
+        .bind(days)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_role_decay_rule(guild_id: i64, role_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM role_decay_rules WHERE guild_id = $1 AND role_id = $2")
+        .bind(guild_id)
+        .bind(role_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Rules for one guild, or for every guild when `guild_id` is None.
+pub async fn get_role_decay_rules(guild_id: Option<i64>) -> Result<Vec<RoleDecayRule>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, RoleDecayRule>("SELECT guild_id, role_id, days FROM role_decay_rules WHERE $1 IS NULL OR guild_id = $2 ORDER BY guild_id, role_id")
+        .bind(guild_id)
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Count a contribution once per `ref_id`. Returns false when it was already recorded.
+pub async fn record_contribution(guild_id: i64, user_id: i64, kind: &str, ref_id: i64, created_at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO contribution_events (guild_id, user_id, kind, ref_id, created_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT(guild_id, user_id, kind, ref_id) DO NOTHING")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(kind)
+        .bind(ref_id)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Copy messages that reached the digest threshold into `contribution_events` as starboard entries,
+/// since `highlight_scores` only keeps the last few weeks.
+pub async fn sync_starboard_contributions(guild_id: i64, threshold: i64) -> Result<u64> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO contribution_events (guild_id, user_id, kind, ref_id, created_at)
+        SELECT guild_id, author_id, 'starboard', message_id, created_at FROM highlight_scores WHERE guild_id = $1 AND score >= $2
+        ON CONFLICT(guild_id, user_id, kind, ref_id) DO NOTHING")
+        .bind(guild_id)
+        .bind(threshold)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+/// Returns (user_id, kind, count) for every member with recorded contributions.
+pub async fn get_contribution_counts(guild_id: i64) -> Result<Vec<ContributionCount>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, ContributionCount>("SELECT user_id, kind, COUNT(*) AS count FROM contribution_events WHERE guild_id = $1 GROUP BY user_id, kind")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_contributor_settings(guild_id: i64) -> Result<ContributorSettings> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, ContributorSettings>("SELECT guild_id, help_threshold, starboard_threshold, intro_threshold, role_id, channel_id FROM contributor_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or(ContributorSettings { guild_id, ..Default::default() }))
+}
+
+/// Settings of every guild with at least one threshold set.
+pub async fn get_enabled_contributor_settings() -> Result<Vec<ContributorSettings>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, ContributorSettings>("SELECT guild_id, help_threshold, starboard_threshold, intro_threshold, role_id, channel_id FROM contributor_settings
+        WHERE help_threshold > 0 OR starboard_threshold > 0 OR intro_threshold > 0")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Set the threshold for `kind` (`help_answer`, `starboard` or `intro_reply`).
+pub async fn set_contributor_threshold(guild_id: i64, kind: &str, count: i64) -> Result<()> {
+    let column = match kind {
+        "help_answer" => "help_threshold",
+        "starboard" => "starboard_threshold",
+        "intro_reply" => "intro_threshold",
+        _ => anyhow::bail!("unknown contribution kind: {}", kind),
+    };
+    let pool = pool();
+    sqlx::query(&format!("INSERT INTO contributor_settings (guild_id, {0}) VALUES ($1, $2)
+        ON CONFLICT(guild_id) DO UPDATE SET {0}=excluded.{0}", column))
+        .bind(guild_id)
+        .bind(count)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_contributor_reward(guild_id: i64, role_id: Option<i64>, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO contributor_settings (guild_id, role_id, channel_id) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id) DO UPDATE SET role_id=excluded.role_id, channel_id=excluded.channel_id")
+        .bind(guild_id)
+        .bind(role_id)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Record that `user_id` was recognised for `kind`. Returns false when they already were.
+pub async fn add_contributor_award(guild_id: i64, user_id: i64, kind: &str, awarded_at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO contributor_awards (guild_id, user_id, kind, awarded_at) VALUES ($1, $2, $3, $4) ON CONFLICT(guild_id, user_id, kind) DO NOTHING")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(kind)
+        .bind(awarded_at)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Returns (user_id, kind, awarded_at), newest first.
+pub async fn get_contributor_awards(guild_id: i64, limit: i64) -> Result<Vec<ContributorAward>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, ContributorAward>("SELECT user_id, kind, awarded_at FROM contributor_awards WHERE guild_id = $1 ORDER BY awarded_at DESC LIMIT $2")
+        .bind(guild_id)
+        .bind(limit)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Start tracking a help thread. Returns false when it already was.
+pub async fn track_help_thread(thread_id: i64, guild_id: i64, asker_id: Option<i64>, created_at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO help_threads (thread_id, guild_id, asker_id, created_at) VALUES ($1, $2, $3, $4) ON CONFLICT(thread_id) DO NOTHING")
+        .bind(thread_id)
+        .bind(guild_id)
+        .bind(asker_id)
+        .bind(created_at)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_help_thread(thread_id: i64) -> Result<Option<HelpThread>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, HelpThread>("SELECT thread_id, guild_id, asker_id, created_at, first_answer_at, resolved_at, resolved_by, title FROM help_threads WHERE thread_id = $1")
+        .bind(thread_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+/// Note the first message in a tracked thread from someone other than the asker. Later ones are ignored.
+pub async fn mark_help_thread_answered(thread_id: i64, author_id: i64, at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE help_threads SET first_answer_at = $1 WHERE thread_id = $2 AND first_answer_at IS NULL AND (asker_id IS NULL OR asker_id != $3)")
+        .bind(at)
+        .bind(thread_id)
+        .bind(author_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns false when the thread was already resolved.
+pub async fn resolve_help_thread(thread_id: i64, resolved_by: i64, at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("UPDATE help_threads SET resolved_at = $1, resolved_by = $2 WHERE thread_id = $3 AND resolved_at IS NULL")
+        .bind(at)
+        .bind(resolved_by)
+        .bind(thread_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Help threads opened since `since`, oldest first.
+pub async fn get_help_threads_since(guild_id: i64, since: i64) -> Result<Vec<HelpThread>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, HelpThread>("SELECT thread_id, guild_id, asker_id, created_at, first_answer_at, resolved_at, resolved_by, title FROM help_threads
+        WHERE guild_id = $1 AND created_at >= $2 ORDER BY created_at")
+        .bind(guild_id)
+        .bind(since)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Save a role menu and its roles in display order.
+pub async fn create_reaction_role_menu(menu: &ReactionRoleMenu, role_ids: &[i64]) -> Result<()> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT INTO reaction_role_menus (message_id, guild_id, channel_id, style, title, created_at) VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(menu.message_id)
+        .bind(menu.guild_id)
+        .bind(menu.channel_id)
+        .bind(&menu.style)
+        .bind(&menu.title)
+        .bind(menu.created_at)
+        .execute(&mut tx)
+        .await?;
+    for (position, role_id) in role_ids.iter().enumerate() {
+        sqlx::query("INSERT INTO reaction_role_options (message_id, role_id, position) VALUES ($1, $2, $3) ON CONFLICT(message_id, role_id) DO NOTHING")
+            .bind(menu.message_id)
+            .bind(role_id)
+            .bind(position as i64)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Roles offered by the menu on `message_id`, in display order. Empty when it isn't a menu.
+pub async fn get_reaction_role_options(message_id: i64) -> Result<Vec<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT role_id FROM reaction_role_options WHERE message_id = $1 ORDER BY position")
+        .bind(message_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<i64, _>(0)).collect())
+}
+
+pub async fn get_reaction_role_menus(guild_id: i64) -> Result<Vec<ReactionRoleMenu>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, ReactionRoleMenu>("SELECT message_id, guild_id, channel_id, style, title, created_at FROM reaction_role_menus WHERE guild_id = $1 ORDER BY created_at")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Returns the removed menu, or None when `message_id` wasn't one of this guild's menus.
+pub async fn remove_reaction_role_menu(guild_id: i64, message_id: i64) -> Result<Option<ReactionRoleMenu>> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    let menu = sqlx::query_as::<_, ReactionRoleMenu>("SELECT message_id, guild_id, channel_id, style, title, created_at FROM reaction_role_menus WHERE guild_id = $1 AND message_id = $2")
+        .bind(guild_id)
+        .bind(message_id)
+        .fetch_optional(&mut tx)
+        .await?;
+    if menu.is_some() {
+        sqlx::query("DELETE FROM reaction_role_options WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM reaction_role_menus WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(menu)
+}
+
+pub async fn add_kb_article(guild_id: i64, title: &str, body: &str, author_id: i64, now: i64) -> Result<i64> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    let id = sqlx::query("INSERT INTO kb_articles (guild_id, title, body, author_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id")
+        .bind(guild_id)
+        .bind(title)
+        .bind(body)
+        .bind(author_id)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>(0);
+    sqlx::query("INSERT INTO kb_fts (rowid, title, body) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(title)
+        .bind(body)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+pub async fn get_kb_article(guild_id: i64, id: i64) -> Result<Option<KbArticle>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, KbArticle>("SELECT id, guild_id, title, body, author_id, created_at, updated_at FROM kb_articles WHERE guild_id = $1 AND id = $2")
+        .bind(guild_id)
+        .bind(id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn remove_kb_article(guild_id: i64, id: i64) -> Result<bool> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    let res = sqlx::query("DELETE FROM kb_articles WHERE guild_id = $1 AND id = $2")
+        .bind(guild_id)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    if res.rows_affected() > 0 {
+        sqlx::query("DELETE FROM kb_fts WHERE rowid = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Quoting each term keeps FTS5 operators in user text from being interpreted.
+fn fts_phrase(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+/// `%term%` for LIKE with `\` as the escape character.
+fn like_pattern(term: &str) -> String {
+    format!("%{}%", term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+}
+
+/// One case-insensitive substring match of `{prefix}title` or `{prefix}body` per term, against
+/// placeholders numbered from `first`. This is what PostgreSQL has instead of FTS5.
+fn like_conditions(prefix: &str, terms: usize, first: usize) -> Vec<String> {
+    let op = match backend() { Backend::Sqlite => "LIKE", Backend::Postgres => "ILIKE" };
+    (first..first + terms).map(|n| format!("({0}title {1} ${2} ESCAPE '\\' OR {0}body {1} ${2} ESCAPE '\\')", prefix, op, n)).collect()
+}
+
+/// How many of `conditions` a row meets, to rank substring matches by.
+fn match_count(conditions: &[String]) -> String {
+    conditions.iter().map(|c| format!("CASE WHEN {} THEN 1 ELSE 0 END", c)).collect::<Vec<_>>().join(" + ")
+}
+
+/// Articles matching every term, best match first. The trigram index can't look up terms shorter
+/// than three characters, so queries with one fall back to a substring scan ordered by recency,
+/// as does every query on PostgreSQL.
+pub async fn search_kb_articles(guild_id: i64, terms: &[String], limit: i64) -> Result<Vec<KbArticle>> {
+    if terms.is_empty() { return Ok(Vec::new()); }
+    let pool = pool();
+    let rows = if backend() == Backend::Sqlite && terms.iter().all(|t| t.chars().count() >= 3) {
+        let query = terms.iter().map(|t| fts_phrase(t)).collect::<Vec<_>>().join(" ");
+        sqlx::query_as::<_, KbArticle>("SELECT a.id, a.guild_id, a.title, a.body, a.author_id, a.created_at, a.updated_at
+            FROM kb_fts JOIN kb_articles a ON a.id = kb_fts.rowid WHERE kb_fts MATCH $1 AND a.guild_id = $2 ORDER BY kb_fts.rank LIMIT $3")
+            .bind(query)
+            .bind(guild_id)
+            .bind(limit)
+            .fetch_all(&*pool)
+            .await?
+    } else {
+        let conditions = like_conditions("", terms.len(), 2).join(" AND ");
+        let sql = format!("SELECT id, guild_id, title, body, author_id, created_at, updated_at FROM kb_articles WHERE guild_id = $1 AND {} ORDER BY updated_at DESC LIMIT ${}", conditions, terms.len() + 2);
+        let mut q = sqlx::query_as::<_, KbArticle>(&sql).bind(guild_id);
+        for term in terms {
+            q = q.bind(like_pattern(term));
+        }
+        q.bind(limit).fetch_all(&*pool).await?
+    };
+    Ok(rows)
+}
+
+pub async fn create_poll(guild_id: i64, channel_id: i64, creator_id: i64, question: &str, options: &[String], created_at: i64, closes_at: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO polls (guild_id, channel_id, creator_id, question, options, created_at, closes_at) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(creator_id)
+        .bind(question)
+        .bind(options.join("\n"))
+        .bind(created_at)
+        .bind(closes_at)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn set_poll_message(poll_id: i64, message_id: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE polls SET message_id = $1 WHERE id = $2")
+        .bind(message_id)
+        .bind(poll_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// `guild_id` None looks the poll up from any guild, for button clicks that only carry the id.
+pub async fn get_poll(guild_id: Option<i64>, poll_id: i64) -> Result<Option<Poll>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, Poll>("SELECT id, guild_id, channel_id, message_id, creator_id, question, options, created_at, closes_at, closed FROM polls WHERE id = $1 AND ($2 IS NULL OR guild_id = $3)")
+        .bind(poll_id)
+        .bind(guild_id)
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+/// Open polls whose closing time has passed.
+pub async fn get_due_polls(now: i64) -> Result<Vec<Poll>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, Poll>("SELECT id, guild_id, channel_id, message_id, creator_id, question, options, created_at, closes_at, closed FROM polls WHERE closed = FALSE AND closes_at <= $1")
+        .bind(now)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Returns false when the poll was already closed, so only one caller announces the result.
+pub async fn close_poll(poll_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("UPDATE polls SET closed = TRUE WHERE id = $1 AND closed = FALSE")
+        .bind(poll_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Record or move a vote. Returns the option previously voted for, if any.
+pub async fn cast_poll_vote(poll_id: i64, user_id: i64, option_index: i64, voted_at: i64) -> Result<Option<i64>> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    let previous = sqlx::query("SELECT option_index FROM poll_votes WHERE poll_id = $1 AND user_id = $2")
+        .bind(poll_id)
+        .bind(user_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .map(|r| r.get::<i64, _>(0));
+    sqlx::query("INSERT INTO poll_votes (poll_id, user_id, option_index, voted_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(poll_id, user_id) DO UPDATE SET option_index=excluded.option_index, voted_at=excluded.voted_at")
+        .bind(poll_id)
+        .bind(user_id)
+        .bind(option_index)
+        .bind(voted_at)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(previous)
+}
+
+/// Vote counts per option index, zero-filled up to `option_count`.
+pub async fn get_poll_counts(poll_id: i64, option_count: usize) -> Result<Vec<i64>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT option_index, COUNT(*) FROM poll_votes WHERE poll_id = $1 GROUP BY option_index")
+        .bind(poll_id)
+        .fetch_all(&*pool)
+        .await?;
+    let mut counts = vec![0; option_count];
+    for r in rows {
+        let (index, count): (i64, i64) = (r.get(0), r.get(1));
+        if let Some(c) = counts.get_mut(index as usize) { *c = count; }
+    }
+    Ok(counts)
+}
+
+/// Articles containing any of `keywords` (three characters or more), best match first.
+pub async fn search_related_kb_articles(guild_id: i64, keywords: &[String], limit: i64) -> Result<Vec<KbArticle>> {
+    if keywords.is_empty() { return Ok(Vec::new()); }
+    let pool = pool();
+    let rows = match backend() {
+        Backend::Sqlite => {
+            sqlx::query_as::<_, KbArticle>("SELECT a.id, a.guild_id, a.title, a.body, a.author_id, a.created_at, a.updated_at
+                FROM kb_fts JOIN kb_articles a ON a.id = kb_fts.rowid WHERE kb_fts MATCH $1 AND a.guild_id = $2 ORDER BY kb_fts.rank LIMIT $3")
+                .bind(keywords.iter().map(|k| fts_phrase(k)).collect::<Vec<_>>().join(" OR "))
+                .bind(guild_id)
+                .bind(limit)
+                .fetch_all(&*pool)
+                .await?
+        }
+        Backend::Postgres => {
+            let conditions = like_conditions("a.", keywords.len(), 2);
+            let sql = format!("SELECT a.id, a.guild_id, a.title, a.body, a.author_id, a.created_at, a.updated_at FROM kb_articles a
+                WHERE a.guild_id = $1 AND ({}) ORDER BY {} DESC, a.updated_at DESC LIMIT ${}", conditions.join(" OR "), match_count(&conditions), keywords.len() + 2);
+            let mut q = sqlx::query_as::<_, KbArticle>(&sql).bind(guild_id);
+            for keyword in keywords {
+                q = q.bind(like_pattern(keyword));
+            }
+            q.bind(limit).fetch_all(&*pool).await?
+        }
+    };
+    Ok(rows)
+}
+
+/// Store a help thread's question text for `search_related_help_threads`.
+pub async fn index_help_thread(thread_id: i64, title: &str, body: &str) -> Result<()> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE help_threads SET title = $1 WHERE thread_id = $2")
+        .bind(title)
+        .bind(thread_id)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query("INSERT INTO help_thread_fts (rowid, title, body) VALUES ($1, $2, $3)")
+        .bind(thread_id)
+        .bind(title)
+        .bind(body)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Returns (thread, question text) for earlier help threads containing any of `keywords`, best match first.
+pub async fn search_related_help_threads(guild_id: i64, keywords: &[String], exclude_thread: i64, since: i64, limit: i64) -> Result<Vec<(HelpThread, String)>> {
+    if keywords.is_empty() { return Ok(Vec::new()); }
+    let pool = pool();
+    let rows = match backend() {
+        Backend::Sqlite => {
+            sqlx::query("SELECT h.thread_id, h.guild_id, h.asker_id, h.created_at, h.first_answer_at, h.resolved_at, h.resolved_by, h.title, help_thread_fts.body
+                FROM help_thread_fts JOIN help_threads h ON h.thread_id = help_thread_fts.rowid
+                WHERE help_thread_fts MATCH $1 AND h.guild_id = $2 AND h.thread_id != $3 AND h.created_at >= $4 ORDER BY help_thread_fts.rank LIMIT $5")
+                .bind(keywords.iter().map(|k| fts_phrase(k)).collect::<Vec<_>>().join(" OR "))
+                .bind(guild_id)
+                .bind(exclude_thread)
+                .bind(since)
+                .bind(limit)
+                .fetch_all(&*pool)
+                .await?
+        }
+        Backend::Postgres => {
+            let conditions = like_conditions("f.", keywords.len(), 5);
+            let sql = format!("SELECT h.thread_id, h.guild_id, h.asker_id, h.created_at, h.first_answer_at, h.resolved_at, h.resolved_by, h.title, f.body
+                FROM help_thread_fts f JOIN help_threads h ON h.thread_id = f.rowid
+                WHERE h.guild_id = $1 AND h.thread_id != $2 AND h.created_at >= $3 AND ({}) ORDER BY {} DESC, h.created_at DESC LIMIT $4",
+                conditions.join(" OR "), match_count(&conditions));
+            let mut q = sqlx::query(&sql).bind(guild_id).bind(exclude_thread).bind(since).bind(limit);
+            for keyword in keywords {
+                q = q.bind(like_pattern(keyword));
+            }
+            q.fetch_all(&*pool).await?
+        }
+    };
+    Ok(rows.iter().map(|r| (HelpThread {
+        thread_id: r.get(0),
+        guild_id: r.get(1),
+        asker_id: r.get(2),
+        created_at: r.get(3),
+        first_answer_at: r.get(4),
+        resolved_at: r.get(5),
+        resolved_by: r.get(6),
+        title: r.get(7),
+    }, r.get::<String, _>(8))).collect())
+}
+
+pub async fn add_scheduled_job(guild_id: i64, kind: &str, user_id: i64, channel_id: i64, message: &str, cron: Option<&str>, next_run_at: i64, created_at: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO scheduled_jobs (guild_id, kind, user_id, channel_id, message, cron, next_run_at, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id")
+        .bind(guild_id)
+        .bind(kind)
+        .bind(user_id)
+        .bind(channel_id)
+        .bind(message)
+        .bind(cron)
+        .bind(next_run_at)
+        .bind(created_at)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn get_due_scheduled_jobs(now: i64) -> Result<Vec<ScheduledJob>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, ScheduledJob>("SELECT id, guild_id, kind, user_id, channel_id, message, cron, next_run_at, created_at FROM scheduled_jobs WHERE next_run_at <= $1 ORDER BY next_run_at")
+        .bind(now)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Jobs of `kind` in a guild, limited to one user's when `user_id` is set, soonest first.
+pub async fn get_scheduled_jobs(guild_id: i64, kind: &str, user_id: Option<i64>) -> Result<Vec<ScheduledJob>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, ScheduledJob>("SELECT id, guild_id, kind, user_id, channel_id, message, cron, next_run_at, created_at FROM scheduled_jobs
+        WHERE guild_id = $1 AND kind = $2 AND ($3 IS NULL OR user_id = $4) ORDER BY next_run_at")
+        .bind(guild_id)
+        .bind(kind)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn reschedule_job(id: i64, next_run_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE scheduled_jobs SET next_run_at = $1 WHERE id = $2")
+        .bind(next_run_at)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Delete a job of `kind` in a guild, only if it belongs to `user_id` when that is set.
+pub async fn remove_scheduled_job(guild_id: i64, kind: &str, id: i64, user_id: Option<i64>) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM scheduled_jobs WHERE id = $1 AND guild_id = $2 AND kind = $3 AND ($4 IS NULL OR user_id = $5)")
+        .bind(id)
+        .bind(guild_id)
+        .bind(kind)
+        .bind(user_id)
+        .bind(user_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_prompt_settings(guild_id: i64) -> Result<Option<PromptSettings>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, PromptSettings>("SELECT guild_id, channel_id, post_hour, last_posted, last_winner_week FROM prompt_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn get_all_prompt_settings() -> Result<Vec<PromptSettings>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, PromptSettings>("SELECT guild_id, channel_id, post_hour, last_posted, last_winner_week FROM prompt_settings")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn set_prompt_channel(guild_id: i64, channel_id: i64, post_hour: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO prompt_settings (guild_id, channel_id, post_hour) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id) DO UPDATE SET channel_id=excluded.channel_id, post_hour=excluded.post_hour")
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(post_hour)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_prompt_settings(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM prompt_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn mark_prompt_posted(guild_id: i64, day: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE prompt_settings SET last_posted = $1 WHERE guild_id = $2")
+        .bind(day)
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn mark_prompt_winner_week(guild_id: i64, week: &str) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE prompt_settings SET last_winner_week = $1 WHERE guild_id = $2")
+        .bind(week)
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_prompt(guild_id: i64, text: &str, created_by: i64, created_at: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO prompts (guild_id, text, created_by, created_at) VALUES ($1, $2, $3, $4) RETURNING id")
+        .bind(guild_id)
+        .bind(text)
+        .bind(created_by)
+        .bind(created_at)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn get_prompts(guild_id: i64) -> Result<Vec<Prompt>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, Prompt>("SELECT id, guild_id, text, used_count, last_used_at FROM prompts WHERE guild_id = $1 ORDER BY id")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// The guild's least recently used prompt, never-used ones first and oldest first among ties.
+pub async fn next_prompt(guild_id: i64) -> Result<Option<Prompt>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, Prompt>("SELECT id, guild_id, text, used_count, last_used_at FROM prompts WHERE guild_id = $1
+        ORDER BY COALESCE(last_used_at, 0), id LIMIT 1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn mark_prompt_used(id: i64, used_at: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE prompts SET used_count = used_count + 1, last_used_at = $1 WHERE id = $2")
+        .bind(used_at)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_prompt(guild_id: i64, id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM prompts WHERE guild_id = $1 AND id = $2")
+        .bind(guild_id)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn add_prompt_post(post: &PromptPost) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO prompt_posts (message_id, guild_id, channel_id, thread_id, prompt, posted_at) VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(post.message_id)
+        .bind(post.guild_id)
+        .bind(post.channel_id)
+        .bind(post.thread_id)
+        .bind(&post.prompt)
+        .bind(post.posted_at)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Prompts posted in `[since, until)`, oldest first.
+pub async fn get_prompt_posts_between(guild_id: i64, since: i64, until: i64) -> Result<Vec<PromptPost>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, PromptPost>("SELECT message_id, guild_id, channel_id, thread_id, prompt, posted_at FROM prompt_posts
+        WHERE guild_id = $1 AND posted_at >= $2 AND posted_at < $3 ORDER BY posted_at")
+        .bind(guild_id)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Count a message and add `amount` XP unless the member earned XP within the last
+/// `cooldown` seconds. Returns the member's XP before and after.
+pub async fn add_user_xp(guild_id: i64, user_id: i64, amount: i64, now: i64, cooldown: i64) -> Result<(i64, i64)> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    let row = sqlx::query("SELECT xp, last_xp_at FROM user_xp WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id)
+        .bind(user_id)
+        .fetch_optional(&mut tx)
+        .await?;
+    let (before, last_xp_at) = row.map(|r| (r.get::<i64, _>(0), r.get::<i64, _>(1))).unwrap_or((0, 0));
+    let earns = last_xp_at <= now - cooldown;
+    let after = if earns { before + amount } else { before };
+    sqlx::query("INSERT INTO user_xp (guild_id, user_id, xp, message_count, last_xp_at) VALUES ($1, $2, $3, 1, $4)
+        ON CONFLICT(guild_id, user_id) DO UPDATE SET xp = excluded.xp, message_count = user_xp.message_count + 1, last_xp_at = excluded.last_xp_at")
+        .bind(guild_id)
+        .bind(user_id)
+        .bind(after)
+        .bind(if earns { now } else { last_xp_at })
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok((before, after))
+}
+
+pub async fn get_user_xp(guild_id: i64, user_id: i64) -> Result<Option<UserXp>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, UserXp>("SELECT guild_id, user_id, xp, message_count, last_xp_at FROM user_xp WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id)
+        .bind(user_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+/// 1-based position of a member with `xp` on the guild's leaderboard.
+pub async fn get_xp_rank(guild_id: i64, xp: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("SELECT COUNT(*) FROM user_xp WHERE guild_id = $1 AND xp > $2")
+        .bind(guild_id)
+        .bind(xp)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0) + 1)
+}
+
+/// The guild's members with the most XP, skipping the first `offset`, plus how many have any.
+pub async fn get_xp_leaderboard(guild_id: i64, limit: i64, offset: i64) -> Result<(Vec<UserXp>, i64)> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, UserXp>("SELECT guild_id, user_id, xp, message_count, last_xp_at FROM user_xp WHERE guild_id = $1 AND xp > 0
+        ORDER BY xp DESC, user_id LIMIT $2 OFFSET $3")
+        .bind(guild_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&*pool)
+        .await?;
+    let total = sqlx::query("SELECT COUNT(*) FROM user_xp WHERE guild_id = $1 AND xp > 0")
+        .bind(guild_id)
+        .fetch_one(&*pool)
+        .await?;
+    Ok((rows, total.get::<i64, _>(0)))
+}
+
+pub async fn get_leveling_settings(guild_id: i64) -> Result<LevelingSettings> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, LevelingSettings>("SELECT guild_id, announce, channel_id FROM leveling_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or(LevelingSettings { guild_id, announce: true, channel_id: None }))
+}
+
+pub async fn set_leveling_announce(guild_id: i64, announce: bool, channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO leveling_settings (guild_id, announce, channel_id) VALUES ($1, $2, $3)
+        ON CONFLICT(guild_id) DO UPDATE SET announce=excluded.announce, channel_id=excluded.channel_id")
+        .bind(guild_id)
+        .bind(announce)
+        .bind(channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_disabled_modules(guild_id: i64) -> Result<Vec<String>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT module FROM disabled_modules WHERE guild_id = $1 ORDER BY module")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<String, _>(0)).collect())
+}
+
+/// Turn `module` off (`enabled` false) or back on in a guild. Returns whether anything changed.
+pub async fn set_module_enabled(guild_id: i64, module: &str, enabled: bool, user_id: i64, now: i64) -> Result<bool> {
+    let pool = pool();
+    let res = if enabled {
+        sqlx::query("DELETE FROM disabled_modules WHERE guild_id = $1 AND module = $2")
+            .bind(guild_id)
+            .bind(module)
+            .execute(&*pool)
+            .await?
+    } else {
+        sqlx::query("INSERT INTO disabled_modules (guild_id, module, disabled_by, disabled_at) VALUES ($1, $2, $3, $4) ON CONFLICT(guild_id, module) DO NOTHING")
+            .bind(guild_id)
+            .bind(module)
+            .bind(user_id)
+            .bind(now)
+            .execute(&*pool)
+            .await?
+    };
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_antiraid_settings(guild_id: i64) -> Result<AntiraidSettings> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, AntiraidSettings>("SELECT guild_id, is_enabled, joins_per_minute, pause_minutes, raise_verification, kick_account_age_days, alert_channel_id, raid_until, previous_verification FROM antiraid_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or_else(|| AntiraidSettings::default_for(guild_id)))
+}
+
+pub async fn set_antiraid_detection(guild_id: i64, is_enabled: bool, joins_per_minute: i64, pause_minutes: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO antiraid_settings (guild_id, is_enabled, joins_per_minute, pause_minutes) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id) DO UPDATE SET is_enabled=excluded.is_enabled, joins_per_minute=excluded.joins_per_minute, pause_minutes=excluded.pause_minutes")
+        .bind(guild_id)
+        .bind(is_enabled)
+        .bind(joins_per_minute)
+        .bind(pause_minutes)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_antiraid_response(guild_id: i64, raise_verification: bool, kick_account_age_days: i64, alert_channel_id: Option<i64>) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO antiraid_settings (guild_id, raise_verification, kick_account_age_days, alert_channel_id) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(guild_id) DO UPDATE SET raise_verification=excluded.raise_verification, kick_account_age_days=excluded.kick_account_age_days, alert_channel_id=excluded.alert_channel_id")
+        .bind(guild_id)
+        .bind(raise_verification)
+        .bind(kick_account_age_days)
+        .bind(alert_channel_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Enter raid mode until `until`. False when the guild is already in raid mode, so only one
+/// of several simultaneous joins acts on a burst.
+pub async fn start_raid(guild_id: i64, until: i64, now: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("UPDATE antiraid_settings SET raid_until = $1, previous_verification = NULL WHERE guild_id = $2 AND (raid_until IS NULL OR raid_until <= $3)")
+        .bind(until)
+        .bind(guild_id)
+        .bind(now)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn set_raid_previous_verification(guild_id: i64, level: i64) -> Result<()> {
+    let pool = pool();
+    sqlx::query("UPDATE antiraid_settings SET previous_verification = $1 WHERE guild_id = $2")
+        .bind(level)
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Guilds whose raid mode has run out but hasn't been wound down yet.
+pub async fn get_expired_raids(now: i64) -> Result<Vec<AntiraidSettings>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, AntiraidSettings>("SELECT guild_id, is_enabled, joins_per_minute, pause_minutes, raise_verification, kick_account_age_days, alert_channel_id, raid_until, previous_verification FROM antiraid_settings
+        WHERE raid_until IS NOT NULL AND raid_until <= $1")
+        .bind(now)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Leave raid mode. False when the guild wasn't in it.
+pub async fn end_raid(guild_id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("UPDATE antiraid_settings SET raid_until = NULL, previous_verification = NULL WHERE guild_id = $1 AND raid_until IS NOT NULL")
+        .bind(guild_id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_spam_filter_settings(guild_id: i64) -> Result<SpamFilterSettings> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, SpamFilterSettings>("SELECT guild_id, is_enabled, action, duplicate_limit, mention_limit, emoji_limit, caps_percent, timeout_minutes FROM spam_filter_settings WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row.unwrap_or_else(|| SpamFilterSettings::default_for(guild_id)))
+}
+
+pub async fn set_spam_filter_settings(settings: &SpamFilterSettings) -> Result<()> {
+    let pool = pool();
+    sqlx::query("INSERT INTO spam_filter_settings (guild_id, is_enabled, action, duplicate_limit, mention_limit, emoji_limit, caps_percent, timeout_minutes) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT(guild_id) DO UPDATE SET is_enabled=excluded.is_enabled, action=excluded.action, duplicate_limit=excluded.duplicate_limit, mention_limit=excluded.mention_limit,
+        emoji_limit=excluded.emoji_limit, caps_percent=excluded.caps_percent, timeout_minutes=excluded.timeout_minutes")
+        .bind(settings.guild_id)
+        .bind(settings.is_enabled)
+        .bind(&settings.action)
+        .bind(settings.duplicate_limit)
+        .bind(settings.mention_limit)
+        .bind(settings.emoji_limit)
+        .bind(settings.caps_percent)
+        .bind(settings.timeout_minutes)
+        .execute(&*pool)
+        .await?;
+    Ok(())
+}
+
+/// Replace the cached domains from one feed in a single transaction, so readers never see it half written.
+pub async fn replace_phishing_domains(source: &str, domains: &[String], fetched_at: i64) -> Result<()> {
+    let pool = pool();
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM phishing_domains WHERE source = $1")
+        .bind(source)
+        .execute(&mut tx)
+        .await?;
+    for domain in domains {
+        sqlx::query("INSERT INTO phishing_domains (domain, source, fetched_at) VALUES ($1, $2, $3) ON CONFLICT(source, domain) DO NOTHING")
+            .bind(domain)
+            .bind(source)
+            .bind(fetched_at)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Every cached domain across all feeds.
+pub async fn get_phishing_domains() -> Result<Vec<String>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT DISTINCT domain FROM phishing_domains")
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<String, _>(0)).collect())
+}
+
+pub async fn add_link_allowlist(guild_id: i64, domain: &str, added_by: i64, added_at: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("INSERT INTO link_allowlist (guild_id, domain, added_by, added_at) VALUES ($1, $2, $3, $4) ON CONFLICT(guild_id, domain) DO NOTHING")
+        .bind(guild_id)
+        .bind(domain)
+        .bind(added_by)
+        .bind(added_at)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn remove_link_allowlist(guild_id: i64, domain: &str) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM link_allowlist WHERE guild_id = $1 AND domain = $2")
+        .bind(guild_id)
+        .bind(domain)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_link_allowlist(guild_id: i64) -> Result<Vec<String>> {
+    let pool = pool();
+    let rows = sqlx::query("SELECT domain FROM link_allowlist WHERE guild_id = $1 ORDER BY domain")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.iter().map(|r| r.get::<String, _>(0)).collect())
+}
+
+pub async fn add_word_filter_rule(guild_id: i64, pattern: &str, is_regex: bool, action: &str, created_by: i64, created_at: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO word_filter_rules (guild_id, pattern, is_regex, action, created_by, created_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id")
+        .bind(guild_id)
+        .bind(pattern)
+        .bind(is_regex)
+        .bind(action)
+        .bind(created_by)
+        .bind(created_at)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn remove_word_filter_rule(guild_id: i64, id: i64) -> Result<bool> {
+    let pool = pool();
+    let res = sqlx::query("DELETE FROM word_filter_rules WHERE guild_id = $1 AND id = $2")
+        .bind(guild_id)
+        .bind(id)
+        .execute(&*pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// A guild's rules in the order they are checked.
+pub async fn get_word_filter_rules(guild_id: i64) -> Result<Vec<WordFilterRule>> {
+    let pool = pool();
+    let rows = sqlx::query_as::<_, WordFilterRule>("SELECT id, guild_id, pattern, is_regex, action FROM word_filter_rules WHERE guild_id = $1 ORDER BY id")
+        .bind(guild_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Returns the report's id, which makes up the error code.
+pub async fn record_error_report(command: &str, guild_id: Option<i64>, channel_id: i64, user_id: i64, options: &str, error: &str, created_at: i64) -> Result<i64> {
+    let pool = pool();
+    let row = sqlx::query("INSERT INTO error_reports (command, guild_id, channel_id, user_id, options, error, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id")
+        .bind(command)
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(user_id)
+        .bind(options)
+        .bind(error)
+        .bind(created_at)
+        .fetch_one(&*pool)
+        .await?;
+    Ok(row.get::<i64, _>(0))
+}
+
+pub async fn get_error_report(id: i64) -> Result<Option<ErrorReport>> {
+    let pool = pool();
+    let row = sqlx::query_as::<_, ErrorReport>("SELECT id, command, guild_id, channel_id, user_id, options, error, created_at FROM error_reports WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&*pool)
+        .await?;
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    // These run on whichever backend the suite uses, so with TEST_DATABASE_URL they cover the
+    // statements that differ between SQLite and PostgreSQL.
+
+    #[test]
+    fn upserts_replace_and_ignore() {
+        testing::run(async {
+            let guild_id = testing::id() as i64;
+            let channel_id = testing::id() as i64;
+            add_temp_voice_channel(channel_id, guild_id, 1).await.unwrap();
+            add_temp_voice_channel(channel_id, guild_id, 2).await.unwrap();
+            assert_eq!(get_temp_voice_owner(channel_id).await.unwrap(), Some(2));
+
+            add_prune_log_entry(7, guild_id, 1, 10, "kick", false, 100).await.unwrap();
+            add_prune_log_entry(7, guild_id, 1, 10, "kick", true, 100).await.unwrap();
+            let log = get_prune_log(guild_id, 0).await.unwrap();
+            assert_eq!(log.len(), 1);
+            assert!(log[0].succeeded);
+
+            assert!(record_first_message(guild_id, 10, channel_id, 1, 100).await.unwrap());
+            assert!(!record_first_message(guild_id, 10, channel_id, 2, 200).await.unwrap());
+        })
+    }
+
+    #[test]
+    fn save_setup_writes_every_setting() {
+        testing::run(async {
+            let guild_id = testing::id() as i64;
+            save_setup(guild_id, Some(11), 50, None, true, Some(13), true).await.unwrap();
+            let welcome = get_welcome_settings(guild_id).await.unwrap();
+            assert!(welcome.is_enabled);
+            assert_eq!((welcome.channel_id, welcome.member_increment), (Some(11), 50));
+            assert!(!get_leave_settings(guild_id).await.unwrap().is_enabled);
+            assert!(get_intro_auto_thread(guild_id).await.unwrap());
+            assert_eq!(get_modlog_channel(guild_id).await.unwrap(), Some(13));
+            assert!(get_daily_summary_enabled(guild_id).await.unwrap());
+        })
+    }
+
+    #[test]
+    fn flags_and_sums_decode() {
+        testing::run(async {
+            let guild_id = testing::id() as i64;
+            update_daily_summary_enabled(guild_id, true).await.unwrap();
+            assert!(get_daily_summary_enabled(guild_id).await.unwrap());
+            assert!(get_enabled_daily_summaries().await.unwrap().iter().any(|(g, _)| *g == guild_id));
+
+            record_member_event(guild_id, 10, "join", true, 100).await.unwrap();
+            assert_eq!(get_flagged_joins(guild_id, 0, 200).await.unwrap(), vec![10]);
+
+            record_message_activity(guild_id, 1, 10, "2024-01-01", 9).await.unwrap();
+            record_message_activity(guild_id, 1, 10, "2024-01-01", 9).await.unwrap();
+            assert_eq!(get_top_channels(guild_id, "2024-01-01", "2024-01-01", 5).await.unwrap(), vec![(1, 2)]);
+            assert_eq!(get_hourly_activity(guild_id, "2024-01-01", "2024-01-01").await.unwrap(), vec![(9, 2)]);
+
+            let message_id = testing::id() as i64;
+            track_highlight_message(message_id, guild_id, 1, 10, 100).await.unwrap();
+            adjust_highlight_score(message_id, -3).await.unwrap();
+            adjust_highlight_score(message_id, 2).await.unwrap();
+            assert_eq!(get_top_highlights(guild_id, 0, 0, 5).await.unwrap()[0].score, 2);
+
+            let poll_id = create_poll(guild_id, 1, 10, "?", &["a".to_string(), "b".to_string()], 100, 200).await.unwrap();
+            assert!(get_due_polls(300).await.unwrap().iter().any(|p| p.id == poll_id));
+            assert!(close_poll(poll_id).await.unwrap());
+            assert!(!close_poll(poll_id).await.unwrap());
+        })
+    }
+
+    #[test]
+    fn kb_search_matches_substrings() {
+        testing::run(async {
+            let guild_id = testing::id() as i64;
+            let id = add_kb_article(guild_id, "ロールの付け方", "サーバー設定からロールを選びます", 10, 100).await.unwrap();
+            add_kb_article(guild_id, "招待リンク", "招待を作ります", 10, 100).await.unwrap();
+
+            let found = search_kb_articles(guild_id, &["サーバー設定".to_string()], 5).await.unwrap();
+            assert_eq!(found.iter().map(|a| a.id).collect::<Vec<_>>(), vec![id]);
+            let short = search_kb_articles(guild_id, &["ロール".to_string(), "付け".to_string()], 5).await.unwrap();
+            assert_eq!(short.iter().map(|a| a.id).collect::<Vec<_>>(), vec![id]);
+            let related = search_related_kb_articles(guild_id, &["ロール".to_string(), "存在しない".to_string()], 5).await.unwrap();
+            assert_eq!(related.iter().map(|a| a.id).collect::<Vec<_>>(), vec![id]);
+
+            assert!(remove_kb_article(guild_id, id).await.unwrap());
+            assert!(search_kb_articles(guild_id, &["サーバー設定".to_string()], 5).await.unwrap().is_empty());
+        })
+    }
+
+    #[test]
+    fn advisory_lock_is_exclusive() {
+        testing::run(async {
+            if backend() != Backend::Postgres { return; }
+            let name = format!("test-{}", testing::id());
+            let mut lock = try_advisory_lock(&name).await.unwrap().expect("lock is free");
+            lock.check().await.unwrap();
+            assert!(try_advisory_lock(&name).await.unwrap().is_none());
+            lock.release().await.unwrap();
+            try_advisory_lock(&name).await.unwrap().expect("released lock is free").release().await.unwrap();
+        })
+    }
+
+    #[test]
+    fn readonly_query_refuses_writes() {
+        testing::run(async {
+            let (columns, rows) = run_readonly_query("SELECT 1 AS one", 5).await.unwrap();
+            assert_eq!(columns, vec!["one"]);
+            assert_eq!(rows, vec![vec!["1".to_string()]]);
+            assert!(run_readonly_query("DELETE FROM shortlinks RETURNING slug", 5).await.is_err());
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use once_cell::sync::Lazy;
+use serenity::model::application::interaction::{Interaction, InteractionResponseType};
+use serenity::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Interaction tokens are only valid for 15 minutes, so a redelivery can't arrive later than that.
+const SEEN_TTL: Duration = Duration::from_secs(15 * 60);
+/// The same user repeating the same state-changing action within this window is a double submit.
+const IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(10);
+/// Expired entries are swept once a map grows past this.
+const SWEEP_THRESHOLD: usize = 1024;
+
+/// Slash commands that change settings or spend a quota. Read-only commands may repeat freely.
+const STATEFUL_COMMANDS: &[&str] = &[
+    "config", "welcome", "leave-message", "modlog", "joingate", "role", "automod", "verify",
+    "tempvoice", "digest", "topic", "event", "privacy", "intro", "intro-template", "invite",
+    "setup", "imagegen", "sandbox", "backfill-snapshots", "triage", "status", "maintenance", "sync-commands", "onboarding", "rules", "crosspost", "shortlink", "highlights", "spotlight", "intro-channel", "import-insights", "prune",
+    "warn", "kick", "ban", "timeout", "role-decay", "contributor", "logsettings", "reactionrole", "kb", "poll", "remind", "schedule", "prompt", "module", "linkfilter", "filter",
+];
+/// Components whose click applies a change; pagination and wizard toggles are left alone.
+const STATEFUL_COMPONENTS: &[&str] = &["bulk_role_", "appeal_", "paste_", "prune_", "rr_"];
+
+fn is_stateful_component(custom_id: &str) -> bool {
+    STATEFUL_COMPONENTS.iter().any(|p| custom_id.starts_with(p)) || (custom_id.starts_with("setup:") && custom_id.ends_with(":save"))
+}
+
+static SEEN: Lazy<Mutex<HashMap<u64, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static KEYS: Lazy<Mutex<HashMap<u64, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Insert `key` unless it was seen within `ttl`; returns whether it was new.
+fn first_within(map: &Mutex<HashMap<u64, Instant>>, key: u64, ttl: Duration) -> bool {
+    let mut map = map.lock().unwrap();
+    let now = Instant::now();
+    if map.len() > SWEEP_THRESHOLD { map.retain(|_, t| now.duration_since(*t) < ttl); }
+    match map.get(&key) {
+        Some(t) if now.duration_since(*t) < ttl => false,
+        _ => { map.insert(key, now); true }
+    }
+}
+
+fn hash_of(parts: impl Hash) -> u64 {
+    let mut h = DefaultHasher::new();
+    parts.hash(&mut h);
+    h.finish()
+}
+
+/// Idempotency key for a state-changing interaction: who did what with which inputs.
+fn idempotency_key(interaction: &Interaction) -> Option<u64> {
+    match interaction {
+        Interaction::ApplicationCommand(c) if STATEFUL_COMMANDS.contains(&c.data.name.as_str()) => {
+            Some(hash_of(("command", c.user.id.0, c.guild_id.map(|g| g.0), &c.data.name, format!("{:?}", c.data.options))))
+        }
+        Interaction::MessageComponent(c) if is_stateful_component(&c.data.custom_id) => {
+            Some(hash_of(("component", c.user.id.0, c.message.id.0, &c.data.custom_id, &c.data.values)))
+        }
+        Interaction::ModalSubmit(m) => Some(hash_of(("modal", m.user.id.0, &m.data.custom_id, format!("{:?}", m.data.components)))),
+        _ => None,
+    }
+}
+
+/// Drop redelivered interactions and answer double submits. Returns true when the
+/// interaction must not be dispatched.
+pub async fn intercept(ctx: &Context, interaction: &Interaction) -> bool {
+    if !first_within(&SEEN, interaction.id().0, SEEN_TTL) {
+        // The first delivery is answering it; a second response would fail anyway.
+        log::info!("dedup: dropped redelivered interaction {}", interaction.id().0);
+        return true;
+    }
+    let key = match idempotency_key(interaction) { Some(k) => k, None => return false };
+    if first_within(&KEYS, key, IDEMPOTENCY_WINDOW) { return false; }
+
+    let msg = "同じ操作はすでに受け付けています。しばらく待ってから結果をご確認ください。";
+    let result = match interaction {
+        Interaction::ApplicationCommand(c) => c.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await,
+        Interaction::MessageComponent(c) => c.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await,
+        Interaction::ModalSubmit(m) => m.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await,
+        _ => Ok(()),
+    };
+    if let Err(e) = result { log::warn!("dedup: failed to reply to duplicate: {}", e); }
+    true
+}
@@ -0,0 +1,351 @@
+use anyhow::Result;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::channel::{GuildChannel, PermissionOverwrite, PermissionOverwriteType};
+use serenity::model::guild::Role;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::model::permissions::Permissions;
+use serenity::prelude::GatewayIntents;
+use std::collections::HashMap;
+
+use crate::commands::{Command, Defer, Invocation};
+use crate::db;
+use crate::intents;
+use crate::modules;
+use crate::permissions;
+
+/// Permissions for posting embeds with a chart attached.
+fn post_permissions() -> Permissions {
+    Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS | Permissions::ATTACH_FILES
+}
+
+/// What the bot can see and do in one guild, fetched once per `/diagnose`.
+struct BotView {
+    user_id: UserId,
+    everyone: RoleId,
+    roles: HashMap<RoleId, Role>,
+    channels: HashMap<ChannelId, GuildChannel>,
+    member_roles: Vec<RoleId>,
+}
+
+impl BotView {
+    async fn fetch(http: &Http, guild_id: GuildId, user_id: UserId) -> Result<Self> {
+        let member = guild_id.member(http, user_id).await?;
+        Ok(BotView {
+            user_id,
+            everyone: RoleId(guild_id.0),
+            roles: guild_id.roles(http).await?,
+            channels: guild_id.channels(http).await?,
+            member_roles: member.roles,
+        })
+    }
+
+    fn guild_permissions(&self) -> Permissions {
+        let mut perms = self.roles.get(&self.everyone).map(|r| r.permissions).unwrap_or_else(Permissions::empty);
+        for id in &self.member_roles {
+            if let Some(role) = self.roles.get(id) { perms |= role.permissions; }
+        }
+        if perms.administrator() { Permissions::all() } else { perms }
+    }
+
+    fn channel_permissions(&self, channel: &GuildChannel) -> Permissions {
+        apply_overwrites(self.guild_permissions(), &channel.permission_overwrites, self.everyone, &self.member_roles, self.user_id)
+    }
+
+    /// The bot can only assign or remove roles below its highest one.
+    fn top_position(&self) -> i64 {
+        self.member_roles.iter().filter_map(|id| self.roles.get(id)).map(|r| r.position).max().unwrap_or(0)
+    }
+}
+
+/// Channel permissions the way Discord resolves them: @everyone's overwrite, then every role
+/// overwrite (denies before allows), then the member's own. Administrators skip overwrites.
+fn apply_overwrites(base: Permissions, overwrites: &[PermissionOverwrite], everyone: RoleId, roles: &[RoleId], user: UserId) -> Permissions {
+    if base.administrator() { return Permissions::all(); }
+    let mut perms = base;
+    if let Some(ow) = overwrites.iter().find(|o| o.kind == PermissionOverwriteType::Role(everyone)) {
+        perms = (perms & !ow.deny) | ow.allow;
+    }
+    let (mut deny, mut allow) = (Permissions::empty(), Permissions::empty());
+    for ow in overwrites {
+        if let PermissionOverwriteType::Role(id) = ow.kind {
+            if id != everyone && roles.contains(&id) { deny |= ow.deny; allow |= ow.allow; }
+        }
+    }
+    perms = (perms & !deny) | allow;
+    if let Some(ow) = overwrites.iter().find(|o| o.kind == PermissionOverwriteType::Member(user)) {
+        perms = (perms & !ow.deny) | ow.allow;
+    }
+    // Without View Channel nothing else in the channel works either.
+    if perms.view_channel() { perms } else { Permissions::empty() }
+}
+
+/// One line of the checklist, with how to fix it when it failed.
+struct Check {
+    passed: bool,
+    text: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(text: impl ToString) -> Self {
+        Check { passed: true, text: text.to_string(), hint: None }
+    }
+
+    fn fail(text: impl ToString, hint: impl ToString) -> Self {
+        Check { passed: false, text: text.to_string(), hint: Some(hint.to_string()) }
+    }
+
+    fn line(&self) -> String {
+        match &self.hint {
+            Some(hint) => format!("{} {}\n　💡 {}", if self.passed { "✅" } else { "❌" }, self.text, hint),
+            None => format!("{} {}", if self.passed { "✅" } else { "❌" }, self.text),
+        }
+    }
+}
+
+/// Names of the permissions in `required` that are missing from `granted`.
+fn missing_permissions(granted: Permissions, required: Permissions) -> Option<String> {
+    let missing = required & !granted;
+    if missing.is_empty() { None } else { Some(missing.get_permission_names().join(", ")) }
+}
+
+fn check_intents(required: GatewayIntents, what: &str) -> Check {
+    let missing = intents::missing(required);
+    if missing.is_empty() {
+        Check::pass(format!("{}に必要なGateway Intentが有効です", what))
+    } else {
+        Check::fail(format!("{}に必要なGateway Intent ({}) が無効です", what, missing.join(", ")),
+            format!("Developer Portal の Bot 設定で有効にし、環境変数 PRIVILEGED_INTENTS に {} を追加して再起動してください", missing.join(",")))
+    }
+}
+
+fn check_guild_permissions(view: &BotView, required: Permissions, what: &str) -> Check {
+    match missing_permissions(view.guild_permissions(), required) {
+        None => Check::pass(format!("{}の権限があります", what)),
+        Some(names) => Check::fail(format!("{}に必要な権限がありません: {}", what, names), "サーバー設定 → ロール で、Botのロールに権限を付与してください"),
+    }
+}
+
+fn check_channel(view: &BotView, channel_id: Option<i64>, required: Permissions, what: &str) -> Check {
+    let id = match channel_id {
+        Some(id) => ChannelId(id as u64),
+        None => return Check::fail(format!("{}のチャンネルが設定されていません", what), "設定コマンドでチャンネルを指定してください"),
+    };
+    let channel = match view.channels.get(&id) {
+        Some(c) => c,
+        None => return Check::fail(format!("{}のチャンネル ({}) が見つかりません", what, id.0), "チャンネルが削除されています。設定し直してください"),
+    };
+    match missing_permissions(view.channel_permissions(channel), required) {
+        None => Check::pass(format!("{}: <#{}> に投稿できます", what, id.0)),
+        Some(names) => Check::fail(format!("{}: <#{}> で権限が不足しています: {}", what, id.0, names),
+            format!("<#{}> の チャンネル設定 → 権限 で、Botのロールに許可してください", id.0)),
+    }
+}
+
+fn check_role(view: &BotView, role_id: i64, what: &str) -> Check {
+    let id = RoleId(role_id as u64);
+    let role = match view.roles.get(&id) {
+        Some(r) => r,
+        None => return Check::fail(format!("{}のロール ({}) が見つかりません", what, role_id), "ロールが削除されています。設定し直してください"),
+    };
+    if !view.guild_permissions().manage_roles() {
+        return Check::fail(format!("{}: ロールの管理権限がありません", what), "Botのロールに「ロールの管理」を付与してください");
+    }
+    if role.position >= view.top_position() {
+        return Check::fail(format!("{}: <@&{}> がBotのロールより上にあります", what, role_id), format!("サーバー設定 → ロール で、Botのロールを <@&{}> より上に移動してください", role_id));
+    }
+    Check::pass(format!("{}: <@&{}> を付与できます", what, role_id))
+}
+
+async fn module_disabled(guild_id: GuildId, module: &str) -> Option<(String, Vec<Check>)> {
+    if modules::is_enabled(guild_id, module).await { return None; }
+    Some((module.to_string(), vec![Check::fail("モジュールが無効です", format!("`/module enable name:{}` で有効にできます", module))]))
+}
+
+/// Checklists for every feature this guild has configured, as (feature, checks).
+async fn run_checks(view: &BotView, guild_id: GuildId) -> Result<Vec<(String, Vec<Check>)>> {
+    let gid = guild_id.0 as i64;
+    let mut sections = Vec::new();
+
+    let welcome = db::get_welcome_settings(gid).await?;
+    let leave = db::get_leave_settings(gid).await?;
+    if welcome.is_enabled || leave.is_enabled {
+        match module_disabled(guild_id, "welcome").await {
+            Some(s) => sections.push(s),
+            None => {
+                let mut checks = vec![check_intents(GatewayIntents::GUILD_MEMBERS, "参加・退室イベントの受信")];
+                if welcome.is_enabled { checks.push(check_channel(view, welcome.channel_id, post_permissions(), "ウェルカムメッセージ")); }
+                if leave.is_enabled { checks.push(check_channel(view, leave.channel_id, Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES, "退室メッセージ")); }
+                if let Some(announce) = db::get_milestone_announce_channel(gid).await? {
+                    checks.push(check_channel(view, Some(announce), post_permissions(), "節目のアナウンス"));
+                }
+                sections.push(("welcome".to_string(), checks));
+            }
+        }
+    }
+
+    if let Some(channel) = db::get_modlog_channel(gid).await? {
+        let checks = vec![check_channel(view, Some(channel), Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS, "モデレーションログ")];
+        sections.push(module_disabled(guild_id, "modlog").await.unwrap_or_else(|| ("modlog".to_string(), checks)));
+    }
+
+    if let Some(settings) = db::get_message_log_settings(gid).await? {
+        let checks = vec![
+            check_intents(crate::logging::REQUIRED_INTENTS, "編集前・削除されたメッセージの内容"),
+            check_channel(view, Some(settings.channel_id), Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS, "メッセージログ"),
+        ];
+        sections.push(module_disabled(guild_id, "logging").await.unwrap_or_else(|| ("logging".to_string(), checks)));
+    }
+
+    let db::JoinGateSettings { min_account_age_days: min_age_days, autorole_id: autorole } = db::get_joingate_settings(gid).await?;
+    if min_age_days > 0 || autorole.is_some() {
+        let mut checks = vec![check_intents(GatewayIntents::GUILD_MEMBERS, "参加イベントの受信")];
+        if min_age_days > 0 { checks.push(check_guild_permissions(view, Permissions::KICK_MEMBERS, "新しいアカウントのキック")); }
+        if let Some(role) = autorole { checks.push(check_role(view, role, "自動ロール")); }
+        sections.push(module_disabled(guild_id, "joingate").await.unwrap_or_else(|| ("joingate".to_string(), checks)));
+    }
+
+    if let (Some(role), _) = db::get_verify_settings(gid).await? {
+        let checks = vec![check_role(view, role, "認証ロール")];
+        sections.push(module_disabled(guild_id, "verify").await.unwrap_or_else(|| ("verify".to_string(), checks)));
+    }
+
+    let antiraid = db::get_antiraid_settings(gid).await?;
+    if antiraid.is_enabled {
+        let mut checks = vec![check_intents(GatewayIntents::GUILD_MEMBERS, "参加の急増の検知")];
+        if antiraid.raise_verification { checks.push(check_guild_permissions(view, Permissions::MANAGE_GUILD, "認証レベルの引き上げ")); }
+        if antiraid.kick_account_age_days > 0 { checks.push(check_guild_permissions(view, Permissions::KICK_MEMBERS, "新しいアカウントのキック")); }
+        if let Some(channel) = antiraid.alert_channel_id {
+            checks.push(check_channel(view, Some(channel), Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS, "レイドの通知"));
+        }
+        sections.push(module_disabled(guild_id, "antiraid").await.unwrap_or_else(|| ("antiraid".to_string(), checks)));
+    }
+
+    let (invite_mode, _) = db::get_invite_filter_settings(gid).await?;
+    let spam = db::get_spam_filter_settings(gid).await?;
+    if invite_mode != "off" || spam.is_enabled {
+        let mut checks = vec![
+            check_intents(GatewayIntents::MESSAGE_CONTENT, "メッセージ本文の確認"),
+            check_guild_permissions(view, Permissions::MANAGE_MESSAGES, "メッセージの削除"),
+        ];
+        if spam.is_enabled && spam.action == "timeout" { checks.push(check_guild_permissions(view, Permissions::MODERATE_MEMBERS, "スパム投稿者のタイムアウト")); }
+        sections.push(module_disabled(guild_id, "automod").await.unwrap_or_else(|| ("automod".to_string(), checks)));
+    }
+
+    // The link filter works without any setup, so it is only reported while its module is on.
+    if modules::is_enabled(guild_id, "linkfilter").await {
+        sections.push(("linkfilter".to_string(), vec![
+            check_intents(crate::linkfilter::REQUIRED_INTENTS, "リンクの確認"),
+            check_guild_permissions(view, Permissions::MANAGE_MESSAGES, "危険なリンクの削除"),
+        ]));
+    }
+
+    if !db::get_word_filter_rules(gid).await?.is_empty() {
+        let checks = vec![
+            check_intents(crate::wordfilter::REQUIRED_INTENTS, "メッセージ本文の確認"),
+            check_guild_permissions(view, Permissions::MANAGE_MESSAGES, "メッセージの削除"),
+        ];
+        sections.push(module_disabled(guild_id, "wordfilter").await.unwrap_or_else(|| ("wordfilter".to_string(), checks)));
+    }
+
+    if let Some(channel) = db::get_intro_channel(gid).await? {
+        let checks = vec![
+            check_intents(crate::zikosyokai::REQUIRED_INTENTS, "自己紹介の検出"),
+            check_channel(view, Some(channel), Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::READ_MESSAGE_HISTORY, "自己紹介"),
+        ];
+        sections.push(module_disabled(guild_id, "zikosyokai").await.unwrap_or_else(|| ("zikosyokai".to_string(), checks)));
+    }
+
+    if let Some(hub) = db::get_tempvoice_hub(gid).await? {
+        let checks = vec![
+            check_channel(view, Some(hub), Permissions::VIEW_CHANNEL | Permissions::CONNECT, "一時ボイスの入口"),
+            check_guild_permissions(view, Permissions::MANAGE_CHANNELS | Permissions::MOVE_MEMBERS, "ボイスチャンネルの作成とメンバーの移動"),
+        ];
+        sections.push(module_disabled(guild_id, "tempvoice").await.unwrap_or_else(|| ("tempvoice".to_string(), checks)));
+    }
+
+    if let (Some(channel), _) = db::get_digest_settings(gid).await? {
+        let checks = vec![check_channel(view, Some(channel), post_permissions(), "月間レポート")];
+        sections.push(module_disabled(guild_id, "digest").await.unwrap_or_else(|| ("digest".to_string(), checks)));
+    }
+
+    Ok(sections)
+}
+
+pub struct DiagnoseCommand;
+
+#[async_trait]
+impl Command for DiagnoseCommand {
+    fn name(&self) -> &'static str { "diagnose" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("設定済みの機能に必要な権限・チャンネル・ロールがBotにあるか確認します")
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+
+        let view = BotView::fetch(&inv.ctx.http, guild_id, inv.ctx.cache.current_user_id()).await?;
+        let sections = run_checks(&view, guild_id).await?;
+        if sections.is_empty() { return inv.say("設定済みの機能がありません。`/setup` で初期設定を始められます。").await; }
+
+        let failed = sections.iter().flat_map(|(_, checks)| checks).filter(|c| !c.passed).count();
+        let mut embed = CreateEmbed::default();
+        embed.title("Botの診断結果");
+        embed.description(if failed == 0 { "すべての項目に問題はありません。".to_string() } else { format!("{}件の問題が見つかりました。💡 の手順で修正してください。", failed) });
+        for (feature, checks) in sections.iter() {
+            let value: String = checks.iter().map(|c| c.line()).collect::<Vec<_>>().join("\n");
+            embed.field(format!("`{}`", feature), value.chars().take(1024).collect::<String>(), false);
+        }
+        embed.color(if failed == 0 { serenity::utils::Colour::DARK_GREEN } else { serenity::utils::Colour::ORANGE });
+        embed.footer(|f| f.text("EvexBot | Diagnose"));
+        command.create_followup_message(&inv.ctx.http, |m| m.embed(|e| { *e = embed; e }).ephemeral(true)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overwrite(kind: PermissionOverwriteType, allow: Permissions, deny: Permissions) -> PermissionOverwrite {
+        PermissionOverwrite { allow, deny, kind }
+    }
+
+    #[test]
+    fn overwrites_apply_everyone_then_roles_then_member() {
+        let (everyone, bot_role, other_role, user) = (RoleId(1), RoleId(2), RoleId(3), UserId(10));
+        let base = Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES;
+        let overwrites = vec![
+            overwrite(PermissionOverwriteType::Role(everyone), Permissions::empty(), Permissions::SEND_MESSAGES),
+            overwrite(PermissionOverwriteType::Role(bot_role), Permissions::SEND_MESSAGES | Permissions::ATTACH_FILES, Permissions::empty()),
+            overwrite(PermissionOverwriteType::Role(other_role), Permissions::all(), Permissions::empty()),
+        ];
+        let perms = apply_overwrites(base, &overwrites, everyone, &[bot_role], user);
+        assert_eq!(perms, Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::ATTACH_FILES);
+
+        let with_member = [overwrites, vec![overwrite(PermissionOverwriteType::Member(user), Permissions::empty(), Permissions::ATTACH_FILES)]].concat();
+        assert!(!apply_overwrites(base, &with_member, everyone, &[bot_role], user).attach_files());
+    }
+
+    #[test]
+    fn hidden_channels_grant_nothing() {
+        let everyone = RoleId(1);
+        let overwrites = vec![overwrite(PermissionOverwriteType::Role(everyone), Permissions::empty(), Permissions::VIEW_CHANNEL)];
+        assert!(apply_overwrites(Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES, &overwrites, everyone, &[], UserId(10)).is_empty());
+        assert_eq!(apply_overwrites(Permissions::ADMINISTRATOR, &overwrites, everyone, &[], UserId(10)), Permissions::all());
+    }
+
+    #[test]
+    fn missing_permissions_are_named() {
+        assert_eq!(missing_permissions(post_permissions(), Permissions::SEND_MESSAGES), None);
+        assert_eq!(missing_permissions(Permissions::VIEW_CHANNEL, Permissions::VIEW_CHANNEL | Permissions::EMBED_LINKS).as_deref(), Some("Embed Links"));
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate, Utc};
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::chart;
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::external;
+use crate::growth;
+use crate::members_history;
+use crate::scheduler;
+use crate::stats;
+use crate::permissions;
+use crate::zikosyokai;
+
+const DIGEST_CHECK_INTERVAL_SECONDS: u64 = 3600;
+const TOP_N: i64 = 5;
+/// Weeks of joins the next-week estimate is based on.
+const JOIN_WEEKS: i64 = 8;
+/// z-score for the 80% range of next week's joins.
+const JOIN_INTERVAL_Z: f64 = 1.28;
+/// Suggested coverage spans the busiest hours that together hold this share of messages.
+const COVERAGE_SHARE: f64 = 0.5;
+/// message_activity hours are UTC; the suggestion is shown in JST.
+const JST_OFFSET_HOURS: i64 = 9;
+
+/// Start the monthly digest job. It checks hourly and posts on the 1st of each month.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("monthly-digest", Duration::from_secs(DIGEST_CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { send_due_digests(&http).await }
+    });
+}
+
+/// First and last day of the month preceding `today`.
+fn previous_month(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first_this_month = today.with_day(1).unwrap();
+    let last_prev = first_this_month.pred_opt().unwrap();
+    (last_prev.with_day(1).unwrap(), last_prev)
+}
+
+async fn send_due_digests(http: &Http) -> Result<()> {
+    let today = Utc::now().date_naive();
+    if today.day() != 1 { return Ok(()); }
+    let (start, end) = previous_month(today);
+    let month_key = start.format("%Y-%m").to_string();
+
+    for (guild_id, channel_id, last_sent) in db::get_enabled_digests().await? {
+        if last_sent.as_deref() == Some(month_key.as_str()) { continue; }
+        if let Err(e) = send_digest(http, GuildId(guild_id as u64), ChannelId(channel_id as u64), start, end).await {
+            log::warn!("digest: failed for guild {}: {}", guild_id, e);
+            continue;
+        }
+        db::mark_digest_sent(guild_id, &month_key).await?;
+    }
+    Ok(())
+}
+
+async fn build_digest(http: &Http, guild_id: GuildId, start: NaiveDate, end: NaiveDate) -> Result<(Vec<CreateEmbed>, chart::Rendered)> {
+    let gid = guild_id.0 as i64;
+    let since = start.to_string();
+    let until = end.to_string();
+
+    let join_dates = crate::member_cache::join_dates(http, guild_id).await?;
+    let (dates, counts) = stats::member_counts(&join_dates, start, end);
+    let size = chart::resolve(Some(gid), None, chart::WIDE).await.unwrap_or(chart::WIDE);
+    let growth_png = members_history::create_plot(&dates, &counts, size, chart::Locale::for_guild(Some(gid)).await)?;
+    let growth_png = chart::finish(Some(gid), growth_png, "digest_growth").await;
+    let start_count = counts.first().copied().unwrap_or(0);
+    let end_count = counts.last().copied().unwrap_or(0);
+    let joined = join_dates.iter().filter(|d| d.date() >= start && d.date() <= end).count();
+
+    let mut growth = CreateEmbed::default();
+    growth.title(format!("📊 {}年{}月の月間レポート", start.year(), start.month()));
+    growth.description(format!("{} 〜 {}", start, end));
+    growth.field("月初のメンバー数", start_count.to_string(), true);
+    growth.field("月末のメンバー数", end_count.to_string(), true);
+    growth.field("増減", format!("{:+}", end_count - start_count), true);
+    growth.field("今月参加して在籍中", format!("{}人", joined), true);
+    growth.image(growth_png.url());
+    growth.color(serenity::utils::Colour::BLURPLE);
+
+    let top_channels = db::get_top_channels(gid, &since, &until, TOP_N).await?;
+    let top_users = db::get_top_users(gid, &since, &until, TOP_N).await?;
+    let mut activity = CreateEmbed::default();
+    activity.title("💬 アクティビティ");
+    let channel_lines: Vec<String> = top_channels.iter().enumerate().map(|(i, (c, n))| format!("{}. <#{}> — {}件", i + 1, c, n)).collect();
+    let user_lines: Vec<String> = top_users.iter().enumerate().map(|(i, (u, n))| format!("{}. <@{}> — {}件", i + 1, u, n)).collect();
+    activity.field("よく使われたチャンネル", if channel_lines.is_empty() { "記録なし".to_string() } else { channel_lines.join("\n") }, false);
+    activity.field("よく発言したメンバー", if user_lines.is_empty() { "記録なし".to_string() } else { user_lines.join("\n") }, false);
+    activity.color(serenity::utils::Colour::DARK_GREEN);
+
+    let intros = match zikosyokai::intro_channel(Some(guild_id)).await? {
+        Some(channel) => db::count_channel_posters(gid, channel.0 as i64, &since, &until).await?,
+        None => 0,
+    };
+    let mut community = CreateEmbed::default();
+    community.title("👋 コミュニティ");
+    community.field("自己紹介を投稿したメンバー", format!("{}人", intros), true);
+    if let Some(lines) = external::summary_lines(gid, start, end).await? {
+        community.field("外部プラットフォーム", lines, false);
+    }
+    community.color(serenity::utils::Colour::GOLD);
+    community.footer(|f| f.text("EvexBot | Monthly Digest"));
+
+    let outlook = build_outlook(gid, &join_dates, &since, &until).await?;
+    Ok((vec![growth, activity, community, outlook], growth_png))
+}
+
+/// Mean and 80% range of weekly joins over the last `JOIN_WEEKS` weeks.
+fn expected_joins(join_dates: &[chrono::NaiveDateTime], today: NaiveDate) -> (f64, f64, f64) {
+    let weekly: Vec<f64> = (0..JOIN_WEEKS).map(|w| {
+        let (from, to) = (today - chrono::Duration::days(7 * (w + 1)), today - chrono::Duration::days(7 * w));
+        join_dates.iter().filter(|d| d.date() >= from && d.date() < to).count() as f64
+    }).collect();
+    let mean = weekly.iter().sum::<f64>() / weekly.len() as f64;
+    let sd = (weekly.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / (weekly.len() - 1) as f64).sqrt();
+    (mean, (mean - JOIN_INTERVAL_Z * sd).max(0.0), mean + JOIN_INTERVAL_Z * sd)
+}
+
+/// Busiest JST hours covering `COVERAGE_SHARE` of messages, merged into ranges like "20:00〜24:00".
+fn coverage_hours(hourly: &[(i64, i64)]) -> Option<String> {
+    let total: i64 = hourly.iter().map(|(_, c)| c).sum();
+    if total == 0 { return None; }
+    let mut by_count: Vec<(i64, i64)> = hourly.iter().map(|(h, c)| ((h + JST_OFFSET_HOURS) % 24, *c)).collect();
+    by_count.sort_by_key(|c| std::cmp::Reverse(c.1));
+    let mut chosen = [false; 24];
+    let mut covered = 0;
+    for (hour, count) in by_count {
+        if covered as f64 >= total as f64 * COVERAGE_SHARE { break; }
+        chosen[hour as usize] = true;
+        covered += count;
+    }
+    let mut ranges = Vec::new();
+    let mut hour = 0;
+    while hour < 24 {
+        if !chosen[hour] { hour += 1; continue; }
+        let start = hour;
+        while hour < 24 && chosen[hour] { hour += 1; }
+        ranges.push(format!("{}:00〜{}:00", start, hour));
+    }
+    Some(ranges.join(", "))
+}
+
+/// Derived suggestions: next milestone date, next week's joins and when moderators are most needed.
+async fn build_outlook(gid: i64, join_dates: &[chrono::NaiveDateTime], since: &str, until: &str) -> Result<CreateEmbed> {
+    let mut outlook = CreateEmbed::default();
+    outlook.title("🧭 今後の見通し");
+
+    let increment = db::get_welcome_settings(gid).await?.member_increment.max(1);
+    let current = join_dates.len() as i64;
+    let next = (current / increment + 1) * increment;
+    let milestone = match growth::predict_date_blocking(growth::Model::Trend, join_dates.to_vec(), next as usize).await? {
+        Some(d) => format!("{}人: {}頃", next, d),
+        None => format!("{}人: 予測できません", next),
+    };
+    outlook.field("次の節目", milestone, true);
+
+    let (mean, low, high) = expected_joins(join_dates, Utc::now().date_naive());
+    outlook.field("来週の参加見込み", format!("約{:.0}人 ({:.0}〜{:.0}人)", mean, low, high), true);
+
+    let coverage = coverage_hours(&db::get_hourly_activity(gid, since, until).await?).unwrap_or_else(|| "記録なし".to_string());
+    outlook.field("モデレーターを厚くしたい時間帯 (JST)", coverage, false);
+    outlook.color(serenity::utils::Colour::DARK_BLUE);
+    outlook.footer(|f| f.text(format!("参加見込みは直近{}週の80%範囲、時間帯はメッセージの{}%が集中する時間", JOIN_WEEKS, (COVERAGE_SHARE * 100.0) as i64)));
+    Ok(outlook)
+}
+
+async fn send_digest(http: &Http, guild_id: GuildId, channel_id: ChannelId, start: NaiveDate, end: NaiveDate) -> Result<()> {
+    let (embeds, chart) = build_digest(http, guild_id, start, end).await?;
+    channel_id.send_files(http, vec![(chart.bytes.as_slice(), chart.filename().as_str())], |m| m.set_embeds(embeds)).await?;
+    Ok(())
+}
+
+pub struct DigestCommand;
+
+#[async_trait]
+impl Command for DigestCommand {
+    fn name(&self) -> &'static str { "digest" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("月間サーバーレポートの設定").create_option(|o| {
+            o.name("action").description("enable|disable|preview").kind(CommandOptionType::String).required(true)
+        }).create_option(|o| {
+            o.name("channel").description("送信先チャンネル").kind(CommandOptionType::Channel).required(false)
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_digest_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_digest_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+    let action = command.data.options.iter().find(|o| o.name=="action").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+    let channel = command.data.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.clone()), _ => None });
+
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+
+    match action {
+        "enable" => {
+            let chan = match channel { Some(c) => c, None => { command.create_followup_message(&ctx.http, |m| m.content("ONにする場合はチャンネルを指定してください。" ).ephemeral(true)).await?; return Ok(()); } };
+            db::update_digest_channel(guild_id.0 as i64, Some(chan.id.0 as i64)).await?;
+            command.create_followup_message(&ctx.http, |m| m.content(format!("毎月1日に<#{}>へ月間レポートを送信します。", chan.id.0)).ephemeral(true)).await?;
+        }
+        "disable" => {
+            db::update_digest_channel(guild_id.0 as i64, None).await?;
+            command.create_followup_message(&ctx.http, |m| m.content("月間レポートを無効にしました!").ephemeral(true)).await?;
+        }
+        "preview" => {
+            let (start, end) = previous_month(Utc::now().date_naive());
+            let (embeds, chart) = build_digest(&ctx.http, guild_id, start, end).await?;
+            command.create_followup_message(&ctx.http, |m| m.add_file((chart.bytes.as_slice(), chart.filename().as_str())).add_embeds(embeds)).await?;
+        }
+        _ => { command.create_followup_message(&ctx.http, |m| m.content("enable、disable、previewのいずれかを指定してください。" ).ephemeral(true)).await?; }
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::builder::CreateEmbed;
+use serenity::model::channel::GuildChannel;
+use serenity::prelude::*;
+
+use crate::db;
+use crate::kb;
+
+const MAX_KEYWORDS: usize = 12;
+/// Candidates fetched per source before the keyword-overlap filter.
+const CANDIDATES: i64 = 10;
+const MAX_SUGGESTIONS: usize = 3;
+const THREAD_LOOKBACK_DAYS: i64 = 180;
+
+/// How many of `keywords` appear in `text`. A single shared word is usually coincidence, so
+/// suggestions need two unless the question only had one keyword to begin with.
+fn overlap(text: &str, keywords: &[String]) -> usize {
+    let text = text.to_lowercase();
+    keywords.iter().filter(|k| text.contains(k.as_str())).count()
+}
+
+/// Index a new help thread's question and reply with related KB articles and earlier threads, if any.
+pub async fn suggest(ctx: &Context, thread: &GuildChannel, body: &str) -> Result<()> {
+    let guild_id = thread.guild_id.0 as i64;
+    db::index_help_thread(thread.id.0 as i64, &thread.name, body).await?;
+
+    let keywords = kb::keywords(&format!("{}\n{}", thread.name, body), MAX_KEYWORDS);
+    let needed = keywords.len().min(2);
+    if needed == 0 { return Ok(()); }
+
+    let articles: Vec<db::KbArticle> = db::search_related_kb_articles(guild_id, &keywords, CANDIDATES).await?.into_iter()
+        .filter(|a| overlap(&format!("{}\n{}", a.title, a.body), &keywords) >= needed)
+        .take(MAX_SUGGESTIONS)
+        .collect();
+    let since = Utc::now().timestamp() - THREAD_LOOKBACK_DAYS * 86400;
+    let threads: Vec<db::HelpThread> = db::search_related_help_threads(guild_id, &keywords, thread.id.0 as i64, since, CANDIDATES).await?.into_iter()
+        .filter(|(t, body)| overlap(&format!("{}\n{}", t.title.as_deref().unwrap_or(""), body), &keywords) >= needed)
+        .map(|(t, _)| t)
+        .take(MAX_SUGGESTIONS)
+        .collect();
+    if articles.is_empty() && threads.is_empty() { return Ok(()); }
+
+    let mut embed = CreateEmbed::default();
+    embed.title("🔎 似た質問・記事が見つかりました");
+    if !articles.is_empty() {
+        embed.field("📚 回答集", articles.iter().map(|a| format!("`/kb show id:{}` {}", a.id, a.title)).collect::<Vec<_>>().join("\n"), false);
+    }
+    if !threads.is_empty() {
+        embed.field("💬 過去のスレッド", threads.iter().map(|t| format!("{} <#{}>", if t.resolved_at.is_some() { "✅" } else { "・" }, t.thread_id)).collect::<Vec<_>>().join("\n"), false);
+    }
+    embed.color(serenity::utils::Colour::BLURPLE);
+    embed.footer(|f| f.text("自動で検索した結果です。解決しない場合はそのまま質問を続けてください"));
+    thread.id.send_message(&ctx.http, |m| m.embed(|e| { *e = embed; e })).await?;
+    Ok(())
+}
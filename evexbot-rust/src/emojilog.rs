@@ -0,0 +1,166 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::channel::{Message, Reaction, ReactionType};
+use serenity::model::guild::{Emoji, Guild};
+use serenity::model::id::{EmojiId, GuildId, StickerId, UserId};
+use serenity::model::sticker::Sticker;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::modlog;
+use crate::report::Report;
+use crate::permissions;
+
+// Audit log action types (https://discord.com/developers/docs/resources/audit-log)
+const AUDIT_EMOJI_CREATE: u8 = 60;
+const AUDIT_EMOJI_DELETE: u8 = 62;
+const AUDIT_STICKER_CREATE: u8 = 90;
+const AUDIT_STICKER_DELETE: u8 = 92;
+
+/// The cache is already updated when the event fires, so keep our own copy to diff against.
+static EMOJI_STATE: Lazy<Mutex<HashMap<GuildId, HashMap<EmojiId, String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static STICKER_STATE: Lazy<Mutex<HashMap<GuildId, HashMap<StickerId, String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static CUSTOM_EMOJI_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<a?:\w+:(\d+)>").unwrap());
+
+pub async fn handle_guild_create(guild: &Guild) {
+    EMOJI_STATE.lock().await.insert(guild.id, guild.emojis.iter().map(|(id, e)| (*id, e.name.clone())).collect());
+    STICKER_STATE.lock().await.insert(guild.id, guild.stickers.iter().map(|(id, s)| (*id, s.name.clone())).collect());
+}
+
+async fn find_actor(http: &Http, guild_id: GuildId, action: u8, target_id: u64) -> Option<UserId> {
+    let logs = guild_id.audit_logs(http, Some(action), None, None, Some(10)).await.ok()?;
+    logs.entries.iter().find(|e| e.target_id == Some(target_id)).map(|e| e.user_id)
+}
+
+fn change_embed(title: &str, lines: Vec<String>, colour: serenity::utils::Colour) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(title);
+    embed.description(lines.join("\n"));
+    embed.color(colour);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Emoji Log"));
+    embed
+}
+
+pub async fn handle_emojis_update(ctx: &Context, guild_id: GuildId, current: &HashMap<EmojiId, Emoji>) -> Result<()> {
+    let previous = {
+        let mut state = EMOJI_STATE.lock().await;
+        state.insert(guild_id, current.iter().map(|(id, e)| (*id, e.name.clone())).collect())
+    };
+    // Without a baseline there is nothing to diff; the next update will be logged.
+    let previous = match previous { Some(p) => p, None => return Ok(()) };
+
+    let mut added = Vec::new();
+    for (id, emoji) in current.iter().filter(|(id, _)| !previous.contains_key(id)) {
+        let uploader = match &emoji.user { Some(u) => Some(u.id), None => find_actor(&ctx.http, guild_id, AUDIT_EMOJI_CREATE, id.0).await };
+        added.push(format!("{} `:{}:` 追加者: {}", emoji, emoji.name, uploader.map(|u| format!("<@{}>", u.0)).unwrap_or_else(|| "不明".to_string())));
+    }
+    let mut removed = Vec::new();
+    for (id, name) in previous.iter().filter(|(id, _)| !current.contains_key(id)) {
+        let actor = find_actor(&ctx.http, guild_id, AUDIT_EMOJI_DELETE, id.0).await;
+        removed.push(format!("`:{}:` 削除者: {}", name, actor.map(|u| format!("<@{}>", u.0)).unwrap_or_else(|| "不明".to_string())));
+    }
+
+    if !added.is_empty() { modlog::send(&ctx.http, guild_id, change_embed("絵文字が追加されました", added, serenity::utils::Colour::DARK_GREEN)).await?; }
+    if !removed.is_empty() { modlog::send(&ctx.http, guild_id, change_embed("絵文字が削除されました", removed, serenity::utils::Colour::RED)).await?; }
+    Ok(())
+}
+
+pub async fn handle_stickers_update(ctx: &Context, guild_id: GuildId, current: &HashMap<StickerId, Sticker>) -> Result<()> {
+    let previous = {
+        let mut state = STICKER_STATE.lock().await;
+        state.insert(guild_id, current.iter().map(|(id, s)| (*id, s.name.clone())).collect())
+    };
+    let previous = match previous { Some(p) => p, None => return Ok(()) };
+
+    let mut added = Vec::new();
+    for (id, sticker) in current.iter().filter(|(id, _)| !previous.contains_key(id)) {
+        let uploader = match &sticker.user { Some(u) => Some(u.id), None => find_actor(&ctx.http, guild_id, AUDIT_STICKER_CREATE, id.0).await };
+        added.push(format!("`{}` 追加者: {}", sticker.name, uploader.map(|u| format!("<@{}>", u.0)).unwrap_or_else(|| "不明".to_string())));
+    }
+    let mut removed = Vec::new();
+    for (id, name) in previous.iter().filter(|(id, _)| !current.contains_key(id)) {
+        let actor = find_actor(&ctx.http, guild_id, AUDIT_STICKER_DELETE, id.0).await;
+        removed.push(format!("`{}` 削除者: {}", name, actor.map(|u| format!("<@{}>", u.0)).unwrap_or_else(|| "不明".to_string())));
+    }
+
+    if !added.is_empty() { modlog::send(&ctx.http, guild_id, change_embed("スタンプが追加されました", added, serenity::utils::Colour::DARK_GREEN)).await?; }
+    if !removed.is_empty() { modlog::send(&ctx.http, guild_id, change_embed("スタンプが削除されました", removed, serenity::utils::Colour::RED)).await?; }
+    Ok(())
+}
+
+/// Count custom emoji usage in message content for /emojistats.
+pub async fn handle_message(message: &Message) -> Result<()> {
+    if message.author.bot { return Ok(()); }
+    let guild_id = match message.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    let now = Utc::now().timestamp();
+    for cap in CUSTOM_EMOJI_RE.captures_iter(&message.content) {
+        if let Ok(id) = cap[1].parse::<i64>() {
+            db::record_emoji_use(guild_id, id, now).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_reaction_add(reaction: &Reaction) -> Result<()> {
+    let guild_id = match reaction.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    if let ReactionType::Custom { id, .. } = &reaction.emoji {
+        db::record_emoji_use(guild_id, id.0 as i64, Utc::now().timestamp()).await?;
+    }
+    Ok(())
+}
+
+pub struct EmojiStatsCommand;
+
+#[async_trait]
+impl Command for EmojiStatsCommand {
+    fn name(&self) -> &'static str { "emojistats" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("絵文字の利用状況").create_option(|o| {
+            o.name("unused").description("最近使われていない絵文字を表示します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|s| s.name("days").description("この日数使われていない絵文字を表示 (デフォルト: 30)").kind(CommandOptionType::Integer).required(false))
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_emojistats_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_emojistats_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+
+    let days = command.data.options.first().and_then(|sub| sub.options.iter().find(|o| o.name=="days")).and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(30).clamp(1, 365);
+    let cutoff = Utc::now().timestamp() - days * 86400;
+
+    let usage: HashMap<i64, (i64, i64)> = db::get_emoji_usage(guild_id.0 as i64).await?.into_iter().map(|(id, uses, last)| (id, (uses, last))).collect();
+    let emojis = guild_id.emojis(&ctx.http).await?;
+    let mut unused: Vec<(Option<i64>, String)> = emojis.iter()
+        .filter_map(|e| match usage.get(&(e.id.0 as i64)) {
+            Some((_, last)) if *last >= cutoff => None,
+            Some((_, last)) => Some((Some(*last), format!("{} `:{}:` 最終使用: <t:{}:R>", e, e.name, last))),
+            None => Some((None, format!("{} `:{}:` 使用記録なし", e, e.name))),
+        })
+        .collect();
+    // never-used first, then the longest-unused
+    unused.sort_by_key(|(last, _)| last.unwrap_or(i64::MIN));
+
+    let report = Report::embed("unused_emojis.txt", format!("{}日間使われていない絵文字", days), serenity::utils::Colour::BLURPLE)
+        .footer(format!("{}個中{}個が削除候補", emojis.len(), unused.len()));
+    let report = if unused.is_empty() { report.line("すべての絵文字が使われています。") } else { report.lines(unused.into_iter().map(|(_, l)| l)) };
+    report.send(&ctx.http, command).await
+}
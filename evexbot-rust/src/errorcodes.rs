@@ -0,0 +1,128 @@
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption};
+
+use crate::commands::{Command, Defer, Invocation};
+use crate::db;
+use crate::report::Report;
+
+/// Letters of the command name kept in the code, e.g. `/welcome` -> `WEL`.
+const PREFIX_LENGTH: usize = 3;
+
+/// `E-WEL-013` style code for an error report: the command's prefix and the report's id.
+fn prefix(command: &str) -> String {
+    let letters: String = command.chars().filter(|c| c.is_ascii_alphanumeric()).take(PREFIX_LENGTH).collect();
+    if letters.is_empty() { "CMD".to_string() } else { letters.to_ascii_uppercase() }
+}
+
+pub fn format_code(command: &str, id: i64) -> String {
+    format!("E-{}-{:03}", prefix(command), id)
+}
+
+/// The report id in `code`. The `E-` and the prefix are optional, so `013` and `WEL-13`
+/// work as well.
+fn parse_code(code: &str) -> Option<i64> {
+    code.trim().rsplit('-').next()?.trim().parse().ok().filter(|id| *id > 0)
+}
+
+/// `name=value` pairs, with subcommand names as bare words.
+fn describe_options(options: &[CommandDataOption]) -> String {
+    options.iter().map(|o| match o.kind {
+        CommandOptionType::SubCommand | CommandOptionType::SubCommandGroup => {
+            let inner = describe_options(&o.options);
+            if inner.is_empty() { o.name.clone() } else { format!("{} {}", o.name, inner) }
+        }
+        _ => format!("{}={}", o.name, o.value.as_ref().map(|v| v.to_string()).unwrap_or_default()),
+    }).collect::<Vec<_>>().join(" ")
+}
+
+/// Store the context of a failed command and log it under its code. Returns the code, or
+/// None when the report couldn't be saved.
+pub async fn record(command: &ApplicationCommandInteraction, error: &anyhow::Error) -> Option<String> {
+    let name = &command.data.name;
+    let options = describe_options(&command.data.options);
+    let details = format!("{:?}", error);
+    let id = db::record_error_report(
+        name,
+        command.guild_id.map(|g| g.0 as i64),
+        command.channel_id.0 as i64,
+        command.user.id.0 as i64,
+        &options,
+        &details,
+        Utc::now().timestamp(),
+    ).await;
+    match id {
+        Ok(id) => {
+            let code = format_code(name, id);
+            log::warn!("commands: /{} failed [{}] guild={:?} user={} options={{{}}}: {:#}", name, code, command.guild_id.map(|g| g.0), command.user.id.0, options, error);
+            Some(code)
+        }
+        Err(e) => {
+            log::warn!("commands: /{} failed: {:#} (error report not saved: {})", name, error, e);
+            None
+        }
+    }
+}
+
+/// Look up an error code users quote from a failure message.
+pub struct ErrorLookupCommand;
+
+#[async_trait]
+impl Command for ErrorLookupCommand {
+    fn name(&self) -> &'static str { "errorlookup" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("オーナー用: エラーコードの詳細を表示します")
+            .create_option(|o| o.name("code").description("エラーコード (例: E-WEL-013)").kind(CommandOptionType::String).required(true))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        if !crate::owner::is_owner(inv.command.user.id) { return inv.say("権限がありません。").await; }
+        let code = inv.args.str("code").unwrap_or("");
+        let id = match parse_code(code) { Some(id) => id, None => return inv.say("エラーコードの形式が正しくありません (例: E-WEL-013)。").await };
+        let report = match db::get_error_report(id).await? {
+            Some(r) => r,
+            None => return inv.say(format!("`{}` の記録はありません (保持期間を過ぎた可能性があります)。", code.trim())).await,
+        };
+        let at = Utc.timestamp_opt(report.created_at, 0).single().map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string()).unwrap_or_default();
+        let guild = report.guild_id.map(|g| g.to_string()).unwrap_or_else(|| "DM".to_string());
+        let header = format!(
+            "**{}** `/{} {}`\n日時: {}\nサーバー: {} / チャンネル: <#{}>\nユーザー: <@{}> ({})",
+            format_code(&report.command, report.id), report.command, report.options, at, guild, report.channel_id, report.user_id, report.user_id,
+        );
+        Report::text(&format!("{}.txt", format_code(&report.command, report.id)))
+            .header(header)
+            .lines(report.error.lines().map(|l| l.to_string()))
+            .code_block()
+            .ephemeral(true)
+            .send(&inv.ctx.http, inv.command)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_use_the_command_prefix_and_a_padded_id() {
+        assert_eq!(format_code("welcome", 13), "E-WEL-013");
+        assert_eq!(format_code("leave-message", 1234), "E-LEA-1234");
+        assert_eq!(format_code("ai", 7), "E-AI-007");
+        assert_eq!(format_code("", 7), "E-CMD-007");
+    }
+
+    #[test]
+    fn codes_parse_with_or_without_the_prefix() {
+        assert_eq!(parse_code("E-WEL-013"), Some(13));
+        assert_eq!(parse_code(" wel-13 "), Some(13));
+        assert_eq!(parse_code("013"), Some(13));
+        assert_eq!(parse_code("E-WEL-"), None);
+        assert_eq!(parse_code("E-WEL-000"), None);
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::Result;
+use chrono::{FixedOffset, NaiveDateTime, TimeZone, Utc};
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::channel::ChannelType;
+use serenity::model::guild::{ScheduledEvent, ScheduledEventStatus};
+use serenity::model::id::ChannelId;
+use serenity::model::voice::VoiceState;
+use serenity::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::scheduler;
+use crate::permissions;
+
+const JOIN_SUMMARY_INTERVAL_SECONDS: u64 = 3600;
+const REMINDER_INTERVAL_SECONDS: u64 = 300;
+/// Reminders go out this long before an event starts.
+const REMINDER_LEAD_SECONDS: i64 = 3600;
+/// Scheduled events without an end time are treated as lasting this long.
+const DEFAULT_SCHEDULED_HOURS: i64 = 2;
+const MAX_EVENT_HOURS: i64 = 72;
+const MAX_SUMMARY_MENTIONS: usize = 50;
+
+/// Event times are entered and shown in JST.
+pub fn jst() -> FixedOffset {
+    FixedOffset::east_opt(9 * 3600).unwrap()
+}
+
+fn parse_start(s: &str) -> Result<i64> {
+    let naive = NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(s.trim(), "%Y/%m/%d %H:%M"))
+        .map_err(|_| anyhow::anyhow!("開始日時は YYYY-MM-DD HH:MM の形式で指定してください。"))?;
+    let local = jst().from_local_datetime(&naive).single().ok_or_else(|| anyhow::anyhow!("開始日時が不正です。"))?;
+    Ok(local.timestamp())
+}
+
+/// Announce speakers joining or leaving an announced event's voice channel.
+pub async fn handle_voice_state_update(http: &Http, old: Option<&VoiceState>, new: &VoiceState) -> Result<()> {
+    let guild_id = match new.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    let before = old.and_then(|o| o.channel_id);
+    let after = new.channel_id;
+    // Mute, deafen and stream toggles also arrive here.
+    if before == after { return Ok(()); }
+    let member = match &new.member { Some(m) if !m.user.bot => m, _ => return Ok(()) };
+
+    for announcement in db::get_active_voice_announcements(guild_id, Utc::now().timestamp()).await? {
+        let (name, voice) = (&announcement.name, announcement.voice_channel_id);
+        if !member.roles.iter().any(|r| r.0 as i64 == announcement.speaker_role_id) { continue; }
+        let voice_id = ChannelId(voice as u64);
+        let content = if after == Some(voice_id) {
+            format!("🎤 {} さんが <#{}> に参加しました ({})", member.display_name(), voice, name)
+        } else if before == Some(voice_id) {
+            format!("👋 {} さんが <#{}> から退出しました ({})", member.display_name(), voice, name)
+        } else {
+            continue;
+        };
+        if let Err(e) = ChannelId(announcement.text_channel_id as u64).say(http, content).await {
+            log::warn!("events: failed to post voice announcement for guild {}: {}", guild_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Start the hourly job that posts one summary for joins batched during events, and the
+/// job that posts reminders an hour before events start.
+pub fn start(http: Arc<Http>) {
+    let summary_http = http.clone();
+    scheduler::spawn_every("event-join-summary", Duration::from_secs(JOIN_SUMMARY_INTERVAL_SECONDS), move || {
+        let http = summary_http.clone();
+        async move { post_join_summaries(&http).await }
+    });
+    scheduler::spawn_every("event-reminders", Duration::from_secs(REMINDER_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { post_reminders(&http).await }
+    });
+}
+
+/// Mirror a Discord scheduled event into `events`, so it batches joins and gets a reminder
+/// like events planned with `/event plan`. Canceled events are dropped.
+pub async fn handle_scheduled_event(event: &ScheduledEvent) -> Result<()> {
+    if matches!(event.status, ScheduledEventStatus::Canceled) { return handle_scheduled_event_delete(event).await; }
+    let starts_at = event.start_time.unix_timestamp();
+    let ends_at = event.end_time.map(|t| t.unix_timestamp()).unwrap_or(starts_at + DEFAULT_SCHEDULED_HOURS * 3600);
+    let created_by = event.creator_id.map(|u| u.0 as i64).unwrap_or(0);
+    db::upsert_scheduled_event(event.guild_id.0 as i64, event.id.0 as i64, &event.name, starts_at, ends_at, created_by).await
+}
+
+pub async fn handle_scheduled_event_delete(event: &ScheduledEvent) -> Result<()> {
+    db::remove_scheduled_event(event.id.0 as i64).await?;
+    Ok(())
+}
+
+async fn post_reminders(http: &Http) -> Result<()> {
+    let now = Utc::now().timestamp();
+    for db::EventReminder { guild_id, channel_id, name, starts_at, scheduled_event_id } in db::take_due_event_reminders(now, now + REMINDER_LEAD_SECONDS).await? {
+        let mut content = format!("⏰ イベント「{}」は <t:{}:R> に始まります (<t:{}:t>)", name, starts_at, starts_at);
+        if let Some(id) = scheduled_event_id {
+            content.push_str(&format!("\nhttps://discord.com/events/{}/{}", guild_id, id));
+            // RSVPs are mentioned here, or DMed when they prefer that.
+            let users = http.get_scheduled_event_users(guild_id as u64, id as u64, Some(100), None, Some(false)).await.unwrap_or_default();
+            let mut mentions = Vec::new();
+            for u in users.iter().map(|u| &u.user).filter(|u| !u.bot) {
+                if crate::preferences::wants_dm_reminders(u.id.0 as i64).await {
+                    let dm = format!("⏰ 興味ありに登録したイベント「{}」は <t:{}:R> に始まります。\nhttps://discord.com/events/{}/{}", name, starts_at, guild_id, id);
+                    if let Ok(ch) = u.id.create_dm_channel(http).await {
+                        if ch.say(http, dm).await.is_ok() { continue; }
+                    }
+                }
+                mentions.push(format!("<@{}>", u.id.0));
+            }
+            if !mentions.is_empty() {
+                let total = mentions.len();
+                content.push('\n');
+                content.push_str(&mentions.into_iter().take(MAX_SUMMARY_MENTIONS).collect::<Vec<_>>().join(" "));
+                if total > MAX_SUMMARY_MENTIONS { content.push_str(&format!(" ほか{}人", total - MAX_SUMMARY_MENTIONS)); }
+            }
+        }
+        if let Err(e) = ChannelId(channel_id as u64).say(http, content).await {
+            log::warn!("events: failed to post reminder for guild {}: {}", guild_id, e);
+        }
+    }
+    Ok(())
+}
+
+async fn post_join_summaries(http: &Http) -> Result<()> {
+    let mut by_guild: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+    for (guild_id, user_id) in db::take_batched_joins().await? {
+        by_guild.entry(guild_id).or_default().push(user_id);
+    }
+    for (guild_id, users) in by_guild {
+        let settings = db::get_welcome_settings(guild_id).await?;
+        let channel_id = match (settings.is_enabled, settings.channel_id) { (true, Some(c)) => ChannelId(c as u64), _ => continue };
+        let mut content = format!("この1時間で{}人が参加しました！ようこそ！", users.len());
+        let mentions: Vec<String> = users.iter().take(MAX_SUMMARY_MENTIONS).map(|u| format!("<@{}>", u)).collect();
+        content.push('\n');
+        content.push_str(&mentions.join(" "));
+        if users.len() > MAX_SUMMARY_MENTIONS { content.push_str(&format!(" ほか{}人", users.len() - MAX_SUMMARY_MENTIONS)); }
+        if let Err(e) = channel_id.say(http, content).await {
+            log::warn!("events: failed to post join summary for guild {}: {}", guild_id, e);
+        }
+    }
+    Ok(())
+}
+
+pub struct EventCommand;
+
+#[async_trait]
+impl Command for EventCommand {
+    fn name(&self) -> &'static str { "event" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("イベントの予定 (開催中は参加メッセージをまとめて送信します)")
+            .create_option(|o| {
+                o.name("plan").description("イベントを登録します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("name").description("イベント名").kind(CommandOptionType::String).required(true))
+                    .create_sub_option(|s| s.name("start").description("開始日時 (JST, YYYY-MM-DD HH:MM)").kind(CommandOptionType::String).required(true))
+                    .create_sub_option(|s| s.name("hours").description(format!("開催時間 (1～{}時間)", MAX_EVENT_HOURS)).kind(CommandOptionType::Integer).required(true))
+            })
+            .create_option(|o| o.name("list").description("予定されているイベントを表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|o| {
+                o.name("cancel").description("イベントを取り消します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("id").description("/event list で表示されるID").kind(CommandOptionType::Integer).required(true))
+            })
+            .create_option(|o| {
+                o.name("reminders").description("開始1時間前のお知らせを送るチャンネル (指定なしで停止)").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("channel").description("お知らせを送るチャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(false))
+            })
+            .create_option(|o| {
+                o.name("announce").description("開催中、登壇者がボイスチャンネルに出入りしたらお知らせします (指定なしで解除)").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("id").description("/event list で表示されるID").kind(CommandOptionType::Integer).required(true))
+                    .create_sub_option(|s| s.name("voice").description("ステージ・ボイスチャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Voice, ChannelType::Stage]).required(false))
+                    .create_sub_option(|s| s.name("channel").description("お知らせを送るチャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(false))
+                    .create_sub_option(|s| s.name("role").description("登壇者のロール").kind(CommandOptionType::Role).required(false))
+            })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_event_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_event_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    let reply = match sub.name.as_str() {
+        "plan" => {
+            let name = sub.options.iter().find(|o| o.name=="name").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").trim().to_string();
+            let start = sub.options.iter().find(|o| o.name=="start").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+            let hours = sub.options.iter().find(|o| o.name=="hours").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+            if !(1..=MAX_EVENT_HOURS).contains(&hours) {
+                format!("開催時間は1～{}時間で指定してください。", MAX_EVENT_HOURS)
+            } else {
+                match parse_start(start) {
+                    Ok(starts_at) => {
+                        let ends_at = starts_at + hours * 3600;
+                        if ends_at < Utc::now().timestamp() { "終了済みのイベントは登録できません。".to_string() } else {
+                            let id = db::add_event(guild_id, &name, starts_at, ends_at, command.user.id.0 as i64).await?;
+                            format!("イベント「{}」(ID: {}) を登録しました: <t:{}:f> ～ <t:{}:f>\n開催中の参加メッセージは1時間ごとにまとめて送信します (マイルストーンは通常通り通知されます)。", name, id, starts_at, ends_at)
+                        }
+                    }
+                    Err(e) => e.to_string(),
+                }
+            }
+        }
+        "list" => {
+            let events = db::get_upcoming_events(guild_id, Utc::now().timestamp()).await?;
+            let announced = db::get_voice_announcement_events(guild_id).await?;
+            if events.is_empty() { "予定されているイベントはありません。".to_string() } else {
+                events.iter().map(|(id, name, s, e)| format!("`{}` {} — <t:{}:f> ～ <t:{}:t>{}", id, name, s, e, if announced.contains(id) { " 🎤" } else { "" })).collect::<Vec<_>>().join("\n")
+            }
+        }
+        "reminders" => {
+            let channel = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Channel(c) => Some(c.id.0 as i64), _ => None });
+            db::set_event_reminder_channel(guild_id, channel).await?;
+            match channel {
+                Some(c) => format!("イベント開始の1時間前に <#{}> でお知らせします (Discordのイベントは「興味あり」の人にもメンションします)。", c),
+                None => "イベントのお知らせを停止しました。".to_string(),
+            }
+        }
+        "announce" => {
+            let id = sub.options.iter().find(|o| o.name=="id").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+            let channel = |name: &str| sub.options.iter().find(|o| o.name==name).and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Channel(c) => Some(c.id.0 as i64), _ => None });
+            let (voice, text) = (channel("voice"), channel("channel"));
+            let role = sub.options.iter().find(|o| o.name=="role").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Role(r) => Some(r.id.0 as i64), _ => None });
+            match (voice, text, role) {
+                (None, None, None) => {
+                    if db::remove_event_voice_announcement(guild_id, id).await? { "お知らせを解除しました。".to_string() } else { "このイベントにはお知らせが設定されていません。".to_string() }
+                }
+                (Some(v), Some(t), Some(r)) => {
+                    if db::set_event_voice_announcement(guild_id, id, v, t, r).await? {
+                        format!("開催中に <@&{}> のメンバーが <#{}> に出入りしたら <#{}> でお知らせします。", r, v, t)
+                    } else {
+                        "指定されたIDのイベントが見つかりません。".to_string()
+                    }
+                }
+                _ => "voice・channel・role をすべて指定してください (解除する場合はすべて省略)。".to_string(),
+            }
+        }
+        "cancel" => {
+            let id = sub.options.iter().find(|o| o.name=="id").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+            if db::remove_event(guild_id, id).await? { "イベントを取り消しました!".to_string() } else { "指定されたIDのイベントが見つかりません。".to_string() }
+        }
+        _ => return Ok(()),
+    };
+    command.create_followup_message(&ctx.http, |m| m.content(reply).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use axum::extract::{Path, Query};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::api;
+use crate::db;
+use crate::web::WebState;
+
+const MAX_SOURCE_LEN: usize = 32;
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: Option<String>,
+}
+
+/// One follower-count reading from another platform, e.g. `{"source": "youtube", "value": 1234}`.
+#[derive(Deserialize)]
+pub struct MetricInput {
+    source: String,
+    value: i64,
+    /// YYYY-MM-DD; defaults to today (UTC).
+    date: Option<String>,
+}
+
+pub fn routes() -> Router<WebState> {
+    Router::new().route("/api/guilds/:id/metrics", post(ingest))
+}
+
+fn valid_source(s: &str) -> bool {
+    !s.is_empty() && s.len() <= MAX_SOURCE_LEN && s.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+async fn ingest(Path(id): Path<u64>, Query(q): Query<TokenQuery>, headers: HeaderMap, Json(input): Json<MetricInput>) -> Response {
+    if let Err(r) = api::authorize(id as i64, &headers, q.token.as_deref()).await { return r; }
+    let source = input.source.trim().to_lowercase();
+    if !valid_source(&source) { return api::error(StatusCode::BAD_REQUEST, "source must be 1-32 chars of [a-z0-9_-]"); }
+    if input.value < 0 { return api::error(StatusCode::BAD_REQUEST, "value must not be negative"); }
+    let day = match input.date.as_deref() {
+        Some(d) => match NaiveDate::parse_from_str(d, "%Y-%m-%d") { Ok(d) => d, Err(_) => return api::error(StatusCode::BAD_REQUEST, "date must be YYYY-MM-DD") },
+        None => Utc::now().date_naive(),
+    };
+    match db::upsert_external_metric(id as i64, &source, &day.to_string(), input.value).await {
+        Ok(_) => api::json_response(StatusCode::OK, json!({ "source": source, "date": day.to_string(), "value": input.value })),
+        Err(e) => {
+            log::warn!("external: failed to store metric for guild {}: {}", id, e);
+            api::error(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        }
+    }
+}
+
+/// One line per source with the latest value and the change over the range, or None when nothing was ingested.
+pub async fn summary_lines(guild_id: i64, since: NaiveDate, until: NaiveDate) -> Result<Option<String>> {
+    let rows = db::get_external_metrics(guild_id, &since.to_string(), &until.to_string()).await?;
+    let mut by_source: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    for (source, _, value) in rows {
+        by_source.entry(source).or_default().push(value);
+    }
+    if by_source.is_empty() { return Ok(None); }
+    let lines: Vec<String> = by_source.iter().map(|(source, values)| {
+        let first = values.first().copied().unwrap_or(0);
+        let last = values.last().copied().unwrap_or(0);
+        format!("{}: {} ({:+})", source, last, last - first)
+    }).collect();
+    Ok(Some(lines.join("\n")))
+}
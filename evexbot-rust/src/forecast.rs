@@ -0,0 +1,314 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDateTime};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use smartcore::linalg::naive::dense_matrix::DenseMatrix;
+use smartcore::linalg::BaseMatrix;
+use smartcore::linear::linear_regression::LinearRegression;
+
+// Growth models and the confidence band, without charts or Discord types: this file only
+// uses external crates, so the benches can build it on its own.
+
+const POLYNOMIAL_DEGREE: usize = 3;
+/// Candidate trend changes, spread over the first 80% of the history like Prophet's defaults.
+const CHANGEPOINTS: usize = 25;
+const CHANGEPOINT_RANGE: f64 = 0.8;
+/// Ridge penalty per sample on changepoint slopes; larger values give a smoother trend.
+const CHANGEPOINT_PENALTY: f64 = 1e-3;
+/// Logistic capacities tried, as multiples of the current count: 1.02x up to about 90x.
+const CAPACITY_STEPS: i32 = 60;
+const CAPACITY_GROWTH: f64 = 1.15;
+/// Refits for the confidence band; each one is a full fit, so keep this small.
+pub const BOOTSTRAP_SAMPLES: usize = 50;
+/// Share of the refits inside the band and the date range (10th to 90th percentile).
+pub const INTERVAL: f64 = 0.8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Model {
+    /// Degree-3 polynomial fit; follows recent curvature but extrapolates poorly.
+    Polynomial,
+    /// Piecewise linear trend with automatic changepoints, continuing the latest slope.
+    Trend,
+    /// Constant growth rate; fits young servers but never slows down.
+    Exponential,
+    /// S-curve that levels off at a fitted capacity, as most servers eventually do.
+    Logistic,
+}
+
+impl Model {
+    pub const ALL: [Model; 4] = [Model::Trend, Model::Logistic, Model::Exponential, Model::Polynomial];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Model::ALL.into_iter().find(|m| m.key() == name || m.aliases().contains(&name.as_str()))
+    }
+
+    /// Older names still accepted: "prophet" since the trend model replaced the Prophet helper,
+    /// and "linear" since the trend is piecewise linear.
+    pub fn aliases(self) -> &'static [&'static str] {
+        match self {
+            Model::Trend => &["prophet", "linear"],
+            Model::Polynomial | Model::Exponential | Model::Logistic => &[],
+        }
+    }
+
+    /// The `/growth predict model` choice value.
+    pub fn key(self) -> &'static str {
+        match self {
+            Model::Polynomial => "polynomial",
+            Model::Trend => "trend",
+            Model::Exponential => "exponential",
+            Model::Logistic => "logistic",
+        }
+    }
+
+    /// How far ahead to look for the target.
+    pub fn horizon_days(self) -> i64 {
+        match self {
+            Model::Polynomial => 304,
+            Model::Trend | Model::Exponential | Model::Logistic => 365 * 5,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Model::Polynomial => "3次多項式",
+            Model::Trend => "変化点つき線形トレンド",
+            Model::Exponential => "指数関数",
+            Model::Logistic => "ロジスティック曲線",
+        }
+    }
+}
+
+/// A fitted model; `predict` takes a day number (`num_days_from_ce`) and returns a member count.
+pub enum Fitted {
+    Polynomial(LinearRegression<f64, DenseMatrix<f64>>),
+    Trend { origin: f64, span: f64, scale: f64, changepoints: Vec<f64>, coefs: Vec<f64> },
+    /// `a * e^(b * t)`, with `t` in days since `origin`.
+    Exponential { origin: f64, a: f64, b: f64 },
+    /// `capacity / (1 + e^(-rate * (t - midpoint)))`, with `t` in days since `origin`.
+    Logistic { origin: f64, capacity: f64, rate: f64, midpoint: f64 },
+}
+
+impl Fitted {
+    pub fn predict(&self, day: f64) -> Result<f64> {
+        match self {
+            Fitted::Polynomial(lr) => {
+                let feats: Vec<f64> = (0..=POLYNOMIAL_DEGREE).map(|p| day.powi(p as i32)).collect();
+                Ok(lr.predict(&DenseMatrix::from_array(1, POLYNOMIAL_DEGREE + 1, &feats))?[0])
+            }
+            Fitted::Trend { origin, span, scale, changepoints, coefs } => {
+                let feats = trend_features((day - origin) / span, changepoints);
+                Ok(feats.iter().zip(coefs).map(|(f, c)| f * c).sum::<f64>() * scale)
+            }
+            Fitted::Exponential { origin, a, b } => Ok(a * (b * (day - origin)).exp()),
+            Fitted::Logistic { origin, capacity, rate, midpoint } => Ok(capacity / (1.0 + (-rate * (day - origin - midpoint)).exp())),
+        }
+    }
+
+    /// Fitted parameters for the result embed; `last_day` is the latest join's day number.
+    pub fn parameters(&self, last_day: f64) -> Result<String> {
+        let pace = self.predict(last_day)? - self.predict(last_day - 1.0)?;
+        let detail = match self {
+            Fitted::Polynomial(lr) => {
+                // The x⁰ feature and the intercept overlap, so their sum is the constant term.
+                let c = lr.coefficients();
+                format!("y = {:.3e} + {:.3e}x + {:.3e}x² + {:.3e}x³ (x = 日数)", lr.intercept() + c.get(0, 0), c.get(1, 0), c.get(2, 0), c.get(3, 0))
+            }
+            Fitted::Trend { changepoints, coefs, .. } => {
+                // Changepoints whose slope change stayed meaningful after the penalty.
+                let shifts = coefs[2..].iter().filter(|d| d.abs() > 0.01).count();
+                format!("傾きの変化: {}/{}か所", shifts, changepoints.len())
+            }
+            Fitted::Exponential { b, .. } => format!("成長率: {:.2}%/日, 倍増期間: {:.0}日", b * 100.0, std::f64::consts::LN_2 / b),
+            Fitted::Logistic { origin, capacity, rate, midpoint } => {
+                let inflection = chrono::NaiveDate::from_num_days_from_ce_opt((origin + midpoint) as i32).map(|d| d.to_string()).unwrap_or_default();
+                format!("上限: {:.0}人, 成長率: {:.4}/日, 変曲点: {}", capacity, rate, inflection)
+            }
+        };
+        Ok(format!("{}\n現在のペース: {:.1}人/日", detail, pace))
+    }
+}
+
+/// Ordinary least squares for `y = intercept + slope * x`.
+fn linear_fit(x: &[f64], y: &[f64]) -> Option<(f64, f64)> {
+    let n = x.len() as f64;
+    let (mx, my) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+    let sxx: f64 = x.iter().map(|v| (v - mx).powi(2)).sum();
+    if sxx <= 0.0 { return None; }
+    let slope = x.iter().zip(y).map(|(a, b)| (a - mx) * (b - my)).sum::<f64>() / sxx;
+    Some((my - slope * mx, slope))
+}
+
+/// Fit `ln y` linearly, which makes the growth rate the slope.
+fn fit_exponential(x: &[f64], y: &[f64]) -> Option<Fitted> {
+    let origin = x[0];
+    let t: Vec<f64> = x.iter().map(|v| v - origin).collect();
+    let ln_y: Vec<f64> = y.iter().map(|v| v.max(1.0).ln()).collect();
+    let (intercept, b) = linear_fit(&t, &ln_y)?;
+    Some(Fitted::Exponential { origin, a: intercept.exp(), b })
+}
+
+/// For a fixed capacity the logistic curve is linear in `ln(capacity / y - 1)`, so try a range
+/// of capacities and keep the one with the smallest squared error.
+fn fit_logistic(x: &[f64], y: &[f64]) -> Option<Fitted> {
+    let origin = x[0];
+    let t: Vec<f64> = x.iter().map(|v| v - origin).collect();
+    let current = y.iter().copied().fold(1.0, f64::max);
+    let mut best: Option<(f64, Fitted)> = None;
+    for step in 0..CAPACITY_STEPS {
+        let capacity = current * (1.0 + 0.02 * CAPACITY_GROWTH.powi(step));
+        let z: Vec<f64> = y.iter().map(|v| (capacity / v - 1.0).ln()).collect();
+        let (intercept, slope) = match linear_fit(&t, &z) { Some(f) => f, None => continue };
+        if slope >= 0.0 { continue; }
+        let (rate, midpoint) = (-slope, intercept / -slope);
+        let candidate = Fitted::Logistic { origin, capacity, rate, midpoint };
+        let sse: f64 = x.iter().zip(y).map(|(xi, yi)| (candidate.predict(*xi).unwrap_or(0.0) - yi).powi(2)).sum();
+        if best.as_ref().map(|(e, _)| sse < *e).unwrap_or(true) { best = Some((sse, candidate)); }
+    }
+    best.map(|(_, f)| f)
+}
+
+/// Intercept, slope, and one hinge per changepoint, so the slope can change at each of them.
+fn trend_features(t: f64, changepoints: &[f64]) -> Vec<f64> {
+    let mut feats = vec![1.0, t];
+    feats.extend(changepoints.iter().map(|c| (t - c).max(0.0)));
+    feats
+}
+
+/// Solve `a x = b` by Gaussian elimination with partial pivoting.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 { return None; }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let f = a[row][col] / pivot_row[col];
+            for (v, p) in a[row].iter_mut().zip(&pivot_row).skip(col) { *v -= f * p; }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Fit the trend by ridge regression: changepoint slopes are penalised so only real shifts are kept.
+fn fit_trend(x: &[f64], y: &[f64]) -> Option<Fitted> {
+    let n = x.len();
+    let (origin, span) = (x[0], (x[n - 1] - x[0]).max(1.0));
+    let scale = y.iter().copied().fold(1.0, f64::max);
+    let t: Vec<f64> = x.iter().map(|v| (v - origin) / span).collect();
+    let changepoints: Vec<f64> = (1..=CHANGEPOINTS)
+        .map(|i| t[((i as f64 * CHANGEPOINT_RANGE * n as f64) / (CHANGEPOINTS + 1) as f64) as usize])
+        .collect();
+
+    let m = CHANGEPOINTS + 2;
+    let mut xtx = vec![vec![0.0; m]; m];
+    let mut xty = vec![0.0; m];
+    for (ti, yi) in t.iter().zip(y) {
+        let feats = trend_features(*ti, &changepoints);
+        for (a, fa) in feats.iter().enumerate() {
+            xty[a] += fa * yi / scale;
+            for (b, fb) in feats.iter().enumerate() { xtx[a][b] += fa * fb; }
+        }
+    }
+    for (j, row) in xtx.iter_mut().enumerate().skip(2) { row[j] += CHANGEPOINT_PENALTY * n as f64; }
+    let coefs = solve(xtx, xty)?;
+    Some(Fitted::Trend { origin, span, scale, changepoints, coefs })
+}
+
+/// Fit `model` to (day number, member count) points.
+pub fn fit(model: Model, x: &[f64], y: &[f64]) -> Result<Option<Fitted>> {
+    if x.len() < 2 { return Ok(None); }
+    Ok(match model {
+        Model::Polynomial => {
+            let n = x.len();
+            let x_poly: Vec<f64> = x.iter().flat_map(|xi| (0..=POLYNOMIAL_DEGREE).map(move |p| xi.powi(p as i32))).collect();
+            let x_mat = DenseMatrix::from_array(n, POLYNOMIAL_DEGREE + 1, &x_poly);
+            Some(Fitted::Polynomial(LinearRegression::fit(&x_mat, &y.to_vec(), Default::default())?))
+        }
+        Model::Trend => fit_trend(x, y),
+        Model::Exponential => fit_exponential(x, y),
+        Model::Logistic => fit_logistic(x, y),
+    })
+}
+
+/// One point per join: day number and member count after that join.
+pub fn join_points(dates: &[NaiveDateTime]) -> (Vec<f64>, Vec<f64>) {
+    let x = dates.iter().map(|d| d.date().num_days_from_ce() as f64).collect();
+    let y = (1..=dates.len()).map(|v| v as f64).collect();
+    (x, y)
+}
+
+/// Refit on the fitted curve plus resampled residuals. Fits that fail are left out.
+pub fn bootstrap(model: Model, fitted: &Fitted, x: &[f64], y: &[f64]) -> Result<Vec<Fitted>> {
+    let base = x.iter().map(|d| fitted.predict(*d)).collect::<Result<Vec<f64>>>()?;
+    let residuals: Vec<f64> = y.iter().zip(&base).map(|(a, b)| a - b).collect();
+    let mut rng = bootstrap_rng();
+    let mut fits = Vec::with_capacity(BOOTSTRAP_SAMPLES);
+    for _ in 0..BOOTSTRAP_SAMPLES {
+        let resampled: Vec<f64> = base.iter().map(|b| (b + residuals[rng.gen_range(0..residuals.len())]).max(1.0)).collect();
+        if let Ok(Some(f)) = fit(model, x, &resampled) { fits.push(f); }
+    }
+    Ok(fits)
+}
+
+/// Seeded in tests so the confidence band, and with it the chart, is the same every run.
+fn bootstrap_rng() -> StdRng {
+    #[cfg(test)]
+    { StdRng::seed_from_u64(0) }
+    #[cfg(not(test))]
+    { StdRng::from_entropy() }
+}
+
+/// Value at quantile `q` of sorted `values`.
+fn quantile(values: &[f64], q: f64) -> f64 {
+    values[((values.len() - 1) as f64 * q).round() as usize]
+}
+
+/// Lower and upper edges of the band: quantiles of the refits' predictions for `day`.
+pub fn band_at(fits: &[Fitted], day: f64) -> Result<(f64, f64)> {
+    let mut values = fits.iter().map(|f| f.predict(day)).collect::<Result<Vec<f64>>>()?;
+    values.sort_by(f64::total_cmp);
+    Ok((quantile(&values, (1.0 - INTERVAL) / 2.0), quantile(&values, (1.0 + INTERVAL) / 2.0)))
+}
+
+/// Earliest and latest likely dates for `target`. `None` when even the earliest is past the
+/// horizon; the latest is `None` when it is past the horizon.
+pub fn reach_range(model: Model, fits: &[Fitted], last_day: f64, target: usize) -> Result<Option<(chrono::NaiveDate, Option<chrono::NaiveDate>)>> {
+    if fits.is_empty() { return Ok(None); }
+    let mut days = Vec::with_capacity(fits.len());
+    for f in fits {
+        days.push(reach_day(model, f, last_day, target)?.map(|d| d.num_days_from_ce() as f64).unwrap_or(f64::INFINITY));
+    }
+    days.sort_by(f64::total_cmp);
+    let to_date = |d: f64| if d.is_finite() { chrono::NaiveDate::from_num_days_from_ce_opt(d as i32) } else { None };
+    Ok(to_date(quantile(&days, (1.0 - INTERVAL) / 2.0)).map(|earliest| (earliest, to_date(quantile(&days, (1.0 + INTERVAL) / 2.0)))))
+}
+
+/// First day from `last_day` on where the fitted curve reaches `target`, within the model's horizon.
+pub fn reach_day(model: Model, fitted: &Fitted, last_day: f64, target: usize) -> Result<Option<chrono::NaiveDate>> {
+    for d in 0..model.horizon_days() {
+        let day = last_day + d as f64;
+        if fitted.predict(day)? >= target as f64 {
+            return Ok(chrono::NaiveDate::from_num_days_from_ce_opt(day as i32));
+        }
+    }
+    Ok(None)
+}
+
+/// Just the date `target` is reached, for reports that don't draw the chart.
+pub fn predict_date(model: Model, dates: &[NaiveDateTime], target: usize) -> Result<Option<chrono::NaiveDate>> {
+    let (x, y) = join_points(dates);
+    match fit(model, &x, &y)? {
+        Some(fitted) => reach_day(model, &fitted, *x.last().unwrap(), target),
+        None => Ok(None),
+    }
+}
@@ -1,147 +1,78 @@
 use anyhow::Result;
 use chrono::{NaiveDateTime, DateTime, Utc};
 use plotters::prelude::*;
-use smartcore::linalg::naive::dense_matrix::DenseMatrix;
-use smartcore::linear::linear_regression::LinearRegression;
-use std::process::Stdio;
 use chrono::Datelike;
-use serde::{Serialize, Deserialize};
-
-#[derive(Serialize)]
-struct ProphetInput {
-    dates: Vec<String>,
-    target: usize,
+use serenity::prelude::GatewayIntents;
+
+use crate::chart::{self, ChartSize, Locale};
+use crate::forecast::{band_at, bootstrap, reach_day, reach_range, BOOTSTRAP_SAMPLES, INTERVAL};
+use crate::stats;
+pub use crate::forecast::{fit, join_points, predict_date, Fitted, Model};
+
+/// Predictions read every member's join date, which needs the member list.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::GUILD_MEMBERS;
+
+pub struct Prediction {
+    pub fitted: Fitted,
+    /// When the target is reached, if it is within the model's horizon.
+    pub date: Option<DateTime<Utc>>,
+    /// Earliest and latest likely dates from the bootstrap refits.
+    pub range: Option<(chrono::NaiveDate, Option<chrono::NaiveDate>)>,
+    /// Chart up to the predicted date; empty when the target isn't reached.
+    pub image: Vec<u8>,
+    /// Day number of the latest join.
+    pub last_day: f64,
 }
 
-#[derive(Deserialize)]
-struct ProphetOutput {
-    predicted_date: Option<String>,
-    image_base64: Option<String>,
+/// `predict_date` on the blocking pool, for callers on the runtime that only need the date:
+/// one fit, no bootstrap and no chart.
+pub async fn predict_date_blocking(model: Model, dates: Vec<NaiveDateTime>, target: usize) -> Result<Option<chrono::NaiveDate>> {
+    tokio::task::spawn_blocking(move || predict_date(model, &dates, target)).await?
 }
 
-/// Predict using Prophet helper (Python). Returns (datetime, PNG bytes) if prediction found.
-async fn call_prophet_helper(dates: &[NaiveDateTime], target: usize) -> Result<Option<(DateTime<Utc>, Vec<u8>)>> {
-    // Prepare python invocation
-    let script = std::path::Path::new("scripts/prophet_predict.py");
-    if !script.exists() {
-        return Ok(None);
-    }
-
-    let input = ProphetInput {
-        dates: dates.iter().map(|d| d.date().to_string()).collect(),
-        target,
-    };
-
-    // Try common python executables (works across platforms/venv)
-    let candidates: &[(&str, &[&str])] = &[("python", &[]), ("python3", &[]), ("py", &["-3"])];
-    let mut child_opt: Option<tokio::process::Child> = None;
-    for (exe, extra_args) in candidates.iter() {
-        let mut cmd = tokio::process::Command::new(exe);
-        for &a in extra_args.iter() { cmd.arg(a); }
-        cmd.arg(script).stdin(Stdio::piped()).stdout(Stdio::piped());
-        match cmd.spawn() {
-            Ok(c) => { child_opt = Some(c); break; }
-            Err(_) => continue,
-        }
-    }
-
-    let mut child = match child_opt {
-        Some(c) => c,
-        None => return Ok(None), // no python available
-    };
-
-    let stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("Failed to open stdin"))?;
-    let input_text = serde_json::to_vec(&input)?;
-    tokio::io::AsyncWriteExt::write_all(&mut tokio::io::BufWriter::new(stdin), &input_text).await?;
-
-    let output = child.wait_with_output().await?;
-    if !output.status.success() {
-        return Ok(None);
-    }
-
-    let out: ProphetOutput = serde_json::from_slice(&output.stdout)?;
-    if let Some(date_str) = out.predicted_date {
-        let dt = DateTime::parse_from_rfc3339(&date_str).map(|d| d.with_timezone(&Utc))?;
-        if let Some(b64) = out.image_base64 {
-            let bytes = base64::decode(&b64)?;
-            return Ok(Some((dt, bytes)));
-        }
-        return Ok(Some((dt, vec![])));
-    }
-    Ok(None)
+/// Fit `model` and search forward for the target. `None` when the model can't be fitted.
+///
+/// The fit, the bootstrap refits and the chart take seconds on a large guild, so they run
+/// on the blocking pool rather than stalling a runtime worker.
+pub async fn predict_with(model: Model, dates: &[NaiveDateTime], target: usize, size: ChartSize, locale: Locale) -> Result<Option<Prediction>> {
+    let dates = dates.to_vec();
+    tokio::task::spawn_blocking(move || predict_blocking(model, &dates, target, size, locale)).await?
 }
 
-pub async fn predict_and_generate(dates: &[NaiveDateTime], target: usize) -> Result<Option<(DateTime<Utc>, Vec<u8>)>> {
-    // Try Prophet helper first
-    if let Ok(Some(res)) = call_prophet_helper(dates, target).await {
-        return Ok(Some(res));
-    }
-
-    // Polynomial regression fallback
-    if dates.len() < 2 {
-        return Ok(None);
-    }
-
-    // Prepare X and y
-    let x: Vec<f64> = dates.iter().map(|d| d.date().num_days_from_ce() as f64).collect();
-    let y: Vec<f64> = (1..=dates.len()).map(|v| v as f64).collect();
-
-    let degree = 3usize;
-    let n = x.len();
-    let mut x_poly = Vec::with_capacity(n * (degree + 1));
-    for xi in x.iter() {
-        for p in 0..=degree {
-            x_poly.push(xi.powi(p as i32));
-        }
-    }
-
-    let x_mat = DenseMatrix::from_array(n, degree + 1, &x_poly);
-    let lr = LinearRegression::fit(&x_mat, &y, Default::default())?;
-
-    // predict forward until target or up to N days
-    let last_day = *x.last().unwrap() as i64;
-    let max_days = 304;
-    for d in 0..max_days {
-        let day = (last_day + d) as f64;
-        let mut feats = Vec::with_capacity(degree + 1);
-        for p in 0..=degree { feats.push(day.powi(p as i32)); }
-        let pred = lr.predict(&DenseMatrix::from_array(1, degree + 1, &feats))?[0];
-        if pred >= target as f64 {
-            let dt = chrono::NaiveDate::from_num_days_from_ce(day as i32).and_hms(0,0,0);
-            let dt_utc = DateTime::<Utc>::from_utc(dt, Utc);
+fn predict_blocking(model: Model, dates: &[NaiveDateTime], target: usize, size: ChartSize, locale: Locale) -> Result<Option<Prediction>> {
+    let (x, y) = join_points(dates);
+    let fitted = match fit(model, &x, &y)? { Some(f) => f, None => return Ok(None) };
+    let last_day = *x.last().unwrap();
+    let fits = bootstrap(model, &fitted, &x, &y)?;
+    let range = reach_range(model, &fits, last_day, target)?;
+
+    // predict forward until target or up to the model's horizon
+    match reach_day(model, &fitted, last_day, target)? {
+        Some(day) => {
+            let dt_utc = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
             // generate plot
-            let img = generate_plot(dates, dt_utc, &lr).await?;
-            return Ok(Some((dt_utc, img)));
+            let image = generate_plot(dates, dt_utc, &fitted, &fits, size, locale)?;
+            Ok(Some(Prediction { fitted, date: Some(dt_utc), range, image, last_day }))
         }
+        None => Ok(Some(Prediction { fitted, date: None, range, image: Vec::new(), last_day })),
     }
-
-    Ok(None)
 }
 
-async fn generate_plot(dates: &[NaiveDateTime], target_date: DateTime<Utc>, lr: &LinearRegression<f64, DenseMatrix<f64>>) -> Result<Vec<u8>> {
+fn generate_plot(dates: &[NaiveDateTime], target_date: DateTime<Utc>, fitted: &Fitted, band: &[Fitted], size: ChartSize, locale: Locale) -> Result<Vec<u8>> {
     // Draw using plotters
     use plotters_bitmap::BitMapBackend;
-    let w = 800;
-    let h = 450;
-    let mut buf = vec![0u8; w * h * 3];
+    let mut buf = vec![0u8; (size.width * size.height * 3) as usize];
     {
-        let backend = BitMapBackend::with_buffer(&mut buf, (w as u32, h as u32));
+        let backend = BitMapBackend::with_buffer(&mut buf, (size.width, size.height));
         let drawing = backend.into_drawing_area();
         drawing.fill(&WHITE)?;
 
         // compute points
         let min_day = dates.first().unwrap().date();
         let max_day = target_date.date_naive();
-        let days = (max_day - min_day).num_days() as usize + 1;
-        let x_vals: Vec<i64> = (0..days).map(|i| (min_day + chrono::Duration::days(i as i64)).num_days_from_ce() as i64).collect();
-        let y_actual: Vec<i32> = {
-            let mut counts = vec![0i32; days];
-            for d in dates.iter() {
-                let idx = (d.date() - min_day).num_days() as usize;
-                for i in idx..days { counts[i] += 1; }
-            }
-            counts
-        };
+        let (axis_dates, y_actual) = stats::member_counts(dates, min_day, max_day);
+        let days = axis_dates.len();
+        let x_vals: Vec<i64> = axis_dates.iter().map(|d| d.num_days_from_ce() as i64).collect();
 
         let max_y = y_actual.iter().copied().max().unwrap_or(0) + 5;
 
@@ -150,21 +81,31 @@ async fn generate_plot(dates: &[NaiveDateTime], target_date: DateTime<Utc>, lr:
             .caption("Growth Prediction", ("sans-serif", 24))
             .x_label_area_size(35)
             .y_label_area_size(40)
-            .build_cartesian_2d(0usize..days, 0i32..(max_y as i32 + 10))?;
-
-        chart.configure_mesh().disable_mesh().x_labels(6).draw()?;
+            .build_cartesian_2d(chart::date_axis(&axis_dates), 0i32..(max_y + 10))?;
+
+        chart.configure_mesh().disable_mesh().x_label_formatter(&chart::date_labels(&axis_dates, locale)).draw()?;
+
+        // Confidence band from the bootstrap refits, under both lines. It starts at the latest join.
+        let last_day = dates.last().unwrap().date().num_days_from_ce() as i64;
+        if !band.is_empty() {
+            let mut lower = Vec::new();
+            let mut upper = Vec::new();
+            for (i, day) in x_vals.iter().enumerate().filter(|(_, d)| **d >= last_day) {
+                let (lo, hi) = band_at(band, *day as f64)?;
+                // Polygons aren't clipped to the plot area, so keep the band inside the y range.
+                lower.push((i, (lo as i32).clamp(0, max_y + 10)));
+                upper.push((i, (hi as i32).clamp(0, max_y + 10)));
+            }
+            upper.extend(lower.into_iter().rev());
+            chart.draw_series(std::iter::once(Polygon::new(upper, RED.mix(0.15).filled())))?;
+        }
 
         chart.draw_series(LineSeries::new((0..days).map(|i| (i, y_actual[i])), &BLUE))?;
 
         // predicted line
-        let degree = 3usize;
         let mut preds = Vec::with_capacity(days);
-        for i in 0..days {
-            let day = x_vals[i] as f64;
-            let mut feats = Vec::with_capacity(degree + 1);
-            for p in 0..=degree { feats.push(day.powi(p as i32)); }
-            let predv = lr.predict(&DenseMatrix::from_array(1, degree + 1, &feats))?[0];
-            preds.push(predv as i32);
+        for day in x_vals.iter() {
+            preds.push(fitted.predict(*day as f64)? as i32);
         }
         chart.draw_series(LineSeries::new((0..days).map(|i| (i, preds[i])), &RED))?;
 
@@ -172,84 +113,241 @@ async fn generate_plot(dates: &[NaiveDateTime], target_date: DateTime<Utc>, lr:
         drawing.present()?;
     }
 
-    // Convert to PNG
-    let image = image::RgbImage::from_raw(w as u32, h as u32, buf).ok_or_else(|| anyhow::anyhow!("Failed to create image"))?;
-    let mut out = Vec::new();
-    image::DynamicImage::ImageRgb8(image).write_to(&mut std::io::Cursor::new(&mut out), image::ImageOutputFormat::Png)?;
-    Ok(out)
+    chart::encode_png(size, buf)
 }
 
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
 use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::autocomplete::AutocompleteInteraction;
 use serenity::prelude::*;
 
-pub async fn handle_growth(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
-    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+use crate::bus;
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::permissions;
+
+const MAX_NOTIFY_TARGET: i64 = 10_000_000;
+
+pub async fn on_event(ctx: &Context, event: &bus::Event) -> Result<()> {
+    match event {
+        bus::Event::MemberJoined(member) => handle_member_join(ctx, member).await,
+        _ => Ok(()),
+    }
+}
+
+/// Ping subscribers whose `/growth notify` target has been reached. Runs on each member join.
+async fn handle_member_join(ctx: &Context, member: &serenity::model::guild::Member) -> Result<()> {
+    let member_count = match ctx.cache.guild(member.guild_id).map(|g| g.member_count) { Some(c) => c as i64, None => return Ok(()) };
+    for db::GrowthNotification { channel_id, user_id, role_id, target, created_at } in db::take_reached_growth_notifications(member.guild_id.0 as i64, member_count).await? {
+        // Personal subscriptions go to DMs for users who prefer that; role pings stay in the channel.
+        if role_id.is_none() && crate::preferences::wants_dm_reminders(user_id).await {
+            let content = format!("🎉 {} のメンバー数が目標の{}人に到達しました! (現在{}人, <t:{}:d>に登録)", member.guild_id.name(&ctx.cache).unwrap_or_default(), target, member_count, created_at);
+            let sent = match serenity::model::id::UserId(user_id as u64).create_dm_channel(&ctx.http).await {
+                Ok(dm) => dm.say(&ctx.http, content).await.is_ok(),
+                Err(_) => false,
+            };
+            if sent { continue; }
+        }
+        let mention = match role_id { Some(r) => format!("<@&{}>", r), None => format!("<@{}>", user_id) };
+        let content = format!("{} 🎉 メンバー数が目標の{}人に到達しました! (現在{}人, <t:{}:d>に登録)", mention, target, member_count, created_at);
+        if let Err(e) = serenity::model::id::ChannelId(channel_id as u64).say(&ctx.http, content).await {
+            log::warn!("growth: failed to send notification in {}: {}", channel_id, e);
+        }
+    }
+    Ok(())
+}
+
+pub struct GrowthCommand;
+
+#[async_trait]
+impl Command for GrowthCommand {
+    fn name(&self) -> &'static str { "growth" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("サーバーの成長を予測します")
+            .create_option(|o| {
+                o.name("predict").description("目標メンバー数に達する日を予測します。使用法: /growth predict model target show_graph:true/false").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("model").description("予測モデル (trend / logistic / exponential / polynomial)").kind(CommandOptionType::String).required(true).set_autocomplete(true))
+                    .create_sub_option(|s| s.name("target").description("目標とするメンバー数").kind(CommandOptionType::Integer).required(true))
+                    .create_sub_option(|s| s.name("show_graph").description("グラフを表示するかどうか").kind(CommandOptionType::Boolean).required(false))
+                    .create_sub_option(|s| s.name("external").description("外部プラットフォームのフォロワー数を併記するかどうか").kind(CommandOptionType::Boolean).required(false))
+                    .create_sub_option(|s| chart::size_option(s))
+            })
+            .create_option(|o| {
+                o.name("notify").description("メンバー数が目標に達したら通知します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("target").description("目標とするメンバー数").kind(CommandOptionType::Integer).required(true))
+                    .create_sub_option(|s| s.name("role").description("自分の代わりにメンションするロール").kind(CommandOptionType::Role).required(false))
+            })
+    }
+
+    // predict and notify acknowledge with different visibility, so each defers by itself.
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_growth(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_growth(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    if sub.name == "notify" { return handle_notify(ctx, command, sub).await; }
 
-    let mut model = "polynomial".to_string();
+    let ephemeral = crate::preferences::wants_ephemeral(command.user.id.0 as i64).await;
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(ephemeral))).await?;
+
+    let mut model = Model::Trend;
+    let mut unknown_model = None;
     let mut target = 0usize;
     let mut show_graph = true;
+    let mut show_external = false;
 
-    for opt in &command.data.options {
+    for opt in &sub.options {
         match opt.name.as_str() {
-            "model" => { if let Some(v) = opt.value.as_ref() { if let Some(s) = v.as_str() { model = s.to_string(); } } }
+            "model" => {
+                if let Some(name) = opt.value.as_ref().and_then(|v| v.as_str()) {
+                    match Model::parse(name) { Some(m) => model = m, None => unknown_model = Some(name.to_string()) }
+                }
+            }
             "target" => { if let Some(v) = opt.value.as_ref() { if let Some(n) = v.as_i64() { target = n as usize; } } }
             "show_graph" => { if let Some(v) = opt.value.as_ref() { if let Some(b) = v.as_bool() { show_graph = b; } } }
+            "external" => { if let Some(v) = opt.value.as_ref() { if let Some(b) = v.as_bool() { show_external = b; } } }
             _ => {}
         }
     }
 
+    if let Some(name) = unknown_model {
+        let names: Vec<&str> = Model::ALL.iter().map(|m| m.key()).collect();
+        command.create_followup_message(&ctx.http, |m| m.content(format!("`{}` というモデルはありません。使用できるモデル: {}", name, names.join(", ")))).await?;
+        return Ok(());
+    }
     if target == 0 { command.create_followup_message(&ctx.http, |m| m.content("targetを指定してください。" )).await?; return Ok(()); }
     let guild = command.guild_id.ok_or_else(|| anyhow::anyhow!("Guild only"))?;
-    let join_dates = {
-        let mut dts = Vec::new();
-        let members = ctx.http.get_guild_members(guild.0, None, None).await?;
-        for m in members.into_iter() {
-            if let Some(j) = m.joined_at {
-                if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&j.to_string()) {
-                    dts.push(dt.naive_utc());
-                }
-            }
-        }
-        dts.sort(); dts
-    };
+    if !crate::intents::has(REQUIRED_INTENTS) { command.create_followup_message(&ctx.http, |m| m.content("このBotはメンバー一覧を取得できない設定のため、成長予測は利用できません。" )).await?; return Ok(()); }
+    let join_dates = crate::member_cache::join_dates(&ctx.http, guild).await?;
     if join_dates.len() < 2 { command.create_followup_message(&ctx.http, |m| m.content("回帰分析を行うためのデータが不足しています。" )).await?; return Ok(()); }
 
-    if model == "prophet" {
-        // try prophet helper
-        if let Ok(Some((dt, img))) = crate::growth::call_prophet_helper(&join_dates, target).await {
-            let mut embed = serenity::builder::CreateEmbed::default();
-            embed.title("Server Growth Prediction");
-            embed.description(format!("{}人に達する予測日: {}", target, dt.date_naive()));
-            embed.color(serenity::utils::Colour::BLUE);
-            if show_graph && !img.is_empty() {
-                embed.image("attachment://growth_prediction.png");
-                command.create_followup_message(&ctx.http, |m| m.add_file((img.as_slice(), "growth_prediction.png")).embed(|e| { *e = embed; e })).await?;
-                return Ok(());
-            }
-            command.create_followup_message(&ctx.http, |m| m.embed(|e| { *e = embed; e })).await?;
-            return Ok(());
+    let size_opt = sub.options.iter().find(|o| o.name=="size").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str());
+    let size = match chart::resolve(Some(guild.0 as i64), size_opt, chart::STANDARD).await {
+        Ok(s) => s,
+        Err(e) => { command.create_followup_message(&ctx.http, |m| m.content(e)).await?; return Ok(()); }
+    };
+
+    // External follower counts over the last 30 days, shown next to the prediction.
+    let external = if show_external {
+        let today = Utc::now().date_naive();
+        crate::external::summary_lines(guild.0 as i64, today - chrono::Duration::days(30), today).await?
+    } else { None };
+
+    if let Ok(Some(prediction)) = predict_with(model, &join_dates, target, size, Locale::for_user(command.user.id.0 as i64, Some(guild.0 as i64)).await).await {
+        let mut embed = serenity::builder::CreateEmbed::default();
+        embed.title("Server Growth Prediction");
+        match prediction.date {
+            Some(dt) => embed.description(match prediction.range {
+                Some((earliest, Some(latest))) => format!("{}人に達する予測日: {} ({}～{})", target, dt.date_naive(), earliest, latest),
+                Some((earliest, None)) => format!("{}人に達する予測日: {} ({}以降、{}日以内に達しない可能性もあります)", target, dt.date_naive(), earliest, model.horizon_days()),
+                None => format!("{}人に達する予測日: {}", target, dt.date_naive()),
+            }),
+            None => embed.description(match prediction.range {
+                Some((earliest, _)) => format!("{}日以内に{}人には達しない予測です (早ければ{})。", model.horizon_days(), target, earliest),
+                None => format!("{}日以内に{}人には達しない予測です。", model.horizon_days(), target),
+            }),
+        };
+        embed.field(format!("モデル: {}", model.label()), prediction.fitted.parameters(prediction.last_day)?, false);
+        embed.color(serenity::utils::Colour::BLUE);
+        if prediction.range.is_some() { embed.footer(|f| f.text(format!("期間は{}回の再推定による{:.0}%区間です", BOOTSTRAP_SAMPLES, INTERVAL * 100.0))); }
+        if let Some(lines) = external.as_ref() { embed.field("外部プラットフォーム (30日間)", lines, false); }
+        let img = prediction.image;
+        if show_graph && !img.is_empty() {
+            let img = chart::finish(Some(guild.0 as i64), img, "growth_prediction").await;
+            embed.image(img.url());
+            command.create_followup_message(&ctx.http, |m| m.add_file((img.bytes.as_slice(), img.filename().as_str())).embed(|e| { *e = embed; e })).await?;
         } else {
-            command.create_followup_message(&ctx.http, |m| m.content("予測できませんでした。" )).await?;
-            return Ok(());
+            command.create_followup_message(&ctx.http, |m| m.embed(|e| { *e = embed; e })).await?;
         }
     } else {
-        // polynomial fallback handled here
-        if let Ok(Some((dt, img))) = predict_and_generate(&join_dates, target).await {
-            let mut embed = serenity::builder::CreateEmbed::default();
-            embed.title("Server Growth Prediction");
-            embed.description(format!("{}人に達する予測日: {}", target, dt.date_naive()));
-            embed.color(serenity::utils::Colour::BLUE);
-            if show_graph && !img.is_empty() {
-                embed.image("attachment://growth_prediction.png");
-                command.create_followup_message(&ctx.http, |m| m.add_file((img.as_slice(), "growth_prediction.png")).embed(|e| { *e = embed; e })).await?;
-            } else {
-                command.create_followup_message(&ctx.http, |m| m.embed(|e| { *e = embed; e })).await?;
-            }
-            return Ok(());
-        } else {
-            command.create_followup_message(&ctx.http, |m| m.content("予測できませんでした。" )).await?;
-            return Ok(());
-        }
+        command.create_followup_message(&ctx.http, |m| m.content("予測できませんでした。" )).await?;
     }
+    Ok(())
 }
 
+
+/// Suggest models for `/growth predict model` that match what has been typed so far.
+pub async fn handle_autocomplete(ctx: &Context, autocomplete: &AutocompleteInteraction) -> Result<()> {
+    let typed = autocomplete.data.options.iter().flat_map(|sub| sub.options.iter())
+        .find(|o| o.focused && o.name == "model")
+        .and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").trim().to_lowercase();
+    let matches = Model::ALL.into_iter().filter(|m| m.key().starts_with(&typed) || m.aliases().iter().any(|a| a.starts_with(&typed)));
+    autocomplete.create_autocomplete_response(&ctx.http, |r| {
+        for m in matches { r.add_string_choice(format!("{} ({})", m.key(), m.label()), m.key()); }
+        r
+    }).await?;
+    Ok(())
+}
+
+async fn handle_notify(ctx: &Context, command: &ApplicationCommandInteraction, sub: &serenity::model::application::interaction::application_command::CommandDataOption) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let guild = command.guild_id.ok_or_else(|| anyhow::anyhow!("Guild only"))?;
+    let target = sub.options.iter().find(|o| o.name=="target").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+    let role = sub.options.iter().find(|o| o.name=="role").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Role(role) => Some(role.clone()), _ => None });
+
+    // Pinging a role is limited to admins so it can't be used to mass-mention.
+    let is_admin = match command.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+    if role.is_some() && !is_admin {
+        command.create_followup_message(&ctx.http, |m| m.content("ロールへの通知にはサーバーの管理権限が必要です。" ).ephemeral(true)).await?;
+        return Ok(());
+    }
+    let member_count = ctx.cache.guild(guild).map(|g| g.member_count as i64).unwrap_or(0);
+    if target <= member_count || target > MAX_NOTIFY_TARGET {
+        command.create_followup_message(&ctx.http, |m| m.content(format!("targetは現在のメンバー数 ({}人) より大きい値を指定してください。", member_count)).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    db::add_growth_notification(guild.0 as i64, command.channel_id.0 as i64, command.user.id.0 as i64, role.as_ref().map(|r| r.id.0 as i64), target, Utc::now().timestamp()).await?;
+    let who = role.map(|r| format!("<@&{}>", r.id.0)).unwrap_or_else(|| "あなた".to_string());
+    command.create_followup_message(&ctx.http, |m| m.content(format!("メンバー数が{}人に達したら、このチャンネルで{}に通知します。", target, who)).ephemeral(true)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn joined_daily(count: i64) -> Vec<NaiveDateTime> {
+        let guild_id = testing::guild_id();
+        testing::members_joined_daily(guild_id, count, "2024-03-01").iter()
+            .filter_map(|m| m.joined_at.and_then(|j| chrono::DateTime::from_timestamp(j.unix_timestamp(), 0).map(|d| d.naive_utc())))
+            .collect()
+    }
+
+    #[test]
+    fn steady_growth_is_extrapolated_linearly() {
+        let dates = joined_daily(60);
+        let date = predict_date(Model::Trend, &dates, 90).unwrap().expect("target reached");
+        let expected = chrono::NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        assert!((date - expected).num_days().abs() <= 3, "{}", date);
+    }
+
+    #[test]
+    fn targets_already_passed_are_reached_on_the_last_join() {
+        let dates = joined_daily(30);
+        assert_eq!(predict_date(Model::Trend, &dates, 10).unwrap(), chrono::NaiveDate::from_ymd_opt(2024, 3, 1));
+    }
+
+    #[test]
+    fn prediction_chart_matches_golden() {
+        testing::chart_fonts();
+        let dates = testing::chart_join_dates(150);
+        let prediction = testing::run(predict_with(Model::Trend, &dates, 250, chart::STANDARD, Locale::Ja)).unwrap().expect("fitted");
+        assert!(prediction.date.is_some());
+        testing::assert_golden("growth_prediction", &prediction.image);
+    }
+
+    #[test]
+    fn a_single_join_is_not_enough_to_fit() {
+        let dates = joined_daily(1);
+        for model in [Model::Polynomial, Model::Trend, Model::Exponential, Model::Logistic] {
+            assert!(predict_date(model, &dates, 10).unwrap().is_none());
+        }
+    }
+}
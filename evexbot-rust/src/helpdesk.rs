@@ -0,0 +1,149 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::{GuildChannel, Message};
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+
+use crate::commands::{Command, Defer, Invocation};
+use crate::db;
+use crate::duplicates;
+use crate::permissions;
+use crate::triage;
+
+pub const RESOLVE_BUTTON_ID: &str = "help_resolve";
+/// Prepended to the thread name once resolved, so it stands out in the channel list.
+const RESOLVED_PREFIX: &str = "✅ ";
+const MAX_THREAD_NAME_LEN: usize = 100;
+const DEFAULT_DAYS: i64 = 30;
+const MIN_DAYS: i64 = 7;
+const MAX_DAYS: i64 = 365;
+
+/// Track a new thread in a help channel and post the 解決済み button in it.
+pub async fn handle_thread_create(ctx: &Context, thread: &GuildChannel) -> Result<()> {
+    let parent = match thread.parent_id { Some(p) => p, None => return Ok(()) };
+    let channels = db::get_triage_channels(thread.guild_id.0 as i64).await?;
+    if !channels.contains(&(parent.0 as i64)) { return Ok(()); }
+
+    let asker = triage::asker(ctx, thread).await;
+    // Gateway resends thread creates when the bot is added later; only the first one posts.
+    if !db::track_help_thread(thread.id.0 as i64, thread.guild_id.0 as i64, asker.map(|a| a as i64), Utc::now().timestamp()).await? { return Ok(()); }
+    let question = question_text(ctx, thread, parent).await;
+    if let Err(e) = duplicates::suggest(ctx, thread, &question).await {
+        log::warn!("helpdesk: failed to suggest related answers in {}: {}", thread.id.0, e);
+    }
+    thread.id.send_message(&ctx.http, |m| {
+        m.content("質問が解決したら、質問者またはスタッフが下のボタンを押してください。")
+            .components(|c| c.create_action_row(|ar| ar.create_button(|b| b.custom_id(RESOLVE_BUTTON_ID).label("✅ 解決済み").style(ButtonStyle::Success))))
+    }).await?;
+    Ok(())
+}
+
+/// Text of the question that opened `thread`. The starter message shares the thread's id and
+/// sits in the parent channel, or in the thread itself for forum posts.
+async fn question_text(ctx: &Context, thread: &GuildChannel, parent: ChannelId) -> String {
+    for channel in [parent, thread.id] {
+        if let Ok(message) = channel.message(&ctx.http, thread.id.0).await { return message.content; }
+    }
+    String::new()
+}
+
+/// Note when a tracked thread gets its first reply from someone other than the asker.
+pub async fn handle_message(msg: &Message) -> Result<()> {
+    if msg.author.bot || msg.guild_id.is_none() { return Ok(()); }
+    db::mark_help_thread_answered(msg.channel_id.0 as i64, msg.author.id.0 as i64, msg.timestamp.unix_timestamp()).await
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let thread = match db::get_help_thread(comp.channel_id.0 as i64).await? {
+        Some(t) => t,
+        None => return reply(ctx, comp, "このスレッドは記録されていません。").await,
+    };
+    let is_asker = thread.asker_id == Some(comp.user.id.0 as i64);
+    let is_staff = match comp.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+    if !is_asker && !is_staff { return reply(ctx, comp, "質問者またはスタッフのみ解決済みにできます。").await; }
+
+    let now = Utc::now().timestamp();
+    if !db::resolve_help_thread(thread.thread_id, comp.user.id.0 as i64, now).await? {
+        return reply(ctx, comp, "このスレッドはすでに解決済みです。").await;
+    }
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| {
+        d.content(format!("✅ <@{}> さんが解決済みにしました (質問から{})", comp.user.id.0, format_span(now - thread.created_at))).components(|c| c)
+    })).await?;
+
+    if let Ok(serenity::model::channel::Channel::Guild(channel)) = comp.channel_id.to_channel(ctx).await {
+        if !channel.name.starts_with(RESOLVED_PREFIX) {
+            let name: String = format!("{}{}", RESOLVED_PREFIX, channel.name).chars().take(MAX_THREAD_NAME_LEN).collect();
+            if let Err(e) = comp.channel_id.edit_thread(&ctx.http, |t| t.name(name)).await {
+                log::warn!("helpdesk: failed to rename resolved thread {}: {}", comp.channel_id.0, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn reply(ctx: &Context, comp: &MessageComponentInteraction, msg: &str) -> Result<()> {
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+fn median(values: &mut [i64]) -> Option<i64> {
+    if values.is_empty() { return None; }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2 } else { values[mid] })
+}
+
+fn format_span(seconds: i64) -> String {
+    let minutes = (seconds.max(0) + 59) / 60;
+    match minutes {
+        0..=59 => format!("{}分", minutes),
+        60..=1439 => format!("{}時間{}分", minutes / 60, minutes % 60),
+        _ => format!("{}日{}時間", minutes / 1440, minutes % 1440 / 60),
+    }
+}
+
+pub struct HelpStatsCommand;
+
+#[async_trait]
+impl Command for HelpStatsCommand {
+    fn name(&self) -> &'static str { "helpstats" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("ヘルプスレッドの回答・解決までの時間を集計します")
+            .create_option(|o| o.name("days").description(format!("集計する日数 (デフォルト{}日)", DEFAULT_DAYS)).kind(CommandOptionType::Integer).min_int_value(MIN_DAYS).max_int_value(MAX_DAYS).required(false))
+    }
+
+    fn defer(&self) -> Defer { Defer::UserPreference }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let gid = inv.command.guild_id.ok_or_else(|| anyhow::anyhow!("Guild only command"))?.0 as i64;
+        let days = inv.args.int("days").unwrap_or(DEFAULT_DAYS).clamp(MIN_DAYS, MAX_DAYS);
+        let threads = db::get_help_threads_since(gid, Utc::now().timestamp() - days * 86400).await?;
+        if threads.is_empty() { return inv.say(format!("直近{}日間に記録されたヘルプスレッドはありません。", days)).await; }
+
+        let mut to_answer: Vec<i64> = threads.iter().filter_map(|t| t.first_answer_at.map(|a| a - t.created_at)).collect();
+        let mut to_resolve: Vec<i64> = threads.iter().filter_map(|t| t.resolved_at.map(|r| r - t.created_at)).collect();
+        let waiting = threads.iter().filter(|t| t.first_answer_at.is_none() && t.resolved_at.is_none()).count();
+        let (answered, resolved) = (to_answer.len(), to_resolve.len());
+        let pct = |n: usize| n as f64 * 100.0 / threads.len() as f64;
+
+        let mut embed = CreateEmbed::default();
+        embed.title(format!("ヘルプスレッドの集計 (直近{}日)", days));
+        embed.field("質問", format!("{}件", threads.len()), true);
+        embed.field("回答あり", format!("{}件 ({:.0}%)", answered, pct(answered)), true);
+        embed.field("解決済み", format!("{}件 ({:.0}%)", resolved, pct(resolved)), true);
+        embed.field("最初の回答までの中央値", median(&mut to_answer).map(format_span).unwrap_or_else(|| "-".to_string()), true);
+        embed.field("解決までの中央値", median(&mut to_resolve).map(format_span).unwrap_or_else(|| "-".to_string()), true);
+        embed.field("回答待ち", format!("{}件", waiting), true);
+        embed.color(serenity::utils::Colour::BLURPLE);
+        embed.footer(|f| f.text("スレッド作成から計測しています"));
+        inv.command.create_followup_message(&inv.ctx.http, |m| m.embed(|e| { *e = embed; e })).await?;
+        Ok(())
+    }
+}
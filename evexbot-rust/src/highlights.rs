@@ -0,0 +1,190 @@
+use anyhow::Result;
+use chrono::{Datelike, Utc, Weekday};
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::{ChannelType, Reaction, ReactionType};
+use serenity::model::id::{ChannelId, GuildId};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+use crate::scheduler;
+
+const DIGEST_CHECK_INTERVAL_SECONDS: u64 = 3600;
+const WEEK_SECONDS: i64 = 7 * 86400;
+const TOP_N: i64 = 10;
+const PREVIEW_LENGTH: usize = 80;
+const MAX_THRESHOLD: i64 = 100;
+/// Reactions that count as a vote for the digest.
+const VOTE_EMOJI: &[&str] = &["⭐", "🌟", "👍"];
+
+/// Start the weekly digest job. It checks hourly and posts on Monday (UTC).
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("weekly-highlights", Duration::from_secs(DIGEST_CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { send_due_digests(&http).await }
+    });
+}
+
+fn is_vote(emoji: &ReactionType) -> bool {
+    matches!(emoji, ReactionType::Unicode(e) if VOTE_EMOJI.contains(&e.as_str()))
+}
+
+/// Count a vote on a message in a tracked channel. Authors voting for themselves don't count.
+pub async fn handle_reaction(http: &Http, reaction: &Reaction, added: bool) -> Result<()> {
+    if !is_vote(&reaction.emoji) { return Ok(()); }
+    let (guild_id, user_id) = match (reaction.guild_id, reaction.user_id) { (Some(g), Some(u)) => (g, u), _ => return Ok(()) };
+    if !db::is_highlight_channel(reaction.channel_id.0 as i64).await? { return Ok(()); }
+
+    let message_id = reaction.message_id.0 as i64;
+    let author = match db::get_highlight_author(message_id).await? {
+        Some(a) => a,
+        None if added => {
+            let message = reaction.message(http).await?;
+            if message.author.bot { return Ok(()); }
+            let author = message.author.id.0 as i64;
+            db::track_highlight_message(message_id, guild_id.0 as i64, reaction.channel_id.0 as i64, author, message.timestamp.unix_timestamp()).await?;
+            author
+        }
+        // Removing a vote from a message that was never scored.
+        None => return Ok(()),
+    };
+    if author == user_id.0 as i64 { return Ok(()); }
+    db::adjust_highlight_score(message_id, if added { 1 } else { -1 }).await
+}
+
+async fn build_digest(http: &Http, guild_id: GuildId, threshold: i64) -> Result<Option<CreateEmbed>> {
+    let since = Utc::now().timestamp() - WEEK_SECONDS;
+    let top = db::get_top_highlights(guild_id.0 as i64, since, threshold, TOP_N).await?;
+    if top.is_empty() { return Ok(None); }
+
+    let mut lines = Vec::new();
+    for (i, db::Highlight { channel_id, message_id, author_id, score }) in top.iter().enumerate() {
+        let link = format!("https://discord.com/channels/{}/{}/{}", guild_id.0, channel_id, message_id);
+        // Deleted messages keep their score but drop out of the digest.
+        let message = match ChannelId(*channel_id as u64).message(http, *message_id as u64).await { Ok(m) => m, Err(_) => continue };
+        // Authors with privacy mode on are linked without quoting.
+        let preview = if db::get_privacy_enabled(*author_id).await? { String::new() } else {
+            let text: String = message.content.replace('\n', " ").chars().take(PREVIEW_LENGTH).collect();
+            if text.is_empty() { String::new() } else { format!("\n> {}", text) }
+        };
+        lines.push(format!("**{}.** ⭐ {} — <@{}> in <#{}> [移動]({}){}", i + 1, score, author_id, channel_id, link, preview));
+    }
+    if lines.is_empty() { return Ok(None); }
+
+    let mut embed = CreateEmbed::default();
+    embed.title("🏆 今週の人気投稿");
+    embed.description(lines.join("\n"));
+    embed.color(serenity::utils::Colour::GOLD);
+    embed.footer(|f| f.text(format!("⭐ 🌟 👍 のリアクションが{}件以上の投稿", threshold)));
+    embed.timestamp(Utc::now().to_rfc3339());
+    Ok(Some(embed))
+}
+
+async fn send_due_digests(http: &Http) -> Result<()> {
+    let today = Utc::now().date_naive();
+    if today.weekday() != Weekday::Mon { return Ok(()); }
+    let week = format!("{}-W{:02}", today.iso_week().year(), today.iso_week().week());
+
+    for settings in db::get_enabled_highlights().await? {
+        if settings.last_sent.as_deref() == Some(week.as_str()) { continue; }
+        let channel = match settings.channel_id { Some(c) => ChannelId(c as u64), None => continue };
+        match build_digest(http, GuildId(settings.guild_id as u64), settings.threshold).await {
+            Ok(Some(embed)) => {
+                if let Err(e) = channel.send_message(http, |m| m.embed(|e| { *e = embed; e })).await {
+                    log::warn!("highlights: failed to post digest for guild {}: {}", settings.guild_id, e);
+                    continue;
+                }
+            }
+            // A quiet week is skipped rather than posting an empty digest.
+            Ok(None) => {}
+            Err(e) => {
+                log::warn!("highlights: failed to build digest for guild {}: {}", settings.guild_id, e);
+                continue;
+            }
+        }
+        db::mark_highlights_sent(settings.guild_id, &week).await?;
+    }
+    db::prune_highlight_scores(Utc::now().timestamp() - 2 * WEEK_SECONDS).await?;
+    Ok(())
+}
+
+pub struct HighlightsCommand;
+
+#[async_trait]
+impl Command for HighlightsCommand {
+    fn name(&self) -> &'static str { "highlights" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        let text = [ChannelType::Text, ChannelType::News];
+        c.description("今週の人気投稿 (⭐ 🌟 👍 リアクション) のまとめ")
+            .create_option(|s| {
+                s.name("channel").description("毎週月曜にまとめを投稿するチャンネル").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("channel").description("投稿先 (省略で無効化)").kind(CommandOptionType::Channel).channel_types(&text).required(false))
+            })
+            .create_option(|s| {
+                s.name("track").description("リアクションを集計するチャンネルを追加します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("channel").description("対象チャンネル").kind(CommandOptionType::Channel).channel_types(&text).required(true))
+            })
+            .create_option(|s| {
+                s.name("untrack").description("集計対象からチャンネルを外します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("channel").description("対象チャンネル").kind(CommandOptionType::Channel).channel_types(&text).required(true))
+            })
+            .create_option(|s| {
+                s.name("threshold").description("まとめに載せる最小リアクション数").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("count").description("リアクション数").kind(CommandOptionType::Integer).min_int_value(1).max_int_value(MAX_THRESHOLD).required(true))
+            })
+            .create_option(|s| s.name("preview").description("直近7日間のまとめを表示します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        let gid = guild_id.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+        let channel = args.channel("channel").map(|c| c.id.0 as i64);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "channel" => {
+                db::update_highlight_channel(gid, channel).await?;
+                match channel {
+                    Some(c) => inv.say(format!("毎週月曜に <#{}> へ今週の人気投稿を投稿します。集計するチャンネルは `/highlights track` で追加してください。", c)).await,
+                    None => inv.say("今週の人気投稿のまとめを無効にしました。").await,
+                }
+            }
+            "track" => {
+                let c = match channel { Some(c) => c, None => return Ok(()) };
+                db::add_highlight_channel(gid, c).await?;
+                let count = db::get_highlight_channels(gid).await?.len();
+                inv.say(format!("<#{}> のリアクションを集計します (計{}チャンネル)。", c, count)).await
+            }
+            "untrack" => {
+                let c = match channel { Some(c) => c, None => return Ok(()) };
+                if db::remove_highlight_channel(gid, c).await? { inv.say(format!("<#{}> を集計対象から外しました。", c)).await } else { inv.say("そのチャンネルは集計対象ではありません。").await }
+            }
+            "threshold" => {
+                let count = sub.options.iter().find(|o| o.name == "count").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+                if !(1..=MAX_THRESHOLD).contains(&count) { return inv.say(format!("リアクション数は1～{}で指定してください。", MAX_THRESHOLD)).await; }
+                db::update_highlight_threshold(gid, count).await?;
+                inv.say(format!("リアクションが{}件以上の投稿をまとめに載せます。", count)).await
+            }
+            "preview" => {
+                let settings = db::get_highlight_settings(gid).await?;
+                match build_digest(&inv.ctx.http, guild_id, settings.threshold).await? {
+                    Some(embed) => { command.create_followup_message(&inv.ctx.http, |m| m.embed(|e| { *e = embed; e }).ephemeral(true)).await?; Ok(()) }
+                    None => inv.say("直近7日間にしきい値を超えた投稿はありません。").await,
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::Result;
 use regex::Regex;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
 use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
 use serenity::prelude::*;
 use std::time::Duration;
 use reqwest::Client;
 
+use crate::commands::{Command, Defer, Invocation};
 
-const API_BASE_URL: &str = "https://image-ai.evex.land";
+
+pub const API_BASE_URL: &str = "https://image-ai.evex.land";
 const MAX_PROMPT_LENGTH: usize = 1000;
 
 fn validate_prompt(prompt: &str) -> Result<(), String> {
@@ -19,13 +24,36 @@ fn validate_prompt(prompt: &str) -> Result<(), String> {
     Ok(())
 }
 
-pub async fn handle_imagegen(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
-    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
-    let prompt = command.data.options.get(0).and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+pub struct ImagegenCommand;
+
+#[async_trait]
+impl Command for ImagegenCommand {
+    fn name(&self) -> &'static str { "imagegen" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("与えられたプロンプトに基づいて画像を生成します").create_option(|o| o.name("prompt").description("生成する画像の説明（プロンプト）").kind(CommandOptionType::String).required(true))
+    }
+
+    fn defer(&self) -> Defer { Defer::Public }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_imagegen(inv.ctx, inv.command, inv.args.str("prompt").unwrap_or("")).await
+    }
+}
+
+async fn handle_imagegen(ctx: &Context, command: &ApplicationCommandInteraction, prompt: &str) -> Result<()> {
     if let Err(err) = validate_prompt(prompt) { command.create_followup_message(&ctx.http, |m| m.content(err)).await?; return Ok(()); }
+    if crate::status::is_down(crate::status::IMAGEGEN) { command.create_followup_message(&ctx.http, |m| m.content(crate::status::BACKEND_DOWN_MESSAGE)).await?; return Ok(()); }
 
     let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
-    let resp = client.get(format!("{}/?prompt={}", API_BASE_URL, urlencoding::encode(prompt))).send().await;
+    let mut req = client.get(format!("{}/?prompt={}", API_BASE_URL, urlencoding::encode(prompt)));
+    // Guilds with their own key are billed separately from the shared quota.
+    if let Some(guild_id) = command.guild_id {
+        if let Ok(Some(key)) = crate::vault::get_api_key(guild_id.0 as i64, "imagegen").await {
+            req = req.bearer_auth(key);
+        }
+    }
+    let resp = req.send().await;
     match resp {
         Ok(r) => {
             if r.status().is_success() {
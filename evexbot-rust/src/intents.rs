@@ -0,0 +1,68 @@
+use once_cell::sync::Lazy;
+use serenity::prelude::GatewayIntents;
+
+/// Intents every feature relies on; none of these need approval in the developer portal.
+fn base() -> GatewayIntents {
+    GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::GUILD_EMOJIS_AND_STICKERS
+        | GatewayIntents::GUILD_VOICE_STATES
+        | GatewayIntents::GUILD_BANS
+        | GatewayIntents::GUILD_SCHEDULED_EVENTS
+}
+
+const PRIVILEGED: &[(&str, GatewayIntents)] = &[
+    ("guild_members", GatewayIntents::GUILD_MEMBERS),
+    ("message_content", GatewayIntents::MESSAGE_CONTENT),
+];
+
+/// Features that stop working without a privileged intent, checked by `report`.
+const FEATURES: &[(&str, GatewayIntents)] = &[
+    ("messagelink", crate::messagelink::REQUIRED_INTENTS),
+    ("linkfilter", crate::linkfilter::REQUIRED_INTENTS),
+    ("wordfilter", crate::wordfilter::REQUIRED_INTENTS),
+    ("zikosyokai", crate::zikosyokai::REQUIRED_INTENTS),
+    ("growth", crate::growth::REQUIRED_INTENTS),
+    ("prune", crate::prune::REQUIRED_INTENTS),
+    ("role-decay", crate::role_decay::REQUIRED_INTENTS),
+    ("logging", crate::logging::REQUIRED_INTENTS),
+];
+
+/// PRIVILEGED_INTENTS is a comma-separated list (default `guild_members,message_content`);
+/// set it to `none` when the portal toggles are off, or Discord closes the gateway with 4014.
+static ENABLED: Lazy<GatewayIntents> = Lazy::new(|| {
+    let names = std::env::var("PRIVILEGED_INTENTS").unwrap_or_else(|_| "guild_members,message_content".to_string());
+    let mut intents = base();
+    for name in names.split(',').map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty() && s != "none") {
+        match PRIVILEGED.iter().find(|(n, _)| *n == name) {
+            Some((_, intent)) => intents |= *intent,
+            None => log::warn!("intents: unknown privileged intent `{}` in PRIVILEGED_INTENTS", name),
+        }
+    }
+    intents
+});
+
+/// The intents to connect with.
+pub fn configured() -> GatewayIntents {
+    *ENABLED
+}
+
+pub fn has(required: GatewayIntents) -> bool {
+    ENABLED.contains(required)
+}
+
+/// PRIVILEGED_INTENTS names of the intents in `required` that are not configured.
+pub fn missing(required: GatewayIntents) -> Vec<&'static str> {
+    PRIVILEGED.iter().filter(|(_, i)| required.contains(*i) && !ENABLED.contains(*i)).map(|(n, _)| *n).collect()
+}
+
+/// Log each feature that is disabled because its intents are not configured.
+pub fn report() {
+    for (feature, required) in FEATURES {
+        let missing = missing(*required);
+        if !missing.is_empty() {
+            log::warn!("intents: {} is disabled because PRIVILEGED_INTENTS lacks {}", feature, missing.join(", "));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::guild::Member;
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::report::Report;
+use crate::permissions;
+
+const MAX_INVITE_USES: i64 = 100;
+const MAX_INVITE_AGE_HOURS: i64 = 168;
+
+/// Last known use count of each tracked invite, used to attribute joins.
+static USES: Lazy<Mutex<HashMap<GuildId, HashMap<String, u64>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Seed the use counts so the first join after startup can be attributed.
+pub async fn handle_guild_create(ctx: &Context, guild_id: GuildId) -> Result<()> {
+    let tracked = db::get_tracked_invite_codes(guild_id.0 as i64).await?;
+    if tracked.is_empty() { return Ok(()); }
+    let current: HashMap<String, u64> = guild_id.invites(&ctx.http).await?.into_iter()
+        .filter(|i| tracked.contains(&i.code))
+        .map(|i| (i.code, i.uses))
+        .collect();
+    USES.lock().await.insert(guild_id, current);
+    Ok(())
+}
+
+/// Attribute a join to the tracked invite whose use count went up.
+pub async fn handle_member_join(ctx: &Context, member: &Member) -> Result<()> {
+    let guild_id = member.guild_id;
+    let tracked = db::get_tracked_invite_codes(guild_id.0 as i64).await?;
+    if tracked.is_empty() { return Ok(()); }
+
+    let current: HashMap<String, u64> = guild_id.invites(&ctx.http).await?.into_iter()
+        .filter(|i| tracked.contains(&i.code))
+        .map(|i| (i.code, i.uses))
+        .collect();
+
+    let previous = USES.lock().await.insert(guild_id, current.clone());
+    let previous = match previous { Some(p) => p, None => return Ok(()) };
+
+    // An invite that hit max_uses disappears from the list; treat that as the used one too.
+    let used = current.iter().find(|(code, uses)| previous.get(*code).map(|p| *uses > p).unwrap_or(**uses > 0)).map(|(c, _)| c.clone())
+        .or_else(|| previous.keys().find(|c| !current.contains_key(*c)).cloned());
+    if let Some(code) = used {
+        db::record_invite_join(guild_id.0 as i64, &code, member.user.id.0 as i64, Utc::now().timestamp()).await?;
+    }
+    Ok(())
+}
+
+pub async fn handle_member_remove(guild_id: GuildId, user_id: UserId) -> Result<()> {
+    db::mark_invite_member_left(guild_id.0 as i64, user_id.0 as i64, Utc::now().timestamp()).await
+}
+
+pub struct InviteCommand;
+
+#[async_trait]
+impl Command for InviteCommand {
+    fn name(&self) -> &'static str { "invite" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("招待リンクの作成と効果測定")
+            .create_option(|o| {
+                o.name("create").description("追跡する招待リンクを作成します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("reason").description("用途 (例: Xの告知用)").kind(CommandOptionType::String).required(true))
+                    .create_sub_option(|s| s.name("max_uses").description("最大使用回数 (0で無制限)").kind(CommandOptionType::Integer).required(false))
+                    .create_sub_option(|s| s.name("max_age").description("有効期間 (時間, 0で無期限)").kind(CommandOptionType::Integer).required(false))
+                    .create_sub_option(|s| s.name("channel").description("招待先チャンネル (デフォルト: このチャンネル)").kind(CommandOptionType::Channel).required(false))
+            })
+            .create_option(|o| o.name("stats").description("招待リンクごとの参加数を表示します").kind(CommandOptionType::SubCommand))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_invite_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_invite_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    match sub.name.as_str() {
+        "create" => {
+            let reason = sub.options.iter().find(|o| o.name=="reason").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").to_string();
+            let max_uses = sub.options.iter().find(|o| o.name=="max_uses").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+            let max_age_hours = sub.options.iter().find(|o| o.name=="max_age").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+            let channel_id = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.id), _ => None }).unwrap_or(command.channel_id);
+
+            if !(0..=MAX_INVITE_USES).contains(&max_uses) { command.create_followup_message(&ctx.http, |m| m.content(format!("max_usesは0～{}の間で指定してください。", MAX_INVITE_USES)).ephemeral(true)).await?; return Ok(()); }
+            if !(0..=MAX_INVITE_AGE_HOURS).contains(&max_age_hours) { command.create_followup_message(&ctx.http, |m| m.content(format!("max_ageは0～{}時間の間で指定してください。", MAX_INVITE_AGE_HOURS)).ephemeral(true)).await?; return Ok(()); }
+
+            let invite = channel_id.create_invite(&ctx.http, |i| i.max_uses(max_uses as u64).max_age((max_age_hours * 3600) as u64).unique(true)).await?;
+            db::add_tracked_invite(&invite.code, guild_id.0 as i64, channel_id.0 as i64, command.user.id.0 as i64, &reason, max_uses, max_age_hours * 3600, Utc::now().timestamp()).await?;
+            USES.lock().await.entry(guild_id).or_default().insert(invite.code.clone(), 0);
+
+            command.create_followup_message(&ctx.http, |m| m.content(format!("招待リンクを作成しました: https://discord.gg/{}\n用途: {}", invite.code, reason)).ephemeral(true)).await?;
+        }
+        "stats" => {
+            let stats = db::get_invite_stats(guild_id.0 as i64).await?;
+            let report = Report::embed("invite_stats.txt", "招待リンクの効果", serenity::utils::Colour::BLURPLE).ephemeral(true);
+            let report = if stats.is_empty() {
+                report.line("追跡中の招待リンクはありません。/invite create で作成できます。")
+            } else {
+                report.lines(stats.iter().map(|s| {
+                    let retention = if s.joins > 0 { format!(" ({:.0}%)", s.still_present as f64 / s.joins as f64 * 100.0) } else { String::new() };
+                    format!("**{} — {}**\n参加: {}人 / 在籍中: {}人{}\n作成: <@{}> <t:{}:d>\n", s.code, s.reason, s.joins, s.still_present, retention, s.creator_id, s.created_at)
+                }))
+            };
+            report.send(&ctx.http, command).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::guild::Member;
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::modlog;
+use crate::scheduler;
+use crate::permissions;
+
+const PENDING_CHECK_INTERVAL_SECONDS: u64 = 600;
+const MAX_ACCOUNT_AGE_DAYS: i64 = 365;
+
+/// Apply the account-age gate to a joining member. Returns true when the member
+/// passed and should be welcomed; young accounts are flagged to the mod-log and
+/// their autorole is deferred until the account is old enough.
+pub async fn handle_member_join(ctx: &Context, member: &Member) -> Result<bool> {
+    if member.user.bot {
+        return Ok(true);
+    }
+
+    let guild_id = member.guild_id.0 as i64;
+    let db::JoinGateSettings { min_account_age_days: min_age_days, autorole_id } = db::get_joingate_settings(guild_id).await?;
+
+    let created_at = member.user.id.created_at().unix_timestamp();
+    let eligible_at = created_at + min_age_days * 86400;
+    let now = Utc::now().timestamp();
+
+    if min_age_days <= 0 || eligible_at <= now {
+        if let Some(role_id) = autorole_id {
+            ctx.http.add_member_role(member.guild_id.0, member.user.id.0, role_id as u64, Some("autorole")).await?;
+        }
+        return Ok(true);
+    }
+
+    if autorole_id.is_some() {
+        db::add_pending_autorole(guild_id, member.user.id.0 as i64, eligible_at).await?;
+    }
+
+    let age_days = (now - created_at) / 86400;
+    let mut embed = CreateEmbed::default();
+    embed.title("⚠️ 新規アカウントの参加");
+    embed.description(format!("{} ({}) が参加しました。\nアカウント作成から{}日 (しきい値: {}日)\nウェルカムメッセージを送信していません。", member.user.mention(), member.user.tag(), age_days, min_age_days));
+    embed.field("ロール付与予定", format!("<t:{}:R>", eligible_at), true);
+    embed.color(serenity::utils::Colour::ORANGE);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Join Gate"));
+    modlog::send(&ctx.http, member.guild_id, embed).await?;
+
+    Ok(false)
+}
+
+pub async fn handle_member_remove(guild_id: GuildId, user_id: UserId) -> Result<()> {
+    db::remove_pending_autorole(guild_id.0 as i64, user_id.0 as i64).await
+}
+
+/// Start the background job that grants deferred autoroles.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("joingate-autoroles", Duration::from_secs(PENDING_CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { grant_due_autoroles(&http).await }
+    });
+}
+
+async fn grant_due_autoroles(http: &Http) -> Result<()> {
+    for (guild_id, user_id) in db::get_due_pending_autoroles(Utc::now().timestamp()).await? {
+        if let Some(role_id) = db::get_joingate_settings(guild_id).await?.autorole_id {
+            if let Err(e) = http.add_member_role(guild_id as u64, user_id as u64, role_id as u64, Some("autorole (account age gate passed)")).await {
+                log::warn!("joingate: could not add role {} to {} in {}: {}", role_id, user_id, guild_id, e);
+            }
+        }
+        db::remove_pending_autorole(guild_id, user_id).await?;
+    }
+    Ok(())
+}
+
+pub struct JoinGateCommand;
+
+#[async_trait]
+impl Command for JoinGateCommand {
+    fn name(&self) -> &'static str { "joingate" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("参加時のアカウント年齢チェックと自動ロールの設定").create_option(|o| {
+            o.name("min_account_age").description("ウェルカム対象とする最小アカウント日数 (0で無効)").kind(serenity::model::application::command::CommandOptionType::Integer).required(false)
+        }).create_option(|o| {
+            o.name("autorole").description("参加時に付与するロール").kind(serenity::model::application::command::CommandOptionType::Role).required(false)
+        }).create_option(|o| {
+            o.name("clear_autorole").description("自動ロールを解除する").kind(serenity::model::application::command::CommandOptionType::Boolean).required(false)
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_joingate_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_joingate_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+    let min_age = command.data.options.iter().find(|o| o.name=="min_account_age").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64());
+    let role = command.data.options.iter().find(|o| o.name=="autorole").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Role(role) => Some(role.clone()), _ => None });
+    let clear_autorole = command.data.options.iter().find(|o| o.name=="clear_autorole").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+
+    if let Some(days) = min_age {
+        if !(0..=MAX_ACCOUNT_AGE_DAYS).contains(&days) { command.create_followup_message(&ctx.http, |m| m.content(format!("0～{}日の間で指定してください。", MAX_ACCOUNT_AGE_DAYS)).ephemeral(true)).await?; return Ok(()); }
+    }
+
+    if clear_autorole {
+        db::clear_joingate_autorole(guild_id).await?;
+    }
+    db::update_joingate_settings(guild_id, min_age, role.as_ref().map(|r| r.id.0 as i64)).await?;
+
+    let settings = db::get_joingate_settings(guild_id).await?;
+    let role_text = settings.autorole_id.map(|id| format!("<@&{}>", id)).unwrap_or_else(|| "なし".to_string());
+    command.create_followup_message(&ctx.http, |m| m.content(format!("参加ゲートを更新しました!\n最小アカウント日数: {}日\n自動ロール: {}", settings.min_account_age_days, role_text)).ephemeral(true)).await?;
+    Ok(())
+}
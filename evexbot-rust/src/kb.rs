@@ -0,0 +1,150 @@
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::InputTextStyle;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::modal::ModalSubmitInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::prelude::*;
+
+use crate::commands::{Args, Command, Invocation};
+use crate::components::modal_value;
+use crate::db;
+use crate::permissions;
+
+pub const ADD_MODAL_ID: &str = "kb_add";
+const MAX_TITLE_LENGTH: u64 = 100;
+const MAX_BODY_LENGTH: u64 = 4000;
+const MAX_RESULTS: i64 = 5;
+const EXCERPT_LEN: usize = 150;
+
+/// Whitespace-separated search terms, full-width spaces included.
+pub fn terms(query: &str) -> Vec<String> {
+    query.split(|c: char| c.is_whitespace() || c == '　').filter(|t| !t.is_empty()).map(|t| t.to_string()).collect()
+}
+
+/// Search keywords in free text: runs of letters and digits (plus `._-`, for names like
+/// `discord.py`) of three characters or more, which the trigram index can look up. Hiragana
+/// splits runs too, since in Japanese it mostly marks particles and verb endings.
+pub fn keywords(text: &str, max: usize) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let splits = |c: char| !(c.is_alphanumeric() || "._-".contains(c)) || ('\u{3041}'..='\u{309f}').contains(&c);
+    for token in text.split(splits) {
+        let token = token.trim_matches(|c| "._-".contains(c)).to_lowercase();
+        if token.chars().count() >= 3 && !out.contains(&token) { out.push(token); }
+        if out.len() >= max { break; }
+    }
+    out
+}
+
+/// Up to `EXCERPT_LEN` characters of `body` around the first term it contains.
+pub fn excerpt(body: &str, terms: &[String]) -> String {
+    let chars: Vec<char> = body.chars().collect();
+    let lower = body.to_lowercase();
+    let start = terms.iter().filter_map(|t| lower.find(&t.to_lowercase())).min()
+        .map(|byte| lower[..byte].chars().count().saturating_sub(EXCERPT_LEN / 3))
+        .unwrap_or(0)
+        .min(chars.len());
+    let text: String = chars[start..].iter().take(EXCERPT_LEN).collect::<String>().replace('\n', " ");
+    format!("{}{}{}", if start > 0 { "…" } else { "" }, text, if chars.len() - start > EXCERPT_LEN { "…" } else { "" })
+}
+
+async fn respond(ctx: &Context, command: &ApplicationCommandInteraction, embed: CreateEmbed) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.embed(|e| { *e = embed; e }))).await?;
+    Ok(())
+}
+
+pub async fn handle_modal(ctx: &Context, modal: &ModalSubmitInteraction) -> Result<()> {
+    let guild_id = modal.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let is_admin = match modal.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+    let title = modal_value(modal, "title").unwrap_or_default();
+    let body = modal_value(modal, "body").unwrap_or_default();
+    let msg = if !is_admin {
+        permissions::DENIED_MESSAGE.to_string()
+    } else if title.trim().is_empty() || body.trim().is_empty() {
+        "タイトルと本文を入力してください。".to_string()
+    } else {
+        let id = db::add_kb_article(guild_id, title.trim(), body.trim(), modal.user.id.0 as i64, Utc::now().timestamp()).await?;
+        format!("記事 #{}「{}」を追加しました。`/kb show id:{}` で表示できます。", id, title.trim(), id)
+    };
+    modal.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+pub struct KbCommand;
+
+#[async_trait]
+impl Command for KbCommand {
+    fn name(&self) -> &'static str { "kb" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("よくある質問の回答集を検索・管理します")
+            .create_option(|s| {
+                s.name("search").description("記事を検索します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("query").description("キーワード (スペース区切りですべてを含む記事)").kind(CommandOptionType::String).required(true))
+            })
+            .create_option(|s| {
+                s.name("show").description("記事を表示します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("記事の番号").kind(CommandOptionType::Integer).min_int_value(1).required(true))
+            })
+            .create_option(|s| s.name("add").description("記事を追加します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("remove").description("記事を削除します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("記事の番号").kind(CommandOptionType::Integer).min_int_value(1).required(true))
+            })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        match sub.name.as_str() {
+            "search" => {
+                let query = args.str("query").unwrap_or("");
+                let terms = terms(query);
+                let articles = db::search_kb_articles(gid, &terms, MAX_RESULTS).await?;
+                if articles.is_empty() { return inv.say(format!("「{}」に一致する記事はありません。", query)).await; }
+                let mut embed = CreateEmbed::default();
+                embed.title(format!("「{}」の検索結果", query.chars().take(50).collect::<String>()));
+                for a in &articles {
+                    embed.field(format!("#{} {}", a.id, a.title), excerpt(&a.body, &terms), false);
+                }
+                embed.color(serenity::utils::Colour::BLURPLE);
+                embed.footer(|f| f.text("/kb show id:<番号> で全文を表示できます"));
+                respond(ctx, command, embed).await
+            }
+            "show" => {
+                let id = args.int("id").unwrap_or(0);
+                let article = match db::get_kb_article(gid, id).await? { Some(a) => a, None => return inv.say(format!("記事 #{} はありません。", id)).await };
+                let mut embed = CreateEmbed::default();
+                embed.title(format!("#{} {}", article.id, article.title));
+                embed.description(&article.body);
+                embed.color(serenity::utils::Colour::BLURPLE);
+                embed.footer(|f| f.text("EvexBot | Knowledge base"));
+                if let Some(t) = Utc.timestamp_opt(article.updated_at, 0).single() { embed.timestamp(t.to_rfc3339()); }
+                respond(ctx, command, embed).await
+            }
+            "add" => {
+                if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+                command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::Modal).interaction_response_data(|d| {
+                    d.custom_id(ADD_MODAL_ID).title("記事の追加").components(|c| {
+                        c.create_action_row(|row| row.create_input_text(|t| t.custom_id("title").label("タイトル").style(InputTextStyle::Short).max_length(MAX_TITLE_LENGTH).required(true)))
+                            .create_action_row(|row| row.create_input_text(|t| t.custom_id("body").label("本文").style(InputTextStyle::Paragraph).max_length(MAX_BODY_LENGTH).required(true)))
+                    })
+                })).await?;
+                Ok(())
+            }
+            "remove" => {
+                if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+                let id = args.int("id").unwrap_or(0);
+                if db::remove_kb_article(gid, id).await? { inv.say(format!("記事 #{} を削除しました。", id)).await } else { inv.say(format!("記事 #{} はありません。", id)).await }
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::db;
+
+const LEASE_NAME: &str = "bot";
+const LEASE_SECONDS: i64 = 30;
+/// Renew well inside the lease so one slow DB round trip doesn't cost the leadership.
+const RENEW_SECONDS: u64 = 10;
+
+/// With LEADER_ELECTION off there is a single instance and it is always the leader.
+static ENABLED: Lazy<bool> = Lazy::new(|| {
+    std::env::var("LEADER_ELECTION").map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "on" | "yes")).unwrap_or(false)
+});
+
+static INSTANCE_ID: Lazy<String> = Lazy::new(|| {
+    std::env::var("INSTANCE_ID").ok().filter(|s| !s.is_empty()).unwrap_or_else(|| {
+        rand::thread_rng().sample_iter(&Alphanumeric).take(12).map(char::from).collect()
+    })
+});
+
+static IS_LEADER: AtomicBool = AtomicBool::new(false);
+
+/// On PostgreSQL leadership is the advisory lock itself; it is kept here so `release` can unlock it.
+static LOCK: Lazy<Mutex<Option<db::AdvisoryLock>>> = Lazy::new(|| Mutex::new(None));
+
+/// Only the leader runs scheduled jobs and reacts to gateway events; a follower keeps its
+/// gateway connection and cache warm so it can take over when the leader's lease lapses.
+pub fn is_leader() -> bool {
+    !*ENABLED || IS_LEADER.load(Ordering::Relaxed)
+}
+
+pub fn instance_id() -> &'static str {
+    &INSTANCE_ID
+}
+
+/// Start campaigning for leadership. Must run after `db::init_db`.
+///
+/// On PostgreSQL the leader holds a session advisory lock, which the server frees the moment the
+/// leader's connection goes away. SQLite has no such lock and can only be shared by instances on
+/// one host, so there the leader renews a lease row instead and a follower takes over at most
+/// `LEASE_SECONDS` after the leader stops renewing.
+pub fn start() {
+    if !*ENABLED { return; }
+    log::info!("leader: election enabled, instance {}", instance_id());
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(Duration::from_secs(RENEW_SECONDS));
+        loop {
+            interval.tick().await;
+            let held = match db::backend() {
+                db::Backend::Postgres => hold_advisory_lock().await,
+                db::Backend::Sqlite => renew_lease().await,
+            };
+            let was = IS_LEADER.swap(held, Ordering::Relaxed);
+            if held != was {
+                log::info!("leader: instance {} is now {}", instance_id(), if held { "leader" } else { "follower" });
+            }
+        }
+    });
+}
+
+async fn hold_advisory_lock() -> bool {
+    let mut lock = LOCK.lock().await;
+    if let Some(held) = lock.as_mut() {
+        match held.check().await {
+            Ok(()) => return true,
+            Err(e) => {
+                // The session is gone and the lock with it; try to take it again below.
+                log::warn!("leader: lost the advisory lock connection: {}", e);
+                *lock = None;
+            }
+        }
+    }
+    match db::try_advisory_lock(LEASE_NAME).await {
+        Ok(taken) => {
+            *lock = taken;
+            lock.is_some()
+        }
+        Err(e) => {
+            log::warn!("leader: advisory lock attempt failed: {}", e);
+            false
+        }
+    }
+}
+
+async fn renew_lease() -> bool {
+    match db::try_acquire_lease(LEASE_NAME, instance_id(), Utc::now().timestamp(), LEASE_SECONDS).await {
+        Ok(held) => held,
+        Err(e) => {
+            // Without the DB we can't prove the lease is still ours; step down.
+            log::warn!("leader: lease renewal failed: {}", e);
+            false
+        }
+    }
+}
+
+/// Hand the lease over on a clean shutdown instead of letting it expire.
+pub async fn release() {
+    if !*ENABLED || !IS_LEADER.swap(false, Ordering::Relaxed) { return; }
+    let result = match LOCK.lock().await.take() {
+        Some(lock) => lock.release().await,
+        None => db::release_lease(LEASE_NAME, instance_id()).await,
+    };
+    if let Err(e) = result {
+        log::warn!("leader: failed to release lease: {}", e);
+    }
+}
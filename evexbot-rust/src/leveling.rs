@@ -0,0 +1,232 @@
+use anyhow::Result;
+use chrono::Utc;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use plotters_bitmap::BitMapBackend;
+use rand::Rng;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateApplicationCommandOption, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::{ChannelType, Message};
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+use std::time::Duration;
+
+use crate::chart::{self, ChartSize};
+use crate::commands::{Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+
+/// A member earns XP for at most one message per this many seconds, so spam doesn't level up.
+const XP_COOLDOWN_SECONDS: i64 = 60;
+const MIN_XP_PER_MESSAGE: i64 = 15;
+const MAX_XP_PER_MESSAGE: i64 = 25;
+const LEADERBOARD_PAGE_SIZE: i64 = 10;
+const AVATAR_TIMEOUT_SECONDS: u64 = 5;
+
+const CARD: ChartSize = ChartSize::new(900, 260);
+const AVATAR_SIZE: u32 = 180;
+const AVATAR_X: u32 = 40;
+const TEXT_X: i32 = 250;
+const RIGHT_X: i32 = 860;
+const BAR_TOP: i32 = 170;
+const BAR_HEIGHT: i32 = 36;
+const BACKGROUND: RGBColor = RGBColor(35, 39, 42);
+const BAR_BACKGROUND: RGBColor = RGBColor(72, 75, 81);
+const BAR_FILL: RGBColor = RGBColor(88, 101, 242);
+const MUTED: RGBColor = RGBColor(185, 187, 190);
+
+/// XP needed to go from `level` to the next one.
+pub fn xp_to_next(level: i64) -> i64 {
+    5 * level * level + 50 * level + 100
+}
+
+/// Level reached with `xp` in total, the XP earned into that level, and the XP the level needs.
+pub fn progress(xp: i64) -> (i64, i64, i64) {
+    let (mut level, mut rest) = (0, xp.max(0));
+    while rest >= xp_to_next(level) {
+        rest -= xp_to_next(level);
+        level += 1;
+    }
+    (level, rest, xp_to_next(level))
+}
+
+/// Award XP for a message and announce the level-up when it crosses one.
+pub async fn handle_message(ctx: &Context, msg: &Message) -> Result<()> {
+    if msg.author.bot { return Ok(()); }
+    let guild_id = match msg.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    let amount = rand::thread_rng().gen_range(MIN_XP_PER_MESSAGE..=MAX_XP_PER_MESSAGE);
+    let (before, after) = db::add_user_xp(guild_id, msg.author.id.0 as i64, amount, Utc::now().timestamp(), XP_COOLDOWN_SECONDS).await?;
+    let level = progress(after).0;
+    if level <= progress(before).0 { return Ok(()); }
+
+    let settings = db::get_leveling_settings(guild_id).await?;
+    if !settings.announce { return Ok(()); }
+    let channel = settings.channel_id.map(|c| ChannelId(c as u64)).unwrap_or(msg.channel_id);
+    let content = format!("🎉 <@{}> さんがレベル **{}** になりました！", msg.author.id.0, level);
+    if let Err(e) = channel.send_message(&ctx.http, |m| m.content(content).allowed_mentions(|a| a.users(vec![msg.author.id]))).await {
+        log::warn!("leveling: failed to announce level {} for {} in guild {}: {}", level, msg.author.id.0, guild_id, e);
+    }
+    Ok(())
+}
+
+async fn fetch_avatar(url: &str) -> Option<image::RgbaImage> {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(AVATAR_TIMEOUT_SECONDS)).build().ok()?;
+    let bytes = client.get(url).send().await.ok()?.error_for_status().ok()?.bytes().await.ok()?;
+    let avatar = image::load_from_memory(&bytes).ok()?;
+    let mut avatar = image::imageops::resize(&avatar.to_rgba8(), AVATAR_SIZE, AVATAR_SIZE, image::imageops::FilterType::Triangle);
+    // Cut the square icon to a circle.
+    let r = AVATAR_SIZE as f64 / 2.0;
+    for (x, y, p) in avatar.enumerate_pixels_mut() {
+        let (dx, dy) = (x as f64 + 0.5 - r, y as f64 + 0.5 - r);
+        if dx * dx + dy * dy > r * r { p[3] = 0; }
+    }
+    Some(avatar)
+}
+
+/// Draw a rank card: avatar on the left, then name, rank, level and a progress bar.
+fn render_card(name: &str, avatar: Option<&image::RgbaImage>, rank: i64, xp: i64) -> Result<Vec<u8>> {
+    let (level, into, needed) = progress(xp);
+    let avatar_y = (CARD.height - AVATAR_SIZE) / 2;
+    let mut buf = vec![0u8; (CARD.width * CARD.height * 3) as usize];
+    {
+        let area = BitMapBackend::with_buffer(&mut buf, (CARD.width, CARD.height)).into_drawing_area();
+        area.fill(&BACKGROUND)?;
+        if avatar.is_none() {
+            let r = (AVATAR_SIZE / 2) as i32;
+            area.draw(&Circle::new(((AVATAR_X as i32) + r, (avatar_y as i32) + r), r, BAR_BACKGROUND.filled()))?;
+        }
+        let name: String = name.chars().take(24).collect();
+        area.draw_text(&name, &("sans-serif", 40).into_font().color(&WHITE).pos(Pos::new(HPos::Left, VPos::Center)), (TEXT_X, 70))?;
+        let top = format!("RANK #{}   LEVEL {}", rank, level);
+        area.draw_text(&top, &("sans-serif", 30).into_font().color(&BAR_FILL).pos(Pos::new(HPos::Right, VPos::Center)), (RIGHT_X, 120))?;
+        let counts = format!("{} / {} XP", into, needed);
+        area.draw_text(&counts, &("sans-serif", 22).into_font().color(&MUTED).pos(Pos::new(HPos::Right, VPos::Bottom)), (RIGHT_X, BAR_TOP - 8))?;
+        area.draw_text(&format!("Total {} XP", xp), &("sans-serif", 22).into_font().color(&MUTED).pos(Pos::new(HPos::Left, VPos::Bottom)), (TEXT_X, BAR_TOP - 8))?;
+        area.draw(&Rectangle::new([(TEXT_X, BAR_TOP), (RIGHT_X, BAR_TOP + BAR_HEIGHT)], BAR_BACKGROUND.filled()))?;
+        let filled = TEXT_X + ((RIGHT_X - TEXT_X) as i64 * into / needed.max(1)) as i32;
+        if filled > TEXT_X {
+            area.draw(&Rectangle::new([(TEXT_X, BAR_TOP), (filled, BAR_TOP + BAR_HEIGHT)], BAR_FILL.filled()))?;
+        }
+        area.present()?;
+    }
+    if let Some(avatar) = avatar {
+        let base = image::RgbImage::from_raw(CARD.width, CARD.height, buf).ok_or_else(|| anyhow::anyhow!("Failed to create image"))?;
+        let mut base = image::DynamicImage::ImageRgb8(base).to_rgba8();
+        image::imageops::overlay(&mut base, avatar, AVATAR_X as i64, avatar_y as i64);
+        buf = image::DynamicImage::ImageRgba8(base).to_rgb8().into_raw();
+    }
+    chart::encode_png(CARD, buf)
+}
+
+pub struct LevelingModule;
+
+#[async_trait]
+impl crate::modules::Module for LevelingModule {
+    fn name(&self) -> &'static str { "leveling" }
+    fn description(&self) -> &'static str { "発言XPとレベル、ランキング" }
+    fn commands(&self) -> &'static [&'static str] { &["rank", "leaderboard"] }
+}
+
+pub struct RankCommand;
+
+#[async_trait]
+impl Command for RankCommand {
+    fn name(&self) -> &'static str { "rank" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("レベルとXPのランクカードを表示します").create_option(|o| o.name("user").description("対象ユーザー (省略で自分)").kind(CommandOptionType::User).required(false))
+    }
+
+    fn defer(&self) -> Defer { Defer::UserPreference }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let (user, nick) = match inv.args.user("user") {
+            Some((u, m)) => (u, m.and_then(|m| m.nick.clone())),
+            None => (&command.user, command.member.as_ref().and_then(|m| m.nick.clone())),
+        };
+        if user.bot { return inv.say("Botにはレベルがありません。").await; }
+        let row = match db::get_user_xp(gid, user.id.0 as i64).await? {
+            Some(r) if r.xp > 0 => r,
+            _ => return inv.say(format!("{} さんはまだXPを獲得していません。", user.name)).await,
+        };
+        let rank = db::get_xp_rank(gid, row.xp).await?;
+        let avatar = fetch_avatar(&user.face()).await;
+        let name = nick.unwrap_or_else(|| user.name.clone());
+        let png = chart::finish(Some(gid), render_card(&name, avatar.as_ref(), rank, row.xp)?, "rank").await;
+        command.create_followup_message(&ctx.http, |m| m.add_file((png.bytes.as_slice(), png.filename().as_str())).ephemeral(inv.ephemeral)).await?;
+        Ok(())
+    }
+}
+
+pub struct LeaderboardCommand;
+
+#[async_trait]
+impl Command for LeaderboardCommand {
+    fn name(&self) -> &'static str { "leaderboard" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("XPのランキングを表示します").create_option(|o| o.name("page").description("ページ (1ページ10人)").kind(CommandOptionType::Integer).min_int_value(1).required(false))
+    }
+
+    fn defer(&self) -> Defer { Defer::UserPreference }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let page = inv.args.int("page").unwrap_or(1).max(1);
+        let (rows, total) = db::get_xp_leaderboard(gid, LEADERBOARD_PAGE_SIZE, (page - 1) * LEADERBOARD_PAGE_SIZE).await?;
+        if total == 0 { return inv.say("まだ誰もXPを獲得していません。").await; }
+        let pages = (total + LEADERBOARD_PAGE_SIZE - 1) / LEADERBOARD_PAGE_SIZE;
+        if rows.is_empty() { return inv.say(format!("ページは{}まであります。", pages)).await; }
+
+        let start = (page - 1) * LEADERBOARD_PAGE_SIZE;
+        let lines: Vec<String> = rows.iter().enumerate().map(|(i, r)| {
+            let place = start + i as i64 + 1;
+            let medal = match place { 1 => "🥇", 2 => "🥈", 3 => "🥉", _ => "" };
+            format!("{}**{}.** <@{}> — レベル {} ({} XP)", medal, place, r.user_id, progress(r.xp).0, r.xp)
+        }).collect();
+        let mut embed = CreateEmbed::default();
+        embed.title("XPランキング");
+        embed.description(lines.join("\n"));
+        embed.color(serenity::utils::Colour::BLURPLE);
+        embed.footer(|f| f.text(format!("{} / {} ページ · {}人", page, pages, total)));
+        command.create_followup_message(&ctx.http, |m| m.embed(|e| { *e = embed; e }).ephemeral(inv.ephemeral)).await?;
+        Ok(())
+    }
+}
+
+/// Adds the `leveling` subcommand group to `/config`.
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("leveling").description("レベルアップの通知").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| {
+            s.name("announce").description("レベルアップを通知するかと通知先を設定します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("enabled").description("通知する").kind(CommandOptionType::Boolean).required(true))
+                .create_sub_option(|o| o.name("channel").description("通知先 (省略で発言したチャンネル)").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(false))
+        })
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let enabled = sub.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(true);
+    let channel = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Channel(c) => Some(c.id.0 as i64), _ => None });
+
+    let msg = if !permissions::is_admin(member).await {
+        permissions::DENIED_MESSAGE.to_string()
+    } else {
+        db::set_leveling_announce(guild_id, enabled, channel).await?;
+        match (enabled, channel) {
+            (false, _) => "レベルアップの通知を停止しました。XPの記録は続きます。".to_string(),
+            (true, Some(c)) => format!("レベルアップを <#{}> に通知します。", c),
+            (true, None) => "レベルアップを発言したチャンネルに通知します。".to_string(),
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
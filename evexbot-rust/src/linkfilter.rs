@@ -0,0 +1,360 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Client;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::Message;
+use serenity::prelude::*;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::automod;
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::modlog;
+use crate::permissions;
+use crate::scheduler;
+
+/// Links are read from message text.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::MESSAGE_CONTENT;
+
+/// Public anti-phishing feeds: one domain per line (`#` comments allowed) or a JSON array
+/// of domains. LINKFILTER_FEEDS replaces the list with comma-separated URLs.
+const DEFAULT_FEEDS: &[&str] = &[
+    "https://raw.githubusercontent.com/Discord-AntiScam/scam-links/main/list.txt",
+    "https://phish.sinking.yachts/v2/all",
+];
+const FEED_REFRESH_SECONDS: u64 = 6 * 3600;
+const FEED_TIMEOUT_SECONDS: u64 = 30;
+/// Every instance reloads the cached list this often; only the leader fetches the feeds.
+const RELOAD_SECONDS: u64 = 3600;
+const MAX_LISTED_DOMAINS: usize = 50;
+
+/// Domains Discord itself serves links from. Lookalikes of these are treated as spoofs.
+const DISCORD_DOMAINS: &[&str] = &[
+    "discord.com", "discord.gg", "discordapp.com", "discordapp.net", "discord.media", "discord.new",
+    "discord.gift", "discord.gifts", "discord.dev", "discord.co", "discordstatus.com", "dis.gd", "discord.design",
+];
+/// Bait that turns a domain merely mentioning Discord (`discord.js.org` is fine) into a likely scam.
+const SCAM_WORDS: &[&str] = &["nitro", "gift", "free", "steam", "airdrop", "claim", "promo", "boost"];
+/// Shorter names are too close to ordinary words ("disco") for the typo check.
+const MIN_TYPO_LENGTH: usize = 6;
+
+static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bhttps?://([^\s/?#<>]+)").unwrap());
+static BLOCKLIST: Lazy<RwLock<(HashSet<String>, Option<Instant>)>> = Lazy::new(|| RwLock::new((HashSet::new(), None)));
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Blocklisted,
+    DiscordSpoof,
+}
+
+impl Verdict {
+    fn describe(&self) -> &'static str {
+        match self {
+            Verdict::Blocklisted => "フィッシング・詐欺サイトのリストに載っています",
+            Verdict::DiscordSpoof => "Discordを装ったドメインです",
+        }
+    }
+}
+
+fn feeds() -> Vec<String> {
+    match std::env::var("LINKFILTER_FEEDS") {
+        Ok(v) => v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+        Err(_) => DEFAULT_FEEDS.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+/// Lowercased host without userinfo, port, trailing dot or a leading `www.`.
+fn normalize_host(raw: &str) -> Option<String> {
+    let host = raw.rsplit('@').next()?.split(':').next()?.trim_end_matches('.').to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+    let valid = host.contains('.') && host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    if valid { Some(host) } else { None }
+}
+
+/// Accepts either a bare domain or a URL, as users paste both into `/linkfilter allow`.
+fn parse_domain(input: &str) -> Option<String> {
+    let input = input.trim();
+    match URL_RE.captures(input) {
+        Some(c) => normalize_host(&c[1]),
+        None => normalize_host(input.split('/').next()?),
+    }
+}
+
+/// Hosts of every link in `content`, without duplicates.
+fn link_hosts(content: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    URL_RE.captures_iter(content).filter_map(|c| normalize_host(&c[1])).filter(|h| seen.insert(h.clone())).collect()
+}
+
+/// `host` and each parent domain with at least two labels, so `a.evil.com` matches `evil.com`.
+fn candidates(host: &str) -> Vec<&str> {
+    let parents = host.matches('.').count().saturating_sub(1);
+    std::iter::once(host).chain(host.match_indices('.').map(|(i, _)| &host[i + 1..]).take(parents)).collect()
+}
+
+fn listed(host: &str, domains: &HashSet<String>) -> bool {
+    candidates(host).iter().any(|d| domains.contains(*d))
+}
+
+/// Map characters scammers swap in for look-alikes, so `dlsc0rd` and `discord` compare equal.
+fn skeleton(s: &str) -> String {
+    s.chars().filter(|c| *c != '-').map(|c| match c {
+        '0' => 'o',
+        '1' | 'i' | '!' | '|' => 'l',
+        '3' => 'e',
+        '4' => 'a',
+        '5' => 's',
+        '7' => 't',
+        c => c,
+    }).collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != *cb)).min(row[j] + 1).min(current + 1);
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Domains dressed up as Discord's: look-alike spellings (`dlsc0rd.gift`), typos
+/// (`discrod.com`), Discord's names under another TLD (`discordapp.gq`) and Discord
+/// paired with scam bait (`discord-nitro.ru`).
+fn spoofs_discord(host: &str) -> bool {
+    if candidates(host).iter().any(|d| DISCORD_DOMAINS.contains(d)) { return false; }
+    let names = [skeleton("discord"), skeleton("discordapp")];
+    if skeleton(host).contains(&names[0]) {
+        return !host.contains("discord") || SCAM_WORDS.iter().any(|w| host.contains(w)) || {
+            let labels: Vec<&str> = host.split('.').collect();
+            labels.len() == 2 && names.contains(&skeleton(labels[0]))
+        };
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    let name = skeleton(labels[labels.len().saturating_sub(2)]);
+    name.len() >= MIN_TYPO_LENGTH && names.iter().any(|t| edit_distance(&name, t) <= 2)
+}
+
+fn judge(host: &str, blocklist: &HashSet<String>, allowlist: &HashSet<String>) -> Option<Verdict> {
+    if listed(host, allowlist) { return None; }
+    if listed(host, blocklist) { return Some(Verdict::Blocklisted); }
+    if spoofs_discord(host) { return Some(Verdict::DiscordSpoof); }
+    None
+}
+
+/// Domains in a feed body; blank lines, comments and entries that aren't domains are skipped.
+fn parse_feed(body: &str) -> Vec<String> {
+    let entries: Vec<String> = match serde_json::from_str::<Vec<String>>(body) {
+        Ok(list) => list,
+        Err(_) => body.lines().map(|l| l.split('#').next().unwrap_or("").trim().to_string()).collect(),
+    };
+    entries.iter().filter(|e| !e.is_empty()).filter_map(|e| parse_domain(e)).collect()
+}
+
+/// Start the job that refreshes the cached feeds.
+pub fn start(_http: Arc<Http>) {
+    scheduler::spawn_every("linkfilter-feeds", Duration::from_secs(FEED_REFRESH_SECONDS), refresh_feeds);
+}
+
+async fn refresh_feeds() -> Result<()> {
+    let client = Client::builder().timeout(Duration::from_secs(FEED_TIMEOUT_SECONDS)).build()?;
+    for url in feeds() {
+        let body = match client.get(&url).header("X-Identity", "EvexBot").send().await.and_then(|r| r.error_for_status()) {
+            Ok(r) => r.text().await?,
+            Err(e) => {
+                log::warn!("linkfilter: failed to fetch {}: {}", url, e);
+                continue;
+            }
+        };
+        let domains = parse_feed(&body);
+        // An empty or unparseable response keeps the previous list rather than clearing it.
+        if domains.is_empty() {
+            log::warn!("linkfilter: {} returned no domains", url);
+            continue;
+        }
+        db::replace_phishing_domains(&url, &domains, Utc::now().timestamp()).await?;
+        log::info!("linkfilter: cached {} domains from {}", domains.len(), url);
+    }
+    reload().await
+}
+
+async fn reload() -> Result<()> {
+    let domains: HashSet<String> = db::get_phishing_domains().await?.into_iter().collect();
+    *BLOCKLIST.write().await = (domains, Some(Instant::now()));
+    Ok(())
+}
+
+async fn reload_if_stale() {
+    let stale = BLOCKLIST.read().await.1.is_none_or(|at| at.elapsed() >= Duration::from_secs(RELOAD_SECONDS));
+    if stale {
+        if let Err(e) = reload().await { log::warn!("linkfilter: failed to load the cached blocklist: {}", e); }
+    }
+}
+
+/// Delete messages linking to known phishing domains or Discord lookalikes. Returns true
+/// when the message was removed and later handlers should not process it.
+pub async fn handle_message(ctx: &Context, message: &Message) -> Result<bool> {
+    if message.author.bot || !crate::intents::has(REQUIRED_INTENTS) { return Ok(false); }
+    let guild_id = match message.guild_id { Some(g) => g, None => return Ok(false) };
+    let hosts = link_hosts(&message.content);
+    if hosts.is_empty() { return Ok(false); }
+
+    reload_if_stale().await;
+    let allowlist: HashSet<String> = db::get_link_allowlist(guild_id.0 as i64).await?.into_iter().collect();
+    let flagged = {
+        let blocklist = BLOCKLIST.read().await;
+        hosts.into_iter().find_map(|h| judge(&h, &blocklist.0, &allowlist).map(|v| (h, v)))
+    };
+    let (host, verdict) = match flagged { Some(f) => f, None => return Ok(false) };
+
+    let monitor_only = automod::is_monitor_only(guild_id).await;
+    if !monitor_only {
+        message.delete(&ctx.http).await?;
+        let _ = message.channel_id.say(&ctx.http, format!("{} 危険な可能性があるリンクを削除しました。", message.author.mention())).await;
+    }
+
+    let mut embed = CreateEmbed::default();
+    embed.title(automod::action_title("危険なリンクを削除しました", monitor_only));
+    embed.description(format!("投稿者: {}\nチャンネル: <#{}>\nドメイン: `{}`\n理由: {}", message.author.mention(), message.channel_id.0, host, verdict.describe()));
+    embed.field("誤検知の場合", format!("`/linkfilter allow domain:{}` で許可できます。", host), false);
+    embed.color(serenity::utils::Colour::RED);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Link Filter"));
+    modlog::send(&ctx.http, guild_id, embed).await?;
+    Ok(!monitor_only)
+}
+
+pub struct LinkFilterCommand;
+
+#[async_trait]
+impl Command for LinkFilterCommand {
+    fn name(&self) -> &'static str { "linkfilter" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("フィッシング・詐欺リンクの削除")
+            .create_option(|s| {
+                s.name("allow").description("ドメインを許可リストに追加します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("domain").description("example.com またはURL (サブドメインも許可されます)").kind(CommandOptionType::String).required(true))
+            })
+            .create_option(|s| {
+                s.name("remove").description("ドメインを許可リストから外します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("domain").description("許可リストのドメイン").kind(CommandOptionType::String).required(true))
+            })
+            .create_option(|s| s.name("list").description("許可リストを表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("check").description("リンクが削除対象か確認します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("url").description("確認するURL").kind(CommandOptionType::String).required(true))
+            })
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "allow" | "remove" => {
+                let domain = match args.str("domain").and_then(parse_domain) { Some(d) => d, None => return inv.say("ドメインの形式が正しくありません (例: example.com)。").await };
+                if sub.name == "allow" {
+                    if db::add_link_allowlist(gid, &domain, command.user.id.0 as i64, Utc::now().timestamp()).await? {
+                        inv.say(format!("`{}` とそのサブドメインへのリンクは削除されなくなりました。", domain)).await
+                    } else {
+                        inv.say(format!("`{}` はすでに許可されています。", domain)).await
+                    }
+                } else if db::remove_link_allowlist(gid, &domain).await? {
+                    inv.say(format!("`{}` を許可リストから外しました。", domain)).await
+                } else {
+                    inv.say(format!("`{}` は許可リストにありません。", domain)).await
+                }
+            }
+            "list" => {
+                let domains = db::get_link_allowlist(gid).await?;
+                if domains.is_empty() { return inv.say("許可リストは空です。").await; }
+                let mut lines: Vec<String> = domains.iter().take(MAX_LISTED_DOMAINS).map(|d| format!("• `{}`", d)).collect();
+                if domains.len() > MAX_LISTED_DOMAINS { lines.push(format!("…ほか{}件", domains.len() - MAX_LISTED_DOMAINS)); }
+                inv.say(format!("許可されたドメイン ({}件)\n{}", domains.len(), lines.join("\n"))).await
+            }
+            "check" => {
+                let host = match args.str("url").and_then(parse_domain) { Some(h) => h, None => return inv.say("URLの形式が正しくありません。").await };
+                reload_if_stale().await;
+                let allowlist: HashSet<String> = db::get_link_allowlist(gid).await?.into_iter().collect();
+                let blocklist = BLOCKLIST.read().await;
+                let msg = match judge(&host, &blocklist.0, &allowlist) {
+                    Some(v) => format!("⛔ `{}` へのリンクは削除されます: {}", host, v.describe()),
+                    None if listed(&host, &allowlist) => format!("✅ `{}` は許可リストにあります。", host),
+                    None => format!("✅ `{}` は削除対象ではありません (キャッシュ済みのリスト: {}件)。", host, blocklist.0.len()),
+                };
+                drop(blocklist);
+                inv.say(msg).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(domains: &[&str]) -> HashSet<String> {
+        domains.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn hosts_are_normalized() {
+        assert_eq!(link_hosts("see https://WWW.Example.com:8080/path and http://user@evil.net."), vec!["example.com", "evil.net"]);
+        assert_eq!(parse_domain("https://docs.rs/serenity"), Some("docs.rs".to_string()));
+        assert_eq!(parse_domain("example.com/page"), Some("example.com".to_string()));
+        assert_eq!(parse_domain("not a domain"), None);
+    }
+
+    #[test]
+    fn subdomains_of_listed_domains_match() {
+        let blocklist = set(&["steamcommunlty.com"]);
+        assert_eq!(judge("login.steamcommunlty.com", &blocklist, &HashSet::new()), Some(Verdict::Blocklisted));
+        assert_eq!(judge("steamcommunity.com", &blocklist, &HashSet::new()), None);
+        assert!(!listed("com", &blocklist));
+    }
+
+    #[test]
+    fn discord_lookalikes_are_spoofs() {
+        for host in ["dlscord.gift", "disc0rd-nitro.com", "discord-gifts.ru", "discrod.com", "free-nitro.discordapp.gq", "dliscordapp.com", "discordapp.gq"] {
+            assert!(spoofs_discord(host), "{}", host);
+        }
+        for host in ["discord.com", "cdn.discordapp.com", "discord.gg", "discord.js.org", "discordpy.readthedocs.io", "example.com", "record.com", "disco.com", "docs.rs"] {
+            assert!(!spoofs_discord(host), "{}", host);
+        }
+    }
+
+    #[test]
+    fn the_allowlist_wins() {
+        let blocklist = set(&["evil.com"]);
+        assert_eq!(judge("a.evil.com", &blocklist, &set(&["evil.com"])), None);
+        assert_eq!(judge("discord-fan.org", &HashSet::new(), &set(&["discord-fan.org"])), None);
+    }
+
+    #[test]
+    fn feeds_parse_as_lines_or_json() {
+        assert_eq!(parse_feed("# list\nevil.com\n\nbad.net # note\n"), vec!["evil.com", "bad.net"]);
+        assert_eq!(parse_feed(r#"["evil.com", "Bad.NET"]"#), vec!["evil.com", "bad.net"]);
+    }
+}
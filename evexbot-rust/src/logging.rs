@@ -0,0 +1,236 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::{Attachment, ChannelType, Message};
+use serenity::model::event::MessageUpdateEvent;
+use serenity::model::id::{ChannelId, GuildId, MessageId};
+use serenity::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::Mutex;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+
+/// Message text is needed for the before/after content.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::MESSAGE_CONTENT;
+
+/// Messages kept in memory for their before/after content; the oldest are dropped first.
+const MAX_TRACKED_MESSAGES: usize = 10_000;
+/// Embed field values are capped at 1024 characters.
+const MAX_FIELD_LENGTH: usize = 1000;
+
+/// What a message looked like when it was posted (or last edited).
+#[derive(Clone)]
+struct Tracked {
+    guild_id: u64,
+    channel_id: u64,
+    author_id: u64,
+    content: String,
+    attachments: Vec<String>,
+}
+
+/// Recent messages in guilds with logging on, by message id. Gateway edit and delete events
+/// don't carry the old content, so it is remembered from the create event. Messages from
+/// before the bot started, or already evicted, are not logged.
+#[derive(Default)]
+struct Store {
+    messages: HashMap<u64, Tracked>,
+    order: VecDeque<u64>,
+}
+
+impl Store {
+    fn insert(&mut self, id: u64, tracked: Tracked) {
+        if self.messages.insert(id, tracked).is_none() { self.order.push_back(id); }
+        while self.order.len() > MAX_TRACKED_MESSAGES {
+            if let Some(old) = self.order.pop_front() { self.messages.remove(&old); }
+        }
+    }
+
+    /// The id stays in `order` and is dropped once it reaches the front.
+    fn remove(&mut self, id: u64) -> Option<Tracked> {
+        self.messages.remove(&id)
+    }
+}
+
+static MESSAGES: Lazy<Mutex<Store>> = Lazy::new(|| Mutex::new(Store::default()));
+
+/// `name (size) url` for each attachment.
+fn describe_attachments(attachments: &[Attachment]) -> Vec<String> {
+    attachments.iter().map(|a| format!("{} ({:.1} KB) {}", a.filename, a.size as f64 / 1024.0, a.url)).collect()
+}
+
+fn truncate(s: &str) -> String {
+    if s.is_empty() { return "(本文なし)".to_string(); }
+    if s.chars().count() > MAX_FIELD_LENGTH { format!("{}…", s.chars().take(MAX_FIELD_LENGTH - 1).collect::<String>()) } else { s.to_string() }
+}
+
+fn base_embed(title: &str, tracked: &Tracked, message_id: u64) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(title);
+    embed.description(format!(
+        "投稿者: <@{}>\nチャンネル: <#{}>\n[メッセージ](https://discord.com/channels/{}/{}/{})",
+        tracked.author_id, tracked.channel_id, tracked.guild_id, tracked.channel_id, message_id,
+    ));
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text(format!("EvexBot | Message Log | ユーザーID: {}", tracked.author_id)));
+    embed
+}
+
+fn add_attachments(embed: &mut CreateEmbed, attachments: &[String]) {
+    if !attachments.is_empty() { embed.field("添付ファイル", truncate(&attachments.join("\n")), false); }
+}
+
+/// The guild's settings when logging is on and `channel_id` isn't the log channel itself.
+async fn settings_for(guild_id: GuildId, channel_id: ChannelId) -> Result<Option<db::MessageLogSettings>> {
+    let settings = db::get_message_log_settings(guild_id.0 as i64).await?;
+    Ok(settings.filter(|s| s.channel_id as u64 != channel_id.0))
+}
+
+async fn send(ctx: &Context, settings: &db::MessageLogSettings, embed: CreateEmbed) -> Result<()> {
+    ChannelId(settings.channel_id as u64).send_message(&ctx.http, |m| m.embed(|e| { *e = embed; e })).await?;
+    Ok(())
+}
+
+/// Remember new messages so their edits and deletion can show the earlier content.
+pub async fn handle_message(msg: &Message) -> Result<()> {
+    if msg.author.bot || !crate::intents::has(REQUIRED_INTENTS) { return Ok(()); }
+    let guild_id = match msg.guild_id { Some(g) => g, None => return Ok(()) };
+    if settings_for(guild_id, msg.channel_id).await?.is_none() { return Ok(()); }
+    let tracked = Tracked {
+        guild_id: guild_id.0,
+        channel_id: msg.channel_id.0,
+        author_id: msg.author.id.0,
+        content: msg.content.clone(),
+        attachments: describe_attachments(&msg.attachments),
+    };
+    MESSAGES.lock().await.insert(msg.id.0, tracked);
+    Ok(())
+}
+
+pub async fn handle_message_update(ctx: &Context, event: &MessageUpdateEvent) -> Result<()> {
+    let guild_id = match event.guild_id { Some(g) => g, None => return Ok(()) };
+    // Updates without content are Discord resolving link embeds, not edits.
+    let content = match &event.content { Some(c) => c, None => return Ok(()) };
+    let before = match MESSAGES.lock().await.messages.get(&event.id.0).cloned() { Some(t) => t, None => return Ok(()) };
+    if before.content == *content { return Ok(()); }
+    let after = Tracked {
+        content: content.clone(),
+        attachments: event.attachments.as_deref().map(describe_attachments).unwrap_or_else(|| before.attachments.clone()),
+        ..before.clone()
+    };
+    MESSAGES.lock().await.insert(event.id.0, after.clone());
+
+    let settings = match settings_for(guild_id, event.channel_id).await? { Some(s) if s.log_edits => s, _ => return Ok(()) };
+    let mut embed = base_embed("メッセージが編集されました", &after, event.id.0);
+    embed.field("編集前", truncate(&before.content), false);
+    embed.field("編集後", truncate(&after.content), false);
+    add_attachments(&mut embed, &after.attachments);
+    embed.color(serenity::utils::Colour::GOLD);
+    send(ctx, &settings, embed).await
+}
+
+pub async fn handle_message_delete(ctx: &Context, channel_id: ChannelId, message_id: MessageId, guild_id: Option<GuildId>) -> Result<()> {
+    let guild_id = match guild_id { Some(g) => g, None => return Ok(()) };
+    let tracked = match MESSAGES.lock().await.remove(message_id.0) { Some(t) => t, None => return Ok(()) };
+    let settings = match settings_for(guild_id, channel_id).await? { Some(s) if s.log_deletes => s, _ => return Ok(()) };
+    let mut embed = base_embed("メッセージが削除されました", &tracked, message_id.0);
+    embed.field("内容", truncate(&tracked.content), false);
+    add_attachments(&mut embed, &tracked.attachments);
+    embed.color(serenity::utils::Colour::RED);
+    send(ctx, &settings, embed).await
+}
+
+pub struct LogSettingsCommand;
+
+#[async_trait]
+impl Command for LogSettingsCommand {
+    fn name(&self) -> &'static str { "logsettings" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("メッセージの編集・削除ログの設定")
+            .create_option(|s| {
+                s.name("set").description("ログの送信先を設定します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("channel").description("送信先チャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(true))
+                    .create_sub_option(|o| o.name("edits").description("編集を記録する (既定: はい)").kind(CommandOptionType::Boolean).required(false))
+                    .create_sub_option(|o| o.name("deletes").description("削除を記録する (既定: はい)").kind(CommandOptionType::Boolean).required(false))
+            })
+            .create_option(|s| s.name("disable").description("ログを無効にします").kind(CommandOptionType::SubCommand))
+            .create_option(|s| s.name("show").description("現在の設定を表示します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "set" => {
+                let channel = match args.channel("channel") { Some(c) => c, None => return Ok(()) };
+                let edits = args.bool("edits").unwrap_or(true);
+                let deletes = args.bool("deletes").unwrap_or(true);
+                if !edits && !deletes { return inv.say("編集と削除の両方をオフにする場合は `/logsettings disable` を使ってください。").await; }
+                db::set_message_log_settings(gid, channel.id.0 as i64, edits, deletes).await?;
+                let warning = if crate::intents::has(REQUIRED_INTENTS) { "" } else { "\n⚠️ このBotはメッセージ本文を読めない設定のため、ログは記録されません。" };
+                inv.say(format!("メッセージの{}を<#{}>に記録します。{}", kinds(edits, deletes), channel.id.0, warning)).await
+            }
+            "disable" => {
+                if db::delete_message_log_settings(gid).await? { inv.say("メッセージログを無効にしました。").await } else { inv.say("メッセージログは設定されていません。").await }
+            }
+            "show" => match db::get_message_log_settings(gid).await? {
+                Some(s) => inv.say(format!("送信先: <#{}>\n記録: {}", s.channel_id, kinds(s.log_edits, s.log_deletes))).await,
+                None => inv.say("メッセージログは無効です。`/logsettings set` で設定できます。").await,
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+fn kinds(edits: bool, deletes: bool) -> &'static str {
+    match (edits, deletes) {
+        (true, true) => "編集と削除",
+        (true, false) => "編集",
+        _ => "削除",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracked(content: &str) -> Tracked {
+        Tracked { guild_id: 1, channel_id: 2, author_id: 3, content: content.to_string(), attachments: Vec::new() }
+    }
+
+    #[test]
+    fn the_oldest_messages_are_evicted_first() {
+        let mut store = Store::default();
+        for id in 0..(MAX_TRACKED_MESSAGES as u64 + 5) { store.insert(id, tracked("hi")); }
+        assert_eq!(store.messages.len(), MAX_TRACKED_MESSAGES);
+        assert!(store.remove(4).is_none());
+        assert_eq!(store.remove(5).map(|t| t.content), Some("hi".to_string()));
+    }
+
+    #[test]
+    fn edits_replace_the_content_without_duplicating_the_id() {
+        let mut store = Store::default();
+        store.insert(1, tracked("before"));
+        store.insert(1, tracked("after"));
+        assert_eq!(store.order.len(), 1);
+        assert_eq!(store.messages[&1].content, "after");
+    }
+
+    #[test]
+    fn long_content_is_cut_to_fit_a_field() {
+        assert_eq!(truncate(""), "(本文なし)");
+        assert_eq!(truncate(&"あ".repeat(2000)).chars().count(), MAX_FIELD_LENGTH);
+    }
+}
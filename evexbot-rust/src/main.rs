@@ -1,20 +1,101 @@
 use anyhow::Result;
 use serenity::async_trait;
-use serenity::prelude::GatewayIntents;
 use serenity::model::gateway::Ready;
 use serenity::prelude::*;
 use std::env;
 
 mod config;
 mod db;
+mod migrations;
 mod welcome;
 mod growth;
+mod forecast;
+mod stats;
 mod imagegen;
 mod avatar;
 mod messagelink;
 mod members_history;
 mod sandbox;
 mod zikosyokai;
+mod modlog;
+mod joingate;
+mod roles;
+mod audit;
+mod emojilog;
+mod tempvoice;
+mod scheduler;
+mod activity;
+mod digest;
+mod web;
+mod verify;
+mod components;
+mod vault;
+mod settings;
+mod automod;
+mod invites;
+mod owner;
+mod snapshots;
+mod privacy;
+mod bookmark;
+mod topic;
+mod events;
+mod api;
+mod external;
+mod compare;
+mod memberlog;
+mod chart;
+mod report;
+mod setup;
+mod appeal;
+mod retention;
+mod maintenance;
+mod leader;
+mod ocr;
+mod triage;
+mod paste;
+mod status;
+mod metrics;
+mod dedup;
+mod milestones;
+mod command_access;
+mod preferences;
+mod thread_title;
+mod commands;
+mod permissions;
+mod onboarding;
+mod rules;
+mod crosspost;
+mod shortlink;
+mod member_cache;
+mod highlights;
+mod spotlight;
+mod reforecast;
+mod backtest;
+mod prune;
+mod moderation;
+mod role_decay;
+mod contributors;
+mod logging;
+mod helpdesk;
+mod duplicates;
+mod reactionroles;
+mod kb;
+mod poll;
+mod cron;
+mod reminders;
+mod prompts;
+mod leveling;
+mod pipeline;
+mod bus;
+mod modules;
+mod antiraid;
+mod linkfilter;
+mod diagnose;
+mod wordfilter;
+mod errorcodes;
+#[cfg(test)]
+mod testing;
+mod intents;
 
 struct Handler;
 
@@ -23,80 +104,241 @@ impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, ready: Ready) {
         println!("Logged in as {}", ready.user.name);
 
-        // Register a minimal set of global application commands used by the bot.
-        let _ = serenity::model::application::command::Command::create_global_application_command(&ctx.http, |c| {
-            c.name("growth").description("サーバーの成長を予測します。使用法: /growth model target show_graph:true/false").create_option(|o| o.name("model").description("polynomial|prophet").kind(serenity::model::application::command::CommandOptionType::String).required(true)).create_option(|o| o.name("target").description("目標とするメンバー数").kind(serenity::model::application::command::CommandOptionType::Integer).required(true)).create_option(|o| o.name("show_graph").description("グラフを表示するかどうか").kind(serenity::model::application::command::CommandOptionType::Boolean).required(false))
-        }).await;
-
-        let _ = serenity::model::application::command::Command::create_global_application_command(&ctx.http, |c| {
-            c.name("members-history").description("指定した日付範囲のメンバー数推移をグラフ化します。")
-                .create_option(|o| {
-                    o.name("start_date").description("開始日 (YYYY-MM-DD)").kind(serenity::model::application::command::CommandOptionType::String).required(true)
-                })
-                .create_option(|o| {
-                    o.name("end_date").description("終了日 (YYYY-MM-DD)").kind(serenity::model::application::command::CommandOptionType::String).required(true)
-                })
-        }).await;
-
-        // Additional command registration performed by modules
-        let _ = welcome::register_commands(&ctx.http).await;
-        let _ = serenity::model::application::command::Command::create_global_application_command(&ctx.http, |c| {
-            c.name("imagegen").description("与えられたプロンプトに基づいて画像を生成します").create_option(|o| o.name("prompt").description("生成する画像の説明（プロンプト）").kind(serenity::model::application::command::CommandOptionType::String).required(true))
-        }).await;
-        let _ = serenity::model::application::command::Command::create_global_application_command(&ctx.http, |c| {
-            c.name("avatar").description("ユーザーのアイコンを表示します").create_option(|o| o.name("user").description("対象ユーザー").kind(serenity::model::application::command::CommandOptionType::User).required(false))
-        }).await;
-        let _ = serenity::model::application::command::Command::create_global_application_command(&ctx.http, |c| {
-            c.name("sandbox").description("コードをサンドボックスで実行し、結果を返します。").create_option(|o| o.name("language").description("言語: python|javascript").kind(serenity::model::application::command::CommandOptionType::String).required(true)).create_option(|o| o.name("code").description("実行するコード").kind(serenity::model::application::command::CommandOptionType::String).required(true))
-        }).await;
-    }
-
-    async fn interaction_create(&self, ctx: Context, interaction: serenity::model::interactions::Interaction) {
+        // Globally, or per guild in development (COMMAND_REGISTRATION=guild)
+        commands::register_all(&ctx.http).await;
+
+        // Keep the maintenance presence across reconnects
+        if maintenance::is_active() { maintenance::apply_presence(&ctx).await; }
+
+        // Background jobs and event bus subscribers for every module
+        modules::init_all(&ctx);
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: serenity::model::application::interaction::Interaction) {
+        // Every instance receives the interaction; only the leader answers it
+        if !leader::is_leader() {
+            return;
+        }
+        if maintenance::intercept(&ctx, &interaction).await {
+            return;
+        }
+        if dedup::intercept(&ctx, &interaction).await {
+            return;
+        }
+        let started = std::time::Instant::now();
         match interaction {
-            serenity::model::interactions::Interaction::ApplicationCommand(command) => {
-                match command.data.name.as_str() {
-                    "growth" => { let _ = growth::handle_growth(&ctx, &command).await; }
-                    "members-history" => { let _ = members_history::handle_members_history(&ctx, &command).await; }
-                    "imagegen" => { let _ = imagegen::handle_imagegen(&ctx, &command).await; }
-                    "avatar" => { let _ = avatar::handle_avatar(&ctx, &command).await; }
-                    "sandbox" => { let _ = sandbox::handle_sandbox(&ctx, &command).await; }
-                    // welcome and leave-message are administrative; handled separately inside welcome module
-                    "welcome" => { let _ = welcome::handle_welcome_command(&ctx, &command).await; }
-                    "leave-message" => { let _ = welcome::handle_leave_command(&ctx, &command).await; }
-                    "milestonetest" => { let _ = welcome::handle_milestone_test(&ctx, &command).await; }
-                    _ => {}
+            serenity::model::application::interaction::Interaction::ApplicationCommand(command) => {
+                // per-guild role/channel restrictions from /config command-permissions
+                if command_access::intercept(&ctx, &command).await {
+                    return;
                 }
+                commands::dispatch(&ctx, &command).await;
             }
-            serenity::model::interactions::Interaction::MessageComponent(comp) => {
+            serenity::model::application::interaction::Interaction::MessageComponent(comp) => {
                 // handle delete button
                 if comp.data.custom_id == "delete_embed_button" {
                     let _ = comp.message.delete(&ctx.http).await;
-                    let _ = comp.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::interactions::InteractionResponseType::DeferredUpdateMessage)).await;
+                    let _ = comp.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredUpdateMessage)).await;
+                } else if comp.data.custom_id.starts_with("bulk_role_") {
+                    let _ = roles::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with("audit_page:") {
+                    let _ = audit::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with("tempvc_") {
+                    let _ = tempvoice::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with("appeal_") {
+                    let _ = appeal::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with("setup:") {
+                    let _ = setup::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with("paste_") {
+                    let _ = paste::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id == "verify_start" {
+                    let _ = verify::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id == zikosyokai::WRITE_BUTTON_ID {
+                    let _ = zikosyokai::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id == rules::ACCEPT_BUTTON_ID {
+                    let _ = rules::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with("prune_") {
+                    let _ = prune::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id == helpdesk::RESOLVE_BUTTON_ID {
+                    let _ = helpdesk::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with(poll::VOTE_PREFIX) {
+                    let _ = poll::handle_component(&ctx, &comp).await;
+                } else if comp.data.custom_id.starts_with(reactionroles::BUTTON_PREFIX) || comp.data.custom_id == reactionroles::SELECT_ID {
+                    let _ = reactionroles::handle_component(&ctx, &comp).await;
+                }
+            }
+            serenity::model::application::interaction::Interaction::ModalSubmit(modal) => {
+                if modal.data.custom_id.starts_with("tempvc_") {
+                    let _ = tempvoice::handle_modal(&ctx, &modal).await;
+                } else if modal.data.custom_id.starts_with("vault_set:") {
+                    let _ = vault::handle_modal(&ctx, &modal).await;
+                } else if modal.data.custom_id == "intro_template_edit" {
+                    let _ = zikosyokai::handle_template_modal(&ctx, &modal).await;
+                } else if modal.data.custom_id == zikosyokai::WRITE_MODAL_ID {
+                    let _ = zikosyokai::handle_write_modal(&ctx, &modal).await;
+                } else if modal.data.custom_id.starts_with("appeal_submit:") {
+                    let _ = appeal::handle_modal(&ctx, &modal).await;
+                } else if modal.data.custom_id.starts_with("onboarding_template:") {
+                    let _ = onboarding::handle_modal(&ctx, &modal).await;
+                } else if modal.data.custom_id == kb::ADD_MODAL_ID {
+                    let _ = kb::handle_modal(&ctx, &modal).await;
                 }
             }
+            serenity::model::application::interaction::Interaction::Autocomplete(autocomplete) if autocomplete.data.name == "growth" => {
+                let _ = growth::handle_autocomplete(&ctx, &autocomplete).await;
+            }
             _ => {}
         }
+        metrics::observe(metrics::INTERACTION, started.elapsed());
     }
 
     async fn guild_member_addition(&self, ctx: Context, new_member: serenity::model::guild::Member) {
-        // Delegate to welcome module
-        let _ = welcome::handle_member_join(&ctx, new_member).await;
+        // Standby instances can take over at any time, so they keep their list current too.
+        member_cache::invalidate(new_member.guild_id).await;
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = memberlog::handle_member_join(&new_member).await;
+        let _ = invites::handle_member_join(&ctx, &new_member).await;
+        // A join burst starts raid mode, which may kick young accounts before anything greets them
+        let stayed = antiraid::handle_member_join(&ctx, &new_member).await.unwrap_or(true);
+        if !stayed {
+            return;
+        }
+        // Account-age gate runs first; flagged members are not welcomed
+        let passed = joingate::handle_member_join(&ctx, &new_member).await.unwrap_or(true);
+        if !passed {
+            return;
+        }
+        // growth notifications, the onboarding drip and the welcome message subscribe to this
+        bus::publish(bus::Event::MemberJoined(new_member));
     }
 
     async fn guild_member_removal(&self, ctx: Context, guild_id: serenity::model::id::GuildId, user: serenity::model::user::User, _member: Option<serenity::model::guild::Member>) {
+        member_cache::invalidate(guild_id).await;
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = joingate::handle_member_remove(guild_id, user.id).await;
+        let _ = invites::handle_member_remove(guild_id, user.id).await;
+        let _ = memberlog::handle_member_remove(&ctx.http, guild_id, user.id).await;
+        let _ = onboarding::handle_member_remove(guild_id, user.id).await;
         // Delegate to welcome module
         let _ = welcome::handle_member_remove(&ctx, guild_id, user.id).await;
     }
 
+    async fn guild_ban_addition(&self, ctx: Context, guild_id: serenity::model::id::GuildId, banned_user: serenity::model::user::User) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = memberlog::handle_ban_addition(guild_id, banned_user.id).await;
+        let _ = appeal::handle_ban_addition(&ctx.http, guild_id, &banned_user).await;
+    }
+
+    async fn guild_member_update(&self, ctx: Context, old_if_available: Option<serenity::model::guild::Member>, new: serenity::model::guild::Member) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = appeal::handle_member_update(&ctx.http, old_if_available.as_ref(), &new).await;
+    }
+
     async fn message(&self, ctx: Context, msg: serenity::model::channel::Message) {
-        // delegate to message link cog
-        let _ = messagelink::handle_message(&ctx, &msg).await;
-        // delegate to zikosyokai for channel template maintenance
-        let _ = zikosyokai::handle_message(&ctx, &msg).await;
+        // Gateway events would write to the DB mid-migration, and a follower must not
+        // act on events the leader already handles
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        pipeline::handle_message(&ctx, &msg).await;
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: serenity::model::channel::Reaction) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = emojilog::handle_reaction_add(&reaction).await;
+        let _ = bookmark::handle_reaction_add(&ctx, &reaction).await;
+        let _ = highlights::handle_reaction(&ctx.http, &reaction, true).await;
+    }
+
+    async fn reaction_remove(&self, ctx: Context, reaction: serenity::model::channel::Reaction) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = highlights::handle_reaction(&ctx.http, &reaction, false).await;
+    }
+
+    async fn thread_create(&self, ctx: Context, thread: serenity::model::channel::GuildChannel) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = helpdesk::handle_thread_create(&ctx, &thread).await;
+    }
+
+    async fn voice_state_update(&self, ctx: Context, old: Option<serenity::model::voice::VoiceState>, new: serenity::model::voice::VoiceState) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = tempvoice::handle_voice_state_update(&ctx, old.as_ref(), &new).await;
+        let _ = events::handle_voice_state_update(&ctx.http, old.as_ref(), &new).await;
+        let _ = activity::handle_voice_state_update(&new).await;
+    }
+
+    async fn guild_scheduled_event_create(&self, _ctx: Context, event: serenity::model::guild::ScheduledEvent) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = events::handle_scheduled_event(&event).await;
+    }
+
+    async fn guild_scheduled_event_update(&self, _ctx: Context, event: serenity::model::guild::ScheduledEvent) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = events::handle_scheduled_event(&event).await;
+    }
+
+    async fn guild_scheduled_event_delete(&self, _ctx: Context, event: serenity::model::guild::ScheduledEvent) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = events::handle_scheduled_event_delete(&event).await;
+    }
+
+    async fn guild_create(&self, ctx: Context, guild: serenity::model::guild::Guild, _is_new: bool) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        emojilog::handle_guild_create(&guild).await;
+        let _ = invites::handle_guild_create(&ctx, guild.id).await;
+    }
+
+    async fn guild_emojis_update(&self, ctx: Context, guild_id: serenity::model::id::GuildId, current_state: std::collections::HashMap<serenity::model::id::EmojiId, serenity::model::guild::Emoji>) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = emojilog::handle_emojis_update(&ctx, guild_id, &current_state).await;
+    }
+
+    async fn guild_stickers_update(&self, ctx: Context, guild_id: serenity::model::id::GuildId, current_state: std::collections::HashMap<serenity::model::id::StickerId, serenity::model::sticker::Sticker>) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = emojilog::handle_stickers_update(&ctx, guild_id, &current_state).await;
+    }
+
+    async fn message_update(&self, ctx: Context, _old_if_available: Option<serenity::model::channel::Message>, _new: Option<serenity::model::channel::Message>, event: serenity::model::event::MessageUpdateEvent) {
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = logging::handle_message_update(&ctx, &event).await;
     }
 
     async fn message_delete(&self, ctx: Context, channel_id: serenity::model::id::ChannelId, deleted_message_id: serenity::model::id::MessageId, guild_id: Option<serenity::model::id::GuildId>) {
-        let _ = zikosyokai::handle_message_delete(&ctx, deleted_message_id, guild_id).await;
+        if maintenance::is_active() || !leader::is_leader() {
+            return;
+        }
+        let _ = logging::handle_message_delete(&ctx, channel_id, deleted_message_id, guild_id).await;
+        let _ = zikosyokai::handle_message_delete(&ctx, channel_id, guild_id).await;
     }
 }
 
@@ -115,20 +357,37 @@ async fn main() -> Result<()> {
         }
     };
 
-    let intents = GatewayIntents::GUILDS
-        | GatewayIntents::GUILD_MEMBERS
-        | GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT
-        | GatewayIntents::GUILD_MESSAGE_REACTIONS;
+    // Privileged intents come from PRIVILEGED_INTENTS; features that need a missing one turn themselves off
+    intents::report();
 
-    let mut client = serenity::Client::builder(&token, intents)
+    if !chart::register_fonts() {
+        log::warn!("chart: bundled font failed to load; charts will render without text");
+    }
+    if owner::owner_ids().is_empty() {
+        log::warn!("owner: OWNER_IDS is not set; owner-only commands are disabled");
+    }
+
+    let mut client = serenity::Client::builder(&token, intents::configured())
         .event_handler(Handler)
         .await?;
 
+    {
+        let mut data = client.data.write().await;
+        data.insert::<metrics::ShardManagerContainer>(client.shard_manager.clone());
+    }
+
     // Initialize database
-    db::init_db(&client.cache_and_http.http).await.expect("DB init failed");
+    db::init_db().await.expect("DB init failed");
+    leader::start();
 
-    // Start client
-    client.start().await?;
+    // Start client; on Ctrl-C hand leadership to a follower before exiting
+    tokio::select! {
+        res = client.start() => res?,
+        _ = tokio::signal::ctrl_c() => {
+            modules::shutdown_all().await;
+            leader::release().await;
+            client.shard_manager.lock().await.shutdown_all().await;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::{Interaction, InteractionResponseType};
+use serenity::model::gateway::Activity;
+use serenity::model::user::OnlineStatus;
+use serenity::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::commands::{Command, Invocation};
+use crate::owner;
+
+const MAINTENANCE_MESSAGE: &str = "メンテナンス中です。しばらくしてからもう一度お試しください。";
+
+/// Starts enabled when MAINTENANCE_MODE is set, so a restart during a migration stays read-only.
+static ACTIVE: Lazy<AtomicBool> = Lazy::new(|| {
+    let on = std::env::var("MAINTENANCE_MODE").map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "on" | "yes")).unwrap_or(false);
+    AtomicBool::new(on)
+});
+
+/// While active, interactions get a canned reply, scheduled jobs skip their runs and
+/// gateway handlers that write to the database return early.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+pub struct MaintenanceCommand;
+
+#[async_trait]
+impl Command for MaintenanceCommand {
+    fn name(&self) -> &'static str { "maintenance" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("オーナー用: メンテナンスモードを切り替えます")
+            .create_option(|o| o.name("enabled").description("有効にする").kind(CommandOptionType::Boolean).required(true))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_maintenance_command(inv.ctx, inv.command).await
+    }
+}
+
+/// Reflect the current mode in the bot's presence; also called from `ready` after reconnects.
+pub async fn apply_presence(ctx: &Context) {
+    if is_active() {
+        ctx.set_presence(Some(Activity::playing("メンテナンス中")), OnlineStatus::DoNotDisturb).await;
+    } else {
+        ctx.set_presence(None, OnlineStatus::Online).await;
+    }
+}
+
+/// Answer an interaction during maintenance. Returns true when it was handled here and
+/// should not be dispatched. Owners pass through so they can verify the bot.
+pub async fn intercept(ctx: &Context, interaction: &Interaction) -> bool {
+    if !is_active() { return false; }
+    let result = match interaction {
+        Interaction::ApplicationCommand(c) if !owner::is_owner(c.user.id) => {
+            c.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(MAINTENANCE_MESSAGE).ephemeral(true))).await
+        }
+        Interaction::MessageComponent(c) if !owner::is_owner(c.user.id) => {
+            c.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(MAINTENANCE_MESSAGE).ephemeral(true))).await
+        }
+        Interaction::ModalSubmit(m) if !owner::is_owner(m.user.id) => {
+            m.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(MAINTENANCE_MESSAGE).ephemeral(true))).await
+        }
+        _ => return false,
+    };
+    if let Err(e) = result { log::warn!("maintenance: failed to reply to interaction: {}", e); }
+    true
+}
+
+async fn handle_maintenance_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    if !owner::is_owner(command.user.id) {
+        command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("権限がありません。").ephemeral(true))).await?;
+        return Ok(());
+    }
+    let enabled = command.data.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+    ACTIVE.store(enabled, Ordering::Relaxed);
+    apply_presence(ctx).await;
+    log::info!("maintenance: {} by {}", if enabled { "enabled" } else { "disabled" }, command.user.id.0);
+    let msg = if enabled {
+        "メンテナンスモードを有効にしました。オーナー以外の操作と定期ジョブを停止しています。"
+    } else {
+        "メンテナンスモードを解除しました。"
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use once_cell::sync::Lazy;
+use serenity::http::Http;
+use serenity::model::guild::Member;
+use serenity::model::id::GuildId;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Discord returns at most this many members per request.
+const PAGE_SIZE: u64 = 1000;
+/// Several features read the member list while handling one join; joins and leaves
+/// invalidate the entry, so this only bounds how stale role changes can get.
+const TTL: Duration = Duration::from_secs(300);
+
+type Cache = HashMap<u64, (Instant, Arc<Vec<Member>>)>;
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Page through the whole member list with `after`.
+async fn fetch(http: &Http, guild_id: GuildId) -> Result<Vec<Member>> {
+    let mut all = Vec::new();
+    let mut after = None;
+    loop {
+        let page = http.get_guild_members(guild_id.0, Some(PAGE_SIZE), after).await?;
+        let len = page.len();
+        after = page.last().map(|m| m.user.id.0);
+        all.extend(page);
+        if (len as u64) < PAGE_SIZE { break; }
+    }
+    Ok(all)
+}
+
+async fn cached(http: &Http, guild_id: GuildId) -> Result<Arc<Vec<Member>>> {
+    if let Some((at, members)) = CACHE.lock().await.get(&guild_id.0) {
+        if at.elapsed() < TTL { return Ok(members.clone()); }
+    }
+    let members = Arc::new(fetch(http, guild_id).await?);
+    CACHE.lock().await.insert(guild_id.0, (Instant::now(), members.clone()));
+    Ok(members)
+}
+
+/// Every member of the guild, however large it is.
+pub async fn members(http: &Http, guild_id: GuildId) -> Result<Vec<Member>> {
+    Ok(cached(http, guild_id).await?.as_ref().clone())
+}
+
+pub async fn count(http: &Http, guild_id: GuildId) -> Result<usize> {
+    Ok(cached(http, guild_id).await?.len())
+}
+
+/// Join times of current members, oldest first.
+pub async fn join_dates(http: &Http, guild_id: GuildId) -> Result<Vec<NaiveDateTime>> {
+    let mut dates: Vec<NaiveDateTime> = cached(http, guild_id).await?.iter()
+        .filter_map(|m| m.joined_at.and_then(|j| chrono::DateTime::from_timestamp(j.unix_timestamp(), 0).map(|d| d.naive_utc())))
+        .collect();
+    dates.sort();
+    Ok(dates)
+}
+
+/// Drop the cached list after a join or leave so counts stay exact.
+pub async fn invalidate(guild_id: GuildId) {
+    CACHE.lock().await.remove(&guild_id.0);
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, FixedOffset, TimeZone, Utc};
+use serenity::builder::CreateEmbed;
+use serenity::http::Http;
+use serenity::model::guild::Member;
+use serenity::model::id::{GuildId, UserId};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db;
+use crate::modlog;
+use crate::scheduler;
+
+const SUMMARY_CHECK_INTERVAL_SECONDS: u64 = 600;
+/// Joins from accounts younger than this are flagged in the daily summary.
+const NOTABLE_ACCOUNT_AGE_DAYS: i64 = 7;
+/// Kicks older than this in the audit log are not tied to a removal.
+const KICK_MATCH_SECONDS: i64 = 30;
+const AUDIT_MEMBER_KICK: u8 = 20;
+const MAX_FLAGGED_MENTIONS: usize = 20;
+
+/// Days are cut at midnight JST.
+fn jst() -> FixedOffset {
+    FixedOffset::east_opt(9 * 3600).unwrap()
+}
+
+pub async fn handle_member_join(member: &Member) -> Result<()> {
+    if member.user.bot { return Ok(()); }
+    let min_age_days = db::get_joingate_settings(member.guild_id.0 as i64).await?.min_account_age_days;
+    let now = Utc::now().timestamp();
+    let age_days = (now - member.user.id.created_at().unix_timestamp()) / 86400;
+    let flagged = age_days < NOTABLE_ACCOUNT_AGE_DAYS.max(min_age_days);
+    db::record_member_event(member.guild_id.0 as i64, member.user.id.0 as i64, "join", flagged, now).await
+}
+
+/// Record a removal, telling kicks apart from leaves via the audit log.
+pub async fn handle_member_remove(http: &Http, guild_id: GuildId, user_id: UserId) -> Result<()> {
+    let now = Utc::now().timestamp();
+    let kicked = match guild_id.audit_logs(http, Some(AUDIT_MEMBER_KICK), None, None, Some(5)).await {
+        Ok(logs) => logs.entries.iter().any(|e| e.target_id == Some(user_id.0) && now - e.id.created_at().unix_timestamp() <= KICK_MATCH_SECONDS),
+        Err(_) => false,
+    };
+    db::record_member_event(guild_id.0 as i64, user_id.0 as i64, if kicked { "kick" } else { "leave" }, false, now).await
+}
+
+pub async fn handle_ban_addition(guild_id: GuildId, user_id: UserId) -> Result<()> {
+    db::record_member_event(guild_id.0 as i64, user_id.0 as i64, "ban", false, Utc::now().timestamp()).await
+}
+
+/// Start the job that posts the previous day's summary to the mod-log shortly after midnight JST.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("daily-member-summary", Duration::from_secs(SUMMARY_CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { send_due_summaries(&http).await }
+    });
+}
+
+async fn send_due_summaries(http: &Http) -> Result<()> {
+    let today = Utc::now().with_timezone(&jst()).date_naive();
+    let day = today.pred_opt().unwrap();
+    let day_key = day.to_string();
+    let since = jst().from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap()).unwrap().timestamp();
+    let until = since + ChronoDuration::days(1).num_seconds();
+
+    for (guild_id, last_sent) in db::get_enabled_daily_summaries().await? {
+        if last_sent.as_deref() == Some(day_key.as_str()) { continue; }
+        let embed = build_summary(guild_id, &day_key, since, until).await?;
+        if let Err(e) = modlog::send(http, GuildId(guild_id as u64), embed).await {
+            log::warn!("memberlog: failed to send daily summary for guild {}: {}", guild_id, e);
+            continue;
+        }
+        db::mark_daily_summary_sent(guild_id, &day_key).await?;
+    }
+    Ok(())
+}
+
+async fn build_summary(guild_id: i64, day: &str, since: i64, until: i64) -> Result<CreateEmbed> {
+    let counts = db::count_member_events(guild_id, since, until).await?;
+    let count = |kind: &str| counts.iter().find(|(k, _)| k == kind).map(|(_, n)| *n).unwrap_or(0);
+    let (joins, leaves, kicks, bans) = (count("join"), count("leave"), count("kick"), count("ban"));
+    let flagged = db::get_flagged_joins(guild_id, since, until).await?;
+
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("📋 {} のメンバー動向", day));
+    embed.field("参加", format!("{}人", joins), true);
+    embed.field("退室", format!("{}人", leaves), true);
+    embed.field("増減", format!("{:+}", joins - leaves - kicks - bans), true);
+    embed.field("キック", format!("{}件", kicks), true);
+    embed.field("BAN", format!("{}件", bans), true);
+    let case_counts = db::count_mod_cases(guild_id, since, until).await?;
+    let cases = |action: &str| case_counts.iter().find(|(a, _)| a == action).map(|(_, n)| *n).unwrap_or(0);
+    embed.field("警告・タイムアウト", format!("{}件 / {}件", cases("warn"), cases("timeout")), true);
+    if !flagged.is_empty() {
+        let mut lines: Vec<String> = flagged.iter().take(MAX_FLAGGED_MENTIONS).map(|u| format!("<@{}>", u)).collect();
+        if flagged.len() > MAX_FLAGGED_MENTIONS { lines.push(format!("ほか{}人", flagged.len() - MAX_FLAGGED_MENTIONS)); }
+        embed.field(format!("新規アカウント ({}人)", flagged.len()), lines.join(" "), false);
+    }
+    embed.color(serenity::utils::Colour::DARK_BLUE);
+    embed.footer(|f| f.text("EvexBot | Daily Summary"));
+    Ok(embed)
+}
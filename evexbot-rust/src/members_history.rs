@@ -1,45 +1,64 @@
 use anyhow::Result;
-use chrono::{NaiveDate, NaiveDateTime, DateTime, Utc, Datelike};
+use chrono::{NaiveDate, NaiveDateTime};
 use plotters::prelude::*;
-use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
-use serenity::prelude::*;
-
-pub async fn handle_members_history(ctx: &serenity::prelude::Context, command: &ApplicationCommandInteraction) -> Result<()> {
-    // Defer response
-    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
-
-    let mut start_date = None;
-    let mut end_date = None;
-    for opt in &command.data.options {
-        match opt.name.as_str() {
-            "start_date" => { if let Some(v) = opt.value.as_ref() { if let Some(s) = v.as_str() { start_date = Some(parse_date(s)?); } } }
-            "end_date" => { if let Some(v) = opt.value.as_ref() { if let Some(s) = v.as_str() { end_date = Some(parse_date(s)?); } } }
-            _ => {}
-        }
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+
+use crate::chart::{self, ChartSize, Locale};
+use crate::commands::{Command, Defer, Invocation};
+
+pub struct MembersHistoryCommand;
+
+#[async_trait]
+impl Command for MembersHistoryCommand {
+    fn name(&self) -> &'static str { "members-history" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("指定した日付範囲のメンバー数推移をグラフ化します。")
+            .create_option(|o| o.name("start_date").description("開始日 (YYYY-MM-DD)").kind(CommandOptionType::String).required(true))
+            .create_option(|o| o.name("end_date").description("終了日 (YYYY-MM-DD)").kind(CommandOptionType::String).required(true))
+            .create_option(|o| chart::size_option(o))
     }
 
-    let start_date = start_date.ok_or_else(|| anyhow::anyhow!("start_date required"))?;
-    let end_date = end_date.ok_or_else(|| anyhow::anyhow!("end_date required"))?;
+    fn defer(&self) -> Defer { Defer::UserPreference }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_members_history(inv).await
+    }
+}
+
+async fn handle_members_history(inv: &Invocation<'_>) -> Result<()> {
+    let (ctx, command) = (inv.ctx, inv.command);
+    let start_date = parse_date(inv.args.str("start_date").ok_or_else(|| anyhow::anyhow!("start_date required"))?)?;
+    let end_date = parse_date(inv.args.str("end_date").ok_or_else(|| anyhow::anyhow!("end_date required"))?)?;
     if start_date > end_date { command.create_followup_message(&ctx.http, |m| m.content("開始日は終了日より前である必要があります。" ) ).await?; return Ok(()); }
     if (end_date - start_date).num_days() > 365 * 3 { command.create_followup_message(&ctx.http, |m| m.content("日付の範囲は最大3年までにしてください。" ) ).await?; return Ok(()); }
 
     // fetch join dates
     let guild = command.guild_id.ok_or_else(|| anyhow::anyhow!("Guild only command"))?;
-    let join_dates = fetch_all_join_dates(&ctx, guild).await?;
-    if join_dates.is_empty() { command.create_followup_message(&ctx.http, |m| m.content("参加履歴が見つかりません。メンバーの参加日時が取得できませんでした。" ) ).await?; return Ok(()); }
+    let join_dates = crate::member_cache::join_dates(&ctx.http, guild).await?;
+    let (dates, counts, from_snapshots) = snapshot_counts(guild.0 as i64, &join_dates, start_date, end_date).await?;
+    if join_dates.is_empty() && !from_snapshots { command.create_followup_message(&ctx.http, |m| m.content("参加履歴が見つかりません。メンバーの参加日時が取得できませんでした。" ) ).await?; return Ok(()); }
+
+    let size = match chart::resolve(Some(guild.0 as i64), inv.args.str("size"), chart::WIDE).await {
+        Ok(s) => s,
+        Err(e) => { command.create_followup_message(&ctx.http, |m| m.content(e)).await?; return Ok(()); }
+    };
 
-    let (dates, counts) = generate_counts(&join_dates, start_date, end_date);
-    let buf = create_plot(&dates, &counts)?;
+    let locale = Locale::for_user(command.user.id.0 as i64, Some(guild.0 as i64)).await;
+    let buf = chart::finish(Some(guild.0 as i64), create_plot(&dates, &counts, size, locale)?, "members_history").await;
 
     let mut embed = serenity::builder::CreateEmbed::default();
     embed.title("Member Count History");
     embed.description(format!("{} から {} までのメンバー数推移", start_date, end_date));
     embed.color(serenity::utils::Colour::BLURPLE);
     embed.field("開始時点のメンバー数", counts.first().map(|c| c.to_string()).unwrap_or("0".to_string()), true);
-    embed.field(&format!("{}時点のメンバー数", end_date), counts.last().map(|c| c.to_string()).unwrap_or("0".to_string()), true);
-    embed.image("attachment://members_history.png");
+    embed.field(format!("{}時点のメンバー数", end_date), counts.last().map(|c| c.to_string()).unwrap_or("0".to_string()), true);
+    embed.image(buf.url());
+    embed.footer(|f| f.text(if from_snapshots { "日次の記録に基づく人数です" } else { "現在のメンバーの参加日から推定した人数です (退出したメンバーは含まれません)" }));
 
-    command.create_followup_message(&ctx.http, |m| m.add_file((buf.as_slice(), "members_history.png")).embed(|e| { *e = embed; e })).await?;
+    command.create_followup_message(&ctx.http, |m| m.add_file((buf.bytes.as_slice(), buf.filename().as_str())).embed(|e| { *e = embed; e })).await?;
 
     Ok(())
 }
@@ -50,59 +69,167 @@ fn parse_date(s: &str) -> Result<NaiveDate> {
     Err(anyhow::anyhow!("日付は YYYY-MM-DD または YYYY/MM/DD の形式で指定してください。"))
 }
 
-async fn fetch_all_join_dates(ctx: &Context, guild_id: serenity::model::id::GuildId) -> Result<Vec<NaiveDateTime>> {
-    let mut dates = Vec::new();
-    let members = ctx.http.get_guild_members(guild_id.0, None, None).await?;
-    for m in members.into_iter() {
-        if let Some(j) = m.joined_at {
-            if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&j.to_string()) {
-                dates.push(dt.naive_utc());
-            }
-        }
+/// Daily counts from `member_snapshots`, which still count members who have since left.
+/// Days before the first snapshot use counts reconstructed from `join_dates` and gaps carry
+/// the previous count forward. The flag is false when there were no snapshots at all.
+pub async fn snapshot_counts(guild_id: i64, join_dates: &[NaiveDateTime], start: NaiveDate, end: NaiveDate) -> Result<(Vec<NaiveDate>, Vec<i32>, bool)> {
+    let (dates, mut counts) = crate::stats::member_counts(join_dates, start, end);
+    let snapshots: std::collections::HashMap<NaiveDate, i64> = crate::db::get_member_snapshots(guild_id, &start.to_string(), &end.to_string()).await?
+        .into_iter().filter_map(|(d, c)| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok().map(|d| (d, c))).collect();
+    if snapshots.is_empty() { return Ok((dates, counts, false)); }
+
+    let mut last = None;
+    for (i, d) in dates.iter().enumerate() {
+        if let Some(c) = snapshots.get(d) { last = Some(*c as i32); }
+        if let Some(c) = last { counts[i] = c; }
     }
-    dates.sort();
-    Ok(dates)
+    Ok((dates, counts, true))
 }
 
-fn generate_counts(join_dates: &Vec<NaiveDateTime>, start: NaiveDate, end: NaiveDate) -> (Vec<NaiveDate>, Vec<i32>) {
-    let days = (end - start).num_days() as usize + 1;
-    let dates: Vec<NaiveDate> = (0..days).map(|i| start + chrono::Duration::days(i as i64)).collect();
-    let jd_nums: Vec<i32> = join_dates.iter().map(|d| d.date().num_days_from_ce()).collect();
-    let counts: Vec<i32> = dates.iter().map(|d| {
-        let dn = d.num_days_from_ce();
-        jd_nums.iter().filter(|&&j| j <= dn).count() as i32
-    }).collect();    (dates, counts)
+pub fn create_plot(dates: &[NaiveDate], counts: &[i32], size: ChartSize, locale: Locale) -> Result<Vec<u8>> {
+    create_line_chart("Member Count History", dates, counts, size, locale)
 }
 
-fn create_plot(dates: &Vec<NaiveDate>, counts: &Vec<i32>) -> Result<Vec<u8>> {
+pub fn create_line_chart(caption: &str, dates: &[NaiveDate], counts: &[i32], size: ChartSize, locale: Locale) -> Result<Vec<u8>> {
     use plotters_bitmap::BitMapBackend;
-    let width = 1200usize; let height = 400usize;
-    let mut buf = vec![0u8; width * height * 3];
+    let mut buf = vec![0u8; (size.width * size.height * 3) as usize];
 
     let days = dates.len();
     let max_count = counts.iter().copied().max().unwrap_or(0) + 1;
 
     {
-        let backend = BitMapBackend::with_buffer(&mut buf, (width as u32, height as u32));
+        let backend = BitMapBackend::with_buffer(&mut buf, (size.width, size.height));
         let drawing = backend.into_drawing_area();
         drawing.fill(&WHITE)?;
 
         let mut chart = ChartBuilder::on(&drawing)
             .margin(10)
-            .caption("Member Count History", ("sans-serif", 20))
+            .caption(caption, ("sans-serif", 20))
             .x_label_area_size(35)
             .y_label_area_size(40)
-            .build_cartesian_2d(0usize..days, 0i32..max_count)?;
+            .build_cartesian_2d(chart::date_axis(dates), 0i32..max_count)?;
 
-        chart.configure_mesh().disable_mesh().x_labels(6).x_label_formatter(&|v| dates[*v].to_string()).draw()?;
+        chart.configure_mesh().disable_mesh().x_label_formatter(&chart::date_labels(dates, locale)).draw()?;
 
         chart.draw_series(LineSeries::new((0..days).map(|i| (i, counts[i])), &BLUE))?;
 
         drawing.present()?;
     }
 
-    let image = image::RgbImage::from_raw(width as u32, height as u32, buf).ok_or_else(|| anyhow::anyhow!("Failed to create image"))?;
-    let mut out = Vec::new();
-    image::DynamicImage::ImageRgb8(image).write_to(&mut std::io::Cursor::new(&mut out), image::ImageOutputFormat::Png)?;
-    Ok(out)
+    chart::encode_png(size, buf)
+}
+
+/// Draw several series on one chart with a legend. Values are plotted as-is, so callers
+/// normalize them first when the series have different scales.
+pub fn create_multi_line_chart(caption: &str, dates: &[NaiveDate], series: &[(String, Vec<f64>)], size: ChartSize, locale: Locale) -> Result<Vec<u8>> {
+    use plotters_bitmap::BitMapBackend;
+    let mut buf = vec![0u8; (size.width * size.height * 3) as usize];
+
+    let max_value = series.iter().flat_map(|(_, v)| v.iter().copied()).fold(0.0f64, f64::max) * 1.05 + 1.0;
+    let min_value = series.iter().flat_map(|(_, v)| v.iter().copied()).fold(f64::MAX, f64::min).min(max_value) * 0.95;
+
+    {
+        let backend = BitMapBackend::with_buffer(&mut buf, (size.width, size.height));
+        let drawing = backend.into_drawing_area();
+        drawing.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&drawing)
+            .margin(10)
+            .caption(caption, ("sans-serif", 20))
+            .x_label_area_size(35)
+            .y_label_area_size(50)
+            .build_cartesian_2d(chart::date_axis(dates), min_value..max_value)?;
+
+        chart.configure_mesh().disable_mesh().x_label_formatter(&chart::date_labels(dates, locale)).draw()?;
+
+        for (i, (name, values)) in series.iter().enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            chart.draw_series(LineSeries::new(values.iter().enumerate().map(|(x, y)| (x, *y)), color.stroke_width(2)))?
+                .label(name.as_str())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2)));
+        }
+        chart.configure_series_labels().background_style(WHITE.mix(0.8)).border_style(BLACK).position(SeriesLabelPosition::UpperLeft).draw()?;
+
+        drawing.present()?;
+    }
+
+    chart::encode_png(size, buf)
+}
+
+/// Vertical bars labelled `1..=n` with their value on top. Callers list what each number
+/// stands for in the embed, so long labels don't crowd the axis.
+pub fn create_bar_chart(caption: &str, values: &[i64], size: ChartSize) -> Result<Vec<u8>> {
+    use plotters_bitmap::BitMapBackend;
+    let mut buf = vec![0u8; (size.width * size.height * 3) as usize];
+
+    let max_value = values.iter().copied().max().unwrap_or(0) + 1;
+
+    {
+        let backend = BitMapBackend::with_buffer(&mut buf, (size.width, size.height));
+        let drawing = backend.into_drawing_area();
+        drawing.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&drawing)
+            .margin(10)
+            .caption(caption, ("sans-serif", 20))
+            .x_label_area_size(35)
+            .y_label_area_size(40)
+            .build_cartesian_2d((0..values.len()).into_segmented(), 0i64..max_value + max_value / 10 + 1)?;
+
+        chart.configure_mesh().disable_x_mesh().x_labels(values.len()).x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(i) => (i + 1).to_string(),
+            _ => String::new(),
+        }).draw()?;
+
+        chart.draw_series(values.iter().enumerate().map(|(i, v)| {
+            let color = Palette99::pick(i).to_rgba();
+            let mut bar = Rectangle::new([(SegmentValue::Exact(i), 0), (SegmentValue::Exact(i + 1), *v)], color.filled());
+            bar.set_margin(0, 0, 8, 8);
+            bar
+        }))?;
+        chart.draw_series(values.iter().enumerate().map(|(i, v)| {
+            Text::new(v.to_string(), (SegmentValue::CenterOf(i), *v), ("sans-serif", 16).into_font().color(&BLACK))
+        }))?;
+
+        drawing.present()?;
+    }
+
+    chart::encode_png(size, buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn history() -> (Vec<NaiveDate>, Vec<i32>) {
+        let dates = testing::chart_join_dates(90);
+        crate::stats::member_counts(&dates, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), NaiveDate::from_ymd_opt(2024, 3, 31).unwrap())
+    }
+
+    #[test]
+    fn history_chart_matches_golden() {
+        testing::chart_fonts();
+        let (dates, counts) = history();
+        testing::assert_golden("members_history", &create_plot(&dates, &counts, chart::STANDARD, Locale::Ja).unwrap());
+        testing::assert_golden("members_history_en", &create_plot(&dates, &counts, chart::STANDARD, Locale::En).unwrap());
+    }
+
+    #[test]
+    fn multi_line_chart_matches_golden() {
+        testing::chart_fonts();
+        let (dates, counts) = history();
+        let max = *counts.iter().max().unwrap() as f64;
+        let series = vec![
+            ("members".to_string(), counts.iter().map(|c| *c as f64 / max).collect()),
+            ("half".to_string(), counts.iter().map(|c| *c as f64 / max / 2.0).collect()),
+        ];
+        testing::assert_golden("members_history_multi", &create_multi_line_chart("Compare", &dates, &series, chart::STANDARD, Locale::Ja).unwrap());
+    }
+
+    #[test]
+    fn bar_chart_matches_golden() {
+        testing::chart_fonts();
+        testing::assert_golden("members_history_bars", &create_bar_chart("Joins by weekday", &[12, 7, 9, 15, 4, 22, 18], chart::STANDARD).unwrap());
+    }
 }
@@ -1,31 +1,42 @@
 use anyhow::Result;
+use once_cell::sync::Lazy;
 use regex::Regex;
+use serenity::http::Http;
 use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, GuildId, MessageId};
 use serenity::prelude::*;
 use serenity::model::prelude::component::ButtonStyle;
 
-pub async fn handle_message(ctx: &Context, message: &Message) -> Result<()> {
-    // ignore bot's own messages
-    if message.author.bot { return Ok(()); }
+/// Without message content every message arrives empty, so no link is ever found.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::MESSAGE_CONTENT;
 
-    let privacy = false; // Privacy cog not implemented in Rust; assume absent
+static LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"https://(?:canary\.|ptb\.)?discord\.com/channels/(\d+)/(\d+)/(\d+)").unwrap());
 
-    if privacy { return Ok(()); }
+/// The first message link in `text`.
+pub fn parse(text: &str) -> Option<(GuildId, ChannelId, MessageId)> {
+    let cap = LINK.captures(text)?;
+    Some((GuildId(cap[1].parse().ok()?), ChannelId(cap[2].parse().ok()?), MessageId(cap[3].parse().ok()?)))
+}
 
-    let re = Regex::new(r"https://(?:canary\.|ptb\.)?discord\.com/channels/(\d+)/(\d+)/(\d+)")?;
-    if let Some(cap) = re.captures(&message.content) {
-        let guild_id: u64 = cap.get(1).unwrap().as_str().parse()?;
-        let channel_id: u64 = cap.get(2).unwrap().as_str().parse()?;
-        let message_id: u64 = cap.get(3).unwrap().as_str().parse()?;
+/// Fetch the message a link in `text` points to, if there is one and it can be read.
+pub async fn fetch(http: &Http, text: &str) -> Option<Message> {
+    let (_, channel, message_id) = parse(text)?;
+    channel.message(http, message_id).await.ok()
+}
+
+pub async fn handle_message(ctx: &Context, message: &Message) -> Result<()> {
+    // ignore bot's own messages
+    if message.author.bot || !crate::intents::has(REQUIRED_INTENTS) { return Ok(()); }
 
-        // fetch guild and channel
-        let channel = serenity::model::id::ChannelId(channel_id);
+    if let Some((_, channel, _)) = parse(&message.content) {
         // check nsfw
         if let Ok(ch) = channel.to_channel(&ctx.http).await {
             if ch.is_nsfw() { return Ok(()); }
         }
 
-        if let Ok(target) = channel.message(&ctx.http, message_id).await {
+        if let Some(target) = fetch(&ctx.http, &message.content).await {
+            // the linked message's author opted out of being quoted
+            if crate::db::get_privacy_enabled(target.author.id.0 as i64).await.unwrap_or(false) { return Ok(()); }
             message.channel_id.send_message(&ctx.http, |m| {
                 m.embed(|e| {
                     e.description(&target.content);
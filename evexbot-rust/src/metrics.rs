@@ -0,0 +1,105 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::client::bridge::gateway::{ShardId, ShardManager};
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+
+/// Samples kept per metric; enough for a stable average without growing unbounded.
+const MAX_SAMPLES: usize = 100;
+
+pub const GATEWAY: &str = "gateway";
+pub const REST: &str = "rest";
+pub const DB: &str = "db";
+pub const INTERACTION: &str = "interaction";
+
+/// The shard manager, stored in the client's data so handlers can read heartbeat latency.
+pub struct ShardManagerContainer;
+
+impl TypeMapKey for ShardManagerContainer {
+    type Value = Arc<Mutex<ShardManager>>;
+}
+
+static SAMPLES: Lazy<std::sync::Mutex<HashMap<&'static str, VecDeque<Duration>>>> = Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Record one latency sample for `name`.
+pub fn observe(name: &'static str, value: Duration) {
+    let mut samples = SAMPLES.lock().unwrap();
+    let entry = samples.entry(name).or_default();
+    if entry.len() == MAX_SAMPLES { entry.pop_front(); }
+    entry.push_back(value);
+}
+
+/// Mean of the recent samples for `name`.
+pub fn average(name: &str) -> Option<Duration> {
+    let samples = SAMPLES.lock().unwrap();
+    let entry = samples.get(name).filter(|e| !e.is_empty())?;
+    Some(entry.iter().sum::<Duration>() / entry.len() as u32)
+}
+
+/// Heartbeat latency of the shard this context belongs to; None until the first heartbeat ACK.
+pub async fn gateway_latency(ctx: &Context) -> Option<Duration> {
+    let manager = ctx.data.read().await.get::<ShardManagerContainer>()?.clone();
+    let manager = manager.lock().await;
+    let runners = manager.runners.lock().await;
+    runners.get(&ShardId(ctx.shard_id)).and_then(|r| r.latency)
+}
+
+async fn time<F, T>(name: &'static str, f: F) -> Result<Duration>
+where
+    F: std::future::Future<Output = Result<T>>,
+{
+    let started = Instant::now();
+    f.await?;
+    let elapsed = started.elapsed();
+    observe(name, elapsed);
+    Ok(elapsed)
+}
+
+pub struct PingCommand;
+
+#[async_trait]
+impl Command for PingCommand {
+    fn name(&self) -> &'static str { "ping" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("Botの応答速度を表示します")
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_ping_command(inv.ctx, inv.command).await
+    }
+}
+
+fn format_ms(d: Option<Duration>) -> String {
+    d.map(|d| format!("{}ms", d.as_millis())).unwrap_or_else(|| "計測不可".to_string())
+}
+
+async fn handle_ping_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    // The deferral itself is the REST round trip.
+    let rest = time(REST, async {
+        command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+        Ok::<_, anyhow::Error>(())
+    }).await?;
+    let db_latency = time(DB, db::ping()).await.ok();
+    let gateway = gateway_latency(ctx).await;
+    if let Some(g) = gateway { observe(GATEWAY, g); }
+
+    let line = |label: &str, now: Option<Duration>, name: &str| format!("{}: {} (平均 {})", label, format_ms(now), format_ms(average(name)));
+    let body = [
+        line("Gateway", gateway, GATEWAY),
+        line("REST", Some(rest), REST),
+        line("DB", db_latency, DB),
+        format!("コマンド処理: 平均 {}", format_ms(average(INTERACTION))),
+    ].join("\n");
+    command.create_followup_message(&ctx.http, |m| m.embed(|e| e.title("🏓 Pong!").description(body).color(serenity::utils::Colour::BLURPLE).footer(|f| f.text("EvexBot | Ping"))).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use crate::db::Backend;
+use sqlx::{AnyPool, Row};
+
+/// Schema changes in the order they apply, as (version, name, SQLite, PostgreSQL). Add a file to
+/// `migrations/` and its PostgreSQL version to `migrations/postgres/`, and a line here, instead
+/// of altering tables from `init_db`; released migrations must never be edited.
+const MIGRATIONS: &[(i64, &str, &str, &str)] = &[
+    (1, "initial", include_str!("../migrations/0001_initial.sql"), include_str!("../migrations/postgres/0001_initial.sql")),
+    (2, "intro_settings_columns", include_str!("../migrations/0002_intro_settings_columns.sql"), include_str!("../migrations/postgres/0002_intro_settings_columns.sql")),
+    (3, "scheduled_events", include_str!("../migrations/0003_scheduled_events.sql"), include_str!("../migrations/postgres/0003_scheduled_events.sql")),
+    (4, "triage_auto_thread", include_str!("../migrations/0004_triage_auto_thread.sql"), include_str!("../migrations/postgres/0004_triage_auto_thread.sql")),
+    (5, "onboarding_dms", include_str!("../migrations/0005_onboarding_dms.sql"), include_str!("../migrations/postgres/0005_onboarding_dms.sql")),
+    (6, "rules", include_str!("../migrations/0006_rules.sql"), include_str!("../migrations/postgres/0006_rules.sql")),
+    (7, "shortlinks", include_str!("../migrations/0007_shortlinks.sql"), include_str!("../migrations/postgres/0007_shortlinks.sql")),
+    (8, "highlights", include_str!("../migrations/0008_highlights.sql"), include_str!("../migrations/postgres/0008_highlights.sql")),
+    (9, "spotlight", include_str!("../migrations/0009_spotlight.sql"), include_str!("../migrations/postgres/0009_spotlight.sql")),
+    (10, "intro_channel", include_str!("../migrations/0010_intro_channel.sql"), include_str!("../migrations/postgres/0010_intro_channel.sql")),
+    (11, "chart_watermark", include_str!("../migrations/0011_chart_watermark.sql"), include_str!("../migrations/postgres/0011_chart_watermark.sql")),
+    (12, "growth_forecasts", include_str!("../migrations/0012_growth_forecasts.sql"), include_str!("../migrations/postgres/0012_growth_forecasts.sql")),
+    (13, "prune", include_str!("../migrations/0013_prune.sql"), include_str!("../migrations/postgres/0013_prune.sql")),
+    (14, "role_decay", include_str!("../migrations/0014_role_decay.sql"), include_str!("../migrations/postgres/0014_role_decay.sql")),
+    (15, "contributors", include_str!("../migrations/0015_contributors.sql"), include_str!("../migrations/postgres/0015_contributors.sql")),
+    (16, "message_logging", include_str!("../migrations/0016_message_logging.sql"), include_str!("../migrations/postgres/0016_message_logging.sql")),
+    (17, "help_threads", include_str!("../migrations/0017_help_threads.sql"), include_str!("../migrations/postgres/0017_help_threads.sql")),
+    (18, "reaction_roles", include_str!("../migrations/0018_reaction_roles.sql"), include_str!("../migrations/postgres/0018_reaction_roles.sql")),
+    (19, "kb", include_str!("../migrations/0019_kb.sql"), include_str!("../migrations/postgres/0019_kb.sql")),
+    (20, "polls", include_str!("../migrations/0020_polls.sql"), include_str!("../migrations/postgres/0020_polls.sql")),
+    (21, "help_thread_search", include_str!("../migrations/0021_help_thread_search.sql"), include_str!("../migrations/postgres/0021_help_thread_search.sql")),
+    (22, "scheduled_jobs", include_str!("../migrations/0022_scheduled_jobs.sql"), include_str!("../migrations/postgres/0022_scheduled_jobs.sql")),
+    (23, "prompts", include_str!("../migrations/0023_prompts.sql"), include_str!("../migrations/postgres/0023_prompts.sql")),
+    (24, "user_xp", include_str!("../migrations/0024_user_xp.sql"), include_str!("../migrations/postgres/0024_user_xp.sql")),
+    (25, "disabled_modules", include_str!("../migrations/0025_disabled_modules.sql"), include_str!("../migrations/postgres/0025_disabled_modules.sql")),
+    (26, "antiraid", include_str!("../migrations/0026_antiraid.sql"), include_str!("../migrations/postgres/0026_antiraid.sql")),
+    (27, "spam_filter", include_str!("../migrations/0027_spam_filter.sql"), include_str!("../migrations/postgres/0027_spam_filter.sql")),
+    (28, "link_filter", include_str!("../migrations/0028_link_filter.sql"), include_str!("../migrations/postgres/0028_link_filter.sql")),
+    (29, "word_filter", include_str!("../migrations/0029_word_filter.sql"), include_str!("../migrations/postgres/0029_word_filter.sql")),
+    (30, "error_reports", include_str!("../migrations/0030_error_reports.sql"), include_str!("../migrations/postgres/0030_error_reports.sql")),
+];
+
+/// Statements of a migration file, with `--` comments removed.
+fn statements(sql: &str) -> Vec<String> {
+    let sql = sql.lines().filter(|l| !l.trim_start().starts_with("--")).collect::<Vec<_>>().join("\n");
+    sql.split(';').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+}
+
+/// Bring the database up to the latest version, one transaction per migration.
+pub async fn run(pool: &AnyPool) -> Result<()> {
+    let backend = crate::db::backend_of(pool);
+    let integer = match backend { Backend::Sqlite => "INTEGER", Backend::Postgres => "BIGINT" };
+    sqlx::query(&format!("CREATE TABLE IF NOT EXISTS schema_version (
+        version {0} PRIMARY KEY,
+        name TEXT NOT NULL,
+        applied_at {0} NOT NULL
+    );", integer))
+    .execute(pool)
+    .await?;
+
+    let current = sqlx::query("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(pool)
+        .await?
+        .get::<i64, _>(0);
+
+    // Databases from before versioning have tables but no recorded version; the old ad-hoc
+    // ALTERs may already have added some of the columns the early migrations add.
+    // PostgreSQL support came after versioning, so there are no such databases there.
+    let legacy = current == 0 && backend == Backend::Sqlite && sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'welcome_settings'")
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+
+    for (version, name, sqlite, postgres) in MIGRATIONS.iter().filter(|(v, _, _, _)| *v > current) {
+        let sql = match backend { Backend::Sqlite => sqlite, Backend::Postgres => postgres };
+        let mut tx = pool.begin().await?;
+        for stmt in statements(sql) {
+            if let Err(e) = sqlx::query(&stmt).execute(&mut tx).await {
+                if legacy && e.to_string().contains("duplicate column name") { continue; }
+                return Err(e).with_context(|| format!("migration {} ({}) failed", version, name));
+            }
+        }
+        sqlx::query("INSERT INTO schema_version (version, name, applied_at) VALUES ($1, $2, $3)")
+            .bind(version)
+            .bind(name)
+            .bind(chrono::Utc::now().timestamp())
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        log::info!("db: applied migration {} ({})", version, name);
+    }
+    Ok(())
+}
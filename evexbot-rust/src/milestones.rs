@@ -0,0 +1,110 @@
+use anyhow::Result;
+use chrono::{NaiveDate, TimeZone, Utc};
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::Message;
+use serenity::prelude::*;
+
+use crate::bus;
+use crate::chart::{self, Locale};
+use crate::commands::{Command, Invocation};
+use crate::db::{self, MilestoneEntry};
+use crate::members_history;
+use crate::report::Report;
+
+pub async fn on_event(_ctx: &Context, event: &bus::Event) -> Result<()> {
+    match event {
+        bus::Event::MilestoneReached { guild_id, member_count, message } => record(*guild_id, *member_count, message).await,
+        _ => Ok(()),
+    }
+}
+
+/// Record a milestone celebration so `/milestones history` can show it later.
+async fn record(guild_id: i64, member_count: i64, message: &Message) -> Result<()> {
+    db::add_milestone_log(guild_id, member_count, message.channel_id.0 as i64, message.id.0 as i64, message.timestamp.unix_timestamp()).await
+}
+
+pub struct MilestonesCommand;
+
+#[async_trait]
+impl Command for MilestonesCommand {
+    fn name(&self) -> &'static str { "milestones" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("メンバー数の節目")
+            .create_option(|s| s.name("history").description("これまでの節目のお祝いと間隔を表示します").kind(CommandOptionType::SubCommand))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_milestones_command(inv.ctx, inv.command).await
+    }
+}
+
+/// Days it took per 100 members between consecutive milestones, paired with the date it ended.
+fn days_per_100(rows: &[MilestoneEntry]) -> Vec<(NaiveDate, f64)> {
+    rows.windows(2).filter_map(|w| {
+        let (prev, cur) = (&w[0], &w[1]);
+        if cur.member_count <= prev.member_count { return None; }
+        let days = (cur.created_at - prev.created_at) as f64 / 86400.0;
+        Some((Utc.timestamp_opt(cur.created_at, 0).single()?.date_naive(), days / (cur.member_count - prev.member_count) as f64 * 100.0))
+    }).collect()
+}
+
+/// Least-squares line through `values` indexed 0..n.
+fn trendline(values: &[f64]) -> Vec<f64> {
+    let n = values.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f64>() / n;
+    let var_x: f64 = (0..values.len()).map(|i| (i as f64 - mean_x).powi(2)).sum();
+    let slope = if var_x == 0.0 { 0.0 } else { values.iter().enumerate().map(|(i, y)| (i as f64 - mean_x) * (y - mean_y)).sum::<f64>() / var_x };
+    (0..values.len()).map(|i| mean_y + slope * (i as f64 - mean_x)).collect()
+}
+
+async fn handle_milestones_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let ephemeral = crate::preferences::wants_ephemeral(command.user.id.0 as i64).await;
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(ephemeral))).await?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let rows = db::get_milestone_log(guild_id).await?;
+    if rows.is_empty() {
+        command.create_followup_message(&ctx.http, |m| m.content("まだ節目のお祝いの記録はありません。")).await?;
+        return Ok(());
+    }
+
+    let mut lines = Vec::new();
+    for (i, entry) in rows.iter().enumerate() {
+        let date = Utc.timestamp_opt(entry.created_at, 0).single().map(|d| d.date_naive().to_string()).unwrap_or_default();
+        let interval = match i.checked_sub(1).map(|p| &rows[p]) {
+            Some(prev) => format!(" (前回から{}日)", (entry.created_at - prev.created_at) / 86400),
+            None => String::new(),
+        };
+        lines.push(format!("**{}人** {}{} [メッセージ](https://discord.com/channels/{}/{}/{})", entry.member_count, date, interval, guild_id, entry.channel_id, entry.message_id));
+    }
+
+    let rates = days_per_100(&rows);
+    let mut report = Report::embed("milestones.txt", "節目の履歴", serenity::utils::Colour::GOLD).lines(lines).ephemeral(ephemeral);
+    if !rates.is_empty() {
+        let values: Vec<f64> = rates.iter().map(|(_, v)| *v).collect();
+        let average = values.iter().sum::<f64>() / values.len() as f64;
+        let trend = trendline(&values);
+        let direction = match (trend.first(), trend.last()) {
+            (Some(a), Some(b)) if values.len() >= 3 && b < &(a * 0.9) => " ・ 成長は加速しています",
+            (Some(a), Some(b)) if values.len() >= 3 && b > &(a * 1.1) => " ・ 成長は鈍化しています",
+            _ => "",
+        };
+        report = report.header(format!("100人あたり平均 {:.1}日{}", average, direction));
+
+        // A trendline needs at least two intervals to say anything.
+        if values.len() >= 2 {
+            let locale = Locale::for_user(command.user.id.0 as i64, Some(guild_id)).await;
+            let size = chart::resolve(Some(guild_id), None, chart::STANDARD).await.unwrap_or(chart::STANDARD);
+            let dates: Vec<NaiveDate> = rates.iter().map(|(d, _)| *d).collect();
+            let series = vec![("100人あたりの日数".to_string(), values), ("傾向".to_string(), trend)];
+            let png = chart::finish(Some(guild_id), members_history::create_multi_line_chart("節目の間隔", &dates, &series, size, locale)?, "milestones").await;
+            command.create_followup_message(&ctx.http, |m| m.add_file((png.bytes.as_slice(), png.filename().as_str())).ephemeral(ephemeral)).await?;
+        }
+    }
+    report.send(&ctx.http, command).await
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::*;
+
+use crate::appeal;
+use crate::bus;
+use crate::commands::{Command, Defer, Invocation};
+use crate::db;
+use crate::member_cache;
+use crate::permissions;
+use crate::report::Report;
+
+const MAX_REASON_LENGTH: u16 = 512;
+/// Discord caps timeouts at 28 days.
+const MAX_TIMEOUT_MINUTES: i64 = 28 * 24 * 60;
+const MAX_DELETE_DAYS: i64 = 7;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Warn,
+    Kick,
+    Ban { delete_days: u8 },
+    Timeout { minutes: i64 },
+}
+
+impl Action {
+    /// The `mod_cases.action` value.
+    fn key(self) -> &'static str {
+        match self {
+            Action::Warn => "warn",
+            Action::Kick => "kick",
+            Action::Ban { .. } => "ban",
+            Action::Timeout { .. } => "timeout",
+        }
+    }
+}
+
+fn user_and_reason(c: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    c.create_option(|o| o.name("user").description("対象のメンバー").kind(CommandOptionType::User).required(true))
+        .create_option(|o| o.name("reason").description("理由 (本人と監査ログに表示されます)").kind(CommandOptionType::String).max_length(MAX_REASON_LENGTH).required(false))
+}
+
+/// DM the member about a warning or kick. Bans and timeouts get an appeal button from `appeal::open_case` instead.
+async fn notify(http: &Http, guild_id: GuildId, target: UserId, action: Action, reason: Option<&str>, case_id: i64) {
+    let guild_name = guild_id.to_partial_guild(http).await.map(|g| g.name).unwrap_or_else(|_| guild_id.0.to_string());
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("{} で{}されました", guild_name, appeal::action_label(action.key())));
+    embed.description(format!("理由: {}", reason.unwrap_or("指定なし")));
+    embed.color(serenity::utils::Colour::ORANGE);
+    embed.footer(|f| f.text(format!("EvexBot | Case #{}", case_id)));
+    let sent = async {
+        let dm = target.create_dm_channel(http).await?;
+        dm.send_message(http, |m| m.embed(|e| { *e = embed; e })).await?;
+        Ok::<_, anyhow::Error>(())
+    }.await;
+    if let Err(e) = sent { log::info!("moderation: could not DM user {} for case {}: {}", target.0, case_id, e); }
+}
+
+async fn apply(http: &Http, guild_id: GuildId, target: UserId, action: Action, reason: &str) -> Result<()> {
+    match action {
+        Action::Warn => {}
+        Action::Kick => guild_id.kick_with_reason(http, target, reason).await?,
+        Action::Ban { delete_days } => guild_id.ban_with_reason(http, target, delete_days, reason).await?,
+        Action::Timeout { minutes } => {
+            let until = Utc::now() + chrono::Duration::minutes(minutes);
+            guild_id.edit_member(http, target, |m| m.disable_communication_until(until.to_rfc3339())).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Shared by `/warn`, `/kick`, `/ban` and `/timeout`: check the target, open the case, act, and log it.
+async fn moderate(inv: &Invocation<'_>, action: Action) -> Result<()> {
+    let (ctx, command) = (inv.ctx, inv.command);
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+    if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+
+    let target = match inv.args.user("user") { Some((u, _)) => u, None => return Ok(()) };
+    if target.id == command.user.id { return inv.say("自分自身は対象にできません。").await; }
+    if target.bot { return inv.say("Botは対象にできません。").await; }
+    // Only bans reach users who already left.
+    match guild_id.member(&ctx.http, target.id).await {
+        Ok(m) if permissions::is_admin(&m).await => return inv.say("管理者は対象にできません。").await,
+        Ok(_) => {}
+        Err(_) if matches!(action, Action::Ban { .. }) => {}
+        Err(_) => return inv.say("そのユーザーはこのサーバーのメンバーではありません。").await,
+    }
+
+    let reason = inv.args.str("reason").map(str::trim).filter(|r| !r.is_empty());
+    let moderator = command.user.id.0 as i64;
+    // The case is opened first: banned members can't be DMed afterwards, and the ban and
+    // timeout events see it and don't open a second one.
+    let case_id = match action {
+        Action::Ban { .. } | Action::Timeout { .. } => appeal::open_case(&ctx.http, guild_id, target.id, action.key(), Some(moderator), reason).await?,
+        Action::Warn | Action::Kick => {
+            let id = db::create_mod_case(guild_id.0 as i64, Some(moderator), target.id.0 as i64, action.key(), reason, Utc::now().timestamp()).await?;
+            notify(&ctx.http, guild_id, target.id, action, reason, id).await;
+            id
+        }
+    };
+    let audit_reason = format!("{} (case #{} by {})", reason.unwrap_or("no reason"), case_id, command.user.tag());
+    if let Err(e) = apply(&ctx.http, guild_id, target.id, action, &audit_reason).await {
+        db::delete_mod_case(case_id).await?;
+        return inv.say(format!("{}に失敗しました: {}", appeal::action_label(action.key()), e)).await;
+    }
+    if matches!(action, Action::Kick | Action::Ban { .. }) { member_cache::invalidate(guild_id).await; }
+
+    bus::publish(bus::Event::ModAction {
+        guild_id,
+        case_id,
+        action: action.key(),
+        target: target.clone(),
+        moderator: command.user.clone(),
+        reason: reason.map(str::to_string),
+        minutes: if let Action::Timeout { minutes } = action { Some(minutes) } else { None },
+    });
+    inv.say(format!("Case #{}: {} を{}しました。", case_id, target.mention(), appeal::action_label(action.key()))).await
+}
+
+pub struct WarnCommand;
+
+#[async_trait]
+impl Command for WarnCommand {
+    fn name(&self) -> &'static str { "warn" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        user_and_reason(c.description("メンバーに警告し、ケースとして記録します"))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> { moderate(inv, Action::Warn).await }
+}
+
+pub struct KickCommand;
+
+#[async_trait]
+impl Command for KickCommand {
+    fn name(&self) -> &'static str { "kick" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        user_and_reason(c.description("メンバーをキックし、ケースとして記録します"))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> { moderate(inv, Action::Kick).await }
+}
+
+pub struct BanCommand;
+
+#[async_trait]
+impl Command for BanCommand {
+    fn name(&self) -> &'static str { "ban" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        user_and_reason(c.description("ユーザーをBANし、ケースとして記録します"))
+            .create_option(|o| o.name("delete_days").description(format!("削除する直近のメッセージの日数 (0～{})", MAX_DELETE_DAYS)).kind(CommandOptionType::Integer).min_int_value(0).max_int_value(MAX_DELETE_DAYS).required(false))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let delete_days = inv.args.int("delete_days").unwrap_or(0).clamp(0, MAX_DELETE_DAYS) as u8;
+        moderate(inv, Action::Ban { delete_days }).await
+    }
+}
+
+pub struct TimeoutCommand;
+
+#[async_trait]
+impl Command for TimeoutCommand {
+    fn name(&self) -> &'static str { "timeout" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("メンバーをタイムアウトし、ケースとして記録します")
+            .create_option(|o| o.name("user").description("対象のメンバー").kind(CommandOptionType::User).required(true))
+            .create_option(|o| o.name("minutes").description("期間 (分、最大28日)").kind(CommandOptionType::Integer).min_int_value(1).max_int_value(MAX_TIMEOUT_MINUTES).required(true))
+            .create_option(|o| o.name("reason").description("理由 (本人と監査ログに表示されます)").kind(CommandOptionType::String).max_length(MAX_REASON_LENGTH).required(false))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let minutes = inv.args.int("minutes").unwrap_or(0);
+        if !(1..=MAX_TIMEOUT_MINUTES).contains(&minutes) { return inv.say(format!("期間は1～{}分で指定してください。", MAX_TIMEOUT_MINUTES)).await; }
+        moderate(inv, Action::Timeout { minutes }).await
+    }
+}
+
+fn case_line(case: &db::ModCase) -> String {
+    let moderator = case.moderator_id.map(|m| format!("<@{}>", m)).unwrap_or_else(|| "不明".to_string());
+    let appeal = match case.appeal_status.as_deref() {
+        Some("pending") => " · 申し立て中",
+        Some("accepted") => " · 申し立て承認",
+        Some("denied") => " · 申し立て却下",
+        _ => "",
+    };
+    format!("**#{}** {} <t:{}:d> by {}{} — {}", case.id, appeal::action_label(&case.action), case.created_at, moderator, appeal, case.reason.as_deref().unwrap_or("理由なし"))
+}
+
+pub struct CaseCommand;
+
+#[async_trait]
+impl Command for CaseCommand {
+    fn name(&self) -> &'static str { "case" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("モデレーションのケースを表示します")
+            .create_option(|o| o.name("id").description("ケース番号").kind(CommandOptionType::Integer).min_int_value(1).required(false))
+            .create_option(|o| o.name("user").description("このユーザーのケースを一覧します").kind(CommandOptionType::User).required(false))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+
+        if let Some(id) = inv.args.int("id") {
+            let case = match db::get_mod_case_detail(gid, id).await? {
+                Some(c) => c,
+                None => return inv.say(format!("Case #{} は見つかりません。", id)).await,
+            };
+            return inv.say(format!("<@{}>\n{}", case.target_id, case_line(&case))).await;
+        }
+        let (user, _) = match inv.args.user("user") {
+            Some(u) => u,
+            None => return inv.say("`id` か `user` を指定してください。").await,
+        };
+        let cases = db::get_mod_cases_for(gid, user.id.0 as i64).await?;
+        let report = Report::embed("cases.txt", format!("{} のケース", user.tag()), serenity::utils::Colour::RED).ephemeral(true);
+        let report = if cases.is_empty() { report.line("ケースはありません。") } else { report.lines(cases.iter().map(case_line)) };
+        report.send(&inv.ctx.http, command).await
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::prelude::*;
+
+use crate::appeal;
+use crate::bus;
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::permissions;
+
+/// Post an embed to the guild's configured mod-log channel. Silently does nothing when unset.
+pub async fn send(http: &Http, guild_id: GuildId, embed: CreateEmbed) -> Result<()> {
+    let channel_id = match db::get_modlog_channel(guild_id.0 as i64).await? {
+        Some(id) => ChannelId(id as u64),
+        None => return Ok(()),
+    };
+    channel_id.send_message(http, |m| m.embed(|e| { *e = embed; e })).await?;
+    Ok(())
+}
+
+/// Log moderation cases and `/config` changes published on the bus.
+pub async fn on_event(ctx: &Context, event: &bus::Event) -> Result<()> {
+    let mut embed = CreateEmbed::default();
+    let guild_id = match event {
+        bus::Event::ModAction { guild_id, case_id, action, target, moderator, reason, minutes } => {
+            embed.title(format!("Case #{} | {}", case_id, appeal::action_label(action)));
+            embed.field("対象", format!("{} ({})", target.mention(), target.tag()), true);
+            embed.field("実行者", moderator.mention(), true);
+            if let Some(m) = minutes { embed.field("期間", format!("{}分", m), true); }
+            embed.field("理由", reason.as_deref().unwrap_or("指定なし"), false);
+            embed.color(serenity::utils::Colour::RED);
+            embed.footer(|f| f.text("EvexBot | Moderation"));
+            *guild_id
+        }
+        bus::Event::SettingsChanged { guild_id, group, subcommand, user } => {
+            embed.title("設定が変更されました");
+            embed.field("コマンド", format!("`/config {} {}`", group, subcommand), true);
+            embed.field("実行者", user.mention(), true);
+            embed.color(serenity::utils::Colour::BLUE);
+            embed.footer(|f| f.text("EvexBot | Settings"));
+            *guild_id
+        }
+        _ => return Ok(()),
+    };
+    embed.timestamp(Utc::now().to_rfc3339());
+    send(&ctx.http, guild_id, embed).await
+}
+
+pub struct ModlogCommand;
+
+#[async_trait]
+impl Command for ModlogCommand {
+    fn name(&self) -> &'static str { "modlog" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("モデレーションログの設定").create_option(|o| {
+            o.name("action").description("enable|disable|summary-on|summary-off").kind(serenity::model::application::command::CommandOptionType::String).required(true)
+        }).create_option(|o| {
+            o.name("channel").description("送信先チャンネル").kind(serenity::model::application::command::CommandOptionType::Channel).required(false)
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_modlog_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_modlog_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+    let action = command.data.options.iter().find(|o| o.name=="action").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+    let channel = command.data.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.clone()), _ => None });
+
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+
+    match action {
+        "enable" => {
+            let chan = match channel { Some(c) => c, None => { command.create_followup_message(&ctx.http, |m| m.content("ONにする場合はチャンネルを指定してください。" ).ephemeral(true)).await?; return Ok(()); } };
+            db::update_modlog_channel(guild_id, Some(chan.id.0 as i64)).await?;
+            command.create_followup_message(&ctx.http, |m| m.content(format!("モデレーションログを<#{}>に送信します。", chan.id.0)).ephemeral(true)).await?;
+        }
+        "disable" => {
+            db::update_modlog_channel(guild_id, None).await?;
+            command.create_followup_message(&ctx.http, |m| m.content("モデレーションログを無効にしました!").ephemeral(true)).await?;
+        }
+        "summary-on" | "summary-off" => {
+            let enabled = action == "summary-on";
+            db::update_daily_summary_enabled(guild_id, enabled).await?;
+            let msg = if enabled { "毎日0時 (JST) に前日のメンバー動向をモデレーションログに送信します。" } else { "日次サマリーを無効にしました!" };
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+        }
+        _ => { command.create_followup_message(&ctx.http, |m| m.content("enable、disable、summary-on、summary-offのいずれかを指定してください。" ).ephemeral(true)).await?; }
+    }
+    Ok(())
+}
@@ -0,0 +1,365 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::{Lazy, OnceCell};
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::bus;
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+
+/// Other instances see `/module` changes within this long.
+const TTL: Duration = Duration::from_secs(60);
+
+/// A feature that can be compiled in but turned off per guild with `/module disable`.
+/// Disabling stops the module's commands, message pipeline stage and event handling in
+/// that guild; background jobs keep following their own per-guild settings.
+#[async_trait]
+pub trait Module: Send + Sync {
+    /// Also the name of the module's stage in `pipeline`, if it has one.
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Slash commands that answer for this module.
+    fn commands(&self) -> &'static [&'static str] { &[] }
+
+    /// Core modules, such as the one answering `/module`, can't be disabled.
+    fn can_disable(&self) -> bool { true }
+
+    /// Start background jobs. Runs on every `ready`, so it must be safe to repeat.
+    fn init(&self, _http: Arc<Http>) {}
+
+    /// Whether `on_event` should receive bus events.
+    fn subscribes(&self) -> bool { false }
+
+    async fn on_event(&self, _ctx: &Context, _event: &bus::Event) -> Result<()> { Ok(()) }
+
+    /// Clean up before the process exits.
+    async fn shutdown(&self) {}
+}
+
+type ModuleFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+type EventFn = for<'a> fn(&'a Context, &'a bus::Event) -> ModuleFuture<'a>;
+
+/// A module whose parts are still plain functions in its file.
+struct Builtin {
+    name: &'static str,
+    description: &'static str,
+    commands: &'static [&'static str],
+    core: bool,
+    init: Option<fn(Arc<Http>)>,
+    on_event: Option<EventFn>,
+}
+
+impl Builtin {
+    fn new(name: &'static str, description: &'static str) -> Self {
+        Builtin { name, description, commands: &[], core: false, init: None, on_event: None }
+    }
+
+    fn commands(mut self, commands: &'static [&'static str]) -> Self { self.commands = commands; self }
+
+    fn core(mut self) -> Self { self.core = true; self }
+
+    fn init(mut self, init: fn(Arc<Http>)) -> Self { self.init = Some(init); self }
+
+    fn on_event(mut self, on_event: EventFn) -> Self { self.on_event = Some(on_event); self }
+}
+
+#[async_trait]
+impl Module for Builtin {
+    fn name(&self) -> &'static str { self.name }
+    fn description(&self) -> &'static str { self.description }
+    fn commands(&self) -> &'static [&'static str] { self.commands }
+    fn can_disable(&self) -> bool { !self.core }
+    fn init(&self, http: Arc<Http>) { if let Some(init) = self.init { init(http); } }
+    fn subscribes(&self) -> bool { self.on_event.is_some() }
+
+    async fn on_event(&self, ctx: &Context, event: &bus::Event) -> Result<()> {
+        match self.on_event { Some(on_event) => on_event(ctx, event).await, None => Ok(()) }
+    }
+}
+
+struct Manager {
+    modules: Vec<Box<dyn Module>>,
+}
+
+impl Manager {
+    fn new() -> Self { Manager { modules: Vec::new() } }
+
+    fn module(mut self, module: impl Module + 'static) -> Self {
+        self.modules.push(Box::new(module));
+        self
+    }
+}
+
+/// Every module, in the order they start. New modules go here and nowhere else.
+static MODULES: Lazy<Manager> = Lazy::new(|| {
+    Manager::new()
+        .module(Builtin::new("modules", "モジュールの有効・無効の切り替え").commands(&["module"]).core())
+        .module(Builtin::new("settings", "サーバー設定").commands(&["config"]).core())
+        .module(Builtin::new("owner", "Botオーナー用のコマンド").commands(&["dbquery", "sync-commands", "errorlookup"]).core())
+        .module(Builtin::new("maintenance", "メンテナンスモード").commands(&["maintenance"]).core())
+        .module(Builtin::new("preferences", "ユーザーごとの表示設定").commands(&["preferences"]).core())
+        .module(Builtin::new("privacy", "メッセージを引用させない設定").commands(&["privacy"]).core())
+        .module(Builtin::new("metrics", "応答速度の表示").commands(&["ping"]).core())
+        .module(Builtin::new("retention", "記録データの定期削除").core().init(|_| crate::retention::start()))
+        .module(Builtin::new("web", "Webダッシュボード").core().init(crate::web::start))
+        .module(Builtin::new("status", "外部サービスの稼働状況").commands(&["status"]).init(crate::status::start))
+        .module(Builtin::new("welcome", "参加・退室メッセージとメンバー数のお祝い").commands(&["welcome", "leave-message", "milestonetest"])
+            .on_event(|c, e| Box::pin(crate::welcome::on_event(c, e))))
+        .module(Builtin::new("milestones", "お祝いの記録と間隔のグラフ").commands(&["milestones"])
+            .on_event(|c, e| Box::pin(crate::milestones::on_event(c, e))))
+        .module(Builtin::new("growth", "メンバー数の推移・予測と目標通知").commands(&["growth", "growth-backtest", "members-history"]).on_event(|c, e| Box::pin(crate::growth::on_event(c, e))))
+        .module(Builtin::new("reforecast", "成長予測の定期的な更新").init(crate::reforecast::start))
+        .module(Builtin::new("snapshots", "メンバー数の記録とインポート").commands(&["import-insights", "backfill-snapshots"])
+            .init(crate::snapshots::start))
+        .module(Builtin::new("compare", "サーバー間の比較").commands(&["compare-guilds"]))
+        .module(Builtin::new("modlog", "モデレーションログ").commands(&["modlog"])
+            .on_event(|c, e| Box::pin(crate::modlog::on_event(c, e))))
+        .module(Builtin::new("memberlog", "参加・退室の記録と日次サマリー").init(crate::memberlog::start))
+        .module(Builtin::new("logging", "メッセージの編集・削除ログ").commands(&["logsettings"]))
+        .module(Builtin::new("joingate", "アカウント作成日による参加制限").commands(&["joingate"])
+            .init(crate::joingate::start))
+        .module(Builtin::new("antiraid", "参加の急増 (レイド) の検知と対応").init(crate::antiraid::start))
+        .module(Builtin::new("onboarding", "新規メンバーへの案内DM").commands(&["onboarding"])
+            .init(crate::onboarding::start).on_event(|c, e| Box::pin(crate::onboarding::on_event(c, e))))
+        .module(Builtin::new("verify", "メンバー認証").commands(&["verify"]))
+        .module(Builtin::new("setup", "初期設定ウィザード").commands(&["setup"]))
+        .module(Builtin::new("rules", "ルールの掲示と同意").commands(&["rules"]))
+        .module(Builtin::new("automod", "自動モデレーション (招待リンク・スパム)").commands(&["automod"]))
+        .module(Builtin::new("linkfilter", "フィッシング・詐欺リンクの削除").commands(&["linkfilter"]).init(crate::linkfilter::start))
+        .module(Builtin::new("wordfilter", "禁止語句のフィルター").commands(&["filter"]))
+        .module(Builtin::new("moderation", "警告・キック・BAN・タイムアウトとケース").commands(&["warn", "kick", "ban", "timeout", "case"]))
+        .module(Builtin::new("diagnose", "Botの権限と設定の診断").commands(&["diagnose"]).core())
+        .module(Builtin::new("audit", "権限の監査").commands(&["audit"]))
+        .module(Builtin::new("roles", "ロールの一括付与").commands(&["role"]))
+        .module(Builtin::new("role-decay", "非アクティブなメンバーのロール解除").commands(&["role-decay"]).init(crate::role_decay::start))
+        .module(Builtin::new("prune", "非アクティブなメンバーの整理").commands(&["prune"]))
+        .module(Builtin::new("invites", "招待リンクの追跡").commands(&["invite"]))
+        .module(Builtin::new("emojilog", "絵文字の使用統計と変更ログ").commands(&["emojistats"]))
+        .module(Builtin::new("tempvoice", "一時ボイスチャンネル").commands(&["tempvoice"]))
+        .module(Builtin::new("zikosyokai", "自己紹介チャンネル").commands(&["intro", "intro-template", "intro-channel"]))
+        .module(Builtin::new("topic", "チャンネルトピックのローテーション").commands(&["topic"])
+            .init(crate::topic::start))
+        .module(Builtin::new("events", "サーバーイベントの通知").commands(&["event"])
+            .init(crate::events::start))
+        .module(Builtin::new("messagelink", "メッセージリンクの展開"))
+        .module(Builtin::new("crosspost", "チャンネル間の転送").commands(&["crosspost"]))
+        .module(Builtin::new("shortlink", "短縮リンク").commands(&["shortlink"]))
+        .module(Builtin::new("highlights", "スターボード").commands(&["highlights"]).init(crate::highlights::start))
+        .module(Builtin::new("digest", "ハイライトのダイジェスト").commands(&["digest"])
+            .init(crate::digest::start))
+        .module(Builtin::new("spotlight", "新規メンバーの初投稿への反応").commands(&["spotlight"]))
+        .module(Builtin::new("paste", "長いコードブロックのペースト化"))
+        .module(Builtin::new("ocr", "画像からのテキスト抽出").commands(&[crate::ocr::COMMAND_NAME]))
+        .module(Builtin::new("triage", "エラーの自動トリアージ").commands(&["triage"]))
+        .module(Builtin::new("helpdesk", "質問スレッドの解決と統計").commands(&["helpstats"]))
+        .module(Builtin::new("contributors", "貢献者の表彰").commands(&["contributor"]).init(crate::contributors::start))
+        .module(Builtin::new("kb", "ナレッジベース").commands(&["kb"]))
+        .module(Builtin::new("reactionroles", "ロールメニュー").commands(&["reactionrole"]))
+        .module(Builtin::new("poll", "投票").commands(&["poll"]).init(crate::poll::start))
+        .module(Builtin::new("reminders", "リマインダーと定期アナウンス").commands(&["remind", "schedule"]).init(crate::reminders::start))
+        .module(Builtin::new("prompts", "毎日のお題").commands(&["prompt"]).init(crate::prompts::start))
+        .module(crate::leveling::LevelingModule)
+        .module(Builtin::new("imagegen", "画像生成").commands(&["imagegen"]))
+        .module(Builtin::new("sandbox", "コードの実行").commands(&["sandbox"]))
+        .module(Builtin::new("avatar", "アイコンの表示").commands(&["avatar"]))
+});
+
+type DisabledCache = HashMap<u64, (Instant, Arc<HashSet<String>>)>;
+static DISABLED: Lazy<tokio::sync::Mutex<DisabledCache>> = Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
+static SUBSCRIBED: OnceCell<()> = OnceCell::new();
+
+fn find(name: &str) -> Option<&'static dyn Module> {
+    MODULES.modules.iter().find(|m| m.name() == name).map(|m| m.as_ref())
+}
+
+async fn disabled(guild_id: GuildId) -> Arc<HashSet<String>> {
+    if let Some((at, set)) = DISABLED.lock().await.get(&guild_id.0) {
+        if at.elapsed() < TTL { return set.clone(); }
+    }
+    let set = match db::get_disabled_modules(guild_id.0 as i64).await {
+        Ok(list) => Arc::new(list.into_iter().collect::<HashSet<_>>()),
+        Err(e) => {
+            log::warn!("modules: failed to load disabled modules for {}: {}", guild_id.0, e);
+            return Arc::new(HashSet::new());
+        }
+    };
+    DISABLED.lock().await.insert(guild_id.0, (Instant::now(), set.clone()));
+    set
+}
+
+/// Whether `module` runs in `guild_id`. Unknown names and core modules are always on.
+pub async fn is_enabled(guild_id: GuildId, module: &str) -> bool {
+    match find(module) {
+        Some(m) if m.can_disable() => !disabled(guild_id).await.contains(module),
+        _ => true,
+    }
+}
+
+/// The disabled module that answers `command` in `guild_id`, if any.
+pub async fn disabled_for_command(guild_id: GuildId, command: &str) -> Option<&'static str> {
+    let module = MODULES.modules.iter().find(|m| m.commands().contains(&command))?;
+    if is_enabled(guild_id, module.name()).await { None } else { Some(module.name()) }
+}
+
+/// Start every module, and on the first call give each subscribing module its own bus queue.
+pub fn init_all(ctx: &Context) {
+    for module in &MODULES.modules {
+        module.init(ctx.http.clone());
+    }
+    if SUBSCRIBED.set(()).is_err() { return; }
+    for module in MODULES.modules.iter().filter(|m| m.subscribes()) {
+        let module: &'static dyn Module = module.as_ref();
+        let ctx = ctx.clone();
+        let mut rx = bus::subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if !is_enabled(event.guild_id(), module.name()).await { continue; }
+                        if let Err(e) = module.on_event(&ctx, &event).await {
+                            log::warn!("modules: {} failed to handle an event: {}", module.name(), e);
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => log::warn!("modules: {} missed {} events", module.name(), n),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+pub async fn shutdown_all() {
+    for module in &MODULES.modules {
+        module.shutdown().await;
+    }
+}
+
+pub struct ModuleCommand;
+
+#[async_trait]
+impl Command for ModuleCommand {
+    fn name(&self) -> &'static str { "module" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("このサーバーで使う機能を切り替えます")
+            .create_option(|s| s.name("list").description("モジュールと有効・無効の状態を表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("enable").description("モジュールを有効にします").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("name").description("モジュール名 (/module list で確認できます)").kind(CommandOptionType::String).required(true))
+            })
+            .create_option(|s| {
+                s.name("disable").description("モジュールを無効にします").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("name").description("モジュール名 (/module list で確認できます)").kind(CommandOptionType::String).required(true))
+            })
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        if sub.name == "list" {
+            let off = disabled(guild_id).await;
+            let lines: Vec<String> = MODULES.modules.iter().map(|m| {
+                let state = if !m.can_disable() { "🔒" } else if off.contains(m.name()) { "⛔" } else { "✅" };
+                format!("{} `{}` {}", state, m.name(), m.description())
+            }).collect();
+            return inv.say(format!("✅ 有効 / ⛔ 無効 / 🔒 無効にできません\n{}", lines.join("\n"))).await;
+        }
+
+        let name = args.str("name").unwrap_or("").trim().to_lowercase();
+        let module = match find(&name) { Some(m) => m, None => return inv.say(format!("`{}` というモジュールはありません。`/module list` で確認できます。", name)).await };
+        if !module.can_disable() { return inv.say(format!("`{}` は無効にできません。", module.name())).await; }
+        let enable = sub.name == "enable";
+        let changed = db::set_module_enabled(guild_id.0 as i64, module.name(), enable, command.user.id.0 as i64, Utc::now().timestamp()).await?;
+        DISABLED.lock().await.remove(&guild_id.0);
+        match (enable, changed) {
+            (true, true) => inv.say(format!("`{}` を有効にしました。", module.name())).await,
+            (false, true) => inv.say(format!("`{}` を無効にしました。コマンドと自動の処理がこのサーバーで止まります。", module.name())).await,
+            (true, false) => inv.say(format!("`{}` はすでに有効です。", module.name())).await,
+            (false, false) => inv.say(format!("`{}` はすでに無効です。", module.name())).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, FakeDiscord};
+    use serde_json::json;
+    use serenity::model::id::UserId;
+
+    #[test]
+    fn disabled_modules_refuse_their_commands() {
+        testing::run(async {
+            let discord = FakeDiscord::start().await;
+            discord.accept_interactions().await;
+            let ctx = discord.context();
+            let guild_id = testing::guild_id();
+            let admin = UserId(testing::id());
+
+            let disable = testing::command("module", guild_id, admin, testing::ADMIN_PERMISSIONS, testing::subcommand("disable", &[("name", "leveling")]));
+            crate::commands::dispatch(&ctx, &disable).await;
+            assert!(!is_enabled(guild_id, "leveling").await);
+
+            let rank = testing::command("rank", guild_id, admin, "0", json!([]));
+            crate::commands::dispatch(&ctx, &rank).await;
+            assert_eq!(discord.replies().await, vec![
+                "`leveling` を無効にしました。コマンドと自動の処理がこのサーバーで止まります。".to_string(),
+                "このサーバーでは「leveling」モジュールが無効になっています。".to_string(),
+            ]);
+        })
+    }
+
+    #[test]
+    fn module_commands_are_all_in_the_registry() {
+        for module in &MODULES.modules {
+            for name in module.commands() {
+                assert!(crate::commands::is_in_registry(name), "/{} of {} is not registered", name, module.name());
+            }
+        }
+    }
+
+    #[test]
+    fn core_modules_stay_enabled() {
+        testing::run(async {
+            let guild_id = testing::guild_id();
+            db::set_module_enabled(guild_id.0 as i64, "settings", false, 1, 0).await.unwrap();
+            assert!(is_enabled(guild_id, "settings").await);
+            assert!(is_enabled(guild_id, "no-such-module").await);
+            assert_eq!(disabled_for_command(guild_id, "config").await, None);
+        })
+    }
+
+    #[test]
+    fn only_admins_change_modules() {
+        testing::run(async {
+            let discord = FakeDiscord::start().await;
+            discord.accept_interactions().await;
+            let guild_id = testing::guild_id();
+            let disable = testing::command("module", guild_id, UserId(testing::id()), "0", testing::subcommand("disable", &[("name", "leveling")]));
+            crate::commands::dispatch(&discord.context(), &disable).await;
+            assert_eq!(discord.replies().await, vec![permissions::DENIED_MESSAGE.to_string()]);
+            assert!(is_enabled(guild_id, "leveling").await);
+        })
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use reqwest::Client;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::{Attachment, Message};
+use serenity::prelude::*;
+use std::process::Stdio;
+use std::time::Duration;
+
+use crate::commands::{Command, Invocation};
+use crate::report::Report;
+
+pub const COMMAND_NAME: &str = "テキスト抽出";
+/// Larger images are almost never screenshots and take tesseract a long time.
+const MAX_IMAGE_BYTES: u64 = 8 * 1024 * 1024;
+const OCR_TIMEOUT_SECONDS: u64 = 30;
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+
+/// OCR_PROVIDER selects the engine: `tesseract` (default, local binary) or `http`, which posts
+/// the image to OCR_API_URL and expects `{"text": "..."}` back.
+enum Provider {
+    Tesseract { languages: String },
+    Http { url: String },
+}
+
+fn provider() -> Provider {
+    match std::env::var("OCR_PROVIDER").unwrap_or_default().to_lowercase().as_str() {
+        "http" => Provider::Http { url: std::env::var("OCR_API_URL").unwrap_or_default() },
+        _ => Provider::Tesseract { languages: std::env::var("OCR_LANGUAGES").unwrap_or_else(|_| "jpn+eng".to_string()) },
+    }
+}
+
+pub struct OcrCommand;
+
+#[async_trait]
+impl Command for OcrCommand {
+    fn name(&self) -> &'static str { COMMAND_NAME }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.kind(CommandType::Message)
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_ocr_command(inv.ctx, inv.command).await
+    }
+}
+
+fn is_image(a: &Attachment) -> bool {
+    if let Some(ct) = &a.content_type { return ct.starts_with("image/"); }
+    let ext = a.filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    IMAGE_EXTENSIONS.contains(&ext.as_str())
+}
+
+/// First image attached to `msg` that is small enough to OCR.
+pub fn first_image(msg: &Message) -> Option<&Attachment> {
+    msg.attachments.iter().find(|a| is_image(a) && a.size <= MAX_IMAGE_BYTES)
+}
+
+/// Extract text from an image with the configured provider. `guild_id` selects a vault key
+/// for the HTTP provider when one is registered.
+pub async fn extract_text(image: &[u8], guild_id: Option<i64>) -> Result<String> {
+    let text = match provider() {
+        Provider::Tesseract { languages } => {
+            let mut child = tokio::process::Command::new("tesseract")
+                .args(["stdin", "stdout", "-l", &languages])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|e| anyhow::anyhow!("tesseract is not available: {}", e))?;
+            let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("Failed to open stdin"))?;
+            tokio::io::AsyncWriteExt::write_all(&mut stdin, image).await?;
+            drop(stdin);
+            let output = tokio::time::timeout(Duration::from_secs(OCR_TIMEOUT_SECONDS), child.wait_with_output()).await
+                .map_err(|_| anyhow::anyhow!("tesseract timed out"))??;
+            if !output.status.success() { return Err(anyhow::anyhow!("tesseract exited with {}", output.status)); }
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Provider::Http { url } => {
+            if url.is_empty() { return Err(anyhow::anyhow!("OCR_API_URL is not set")); }
+            let client = Client::builder().timeout(Duration::from_secs(OCR_TIMEOUT_SECONDS)).build()?;
+            let mut req = client.post(&url).header("Content-Type", "application/octet-stream").body(image.to_vec());
+            if let Some(gid) = guild_id {
+                if let Ok(Some(key)) = crate::vault::get_api_key(gid, "ocr").await { req = req.bearer_auth(key); }
+            }
+            let resp = req.send().await?.error_for_status()?;
+            let json: serde_json::Value = resp.json().await?;
+            json.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string()
+        }
+    };
+    Ok(text.trim().to_string())
+}
+
+async fn handle_ocr_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let target = command.data.target_id.and_then(|t| command.data.resolved.messages.get(&t.to_message_id()));
+    let attachment = match target.and_then(first_image) {
+        Some(a) => a,
+        None => {
+            command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("画像が添付されたメッセージを選択してください (8MBまで)。").ephemeral(true))).await?;
+            return Ok(());
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+
+    let bytes = match attachment.download().await {
+        Ok(b) => b,
+        Err(e) => {
+            command.create_followup_message(&ctx.http, |m| m.content(format!("画像の取得に失敗しました: {}", e))).await?;
+            return Ok(());
+        }
+    };
+    let text = match extract_text(&bytes, command.guild_id.map(|g| g.0 as i64)).await {
+        Ok(t) => t,
+        Err(e) => {
+            log::warn!("ocr: extraction failed: {}", e);
+            command.create_followup_message(&ctx.http, |m| m.content("テキストの抽出に失敗しました。")).await?;
+            return Ok(());
+        }
+    };
+    if text.is_empty() {
+        command.create_followup_message(&ctx.http, |m| m.content("テキストが見つかりませんでした。")).await?;
+        return Ok(());
+    }
+    // Keep OCR output from closing the code block early.
+    let text = text.replace("```", "`\u{200b}``");
+    Report::text("ocr.txt")
+        .header(format!("{} の抽出結果:", attachment.filename))
+        .lines(text.lines().map(str::to_string))
+        .code_block()
+        .send(&ctx.http, command)
+        .await
+}
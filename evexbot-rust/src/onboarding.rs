@@ -0,0 +1,197 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::InputTextStyle;
+use serenity::model::application::interaction::modal::ModalSubmitInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::guild::Member;
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bus;
+use crate::commands::{Command, Invocation};
+use crate::components::modal_value;
+use crate::db;
+use crate::permissions;
+use crate::scheduler;
+
+const DRIP_INTERVAL_SECONDS: u64 = 300;
+const MAX_TEMPLATE_LENGTH: usize = 2000;
+
+/// One DM of the drip, sent `day` days after joining.
+struct Step {
+    step: i64,
+    day: i64,
+    label: &'static str,
+    default_template: &'static str,
+}
+
+/// `{user}` and `{server}` in templates are replaced with the member's name and the server name.
+const STEPS: &[Step] = &[
+    Step { step: 0, day: 0, label: "参加直後", default_template: "{user} さん、{server} へようこそ！\nまずは自己紹介チャンネルで自己紹介してみてください。" },
+    Step { step: 1, day: 2, label: "2日後", default_template: "{user} さん、{server} には慣れましたか？\nお困りのことがあれば、ヘルプチャンネルで気軽に質問してください。" },
+    Step { step: 2, day: 7, label: "7日後", default_template: "{user} さん、{server} に参加して1週間が経ちました。\nサーバーをより良くするため、ご意見・ご感想をお聞かせください。" },
+];
+
+fn step(n: i64) -> Option<&'static Step> {
+    STEPS.iter().find(|s| s.step == n)
+}
+
+/// Start the job that delivers due drip DMs.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("onboarding-drip", Duration::from_secs(DRIP_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { deliver_due(&http).await }
+    });
+}
+
+pub async fn on_event(_ctx: &Context, event: &bus::Event) -> Result<()> {
+    match event {
+        bus::Event::MemberJoined(member) => handle_member_join(member).await,
+        _ => Ok(()),
+    }
+}
+
+/// Queue the drip for a new member when the guild has it enabled.
+async fn handle_member_join(member: &Member) -> Result<()> {
+    if member.user.bot { return Ok(()); }
+    let guild_id = member.guild_id.0 as i64;
+    if !db::get_onboarding_enabled(guild_id).await? { return Ok(()); }
+    let now = Utc::now().timestamp();
+    for s in STEPS {
+        db::queue_onboarding_dm(guild_id, member.user.id.0 as i64, s.step, now + s.day * 86400).await?;
+    }
+    Ok(())
+}
+
+/// Members who leave don't get the rest of the drip.
+pub async fn handle_member_remove(guild_id: GuildId, user_id: UserId) -> Result<()> {
+    db::clear_onboarding_queue(guild_id.0 as i64, user_id.0 as i64).await
+}
+
+fn render(template: &str, user: &str, server: &str) -> String {
+    template.replace("{user}", user).replace("{server}", server)
+}
+
+async fn template(guild_id: i64, s: &Step) -> Result<String> {
+    Ok(db::get_onboarding_template(guild_id, s.step).await?.unwrap_or_else(|| s.default_template.to_string()))
+}
+
+async fn deliver_due(http: &Http) -> Result<()> {
+    for db::OnboardingDm { guild_id, user_id, step: n } in db::take_due_onboarding_dms(Utc::now().timestamp()).await? {
+        let s = match step(n) { Some(s) => s, None => continue };
+        if !db::get_user_prefs(user_id).await?.onboarding_dms { continue; }
+        let user = UserId(user_id as u64);
+        let server = GuildId(guild_id as u64).to_partial_guild(http).await.map(|g| g.name).unwrap_or_default();
+        let name = user.to_user(http).await.map(|u| u.name).unwrap_or_default();
+        let content = format!("{}\n-# `/preferences set onboarding_dms:False` でこのようなDMを停止できます。", render(&template(guild_id, s).await?, &name, &server));
+        let sent = match user.create_dm_channel(http).await {
+            Ok(dm) => dm.say(http, content).await.is_ok(),
+            Err(_) => false,
+        };
+        if !sent {
+            // Closed DMs stay closed; don't retry the later steps either.
+            log::info!("onboarding: could not DM {} in guild {}; dropping the rest of the drip", user_id, guild_id);
+            db::clear_onboarding_queue(guild_id, user_id).await?;
+        }
+    }
+    Ok(())
+}
+
+pub struct OnboardingCommand;
+
+#[async_trait]
+impl Command for OnboardingCommand {
+    fn name(&self) -> &'static str { "onboarding" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("新規メンバーへの案内DM (参加直後・2日後・7日後)")
+            .create_option(|s| {
+                s.name("enable").description("案内DMの有効・無効を切り替えます").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("enabled").description("有効にする").kind(CommandOptionType::Boolean).required(true))
+            })
+            .create_option(|s| step_option(s.name("template").description("案内DMの文面を編集します ({user} {server} が使えます)").kind(CommandOptionType::SubCommand)))
+            .create_option(|s| step_option(s.name("preview").description("案内DMの文面を確認します").kind(CommandOptionType::SubCommand)))
+    }
+
+    // The template editor is a modal, so nothing is deferred.
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let selected = sub.options.iter().find(|o| o.name=="step").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).and_then(step);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match (sub.name.as_str(), selected) {
+            ("enable", _) => {
+                let enabled = sub.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+                db::set_onboarding_enabled(guild_id, enabled).await?;
+                inv.say(if enabled { "新規メンバーへの案内DMを有効にしました。これから参加するメンバーに送信されます。" } else { "案内DMを無効にしました。" }).await
+            }
+            ("template", Some(s)) => {
+                let current = template(guild_id, s).await?;
+                command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::Modal).interaction_response_data(|d| {
+                    d.custom_id(format!("onboarding_template:{}", s.step)).title(format!("案内DM ({})", s.label)).components(|c| c.create_action_row(|row| {
+                        row.create_input_text(|t| t.custom_id("content").label("文面 (空欄で標準に戻す)").style(InputTextStyle::Paragraph).value(current).max_length(MAX_TEMPLATE_LENGTH as u64).required(false))
+                    }))
+                })).await?;
+                Ok(())
+            }
+            ("preview", Some(s)) => {
+                let server = command.guild_id.and_then(|g| g.name(&ctx.cache)).unwrap_or_default();
+                inv.say(format!("**{}** の案内DM:\n{}", s.label, render(&template(guild_id, s).await?, &command.user.name, &server))).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn step_option(s: &mut serenity::builder::CreateApplicationCommandOption) -> &mut serenity::builder::CreateApplicationCommandOption {
+    s.create_sub_option(|o| {
+        o.name("step").description("どの案内DMか").kind(CommandOptionType::Integer).required(true);
+        for st in STEPS { o.add_int_choice(st.label, st.step as i32); }
+        o
+    })
+}
+
+pub async fn handle_modal(ctx: &Context, modal: &ModalSubmitInteraction) -> Result<()> {
+    let guild_id = modal.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let s = modal.data.custom_id.strip_prefix("onboarding_template:").and_then(|n| n.parse().ok()).and_then(step).ok_or_else(|| anyhow::anyhow!("unknown step"))?;
+    let is_admin = match modal.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+    let content = modal_value(modal, "content").unwrap_or_default();
+    let content = content.trim();
+
+    let msg = if !is_admin {
+        permissions::DENIED_MESSAGE.to_string()
+    } else if content.is_empty() {
+        db::set_onboarding_template(guild_id, s.step, None).await?;
+        format!("{}の案内DMを標準の文面に戻しました。", s.label)
+    } else {
+        db::set_onboarding_template(guild_id, s.step, Some(content)).await?;
+        format!("{}の案内DMを更新しました。", s.label)
+    };
+    modal.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_fill_in_user_and_server() {
+        assert_eq!(render("{user} さん、{server} へようこそ！{user}", "alice", "Evex"), "alice さん、Evex へようこそ！alice");
+        assert_eq!(render("プレースホルダーなし", "alice", "Evex"), "プレースホルダーなし");
+    }
+
+    #[test]
+    fn every_default_template_names_the_user() {
+        for step in STEPS { assert!(render(step.default_template, "alice", "Evex").contains("alice"), "{}", step.label); }
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::id::UserId;
+use serenity::prelude::*;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::report::Report;
+
+const DEFAULT_ROW_LIMIT: i64 = 20;
+const MAX_ROW_LIMIT: i64 = 500;
+const MAX_CELL_WIDTH: usize = 40;
+
+/// Bot owners, from the comma-separated OWNER_IDS env var. Without it nobody can run the
+/// owner-only commands; there is no built-in owner, so a fork can't inherit someone else's.
+static OWNER_IDS: Lazy<Vec<u64>> = Lazy::new(|| {
+    std::env::var("OWNER_IDS").unwrap_or_default()
+        .split(',').filter_map(|s| s.trim().parse().ok()).collect()
+});
+
+pub fn owner_ids() -> &'static [u64] {
+    &OWNER_IDS
+}
+
+pub fn is_owner(user_id: UserId) -> bool {
+    owner_ids().contains(&user_id.0)
+}
+
+/// Only a single SELECT / WITH statement is accepted; the query itself also runs read-only (see `db::run_readonly_query`).
+fn validate_query(sql: &str) -> std::result::Result<String, &'static str> {
+    let sql = sql.trim().trim_end_matches(';').trim();
+    if sql.contains(';') { return Err("複数のステートメントは実行できません。"); }
+    let head = sql.split_whitespace().next().unwrap_or("").to_ascii_uppercase();
+    if head != "SELECT" && head != "WITH" { return Err("SELECT (またはWITH) 文のみ実行できます。"); }
+    Ok(sql.to_string())
+}
+
+fn truncate_cell(s: &str) -> String {
+    let s = s.replace('\n', " ");
+    if s.chars().count() > MAX_CELL_WIDTH { format!("{}…", s.chars().take(MAX_CELL_WIDTH - 1).collect::<String>()) } else { s }
+}
+
+fn format_table(columns: &[String], rows: &[Vec<String>]) -> String {
+    let cells: Vec<Vec<String>> = rows.iter().map(|r| r.iter().map(|c| truncate_cell(c)).collect()).collect();
+    let widths: Vec<usize> = columns.iter().enumerate()
+        .map(|(i, c)| cells.iter().map(|r| r[i].chars().count()).chain(std::iter::once(c.chars().count())).max().unwrap_or(0))
+        .collect();
+    let line = |values: &[String]| values.iter().enumerate().map(|(i, v)| format!("{:<w$}", v, w = widths[i])).collect::<Vec<_>>().join(" | ");
+    let mut out = vec![line(columns), widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-")];
+    out.extend(cells.iter().map(|r| line(r)));
+    out.join("\n")
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(',') || s.contains('"') || s.contains('\n') { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+fn format_csv(columns: &[String], rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    out.push_str(&columns.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","));
+    out.push('\n');
+    for row in rows {
+        out.push_str(&row.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+pub struct DbQueryCommand;
+
+#[async_trait]
+impl Command for DbQueryCommand {
+    fn name(&self) -> &'static str { "dbquery" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("オーナー用: データベースを読み取り専用で参照します")
+            .create_option(|o| o.name("sql").description("SELECT文").kind(CommandOptionType::String).required(true))
+            .create_option(|o| o.name("limit").description(format!("最大行数 (デフォルト{}, 最大{})", DEFAULT_ROW_LIMIT, MAX_ROW_LIMIT)).kind(CommandOptionType::Integer).required(false))
+            .create_option(|o| {
+                o.name("format").description("出力形式").kind(CommandOptionType::String).required(false)
+                    .add_string_choice("table", "table")
+                    .add_string_choice("csv", "csv")
+            })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_dbquery_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_dbquery_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    if !is_owner(command.user.id) { command.create_followup_message(&ctx.http, |m| m.content("権限がありません。" ).ephemeral(true)).await?; return Ok(()); }
+
+    let sql = command.data.options.iter().find(|o| o.name=="sql").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+    let limit = command.data.options.iter().find(|o| o.name=="limit").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(DEFAULT_ROW_LIMIT).clamp(1, MAX_ROW_LIMIT);
+    let format = command.data.options.iter().find(|o| o.name=="format").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("table");
+    let user_id = command.user.id.0 as i64;
+    let now = Utc::now().timestamp();
+    log::info!("dbquery: user {} ran: {}", user_id, sql);
+
+    let query = match validate_query(sql) {
+        Ok(q) => q,
+        Err(msg) => {
+            db::record_dbquery_audit(user_id, sql, None, Some(msg), now).await?;
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+            return Ok(());
+        }
+    };
+
+    let (columns, rows) = match db::run_readonly_query(&query, limit).await {
+        Ok(r) => r,
+        Err(e) => {
+            db::record_dbquery_audit(user_id, &query, None, Some(&e.to_string()), now).await?;
+            command.create_followup_message(&ctx.http, |m| m.content(format!("クエリの実行に失敗しました: {}", e)).ephemeral(true)).await?;
+            return Ok(());
+        }
+    };
+    db::record_dbquery_audit(user_id, &query, Some(rows.len() as i64), None, now).await?;
+
+    if rows.is_empty() {
+        command.create_followup_message(&ctx.http, |m| m.content("結果は0行でした。").ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let table = format_table(&columns, &rows);
+    let csv = format_csv(&columns, &rows);
+    Report::text("query.csv")
+        .header(format!("{}行", rows.len()))
+        .lines(table.lines().map(|l| l.to_string()))
+        .code_block()
+        .file("query.csv", csv.into_bytes())
+        .attachment_only(format == "csv")
+        .ephemeral(true)
+        .send(&ctx.http, command)
+        .await
+}
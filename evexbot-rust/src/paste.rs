@@ -0,0 +1,147 @@
+use anyhow::Result;
+use axum::extract::Path;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use regex::Regex;
+use reqwest::Client;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::Message;
+use serenity::model::id::MessageId;
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::prelude::*;
+use std::time::Duration;
+
+use crate::db;
+use crate::sandbox;
+use crate::web::{self, WebState};
+use crate::permissions;
+
+static CODE_BLOCK_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)```([\w+#-]*)\n(.*?)```").unwrap());
+const DEFAULT_MIN_LINES: usize = 30;
+
+/// Code blocks at least this many lines long get the paste offer (PASTE_MIN_LINES).
+fn min_lines() -> usize {
+    std::env::var("PASTE_MIN_LINES").ok().and_then(|v| v.parse().ok()).filter(|n| *n > 0).unwrap_or(DEFAULT_MIN_LINES)
+}
+
+/// (language hint, code) for every fenced block in `content`.
+fn code_blocks(content: &str) -> Vec<(String, String)> {
+    CODE_BLOCK_RE.captures_iter(content).map(|c| (c[1].to_lowercase(), c[2].trim_end().to_string())).collect()
+}
+
+/// Sandbox language for a code fence hint, if the sandbox can run it.
+fn sandbox_language(hint: &str) -> Option<&'static str> {
+    match hint {
+        "py" | "python" | "python3" => Some("python"),
+        "js" | "javascript" | "node" => Some("javascript"),
+        _ => None,
+    }
+}
+
+pub fn routes() -> Router<WebState> {
+    Router::new().route("/paste/:id", get(show))
+}
+
+async fn show(Path(id): Path<String>) -> Response {
+    match db::get_paste(&id).await {
+        Ok(Some(paste)) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; charset=utf-8")], paste.content).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "database error").into_response(),
+    }
+}
+
+pub async fn handle_message(ctx: &Context, msg: &Message) -> Result<()> {
+    if msg.author.bot || msg.guild_id.is_none() { return Ok(()); }
+    let longest = code_blocks(&msg.content).iter().map(|(_, code)| code.lines().count()).max().unwrap_or(0);
+    if longest < min_lines() { return Ok(()); }
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.content(format!("長いコードです ({}行)。ペーストサービスに保存すると読みやすくなります。", longest))
+            .reference_message(msg)
+            .allowed_mentions(|a| a.replied_user(false))
+            .components(|c| c.create_action_row(|ar| {
+                ar.create_button(|b| b.custom_id(format!("paste_save:{}:{}", msg.id.0, msg.author.id.0)).label("ペーストに保存").style(ButtonStyle::Primary))
+            }))
+    }).await?;
+    Ok(())
+}
+
+/// Upload to PASTE_SERVICE_URL when configured, otherwise serve it from our own HTTP server.
+/// The service receives the code as text/plain and answers with the URL (plain or `{"url": ...}`).
+async fn publish(id: &str, content: &str) -> Result<String> {
+    let service = std::env::var("PASTE_SERVICE_URL").unwrap_or_default();
+    if service.is_empty() { return Ok(format!("{}/paste/{}", web::public_base_url(), id)); }
+    let client = Client::builder().timeout(Duration::from_secs(15)).build()?;
+    let body = client.post(&service).header("Content-Type", "text/plain; charset=utf-8").body(content.to_string()).send().await?.error_for_status()?.text().await?;
+    let url = serde_json::from_str::<serde_json::Value>(&body).ok()
+        .and_then(|v| v.get("url").and_then(|u| u.as_str()).map(str::to_string))
+        .unwrap_or_else(|| body.trim().to_string());
+    if !url.starts_with("http") { return Err(anyhow::anyhow!("unexpected paste service response")); }
+    Ok(url)
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let reply = |msg: String| async move {
+        comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await
+    };
+    let parts: Vec<&str> = comp.data.custom_id.split(':').collect();
+    match parts.as_slice() {
+        ["paste_save", message_id, author_id] => {
+            let is_admin = match comp.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+            if comp.user.id.0.to_string() != *author_id && !is_admin {
+                reply("投稿者のみが保存できます。".to_string()).await?;
+                return Ok(());
+            }
+            let original = match comp.channel_id.message(&ctx.http, MessageId(message_id.parse()?)).await {
+                Ok(m) => m,
+                Err(_) => { reply("元のメッセージが見つかりません。".to_string()).await?; return Ok(()); }
+            };
+            let blocks = code_blocks(&original.content);
+            if blocks.is_empty() { reply("コードブロックが見つかりません。".to_string()).await?; return Ok(()); }
+            let language = blocks[0].0.clone();
+            let content = blocks.iter().map(|(_, c)| c.as_str()).collect::<Vec<_>>().join("\n\n");
+
+            let id: String = rand::thread_rng().sample_iter(&Alphanumeric).take(10).map(char::from).collect();
+            let guild_id = comp.guild_id.map(|g| g.0 as i64).unwrap_or(0);
+            db::create_paste(&id, guild_id, original.author.id.0 as i64, &language, &content, Utc::now().timestamp()).await?;
+            let url = match publish(&id, &content).await {
+                Ok(u) => u,
+                Err(e) => {
+                    log::warn!("paste: upload failed, serving locally: {}", e);
+                    format!("{}/paste/{}", web::public_base_url(), id)
+                }
+            };
+            let runnable = sandbox_language(&language).is_some();
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| {
+                d.content(format!("📋 {} のコード ({}行): {}", original.author.mention(), content.lines().count(), url))
+                    .components(|c| c.create_action_row(|ar| {
+                        ar.create_button(|b| b.label("開く").style(ButtonStyle::Link).url(&url));
+                        if runnable { ar.create_button(|b| b.custom_id(format!("paste_run:{}", id)).label("サンドボックスで実行").style(ButtonStyle::Secondary)); }
+                        ar
+                    }))
+            })).await?;
+        }
+        ["paste_run", id] => {
+            let db::Paste { language, content } = match db::get_paste(id).await? {
+                Some(p) => p,
+                None => { reply("ペーストが見つかりません。".to_string()).await?; return Ok(()); }
+            };
+            let language = match sandbox_language(&language) {
+                Some(l) => l,
+                None => { reply("この言語は実行できません。".to_string()).await?; return Ok(()); }
+            };
+            if let Err(e) = sandbox::validate_code(&content, language) { reply(e.to_string()).await?; return Ok(()); }
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+            let out = sandbox::run_code(language, &content).await;
+            comp.create_followup_message(&ctx.http, |m| m.content(format!("{} が実行しました。\n{}", comp.user.mention(), out))).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
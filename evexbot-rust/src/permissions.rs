@@ -0,0 +1,130 @@
+use anyhow::Result;
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::Message;
+use serenity::model::guild::Member;
+use serenity::model::id::{GuildId, RoleId};
+use serenity::model::Permissions;
+use serenity::prelude::*;
+
+use crate::db;
+
+pub const DENIED_MESSAGE: &str = "コマンドを使用するにはサーバーの管理権限が必要です。";
+
+/// Administrator or Manage Guild in the channel the interaction came from. Discord only
+/// fills `permissions` for interaction members.
+pub fn has_manage_guild(member: &Member) -> bool {
+    member.permissions.map(|p| p.administrator() || p.manage_guild()).unwrap_or(false)
+}
+
+/// Whether `member` may use admin commands: Manage Guild / Administrator, or one of the
+/// admin roles configured with `/config admin-roles`.
+pub async fn is_admin(member: &Member) -> bool {
+    has_manage_guild(member) || has_admin_role(member.guild_id, &member.roles).await
+}
+
+async fn has_admin_role(guild_id: GuildId, member_roles: &[RoleId]) -> bool {
+    let roles = match db::get_admin_roles(guild_id.0 as i64).await {
+        Ok(r) => r,
+        Err(e) => {
+            log::warn!("permissions: failed to load admin roles for {}: {}", guild_id.0, e);
+            return false;
+        }
+    };
+    member_roles.iter().any(|r| roles.contains(&(r.0 as i64)))
+}
+
+/// Whether the author of `message` is an admin, so message filters let it through unchecked.
+/// The gateway's partial member carries the author's roles but no permissions, so those are
+/// worked out from the cached guild's roles; this runs on every message and never calls the API.
+pub async fn is_exempt(ctx: &Context, guild_id: GuildId, message: &Message) -> bool {
+    let member = match message.member.as_ref() { Some(m) => m, None => return false };
+    let manages_guild = ctx.cache.guild_field(guild_id, |g| {
+        if g.owner_id == message.author.id { return true; }
+        let everyone = g.roles.get(&RoleId(guild_id.0)).map(|r| r.permissions).unwrap_or_else(Permissions::empty);
+        let p = member.roles.iter().filter_map(|r| g.roles.get(r)).fold(everyone, |p, r| p | r.permissions);
+        p.administrator() || p.manage_guild()
+    }).unwrap_or(false);
+    manages_guild || has_admin_role(guild_id, &member.roles).await
+}
+
+/// Adds the `admin-roles` subcommand group to `/config`.
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("admin-roles").description("管理コマンドを使えるロール").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| {
+            s.name("add").description("管理ロールを追加します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("role").description("ロール").kind(CommandOptionType::Role).required(true))
+        })
+        .create_sub_option(|s| {
+            s.name("remove").description("管理ロールを外します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("role").description("ロール").kind(CommandOptionType::Role).required(true))
+        })
+        .create_sub_option(|s| s.name("list").description("管理ロールを表示します").kind(CommandOptionType::SubCommand))
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let role = sub.options.iter().find(|o| o.name=="role").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Role(r) => Some(r.id.0 as i64), _ => None });
+
+    // Admin roles can't grant themselves more admin roles.
+    let msg = if !has_manage_guild(member) {
+        "管理ロールの設定には「サーバー管理」権限が必要です。".to_string()
+    } else {
+        match (sub.name.as_str(), role) {
+            ("add", Some(r)) => {
+                db::add_admin_role(guild_id, r).await?;
+                format!("<@&{}> を管理ロールに追加しました。", r)
+            }
+            ("remove", Some(r)) => {
+                if db::remove_admin_role(guild_id, r).await? { format!("<@&{}> を管理ロールから外しました。", r) } else { "そのロールは管理ロールではありません。".to_string() }
+            }
+            ("list", _) => {
+                let roles = db::get_admin_roles(guild_id).await?;
+                if roles.is_empty() { "管理ロールはありません (「サーバー管理」権限を持つメンバーのみ使用できます)。".to_string() } else {
+                    format!("管理ロール: {}", roles.iter().map(|r| format!("<@&{}>", r)).collect::<Vec<_>>().join(" "))
+                }
+            }
+            _ => return Ok(()),
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, FakeDiscord};
+    use serenity::model::id::{ChannelId, UserId};
+
+    fn message_with_roles(guild_id: GuildId, roles: &[u64]) -> Message {
+        let mut json = testing::message_json(ChannelId(testing::id()), UserId(testing::id()), "hello");
+        json["guild_id"] = serde_json::json!(guild_id.0.to_string());
+        json["member"] = serde_json::json!({
+            "roles": roles.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+            "joined_at": "2024-01-01T00:00:00+00:00",
+            "deaf": false,
+            "mute": false,
+        });
+        serde_json::from_value(json).expect("message fixture")
+    }
+
+    #[test]
+    fn admin_roles_exempt_messages_without_api_calls() {
+        testing::run(async {
+            let discord = FakeDiscord::start().await;
+            let ctx = discord.context();
+            let guild_id = testing::guild_id();
+            let role = testing::id();
+            db::add_admin_role(guild_id.0 as i64, role as i64).await.unwrap();
+
+            assert!(is_exempt(&ctx, guild_id, &message_with_roles(guild_id, &[role])).await);
+            assert!(!is_exempt(&ctx, guild_id, &message_with_roles(guild_id, &[testing::id()])).await);
+            assert!(discord.requests("GET", "").await.is_empty());
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serenity::model::channel::Message;
+use serenity::prelude::*;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::{activity, automod, contributors, emojilog, helpdesk, leveling, linkfilter, logging, messagelink, modules, paste, spotlight, triage, wordfilter, zikosyokai};
+
+/// What the pipeline does after a stage has seen a message.
+pub enum Flow {
+    Continue,
+    /// Skip every later stage, e.g. because the message was removed.
+    Stop,
+}
+
+type StageFuture<'a> = Pin<Box<dyn Future<Output = Result<Flow>> + Send + 'a>>;
+type StageFn = for<'a> fn(&'a Context, &'a Message) -> StageFuture<'a>;
+
+struct Stage {
+    name: &'static str,
+    priority: i32,
+    run: StageFn,
+}
+
+struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    fn new() -> Self { Pipeline { stages: Vec::new() } }
+
+    /// Add a stage. Lower priorities run first; equal priorities run in registration order.
+    fn stage(mut self, name: &'static str, priority: i32, run: StageFn) -> Self {
+        self.stages.push(Stage { name, priority, run });
+        self.stages.sort_by_key(|s| s.priority);
+        self
+    }
+}
+
+/// For handlers that never stop the pipeline.
+async fn continues(handler: impl Future<Output = Result<()>> + Send) -> Result<Flow> {
+    handler.await.map(|_| Flow::Continue)
+}
+
+/// Every stage a guild or DM message passes through. New stages go here and nowhere else.
+static PIPELINE: Lazy<Pipeline> = Lazy::new(|| {
+    Pipeline::new()
+        // automod runs first; removed messages are not processed further
+        .stage("automod", 0, |c, m| Box::pin(async move {
+            Ok(if automod::handle_message(c, m).await? { Flow::Stop } else { Flow::Continue })
+        }))
+        // phishing and Discord-lookalike links
+        .stage("linkfilter", 10, |c, m| Box::pin(async move {
+            Ok(if linkfilter::handle_message(c, m).await? { Flow::Stop } else { Flow::Continue })
+        }))
+        // per-guild keyword and regex rules from /filter
+        .stage("wordfilter", 20, |c, m| Box::pin(async move {
+            Ok(if wordfilter::handle_message(c, m).await? { Flow::Stop } else { Flow::Continue })
+        }))
+        // remember content for edit/delete logs
+        .stage("logging", 50, |_, m| Box::pin(continues(logging::handle_message(m))))
+        .stage("messagelink", 100, |c, m| Box::pin(continues(messagelink::handle_message(c, m))))
+        // channel template maintenance
+        .stage("zikosyokai", 110, |c, m| Box::pin(continues(zikosyokai::handle_message(c, m))))
+        // custom emoji usage counting for /emojistats
+        .stage("emojilog", 120, |_, m| Box::pin(continues(emojilog::handle_message(m))))
+        // thank newcomers for their first post; must see activity before it is recorded
+        .stage("spotlight", 130, |c, m| Box::pin(continues(spotlight::handle_message(&c.http, m))))
+        .stage("activity", 140, |_, m| Box::pin(continues(activity::handle_message(m))))
+        // XP and level-up announcements for /rank
+        .stage("leveling", 150, |c, m| Box::pin(continues(leveling::handle_message(c, m))))
+        // offer to move long code blocks to a paste
+        .stage("paste", 200, |c, m| Box::pin(continues(paste::handle_message(c, m))))
+        // first-answer times for /helpstats
+        .stage("helpdesk", 210, |_, m| Box::pin(continues(helpdesk::handle_message(m))))
+        // count help answers and intro replies towards /contributor
+        .stage("contributors", 220, |c, m| Box::pin(continues(contributors::handle_message(c, m))))
+        // error triage may OCR a screenshot, so it runs after the cheap handlers
+        .stage("triage", 900, |c, m| Box::pin(continues(triage::handle_message(c, m))))
+});
+
+/// Run `msg` through the stages in priority order. A failing stage is logged and the
+/// rest still run; only `Flow::Stop` ends the chain early. Stages are named after their
+/// module and are skipped in guilds where it is disabled.
+pub async fn handle_message(ctx: &Context, msg: &Message) {
+    for stage in &PIPELINE.stages {
+        if let Some(guild_id) = msg.guild_id {
+            if !modules::is_enabled(guild_id, stage.name).await { continue; }
+        }
+        match (stage.run)(ctx, msg).await {
+            Ok(Flow::Continue) => {}
+            Ok(Flow::Stop) => return,
+            Err(e) => log::debug!("pipeline: stage {} failed on message {}: {}", stage.name, msg.id.0, e),
+        }
+    }
+}
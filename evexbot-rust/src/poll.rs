@@ -0,0 +1,219 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::chart::{self, ChartSize, Rendered};
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::members_history;
+use crate::permissions;
+use crate::scheduler;
+
+pub const VOTE_PREFIX: &str = "poll_vote:";
+const MAX_OPTIONS: usize = 10;
+const BUTTONS_PER_ROW: usize = 5;
+const MAX_OPTION_LENGTH: u16 = 80;
+const MAX_QUESTION_LENGTH: u16 = 200;
+/// Leaves room for the number prefix within Discord's 80-character button labels.
+const MAX_BUTTON_LABEL_LENGTH: usize = 75;
+const DEFAULT_HOURS: i64 = 24;
+const MAX_HOURS: i64 = 24 * 14;
+const CHECK_INTERVAL_SECONDS: u64 = 60;
+
+/// Start the job that closes polls once their time is up.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("polls", Duration::from_secs(CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move {
+            for poll in db::get_due_polls(Utc::now().timestamp()).await? {
+                if let Err(e) = close(&http, &poll).await {
+                    log::warn!("poll: failed to close poll {} in guild {}: {}", poll.id, poll.guild_id, e);
+                }
+            }
+            Ok(())
+        }
+    });
+}
+
+fn poll_embed(poll: &db::Poll) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("📊 {}", poll.question));
+    let options: Vec<String> = poll.option_labels().iter().enumerate().map(|(i, l)| format!("**{}.** {}", i + 1, l)).collect();
+    let status = if poll.closed { "締め切りました。".to_string() } else { format!("締切: <t:{}:f> (<t:{}:R>)", poll.closes_at, poll.closes_at) };
+    embed.description(format!("{}\n\n{}", options.join("\n"), status));
+    embed.color(serenity::utils::Colour::BLURPLE);
+    let footer = if poll.closed { format!("投票 #{}", poll.id) } else { format!("投票 #{} | ボタンで投票 (押し直すと変更できます)", poll.id) };
+    embed.footer(|f| f.text(footer));
+    embed
+}
+
+fn results_embed(poll: &db::Poll, counts: &[i64], chart: &Rendered) -> CreateEmbed {
+    let total: i64 = counts.iter().sum();
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("📊 {} — {}", poll.question, if poll.closed { "最終結果" } else { "途中結果" }));
+    let lines: Vec<String> = poll.option_labels().iter().zip(counts).enumerate().map(|(i, (label, count))| {
+        let pct = if total > 0 { *count as f64 * 100.0 / total as f64 } else { 0.0 };
+        format!("**{}.** {} — {}票 ({:.0}%)", i + 1, label, count, pct)
+    }).collect();
+    embed.description(format!("{}\n\n合計 {}票", lines.join("\n"), total));
+    embed.image(chart.url());
+    embed.color(serenity::utils::Colour::BLURPLE);
+    embed.footer(|f| f.text(format!("投票 #{}", poll.id)));
+    embed
+}
+
+async fn results_chart(poll: &db::Poll, counts: &[i64], size: ChartSize) -> Result<Rendered> {
+    let png = members_history::create_bar_chart(&format!("Poll #{} results", poll.id), counts, size)?;
+    Ok(chart::finish(Some(poll.guild_id), png, "poll_results").await)
+}
+
+/// Close `poll`, drop its buttons and post the final chart. Does nothing if it was already closed.
+async fn close(http: &Http, poll: &db::Poll) -> Result<()> {
+    if !db::close_poll(poll.id).await? { return Ok(()); }
+    let poll = db::Poll { closed: true, ..poll.clone() };
+    let channel = ChannelId(poll.channel_id as u64);
+    if let Some(message_id) = poll.message_id {
+        if let Ok(mut message) = channel.message(http, message_id as u64).await {
+            message.edit(http, |m| m.set_embed(poll_embed(&poll)).components(|c| c)).await?;
+        }
+    }
+    let counts = db::get_poll_counts(poll.id, poll.option_labels().len()).await?;
+    let size = chart::resolve(Some(poll.guild_id), None, chart::STANDARD).await.unwrap_or(chart::STANDARD);
+    let png = results_chart(&poll, &counts, size).await?;
+    let embed = results_embed(&poll, &counts, &png);
+    channel.send_message(http, |m| {
+        m.add_file((png.bytes.as_slice(), png.filename().as_str())).embed(|e| { *e = embed; e });
+        if let Some(id) = poll.message_id { m.reference_message((channel, serenity::model::id::MessageId(id as u64))); }
+        m
+    }).await?;
+    Ok(())
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let mut parts = comp.data.custom_id.trim_start_matches(VOTE_PREFIX).split(':');
+    let poll_id: i64 = parts.next().unwrap_or("").parse()?;
+    let index: usize = parts.next().unwrap_or("").parse()?;
+    let poll = match db::get_poll(None, poll_id).await? { Some(p) => p, None => return reply(ctx, comp, "この投票は見つかりません。").await };
+    if poll.closed || poll.closes_at <= Utc::now().timestamp() { return reply(ctx, comp, "この投票は締め切られています。").await; }
+    let labels = poll.option_labels();
+    let label = match labels.get(index) { Some(l) => *l, None => return Ok(()) };
+
+    let msg = match db::cast_poll_vote(poll.id, comp.user.id.0 as i64, index as i64, Utc::now().timestamp()).await? {
+        Some(prev) if prev as usize == index => format!("すでに「{}」に投票済みです。", label),
+        Some(prev) => format!("投票を「{}」から「{}」に変更しました。", labels.get(prev as usize).copied().unwrap_or("?"), label),
+        None => format!("「{}」に投票しました。", label),
+    };
+    reply(ctx, comp, &msg).await
+}
+
+async fn reply(ctx: &Context, comp: &MessageComponentInteraction, msg: &str) -> Result<()> {
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+pub struct PollCommand;
+
+#[async_trait]
+impl Command for PollCommand {
+    fn name(&self) -> &'static str { "poll" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("ボタンで投票できるアンケートを作成します")
+            .create_option(|s| {
+                s.name("create").description("投票を作成します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("question").description("質問").kind(CommandOptionType::String).max_length(MAX_QUESTION_LENGTH).required(true))
+                    .create_sub_option(|o| o.name("option1").description("選択肢").kind(CommandOptionType::String).max_length(MAX_OPTION_LENGTH).required(true))
+                    .create_sub_option(|o| o.name("option2").description("選択肢").kind(CommandOptionType::String).max_length(MAX_OPTION_LENGTH).required(true));
+                for i in 3..=MAX_OPTIONS {
+                    s.create_sub_option(|o| o.name(format!("option{}", i)).description("選択肢").kind(CommandOptionType::String).max_length(MAX_OPTION_LENGTH).required(false));
+                }
+                s.create_sub_option(|o| o.name("hours").description(format!("締め切りまでの時間 (デフォルト{}時間)", DEFAULT_HOURS)).kind(CommandOptionType::Integer).min_int_value(1).max_int_value(MAX_HOURS).required(false))
+            })
+            .create_option(|s| {
+                s.name("results").description("結果をグラフで表示します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("投票の番号").kind(CommandOptionType::Integer).min_int_value(1).required(true))
+                    .create_sub_option(|o| chart::size_option(o))
+            })
+            .create_option(|s| {
+                s.name("close").description("投票を締め切ります").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("投票の番号").kind(CommandOptionType::Integer).min_int_value(1).required(true))
+            })
+    }
+
+    // The poll itself is the command's reply, so it is always public.
+    fn defer(&self) -> Defer { Defer::Public }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        match sub.name.as_str() {
+            "create" => {
+                let question = args.str("question").unwrap_or("").trim();
+                let mut options: Vec<String> = Vec::new();
+                for i in 1..=MAX_OPTIONS {
+                    if let Some(o) = args.str(&format!("option{}", i)).map(str::trim).filter(|o| !o.is_empty()) {
+                        if !options.iter().any(|e| e == o) { options.push(o.to_string()); }
+                    }
+                }
+                if options.len() < 2 { return inv.say("異なる選択肢を2つ以上指定してください。").await; }
+                let hours = args.int("hours").unwrap_or(DEFAULT_HOURS).clamp(1, MAX_HOURS);
+                let now = Utc::now().timestamp();
+                let id = db::create_poll(gid, command.channel_id.0 as i64, command.user.id.0 as i64, question, &options, now, now + hours * 3600).await?;
+                let poll = db::get_poll(Some(gid), id).await?.ok_or_else(|| anyhow::anyhow!("poll {} vanished", id))?;
+
+                let embed = poll_embed(&poll);
+                let message = command.create_followup_message(&ctx.http, |m| {
+                    m.embed(|e| { *e = embed; e }).components(|c| {
+                        for (row, labels) in options.chunks(BUTTONS_PER_ROW).enumerate() {
+                            c.create_action_row(|ar| {
+                                for (j, label) in labels.iter().enumerate() {
+                                    let index = row * BUTTONS_PER_ROW + j;
+                                    ar.create_button(|b| b.custom_id(format!("{}{}:{}", VOTE_PREFIX, id, index)).label(format!("{}. {}", index + 1, label.chars().take(MAX_BUTTON_LABEL_LENGTH).collect::<String>())).style(ButtonStyle::Secondary));
+                                }
+                                ar
+                            });
+                        }
+                        c
+                    })
+                }).await?;
+                db::set_poll_message(id, message.id.0 as i64).await
+            }
+            "results" => {
+                let id = args.int("id").unwrap_or(0);
+                let poll = match db::get_poll(Some(gid), id).await? { Some(p) => p, None => return inv.say(format!("投票 #{} はありません。", id)).await };
+                let size = match chart::resolve(Some(gid), args.str("size"), chart::STANDARD).await {
+                    Ok(s) => s,
+                    Err(e) => return inv.say(e).await,
+                };
+                let counts = db::get_poll_counts(poll.id, poll.option_labels().len()).await?;
+                let png = results_chart(&poll, &counts, size).await?;
+                let embed = results_embed(&poll, &counts, &png);
+                command.create_followup_message(&ctx.http, |m| m.add_file((png.bytes.as_slice(), png.filename().as_str())).embed(|e| { *e = embed; e })).await?;
+                Ok(())
+            }
+            "close" => {
+                let id = args.int("id").unwrap_or(0);
+                let poll = match db::get_poll(Some(gid), id).await? { Some(p) => p, None => return inv.say(format!("投票 #{} はありません。", id)).await };
+                if poll.creator_id != command.user.id.0 as i64 && !permissions::is_admin(member).await { return inv.say("投票を作成したユーザーまたは管理者のみ締め切れます。").await; }
+                if poll.closed { return inv.say(format!("投票 #{} はすでに締め切られています。", id)).await; }
+                close(&ctx.http, &poll).await?;
+                inv.say(format!("投票 #{} を締め切りました。", id)).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
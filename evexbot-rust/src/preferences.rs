@@ -0,0 +1,83 @@
+use anyhow::Result;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::prelude::*;
+
+use crate::commands::{Command, Invocation};
+use crate::db::{self, UserPrefs};
+
+/// Whether the user asked for command results only they can see.
+pub async fn wants_ephemeral(user_id: i64) -> bool {
+    db::get_user_prefs(user_id).await.map(|p| p.ephemeral).unwrap_or(false)
+}
+
+/// Whether personal notifications should go to the user's DMs instead of a channel mention.
+pub async fn wants_dm_reminders(user_id: i64) -> bool {
+    db::get_user_prefs(user_id).await.map(|p| p.dm_reminders).unwrap_or(false)
+}
+
+pub struct PreferencesCommand;
+
+#[async_trait]
+impl Command for PreferencesCommand {
+    fn name(&self) -> &'static str { "preferences" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("あなた個人の設定")
+            .create_option(|s| s.name("show").description("現在の設定を表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("set").description("設定を変更します (指定した項目のみ)").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("language").description("グラフなどの表示言語").kind(CommandOptionType::String).required(false)
+                        .add_string_choice("サーバーの設定に従う", "server").add_string_choice("日本語", "ja").add_string_choice("English", "en"))
+                    .create_sub_option(|o| o.name("dm_reminders").description("個人向けの通知をDMで受け取る").kind(CommandOptionType::Boolean).required(false))
+                    .create_sub_option(|o| o.name("quote_privacy").description("リンク展開やブックマークで引用させない").kind(CommandOptionType::Boolean).required(false))
+                    .create_sub_option(|o| o.name("ephemeral").description("コマンドの結果を自分だけに表示する").kind(CommandOptionType::Boolean).required(false))
+                    .create_sub_option(|o| o.name("onboarding_dms").description("サーバー参加後の案内DMを受け取る").kind(CommandOptionType::Boolean).required(false))
+            })
+            .create_option(|s| s.name("reset").description("すべての設定を初期値に戻します").kind(CommandOptionType::SubCommand))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_preferences_command(inv.ctx, inv.command).await
+    }
+}
+
+fn on_off(v: bool) -> &'static str { if v { "オン" } else { "オフ" } }
+
+fn describe(p: &UserPrefs) -> String {
+    let language = match p.language.as_deref() { Some("en") => "English", Some("ja") => "日本語", _ => "サーバーの設定に従う" };
+    format!("表示言語: {}\nDM通知: {}\n引用の拒否: {}\n結果を自分だけに表示: {}\n参加後の案内DM: {}", language, on_off(p.dm_reminders), on_off(p.quote_privacy), on_off(p.ephemeral), on_off(p.onboarding_dms))
+}
+
+async fn handle_preferences_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let user_id = command.user.id.0 as i64;
+    let msg = match sub.name.as_str() {
+        "show" => describe(&db::get_user_prefs(user_id).await?),
+        "set" => {
+            let mut prefs = db::get_user_prefs(user_id).await?;
+            for opt in &sub.options {
+                match (opt.name.as_str(), opt.value.as_ref()) {
+                    ("language", Some(v)) => prefs.language = v.as_str().filter(|s| *s != "server").map(str::to_string),
+                    ("dm_reminders", Some(v)) => prefs.dm_reminders = v.as_bool().unwrap_or(prefs.dm_reminders),
+                    ("quote_privacy", Some(v)) => prefs.quote_privacy = v.as_bool().unwrap_or(prefs.quote_privacy),
+                    ("ephemeral", Some(v)) => prefs.ephemeral = v.as_bool().unwrap_or(prefs.ephemeral),
+                    ("onboarding_dms", Some(v)) => prefs.onboarding_dms = v.as_bool().unwrap_or(prefs.onboarding_dms),
+                    _ => {}
+                }
+            }
+            db::update_user_prefs(&prefs).await?;
+            format!("設定を更新しました。\n{}", describe(&prefs))
+        }
+        "reset" => {
+            db::delete_user_prefs(user_id).await?;
+            format!("設定を初期値に戻しました。\n{}", describe(&UserPrefs::default_for(user_id)))
+        }
+        _ => return Ok(()),
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
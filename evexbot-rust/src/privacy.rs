@@ -0,0 +1,35 @@
+use anyhow::Result;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::prelude::*;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+
+pub struct PrivacyCommand;
+
+#[async_trait]
+impl Command for PrivacyCommand {
+    fn name(&self) -> &'static str { "privacy" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("あなたのメッセージをリンク展開やブックマークで引用させない設定").create_option(|o| {
+            o.name("enabled").description("有効にすると引用されなくなります").kind(CommandOptionType::Boolean).required(true)
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_privacy_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_privacy_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let enabled = command.data.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+    db::update_privacy_enabled(command.user.id.0 as i64, enabled).await?;
+    // Same setting as `/preferences set quote_privacy`.
+    let msg = if enabled { "プライバシーモードを有効にしました。あなたのメッセージはリンク展開やブックマークで引用されません。" } else { "プライバシーモードを無効にしました!" };
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDate, TimeZone, Timelike, Utc};
+use reqwest::Client;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::{ChannelType, Message};
+use serenity::model::id::{ChannelId, MessageId};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::events::jst;
+use crate::permissions;
+use crate::scheduler;
+use crate::thread_title;
+
+const CHECK_INTERVAL_SECONDS: u64 = 600;
+const DEFAULT_POST_HOUR: i64 = 9;
+const MAX_PROMPT_LENGTH: u16 = 500;
+const MAX_PROMPTS_PER_GUILD: usize = 100;
+/// Submission threads stay open for a week so they can still be judged.
+const THREAD_ARCHIVE_MINUTES: u16 = 10080;
+/// Messages read per submission thread when judging; fetched 100 at a time.
+const MAX_MESSAGES_PER_THREAD: usize = 500;
+const PROVIDER_TIMEOUT_SECONDS: u64 = 10;
+
+/// PROMPT_PROVIDER selects where prompts come from: `list` (default, the guild's `/prompt add`
+/// list, least recently used first) or `http`, which GETs PROMPT_API_URL and expects
+/// `{"prompt": "..."}` back. The list is used whenever the provider fails.
+enum Provider {
+    List,
+    Http { url: String },
+}
+
+fn provider() -> Provider {
+    match std::env::var("PROMPT_PROVIDER").unwrap_or_default().to_lowercase().as_str() {
+        "http" => Provider::Http { url: std::env::var("PROMPT_API_URL").unwrap_or_default() },
+        _ => Provider::List,
+    }
+}
+
+async fn fetch_provided(guild_id: i64) -> Result<Option<String>> {
+    let url = match provider() { Provider::List => return Ok(None), Provider::Http { url } => url };
+    if url.is_empty() { return Err(anyhow::anyhow!("PROMPT_API_URL is not set")); }
+    let client = Client::builder().timeout(Duration::from_secs(PROVIDER_TIMEOUT_SECONDS)).build()?;
+    let mut req = client.get(&url).query(&[("guild_id", guild_id.to_string())]);
+    if let Ok(Some(key)) = crate::vault::get_api_key(guild_id, "prompt").await { req = req.bearer_auth(key); }
+    let json: serde_json::Value = req.send().await?.error_for_status()?.json().await?;
+    let text = json.get("prompt").and_then(|v| v.as_str()).unwrap_or("").trim().to_string();
+    Ok(if text.is_empty() { None } else { Some(text.chars().take(MAX_PROMPT_LENGTH as usize).collect()) })
+}
+
+/// Today's prompt: the provider's when it answers, else the least recently used one from the list.
+async fn pick_prompt(guild_id: i64) -> Result<Option<String>> {
+    match fetch_provided(guild_id).await {
+        Ok(Some(text)) => return Ok(Some(text)),
+        Ok(None) => {}
+        Err(e) => log::warn!("prompts: provider failed for guild {}, using the list: {}", guild_id, e),
+    }
+    match db::next_prompt(guild_id).await? {
+        Some(p) => {
+            db::mark_prompt_used(p.id, Utc::now().timestamp()).await?;
+            Ok(Some(p.text))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Start the prompt job. Each guild gets one prompt per JST day at its post hour, and on the
+/// first run of each week the previous week's submissions are judged.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("prompt-of-the-day", Duration::from_secs(CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { run_due(&http).await }
+    });
+}
+
+async fn run_due(http: &Http) -> Result<()> {
+    let now = Utc::now().with_timezone(&jst());
+    let today = now.date_naive();
+    let monday = today - ChronoDuration::days(today.weekday().num_days_from_monday() as i64);
+    for settings in db::get_all_prompt_settings().await? {
+        let gid = settings.guild_id;
+        // A week is judged once its Monday's post hour has passed, or on any later day if the bot was down.
+        let judge_due = today > monday || now.hour() as i64 >= settings.post_hour;
+        if judge_due && settings.last_winner_week.as_deref() != Some(monday.to_string().as_str()) {
+            if let Err(e) = announce_winner(http, &settings, monday).await {
+                log::warn!("prompts: failed to judge week before {} in guild {}: {}", monday, gid, e);
+            }
+            db::mark_prompt_winner_week(gid, &monday.to_string()).await?;
+        }
+        if now.hour() as i64 >= settings.post_hour && settings.last_posted.as_deref() != Some(today.to_string().as_str()) {
+            match post_prompt(http, &settings, today).await {
+                Ok(true) => {}
+                Ok(false) => log::debug!("prompts: no prompt available for guild {}", gid),
+                Err(e) => log::warn!("prompts: failed to post in guild {}: {}", gid, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Post today's prompt and open its submission thread. False when there was nothing to post.
+async fn post_prompt(http: &Http, settings: &db::PromptSettings, today: NaiveDate) -> Result<bool> {
+    let text = match pick_prompt(settings.guild_id).await? { Some(t) => t, None => return Ok(false) };
+    let channel = ChannelId(settings.channel_id as u64);
+    let message = channel.send_message(http, |m| m.embed(|e| {
+        e.title(format!("🎨 今日のお題 ({})", today.format("%m/%d")))
+            .description(&text)
+            .color(serenity::utils::Colour::from_rgb(0xF4, 0x9E, 0x42))
+            .footer(|f| f.text("スレッドに作品を投稿してください。一番リアクションを集めた作品を週の初めに発表します"))
+    })).await?;
+    let name = thread_title::with_prefix(&format!("{} ", today.format("%m/%d")), &text);
+    let thread = channel.create_public_thread(http, message.id, |t| t.name(name).auto_archive_duration(THREAD_ARCHIVE_MINUTES)).await?;
+    db::add_prompt_post(&db::PromptPost {
+        message_id: message.id.0 as i64,
+        guild_id: settings.guild_id,
+        channel_id: settings.channel_id,
+        thread_id: thread.id.0 as i64,
+        prompt: text,
+        posted_at: Utc::now().timestamp(),
+    }).await?;
+    // Marked only after the post, so a failed send is retried on the next check.
+    db::mark_prompt_posted(settings.guild_id, &today.to_string()).await?;
+    Ok(true)
+}
+
+/// Reactions from others on a submission; the bot's own reaction doesn't count.
+fn score(message: &Message) -> u64 {
+    message.reactions.iter().map(|r| r.count - r.me as u64).sum()
+}
+
+async fn thread_messages(http: &Http, thread: ChannelId) -> Result<Vec<Message>> {
+    let mut out: Vec<Message> = Vec::new();
+    let mut before: Option<MessageId> = None;
+    while out.len() < MAX_MESSAGES_PER_THREAD {
+        let page = thread.messages(http, |b| { b.limit(100); if let Some(id) = before { b.before(id); } b }).await?;
+        if page.is_empty() { break; }
+        before = page.last().map(|m| m.id);
+        let done = page.len() < 100;
+        out.extend(page);
+        if done { break; }
+    }
+    Ok(out)
+}
+
+/// Announce the submission with the most reactions among prompts posted in the week before `monday`.
+async fn announce_winner(http: &Http, settings: &db::PromptSettings, monday: NaiveDate) -> Result<()> {
+    let week_start = jst().from_local_datetime(&(monday - ChronoDuration::days(7)).and_hms_opt(0, 0, 0).unwrap()).unwrap().timestamp();
+    let week_end = week_start + 7 * 86400;
+    let posts = db::get_prompt_posts_between(settings.guild_id, week_start, week_end).await?;
+    let mut best: Option<(u64, Message, String)> = None;
+    for post in posts {
+        let messages = match thread_messages(http, ChannelId(post.thread_id as u64)).await {
+            Ok(m) => m,
+            Err(e) => {
+                log::warn!("prompts: failed to read submissions in thread {}: {}", post.thread_id, e);
+                continue;
+            }
+        };
+        for m in messages.into_iter().filter(|m| !m.author.bot) {
+            let s = score(&m);
+            // Ties go to the earlier submission.
+            let better = match &best { Some((b, bm, _)) => s > *b || (s == *b && m.id < bm.id), None => s > 0 };
+            if better { best = Some((s, m, post.prompt.clone())); }
+        }
+    }
+    let (count, message, prompt) = match best { Some(b) => b, None => return Ok(()) };
+    let link = format!("https://discord.com/channels/{}/{}/{}", settings.guild_id, message.channel_id.0, message.id.0);
+    ChannelId(settings.channel_id as u64).send_message(http, |m| m.embed(|e| {
+        e.title("🏆 先週のお題の優秀作品")
+            .description(format!("<@{}> さんの作品がリアクション {} 件で選ばれました！\nお題: {}\n[作品を見る]({})", message.author.id.0, count, prompt, link))
+            .color(serenity::utils::Colour::GOLD);
+        if let Some(image) = message.attachments.iter().find(|a| a.content_type.as_deref().is_some_and(|t| t.starts_with("image/"))) {
+            e.image(&image.url);
+        }
+        e
+    })).await?;
+    Ok(())
+}
+
+pub struct PromptCommand;
+
+#[async_trait]
+impl Command for PromptCommand {
+    fn name(&self) -> &'static str { "prompt" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("毎日のお題の投稿と、作品の週間優秀賞を管理します")
+            .create_option(|s| {
+                s.name("channel").description("お題を投稿するチャンネルと時刻を設定します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("channel").description("投稿先").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text, ChannelType::News]).required(true))
+                    .create_sub_option(|o| o.name("hour").description("投稿する時刻 (JST, デフォルト: 9時)").kind(CommandOptionType::Integer).min_int_value(0).max_int_value(23).required(false))
+            })
+            .create_option(|s| s.name("disable").description("お題の投稿を停止します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("add").description("お題を追加します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("text").description("お題").kind(CommandOptionType::String).max_length(MAX_PROMPT_LENGTH).required(true))
+            })
+            .create_option(|s| {
+                s.name("remove").description("お題を削除します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("/prompt list で表示される番号").kind(CommandOptionType::Integer).min_int_value(1).required(true))
+            })
+            .create_option(|s| s.name("list").description("設定と登録済みのお題を表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| s.name("post-now").description("今日のお題をすぐに投稿します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "channel" => {
+                let channel = match args.channel("channel") { Some(c) => c.id, None => return inv.say("チャンネルを指定してください。").await };
+                let hour = args.int("hour").unwrap_or(DEFAULT_POST_HOUR);
+                db::set_prompt_channel(gid, channel.0 as i64, hour).await?;
+                inv.say(format!("毎日{}時 (JST) に <#{}> へお題を投稿します。作品はお題ごとのスレッドで受け付けます。", hour, channel.0)).await
+            }
+            "disable" => {
+                if db::remove_prompt_settings(gid).await? { inv.say("お題の投稿を停止しました。登録済みのお題は残っています。").await } else { inv.say("お題の投稿は設定されていません。").await }
+            }
+            "add" => {
+                let text = args.str("text").unwrap_or("").trim();
+                if text.is_empty() { return inv.say("お題を入力してください。").await; }
+                if db::get_prompts(gid).await?.len() >= MAX_PROMPTS_PER_GUILD { return inv.say(format!("お題は{}件まで登録できます。", MAX_PROMPTS_PER_GUILD)).await; }
+                let id = db::add_prompt(gid, text, command.user.id.0 as i64, Utc::now().timestamp()).await?;
+                inv.say(format!("お題 #{} を追加しました。", id)).await
+            }
+            "remove" => {
+                let id = args.int("id").unwrap_or(0);
+                if db::remove_prompt(gid, id).await? { inv.say(format!("お題 #{} を削除しました。", id)).await } else { inv.say(format!("お題 #{} はありません。", id)).await }
+            }
+            "list" => {
+                let mut embed = CreateEmbed::default();
+                embed.title("毎日のお題");
+                embed.description(match db::get_prompt_settings(gid).await? {
+                    Some(s) => format!("<#{}> に毎日{}時 (JST) に投稿します。", s.channel_id, s.post_hour),
+                    None => "投稿先が設定されていません。`/prompt channel` で設定できます。".to_string(),
+                });
+                let prompts = db::get_prompts(gid).await?;
+                let lines: Vec<String> = prompts.iter().map(|p| format!("`#{}` {} ({}回使用)", p.id, p.text.chars().take(80).collect::<String>(), p.used_count)).collect();
+                let list = if lines.is_empty() { "お題はまだありません。`/prompt add` で追加できます。".to_string() } else { lines.join("\n") };
+                embed.field(format!("お題 ({}件)", prompts.len()), list.chars().take(1024).collect::<String>(), false);
+                embed.color(serenity::utils::Colour::BLURPLE);
+                command.create_followup_message(&ctx.http, |m| m.ephemeral(true).embed(|e| { *e = embed; e })).await?;
+                Ok(())
+            }
+            "post-now" => {
+                let settings = match db::get_prompt_settings(gid).await? { Some(s) => s, None => return inv.say("先に `/prompt channel` で投稿先を設定してください。").await };
+                match post_prompt(&ctx.http, &settings, Utc::now().with_timezone(&jst()).date_naive()).await {
+                    Ok(true) => inv.say(format!("<#{}> にお題を投稿しました。", settings.channel_id)).await,
+                    Ok(false) => inv.say("投稿できるお題がありません。`/prompt add` で追加してください。").await,
+                    Err(e) => inv.say(format!("<#{}> に投稿できませんでした: {}", settings.channel_id, e)).await,
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::id::{GuildId, RoleId, UserId};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::member_cache;
+use crate::modlog;
+use crate::permissions;
+use crate::report::Report;
+
+/// Finding inactive members reads the whole member list.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::GUILD_MEMBERS;
+
+const DEFAULT_DAYS: i64 = 90;
+const MIN_DAYS: i64 = 7;
+const MAX_DAYS: i64 = 365;
+/// Previews can be confirmed for as long as the interaction token lives.
+const PREVIEW_TTL: Duration = Duration::from_secs(15 * 60);
+const MEMBER_EDIT_DELAY_MS: u64 = 250;
+const LOG_DAYS: i64 = 30;
+/// Mentions listed in the mod-log embed; the rest are in `/prune log`.
+const MODLOG_MENTIONS: usize = 40;
+
+/// A preview waiting for its confirm button.
+struct Pending {
+    guild_id: GuildId,
+    moderator: UserId,
+    users: Vec<UserId>,
+    inactive_role: Option<RoleId>,
+    days: i64,
+}
+
+static PENDING: Lazy<Mutex<HashMap<u64, (Instant, Pending)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub struct PruneCommand;
+
+#[async_trait]
+impl Command for PruneCommand {
+    fn name(&self) -> &'static str { "prune" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("活動のないメンバーの整理")
+            .create_option(|s| {
+                s.name("preview").description("一定期間メッセージ・ボイスの記録がないメンバーを一覧し、キックかロール付与を確認します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("days").description(format!("活動を確認する日数 (デフォルト{}日)", DEFAULT_DAYS)).kind(CommandOptionType::Integer).min_int_value(MIN_DAYS).max_int_value(MAX_DAYS).required(false))
+                    .create_sub_option(|o| o.name("role").description("このロールを持つメンバーだけを対象にします").kind(CommandOptionType::Role).required(false))
+                    .create_sub_option(|o| o.name("inactive_role").description("キックの代わりに付与できる「非アクティブ」ロール").kind(CommandOptionType::Role).required(false))
+            })
+            .create_option(|s| s.name("log").description(format!("直近{}日の整理の記録を表示します", LOG_DAYS)).kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "preview" => preview(inv, guild_id, Args::new(&sub.options)).await,
+            "log" => show_log(inv, guild_id).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Members without activity since the cutoff. Bots, admins and members who joined inside the
+/// window are left out, since none of them can be judged by activity.
+async fn inactive_members(inv: &Invocation<'_>, guild_id: GuildId, days: i64, role: Option<RoleId>) -> Result<Vec<(UserId, i64)>> {
+    let cutoff = Utc::now() - ChronoDuration::days(days);
+    let active = db::get_active_user_ids(guild_id.0 as i64, &cutoff.date_naive().to_string()).await?;
+    let mut inactive = Vec::new();
+    for m in member_cache::members(&inv.ctx.http, guild_id).await? {
+        let joined = match m.joined_at { Some(j) => j.unix_timestamp(), None => continue };
+        if m.user.bot || joined >= cutoff.timestamp() || active.contains(&(m.user.id.0 as i64)) { continue; }
+        if role.map(|r| !m.roles.contains(&r)).unwrap_or(false) { continue; }
+        if permissions::is_admin(&m).await { continue; }
+        inactive.push((m.user.id, joined));
+    }
+    inactive.sort_by_key(|(_, joined)| *joined);
+    Ok(inactive)
+}
+
+async fn preview(inv: &Invocation<'_>, guild_id: GuildId, args: Args<'_>) -> Result<()> {
+    if !crate::intents::has(REQUIRED_INTENTS) { return inv.say("このBotはメンバー一覧を取得できない設定のため、整理は利用できません。").await; }
+    let days = args.int("days").unwrap_or(DEFAULT_DAYS).clamp(MIN_DAYS, MAX_DAYS);
+    let role = args.role("role").map(|r| r.id);
+    let inactive_role = args.role("inactive_role").map(|r| r.id);
+    let inactive = inactive_members(inv, guild_id, days, role).await?;
+    if inactive.is_empty() { return inv.say(format!("直近{}日間に活動のないメンバーはいません。", days)).await; }
+
+    let scope = role.map(|r| format!(" (<@&{}> のみ)", r.0)).unwrap_or_default();
+    Report::embed("prune_preview.txt", format!("直近{}日間に活動のないメンバー{}", days, scope), serenity::utils::Colour::ORANGE)
+        .header(format!("{}人が対象です。", inactive.len()))
+        .lines(inactive.iter().map(|(u, joined)| format!("<@{}> — 参加 <t:{}:d>", u.0, joined)))
+        .footer("メッセージとボイス参加の記録に基づきます。記録が始まる前の活動は含まれません")
+        .ephemeral(true)
+        .send(&inv.ctx.http, inv.command)
+        .await?;
+
+    let id = inv.command.id.0;
+    let count = inactive.len();
+    {
+        let mut pending = PENDING.lock().await;
+        pending.retain(|_, (at, _)| at.elapsed() < PREVIEW_TTL);
+        pending.insert(id, (Instant::now(), Pending { guild_id, moderator: inv.command.user.id, users: inactive.into_iter().map(|(u, _)| u).collect(), inactive_role, days }));
+    }
+    inv.command.create_followup_message(&inv.ctx.http, |m| {
+        m.content("内容を確認して、実行する操作を選んでください。").ephemeral(true).components(|c| c.create_action_row(|row| {
+            row.create_button(|b| b.custom_id(format!("prune_kick:{}", id)).label(format!("{}人をキック", count)).style(ButtonStyle::Danger));
+            if inactive_role.is_some() {
+                row.create_button(|b| b.custom_id(format!("prune_role:{}", id)).label(format!("{}人にロールを付与", count)).style(ButtonStyle::Primary));
+            }
+            row.create_button(|b| b.custom_id(format!("prune_cancel:{}", id)).label("キャンセル").style(ButtonStyle::Secondary))
+        }))
+    }).await?;
+    Ok(())
+}
+
+async fn show_log(inv: &Invocation<'_>, guild_id: GuildId) -> Result<()> {
+    let since = Utc::now().timestamp() - LOG_DAYS * 86400;
+    let entries = db::get_prune_log(guild_id.0 as i64, since).await?;
+    let report = Report::embed("prune_log.txt", format!("整理の記録 (直近{}日)", LOG_DAYS), serenity::utils::Colour::ORANGE).ephemeral(true);
+    let report = if entries.is_empty() {
+        report.line("記録はありません。")
+    } else {
+        report.lines(entries.iter().map(|e| {
+            format!("<t:{}:f> {} <@{}> (実行者: <@{}>){}", e.created_at, action_label(&e.action), e.user_id, e.moderator_id, if e.succeeded { "" } else { " ⚠️ 失敗" })
+        }))
+    };
+    report.send(&inv.ctx.http, inv.command).await
+}
+
+fn action_label(action: &str) -> &'static str {
+    match action { "kick" => "キック", "role" => "ロール付与", _ => "不明" }
+}
+
+/// Confirm buttons: `prune_kick:<id>`, `prune_role:<id>` and `prune_cancel:<id>`.
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let mut parts = comp.data.custom_id.split(':');
+    let action = parts.next().unwrap_or("").trim_start_matches("prune_").to_string();
+    let id: u64 = parts.next().unwrap_or("").parse()?;
+
+    let mut map = PENDING.lock().await;
+    let moderator = map.get(&id).filter(|(at, _)| at.elapsed() < PREVIEW_TTL).map(|(_, p)| p.moderator);
+    if moderator.map(|m| m != comp.user.id).unwrap_or(false) {
+        drop(map);
+        return reply(ctx, comp, "プレビューを実行したユーザーのみ確定できます。").await;
+    }
+    // Taking the preview out means a second click finds nothing to run.
+    let pending = moderator.and_then(|_| map.remove(&id)).map(|(_, p)| p);
+    drop(map);
+    let pending = match pending {
+        Some(p) => p,
+        None => return reply(ctx, comp, "プレビューの有効期限が切れました。もう一度 /prune preview を実行してください。").await,
+    };
+    if action == "cancel" {
+        return update(ctx, comp, "キャンセルしました。").await;
+    }
+    let role = match action.as_str() {
+        "kick" => None,
+        "role" => match pending.inactive_role { Some(r) => Some(r), None => return Ok(()) },
+        _ => return Ok(()),
+    };
+    update(ctx, comp, format!("{}人に対して{}を実行しています…", pending.users.len(), action_label(&action))).await?;
+
+    let reason = format!("{}日間活動なし (/prune by {})", pending.days, comp.user.tag());
+    let now = Utc::now().timestamp();
+    let mut done = Vec::new();
+    let mut failed = 0;
+    for user in pending.users.iter() {
+        let result = match role {
+            Some(r) => ctx.http.add_member_role(pending.guild_id.0, user.0, r.0, Some(&reason)).await,
+            None => ctx.http.kick_member_with_reason(pending.guild_id.0, user.0, &reason).await,
+        };
+        if let Err(e) = &result {
+            log::warn!("prune: failed to {} {} in {}: {}", action, user.0, pending.guild_id.0, e);
+            failed += 1;
+        } else {
+            done.push(*user);
+        }
+        db::add_prune_log_entry(id as i64, pending.guild_id.0 as i64, comp.user.id.0 as i64, user.0 as i64, &action, result.is_ok(), now).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(MEMBER_EDIT_DELAY_MS)).await;
+    }
+    if role.is_none() { member_cache::invalidate(pending.guild_id).await; }
+
+    let mut mentions: Vec<String> = done.iter().take(MODLOG_MENTIONS).map(|u| format!("<@{}>", u.0)).collect();
+    if done.len() > MODLOG_MENTIONS { mentions.push(format!("他{}人 (`/prune log` で確認できます)", done.len() - MODLOG_MENTIONS)); }
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("🧹 非アクティブメンバーの{}", action_label(&action)));
+    embed.description(format!("実行者: {}\n条件: {}日間活動なし{}\n\n{}", comp.user.mention(), pending.days, role.map(|r| format!("\n付与したロール: <@&{}>", r.0)).unwrap_or_default(), mentions.join(" ")));
+    embed.field("成功", format!("{}人", done.len()), true);
+    embed.field("失敗", format!("{}人", failed), true);
+    embed.color(serenity::utils::Colour::ORANGE);
+    embed.timestamp(Utc::now().to_rfc3339());
+    if let Err(e) = modlog::send(&ctx.http, pending.guild_id, embed).await {
+        log::warn!("prune: failed to post mod log for {}: {}", pending.guild_id.0, e);
+    }
+    comp.edit_original_interaction_response(&ctx.http, |m| m.content(format!("{}人に{}を実行しました (失敗: {}人)。", done.len(), action_label(&action), failed))).await?;
+    Ok(())
+}
+
+async fn reply(ctx: &Context, comp: &MessageComponentInteraction, msg: &str) -> Result<()> {
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+/// Replace the confirm message, dropping its buttons so it can't be pressed twice.
+async fn update(ctx: &Context, comp: &MessageComponentInteraction, msg: impl ToString) -> Result<()> {
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.content(msg.to_string()).components(|c| c))).await?;
+    Ok(())
+}
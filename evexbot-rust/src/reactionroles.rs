@@ -0,0 +1,185 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateComponents, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::ChannelType;
+use serenity::model::guild::Role;
+use serenity::model::id::{ChannelId, RoleId};
+use serenity::prelude::*;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+
+pub const BUTTON_PREFIX: &str = "rr_role:";
+pub const SELECT_ID: &str = "rr_select";
+const MAX_ROLES: usize = 10;
+const BUTTONS_PER_ROW: usize = 5;
+const MAX_LABEL_LEN: usize = 80;
+const MAX_TITLE_LEN: u16 = 100;
+const MAX_DESCRIPTION_LEN: u16 = 1000;
+
+fn label(role: &Role) -> String {
+    role.name.chars().take(MAX_LABEL_LEN).collect()
+}
+
+fn render_components<'a>(c: &'a mut CreateComponents, style: &str, roles: &[&Role]) -> &'a mut CreateComponents {
+    if style == "select" {
+        return c.create_action_row(|ar| ar.create_select_menu(|s| {
+            s.custom_id(SELECT_ID).placeholder("ロールを選択").min_values(0).max_values(roles.len() as u64).options(|o| {
+                for role in roles { o.create_option(|opt| opt.label(label(role)).value(role.id.0.to_string())); }
+                o
+            })
+        }));
+    }
+    for row in roles.chunks(BUTTONS_PER_ROW) {
+        c.create_action_row(|ar| {
+            for role in row { ar.create_button(|b| b.custom_id(format!("{}{}", BUTTON_PREFIX, role.id.0)).label(label(role)).style(ButtonStyle::Secondary)); }
+            ar
+        });
+    }
+    c
+}
+
+/// Toggle the pressed role, or sync the menu's roles to a select menu choice.
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let (guild_id, member) = match (comp.guild_id, comp.member.as_ref()) { (Some(g), Some(m)) => (g, m), _ => return Ok(()) };
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    // The stored mapping is what counts, so a stale or forged custom_id can't hand out other roles.
+    let menu_roles: Vec<RoleId> = db::get_reaction_role_options(comp.message.id.0 as i64).await?.into_iter().map(|r| RoleId(r as u64)).collect();
+    if menu_roles.is_empty() { return followup(ctx, comp, "このメニューは削除されています。").await; }
+
+    let wanted: Vec<RoleId> = if comp.data.custom_id == SELECT_ID {
+        comp.data.values.iter().filter_map(|v| v.parse().ok()).map(RoleId).collect()
+    } else {
+        let pressed = match comp.data.custom_id.strip_prefix(BUTTON_PREFIX).and_then(|r| r.parse().ok()).map(RoleId) { Some(r) => r, None => return Ok(()) };
+        let mut wanted: Vec<RoleId> = menu_roles.iter().copied().filter(|r| member.roles.contains(r)).collect();
+        if wanted.contains(&pressed) { wanted.retain(|r| *r != pressed); } else { wanted.push(pressed); }
+        wanted
+    };
+
+    let (mut added, mut removed, mut failed) = (Vec::new(), Vec::new(), 0);
+    for role in menu_roles {
+        let (has, want) = (member.roles.contains(&role), wanted.contains(&role));
+        if has == want { continue; }
+        let result = if want {
+            ctx.http.add_member_role(guild_id.0, member.user.id.0, role.0, Some("reaction role menu")).await
+        } else {
+            ctx.http.remove_member_role(guild_id.0, member.user.id.0, role.0, Some("reaction role menu")).await
+        };
+        match result {
+            Ok(()) if want => added.push(format!("<@&{}>", role.0)),
+            Ok(()) => removed.push(format!("<@&{}>", role.0)),
+            Err(e) => {
+                log::warn!("reactionroles: failed to update role {} for {} in {}: {}", role.0, member.user.id.0, guild_id.0, e);
+                failed += 1;
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    if !added.is_empty() { lines.push(format!("付与しました: {}", added.join(" "))); }
+    if !removed.is_empty() { lines.push(format!("外しました: {}", removed.join(" "))); }
+    if failed > 0 { lines.push(format!("{}件のロールを変更できませんでした。Botのロールの位置を確認するようサーバー管理者に伝えてください。", failed)); }
+    if lines.is_empty() { lines.push("変更はありません。".to_string()); }
+    followup(ctx, comp, &lines.join("\n")).await
+}
+
+async fn followup(ctx: &Context, comp: &MessageComponentInteraction, msg: &str) -> Result<()> {
+    comp.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+    Ok(())
+}
+
+pub struct ReactionRoleCommand;
+
+#[async_trait]
+impl Command for ReactionRoleCommand {
+    fn name(&self) -> &'static str { "reactionrole" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("ボタンやメニューで自分にロールを付け外しできるメッセージを管理します")
+            .create_option(|s| {
+                s.name("create").description("ロールメニューを投稿します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("title").description("メニューのタイトル").kind(CommandOptionType::String).max_length(MAX_TITLE_LEN).required(true))
+                    .create_sub_option(|o| o.name("role1").description("ロール").kind(CommandOptionType::Role).required(true));
+                for i in 2..=MAX_ROLES {
+                    s.create_sub_option(|o| o.name(format!("role{}", i)).description("ロール").kind(CommandOptionType::Role).required(false));
+                }
+                s.create_sub_option(|o| o.name("style").description("表示形式 (デフォルト: ボタン)").kind(CommandOptionType::String).add_string_choice("ボタン", "buttons").add_string_choice("セレクトメニュー", "select").required(false))
+                    .create_sub_option(|o| o.name("description").description("説明文").kind(CommandOptionType::String).max_length(MAX_DESCRIPTION_LEN).required(false))
+                    .create_sub_option(|o| o.name("channel").description("投稿先 (省略でこのチャンネル)").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(false))
+            })
+            .create_option(|s| {
+                s.name("delete").description("ロールメニューを削除します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("message_id").description("メニューのメッセージID").kind(CommandOptionType::String).required(true))
+            })
+            .create_option(|s| s.name("list").description("このサーバーのロールメニューを一覧します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let (ctx, command) = (inv.ctx, inv.command);
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        let gid = guild_id.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "create" => {
+                let mut roles: Vec<&Role> = Vec::new();
+                for i in 1..=MAX_ROLES {
+                    if let Some(role) = args.role(&format!("role{}", i)) {
+                        if !roles.iter().any(|r| r.id == role.id) { roles.push(role); }
+                    }
+                }
+                if roles.iter().any(|r| r.managed || r.id.0 == guild_id.0) { return inv.say("連携ロールと@everyoneはメニューに追加できません。").await; }
+                let title = args.str("title").unwrap_or("").trim();
+                let style = args.str("style").unwrap_or("buttons");
+                let channel = args.channel("channel").map(|c| c.id).unwrap_or(command.channel_id);
+
+                let mut embed = CreateEmbed::default();
+                embed.title(title);
+                let intro = args.str("description").map(|d| format!("{}\n\n", d)).unwrap_or_default();
+                let how = if style == "select" { "メニューから選ぶとロールが付き、選択を外すとロールも外れます。" } else { "ボタンを押すとロールの付け外しができます。" };
+                embed.description(format!("{}{}\n{}", intro, roles.iter().map(|r| format!("・<@&{}>", r.id.0)).collect::<Vec<_>>().join("\n"), how));
+                embed.color(serenity::utils::Colour::BLURPLE);
+                let message = match channel.send_message(&ctx.http, |m| m.embed(|e| { *e = embed; e }).components(|c| render_components(c, style, &roles))).await {
+                    Ok(m) => m,
+                    Err(e) => return inv.say(format!("<#{}> に投稿できませんでした: {}", channel.0, e)).await,
+                };
+                let menu = db::ReactionRoleMenu { message_id: message.id.0 as i64, guild_id: gid, channel_id: channel.0 as i64, style: style.to_string(), title: title.to_string(), created_at: Utc::now().timestamp() };
+                db::create_reaction_role_menu(&menu, &roles.iter().map(|r| r.id.0 as i64).collect::<Vec<_>>()).await?;
+                inv.say(format!("<#{}> にロールメニューを投稿しました ({}個のロール)。", channel.0, roles.len())).await
+            }
+            "delete" => {
+                let message_id: i64 = match args.str("message_id").and_then(|m| m.trim().parse().ok()) { Some(m) => m, None => return inv.say("メッセージIDを数字で指定してください。").await };
+                match db::remove_reaction_role_menu(gid, message_id).await? {
+                    Some(menu) => {
+                        let _ = ChannelId(menu.channel_id as u64).delete_message(&ctx.http, menu.message_id as u64).await;
+                        inv.say(format!("ロールメニュー「{}」を削除しました。", menu.title)).await
+                    }
+                    None => inv.say("そのIDのロールメニューはありません。`/reactionrole list` で確認できます。").await,
+                }
+            }
+            "list" => {
+                let menus = db::get_reaction_role_menus(gid).await?;
+                if menus.is_empty() { return inv.say("ロールメニューはありません。`/reactionrole create` で作成できます。").await; }
+                let mut lines = Vec::new();
+                for menu in menus {
+                    let roles = db::get_reaction_role_options(menu.message_id).await?;
+                    lines.push(format!("**{}** (`{}`) — https://discord.com/channels/{}/{}/{}\n{}", menu.title, menu.message_id, gid, menu.channel_id, menu.message_id,
+                        roles.iter().map(|r| format!("<@&{}>", r)).collect::<Vec<_>>().join(" ")));
+                }
+                inv.say(lines.join("\n")).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use serenity::http::Http;
+use serenity::model::id::{ChannelId, GuildId};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db;
+use crate::growth::{self, Model};
+use crate::intents;
+use crate::member_cache;
+use crate::scheduler;
+
+const CHECK_INTERVAL_SECONDS: u64 = 3600;
+const REFORECAST_AFTER_SECONDS: i64 = 7 * 86400;
+/// Lines starting with this are the prediction, in welcome messages and on their own.
+pub const PREDICTION_PREFIX: &str = "次の目標到達予測";
+
+/// Start the job that refreshes prediction messages a week after they were last updated.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("growth-reforecast", Duration::from_secs(CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { reforecast_due(&http).await }
+    });
+}
+
+/// Track a freshly posted prediction so the weekly job can update it.
+pub async fn track(guild_id: i64, channel_id: ChannelId, message_id: u64, target: i64) {
+    if let Err(e) = db::set_growth_forecast(guild_id, channel_id.0 as i64, message_id as i64, target, Utc::now().timestamp()).await {
+        log::warn!("reforecast: failed to track prediction for guild {}: {}", guild_id, e);
+    }
+}
+
+fn prediction_line(target: i64, date: Option<NaiveDate>, today: NaiveDate) -> String {
+    match date {
+        Some(d) => format!("{}: {}人: {} (あと{}日) ※予測更新 {}", PREDICTION_PREFIX, target, d, (d - today).num_days(), today),
+        None => format!("{}: {}人: 予測期間内には達しない見込みです ※予測更新 {}", PREDICTION_PREFIX, target, today),
+    }
+}
+
+/// Swap the prediction line in `content`, or append it if the message has none.
+fn replace_prediction(content: &str, line: &str) -> String {
+    let mut lines: Vec<&str> = content.lines().filter(|l| !l.starts_with(PREDICTION_PREFIX)).collect();
+    lines.push(line);
+    lines.join("\n")
+}
+
+async fn reforecast_due(http: &Http) -> Result<()> {
+    if !intents::has(growth::REQUIRED_INTENTS) { return Ok(()); }
+    let now = Utc::now();
+    for forecast in db::get_due_growth_forecasts(now.timestamp() - REFORECAST_AFTER_SECONDS).await? {
+        if let Err(e) = reforecast(http, &forecast, now.date_naive()).await {
+            log::warn!("reforecast: failed to update prediction for guild {}: {}", forecast.guild_id, e);
+        }
+    }
+    Ok(())
+}
+
+async fn reforecast(http: &Http, forecast: &db::GrowthForecast, today: NaiveDate) -> Result<()> {
+    let guild_id = GuildId(forecast.guild_id as u64);
+    // Reached targets and disabled welcomes have nothing pending; the next milestone post tracks a new one.
+    let settings = db::get_welcome_settings(forecast.guild_id).await?;
+    if !settings.is_enabled || member_cache::count(http, guild_id).await? as i64 >= forecast.target {
+        db::remove_growth_forecast(forecast.guild_id).await?;
+        return Ok(());
+    }
+
+    let join_dates = member_cache::join_dates(http, guild_id).await?;
+    let date = growth::predict_date_blocking(Model::Trend, join_dates, forecast.target as usize).await?;
+    let line = prediction_line(forecast.target, date, today);
+    let channel = ChannelId(forecast.channel_id as u64);
+
+    // Edit in place; if the message is gone, repost and track the new one instead.
+    let message_id = match channel.message(http, forecast.message_id as u64).await {
+        Ok(mut message) => {
+            let content = replace_prediction(&message.content, &line);
+            message.edit(http, |m| m.content(content)).await?;
+            message.id.0
+        }
+        Err(_) => channel.say(http, &line).await?.id.0,
+    };
+    db::set_growth_forecast(forecast.guild_id, forecast.channel_id, message_id as i64, forecast.target, Utc::now().timestamp()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prediction_line_is_replaced_not_duplicated() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let old = format!("ようこそ！\n{}: 200人: 2024-05-01 (あと70日)", PREDICTION_PREFIX);
+        let line = prediction_line(200, NaiveDate::from_ymd_opt(2024, 4, 1), today);
+        assert_eq!(replace_prediction(&old, &line), format!("ようこそ！\n{}: 200人: 2024-04-01 (あと31日) ※予測更新 2024-03-01", PREDICTION_PREFIX));
+        assert_eq!(replace_prediction("ようこそ！", &line).lines().count(), 2);
+    }
+
+    #[test]
+    fn unreachable_targets_say_so() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert!(prediction_line(200, None, today).contains("予測期間内には達しない見込みです"));
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, FixedOffset, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::ChannelType;
+use serenity::model::id::{ChannelId, UserId};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::cron::Schedule;
+use crate::db;
+use crate::events::jst;
+use crate::permissions;
+use crate::preferences;
+use crate::scheduler;
+
+const CHECK_INTERVAL_SECONDS: u64 = 30;
+const MAX_REMINDERS_PER_USER: usize = 25;
+const MAX_ANNOUNCEMENTS_PER_GUILD: usize = 25;
+const MAX_AHEAD_DAYS: i64 = 365;
+const MAX_TEXT_LENGTH: u16 = 1000;
+/// Runs this late were missed while the bot was down, and say so.
+const LATE_AFTER_SECONDS: i64 = 300;
+
+static RELATIVE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\d+)\s*(日|d|時間|h|分|m)").unwrap());
+
+/// Start the job that delivers due reminders and announcements. Everything lives in
+/// `scheduled_jobs`, so anything that came due during a restart is sent on the first run.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("scheduled-jobs", Duration::from_secs(CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { run_due(&http).await }
+    });
+}
+
+async fn run_due(http: &Http) -> Result<()> {
+    let now = Utc::now();
+    for job in db::get_due_scheduled_jobs(now.timestamp()).await? {
+        let late = now.timestamp() - job.next_run_at > LATE_AFTER_SECONDS;
+        let result = match job.kind.as_str() {
+            "reminder" => remind(http, &job, late).await,
+            _ => ChannelId(job.channel_id as u64).say(http, &job.message).await.map(|_| ()).map_err(Into::into),
+        };
+        if let Err(e) = result {
+            log::warn!("reminders: failed to deliver job {} in guild {}: {}", job.id, job.guild_id, e);
+        }
+        // Runs missed during downtime are sent once, then the schedule picks up from now.
+        let next = job.cron.as_deref().and_then(|c| Schedule::parse(c).ok()).and_then(|s| s.next_after(&now.with_timezone(&jst())));
+        match next {
+            Some(next) => db::reschedule_job(job.id, next.timestamp()).await?,
+            None => { db::remove_scheduled_job(job.guild_id, &job.kind, job.id, None).await?; }
+        }
+    }
+    Ok(())
+}
+
+/// DM the reminder to users who asked for that, falling back to a mention where it was set.
+async fn remind(http: &Http, job: &db::ScheduledJob, late: bool) -> Result<()> {
+    let note = if late { "\n(Botが停止していたため、予定より遅れてお届けしました)" } else { "" };
+    if preferences::wants_dm_reminders(job.user_id).await {
+        let content = format!("⏰ リマインダー (<t:{}:R>に設定): {}{}", job.created_at, job.message, note);
+        if let Ok(dm) = UserId(job.user_id as u64).create_dm_channel(http).await {
+            if dm.say(http, content).await.is_ok() { return Ok(()); }
+        }
+    }
+    let content = format!("⏰ <@{}> リマインダー (<t:{}:R>に設定): {}{}", job.user_id, job.created_at, job.message, note);
+    ChannelId(job.channel_id as u64).send_message(http, |m| m.content(content).allowed_mentions(|a| a.users(vec![UserId(job.user_id as u64)]))).await?;
+    Ok(())
+}
+
+/// `10m`, `2h30m`, `1日` and the like from now, `HH:MM` at the next such time, or
+/// `YYYY-MM-DD HH:MM`, all in JST.
+fn parse_when(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    let tz: FixedOffset = jst();
+    let when = if RELATIVE.replace_all(s, "").trim().is_empty() && RELATIVE.is_match(s) {
+        let mut total = ChronoDuration::zero();
+        for c in RELATIVE.captures_iter(s) {
+            let n: i64 = c[1].parse().map_err(|_| "時間が大きすぎます。".to_string())?;
+            total += match &c[2] { "日" | "d" => ChronoDuration::days(n.min(MAX_AHEAD_DAYS + 1)), "時間" | "h" => ChronoDuration::hours(n.min(24 * (MAX_AHEAD_DAYS + 1))), _ => ChronoDuration::minutes(n.min(1440 * (MAX_AHEAD_DAYS + 1))) };
+        }
+        now + total
+    } else if let Ok(t) = NaiveTime::parse_from_str(s, "%H:%M") {
+        let local = now.with_timezone(&tz);
+        let today = tz.from_local_datetime(&local.date_naive().and_time(t)).single().ok_or("時刻が不正です。")?;
+        (if today > local { today } else { today + ChronoDuration::days(1) }).with_timezone(&Utc)
+    } else {
+        let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").or_else(|_| NaiveDateTime::parse_from_str(s, "%Y/%m/%d %H:%M"))
+            .map_err(|_| "時間は `30m`・`2h`・`1d` のような長さか、`21:00`・`2024-12-31 21:00` (JST) の形式で指定してください。".to_string())?;
+        tz.from_local_datetime(&naive).single().ok_or("日時が不正です。")?.with_timezone(&Utc)
+    };
+    if when <= now { return Err("未来の日時を指定してください。".to_string()); }
+    if when > now + ChronoDuration::days(MAX_AHEAD_DAYS) { return Err(format!("{}日以内で指定してください。", MAX_AHEAD_DAYS)); }
+    Ok(when)
+}
+
+pub struct RemindCommand;
+
+#[async_trait]
+impl Command for RemindCommand {
+    fn name(&self) -> &'static str { "remind" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("指定した時間にリマインドします")
+            .create_option(|s| {
+                s.name("me").description("リマインダーを設定します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("time").description("30m, 2h, 1d, 21:00, 2024-12-31 21:00 (JST)").kind(CommandOptionType::String).required(true))
+                    .create_sub_option(|o| o.name("text").description("内容").kind(CommandOptionType::String).max_length(MAX_TEXT_LENGTH).required(true))
+            })
+            .create_option(|s| s.name("list").description("設定中のリマインダーを表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("cancel").description("リマインダーを取り消します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("リマインダーの番号").kind(CommandOptionType::Integer).min_int_value(1).required(true))
+            })
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let uid = command.user.id.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        match sub.name.as_str() {
+            "me" => {
+                let now = Utc::now();
+                let when = match parse_when(args.str("time").unwrap_or(""), now) { Ok(w) => w, Err(e) => return inv.say(e).await };
+                if db::get_scheduled_jobs(gid, "reminder", Some(uid)).await?.len() >= MAX_REMINDERS_PER_USER {
+                    return inv.say(format!("リマインダーは1人{}件までです。`/remind cancel` で整理してください。", MAX_REMINDERS_PER_USER)).await;
+                }
+                let text = args.str("text").unwrap_or("").trim();
+                let id = db::add_scheduled_job(gid, "reminder", uid, command.channel_id.0 as i64, text, None, when.timestamp(), now.timestamp()).await?;
+                let via = if preferences::wants_dm_reminders(uid).await { "DMでお知らせします" } else { "このチャンネルでメンションします" };
+                inv.say(format!("<t:{}:f> (<t:{}:R>) に{} (#{})。", when.timestamp(), when.timestamp(), via, id)).await
+            }
+            "list" => {
+                let jobs = db::get_scheduled_jobs(gid, "reminder", Some(uid)).await?;
+                if jobs.is_empty() { return inv.say("設定中のリマインダーはありません。").await; }
+                let lines: Vec<String> = jobs.iter().map(|j| format!("#{} <t:{}:f> — {}", j.id, j.next_run_at, j.message.chars().take(80).collect::<String>())).collect();
+                inv.say(lines.join("\n")).await
+            }
+            "cancel" => {
+                let id = args.int("id").unwrap_or(0);
+                if db::remove_scheduled_job(gid, "reminder", id, Some(uid)).await? { inv.say(format!("リマインダー #{} を取り消しました。", id)).await } else { inv.say(format!("リマインダー #{} はありません。", id)).await }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub struct ScheduleCommand;
+
+#[async_trait]
+impl Command for ScheduleCommand {
+    fn name(&self) -> &'static str { "schedule" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("定期的なお知らせを管理します")
+            .create_option(|s| {
+                s.name("announce").description("cron式の日時にメッセージを投稿します (JST)").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("cron").description("分 時 日 月 曜日 (例: 0 9 * * 1 で毎週月曜9:00)").kind(CommandOptionType::String).required(true))
+                    .create_sub_option(|o| o.name("channel").description("投稿先").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text, ChannelType::News]).required(true))
+                    .create_sub_option(|o| o.name("message").description("投稿する内容").kind(CommandOptionType::String).max_length(MAX_TEXT_LENGTH).required(true))
+            })
+            .create_option(|s| s.name("list").description("定期投稿を一覧します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("remove").description("定期投稿を削除します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("定期投稿の番号").kind(CommandOptionType::Integer).min_int_value(1).required(true))
+            })
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "announce" => {
+                let expr = args.str("cron").unwrap_or("").trim();
+                let schedule = match Schedule::parse(expr) { Ok(s) => s, Err(e) => return inv.say(e).await };
+                let now = Utc::now();
+                let next = match schedule.next_after(&now.with_timezone(&jst())) { Some(n) => n, None => return inv.say("このcron式に一致する日時がありません。").await };
+                if db::get_scheduled_jobs(gid, "announce", None).await?.len() >= MAX_ANNOUNCEMENTS_PER_GUILD {
+                    return inv.say(format!("定期投稿は1サーバー{}件までです。", MAX_ANNOUNCEMENTS_PER_GUILD)).await;
+                }
+                let channel = match args.channel("channel") { Some(c) => c.id, None => return Ok(()) };
+                let message = args.str("message").unwrap_or("");
+                let id = db::add_scheduled_job(gid, "announce", command.user.id.0 as i64, channel.0 as i64, message, Some(expr), next.timestamp(), now.timestamp()).await?;
+                inv.say(format!("定期投稿 #{} を <#{}> に設定しました。次回は <t:{}:f> です。", id, channel.0, next.timestamp())).await
+            }
+            "list" => {
+                let jobs = db::get_scheduled_jobs(gid, "announce", None).await?;
+                if jobs.is_empty() { return inv.say("定期投稿はありません。`/schedule announce` で追加できます。").await; }
+                let lines: Vec<String> = jobs.iter().map(|j| format!("#{} `{}` → <#{}> (次回 <t:{}:f>)\n{}", j.id, j.cron.as_deref().unwrap_or(""), j.channel_id, j.next_run_at, j.message.chars().take(80).collect::<String>())).collect();
+                inv.say(lines.join("\n")).await
+            }
+            "remove" => {
+                let id = args.int("id").unwrap_or(0);
+                if db::remove_scheduled_job(gid, "announce", id, None).await? { inv.say(format!("定期投稿 #{} を削除しました。", id)).await } else { inv.say(format!("定期投稿 #{} はありません。", id)).await }
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use serenity::builder::CreateEmbed;
+use serenity::http::Http;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::utils::Colour;
+
+/// Discord caps message content at 2000 characters; leave room for the header and code fences.
+const MAX_TEXT_CHUNK: usize = 1900;
+/// Embed descriptions are capped at 4096 characters.
+const MAX_EMBED_CHUNK: usize = 4000;
+/// Reports needing more followups than this are sent as a single attachment instead.
+const MAX_MESSAGES: usize = 4;
+
+enum Style {
+    Text { code_block: bool },
+    Embed { title: String, colour: Colour, footer: Option<String> },
+}
+
+/// Line-oriented output for command followups. Lines are packed into as few messages as
+/// possible, split across several followups when they don't fit, and attached as a file when
+/// even that would be too many messages.
+pub struct Report {
+    header: Option<String>,
+    lines: Vec<String>,
+    style: Style,
+    ephemeral: bool,
+    filename: String,
+    file: Option<Vec<u8>>,
+    attachment_only: bool,
+}
+
+impl Report {
+    /// Plain-text report; `filename` names the attachment used when it's too long.
+    pub fn text(filename: &str) -> Self {
+        Report { header: None, lines: Vec::new(), style: Style::Text { code_block: false }, ephemeral: false, filename: filename.to_string(), file: None, attachment_only: false }
+    }
+
+    /// Report rendered as embeds, one description chunk per embed.
+    pub fn embed(filename: &str, title: impl Into<String>, colour: Colour) -> Self {
+        Report { style: Style::Embed { title: title.into(), colour, footer: None }, ..Report::text(filename) }
+    }
+
+    /// Text shown above the report (or alongside the attachment).
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    pub fn line(mut self, line: impl Into<String>) -> Self {
+        self.lines.push(line.into());
+        self
+    }
+
+    pub fn lines<I: IntoIterator<Item = String>>(mut self, lines: I) -> Self {
+        self.lines.extend(lines);
+        self
+    }
+
+    /// Wrap each text chunk in a code block (for tables).
+    pub fn code_block(mut self) -> Self {
+        if let Style::Text { code_block } = &mut self.style { *code_block = true; }
+        self
+    }
+
+    pub fn footer(mut self, text: impl Into<String>) -> Self {
+        if let Style::Embed { footer, .. } = &mut self.style { *footer = Some(text.into()); }
+        self
+    }
+
+    pub fn ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
+    /// Attachment contents to use instead of the joined lines (e.g. CSV for a table).
+    pub fn file(mut self, filename: &str, contents: Vec<u8>) -> Self {
+        self.filename = filename.to_string();
+        self.file = Some(contents);
+        self
+    }
+
+    /// Always send the attachment, regardless of length.
+    pub fn attachment_only(mut self, attachment_only: bool) -> Self {
+        self.attachment_only = attachment_only;
+        self
+    }
+
+    fn chunk_limit(&self) -> usize {
+        match self.style { Style::Text { .. } => MAX_TEXT_CHUNK, Style::Embed { .. } => MAX_EMBED_CHUNK }
+    }
+
+    /// Pack lines into chunks no longer than `limit`, hard-splitting any single overlong line.
+    fn chunks(&self) -> Vec<String> {
+        let limit = self.chunk_limit();
+        let mut chunks = Vec::new();
+        let mut current = String::new();
+        for line in self.lines.iter() {
+            let mut rest: &str = line;
+            while rest.chars().count() > limit {
+                let split = rest.char_indices().nth(limit).map(|(i, _)| i).unwrap_or(rest.len());
+                if !current.is_empty() { chunks.push(std::mem::take(&mut current)); }
+                chunks.push(rest[..split].to_string());
+                rest = &rest[split..];
+            }
+            if !current.is_empty() && current.chars().count() + rest.chars().count() + 1 > limit {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() { current.push('\n'); }
+            current.push_str(rest);
+        }
+        if !current.is_empty() { chunks.push(current); }
+        chunks
+    }
+
+    pub async fn send(self, http: &Http, command: &ApplicationCommandInteraction) -> Result<()> {
+        let chunks = self.chunks();
+        let ephemeral = self.ephemeral;
+
+        if self.attachment_only || chunks.len() > MAX_MESSAGES {
+            let contents = match self.file {
+                Some(f) => f,
+                None => {
+                    let mut text = self.header.clone().map(|h| format!("{}\n\n", h)).unwrap_or_default();
+                    text.push_str(&self.lines.join("\n"));
+                    text.into_bytes()
+                }
+            };
+            let note = match &self.style {
+                Style::Embed { title, .. } => format!("**{}**\n{}件 (添付ファイル)", title, self.lines.len()),
+                Style::Text { .. } => self.header.clone().unwrap_or_else(|| format!("{}件", self.lines.len())),
+            };
+            command.create_followup_message(http, |m| m.content(note).add_file((contents.as_slice(), self.filename.as_str())).ephemeral(ephemeral)).await?;
+            return Ok(());
+        }
+
+        let total = chunks.len().max(1);
+        match &self.style {
+            Style::Text { code_block } => {
+                let chunks = if chunks.is_empty() { vec![String::new()] } else { chunks };
+                for (i, chunk) in chunks.into_iter().enumerate() {
+                    let body = if *code_block { format!("```\n{}\n```", chunk) } else { chunk };
+                    let content = match (&self.header, i) {
+                        (Some(h), 0) => format!("{}\n{}", h, body),
+                        _ => body,
+                    };
+                    command.create_followup_message(http, |m| m.content(content).ephemeral(ephemeral)).await?;
+                }
+            }
+            Style::Embed { title, colour, footer } => {
+                let chunks = if chunks.is_empty() { vec![String::new()] } else { chunks };
+                for (i, chunk) in chunks.into_iter().enumerate() {
+                    let mut embed = CreateEmbed::default();
+                    if i == 0 { embed.title(title); }
+                    if !chunk.is_empty() { embed.description(chunk); }
+                    embed.color(*colour);
+                    let footer_text = match (footer, total) {
+                        (Some(f), 1) => Some(f.clone()),
+                        (Some(f), _) => Some(format!("{}  {}/{}", f, i + 1, total)),
+                        (None, 1) => None,
+                        (None, _) => Some(format!("{}/{}", i + 1, total)),
+                    };
+                    if let Some(text) = footer_text { embed.footer(|f| f.text(text)); }
+                    let header = if i == 0 { self.header.clone() } else { None };
+                    command.create_followup_message(http, |m| {
+                        if let Some(h) = header { m.content(h); }
+                        m.embed(|e| { *e = embed; e }).ephemeral(ephemeral)
+                    }).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
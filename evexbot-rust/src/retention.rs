@@ -0,0 +1,128 @@
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption};
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::prelude::*;
+use std::time::Duration;
+
+use crate::db;
+use crate::scheduler;
+use crate::permissions;
+
+const CLEANUP_INTERVAL_SECONDS: u64 = 6 * 3600;
+const MIN_RETENTION_DAYS: i64 = 7;
+const MAX_RETENTION_DAYS: i64 = 3650;
+/// Owner query audit entries are kept this long regardless of guild.
+const DBQUERY_AUDIT_DAYS: i64 = 180;
+/// Error codes are usually quoted within days of the failure.
+const ERROR_REPORT_DAYS: i64 = 90;
+/// Unused OAuth states are useless after verify's TTL; keep a day for debugging.
+const VERIFY_STATE_SECONDS: i64 = 86400;
+
+/// How a table's age column is stored.
+enum AgeColumn {
+    /// `YYYY-MM-DD` text, compared lexically.
+    Day(&'static str),
+    /// Unix seconds.
+    Timestamp(&'static str),
+}
+
+/// A per-guild, high-churn table and how long its rows are kept by default.
+/// New tables that grow with message or member traffic should be added here.
+struct Policy {
+    data_type: &'static str,
+    label: &'static str,
+    table: &'static str,
+    column: AgeColumn,
+    default_days: i64,
+}
+
+const POLICIES: &[Policy] = &[
+    Policy { data_type: "activity", label: "メッセージ集計", table: "message_activity", column: AgeColumn::Day("day"), default_days: 400 },
+    Policy { data_type: "voice_activity", label: "ボイス参加の記録", table: "voice_activity", column: AgeColumn::Day("day"), default_days: 400 },
+    Policy { data_type: "member_events", label: "参加・退室の記録", table: "member_events", column: AgeColumn::Timestamp("created_at"), default_days: 365 },
+    Policy { data_type: "pastes", label: "ペースト", table: "pastes", column: AgeColumn::Timestamp("created_at"), default_days: 90 },
+];
+
+pub fn start() {
+    scheduler::spawn_every("retention-cleanup", Duration::from_secs(CLEANUP_INTERVAL_SECONDS), cleanup);
+}
+
+async fn purge(policy: &Policy, guild_id: Option<i64>, days: i64) -> Result<u64> {
+    let cutoff = Utc::now() - ChronoDuration::days(days);
+    match policy.column {
+        AgeColumn::Day(col) => db::purge_before(policy.table, col, policy.data_type, guild_id, cutoff.date_naive().to_string()).await,
+        AgeColumn::Timestamp(col) => db::purge_before(policy.table, col, policy.data_type, guild_id, cutoff.timestamp()).await,
+    }
+}
+
+async fn cleanup() -> Result<()> {
+    let now = Utc::now().timestamp();
+    for policy in POLICIES {
+        // Guilds without an override share the default, then each override is applied.
+        let mut removed = purge(policy, None, policy.default_days).await?;
+        for o in db::get_retention_overrides(policy.data_type).await? {
+            removed += purge(policy, Some(o.guild_id), o.days).await?;
+        }
+        if removed > 0 { log::info!("retention: removed {} rows from {}", removed, policy.table); }
+    }
+    db::purge_global_before("dbquery_audit", "executed_at", now - DBQUERY_AUDIT_DAYS * 86400).await?;
+    db::purge_global_before("error_reports", "created_at", now - ERROR_REPORT_DAYS * 86400).await?;
+    db::purge_global_before("verify_states", "created_at", now - VERIFY_STATE_SECONDS).await?;
+    Ok(())
+}
+
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("retention").description("記録データの保持期間").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| {
+            s.name("set").description("保持期間を設定します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| {
+                    o.name("data").description("対象データ").kind(CommandOptionType::String).required(true);
+                    for p in POLICIES { o.add_string_choice(p.label, p.data_type); }
+                    o
+                })
+                .create_sub_option(|o| o.name("days").description(format!("保持日数 ({}〜{}、0で標準に戻す)", MIN_RETENTION_DAYS, MAX_RETENTION_DAYS)).kind(CommandOptionType::Integer).required(true))
+        })
+        .create_sub_option(|s| s.name("show").description("現在の保持期間を表示します").kind(CommandOptionType::SubCommand))
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    let msg = if !permissions::is_admin(member).await {
+        permissions::DENIED_MESSAGE.to_string()
+    } else {
+        match sub.name.as_str() {
+            "set" => {
+                let data = sub.options.iter().find(|o| o.name=="data").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+                let days = sub.options.iter().find(|o| o.name=="days").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+                match POLICIES.iter().find(|p| p.data_type == data) {
+                    None => "不明なデータです。".to_string(),
+                    Some(p) if days == 0 => {
+                        db::set_guild_retention(guild_id, p.data_type, None).await?;
+                        format!("{}の保持期間を標準 ({}日) に戻しました。", p.label, p.default_days)
+                    }
+                    Some(_) if !(MIN_RETENTION_DAYS..=MAX_RETENTION_DAYS).contains(&days) => format!("保持日数は{}〜{}日で指定してください。", MIN_RETENTION_DAYS, MAX_RETENTION_DAYS),
+                    Some(p) => {
+                        db::set_guild_retention(guild_id, p.data_type, Some(days)).await?;
+                        format!("{}を{}日間保持します。古い記録は定期的に削除されます。", p.label, days)
+                    }
+                }
+            }
+            "show" => {
+                let overrides = db::get_guild_retention(guild_id).await?;
+                POLICIES.iter().map(|p| match overrides.iter().find(|o| o.data_type == p.data_type) {
+                    Some(o) => format!("{}: {}日", p.label, o.days),
+                    None => format!("{}: {}日 (標準)", p.label, p.default_days),
+                }).collect::<Vec<_>>().join("\n")
+            }
+            _ => return Ok(()),
+        }
+    };
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
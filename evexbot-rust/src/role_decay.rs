@@ -0,0 +1,134 @@
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::id::{GuildId, RoleId};
+use serenity::prelude::GatewayIntents;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::member_cache;
+use crate::modlog;
+use crate::permissions;
+use crate::scheduler;
+
+/// Role holders come from the member list.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::GUILD_MEMBERS;
+
+const CHECK_INTERVAL_SECONDS: u64 = 6 * 3600;
+const MIN_DAYS: i64 = 1;
+const MAX_DAYS: i64 = 365;
+const ROLE_EDIT_DELAY_MS: u64 = 250;
+const MAX_LOGGED_MENTIONS: usize = 40;
+
+/// Start the job that takes decayed roles back.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("role-decay", Duration::from_secs(CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { decay_all(&http).await }
+    });
+}
+
+async fn decay_all(http: &Http) -> Result<()> {
+    if !crate::intents::has(REQUIRED_INTENTS) { return Ok(()); }
+    for rule in db::get_role_decay_rules(None).await? {
+        if let Err(e) = decay(http, &rule).await {
+            log::warn!("role_decay: failed to apply rule for role {} in guild {}: {}", rule.role_id, rule.guild_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Remove the role from holders with no message or voice activity in the last `days`. Members who
+/// joined inside the window keep it, since there is nothing to judge them by yet.
+async fn decay(http: &Http, rule: &db::RoleDecayRule) -> Result<()> {
+    let guild_id = GuildId(rule.guild_id as u64);
+    let role = RoleId(rule.role_id as u64);
+    let cutoff = Utc::now() - ChronoDuration::days(rule.days);
+    let active = db::get_active_user_ids(rule.guild_id, &cutoff.date_naive().to_string()).await?;
+    let stale: Vec<_> = member_cache::members(http, guild_id).await?.into_iter()
+        .filter(|m| m.roles.contains(&role) && !m.user.bot && !active.contains(&(m.user.id.0 as i64)))
+        .filter(|m| m.joined_at.map(|j| j.unix_timestamp() < cutoff.timestamp()).unwrap_or(false))
+        .map(|m| m.user.id)
+        .collect();
+    if stale.is_empty() { return Ok(()); }
+
+    let reason = format!("role decay: no activity for {} days", rule.days);
+    let mut removed = Vec::new();
+    for user_id in stale {
+        match http.remove_member_role(guild_id.0, user_id.0, role.0, Some(&reason)).await {
+            Ok(()) => removed.push(user_id),
+            Err(e) => log::warn!("role_decay: failed to remove role {} from {} in {}: {}", role.0, user_id.0, guild_id.0, e),
+        }
+        tokio::time::sleep(Duration::from_millis(ROLE_EDIT_DELAY_MS)).await;
+    }
+    if removed.is_empty() { return Ok(()); }
+
+    let mut mentions: Vec<String> = removed.iter().take(MAX_LOGGED_MENTIONS).map(|u| format!("<@{}>", u.0)).collect();
+    if removed.len() > MAX_LOGGED_MENTIONS { mentions.push(format!("ほか{}人", removed.len() - MAX_LOGGED_MENTIONS)); }
+    let mut embed = CreateEmbed::default();
+    embed.title("⏳ ロールの自動解除");
+    embed.description(format!("{}日間活動のなかった{}人から <@&{}> を外しました。\n{}", rule.days, removed.len(), role.0, mentions.join(" ")));
+    embed.color(serenity::utils::Colour::DARK_GREY);
+    embed.timestamp(Utc::now().to_rfc3339());
+    modlog::send(http, guild_id, embed).await
+}
+
+pub struct RoleDecayCommand;
+
+#[async_trait]
+impl Command for RoleDecayCommand {
+    fn name(&self) -> &'static str { "role-decay" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("活動のないメンバーからロールを自動で外します")
+            .create_option(|s| {
+                s.name("set").description("ロールと、外すまでの日数を設定します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("role").description("活動に応じたロール").kind(CommandOptionType::Role).required(true))
+                    .create_sub_option(|o| o.name("days").description("メッセージ・ボイスの記録がない日数").kind(CommandOptionType::Integer).min_int_value(MIN_DAYS).max_int_value(MAX_DAYS).required(true))
+            })
+            .create_option(|s| {
+                s.name("remove").description("自動解除をやめます").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("role").description("対象ロール").kind(CommandOptionType::Role).required(true))
+            })
+            .create_option(|s| s.name("list").description("設定を一覧します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let gid = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "set" => {
+                let role = match args.role("role") { Some(r) => r, None => return Ok(()) };
+                if role.managed { return inv.say("連携ロールは操作できません。").await; }
+                let days = args.int("days").unwrap_or(0);
+                if !(MIN_DAYS..=MAX_DAYS).contains(&days) { return inv.say(format!("日数は{}～{}で指定してください。", MIN_DAYS, MAX_DAYS)).await; }
+                db::set_role_decay_rule(gid, role.id.0 as i64, days).await?;
+                let warning = if crate::intents::has(REQUIRED_INTENTS) { "" } else { "\n⚠️ このBotはメンバー一覧を取得できない設定のため、自動解除は実行されません。" };
+                inv.say(format!("{}日間メッセージ・ボイスの記録がないメンバーから <@&{}> を外します (6時間ごとに確認)。{}", days, role.id.0, warning)).await
+            }
+            "remove" => {
+                let role = match args.role("role") { Some(r) => r, None => return Ok(()) };
+                if db::remove_role_decay_rule(gid, role.id.0 as i64).await? { inv.say(format!("<@&{}> の自動解除をやめました。", role.id.0)).await } else { inv.say("そのロールには設定がありません。").await }
+            }
+            "list" => {
+                let rules = db::get_role_decay_rules(Some(gid)).await?;
+                if rules.is_empty() { return inv.say("自動解除の設定はありません。`/role-decay set` で追加できます。").await; }
+                let lines: Vec<String> = rules.iter().map(|r| format!("<@&{}> — {}日間活動なしで解除", r.role_id, r.days)).collect();
+                inv.say(lines.join("\n")).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
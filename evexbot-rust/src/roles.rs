@@ -0,0 +1,169 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::id::{GuildId, RoleId, UserId};
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::commands::{Command, Invocation};
+use crate::permissions;
+
+/// Delay between role edits; serenity also honours rate-limit headers, this just keeps bursts small.
+const ROLE_EDIT_DELAY_MS: u64 = 250;
+const PROGRESS_UPDATE_EVERY: usize = 25;
+
+struct BulkRoleJob {
+    guild_id: GuildId,
+    invoker: UserId,
+    role_id: RoleId,
+    add: bool,
+    targets: Vec<UserId>,
+}
+
+static PENDING_JOBS: Lazy<Mutex<HashMap<u64, BulkRoleJob>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub struct RoleCommand;
+
+#[async_trait]
+impl Command for RoleCommand {
+    fn name(&self) -> &'static str { "role" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("ロール管理").create_option(|g| {
+            g.name("bulk").description("ロールの一括操作").kind(CommandOptionType::SubCommandGroup);
+            for (name, desc) in [("add", "条件に一致するメンバーにロールを付与"), ("remove", "条件に一致するメンバーからロールを削除")] {
+                g.create_sub_option(|s| {
+                    s.name(name).description(desc).kind(CommandOptionType::SubCommand)
+                        .create_sub_option(|o| o.name("role").description("対象ロール").kind(CommandOptionType::Role).required(true))
+                        .create_sub_option(|o| {
+                            o.name("filter").description("対象メンバー").kind(CommandOptionType::String).required(true)
+                                .add_string_choice("all", "all")
+                                .add_string_choice("humans", "humans")
+                                .add_string_choice("bots", "bots")
+                                .add_string_choice("has-role", "has-role")
+                        })
+                        .create_sub_option(|o| o.name("has_role").description("filter:has-role のときの条件ロール").kind(CommandOptionType::Role).required(false))
+                });
+            }
+            g
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_role_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_role_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+
+    let group = command.data.options.iter().find(|o| o.name == "bulk").ok_or_else(|| anyhow::anyhow!("unknown subcommand group"))?;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let add = sub.name == "add";
+    let role = sub.options.iter().find(|o| o.name=="role").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Role(role) => Some(role.clone()), _ => None }).ok_or_else(|| anyhow::anyhow!("role required"))?;
+    let filter = sub.options.iter().find(|o| o.name=="filter").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("all").to_string();
+    let has_role = sub.options.iter().find(|o| o.name=="has_role").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Role(role) => Some(role.id), _ => None });
+
+    if filter == "has-role" && has_role.is_none() { command.create_followup_message(&ctx.http, |m| m.content("filter:has-role の場合は has_role を指定してください。" ).ephemeral(true)).await?; return Ok(()); }
+    if role.managed { command.create_followup_message(&ctx.http, |m| m.content("連携ロールは操作できません。" ).ephemeral(true)).await?; return Ok(()); }
+
+    let members = crate::member_cache::members(&ctx.http, guild_id).await?;
+    let targets: Vec<UserId> = members.iter()
+        .filter(|m| match filter.as_str() {
+            "humans" => !m.user.bot,
+            "bots" => m.user.bot,
+            "has-role" => has_role.map(|r| m.roles.contains(&r)).unwrap_or(false),
+            _ => true,
+        })
+        // skip members already in the desired state
+        .filter(|m| m.roles.contains(&role.id) != add)
+        .map(|m| m.user.id)
+        .collect();
+
+    if targets.is_empty() { command.create_followup_message(&ctx.http, |m| m.content("対象となるメンバーがいません。" ).ephemeral(true)).await?; return Ok(()); }
+
+    let job_id = command.id.0;
+    let verb = if add { "付与" } else { "削除" };
+    let summary = format!("<@&{}> を {} 人のメンバーに{}します。よろしいですか？ (filter: {})", role.id.0, targets.len(), verb, filter);
+    PENDING_JOBS.lock().await.insert(job_id, BulkRoleJob { guild_id, invoker: command.user.id, role_id: role.id, add, targets });
+
+    command.create_followup_message(&ctx.http, |m| {
+        m.content(summary).ephemeral(true).components(|c| c.create_action_row(|ar| {
+            ar.create_button(|b| b.custom_id(format!("bulk_role_confirm:{}", job_id)).label("実行").style(ButtonStyle::Danger));
+            ar.create_button(|b| b.custom_id(format!("bulk_role_cancel:{}", job_id)).label("キャンセル").style(ButtonStyle::Secondary))
+        }))
+    }).await?;
+    Ok(())
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let (action, id) = comp.data.custom_id.split_once(':').ok_or_else(|| anyhow::anyhow!("malformed custom_id"))?;
+    let job_id: u64 = id.parse()?;
+
+    let job = {
+        let mut jobs = PENDING_JOBS.lock().await;
+        match jobs.get(&job_id) {
+            Some(j) if j.invoker != comp.user.id => None,
+            Some(_) => jobs.remove(&job_id),
+            None => None,
+        }
+    };
+    let job = match job {
+        Some(j) => j,
+        None => {
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("この操作は期限切れか、実行権限がありません。").ephemeral(true))).await?;
+            return Ok(());
+        }
+    };
+
+    if action == "bulk_role_cancel" {
+        comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.content("キャンセルしました。").components(|c| c))).await?;
+        return Ok(());
+    }
+
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.content(format!("実行中... 0/{}", job.targets.len())).components(|c| c))).await?;
+
+    let http = ctx.http.clone();
+    let comp = comp.clone();
+    tokio::spawn(async move {
+        let (done, failed) = run_job(&http, &comp, &job).await;
+        let verb = if job.add { "付与" } else { "削除" };
+        let _ = comp.edit_original_interaction_response(&http, |r| r.content(format!("完了しました: <@&{}> を {} 人に{}しました。(失敗: {} 人)", job.role_id.0, done, verb, failed))).await;
+    });
+    Ok(())
+}
+
+async fn run_job(http: &Arc<Http>, comp: &MessageComponentInteraction, job: &BulkRoleJob) -> (usize, usize) {
+    let reason = format!("bulk role operation by {}", job.invoker.0);
+    let total = job.targets.len();
+    let mut done = 0usize;
+    let mut failed = 0usize;
+    for (i, user_id) in job.targets.iter().enumerate() {
+        let res = if job.add {
+            http.add_member_role(job.guild_id.0, user_id.0, job.role_id.0, Some(&reason)).await
+        } else {
+            http.remove_member_role(job.guild_id.0, user_id.0, job.role_id.0, Some(&reason)).await
+        };
+        match res { Ok(_) => done += 1, Err(_) => failed += 1 }
+
+        if (i + 1) % PROGRESS_UPDATE_EVERY == 0 {
+            let _ = comp.edit_original_interaction_response(http, |r| r.content(format!("実行中... {}/{}", i + 1, total))).await;
+        }
+        tokio::time::sleep(Duration::from_millis(ROLE_EDIT_DELAY_MS)).await;
+    }
+    (done, failed)
+}
@@ -0,0 +1,150 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::ChannelType;
+use serenity::model::id::{GuildId, RoleId};
+use serenity::prelude::*;
+use std::time::Duration;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::member_cache;
+use crate::permissions;
+
+pub const ACCEPT_BUTTON_ID: &str = "rules_accept";
+const DEFAULT_RULES: &str = "サーバーのルールを読み、同意する場合は下のボタンを押してください。";
+const ROLE_EDIT_DELAY_MS: u64 = 250;
+const RECENT_DAYS: i64 = 7;
+
+pub struct RulesCommand;
+
+#[async_trait]
+impl Command for RulesCommand {
+    fn name(&self) -> &'static str { "rules" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("サーバールールへの同意")
+            .create_option(|s| {
+                s.name("panel").description("「同意する」ボタン付きのルールパネルを投稿します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("channel").description("投稿先").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(true))
+                    .create_sub_option(|o| o.name("role").description("同意したメンバーに付与するロール").kind(CommandOptionType::Role).required(true))
+                    .create_sub_option(|o| o.name("text").description("ルール本文 (\\n で改行)").kind(CommandOptionType::String).required(false))
+            })
+            .create_option(|s| s.name("reaccept").description("ルール変更後、全員に再同意を求めます (ロールを外します)").kind(CommandOptionType::SubCommand))
+            .create_option(|s| s.name("stats").description("ルールへの同意状況を表示します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "panel" => post_panel(inv, guild_id, Args::new(&sub.options)).await,
+            "reaccept" => reaccept(inv, guild_id).await,
+            "stats" => stats(inv, guild_id).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn post_panel(inv: &Invocation<'_>, guild_id: GuildId, args: Args<'_>) -> Result<()> {
+    let (channel, role) = match (args.channel("channel"), args.role("role")) {
+        (Some(c), Some(r)) => (c.id, r.id),
+        _ => return Ok(()),
+    };
+    let text = args.str("text").map(|t| t.replace("\\n", "\n")).unwrap_or_else(|| DEFAULT_RULES.to_string());
+    let panel = channel.send_message(&inv.ctx.http, |m| {
+        m.embed(|e| e.title("📜 サーバールール").description(text).color(serenity::utils::Colour::BLURPLE))
+            .components(|c| c.create_action_row(|row| row.create_button(|b| b.custom_id(ACCEPT_BUTTON_ID).label("同意する").emoji('✅').style(ButtonStyle::Success))))
+    }).await?;
+    db::set_rules_panel(guild_id.0 as i64, channel.0 as i64, panel.id.0 as i64, role.0 as i64).await?;
+    inv.say(format!("<#{}> にルールパネルを投稿しました。同意したメンバーには <@&{}> が付与されます。", channel.0, role.0)).await
+}
+
+/// Start a new rules version and take the role back from everyone until they accept again.
+async fn reaccept(inv: &Invocation<'_>, guild_id: GuildId) -> Result<()> {
+    let settings = match db::get_rules_settings(guild_id.0 as i64).await? {
+        Some(s) => s,
+        None => return inv.say("ルールパネルがありません。先に `/rules panel` で投稿してください。").await,
+    };
+    let version = db::bump_rules_version(guild_id.0 as i64).await?;
+    let role = match settings.role_id { Some(r) => RoleId(r as u64), None => return inv.say(format!("ルールを v{} に更新しました。", version)).await };
+    let holders: Vec<_> = member_cache::members(&inv.ctx.http, guild_id).await?.into_iter().filter(|m| m.roles.contains(&role)).map(|m| m.user.id).collect();
+    inv.say(format!("ルールを v{} に更新しました。{} 人から <@&{}> を外し、再同意を待ちます。", version, holders.len(), role.0)).await?;
+
+    let http = inv.ctx.http.clone();
+    tokio::spawn(async move {
+        for user_id in holders {
+            if let Err(e) = http.remove_member_role(guild_id.0, user_id.0, role.0, Some("rules re-acceptance")).await {
+                log::warn!("rules: failed to remove role from {} in {}: {}", user_id.0, guild_id.0, e);
+            }
+            tokio::time::sleep(Duration::from_millis(ROLE_EDIT_DELAY_MS)).await;
+        }
+    });
+    Ok(())
+}
+
+async fn stats(inv: &Invocation<'_>, guild_id: GuildId) -> Result<()> {
+    let settings = match db::get_rules_settings(guild_id.0 as i64).await? {
+        Some(s) => s,
+        None => return inv.say("ルールパネルがありません。先に `/rules panel` で投稿してください。").await,
+    };
+    let members: std::collections::HashSet<i64> = member_cache::members(&inv.ctx.http, guild_id).await?
+        .into_iter().filter(|m| !m.user.bot).map(|m| m.user.id.0 as i64).collect();
+    // Only members still in the server count towards the rate.
+    let acceptances: Vec<_> = db::get_rules_acceptances(guild_id.0 as i64).await?.into_iter().filter(|a| members.contains(&a.user_id)).collect();
+    let current = acceptances.iter().filter(|a| a.version == settings.version).count();
+    let outdated = acceptances.len() - current;
+    let since = Utc::now().timestamp() - RECENT_DAYS * 86400;
+    let recent = acceptances.iter().filter(|a| a.version == settings.version && a.accepted_at >= since).count();
+    let rate = if members.is_empty() { 0.0 } else { current as f64 * 100.0 / members.len() as f64 };
+
+    let panel = match (settings.channel_id, settings.message_id) {
+        (Some(c), Some(m)) => format!("https://discord.com/channels/{}/{}/{}", guild_id.0, c, m),
+        _ => "-".to_string(),
+    };
+    inv.command.create_followup_message(&inv.ctx.http, |m| m.ephemeral(true).embed(|e| {
+        e.title(format!("ルール同意状況 (v{})", settings.version))
+            .field("同意率", format!("{:.1}% ({}/{}人)", rate, current, members.len()), true)
+            .field(format!("直近{}日の同意", RECENT_DAYS), format!("{}人", recent), true)
+            .field("旧バージョンのみ同意", format!("{}人", outdated), true)
+            .field("パネル", panel, false)
+            .color(serenity::utils::Colour::BLURPLE)
+    })).await?;
+    Ok(())
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let guild_id = comp.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+    let msg = match db::get_rules_settings(guild_id.0 as i64).await? {
+        None => "このサーバーではルールパネルが設定されていません。".to_string(),
+        Some(settings) => {
+            let already = db::get_rules_acceptance(guild_id.0 as i64, comp.user.id.0 as i64).await? == Some(settings.version);
+            let has_role = match (settings.role_id, comp.member.as_ref()) {
+                (Some(r), Some(m)) => m.roles.contains(&RoleId(r as u64)),
+                _ => true,
+            };
+            if already && has_role {
+                "すでにルールに同意しています。".to_string()
+            } else {
+                db::accept_rules(guild_id.0 as i64, comp.user.id.0 as i64, settings.version, Utc::now().timestamp()).await?;
+                if let Some(r) = settings.role_id {
+                    ctx.http.add_member_role(guild_id.0, comp.user.id.0, r as u64, Some("rules accepted")).await?;
+                }
+                "ルールに同意しました。ようこそ！".to_string()
+            }
+        }
+    };
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
@@ -1,14 +1,18 @@
 use anyhow::Result;
 use reqwest::Client;
 use serde_json::Value;
-use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
-use serenity::prelude::*;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
 
-const API_BASE_URLS_PY: &str = "https://py-sandbox.evex.land/";
-const API_BASE_URLS_JS: &str = "https://js-sandbox.evex.land/";
+use crate::commands::{Command, Defer, Invocation};
+use crate::status;
+
+pub const API_BASE_URLS_PY: &str = "https://py-sandbox.evex.land/";
+pub const API_BASE_URLS_JS: &str = "https://js-sandbox.evex.land/";
 const MAX_CODE_LENGTH: usize = 2000;
 
-fn validate_code(code: &str, language: &str) -> Result<(), &'static str> {
+pub fn validate_code(code: &str, language: &str) -> Result<(), &'static str> {
     if code.is_empty() { return Err("実行するコードを入力してください。"); }
     if code.len() > MAX_CODE_LENGTH { return Err("コードは2000文字以内で指定してください。"); }
     let dangerous_python = ["import os", "import sys", "import subprocess", "__import__", "eval(", "exec(", "open("];
@@ -19,39 +23,52 @@ fn validate_code(code: &str, language: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
-pub async fn handle_sandbox(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
-    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
-    let language = command.data.options.get(0).and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
-    let code = command.data.options.get(1).and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+pub struct SandboxCommand;
+
+#[async_trait]
+impl Command for SandboxCommand {
+    fn name(&self) -> &'static str { "sandbox" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("コードをサンドボックスで実行し、結果を返します。")
+            .create_option(|o| o.name("language").description("言語: python|javascript").kind(CommandOptionType::String).required(true))
+            .create_option(|o| o.name("code").description("実行するコード").kind(CommandOptionType::String).required(true))
+    }
+
+    fn defer(&self) -> Defer { Defer::Public }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let language = inv.args.str("language").unwrap_or("");
+        let code = inv.args.str("code").unwrap_or("");
 
-    if language != "python" && language != "javascript" { command.create_followup_message(&ctx.http, |m| m.content("サポートされていない言語です。python または javascript を指定してください。" )).await?; return Ok(()); }
-    if let Err(e) = validate_code(code, language) { command.create_followup_message(&ctx.http, |m| m.content(e)).await?; return Ok(()); }
+        if language != "python" && language != "javascript" { return inv.say("サポートされていない言語です。python または javascript を指定してください。").await; }
+        if let Err(e) = validate_code(code, language) { return inv.say(e).await; }
 
+        inv.say(run_code(language, code).await).await
+    }
+}
+
+/// Run `code` on the evex.land sandbox and format the result (or the failure) as a reply.
+/// `language` must be python or javascript.
+pub async fn run_code(language: &str, code: &str) -> String {
+    let service = if language == "python" { status::SANDBOX_PYTHON } else { status::SANDBOX_JAVASCRIPT };
+    if status::is_down(service) { return status::BACKEND_DOWN_MESSAGE.to_string(); }
     let url = if language == "python" { API_BASE_URLS_PY } else { API_BASE_URLS_JS };
     let client = Client::new();
     let resp = client.post(url).json(&serde_json::json!({"code": code})).send().await;
     match resp {
-        Ok(r) => {
-            if r.status().is_success() {
-                let txt = r.text().await?;
-                match serde_json::from_str::<Value>(&txt) {
-                    Ok(json) => {
-                        let exitcode = json.get("exitcode").and_then(|v| v.as_i64()).unwrap_or(0);
-                        let message = json.get("message").and_then(|v| v.as_str()).unwrap_or("");
-                        let out = format!("終了コード: {}\n出力:\n```{}```", exitcode, if message.is_empty() { "(出力なし)" } else { message });
-                        command.create_followup_message(&ctx.http, |m| m.content(out)).await?;
-                    }
-                    Err(_) => {
-                        command.create_followup_message(&ctx.http, |m| m.content("APIからの応答の解析に失敗しました。" )).await?;
-                    }
+        Ok(r) if r.status().is_success() => {
+            let txt = match r.text().await { Ok(t) => t, Err(e) => return format!("API通信エラー: {}", e) };
+            match serde_json::from_str::<Value>(&txt) {
+                Ok(json) => {
+                    let exitcode = json.get("exitcode").and_then(|v| v.as_i64()).unwrap_or(0);
+                    let message = json.get("message").and_then(|v| v.as_str()).unwrap_or("");
+                    format!("終了コード: {}\n出力:\n```{}```", exitcode, if message.is_empty() { "(出力なし)" } else { message })
                 }
-            } else {
-                command.create_followup_message(&ctx.http, |m| m.content("コードの実行に失敗しました。" )).await?;
+                Err(_) => "APIからの応答の解析に失敗しました。".to_string(),
             }
         }
-        Err(e) => {
-            command.create_followup_message(&ctx.http, |m| m.content(format!("API通信エラー: {}", e))).await?;
-        }
+        Ok(_) => "コードの実行に失敗しました。".to_string(),
+        Err(e) => format!("API通信エラー: {}", e),
     }
-    Ok(())
 }
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+static STARTED: Lazy<Mutex<HashSet<&'static str>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Run `job` every `period` in the background. Each job name is started only once,
+/// so this is safe to call from `ready`, which fires again on reconnects. Runs are
+/// skipped while the bot is in maintenance mode or this instance is a follower.
+pub fn spawn_every<F, Fut>(name: &'static str, period: Duration, job: F)
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    if !STARTED.lock().unwrap().insert(name) {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if crate::maintenance::is_active() || !crate::leader::is_leader() {
+                continue;
+            }
+            if let Err(e) = job().await {
+                log::warn!("scheduler: job {} failed: {}", name, e);
+            }
+        }
+    });
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::prelude::*;
+
+use crate::antiraid;
+use crate::api;
+use crate::bus;
+use crate::chart;
+use crate::command_access;
+use crate::commands::{Command, Invocation};
+use crate::leveling;
+use crate::permissions;
+use crate::retention;
+use crate::vault;
+
+/// `/config` groups per-guild settings owned by several modules under one command.
+pub struct ConfigCommand;
+
+#[async_trait]
+impl Command for ConfigCommand {
+    fn name(&self) -> &'static str { "config" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("サーバー設定")
+            .create_option(|g| vault::build_config_group(g))
+            .create_option(|g| api::build_config_group(g))
+            .create_option(|g| chart::build_config_group(g))
+            .create_option(|g| chart::build_language_group(g))
+            .create_option(|g| retention::build_config_group(g))
+            .create_option(|g| command_access::build_config_group(g))
+            .create_option(|g| permissions::build_config_group(g))
+            .create_option(|g| leveling::build_config_group(g))
+            .create_option(|g| antiraid::build_config_group(g))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_config_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_config_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let group = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand group required"))?;
+    let result = match group.name.as_str() {
+        "apikey" => vault::handle_config_group(ctx, command, group).await,
+        "apitoken" => api::handle_config_group(ctx, command, group).await,
+        "chart" => chart::handle_config_group(ctx, command, group).await,
+        "language" => chart::handle_language_group(ctx, command, group).await,
+        "retention" => retention::handle_config_group(ctx, command, group).await,
+        "command-permissions" => command_access::handle_config_group(ctx, command, group).await,
+        "admin-roles" => permissions::handle_config_group(ctx, command, group).await,
+        "leveling" => leveling::handle_config_group(ctx, command, group).await,
+        "antiraid" => antiraid::handle_config_group(ctx, command, group).await,
+        _ => Ok(()),
+    };
+    // Each group checks permissions itself with the same rule used here, so only changes by
+    // members who could make them are announced; show/list only read.
+    let subcommand = group.options.first().map(|s| s.name.clone()).unwrap_or_default();
+    if let (Ok(()), Some(guild_id), Some(member)) = (&result, command.guild_id, command.member.as_ref()) {
+        let allowed = if group.name == "admin-roles" { permissions::has_manage_guild(member) } else { permissions::is_admin(member).await };
+        if !["show", "list"].contains(&subcommand.as_str()) && allowed {
+            bus::publish(bus::Event::SettingsChanged { guild_id, group: group.name.clone(), subcommand, user: command.user.clone() });
+        }
+    }
+    result
+}
@@ -0,0 +1,270 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateComponents, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::ChannelType;
+use serenity::model::id::{GuildId, UserId};
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::permissions;
+
+const WIZARD_TTL: Duration = Duration::from_secs(15 * 60);
+/// Select menus hold at most 25 options; one is reserved for "none". Guilds with more text
+/// channels page through them.
+const CHANNELS_PER_PAGE: usize = 24;
+const INCREMENTS: [i64; 6] = [10, 50, 100, 200, 500, 1000];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Step { Welcome, Leave, Intro, ModLog, Features, Confirm }
+
+impl Step {
+    const ALL: [Step; 6] = [Step::Welcome, Step::Leave, Step::Intro, Step::ModLog, Step::Features, Step::Confirm];
+
+    fn index(self) -> usize {
+        Step::ALL.iter().position(|s| *s == self).unwrap_or(0)
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Step::Welcome => "参加メッセージ",
+            Step::Leave => "退室メッセージ",
+            Step::Intro => "自己紹介",
+            Step::ModLog => "モデレーションログ",
+            Step::Features => "その他の機能",
+            Step::Confirm => "確認",
+        }
+    }
+}
+
+/// Settings collected by the wizard; nothing is written until the final save.
+#[derive(Clone)]
+struct Wizard {
+    guild_id: GuildId,
+    invoker: UserId,
+    step: Step,
+    channels: Vec<(u64, String)>,
+    /// Page of `channels` shown in the current step's channel menu.
+    page: usize,
+    welcome_channel: Option<u64>,
+    increment: i64,
+    leave_channel: Option<u64>,
+    intro_auto_thread: bool,
+    modlog_channel: Option<u64>,
+    daily_summary: bool,
+}
+
+static WIZARDS: Lazy<Mutex<HashMap<u64, (Instant, Wizard)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub struct SetupCommand;
+
+#[async_trait]
+impl Command for SetupCommand {
+    fn name(&self) -> &'static str { "setup" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("参加・退室メッセージや自己紹介、ログなどの設定をまとめて行います")
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_setup_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn load(http: &Http, guild_id: GuildId, invoker: UserId) -> Result<Wizard> {
+    let gid = guild_id.0 as i64;
+    let mut channels: Vec<_> = guild_id.channels(http).await?.into_values().filter(|c| c.kind == ChannelType::Text).collect();
+    channels.sort_by_key(|c| c.position);
+    let welcome = db::get_welcome_settings(gid).await?;
+    let leave = db::get_leave_settings(gid).await?;
+    Ok(Wizard {
+        guild_id,
+        invoker,
+        step: Step::Welcome,
+        channels: channels.iter().map(|c| (c.id.0, c.name.clone())).collect(),
+        page: 0,
+        welcome_channel: welcome.channel_id.filter(|_| welcome.is_enabled).map(|c| c as u64),
+        increment: welcome.member_increment,
+        leave_channel: leave.channel_id.filter(|_| leave.is_enabled).map(|c| c as u64),
+        intro_auto_thread: db::get_intro_auto_thread(gid).await?,
+        modlog_channel: db::get_modlog_channel(gid).await?.map(|c| c as u64),
+        daily_summary: db::get_daily_summary_enabled(gid).await?,
+    })
+}
+
+fn channel_label(channel: Option<u64>) -> String {
+    channel.map(|c| format!("<#{}>", c)).unwrap_or_else(|| "無効".to_string())
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "ON" } else { "OFF" }
+}
+
+fn render_embed(w: &Wizard) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("サーバー設定 ({}/{}) {}", w.step.index() + 1, Step::ALL.len(), w.step.title()));
+    let description = match w.step {
+        Step::Welcome => format!("新しいメンバーの参加時にメッセージを送るチャンネルと、記念メッセージを送るメンバー数の間隔を選んでください。\n\nチャンネル: {}\n間隔: {}人ごと", channel_label(w.welcome_channel), w.increment),
+        Step::Leave => format!("メンバーの退室時にメッセージを送るチャンネルを選んでください。\n\nチャンネル: {}", channel_label(w.leave_channel)),
+        Step::Intro => format!("自己紹介ごとに返信用スレッドを作成し、チャンネルへの直接返信をスレッドへ移動します。\n\n返信用スレッド: {}", on_off(w.intro_auto_thread)),
+        Step::ModLog => format!("モデレーションの記録を送るチャンネルを選んでください。\n\nチャンネル: {}", channel_label(w.modlog_channel)),
+        Step::Features => format!("モデレーションログに前日の参加・退室のまとめを毎日投稿します。\n\n日次サマリー: {}{}", on_off(w.daily_summary), if w.daily_summary && w.modlog_channel.is_none() { "\n⚠️ モデレーションログのチャンネルが未設定のため投稿されません。" } else { "" }),
+        Step::Confirm => format!(
+            "以下の内容で保存します。\n\n参加メッセージ: {} ({}人ごと)\n退室メッセージ: {}\n自己紹介の返信用スレッド: {}\nモデレーションログ: {}\n日次サマリー: {}",
+            channel_label(w.welcome_channel), w.increment, channel_label(w.leave_channel), on_off(w.intro_auto_thread), channel_label(w.modlog_channel), on_off(w.daily_summary)
+        ),
+    };
+    embed.description(description);
+    embed.color(serenity::utils::Colour::BLURPLE);
+    embed.footer(|f| f.text("EvexBot | Setup"));
+    embed
+}
+
+fn page_count(w: &Wizard) -> usize {
+    w.channels.len().div_ceil(CHANNELS_PER_PAGE).max(1)
+}
+
+fn channel_select<'a>(c: &'a mut CreateComponents, id: u64, field: &str, w: &Wizard, selected: Option<u64>) -> &'a mut CreateComponents {
+    let pages = page_count(w);
+    let placeholder = if pages > 1 { format!("チャンネルを選択 ({}/{}ページ)", w.page + 1, pages) } else { "チャンネルを選択".to_string() };
+    c.create_action_row(|ar| ar.create_select_menu(|s| {
+        s.custom_id(format!("setup:{}:{}", id, field)).placeholder(placeholder).options(|o| {
+            o.create_option(|opt| opt.label("無効").value("none").default_selection(selected.is_none()));
+            for (channel_id, name) in w.channels.iter().skip(w.page * CHANNELS_PER_PAGE).take(CHANNELS_PER_PAGE) {
+                o.create_option(|opt| opt.label(format!("#{}", name)).value(channel_id.to_string()).default_selection(selected == Some(*channel_id)));
+            }
+            o
+        })
+    }));
+    if pages > 1 {
+        c.create_action_row(|ar| {
+            ar.create_button(|b| b.custom_id(format!("setup:{}:prev_page", id)).label("前のページ").style(ButtonStyle::Secondary).disabled(w.page == 0));
+            ar.create_button(|b| b.custom_id(format!("setup:{}:next_page", id)).label("次のページ").style(ButtonStyle::Secondary).disabled(w.page + 1 >= pages))
+        });
+    }
+    c
+}
+
+fn render_components<'a>(c: &'a mut CreateComponents, id: u64, w: &Wizard) -> &'a mut CreateComponents {
+    match w.step {
+        Step::Welcome => {
+            channel_select(c, id, "welcome_channel", w, w.welcome_channel);
+            c.create_action_row(|ar| ar.create_select_menu(|s| {
+                s.custom_id(format!("setup:{}:increment", id)).placeholder("メンバー数の間隔").options(|o| {
+                    for inc in INCREMENTS {
+                        o.create_option(|opt| opt.label(format!("{}人ごと", inc)).value(inc.to_string()).default_selection(w.increment == inc));
+                    }
+                    o
+                })
+            }));
+        }
+        Step::Leave => { channel_select(c, id, "leave_channel", w, w.leave_channel); }
+        Step::Intro => {
+            c.create_action_row(|ar| ar.create_button(|b| b.custom_id(format!("setup:{}:toggle_intro", id)).label(format!("返信用スレッド: {}", on_off(w.intro_auto_thread))).style(if w.intro_auto_thread { ButtonStyle::Success } else { ButtonStyle::Secondary })));
+        }
+        Step::ModLog => { channel_select(c, id, "modlog_channel", w, w.modlog_channel); }
+        Step::Features => {
+            c.create_action_row(|ar| ar.create_button(|b| b.custom_id(format!("setup:{}:toggle_summary", id)).label(format!("日次サマリー: {}", on_off(w.daily_summary))).style(if w.daily_summary { ButtonStyle::Success } else { ButtonStyle::Secondary })));
+        }
+        Step::Confirm => {}
+    }
+    c.create_action_row(|ar| {
+        ar.create_button(|b| b.custom_id(format!("setup:{}:back", id)).label("戻る").style(ButtonStyle::Secondary).disabled(w.step == Step::Welcome));
+        if w.step == Step::Confirm {
+            ar.create_button(|b| b.custom_id(format!("setup:{}:save", id)).label("保存").style(ButtonStyle::Success));
+        } else {
+            ar.create_button(|b| b.custom_id(format!("setup:{}:next", id)).label("次へ").style(ButtonStyle::Primary));
+        }
+        ar.create_button(|b| b.custom_id(format!("setup:{}:cancel", id)).label("キャンセル").style(ButtonStyle::Danger))
+    })
+}
+
+async fn handle_setup_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+
+    let wizard = load(&ctx.http, guild_id, command.user.id).await?;
+    let id = command.id.0;
+    let embed = render_embed(&wizard);
+    command.create_followup_message(&ctx.http, |m| m.ephemeral(true).embed(|e| { *e = embed; e }).components(|c| render_components(c, id, &wizard))).await?;
+    {
+        let mut wizards = WIZARDS.lock().await;
+        wizards.retain(|_, (created, _)| created.elapsed() < WIZARD_TTL);
+        wizards.insert(id, (Instant::now(), wizard));
+    }
+    Ok(())
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let mut parts = comp.data.custom_id.splitn(3, ':').skip(1);
+    let id: u64 = parts.next().unwrap_or("").parse()?;
+    let action = parts.next().unwrap_or("").to_string();
+    let value = comp.data.values.first().cloned();
+
+    let wizard = {
+        let mut wizards = WIZARDS.lock().await;
+        match wizards.get_mut(&id) {
+            Some((_, w)) if w.invoker == comp.user.id => {
+                let next = |s: Step, d: isize| Step::ALL[(s.index() as isize + d).clamp(0, Step::ALL.len() as isize - 1) as usize];
+                let parse_channel = |v: &Option<String>| v.as_deref().and_then(|v| v.parse::<u64>().ok());
+                match action.as_str() {
+                    "welcome_channel" => w.welcome_channel = parse_channel(&value),
+                    "increment" => if let Some(inc) = value.as_deref().and_then(|v| v.parse().ok()) { w.increment = inc },
+                    "leave_channel" => w.leave_channel = parse_channel(&value),
+                    "modlog_channel" => w.modlog_channel = parse_channel(&value),
+                    "toggle_intro" => w.intro_auto_thread = !w.intro_auto_thread,
+                    "toggle_summary" => w.daily_summary = !w.daily_summary,
+                    "prev_page" => w.page = w.page.saturating_sub(1),
+                    "next_page" => w.page = (w.page + 1).min(page_count(w) - 1),
+                    "next" => { w.step = next(w.step, 1); w.page = 0; }
+                    "back" => { w.step = next(w.step, -1); w.page = 0; }
+                    _ => {}
+                }
+                let w = w.clone();
+                if action == "cancel" { wizards.remove(&id); }
+                Some(w)
+            }
+            _ => None,
+        }
+    };
+    let wizard = match wizard {
+        Some(w) => w,
+        None => {
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("この操作は期限切れか、実行権限がありません。もう一度 /setup を実行してください。").ephemeral(true))).await?;
+            return Ok(());
+        }
+    };
+
+    match action.as_str() {
+        "cancel" => {
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.content("設定をキャンセルしました。変更は保存されていません。").set_embeds(Vec::new()).components(|c| c))).await?;
+        }
+        "save" => {
+            save(&wizard).await?;
+            // Only now: if the save failed, the wizard stays open and the button can be retried.
+            WIZARDS.lock().await.remove(&id);
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.content("設定を保存しました!").set_embed(render_embed(&wizard)).components(|c| c))).await?;
+        }
+        _ => {
+            let embed = render_embed(&wizard);
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| d.set_embed(embed).components(|c| render_components(c, id, &wizard)))).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn save(w: &Wizard) -> Result<()> {
+    let as_id = |c: Option<u64>| c.map(|c| c as i64);
+    db::save_setup(w.guild_id.0 as i64, as_id(w.welcome_channel), w.increment, as_id(w.leave_channel), w.intro_auto_thread, as_id(w.modlog_channel), w.daily_summary).await
+}
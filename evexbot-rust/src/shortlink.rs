@@ -0,0 +1,114 @@
+use anyhow::Result;
+use axum::extract::Path;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use regex::Regex;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+use crate::report::Report;
+use crate::web::{self, WebState};
+
+static SLUG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z0-9_-]{1,32}$").unwrap());
+const RANDOM_SLUG_LENGTH: usize = 6;
+
+pub fn routes() -> Router<WebState> {
+    Router::new().route("/s/:slug", get(follow))
+}
+
+/// Link previews (Discord, Slack, Twitter...) fetch the URL as soon as it's posted.
+fn is_crawler(headers: &HeaderMap) -> bool {
+    let agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()).unwrap_or("").to_lowercase();
+    agent.is_empty() || agent.contains("bot") || agent.contains("preview")
+}
+
+async fn follow(Path(slug): Path<String>, headers: HeaderMap) -> Response {
+    let url = if is_crawler(&headers) { db::get_shortlink_url(&slug).await } else { db::click_shortlink(&slug, Utc::now().timestamp()).await };
+    match url {
+        Ok(Some(url)) => Redirect::temporary(&url).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "database error").into_response(),
+    }
+}
+
+fn short_url(slug: &str) -> String {
+    format!("{}/s/{}", web::public_base_url(), slug)
+}
+
+pub struct ShortlinkCommand;
+
+#[async_trait]
+impl Command for ShortlinkCommand {
+    fn name(&self) -> &'static str { "shortlink" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("クリック数を計測できる短縮リンク")
+            .create_option(|s| {
+                s.name("create").description("短縮リンクを作成します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("url").description("リンク先 (https://...)").kind(CommandOptionType::String).required(true))
+                    .create_sub_option(|o| o.name("slug").description("リンクの末尾 (英数字 - _、省略でランダム)").kind(CommandOptionType::String).required(false))
+            })
+            .create_option(|s| {
+                s.name("delete").description("短縮リンクを削除します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("slug").description("リンクの末尾").kind(CommandOptionType::String).required(true))
+            })
+            .create_option(|s| s.name("stats").description("短縮リンクごとのクリック数を表示します").kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "create" => {
+                let url = args.str("url").unwrap_or("").trim();
+                if !(url.starts_with("https://") || url.starts_with("http://")) || url.contains(char::is_whitespace) {
+                    return inv.say("URL は http:// または https:// で始めてください。").await;
+                }
+                let slug = match args.str("slug").map(str::trim) {
+                    Some(s) if !SLUG_RE.is_match(s) => return inv.say("リンクの末尾には英数字・`-`・`_` を32文字以内で指定してください。").await,
+                    Some(s) => s.to_string(),
+                    None => rand::thread_rng().sample_iter(&Alphanumeric).take(RANDOM_SLUG_LENGTH).map(char::from).collect(),
+                };
+                if !db::create_shortlink(&slug, guild_id, url, command.user.id.0 as i64, Utc::now().timestamp()).await? {
+                    return inv.say(format!("`{}` はすでに使われています。", slug)).await;
+                }
+                inv.say(format!("短縮リンクを作成しました: {}\nリンク先: <{}>", short_url(&slug), url)).await
+            }
+            "delete" => {
+                let slug = args.str("slug").unwrap_or("").trim();
+                if db::delete_shortlink(guild_id, slug).await? { inv.say(format!("`{}` を削除しました。", slug)).await } else { inv.say("その短縮リンクは見つかりません。").await }
+            }
+            "stats" => {
+                let links = db::get_shortlinks(guild_id).await?;
+                let report = Report::embed("shortlinks.txt", "短縮リンクのクリック数", serenity::utils::Colour::BLURPLE).ephemeral(true);
+                let report = if links.is_empty() {
+                    report.line("短縮リンクはまだありません。`/shortlink create` で作成できます。")
+                } else {
+                    report.lines(links.iter().map(|l| {
+                        let last = l.last_clicked_at.map(|t| format!(" (最終 <t:{}:R>)", t)).unwrap_or_default();
+                        format!("**{}** クリック{} — {}\n<{}> by <@{}> <t:{}:d>", l.clicks, last, short_url(&l.slug), l.url, l.created_by, l.created_at)
+                    }))
+                };
+                report.send(&inv.ctx.http, command).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Command, Defer, Invocation};
+use crate::db;
+use crate::owner;
+use crate::permissions;
+use crate::scheduler;
+
+/// The job checks hourly but only the first snapshot of each day is kept.
+const SNAPSHOT_CHECK_INTERVAL_SECONDS: u64 = 3600;
+/// Several years of daily rows fit comfortably in this.
+const MAX_CSV_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Start the job that records each guild's member count once a day.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("member-snapshots", Duration::from_secs(SNAPSHOT_CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { record_live(&http).await }
+    });
+}
+
+async fn record_live(http: &Http) -> Result<()> {
+    let today = Utc::now().date_naive().to_string();
+    for guild in http.get_guilds(None, None).await? {
+        let count = match http.get_guild_with_counts(guild.id.0).await {
+            Ok(g) => g.approximate_member_count.unwrap_or(0) as i64,
+            Err(e) => {
+                log::warn!("snapshots: failed to count members of {}: {}", guild.id.0, e);
+                continue;
+            }
+        };
+        if count == 0 { continue; }
+        db::insert_member_snapshots(guild.id.0 as i64, &[(today.clone(), count)], "live", false).await?;
+    }
+    Ok(())
+}
+
+pub async fn backfill(http: &Http, guild_id: GuildId, overwrite: bool) -> Result<(u64, usize)> {
+    let join_dates = crate::member_cache::join_dates(http, guild_id).await?;
+    let departed = db::get_departed_invite_joins(guild_id.0 as i64).await?;
+    // Today is left to the live snapshot.
+    let yesterday = Utc::now().date_naive().pred_opt().unwrap();
+    let snapshots = crate::stats::reconstruct_counts(&join_dates, &departed, yesterday);
+    let written = db::insert_member_snapshots(guild_id.0 as i64, &snapshots, "backfill", overwrite).await?;
+    Ok((written, snapshots.len()))
+}
+
+pub struct BackfillSnapshotsCommand;
+
+#[async_trait]
+impl Command for BackfillSnapshotsCommand {
+    fn name(&self) -> &'static str { "backfill-snapshots" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("オーナー用: 参加日からメンバー数の履歴を再構築します")
+            .create_option(|o| o.name("guild_id").description("対象サーバーID (デフォルト: このサーバー)").kind(CommandOptionType::String).required(false))
+            .create_option(|o| o.name("overwrite").description("既存の記録を上書きする").kind(CommandOptionType::Boolean).required(false))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_backfill_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_backfill_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    if !owner::is_owner(command.user.id) { command.create_followup_message(&ctx.http, |m| m.content("権限がありません。" ).ephemeral(true)).await?; return Ok(()); }
+
+    let guild_id = command.data.options.iter().find(|o| o.name=="guild_id").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).and_then(|s| s.trim().parse::<u64>().ok()).map(GuildId).or(command.guild_id);
+    let guild_id = match guild_id { Some(g) => g, None => { command.create_followup_message(&ctx.http, |m| m.content("サーバーIDを指定してください。" ).ephemeral(true)).await?; return Ok(()); } };
+    let overwrite = command.data.options.iter().find(|o| o.name=="overwrite").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let (written, days) = backfill(&ctx.http, guild_id, overwrite).await?;
+    log::info!("snapshots: backfilled guild {} ({} of {} days written) by {}", guild_id.0, written, days, command.user.id.0);
+    command.create_followup_message(&ctx.http, |m| m.content(format!("{}日分の履歴を再構築し、{}件を書き込みました。\n※ 退出済みメンバーは招待追跡の記録がある場合のみ反映されます。", days, written)).ephemeral(true)).await?;
+    Ok(())
+}
+
+fn parse_day(field: &str) -> Option<NaiveDate> {
+    let field = field.trim();
+    // ISO timestamps ("2024-01-31T00:00:00") and plain dates share the first ten characters.
+    field.get(..10).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .or_else(|| NaiveDate::parse_from_str(field.split(' ').next().unwrap_or(""), "%m/%d/%Y").ok())
+}
+
+/// Turn a server insights growth export into daily member counts, oldest first.
+///
+/// Exports with a total membership column are used as is. Exports with only joins and
+/// leaves are anchored to `current` on the last day and summed backwards, which assumes
+/// nothing changed between the end of the export and now.
+fn parse_insights_csv(text: &str, current: i64, before: NaiveDate) -> std::result::Result<Vec<(String, i64)>, String> {
+    let mut lines = text.lines().map(|l| l.split(',').map(|f| f.trim().trim_matches('"')).collect::<Vec<_>>());
+    let header: Vec<String> = lines.next().ok_or("CSV が空です。")?.iter().map(|h| h.to_lowercase()).collect();
+    let find = |keys: &[&str]| header.iter().position(|h| keys.iter().any(|k| h.contains(k)));
+    let date_col = find(&["timestamp", "date", "day", "interval"]).ok_or("日付の列が見つかりません。")?;
+    let total_col = find(&["total", "membership"]);
+    let (join_col, leave_col) = (find(&["new_member", "join"]), find(&["leave"]));
+    if total_col.is_none() && (join_col.is_none() || leave_col.is_none()) {
+        return Err("メンバー数 (total_membership) または参加数・退出数の列が見つかりません。".to_string());
+    }
+
+    let number = |row: &[&str], col: Option<usize>| col.and_then(|c| row.get(c)).and_then(|v| v.parse::<f64>().ok()).map(|v| v as i64);
+    // day -> (total, joins - leaves); later rows for the same day overwrite the total.
+    let mut days: BTreeMap<NaiveDate, (Option<i64>, i64)> = BTreeMap::new();
+    for row in lines {
+        let day = match row.get(date_col).and_then(|d| parse_day(d)) { Some(d) if d < before => d, _ => continue };
+        let entry = days.entry(day).or_default();
+        if let Some(total) = number(&row, total_col) { entry.0 = Some(total); }
+        entry.1 += number(&row, join_col).unwrap_or(0) - number(&row, leave_col).unwrap_or(0);
+    }
+    if days.is_empty() { return Err("取り込める行がありません。".to_string()); }
+
+    if total_col.is_some() {
+        return Ok(days.into_iter().filter_map(|(d, (total, _))| total.map(|t| (d.to_string(), t))).collect());
+    }
+    let mut count = current;
+    let mut out: Vec<(String, i64)> = Vec::new();
+    for (d, (_, net)) in days.into_iter().rev() {
+        out.push((d.to_string(), count.max(0)));
+        count -= net;
+    }
+    out.reverse();
+    Ok(out)
+}
+
+pub struct ImportInsightsCommand;
+
+#[async_trait]
+impl Command for ImportInsightsCommand {
+    fn name(&self) -> &'static str { "import-insights" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("サーバーインサイトの成長 CSV からメンバー数の履歴を取り込みます")
+            .create_option(|o| o.name("file").description("サーバーインサイトからエクスポートした CSV").kind(CommandOptionType::Attachment).required(true))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+
+        let file = match inv.args.attachment("file") { Some(f) => f, None => return Ok(()) };
+        if file.size > MAX_CSV_BYTES { return inv.say(format!("ファイルが大きすぎます (最大{}MB)。", MAX_CSV_BYTES / 1024 / 1024)).await; }
+        let text = String::from_utf8_lossy(&file.download().await?).trim_start_matches('\u{feff}').to_string();
+
+        let current = crate::member_cache::count(&inv.ctx.http, guild_id).await? as i64;
+        // Today is left to the live snapshot.
+        let rows = match parse_insights_csv(&text, current, Utc::now().date_naive()) {
+            Ok(r) => r,
+            Err(e) => return inv.say(e).await,
+        };
+        let written = db::import_member_snapshots(guild_id.0 as i64, &rows, "insights").await?;
+        log::info!("snapshots: imported {} insights rows for guild {} by {}", written, guild_id.0, command.user.id.0);
+        let (first, last) = (&rows[0].0, &rows[rows.len() - 1].0);
+        inv.say(format!("{} 〜 {} の{}日分を読み込み、{}件を書き込みました。\n※ Bot が毎日記録した日の値はそのまま残ります。", first, last, rows.len(), written)).await
+    }
+}
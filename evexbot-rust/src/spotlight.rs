@@ -0,0 +1,108 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::{ChannelType, Message, ReactionType};
+use serenity::model::id::ChannelId;
+use serenity::prelude::Mentionable;
+
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::permissions;
+use crate::zikosyokai;
+
+/// Only members who joined this recently are treated as newcomers.
+const NEWCOMER_DAYS: i64 = 30;
+const REACTION: &str = "🎉";
+const MODES: &[(&str, &str)] = &[("react", "リアクションのみ"), ("post", "チャンネルに投稿"), ("both", "両方")];
+
+/// Acknowledge a newcomer's first message outside the intro channel.
+/// Must run before `activity::handle_message` so this message isn't counted as earlier activity.
+pub async fn handle_message(http: &Http, message: &Message) -> Result<()> {
+    if message.author.bot { return Ok(()); }
+    let guild_id = match message.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    if zikosyokai::intro_channel(message.guild_id).await? == Some(message.channel_id) { return Ok(()); }
+    let settings = db::get_spotlight_settings(guild_id).await?;
+    if !settings.is_enabled { return Ok(()); }
+
+    let user_id = message.author.id.0 as i64;
+    let now = Utc::now().timestamp();
+    let joined = match db::get_last_join(guild_id, user_id).await? {
+        Some(t) if now - t <= NEWCOMER_DAYS * 86400 => t,
+        _ => return Ok(()),
+    };
+    // Members who already talked before the spotlight was turned on aren't lurkers.
+    let join_day = chrono::DateTime::from_timestamp(joined, 0).map(|d| d.naive_utc()).map(|d| d.date().to_string()).unwrap_or_default();
+    if db::has_message_activity(guild_id, user_id, &join_day).await? { return Ok(()); }
+    if !db::record_first_message(guild_id, user_id, message.channel_id.0 as i64, message.id.0 as i64, now).await? { return Ok(()); }
+
+    if settings.mode != "post" {
+        message.react(http, ReactionType::Unicode(REACTION.to_string())).await?;
+    }
+    if let (true, Some(channel)) = (settings.mode != "react", settings.channel_id) {
+        let link = format!("https://discord.com/channels/{}/{}/{}", guild_id, message.channel_id.0, message.id.0);
+        ChannelId(channel as u64).send_message(http, |m| {
+            m.content(format!("{} 初投稿ありがとう！ <#{}> での最初のメッセージです → {}", message.author.mention(), message.channel_id.0, link))
+                .allowed_mentions(|a| a.users(vec![message.author.id]))
+        }).await?;
+    }
+    Ok(())
+}
+
+pub struct SpotlightCommand;
+
+#[async_trait]
+impl Command for SpotlightCommand {
+    fn name(&self) -> &'static str { "spotlight" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("新メンバーの初投稿にお礼をします")
+            .create_option(|s| {
+                s.name("setup").description("初投稿へのお礼を設定します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("enabled").description("有効にするか").kind(CommandOptionType::Boolean).required(true))
+                    .create_sub_option(|o| {
+                        o.name("mode").description("お礼の方法").kind(CommandOptionType::String).required(false);
+                        for (value, label) in MODES { o.add_string_choice(*label, *value); }
+                        o
+                    })
+                    .create_sub_option(|o| o.name("channel").description("お礼を投稿するチャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(false))
+            })
+            .create_option(|s| s.name("stats").description(format!("直近{}日の新メンバーの初投稿率を表示します", NEWCOMER_DAYS)).kind(CommandOptionType::SubCommand))
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "setup" => {
+                let current = db::get_spotlight_settings(guild_id).await?;
+                let enabled = sub.options.iter().find(|o| o.name == "enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+                let mode = args.str("mode").map(str::to_string).unwrap_or(current.mode);
+                let channel = args.channel("channel").map(|c| c.id.0 as i64).or(current.channel_id);
+                if enabled && mode != "react" && channel.is_none() {
+                    return inv.say("チャンネルに投稿するには `channel` を指定してください。").await;
+                }
+                db::update_spotlight_settings(guild_id, enabled, &mode, channel).await?;
+                if !enabled { return inv.say("初投稿へのお礼を無効にしました。").await; }
+                let label = MODES.iter().find(|(v, _)| *v == mode).map(|(_, l)| *l).unwrap_or("");
+                let target = match (mode.as_str(), channel) { ("react", _) | (_, None) => String::new(), (_, Some(c)) => format!(" (投稿先: <#{}>)", c) };
+                inv.say(format!("参加{}日以内のメンバーの初投稿にお礼をします: {}{}", NEWCOMER_DAYS, label, target)).await
+            }
+            "stats" => {
+                let (joins, posted) = db::count_first_message_conversion(guild_id, Utc::now().timestamp() - NEWCOMER_DAYS * 86400).await?;
+                let rate = if joins == 0 { 0.0 } else { posted as f64 * 100.0 / joins as f64 };
+                inv.say(format!("直近{}日の参加者 {}人のうち {}人 ({:.1}%) が初投稿しました。", NEWCOMER_DAYS, joins, posted, rate)).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::BTreeMap;
+
+// Counting over join histories, shared by every chart that plots member counts. Like
+// `forecast`, this only uses external crates so the benches can build it on its own.
+
+/// Member count at the end of each day from `start` to `end`, counting every join up to
+/// that day. Joins are bucketed per day and summed once, so this is O(joins + days).
+pub fn member_counts(join_dates: &[NaiveDateTime], start: NaiveDate, end: NaiveDate) -> (Vec<NaiveDate>, Vec<i32>) {
+    let days = ((end - start).num_days() + 1).max(0) as usize;
+    let dates: Vec<NaiveDate> = (0..days).map(|i| start + chrono::Duration::days(i as i64)).collect();
+    let mut before = 0i32;
+    let mut per_day = vec![0i32; days];
+    for d in join_dates {
+        let offset = (d.date() - start).num_days();
+        if offset < 0 {
+            before += 1;
+        } else if (offset as usize) < days {
+            per_day[offset as usize] += 1;
+        }
+    }
+    let counts = per_day.iter().scan(before, |total, n| { *total += n; Some(*total) }).collect();
+    (dates, counts)
+}
+
+/// Reconstruct daily member counts from current members' join dates, plus tracked-invite
+/// joins of members who have since left. Members who left without a record are missing,
+/// so older counts are a lower bound.
+pub fn reconstruct_counts(join_dates: &[NaiveDateTime], departed: &[(i64, i64)], end: NaiveDate) -> Vec<(String, i64)> {
+    // day -> change in member count on that day
+    let mut deltas: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for d in join_dates {
+        *deltas.entry(d.date()).or_default() += 1;
+    }
+    for (joined_at, left_at) in departed {
+        let (joined, left) = match (chrono::DateTime::from_timestamp(*joined_at, 0).map(|d| d.naive_utc()), chrono::DateTime::from_timestamp(*left_at, 0).map(|d| d.naive_utc())) {
+            (Some(j), Some(l)) => (j.date(), l.date()),
+            _ => continue,
+        };
+        if left <= joined { continue; }
+        *deltas.entry(joined).or_default() += 1;
+        *deltas.entry(left).or_default() -= 1;
+    }
+
+    let start = match deltas.keys().next() { Some(d) => *d, None => return Vec::new() };
+    let mut out = Vec::new();
+    let mut count = 0;
+    let mut day = start;
+    while day <= end {
+        count += deltas.get(&day).copied().unwrap_or(0);
+        out.push((day.to_string(), count));
+        day = match day.succ_opt() { Some(d) => d, None => break };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str) -> NaiveDateTime {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap().and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn counts_include_joins_before_the_range() {
+        let joins = [at("2024-01-01"), at("2024-01-03"), at("2024-01-03"), at("2024-01-06")];
+        let (dates, counts) = member_counts(&joins, day("2024-01-02"), day("2024-01-05"));
+        assert_eq!(dates, vec![day("2024-01-02"), day("2024-01-03"), day("2024-01-04"), day("2024-01-05")]);
+        assert_eq!(counts, vec![1, 3, 3, 3]);
+    }
+
+    #[test]
+    fn counts_match_the_per_day_scan() {
+        let joins: Vec<NaiveDateTime> = (0..500).map(|i| at("2023-06-01") + chrono::Duration::days(i * 7 % 400)).collect();
+        let (start, end) = (day("2023-05-01"), day("2024-08-01"));
+        let (dates, counts) = member_counts(&joins, start, end);
+        for (d, c) in dates.iter().zip(&counts) {
+            assert_eq!(*c, joins.iter().filter(|j| j.date() <= *d).count() as i32, "{}", d);
+        }
+    }
+
+    #[test]
+    fn an_inverted_range_is_empty() {
+        let (dates, counts) = member_counts(&[at("2024-01-01")], day("2024-01-05"), day("2024-01-01"));
+        assert!(dates.is_empty() && counts.is_empty());
+    }
+
+    #[test]
+    fn departed_members_count_while_they_stayed() {
+        let joined = at("2024-01-01").and_utc().timestamp();
+        let left = at("2024-01-03").and_utc().timestamp();
+        let counts = reconstruct_counts(&[at("2024-01-02")], &[(joined, left)], day("2024-01-04"));
+        assert_eq!(counts.iter().map(|(_, c)| *c).collect::<Vec<_>>(), vec![1, 2, 1, 1]);
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::channel::ChannelType;
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::imagegen;
+use crate::sandbox;
+use crate::scheduler;
+use crate::permissions;
+
+const CHECK_INTERVAL_SECONDS: u64 = 60;
+const CHECK_TIMEOUT_SECONDS: u64 = 10;
+/// Consecutive failed checks before a service is reported down, so one slow probe isn't an outage.
+const FAILURES_BEFORE_DOWN: u32 = 2;
+
+/// Shown by commands that depend on a service while it is down.
+pub const BACKEND_DOWN_MESSAGE: &str = "バックエンド停止中のため、現在このコマンドは利用できません。復旧までお待ちください。";
+
+struct Service {
+    key: &'static str,
+    label: &'static str,
+    url: &'static str,
+}
+
+pub const SANDBOX_PYTHON: &str = "sandbox-python";
+pub const SANDBOX_JAVASCRIPT: &str = "sandbox-javascript";
+pub const IMAGEGEN: &str = "imagegen";
+
+const SERVICES: &[Service] = &[
+    Service { key: SANDBOX_PYTHON, label: "Python サンドボックス", url: sandbox::API_BASE_URLS_PY },
+    Service { key: SANDBOX_JAVASCRIPT, label: "JavaScript サンドボックス", url: sandbox::API_BASE_URLS_JS },
+    Service { key: IMAGEGEN, label: "画像生成", url: imagegen::API_BASE_URL },
+];
+
+#[derive(Default)]
+struct ServiceState {
+    failures: u32,
+    down: bool,
+    /// When the current up/down state began (unix seconds); 0 before the first check.
+    since: i64,
+}
+
+static STATE: Lazy<Mutex<HashMap<&'static str, ServiceState>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `key` (one of the service constants) failed its recent health checks.
+pub fn is_down(key: &str) -> bool {
+    STATE.lock().unwrap().get(key).map(|s| s.down).unwrap_or(false)
+}
+
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("status-monitor", Duration::from_secs(CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { check_services(&http).await }
+    });
+}
+
+/// Any response below 500 counts as up; the sandboxes answer GET with 405 but are serving.
+async fn probe(client: &Client, url: &str) -> bool {
+    match client.get(url).send().await {
+        Ok(r) => !r.status().is_server_error(),
+        Err(_) => false,
+    }
+}
+
+async fn check_services(http: &Http) -> Result<()> {
+    let client = Client::builder().timeout(Duration::from_secs(CHECK_TIMEOUT_SECONDS)).build()?;
+    let now = Utc::now().timestamp();
+    for service in SERVICES {
+        let ok = probe(&client, service.url).await;
+        // Work out the transition under the lock, announce it after releasing it.
+        let transition = {
+            let mut state = STATE.lock().unwrap();
+            let s = state.entry(service.key).or_default();
+            if s.since == 0 { s.since = now; }
+            s.failures = if ok { 0 } else { s.failures + 1 };
+            let down = s.failures >= FAILURES_BEFORE_DOWN;
+            if down != s.down {
+                let lasted = now - s.since;
+                s.down = down;
+                s.since = now;
+                Some((down, lasted))
+            } else { None }
+        };
+        if let Some((down, lasted)) = transition {
+            log::warn!("status: {} is {}", service.key, if down { "down" } else { "back up" });
+            announce(http, service, down, lasted).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn announce(http: &Http, service: &Service, down: bool, lasted: i64) -> Result<()> {
+    let mut embed = CreateEmbed::default();
+    if down {
+        embed.title(format!("🔴 {} が停止しています", service.label));
+        embed.description("関連するコマンドは復旧まで利用できません。");
+        embed.color(serenity::utils::Colour::RED);
+    } else {
+        embed.title(format!("🟢 {} が復旧しました", service.label));
+        embed.description(format!("停止時間: 約{}分", (lasted + 59) / 60));
+        embed.color(serenity::utils::Colour::DARK_GREEN);
+    }
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Status"));
+    for db::StatusSubscription { guild_id, channel_id } in db::get_status_subscriptions().await? {
+        let e = embed.clone();
+        if let Err(err) = ChannelId(channel_id as u64).send_message(http, |m| m.embed(|x| { *x = e; x })).await {
+            log::warn!("status: failed to notify guild {}: {}", guild_id, err);
+        }
+    }
+    Ok(())
+}
+
+pub struct StatusCommand;
+
+#[async_trait]
+impl Command for StatusCommand {
+    fn name(&self) -> &'static str { "status" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("外部サービスの稼働状況")
+            .create_option(|s| s.name("show").description("現在の稼働状況を表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("subscribe").description("停止・復旧の通知を受け取るチャンネルを設定します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("channel").description("通知先").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text, ChannelType::News]).required(true))
+            })
+            .create_option(|s| s.name("unsubscribe").description("通知を停止します").kind(CommandOptionType::SubCommand))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_status_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_status_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+
+    let reply = match sub.name.as_str() {
+        "show" => {
+            let now = Utc::now().timestamp();
+            let state = STATE.lock().unwrap();
+            let lines = SERVICES.iter().map(|svc| match state.get(svc.key) {
+                Some(s) if s.down => format!("🔴 {}: 停止中 (約{}分前から)", svc.label, (now - s.since) / 60),
+                Some(_) => format!("🟢 {}: 稼働中", svc.label),
+                None => format!("⚪ {}: 未確認", svc.label),
+            }).collect::<Vec<_>>();
+            lines.join("\n")
+        }
+        "subscribe" | "unsubscribe" => {
+            let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+            if !permissions::is_admin(member).await {
+                permissions::DENIED_MESSAGE.to_string()
+            } else if sub.name == "subscribe" {
+                let channel_id = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Channel(c) => Some(c.id), _ => None }).ok_or_else(|| anyhow::anyhow!("channel required"))?;
+                db::set_status_subscription(guild_id, Some(channel_id.0 as i64)).await?;
+                format!("外部サービスの停止・復旧を <#{}> に通知します。", channel_id.0)
+            } else {
+                db::set_status_subscription(guild_id, None).await?;
+                "通知を停止しました。".to_string()
+            }
+        }
+        _ => return Ok(()),
+    };
+    command.create_followup_message(&ctx.http, |m| m.content(reply).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::InputTextStyle;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::modal::ModalSubmitInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::{ChannelType, PermissionOverwrite, PermissionOverwriteType};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::model::permissions::Permissions;
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::model::voice::VoiceState;
+use serenity::prelude::*;
+
+use crate::commands::{Command, Invocation};
+use crate::components::modal_value;
+use crate::db;
+use crate::permissions;
+
+const MAX_USER_LIMIT: u64 = 99;
+
+pub async fn handle_voice_state_update(ctx: &Context, old: Option<&VoiceState>, new: &VoiceState) -> Result<()> {
+    let guild_id = match new.guild_id { Some(g) => g, None => return Ok(()) };
+
+    // Member left (or moved out of) a channel: clean up empty temp channels.
+    if let Some(old_channel) = old.and_then(|o| o.channel_id) {
+        if Some(old_channel) != new.channel_id {
+            cleanup_if_empty(ctx, guild_id, old_channel).await?;
+        }
+    }
+
+    let joined = match new.channel_id { Some(c) => c, None => return Ok(()) };
+    let hub = match db::get_tempvoice_hub(guild_id.0 as i64).await? { Some(h) => ChannelId(h as u64), None => return Ok(()) };
+    if joined != hub { return Ok(()); }
+
+    let member = match &new.member { Some(m) => m.clone(), None => guild_id.member(&ctx.http, new.user_id).await? };
+    if member.user.bot { return Ok(()); }
+    let parent = ctx.cache.guild_channel(hub).and_then(|c| c.parent_id);
+    let owner_overwrite = PermissionOverwrite {
+        allow: Permissions::CONNECT | Permissions::MANAGE_CHANNELS | Permissions::MOVE_MEMBERS,
+        deny: Permissions::empty(),
+        kind: PermissionOverwriteType::Member(member.user.id),
+    };
+
+    let channel = guild_id.create_channel(&ctx.http, |c| {
+        c.name(format!("{}のチャンネル", member.display_name())).kind(ChannelType::Voice).permissions(vec![owner_overwrite]);
+        if let Some(p) = parent { c.category(p); }
+        c
+    }).await?;
+    db::add_temp_voice_channel(channel.id.0 as i64, guild_id.0 as i64, member.user.id.0 as i64).await?;
+    guild_id.move_member(&ctx.http, member.user.id, channel.id).await?;
+
+    // Controls are posted to the voice channel's built-in text chat.
+    channel.id.send_message(&ctx.http, |m| {
+        m.content(format!("{} さんの一時ボイスチャンネルです。全員が退室すると自動で削除されます。", member.user.mention()));
+        m.components(|c| c.create_action_row(|ar| {
+            ar.create_button(|b| b.custom_id(format!("tempvc_rename:{}", channel.id.0)).label("名前変更").style(ButtonStyle::Primary));
+            ar.create_button(|b| b.custom_id(format!("tempvc_limit:{}", channel.id.0)).label("人数制限").style(ButtonStyle::Secondary));
+            ar.create_button(|b| b.custom_id(format!("tempvc_lock:{}", channel.id.0)).label("ロック").style(ButtonStyle::Danger));
+            ar.create_button(|b| b.custom_id(format!("tempvc_unlock:{}", channel.id.0)).label("ロック解除").style(ButtonStyle::Success))
+        }));
+        m
+    }).await?;
+    Ok(())
+}
+
+async fn cleanup_if_empty(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> Result<()> {
+    if db::get_temp_voice_owner(channel_id.0 as i64).await?.is_none() { return Ok(()); }
+    let occupants = ctx.cache.guild(guild_id).map(|g| g.voice_states.values().filter(|v| v.channel_id == Some(channel_id)).count()).unwrap_or(0);
+    if occupants > 0 { return Ok(()); }
+    // The channel may already be gone (deleted manually); drop the record either way.
+    let _ = channel_id.delete(&ctx.http).await;
+    db::remove_temp_voice_channel(channel_id.0 as i64).await?;
+    Ok(())
+}
+
+async fn check_owner(user_id: UserId, channel_id: ChannelId) -> Result<bool> {
+    Ok(db::get_temp_voice_owner(channel_id.0 as i64).await? == Some(user_id.0 as i64))
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let (action, id) = comp.data.custom_id.split_once(':').ok_or_else(|| anyhow::anyhow!("malformed custom_id"))?;
+    let channel_id = ChannelId(id.parse()?);
+    if !check_owner(comp.user.id, channel_id).await? {
+        comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("チャンネルの作成者のみ操作できます。").ephemeral(true))).await?;
+        return Ok(());
+    }
+    let guild_id = comp.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+    let everyone = PermissionOverwriteType::Role(RoleId(guild_id.0));
+
+    match action {
+        "tempvc_rename" | "tempvc_limit" => {
+            let (title, label) = if action == "tempvc_rename" { ("チャンネル名の変更", "新しい名前") } else { ("人数制限の変更", "上限人数 (0で無制限)") };
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::Modal).interaction_response_data(|d| {
+                d.custom_id(format!("{}_modal:{}", action, channel_id.0)).title(title).components(|c| c.create_action_row(|row| {
+                    row.create_input_text(|t| t.custom_id("value").label(label).style(InputTextStyle::Short).max_length(100).required(true))
+                }))
+            })).await?;
+        }
+        "tempvc_lock" => {
+            channel_id.create_permission(&ctx.http, &PermissionOverwrite { allow: Permissions::empty(), deny: Permissions::CONNECT, kind: everyone }).await?;
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("🔒 チャンネルをロックしました。").ephemeral(true))).await?;
+        }
+        "tempvc_unlock" => {
+            channel_id.delete_permission(&ctx.http, everyone).await?;
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("🔓 ロックを解除しました。").ephemeral(true))).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub async fn handle_modal(ctx: &Context, modal: &ModalSubmitInteraction) -> Result<()> {
+    let (action, id) = modal.data.custom_id.split_once(':').ok_or_else(|| anyhow::anyhow!("malformed custom_id"))?;
+    let channel_id = ChannelId(id.parse()?);
+    if !check_owner(modal.user.id, channel_id).await? { return Ok(()); }
+    let value = modal_value(modal, "value").unwrap_or_default();
+
+    let reply = match action {
+        "tempvc_rename_modal" => {
+            let name = value.trim();
+            if name.is_empty() { "名前を入力してください。".to_string() } else {
+                channel_id.edit(&ctx.http, |c| c.name(name)).await?;
+                format!("チャンネル名を「{}」に変更しました。", name)
+            }
+        }
+        "tempvc_limit_modal" => match value.trim().parse::<u64>() {
+            Ok(n) if n <= MAX_USER_LIMIT => {
+                channel_id.edit(&ctx.http, |c| c.user_limit(n)).await?;
+                if n == 0 { "人数制限を解除しました。".to_string() } else { format!("人数制限を{}人に設定しました。", n) }
+            }
+            _ => format!("0～{}の数字を入力してください。", MAX_USER_LIMIT),
+        },
+        _ => return Ok(()),
+    };
+    modal.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(reply).ephemeral(true))).await?;
+    Ok(())
+}
+
+pub struct TempVoiceCommand;
+
+#[async_trait]
+impl Command for TempVoiceCommand {
+    fn name(&self) -> &'static str { "tempvoice" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("一時ボイスチャンネルの設定").create_option(|o| {
+            o.name("action").description("enable|disable").kind(CommandOptionType::String).required(true)
+        }).create_option(|o| {
+            o.name("hub").description("参加すると一時チャンネルを作成するボイスチャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Voice]).required(false)
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_tempvoice_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_tempvoice_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource)).await?;
+    let action = command.data.options.iter().find(|o| o.name=="action").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+    let hub = command.data.options.iter().find(|o| o.name=="hub").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.clone()), _ => None });
+
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+
+    match action {
+        "enable" => {
+            let hub = match hub { Some(h) => h, None => { command.create_followup_message(&ctx.http, |m| m.content("ONにする場合はボイスチャンネルを指定してください。" ).ephemeral(true)).await?; return Ok(()); } };
+            db::update_tempvoice_hub(guild_id, Some(hub.id.0 as i64)).await?;
+            command.create_followup_message(&ctx.http, |m| m.content(format!("<#{}> に参加すると一時ボイスチャンネルを作成します。", hub.id.0)).ephemeral(true)).await?;
+        }
+        "disable" => {
+            db::update_tempvoice_hub(guild_id, None).await?;
+            command.create_followup_message(&ctx.http, |m| m.content("一時ボイスチャンネルを無効にしました!").ephemeral(true)).await?;
+        }
+        _ => { command.create_followup_message(&ctx.http, |m| m.content("enableまたはdisableを指定してください。" ).ephemeral(true)).await?; }
+    }
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use serenity::cache::Cache;
+use serenity::client::bridge::gateway::ShardMessenger;
+use serenity::http::{Http, HttpBuilder};
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::guild::Member;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::prelude::*;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use wiremock::matchers::{method, path_regex};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+/// Manage Guild, which `permissions::is_admin` accepts without configured admin roles.
+pub const ADMIN_PERMISSIONS: &str = "32";
+
+/// One runtime for every test: the database pool is global and its connection must stay on
+/// the runtime that opened it.
+static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread().enable_all().build().expect("test runtime")
+});
+static DB: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(100_000_000_000_000_000);
+
+/// Run a test body with the in-memory database migrated and ready.
+pub fn run<F: Future>(test: F) -> F::Output {
+    RUNTIME.block_on(async {
+        DB.get_or_init(|| async { crate::db::init_test_pool().await.expect("test database") }).await;
+        test.await
+    })
+}
+
+/// A snowflake no other test uses. Tests share the database and process-wide caches, so
+/// each one works in its own guild.
+pub fn id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub fn guild_id() -> GuildId {
+    GuildId(id())
+}
+
+/// A stand-in for the Discord REST API. Unmatched requests get a 404, which the code
+/// under test sees as an ordinary HTTP error.
+pub struct FakeDiscord {
+    server: MockServer,
+    pub http: Arc<Http>,
+}
+
+impl FakeDiscord {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let http = HttpBuilder::new("test-token").application_id(1).proxy(server.uri()).expect("proxy url").ratelimiter_disabled(true).build();
+        FakeDiscord { server, http: Arc::new(http) }
+    }
+
+    /// A gateway-less context sharing this fake's `Http`, with an empty cache.
+    pub fn context(&self) -> Context {
+        let (tx, _rx) = serenity::futures::channel::mpsc::unbounded();
+        Context {
+            data: Arc::new(RwLock::new(TypeMap::new())),
+            shard: ShardMessenger::new(tx),
+            shard_id: 0,
+            http: self.http.clone(),
+            cache: Arc::new(Cache::new()),
+        }
+    }
+
+    /// Answer member list requests for `guild_id` with `members`, in one page.
+    pub async fn members(&self, guild_id: GuildId, members: &[Member]) {
+        let body: Vec<Value> = members.iter().map(|m| serde_json::to_value(m).expect("member json")).collect();
+        Mock::given(method("GET")).and(path_regex(format!(r"^/api/v\d+/guilds/{}/members$", guild_id.0)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server).await;
+    }
+
+    /// Accept messages posted to `channel_id`, echoing each back as the created message.
+    pub async fn accept_messages(&self, channel_id: ChannelId) {
+        Mock::given(method("POST")).and(path_regex(format!(r"^/api/v\d+/channels/{}/messages$", channel_id.0)))
+            .respond_with(move |req: &Request| {
+                let body: Value = serde_json::from_slice(&req.body).unwrap_or_default();
+                let content = body.get("content").and_then(Value::as_str).unwrap_or("");
+                ResponseTemplate::new(200).set_body_json(message_json(channel_id, UserId(1), content))
+            })
+            .mount(&self.server).await;
+    }
+
+    /// Accept initial responses and followups for every interaction.
+    pub async fn accept_interactions(&self) {
+        Mock::given(method("POST")).and(path_regex(r"^/api/v\d+/interactions/\d+/[^/]+/callback$"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&self.server).await;
+        Mock::given(method("POST")).and(path_regex(r"^/api/v\d+/webhooks/\d+/[^/]+$"))
+            .respond_with(|req: &Request| {
+                let body: Value = serde_json::from_slice(&req.body).unwrap_or_default();
+                let content = body.get("content").and_then(Value::as_str).unwrap_or("");
+                ResponseTemplate::new(200).set_body_json(message_json(ChannelId(1), UserId(1), content))
+            })
+            .mount(&self.server).await;
+    }
+
+    /// JSON bodies of every `method` request whose path ends with `suffix`, oldest first.
+    pub async fn requests(&self, method: &str, suffix: &str) -> Vec<Value> {
+        self.server.received_requests().await.unwrap_or_default().into_iter()
+            .filter(|r| r.method.to_string() == method && r.url.path().ends_with(suffix))
+            .map(|r| serde_json::from_slice(&r.body).unwrap_or(Value::Null))
+            .collect()
+    }
+
+    /// `content` of every message posted to `channel_id`.
+    pub async fn sent_messages(&self, channel_id: ChannelId) -> Vec<String> {
+        self.requests("POST", &format!("/channels/{}/messages", channel_id.0)).await.iter()
+            .map(|b| b.get("content").and_then(Value::as_str).unwrap_or("").to_string())
+            .collect()
+    }
+
+    /// `content` of every interaction response and followup, whichever way it was sent.
+    pub async fn replies(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for r in self.server.received_requests().await.unwrap_or_default() {
+            if r.method.to_string() != "POST" { continue; }
+            let body: Value = serde_json::from_slice(&r.body).unwrap_or(Value::Null);
+            let content = if r.url.path().ends_with("/callback") { body.pointer("/data/content") } else if r.url.path().contains("/webhooks/") { body.get("content") } else { None };
+            if let Some(c) = content.and_then(Value::as_str) { out.push(c.to_string()); }
+        }
+        out
+    }
+}
+
+pub fn user_json(user_id: UserId, name: &str) -> Value {
+    json!({ "id": user_id.0.to_string(), "username": name, "discriminator": "0000", "avatar": null, "bot": false })
+}
+
+/// A member of `guild_id` who joined at `joined_at` (RFC 3339).
+pub fn member(guild_id: GuildId, user_id: UserId, joined_at: &str) -> Member {
+    serde_json::from_value(member_json(guild_id, user_id, joined_at, None)).expect("member fixture")
+}
+
+fn member_json(guild_id: GuildId, user_id: UserId, joined_at: &str, permissions: Option<&str>) -> Value {
+    let mut m = json!({
+        "guild_id": guild_id.0.to_string(),
+        "user": user_json(user_id, &format!("user{}", user_id.0)),
+        "roles": [],
+        "joined_at": joined_at,
+        "deaf": false,
+        "mute": false,
+    });
+    if let Some(p) = permissions { m["permissions"] = json!(p); }
+    m
+}
+
+/// `count` members who joined one per day, ending at `last` (RFC 3339 date).
+pub fn members_joined_daily(guild_id: GuildId, count: i64, last: &str) -> Vec<Member> {
+    let last = chrono::NaiveDate::parse_from_str(last, "%Y-%m-%d").expect("date");
+    (0..count).map(|i| {
+        let day = last - chrono::Duration::days(count - 1 - i);
+        member(guild_id, UserId(id()), &format!("{}T12:00:00+00:00", day))
+    }).collect()
+}
+
+pub fn message_json(channel_id: ChannelId, author: UserId, content: &str) -> Value {
+    json!({
+        "id": id().to_string(),
+        "channel_id": channel_id.0.to_string(),
+        "author": user_json(author, "author"),
+        "content": content,
+        "timestamp": "2024-01-01T00:00:00+00:00",
+        "edited_timestamp": null,
+        "tts": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "attachments": [],
+        "embeds": [],
+        "pinned": false,
+        "type": 0,
+    })
+}
+
+/// A slash command run in `guild_id` by a member with `permissions` (a bitfield string).
+/// `options` is the interaction's `data.options`, e.g. one subcommand with its own options.
+pub fn command(name: &str, guild_id: GuildId, user_id: UserId, permissions: &str, options: Value) -> ApplicationCommandInteraction {
+    serde_json::from_value(json!({
+        "id": id().to_string(),
+        "application_id": "1", // matches the fake `Http`, so followups reach the webhook mock
+        "type": 2,
+        "data": { "id": id().to_string(), "name": name, "type": 1, "options": options },
+        "guild_id": guild_id.0.to_string(),
+        "channel_id": id().to_string(),
+        "member": member_json(guild_id, user_id, "2024-01-01T00:00:00+00:00", Some(permissions)),
+        "token": "interaction-token",
+        "version": 1,
+        "locale": "ja",
+        "guild_locale": "ja",
+    })).expect("interaction fixture")
+}
+
+/// `data.options` for a subcommand with string options.
+pub fn subcommand(name: &str, options: &[(&str, &str)]) -> Value {
+    let options: Vec<Value> = options.iter().map(|(n, v)| json!({ "name": n, "type": 3, "value": v })).collect();
+    json!([{ "name": name, "type": 1, "options": options }])
+}
+
+/// Pixels may differ by this much per channel before they count as changed, which absorbs
+/// rounding in antialiasing without hiding a moved line.
+const GOLDEN_CHANNEL_TOLERANCE: u8 = 8;
+/// Share of changed pixels a chart may have and still match its golden image.
+const GOLDEN_PIXEL_TOLERANCE: f64 = 0.001;
+/// Register the bundled chart font, as `main` does at startup. Call before rendering a chart
+/// for `assert_golden`.
+pub fn chart_fonts() {
+    assert!(crate::chart::register_fonts(), "bundled chart font failed to load");
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+}
+
+/// Compare a rendered PNG with `tests/golden/<name>.png`.
+///
+/// UPDATE_GOLDEN=1 rewrites the image instead. A missing image fails like a mismatch, so
+/// an unreviewed rendering never becomes the baseline by accident. On a mismatch the new
+/// rendering is saved as `<name>.actual.png` next to the golden one.
+pub fn assert_golden(name: &str, png: &[u8]) {
+    let path = golden_path(name);
+    let update = std::env::var("UPDATE_GOLDEN").map(|v| v == "1").unwrap_or(false);
+    if !update && !path.exists() {
+        std::fs::write(path.with_extension("actual.png"), png).ok();
+        panic!("{}: golden image {} is missing; review the rendering and record it with UPDATE_GOLDEN=1", name, path.display());
+    }
+    if update {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("golden dir");
+        std::fs::write(&path, png).expect("write golden");
+        return;
+    }
+    let expected = image::open(&path).expect("golden image").to_rgb8();
+    let actual = image::load_from_memory(png).expect("rendered png").to_rgb8();
+    let actual_path = path.with_extension("actual.png");
+    if expected.dimensions() != actual.dimensions() {
+        std::fs::write(&actual_path, png).ok();
+        panic!("{}: size changed from {:?} to {:?}", name, expected.dimensions(), actual.dimensions());
+    }
+    let changed = expected.pixels().zip(actual.pixels())
+        .filter(|(e, a)| e.0.iter().zip(a.0.iter()).any(|(x, y)| x.abs_diff(*y) > GOLDEN_CHANNEL_TOLERANCE))
+        .count();
+    let share = changed as f64 / (expected.width() * expected.height()) as f64;
+    if share > GOLDEN_PIXEL_TOLERANCE {
+        std::fs::write(&actual_path, png).ok();
+        panic!("{}: {} pixels ({:.2}%) differ from the golden image; see {}", name, changed, share * 100.0, actual_path.display());
+    }
+}
+
+/// Join times for charts: `count` joins on a fixed calendar, a few per day in a weekly rhythm.
+pub fn chart_join_dates(count: usize) -> Vec<chrono::NaiveDateTime> {
+    let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let mut dates = Vec::with_capacity(count);
+    let mut day = 0;
+    while dates.len() < count {
+        for _ in 0..(1 + day % 7 / 3) {
+            if dates.len() < count { dates.push(start + chrono::Duration::days(day)); }
+        }
+        day += 1;
+    }
+    dates
+}
+
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Client;
+use serde_json::json;
+use std::time::Duration;
+
+/// Discord rejects thread names longer than this.
+pub const MAX_LEN: usize = 100;
+/// Fallback titles are cut here so the thread list stays readable.
+const FALLBACK_LEN: usize = 50;
+/// Only the start of a long message is sent for summarizing.
+const MAX_INPUT_CHARS: usize = 2000;
+/// Thread creation waits on the provider, so a slow one falls back quickly.
+const SUMMARY_TIMEOUT_SECONDS: u64 = 5;
+const OPENAI_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+/// THREAD_TITLE_PROVIDER selects how titles are made: `none` (default, truncated text),
+/// `openai` (chat completion with the guild's vault key), or `http`, which posts
+/// `{"text": "..."}` to THREAD_TITLE_API_URL and expects `{"title": "..."}` back.
+enum Provider {
+    Truncate,
+    OpenAi { model: String },
+    Http { url: String },
+}
+
+fn provider() -> Provider {
+    match std::env::var("THREAD_TITLE_PROVIDER").unwrap_or_default().to_lowercase().as_str() {
+        "openai" => Provider::OpenAi { model: std::env::var("THREAD_TITLE_MODEL").unwrap_or_else(|_| "gpt-4o-mini".to_string()) },
+        "http" => Provider::Http { url: std::env::var("THREAD_TITLE_API_URL").unwrap_or_default() },
+        _ => Provider::Truncate,
+    }
+}
+
+static CODE_BLOCK: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)```.*?(```|$)").unwrap());
+static NOISE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<a?:\w+:\d+>|<[@#][!&]?\d+>|https?://\S+").unwrap());
+static MARKDOWN: Lazy<Regex> = Lazy::new(|| Regex::new(r"[*_~`|>#]+").unwrap());
+
+/// Message text with code, mentions, custom emoji, links and markdown removed.
+fn clean(text: &str) -> String {
+    let text = CODE_BLOCK.replace_all(text, " ");
+    let text = NOISE.replace_all(&text, " ");
+    let text = MARKDOWN.replace_all(&text, "");
+    text.lines().map(|l| l.split_whitespace().collect::<Vec<_>>().join(" ")).filter(|l| !l.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max { return text.to_string(); }
+    let mut out: String = text.chars().take(max - 1).collect();
+    out.push('…');
+    out
+}
+
+/// First meaningful line of `text`, cut to a thread-list friendly length.
+pub fn truncated(text: &str) -> Option<String> {
+    clean(text).lines().next().map(|l| truncate(l, FALLBACK_LEN))
+}
+
+async fn summarize(text: &str, guild_id: Option<i64>) -> Result<Option<String>> {
+    let input: String = text.chars().take(MAX_INPUT_CHARS).collect();
+    let client = Client::builder().timeout(Duration::from_secs(SUMMARY_TIMEOUT_SECONDS)).build()?;
+    let title = match provider() {
+        Provider::Truncate => return Ok(None),
+        Provider::OpenAi { model } => {
+            let gid = guild_id.ok_or_else(|| anyhow::anyhow!("openai titles need a guild"))?;
+            let key = crate::vault::get_api_key(gid, "openai").await?.ok_or_else(|| anyhow::anyhow!("no openai key registered"))?;
+            let body = json!({
+                "model": model,
+                "max_tokens": 40,
+                "messages": [
+                    {"role": "system", "content": "Summarize the user's message as a short thread title in the message's language. Reply with the title only, at most 40 characters, no quotes."},
+                    {"role": "user", "content": input},
+                ],
+            });
+            let resp = client.post(OPENAI_URL).bearer_auth(key).json(&body).send().await?.error_for_status()?;
+            let json: serde_json::Value = resp.json().await?;
+            json.pointer("/choices/0/message/content").and_then(|v| v.as_str()).unwrap_or("").to_string()
+        }
+        Provider::Http { url } => {
+            if url.is_empty() { return Err(anyhow::anyhow!("THREAD_TITLE_API_URL is not set")); }
+            let mut req = client.post(&url).json(&json!({ "text": input }));
+            if let Some(gid) = guild_id {
+                if let Ok(Some(key)) = crate::vault::get_api_key(gid, "summary").await { req = req.bearer_auth(key); }
+            }
+            let resp = req.send().await?.error_for_status()?;
+            let json: serde_json::Value = resp.json().await?;
+            json.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string()
+        }
+    };
+    let title = clean(title.trim().trim_matches(|c| c == '"' || c == '「' || c == '」'));
+    Ok(title.lines().next().map(|l| truncate(l, MAX_LEN)))
+}
+
+/// A short thread title describing `text`, or None when there's nothing to describe
+/// (e.g. only an image). Uses the configured provider and falls back to truncated text.
+pub async fn generate(text: &str, guild_id: Option<i64>) -> Option<String> {
+    if clean(text).is_empty() { return None; }
+    match summarize(text, guild_id).await {
+        Ok(Some(title)) => Some(title),
+        Ok(None) => truncated(text),
+        Err(e) => {
+            log::warn!("thread_title: summarization failed, using truncated text: {}", e);
+            truncated(text)
+        }
+    }
+}
+
+/// `prefix` and `title` joined so the result fits Discord's limit.
+pub fn with_prefix(prefix: &str, title: &str) -> String {
+    truncate(&format!("{}{}", prefix, title), MAX_LEN)
+}
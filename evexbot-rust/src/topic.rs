@@ -0,0 +1,113 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::channel::ChannelType;
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::scheduler;
+use crate::permissions;
+
+const ROTATION_CHECK_INTERVAL_SECONDS: u64 = 3600;
+const MAX_TOPIC_LEN: usize = 1024;
+
+/// Start the daily topic rotation. It checks hourly and rotates each channel once per day.
+pub fn start(http: Arc<Http>) {
+    scheduler::spawn_every("topic-rotation", Duration::from_secs(ROTATION_CHECK_INTERVAL_SECONDS), move || {
+        let http = http.clone();
+        async move { rotate_due_topics(&http).await }
+    });
+}
+
+async fn rotate_due_topics(http: &Http) -> Result<()> {
+    let today = Utc::now().date_naive().to_string();
+    for (channel_id, next_index, last_rotated) in db::get_topic_rotations().await? {
+        if last_rotated.as_deref() == Some(today.as_str()) { continue; }
+        let topics = db::get_channel_topics(channel_id).await?;
+        if topics.is_empty() { continue; }
+        let index = next_index as usize % topics.len();
+        if let Err(e) = ChannelId(channel_id as u64).edit(http, |c| c.topic(&topics[index].1)).await {
+            log::warn!("topic: failed to rotate channel {}: {}", channel_id, e);
+            continue;
+        }
+        db::mark_topic_rotated(channel_id, ((index + 1) % topics.len()) as i64, &today).await?;
+    }
+    Ok(())
+}
+
+pub struct TopicCommand;
+
+#[async_trait]
+impl Command for TopicCommand {
+    fn name(&self) -> &'static str { "topic" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("チャンネルトピックの管理").create_option(|g| {
+            g.name("rotate").description("トピックを毎日切り替えます").kind(CommandOptionType::SubCommandGroup)
+                .create_sub_option(|s| {
+                    s.name("add").description("ローテーションにトピックを追加").kind(CommandOptionType::SubCommand)
+                        .create_sub_option(|o| o.name("channel").description("対象チャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text, ChannelType::News]).required(true))
+                        .create_sub_option(|o| o.name("text").description("トピック").kind(CommandOptionType::String).required(true))
+                })
+                .create_sub_option(|s| {
+                    s.name("remove").description("ローテーションからトピックを削除").kind(CommandOptionType::SubCommand)
+                        .create_sub_option(|o| o.name("channel").description("対象チャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text, ChannelType::News]).required(true))
+                        .create_sub_option(|o| o.name("id").description("/topic rotate list で表示されるID").kind(CommandOptionType::Integer).required(true))
+                })
+                .create_sub_option(|s| {
+                    s.name("list").description("登録済みのトピックを表示").kind(CommandOptionType::SubCommand)
+                        .create_sub_option(|o| o.name("channel").description("対象チャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text, ChannelType::News]).required(true))
+                })
+        })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_topic_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_topic_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+
+    let group = command.data.options.iter().find(|o| o.name == "rotate").ok_or_else(|| anyhow::anyhow!("unknown subcommand group"))?;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let channel_id = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.id), _ => None }).ok_or_else(|| anyhow::anyhow!("channel required"))?;
+
+    let reply = match sub.name.as_str() {
+        "add" => {
+            let text = sub.options.iter().find(|o| o.name=="text").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").trim();
+            if text.is_empty() || text.chars().count() > MAX_TOPIC_LEN {
+                format!("トピックは1～{}文字で指定してください。", MAX_TOPIC_LEN)
+            } else {
+                db::add_channel_topic(guild_id, channel_id.0 as i64, text).await?;
+                let count = db::get_channel_topics(channel_id.0 as i64).await?.len();
+                format!("<#{}> のローテーションにトピックを追加しました (計{}件)。", channel_id.0, count)
+            }
+        }
+        "remove" => {
+            let id = sub.options.iter().find(|o| o.name=="id").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+            if db::remove_channel_topic(channel_id.0 as i64, id).await? { "トピックを削除しました!".to_string() } else { "指定されたIDのトピックが見つかりません。".to_string() }
+        }
+        "list" => {
+            let topics = db::get_channel_topics(channel_id.0 as i64).await?;
+            if topics.is_empty() { "登録されているトピックはありません。".to_string() } else {
+                let lines: Vec<String> = topics.iter().map(|(id, t)| format!("`{}` {}", id, t.chars().take(100).collect::<String>())).collect();
+                format!("<#{}> のトピック:\n{}", channel_id.0, lines.join("\n"))
+            }
+        }
+        _ => return Ok(()),
+    };
+    command.create_followup_message(&ctx.http, |m| m.content(reply).ephemeral(true)).await?;
+    Ok(())
+}
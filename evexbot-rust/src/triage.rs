@@ -0,0 +1,251 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateApplicationCommandOption, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::channel::{Channel, ChannelType, GuildChannel, Message};
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::ocr;
+use crate::thread_title;
+use crate::permissions;
+
+const MAX_SNIPPET_LEN: usize = 1000;
+/// serenity 0.11 has no `ChannelType::Forum`, so forum channels are allowed by their raw type.
+const FORUM_CHANNEL_TYPE: u8 = 15;
+/// Snippets shown per reply; the embed gets noisy beyond this.
+const MAX_SNIPPETS_SHOWN: usize = 3;
+
+/// Who asked in each help thread, so bot-opened threads only need their starter message fetched once.
+static ASKERS: Lazy<Mutex<HashMap<u64, Option<u64>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A language or framework recognised from error output. Snippets are keyed by `tag`.
+struct Signature {
+    tag: &'static str,
+    label: &'static str,
+    pattern: Regex,
+}
+
+fn signature(tag: &'static str, label: &'static str, re: &str) -> Signature {
+    Signature { tag, label, pattern: Regex::new(re).unwrap() }
+}
+
+// Frameworks come before their language so both are reported, most specific first.
+static SIGNATURES: Lazy<Vec<Signature>> = Lazy::new(|| vec![
+    signature("discordpy", "discord.py", r"discord\.(?:errors|ext\.commands)\.\w+|discord\.py"),
+    signature("discordjs", "discord.js", r"DiscordAPIError|discord\.js|node_modules[/\\]@?discord"),
+    signature("python", "Python", r#"Traceback \(most recent call last\)|File "[^"]+", line \d+|\b(?:ModuleNotFound|Indentation|Attribute|Key|Name)Error\b"#),
+    signature("javascript", "JavaScript / Node.js", r"at (?:Object\.<anonymous>|Module\._compile|process\.processTicksAndRejections)|node:internal|npm ERR!|\bis not a function\b|Cannot read propert(?:y|ies) of (?:undefined|null)"),
+    signature("typescript", "TypeScript", r"\bTS\d{4}:|\.tsx?:\d+:\d+"),
+    signature("rust", "Rust", r"error\[E\d{4}\]|thread '[^']*' panicked at|cargo (?:build|run)"),
+    signature("java", "Java", r"Exception in thread|\bat [\w$.]+\([\w$]+\.java:\d+\)|java\.lang\.\w+(?:Exception|Error)"),
+    signature("csharp", "C#", r"System\.\w+Exception|\bCS\d{4}\b|\.cs:line \d+"),
+    signature("go", "Go", r"panic: |goroutine \d+ \[|\.go:\d+ \+0x"),
+    signature("php", "PHP", r"PHP (?:Fatal|Parse|Warning)|Stack trace:\s*#0|\.php on line \d+"),
+]);
+
+/// Tags whose signature appears in `text`, in `SIGNATURES` order.
+fn detect(text: &str) -> Vec<&'static Signature> {
+    SIGNATURES.iter().filter(|s| s.pattern.is_match(text)).collect()
+}
+
+/// Text and forum channels.
+fn help_channel_types(o: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    o.0.insert("channel_types", serde_json::json!([ChannelType::Text as u8, FORUM_CHANNEL_TYPE]));
+    o
+}
+
+pub struct TriageCommand;
+
+#[async_trait]
+impl Command for TriageCommand {
+    fn name(&self) -> &'static str { "triage" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("ヘルプチャンネルのエラー自動判定")
+            .create_option(|g| {
+                g.name("channel").description("対象チャンネル").kind(CommandOptionType::SubCommandGroup)
+                    .create_sub_option(|s| {
+                        s.name("add").description("エラー判定を行うチャンネルを追加").kind(CommandOptionType::SubCommand)
+                            .create_sub_option(|o| help_channel_types(o.name("channel").description("ヘルプチャンネル (フォーラム可)").kind(CommandOptionType::Channel).required(true)))
+                            .create_sub_option(|o| o.name("autothread").description("質問ごとに内容に沿った名前のスレッドを作成する (テキストチャンネルのみ)").kind(CommandOptionType::Boolean).required(false))
+                    })
+                    .create_sub_option(|s| {
+                        s.name("remove").description("チャンネルを対象から外す").kind(CommandOptionType::SubCommand)
+                            .create_sub_option(|o| help_channel_types(o.name("channel").description("ヘルプチャンネル").kind(CommandOptionType::Channel).required(true)))
+                    })
+            })
+            .create_option(|g| {
+                g.name("snippet").description("判定結果に添える案内").kind(CommandOptionType::SubCommandGroup)
+                    .create_sub_option(|s| {
+                        s.name("set").description("タグの案内文を設定").kind(CommandOptionType::SubCommand)
+                            .create_sub_option(|o| tag_option(o))
+                            .create_sub_option(|o| o.name("content").description("案内文やドキュメントのURL").kind(CommandOptionType::String).required(true))
+                    })
+                    .create_sub_option(|s| {
+                        s.name("remove").description("タグの案内文を削除").kind(CommandOptionType::SubCommand)
+                            .create_sub_option(|o| tag_option(o))
+                    })
+            })
+            .create_option(|s| s.name("list").description("設定を表示").kind(CommandOptionType::SubCommand))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_triage_command(inv.ctx, inv.command).await
+    }
+}
+
+fn tag_option(o: &mut serenity::builder::CreateApplicationCommandOption) -> &mut serenity::builder::CreateApplicationCommandOption {
+    o.name("tag").description("言語・フレームワーク").kind(CommandOptionType::String).required(true);
+    for s in SIGNATURES.iter() { o.add_string_choice(s.label, s.tag); }
+    o
+}
+
+async fn handle_triage_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let top = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    let reply = match top.name.as_str() {
+        "channel" => {
+            let sub = top.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+            let channel_id = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { CommandDataOptionValue::Channel(c) => Some(c.id), _ => None }).ok_or_else(|| anyhow::anyhow!("channel required"))?;
+            if sub.name == "add" {
+                let auto_thread = sub.options.iter().find(|o| o.name=="autothread").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+                db::add_triage_channel(guild_id, channel_id.0 as i64, auto_thread).await?;
+                if auto_thread {
+                    format!("<#{}> のエラーを自動判定し、質問ごとにスレッドを作成します。", channel_id.0)
+                } else {
+                    format!("<#{}> のエラーを自動判定します。", channel_id.0)
+                }
+            } else if db::remove_triage_channel(guild_id, channel_id.0 as i64).await? {
+                format!("<#{}> を対象から外しました。", channel_id.0)
+            } else {
+                "そのチャンネルは対象になっていません。".to_string()
+            }
+        }
+        "snippet" => {
+            let sub = top.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+            let tag = sub.options.iter().find(|o| o.name=="tag").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+            let label = SIGNATURES.iter().find(|s| s.tag == tag).map(|s| s.label).unwrap_or(tag);
+            if sub.name == "set" {
+                let content = sub.options.iter().find(|o| o.name=="content").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").trim();
+                if content.is_empty() || content.chars().count() > MAX_SNIPPET_LEN {
+                    format!("案内文は1～{}文字で指定してください。", MAX_SNIPPET_LEN)
+                } else {
+                    db::set_triage_snippet(guild_id, tag, content).await?;
+                    format!("{} の案内文を設定しました。", label)
+                }
+            } else if db::remove_triage_snippet(guild_id, tag).await? {
+                format!("{} の案内文を削除しました。", label)
+            } else {
+                format!("{} の案内文は設定されていません。", label)
+            }
+        }
+        "list" => {
+            let channels = db::get_triage_channels(guild_id).await?;
+            let snippets = db::get_triage_snippets(guild_id).await?;
+            let channels = if channels.is_empty() { "なし".to_string() } else { channels.iter().map(|c| format!("<#{}>", c)).collect::<Vec<_>>().join(" ") };
+            let snippets = if snippets.is_empty() { "なし".to_string() } else {
+                snippets.iter().map(|snippet| {
+                    let label = SIGNATURES.iter().find(|s| s.tag == snippet.tag).map(|s| s.label).unwrap_or(snippet.tag.as_str());
+                    format!("**{}**: {}", label, snippet.content.chars().take(100).collect::<String>())
+                }).collect::<Vec<_>>().join("\n")
+            };
+            format!("対象チャンネル: {}\n案内文:\n{}", channels, snippets)
+        }
+        _ => return Ok(()),
+    };
+    command.create_followup_message(&ctx.http, |m| m.content(reply).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Whether `msg` was posted in a configured help channel, or a thread/forum post inside one.
+async fn in_help_channel(ctx: &Context, msg: &Message, channels: &[i64]) -> bool {
+    if channels.contains(&(msg.channel_id.0 as i64)) { return true; }
+    match msg.channel_id.to_channel(ctx).await {
+        Ok(Channel::Guild(c)) => c.parent_id.map(|p| channels.contains(&(p.0 as i64))).unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// The member who asked in `thread`. Triage opens threads on the question itself, so for
+/// bot-owned threads the asker is the author of the starter message, which shares the thread's id.
+pub async fn asker(ctx: &Context, thread: &GuildChannel) -> Option<u64> {
+    if let Some(cached) = ASKERS.lock().await.get(&thread.id.0) {
+        return *cached;
+    }
+    let asker = match thread.owner_id {
+        Some(owner) if owner != ctx.cache.current_user_id() => Some(owner.0),
+        Some(_) => match thread.parent_id {
+            Some(parent) => parent.message(&ctx.http, thread.id.0).await.ok().map(|m| m.author.id.0),
+            None => None,
+        },
+        None => None,
+    };
+    ASKERS.lock().await.insert(thread.id.0, asker);
+    asker
+}
+
+/// Start a thread on a question posted straight into a help channel, named after its content.
+async fn open_help_thread(ctx: &Context, msg: &Message) -> Result<ChannelId> {
+    let name = match thread_title::generate(&msg.content, msg.guild_id.map(|g| g.0 as i64)).await {
+        Some(title) => title,
+        None => format!("{}さんの質問", msg.author.name.chars().take(80).collect::<String>()),
+    };
+    let thread = msg.channel_id.create_public_thread(&ctx.http, msg.id, |t| t.name(name).auto_archive_duration(1440)).await?;
+    Ok(thread.id)
+}
+
+pub async fn handle_message(ctx: &Context, msg: &Message) -> Result<()> {
+    if msg.author.bot { return Ok(()); }
+    let guild_id = match msg.guild_id { Some(g) => g.0 as i64, None => return Ok(()) };
+    let channels = db::get_triage_channels(guild_id).await?;
+    if channels.is_empty() || !in_help_channel(ctx, msg, &channels).await { return Ok(()); }
+
+    // Only top-level questions get a thread; replies stay where they were posted.
+    let thread = if channels.contains(&(msg.channel_id.0 as i64)) && msg.message_reference.is_none() && db::get_triage_auto_thread(msg.channel_id.0 as i64).await? {
+        Some(open_help_thread(ctx, msg).await?)
+    } else {
+        None
+    };
+
+    let mut found = detect(&msg.content);
+    let mut from_image = false;
+    // Screenshots are only read when the text itself gave nothing away.
+    if found.is_empty() {
+        if let Some(attachment) = ocr::first_image(msg) {
+            let text = match attachment.download().await {
+                Ok(bytes) => ocr::extract_text(&bytes, Some(guild_id)).await.unwrap_or_default(),
+                Err(_) => String::new(),
+            };
+            found = detect(&text);
+            from_image = true;
+        }
+    }
+    if found.is_empty() { return Ok(()); }
+
+    let snippets = db::get_triage_snippets(guild_id).await?;
+    let mut embed = CreateEmbed::default();
+    embed.title("エラーの推定");
+    embed.description(format!("{}から判定: {}", if from_image { "スクリーンショット" } else { "メッセージ" }, found.iter().map(|s| format!("`{}`", s.label)).collect::<Vec<_>>().join(" ")));
+    for (label, content) in found.iter().filter_map(|s| snippets.iter().find(|snippet| snippet.tag == s.tag).map(|snippet| (s.label, &snippet.content))).take(MAX_SNIPPETS_SHOWN) {
+        embed.field(label, content, false);
+    }
+    embed.color(serenity::utils::Colour::BLURPLE);
+    embed.footer(|f| f.text("EvexBot | Triage"));
+    match thread {
+        Some(t) => { t.send_message(&ctx.http, |m| m.embed(|e| { *e = embed; e })).await?; }
+        None => { msg.channel_id.send_message(&ctx.http, |m| m.embed(|e| { *e = embed; e }).reference_message(msg).allowed_mentions(|a| a.replied_user(false))).await?; }
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::Result;
+use base64::Engine;
+use chrono::Utc;
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::InputTextStyle;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption};
+use serenity::model::application::interaction::modal::ModalSubmitInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::prelude::*;
+
+use crate::components::modal_value;
+use crate::db;
+use crate::permissions;
+
+/// Providers that features may look up keys for.
+pub const PROVIDERS: &[&str] = &["openai", "deepl", "translation", "imagegen", "ocr", "summary", "prompt"];
+const MAX_KEY_LENGTH: usize = 512;
+
+fn cipher() -> Result<Aes256Gcm> {
+    let raw = std::env::var("VAULT_MASTER_KEY").map_err(|_| anyhow::anyhow!("VAULT_MASTER_KEY is not set"))?;
+    let bytes = base64::engine::general_purpose::STANDARD.decode(raw.trim())?;
+    if bytes.len() != 32 {
+        return Err(anyhow::anyhow!("VAULT_MASTER_KEY must decode to 32 bytes"));
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)))
+}
+
+/// Bind ciphertexts to their row so a stored key cannot be copied to another guild or provider.
+fn associated_data(guild_id: i64, provider: &str) -> Vec<u8> {
+    format!("{}:{}", guild_id, provider).into_bytes()
+}
+
+pub async fn set_api_key(guild_id: i64, provider: &str, key: &str, updated_by: i64) -> Result<()> {
+    let cipher = cipher()?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let aad = associated_data(guild_id, provider);
+    let ciphertext = cipher.encrypt(&nonce, Payload { msg: key.as_bytes(), aad: &aad }).map_err(|_| anyhow::anyhow!("encryption failed"))?;
+    db::upsert_api_key(guild_id, provider, nonce.as_slice(), &ciphertext, updated_by, Utc::now().timestamp()).await
+}
+
+/// Decrypted API key for a guild and provider, if one has been configured.
+pub async fn get_api_key(guild_id: i64, provider: &str) -> Result<Option<String>> {
+    let (nonce, ciphertext) = match db::get_api_key(guild_id, provider).await? { Some(v) => v, None => return Ok(None) };
+    let cipher = cipher()?;
+    let aad = associated_data(guild_id, provider);
+    let plain = cipher.decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &aad }).map_err(|_| anyhow::anyhow!("decryption failed (wrong VAULT_MASTER_KEY?)"))?;
+    Ok(Some(String::from_utf8(plain)?))
+}
+
+/// Adds the `apikey` subcommand group to `/config`.
+pub fn build_config_group(g: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    g.name("apikey").description("外部サービスのAPIキー").kind(CommandOptionType::SubCommandGroup)
+        .create_sub_option(|s| provider_option(s.name("set").description("APIキーを登録します").kind(CommandOptionType::SubCommand)))
+        .create_sub_option(|s| provider_option(s.name("remove").description("APIキーを削除します").kind(CommandOptionType::SubCommand)))
+        .create_sub_option(|s| s.name("list").description("登録済みのAPIキーを表示します").kind(CommandOptionType::SubCommand))
+}
+
+fn provider_option(s: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    s.create_sub_option(|o| {
+        o.name("provider").description("サービス").kind(CommandOptionType::String).required(true);
+        for p in PROVIDERS { o.add_string_choice(*p, *p); }
+        o
+    })
+}
+
+pub async fn handle_config_group(ctx: &Context, command: &ApplicationCommandInteraction, group: &CommandDataOption) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = group.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+    let provider = sub.options.iter().find(|o| o.name=="provider").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("").to_string();
+
+    if !permissions::is_admin(member).await {
+        command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(permissions::DENIED_MESSAGE).ephemeral(true))).await?;
+        return Ok(());
+    }
+
+    match sub.name.as_str() {
+        "set" => {
+            // Keys are entered in a modal so they never appear in the command log or channel.
+            command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::Modal).interaction_response_data(|d| {
+                d.custom_id(format!("vault_set:{}", provider)).title(format!("{} のAPIキー", provider)).components(|c| c.create_action_row(|row| {
+                    row.create_input_text(|t| t.custom_id("key").label("APIキー").style(InputTextStyle::Short).max_length(MAX_KEY_LENGTH as u64).required(true))
+                }))
+            })).await?;
+        }
+        "remove" => {
+            let removed = db::delete_api_key(guild_id, &provider).await?;
+            let msg = if removed { format!("{} のAPIキーを削除しました。", provider) } else { format!("{} のAPIキーは登録されていません。", provider) };
+            command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+        }
+        "list" => {
+            let keys = db::list_api_keys(guild_id).await?;
+            let msg = if keys.is_empty() { "登録されているAPIキーはありません。".to_string() } else {
+                keys.iter().map(|(p, by, at)| format!("• {} — <@{}> が <t:{}:f> に登録", p, by, at)).collect::<Vec<_>>().join("\n")
+            };
+            command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub async fn handle_modal(ctx: &Context, modal: &ModalSubmitInteraction) -> Result<()> {
+    let provider = modal.data.custom_id.trim_start_matches("vault_set:").to_string();
+    let guild_id = modal.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let is_admin = match modal.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+    let key = modal_value(modal, "key").unwrap_or_default();
+
+    let msg = if !is_admin {
+        permissions::DENIED_MESSAGE.to_string()
+    } else if !PROVIDERS.contains(&provider.as_str()) {
+        "不明なサービスです。".to_string()
+    } else if key.trim().is_empty() {
+        "APIキーを入力してください。".to_string()
+    } else {
+        match set_api_key(guild_id, &provider, key.trim(), modal.user.id.0 as i64).await {
+            Ok(_) => format!("{} のAPIキーを暗号化して保存しました。", provider),
+            Err(e) => {
+                log::error!("vault: failed to store key for guild {}: {}", guild_id, e);
+                "APIキーの保存に失敗しました。Botの管理者に連絡してください。".to_string()
+            }
+        }
+    };
+    modal.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
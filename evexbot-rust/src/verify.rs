@@ -0,0 +1,234 @@
+use anyhow::Result;
+use axum::extract::{Query, State};
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Deserialize;
+use serenity::async_trait;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::prelude::*;
+
+use crate::commands::{Command, Invocation};
+use crate::db;
+use crate::web::{self, WebState};
+use crate::permissions;
+
+const STATE_TTL_SECONDS: i64 = 600;
+const DISCORD_API: &str = "https://discord.com/api/v10";
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct OAuthUser {
+    id: String,
+    email: Option<String>,
+    verified: Option<bool>,
+}
+
+fn redirect_uri() -> String {
+    format!("{}/verify/callback", web::public_base_url())
+}
+
+pub fn routes() -> Router<WebState> {
+    Router::new().route("/verify/callback", get(callback))
+}
+
+/// Text made safe to place in HTML element content or a quoted attribute.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn page(title: &str, message: &str) -> Html<String> {
+    Html(format!("<!doctype html><html lang=\"ja\"><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width,initial-scale=1\"><title>{0}</title></head><body style=\"font-family:sans-serif;max-width:32rem;margin:4rem auto;padding:0 1rem\"><h1>{0}</h1><p>{1}</p></body></html>", escape_html(title), escape_html(message)))
+}
+
+async fn callback(State(state): State<WebState>, Query(q): Query<CallbackQuery>) -> Html<String> {
+    if q.error.is_some() {
+        return page("認証がキャンセルされました", "Discordに戻り、もう一度「認証する」ボタンを押してください。");
+    }
+    let (code, state_token) = match (q.code, q.state) {
+        (Some(c), Some(s)) => (c, s),
+        _ => return page("無効なリクエスト", "認証リンクが正しくありません。"),
+    };
+    match complete_verification(&state.http, &code, &state_token).await {
+        Ok(msg) => page("認証完了", &msg),
+        Err(e) => {
+            // The details can name the guild's settings or upstream responses; they go to the log only
+            log::warn!("verify: callback failed: {:#}", e);
+            page("認証に失敗しました", "認証を完了できませんでした。Discordに戻ってもう一度お試しいただくか、サーバーの管理者にお問い合わせください。")
+        }
+    }
+}
+
+async fn complete_verification(http: &Http, code: &str, state_token: &str) -> Result<String> {
+    let (guild_id, user_id, created_at) = db::take_verify_state(state_token).await?
+        .ok_or_else(|| anyhow::anyhow!("認証リンクの有効期限が切れています。もう一度お試しください。"))?;
+    if Utc::now().timestamp() - created_at > STATE_TTL_SECONDS {
+        return Err(anyhow::anyhow!("認証リンクの有効期限が切れています。もう一度お試しください。"));
+    }
+
+    let client_id = std::env::var("DISCORD_CLIENT_ID")?;
+    let client_secret = std::env::var("DISCORD_CLIENT_SECRET")?;
+    let client = reqwest::Client::new();
+    let token: TokenResponse = client.post(format!("{}/oauth2/token", DISCORD_API))
+        .form(&[
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri().as_str()),
+        ])
+        .send().await?
+        .error_for_status()?
+        .json().await?;
+    let user: OAuthUser = client.get(format!("{}/users/@me", DISCORD_API))
+        .bearer_auth(&token.access_token)
+        .send().await?
+        .error_for_status()?
+        .json().await?;
+
+    if user.id != user_id.to_string() {
+        return Err(anyhow::anyhow!("認証を開始したアカウントとログインしたアカウントが異なります。"));
+    }
+
+    let (role_id, email_domain) = db::get_verify_settings(guild_id).await?;
+    let role_id = role_id.ok_or_else(|| anyhow::anyhow!("このサーバーでは認証が無効になっています。"))?;
+    if let Some(domain) = email_domain {
+        let ok = user.verified.unwrap_or(false) && user.email.as_deref().map(|e| e.to_lowercase().ends_with(&format!("@{}", domain.to_lowercase()))).unwrap_or(false);
+        if !ok {
+            return Err(anyhow::anyhow!(format!("@{} の確認済みメールアドレスが必要です。", domain)));
+        }
+    }
+
+    http.add_member_role(guild_id as u64, user_id as u64, role_id as u64, Some("web verification")).await?;
+    Ok("ロールを付与しました。このページを閉じてDiscordに戻ってください。".to_string())
+}
+
+pub struct VerifyCommand;
+
+#[async_trait]
+impl Command for VerifyCommand {
+    fn name(&self) -> &'static str { "verify" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("Web認証の設定")
+            .create_option(|o| {
+                o.name("setup").description("認証パネルを設置します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("role").description("認証後に付与するロール").kind(CommandOptionType::Role).required(true))
+                    .create_sub_option(|s| s.name("channel").description("認証パネルを送信するチャンネル").kind(CommandOptionType::Channel).required(true))
+                    .create_sub_option(|s| s.name("email_domain").description("必須とするメールドメイン (例: example.ac.jp)").kind(CommandOptionType::String).required(false))
+            })
+            .create_option(|o| o.name("disable").description("Web認証を無効にします").kind(CommandOptionType::SubCommand))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_verify_command(inv.ctx, inv.command).await
+    }
+}
+
+async fn handle_verify_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    match sub.name.as_str() {
+        "setup" => {
+            let role = sub.options.iter().find(|o| o.name=="role").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Role(role) => Some(role.clone()), _ => None }).ok_or_else(|| anyhow::anyhow!("role required"))?;
+            let channel = sub.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.clone()), _ => None }).ok_or_else(|| anyhow::anyhow!("channel required"))?;
+            let domain = sub.options.iter().find(|o| o.name=="email_domain").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).map(|s| s.trim().trim_start_matches('@').to_string()).filter(|s| !s.is_empty());
+
+            if std::env::var("DISCORD_CLIENT_ID").is_err() || std::env::var("DISCORD_CLIENT_SECRET").is_err() {
+                command.create_followup_message(&ctx.http, |m| m.content("DISCORD_CLIENT_ID / DISCORD_CLIENT_SECRET が設定されていません。" ).ephemeral(true)).await?;
+                return Ok(());
+            }
+
+            db::update_verify_settings(guild_id, Some(role.id.0 as i64), domain.clone()).await?;
+            let requirement = domain.as_ref().map(|d| format!("\n※ @{} の確認済みメールアドレスが必要です。", d)).unwrap_or_default();
+            channel.id.send_message(&ctx.http, |m| {
+                m.embed(|e| e.title("メンバー認証").description(format!("下のボタンからDiscordでログインして認証してください。{}", requirement)).color(serenity::utils::Colour::BLURPLE));
+                m.components(|c| c.create_action_row(|ar| ar.create_button(|b| b.custom_id("verify_start").label("認証する").style(ButtonStyle::Success))))
+            }).await?;
+            command.create_followup_message(&ctx.http, |m| m.content(format!("<#{}> に認証パネルを設置しました。認証後に <@&{}> を付与します。", channel.id.0, role.id.0)).ephemeral(true)).await?;
+        }
+        "disable" => {
+            db::update_verify_settings(guild_id, None, None).await?;
+            command.create_followup_message(&ctx.http, |m| m.content("Web認証を無効にしました!").ephemeral(true)).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let guild_id = comp.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let (role_id, _) = db::get_verify_settings(guild_id).await?;
+    let client_id = std::env::var("DISCORD_CLIENT_ID").ok();
+    let (role_id, client_id) = match (role_id, client_id) {
+        (Some(r), Some(c)) => (r, c),
+        _ => {
+            comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("このサーバーでは認証が無効になっています。").ephemeral(true))).await?;
+            return Ok(());
+        }
+    };
+    if comp.member.as_ref().map(|m| m.roles.iter().any(|r| r.0 == role_id as u64)).unwrap_or(false) {
+        comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content("すでに認証済みです。").ephemeral(true))).await?;
+        return Ok(());
+    }
+
+    let state: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+    db::create_verify_state(&state, guild_id, comp.user.id.0 as i64, Utc::now().timestamp()).await?;
+    let (_, domain) = db::get_verify_settings(guild_id).await?;
+    let scope = if domain.is_some() { "identify email" } else { "identify" };
+    let url = format!("https://discord.com/oauth2/authorize?client_id={}&redirect_uri={}&response_type=code&scope={}&state={}",
+        client_id, urlencoding::encode(&redirect_uri()), urlencoding::encode(scope), state);
+
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| {
+        d.content("下のリンクから認証を完了してください (10分間有効)。").ephemeral(true)
+            .components(|c| c.create_action_row(|ar| ar.create_button(|b| b.label("Discordでログイン").style(ButtonStyle::Link).url(url))))
+    })).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_escape_interpolated_text() {
+        let Html(body) = page("<b>", "a & \"b\" <script>");
+        assert!(body.contains("<h1>&lt;b&gt;</h1>"));
+        assert!(body.contains("<p>a &amp; &quot;b&quot; &lt;script&gt;</p>"));
+    }
+}
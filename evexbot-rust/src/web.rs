@@ -0,0 +1,55 @@
+use axum::Router;
+use serenity::http::Http;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::api;
+use crate::external;
+use crate::paste;
+use crate::shortlink;
+use crate::verify;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Shared state handed to every HTTP handler.
+#[derive(Clone)]
+pub struct WebState {
+    pub http: Arc<Http>,
+}
+
+/// Public base URL of the HTTP server, without a trailing slash.
+pub fn public_base_url() -> String {
+    std::env::var("PUBLIC_BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()).trim_end_matches('/').to_string()
+}
+
+/// Start the HTTP server in the background. Safe to call on every `ready`.
+pub fn start(http: Arc<Http>) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let bind = std::env::var("HTTP_BIND").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+    let addr: SocketAddr = match bind.parse() {
+        Ok(a) => a,
+        Err(e) => {
+            log::error!("web: invalid HTTP_BIND {}: {}", bind, e);
+            return;
+        }
+    };
+
+    let state = WebState { http };
+    let app = Router::new()
+        .merge(verify::routes())
+        .merge(api::routes())
+        .merge(external::routes())
+        .merge(paste::routes())
+        .merge(shortlink::routes())
+        .with_state(state);
+
+    tokio::spawn(async move {
+        log::info!("web: listening on {}", addr);
+        if let Err(e) = axum::Server::bind(&addr).serve(app.into_make_service()).await {
+            log::error!("web: server stopped: {}", e);
+        }
+    });
+}
@@ -1,10 +1,8 @@
 use anyhow::Result;
 use chrono::Utc;
 use plotters::prelude::*;
-use serenity::builder::CreateEmbed;
-use serenity::http::Http;
-use serenity::model::application::interaction::InteractionResponseType;
-use serenity::model::gateway::Ready;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
 use serenity::model::id::{GuildId, ChannelId, UserId};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
@@ -12,28 +10,65 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use crate::bus;
+use crate::chart::{self, ChartSize};
+use crate::commands::{Command, Invocation};
 use crate::db;
 use crate::growth;
+use crate::member_cache;
+use crate::permissions;
+use crate::stats;
 
-static LAST_WELCOME: once_cell::sync::Lazy<Arc<Mutex<HashMap<i64, chrono::DateTime<chrono::Utc>>>>> = once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+/// Milestone graph size when the guild has no chart default.
+const MILESTONE_CHART_SIZE: ChartSize = ChartSize::new(800, 300);
+/// Past milestone lines on the celebration chart; older ones would crowd the y axis.
+const PAST_MILESTONE_LINES: i64 = 3;
+
+type LastWelcome = HashMap<i64, chrono::DateTime<chrono::Utc>>;
+static LAST_WELCOME: once_cell::sync::Lazy<Arc<Mutex<LastWelcome>>> = once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
-pub const ROLE_ID: u64 = 1255803402898898964;
 const JOIN_COOLDOWN_SECONDS: i64 = 3;
 
-pub async fn handle_member_join(ctx: &Context, new_member: Member) -> Result<()> {
+pub async fn on_event(ctx: &Context, event: &bus::Event) -> Result<()> {
+    match event {
+        bus::Event::MemberJoined(member) => handle_member_join(ctx, member.clone()).await,
+        _ => Ok(()),
+    }
+}
+
+/// Whether `member_count` is itself a milestone, and the next one after it.
+fn next_milestone(member_count: i64, increment: i64) -> (bool, i64) {
+    let remainder = member_count % increment;
+    if remainder == 0 {
+        (true, member_count + increment)
+    } else {
+        (false, member_count + (increment - remainder))
+    }
+}
+
+async fn handle_member_join(ctx: &Context, new_member: Member) -> Result<()> {
     if new_member.user.bot {
         return Ok(());
     }
 
     let guild_id = new_member.guild_id.0 as i64;
 
-    let (is_enabled, increment, channel_id_opt) = db::get_welcome_settings(guild_id).await?;
-    if !is_enabled {
+    let settings = db::get_welcome_settings(guild_id).await?;
+    let increment = settings.member_increment;
+    if !settings.is_enabled {
+        return Ok(());
+    }
+
+    // Raid mode pauses welcomes; the anti-raid alert covers these joins.
+    if crate::antiraid::is_raid_active(guild_id).await? {
         return Ok(());
     }
 
+    // During planned events joins are collected and summarized hourly instead.
+    let batching = db::is_event_active(guild_id, Utc::now().timestamp()).await?;
+
     // Cooldown
-    {
+    if !batching {
         let last_welcome = LAST_WELCOME.clone();
         let mut lock = last_welcome.lock().await;
         if let Some(last) = lock.get(&guild_id) {
@@ -44,7 +79,7 @@ pub async fn handle_member_join(ctx: &Context, new_member: Member) -> Result<()>
         lock.insert(guild_id, Utc::now());
     }
 
-    let channel_id = match channel_id_opt {
+    let channel_id = match settings.channel_id {
         Some(id) => ChannelId(id as u64),
         None => {
             // disable
@@ -54,62 +89,76 @@ pub async fn handle_member_join(ctx: &Context, new_member: Member) -> Result<()>
     };
 
     // Fetch member count
-    let member_count = {
-        let members = ctx.http.get_guild_members(new_member.guild_id.0, None, None).await?;
-        members.len() as i64
-    };
+    let member_count = member_cache::count(&ctx.http, new_member.guild_id).await? as i64;
 
-    let remainder = member_count % increment;
-    let (is_milestone, next_target) = if remainder == 0 {
-        (true, member_count + increment)
-    } else {
-        (false, member_count + (increment - remainder))
-    };
+    let (is_milestone, next_target) = next_milestone(member_count, increment);
+
+    if batching && !is_milestone {
+        db::add_batched_join(guild_id, new_member.user.id.0 as i64, Utc::now().timestamp()).await?;
+        return Ok(());
+    }
 
     // Fetch join dates
-    let join_dates = fetch_all_join_dates(ctx, new_member.guild_id).await?;
+    let join_dates = member_cache::join_dates(&ctx.http, new_member.guild_id).await?;
+    let locale = chart::Locale::for_guild(Some(guild_id)).await;
+    let intro_invite = match crate::zikosyokai::intro_channel(Some(new_member.guild_id)).await? {
+        Some(c) => format!("\n良ければ、<#{}>で自己紹介お願いします！。", c.0),
+        None => String::new(),
+    };
 
     if is_milestone {
         // Generate graph
-        if let Some(buf) = create_growth_graph(&join_dates, member_count).await? {
+        let size = chart::resolve(Some(guild_id), None, MILESTONE_CHART_SIZE).await.unwrap_or(MILESTONE_CHART_SIZE);
+        if let Some(buf) = create_growth_graph(&join_dates, member_count, increment, size, locale).await? {
+            let buf = chart::finish(Some(guild_id), buf, "growth").await;
             // send embed with image
             let mut embed = CreateEmbed::default();
             embed.title("🎉 Welcome EvexDevelopers! 🎉");
             let guild_name = ctx.cache.guild(new_member.guild_id.0).map(|g| g.name.clone()).unwrap_or_else(|| "Server".to_string());
-            embed.description(format!("{} さん、ようこそ！\n現在のメンバー数: **{}人**\n{}のメンバーが{}人になりました！皆さんありがとうございます！\n良ければ、<#1445478071221223515>で自己紹介お願いします！。", new_member.user.mention(), member_count, guild_name, member_count));
+            embed.description(format!("{} さん、ようこそ！\n現在のメンバー数: **{}人**\n{}のメンバーが{}人になりました！皆さんありがとうございます！{}", new_member.user.mention(), member_count, guild_name, member_count, intro_invite));
             embed.color(serenity::utils::Colour::GOLD);
             embed.timestamp(Utc::now().to_rfc3339());
             embed.footer(|f| f.text("EvexBot | Member Growth"));
 
             // send using byte slice tuple expected by serenity add_file/send_files
-            channel_id.send_files(&ctx.http, vec![(buf.as_slice(), "growth.png")], |m| m.embed(|e| { *e = embed; e })).await?;
+            let celebration = channel_id.send_files(&ctx.http, vec![(buf.bytes.as_slice(), buf.filename().as_str())], |m| m.embed(|e| { *e = embed.clone(); e }).components(|c| crate::zikosyokai::write_button(c))).await?;
+            bus::publish(bus::Event::MilestoneReached { guild_id, member_count, message: Box::new(celebration) });
+
+            // also publish to the announcement channel so following servers receive it
+            if let Some(announce_id) = db::get_milestone_announce_channel(guild_id).await? {
+                let announce = ChannelId(announce_id as u64);
+                if announce != channel_id {
+                    match announce.send_files(&ctx.http, vec![(buf.bytes.as_slice(), buf.filename().as_str())], |m| m.embed(|e| { *e = embed; e })).await {
+                        Ok(msg) => { let _ = msg.crosspost(&ctx.http).await; }
+                        Err(e) => log::warn!("welcome: failed to post milestone announcement in {}: {}", announce_id, e),
+                    }
+                }
+            }
 
             // spawn prediction task to compute when next_target is reached and edit message
             let http = ctx.http.clone();
             let ch = channel_id;
             let join_dates_clone = join_dates.clone();
             tokio::spawn(async move {
-                if let Ok(Some((target_date, _img))) = growth::predict_and_generate(&join_dates_clone, next_target as usize).await {
-                    let content = format!("次の目標到達予測: {}人: {}", next_target, target_date.date_naive());
-                    let _ = ch.say(&http, content).await;
+                if let Ok(Some(target_date)) = growth::predict_date_blocking(growth::Model::Trend, join_dates_clone, next_target as usize).await {
+                    let content = format!("次の目標到達予測: {}人: {}", next_target, target_date);
+                    if let Ok(msg) = ch.say(&http, content).await { crate::reforecast::track(guild_id, ch, msg.id.0, next_target).await; }
                 }
             });
         }
     } else {
-        let content = format!("{} さん、ようこそ！\n現在のメンバー数: {}人\nあと {} 人で {}人達成です！\n良ければ、<#1445478071221223515>で自己紹介お願いします！。", new_member.user.mention(), member_count, increment - remainder, next_target);
-        let sent = channel_id.say(&ctx.http, content).await?;
+        let content = format!("{} さん、ようこそ！\n現在のメンバー数: {}人\nあと {} 人で {}人達成です！{}", new_member.user.mention(), member_count, next_target - member_count, next_target, intro_invite);
+        let sent = channel_id.send_message(&ctx.http, |m| m.content(content).components(|c| crate::zikosyokai::write_button(c))).await?;
 
         // spawn prediction background task that edits the message
         let http = ctx.http.clone();
         let mut sent_clone = sent.clone();
         let join_dates_clone = join_dates.clone();
         tokio::spawn(async move {
-            if let Ok(pred) = growth::predict_and_generate(&join_dates_clone, next_target as usize).await {
-                if let Some((target_date, _img)) = pred {
-                    let days = (target_date.date_naive() - chrono::Utc::now().date_naive()).num_days();
-                    let edit_content = format!("{}\n次の目標到達予測: {}人: {} (あと{}日)", sent_clone.content, next_target, target_date.date_naive(), days);
-                    let _ = sent_clone.edit(&http, |b| b.content(edit_content)).await;
-                }
+            if let Ok(Some(target_date)) = growth::predict_date_blocking(growth::Model::Trend, join_dates_clone, next_target as usize).await {
+                let days = (target_date - chrono::Utc::now().date_naive()).num_days();
+                let edit_content = format!("{}\n次の目標到達予測: {}人: {} (あと{}日)", sent_clone.content, next_target, target_date, days);
+                if sent_clone.edit(&http, |b| b.content(edit_content)).await.is_ok() { crate::reforecast::track(guild_id, sent_clone.channel_id, sent_clone.id.0, next_target).await; }
             }
         });
     }
@@ -117,42 +166,25 @@ pub async fn handle_member_join(ctx: &Context, new_member: Member) -> Result<()>
     Ok(())
 }
 
-async fn fetch_all_join_dates(ctx: &Context, guild_id: GuildId) -> Result<Vec<chrono::NaiveDateTime>> {
-    let mut dates = Vec::new();
-    let members = ctx.http.get_guild_members(guild_id.0, None, None).await?;
-    for m in members.into_iter() {
-        if let Some(joined_at) = m.joined_at {
-            if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&joined_at.to_string()) {
-                dates.push(dt.naive_utc());
-            }
-        }
-    }
-    dates.sort();
-    Ok(dates)
-}
 
-async fn create_growth_graph(dates: &Vec<chrono::NaiveDateTime>, achieved_count: i64) -> Result<Option<Vec<u8>>> {
+/// Member count history with dashed lines at the latest milestones and the next one.
+async fn create_growth_graph(dates: &[chrono::NaiveDateTime], achieved_count: i64, increment: i64, size: ChartSize, locale: chart::Locale) -> Result<Option<Vec<u8>>> {
     if dates.is_empty() { return Ok(None); }
     use plotters_bitmap::BitMapBackend;
 
-    let min_date = dates[0].date();
-    let max_date = dates.last().unwrap().date();
-    let days = (max_date - min_date).num_days() as usize + 1;
-    let mut counts = vec![0i32; days];
-    for dt in dates.iter() {
-        let idx = (dt.date() - min_date).num_days() as usize;
-        for i in idx..days { counts[i] += 1; }
-    }
+    let (date_labels, counts) = stats::member_counts(dates, dates[0].date(), dates.last().unwrap().date());
+    let days = date_labels.len();
 
-    let width = 800;
-    let height = 300;
-    let mut buf: Vec<u8> = vec![0; width * height * 3];
+    let mut buf: Vec<u8> = vec![0; (size.width * size.height * 3) as usize];
 
-    let date_labels: Vec<chrono::NaiveDate> = (0..days).map(|i| min_date + chrono::Duration::days(i as i64)).collect();
-    let max_count = *counts.iter().max().unwrap_or(&0) as i32 + 2;
+    let increment = increment.max(1);
+    let next = (achieved_count / increment + 1) * increment;
+    let past: Vec<i64> = (0..PAST_MILESTONE_LINES).map(|k| (achieved_count / increment - k) * increment).filter(|m| *m > 0).collect();
+    // Keep the next milestone line in view with a little room above it.
+    let max_count = (*counts.iter().max().unwrap_or(&0)).max(next as i32) + (increment as i32 / 4).max(2);
 
     {
-        let backend = BitMapBackend::with_buffer(&mut buf, (width as u32, height as u32));
+        let backend = BitMapBackend::with_buffer(&mut buf, (size.width, size.height));
         let drawing_area = backend.into_drawing_area();
         drawing_area.fill(&WHITE)?;
 
@@ -161,8 +193,15 @@ async fn create_growth_graph(dates: &Vec<chrono::NaiveDateTime>, achieved_count:
             .caption("Member Growth History", ("sans-serif", 20))
             .x_label_area_size(35)
             .y_label_area_size(40)
-            .build_cartesian_2d(0usize..days, 0i32..max_count)?;
-        chart.configure_mesh().disable_mesh().x_labels(6).x_label_formatter(&|v| date_labels[*v].to_string()).draw()?;
+            .build_cartesian_2d(chart::date_axis(&date_labels), 0i32..max_count)?;
+        chart.configure_mesh().disable_mesh().x_label_formatter(&chart::date_labels(&date_labels, locale)).draw()?;
+
+        let font = ("sans-serif", 14).into_font();
+        for (value, color) in past.iter().map(|m| (*m, BLACK.mix(0.4))).chain(std::iter::once((next, RED.mix(0.7)))) {
+            chart.draw_series(DashedLineSeries::new(vec![(0, value as i32), (days - 1, value as i32)], 6, 4, color.stroke_width(1)))?;
+            let label = if value == next { format!("次の目標 {}人", value) } else { format!("{}人", value) };
+            chart.draw_series(std::iter::once(Text::new(label, (0, value as i32), font.color(&color))))?;
+        }
 
         chart.draw_series(LineSeries::new(
             (0..days).map(|i| (i, counts[i])),
@@ -172,78 +211,114 @@ async fn create_growth_graph(dates: &Vec<chrono::NaiveDateTime>, achieved_count:
         drawing_area.present()?;
     }
 
-    // Convert RGB buffer to PNG
-    let image = image::RgbImage::from_raw(width as u32, height as u32, buf).ok_or_else(|| anyhow::anyhow!("Failed to create image"))?;
-    let mut out = Vec::new();
-    image::DynamicImage::ImageRgb8(image).write_to(&mut std::io::Cursor::new(&mut out), image::ImageOutputFormat::Png)?;
-    Ok(Some(out))
+    Ok(Some(chart::encode_png(size, buf)?))
 }
 
 pub async fn handle_member_remove(ctx: &Context, guild_id: GuildId, user_id: UserId) -> Result<()> {
     let _guild = guild_id.to_guild_cached(&ctx.cache).ok_or_else(|| anyhow::anyhow!("Guild not in cache"))?;
     let guild_id = guild_id.0 as i64;
-    let (is_enabled, channel_id_opt) = db::get_leave_settings(guild_id).await?;
-    if !is_enabled {
+    let settings = db::get_leave_settings(guild_id).await?;
+    if !settings.is_enabled {
         return Ok(());
     }
-    let channel_id = match channel_id_opt { Some(id) => ChannelId(id as u64), None => { db::update_leave_settings(guild_id, false, None).await.ok(); return Ok(()); } };
+    let channel_id = match settings.channel_id { Some(id) => ChannelId(id as u64), None => { db::update_leave_settings(guild_id, false, None).await.ok(); return Ok(()); } };
 
     // Compute member_count
-    let members = ctx.http.get_guild_members(GuildId(guild_id as u64).0, None, None).await?;
-    let member_count = members.len();
+    let member_count = member_cache::count(&ctx.http, GuildId(guild_id as u64)).await?;
 
     let message = format!("<@{}> さんがサーバーを退室しました。\n現在のメンバー数: {}人", user_id.0, member_count);
     channel_id.say(&ctx.http, message).await?;
     Ok(())
 }
 
-pub async fn register_commands(http: &Http) -> Result<()> {
-    // Register /welcome and /leave-message and /milestonetest
-    let _ = serenity::model::application::command::Command::create_global_application_command(http, |c| {
-        c.name("welcome").description("参加メッセージの設定").create_option(|o| {
+pub struct WelcomeCommand;
+
+#[async_trait]
+impl Command for WelcomeCommand {
+    fn name(&self) -> &'static str { "welcome" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("参加メッセージの設定").create_option(|o| {
             o.name("action").description("enable|disable").kind(serenity::model::application::command::CommandOptionType::String).required(true)
         }).create_option(|o| {
             o.name("increment").description("何人ごとにお祝い").kind(serenity::model::application::command::CommandOptionType::Integer).required(false)
         }).create_option(|o| {
             o.name("channel").description("送信先チャンネル").kind(serenity::model::application::command::CommandOptionType::Channel).required(false)
+        }).create_option(|o| {
+            o.name("announce_channel").description("マイルストーンを公開するアナウンスチャンネル").kind(serenity::model::application::command::CommandOptionType::Channel).channel_types(&[ChannelType::News]).required(false)
+        }).create_option(|o| {
+            o.name("clear_announce").description("アナウンスチャンネルへの公開をやめる").kind(serenity::model::application::command::CommandOptionType::Boolean).required(false)
         })
-    }).await;
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_welcome_command(inv.ctx, inv.command).await
+    }
+}
 
-    let _ = serenity::model::application::command::Command::create_global_application_command(http, |c| {
-        c.name("leave-message").description("退室メッセージの設定").create_option(|o| {
+pub struct LeaveMessageCommand;
+
+#[async_trait]
+impl Command for LeaveMessageCommand {
+    fn name(&self) -> &'static str { "leave-message" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("退室メッセージの設定").create_option(|o| {
             o.name("action").description("enable|disable").kind(serenity::model::application::command::CommandOptionType::String).required(true)
         }).create_option(|o| {
             o.name("channel").description("送信先チャンネル").kind(serenity::model::application::command::CommandOptionType::Channel).required(false)
         })
-    }).await;
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_leave_command(inv.ctx, inv.command).await
+    }
+}
 
-    let _ = serenity::model::application::command::Command::create_global_application_command(http, |c| {
-        c.name("milestonetest").description("管理者用: マイルストーンテスト")
-    }).await;
+pub struct MilestoneTestCommand;
 
-    Ok(())
+#[async_trait]
+impl Command for MilestoneTestCommand {
+    fn name(&self) -> &'static str { "milestonetest" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("管理者用: マイルストーンテスト")
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_milestone_test(inv.ctx, inv.command).await
+    }
 }
 
 use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
 
-pub async fn handle_welcome_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+async fn handle_welcome_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
     command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
-    let action = command.data.options.get(0).and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
-    let increment = command.data.options.iter().find(|o| o.name=="increment").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).map(|v| v as i64);
+    let action = command.data.options.first().and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+    let increment = command.data.options.iter().find(|o| o.name=="increment").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64());
     let channel = command.data.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.clone()), _ => None });
+    let announce_channel = command.data.options.iter().find(|o| o.name=="announce_channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.clone()), _ => None });
+    let clear_announce = command.data.options.iter().find(|o| o.name=="clear_announce").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
 
     // role check
     let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
-    if !member.roles.iter().any(|r| r.0 == ROLE_ID) { command.create_followup_message(&ctx.http, |m| m.content("コマンドを使用するにはサーバーの管理権限が必要です。" ).ephemeral(true)).await?; return Ok(()); }
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+
+    if clear_announce {
+        db::update_milestone_announce_channel(command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64, None).await?;
+    } else if let Some(announce) = announce_channel.as_ref() {
+        db::update_milestone_announce_channel(command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64, Some(announce.id.0 as i64)).await?;
+    }
 
     match action {
         "enable" => {
             if channel.is_none() { command.create_followup_message(&ctx.http, |m| m.content("ONにする場合はチャンネルを指定してください。" ).ephemeral(true)).await?; return Ok(()); }
             let chan_id = if let Some(c) = channel { c.id.0 as i64 } else { 0 };
             let inc = increment.unwrap_or(100);
-            if inc < 5 || inc > 1000 { command.create_followup_message(&ctx.http, |m| m.content("5～1000人の間で指定してください。" ).ephemeral(true)).await?; return Ok(()); }
+            if !(5..=1000).contains(&inc) { command.create_followup_message(&ctx.http, |m| m.content("5～1000人の間で指定してください。" ).ephemeral(true)).await?; return Ok(()); }
             db::update_welcome_settings(command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64, true, Some(inc), Some(chan_id)).await?;
-            command.create_followup_message(&ctx.http, |m| m.content(format!("参加メッセージをONにしました!\n{}人ごとに<#{}>でお祝いメッセージを送信します", inc, chan_id)).ephemeral(true)).await?;
+            let announce_note = announce_channel.map(|a| format!("\nマイルストーンは<#{}>にも公開されます", a.id.0)).unwrap_or_default();
+            command.create_followup_message(&ctx.http, |m| m.content(format!("参加メッセージをONにしました!\n{}人ごとに<#{}>でお祝いメッセージを送信します{}", inc, chan_id, announce_note)).ephemeral(true)).await?;
         }
         "disable" => {
             db::update_welcome_settings(command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64, false, None, None).await?;
@@ -254,13 +329,13 @@ pub async fn handle_welcome_command(ctx: &Context, command: &ApplicationCommandI
     Ok(())
 }
 
-pub async fn handle_leave_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+async fn handle_leave_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
     command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
-    let action = command.data.options.get(0).and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+    let action = command.data.options.first().and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
     let channel = command.data.options.iter().find(|o| o.name=="channel").and_then(|o| o.resolved.as_ref()).and_then(|r| match r { serenity::model::prelude::application_command::CommandDataOptionValue::Channel(c) => Some(c.clone()), _ => None });
 
     let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
-    if !member.roles.iter().any(|r| r.0 == ROLE_ID) { command.create_followup_message(&ctx.http, |m| m.content("コマンドを使用するにはサーバーの管理権限が必要です。" ).ephemeral(true)).await?; return Ok(()); }
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
 
     match action {
         "enable" => {
@@ -278,18 +353,21 @@ pub async fn handle_leave_command(ctx: &Context, command: &ApplicationCommandInt
     Ok(())
 }
 
-pub async fn handle_milestone_test(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+async fn handle_milestone_test(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
     command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource)).await?;
-    // permission check by user id
-    if command.user.id.0 != 1241397634095120438u64 { command.create_followup_message(&ctx.http, |m| m.content("権限がありません。" )).await?; return Ok(()); }
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE)).await?; return Ok(()); }
 
     let guild = command.guild_id.ok_or_else(|| anyhow::anyhow!("Guild only"))?;
-    let join_dates = fetch_all_join_dates(&ctx, guild).await?;
-    let member_count = ctx.http.get_guild_members(guild.0, None, None).await?.len();
+    let join_dates = member_cache::join_dates(&ctx.http, guild).await?;
+    let member_count = member_cache::count(&ctx.http, guild).await?;
     let next_target = member_count as i64 + 100;
 
     // generate graph
-    if let Some(buf) = create_growth_graph(&join_dates, member_count as i64).await? {
+    let size = chart::resolve(Some(guild.0 as i64), None, MILESTONE_CHART_SIZE).await.unwrap_or(MILESTONE_CHART_SIZE);
+    let locale = chart::Locale::for_guild(Some(guild.0 as i64)).await;
+    if let Some(buf) = create_growth_graph(&join_dates, member_count as i64, db::get_welcome_settings(guild.0 as i64).await?.member_increment, size, locale).await? {
+        let buf = chart::finish(Some(guild.0 as i64), buf, "growth").await;
         let mut embed = serenity::builder::CreateEmbed::default();
         embed.title("🎉 Welcome EvexDevelopers! 🎉");
         let guild_name = command.guild_id.and_then(|gid| ctx.cache.guild(gid.0).map(|g| g.name.clone())).unwrap_or_else(|| "Server".to_string());
@@ -297,17 +375,70 @@ pub async fn handle_milestone_test(ctx: &Context, command: &ApplicationCommandIn
         embed.color(serenity::utils::Colour::GOLD);
         embed.timestamp(chrono::Utc::now().to_rfc3339());
         embed.footer(|f| f.text("EvexBot | Member Growth"));
-        command.create_followup_message(&ctx.http, |m| m.add_file((buf.as_slice(), "growth.png")).embed(|e| { *e = embed; e })).await?;
+        command.create_followup_message(&ctx.http, |m| m.add_file((buf.bytes.as_slice(), buf.filename().as_str())).embed(|e| { *e = embed; e })).await?;
 
         let join_dates_clone = join_dates.clone();
         let cmd_clone = command.clone();
         let http = ctx.http.clone();
         tokio::spawn(async move {
-            if let Ok(Some((target_date, _))) = crate::growth::predict_and_generate(&join_dates_clone, next_target as usize).await {
-                let days = (target_date.date_naive() - chrono::Utc::now().date_naive()).num_days();
-                let _ = cmd_clone.create_followup_message(&http, |m| m.content(format!("次の目標到達予測: {}人: {} (あと{}日)", next_target, target_date.date_naive(), days))).await;
+            if let Ok(Some(target_date)) = crate::growth::predict_date_blocking(crate::growth::Model::Trend, join_dates_clone, next_target as usize).await {
+                let days = (target_date - chrono::Utc::now().date_naive()).num_days();
+                let _ = cmd_clone.create_followup_message(&http, |m| m.content(format!("次の目標到達予測: {}人: {} (あと{}日)", next_target, target_date, days))).await;
             }
         });
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, FakeDiscord};
+
+    #[test]
+    fn milestones_land_on_multiples_of_the_increment() {
+        assert_eq!(next_milestone(100, 100), (true, 200));
+        assert_eq!(next_milestone(101, 100), (false, 200));
+        assert_eq!(next_milestone(199, 100), (false, 200));
+        assert_eq!(next_milestone(7, 5), (false, 10));
+    }
+
+    #[test]
+    fn join_announces_how_many_members_to_the_next_milestone() {
+        testing::run(async {
+            let discord = FakeDiscord::start().await;
+            let guild_id = testing::guild_id();
+            let channel_id = ChannelId(testing::id());
+            db::update_welcome_settings(guild_id.0 as i64, true, Some(5), Some(channel_id.0 as i64)).await.unwrap();
+            let members = testing::members_joined_daily(guild_id, 7, "2024-03-01");
+            discord.members(guild_id, &members).await;
+            discord.accept_messages(channel_id).await;
+
+            let joined = members.last().unwrap().clone();
+            on_event(&discord.context(), &bus::Event::MemberJoined(joined.clone())).await.unwrap();
+
+            let sent = discord.sent_messages(channel_id).await;
+            assert_eq!(sent.len(), 1);
+            assert!(sent[0].starts_with(&format!("<@{}> さん、ようこそ！\n現在のメンバー数: 7人\nあと 3 人で 10人達成です！", joined.user.id.0)), "{}", sent[0]);
+        })
+    }
+
+    #[test]
+    fn milestone_chart_matches_golden() {
+        testing::chart_fonts();
+        let dates = testing::chart_join_dates(120);
+        let png = testing::run(create_growth_graph(&dates, 120, 50, MILESTONE_CHART_SIZE, chart::Locale::Ja)).unwrap().expect("chart");
+        testing::assert_golden("welcome_milestone", &png);
+    }
+
+    #[test]
+    fn join_is_ignored_when_welcome_is_disabled() {
+        testing::run(async {
+            let discord = FakeDiscord::start().await;
+            let guild_id = testing::guild_id();
+            let member = testing::member(guild_id, UserId(testing::id()), "2024-03-01T00:00:00+00:00");
+            on_event(&discord.context(), &bus::Event::MemberJoined(member)).await.unwrap();
+            assert!(discord.requests("GET", "/members").await.is_empty());
+        })
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateEmbed};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::channel::Message;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::automod;
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::db;
+use crate::modlog;
+use crate::permissions;
+
+/// Message text is needed to match the rules.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::MESSAGE_CONTENT;
+
+const ACTIONS: [&str; 3] = ["delete", "warn", "log"];
+const MAX_RULES: usize = 50;
+const MAX_PATTERN_LENGTH: usize = 200;
+/// Compiled programs are capped so one rule can't take a large share of memory.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// Other instances pick up rule changes within this long.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+const QUOTED_CONTENT_LENGTH: usize = 200;
+
+/// guild id -> (loaded at, compiled rules)
+type RuleCache = HashMap<u64, (Instant, Arc<Vec<Rule>>)>;
+static RULES: Lazy<Mutex<RuleCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A rule ready to match. Keywords compile to an escaped, case-insensitive regex too.
+struct Rule {
+    id: i64,
+    pattern: String,
+    is_regex: bool,
+    action: String,
+    regex: Regex,
+}
+
+fn compile(pattern: &str, is_regex: bool) -> std::result::Result<Regex, regex::Error> {
+    let source = if is_regex { pattern.to_string() } else { regex::escape(pattern) };
+    RegexBuilder::new(&source).case_insensitive(true).size_limit(REGEX_SIZE_LIMIT).build()
+}
+
+/// Rules whose pattern no longer compiles (e.g. after a regex crate upgrade) are skipped.
+fn compile_rules(rows: Vec<db::WordFilterRule>) -> Vec<Rule> {
+    rows.into_iter().filter_map(|r| match compile(&r.pattern, r.is_regex) {
+        Ok(regex) => Some(Rule { id: r.id, pattern: r.pattern, is_regex: r.is_regex, action: r.action, regex }),
+        Err(e) => {
+            log::warn!("wordfilter: skipping rule #{} in {}: {}", r.id, r.guild_id, e);
+            None
+        }
+    }).collect()
+}
+
+async fn rules(guild_id: u64) -> Result<Arc<Vec<Rule>>> {
+    if let Some((at, rules)) = RULES.lock().await.get(&guild_id) {
+        if at.elapsed() < CACHE_TTL { return Ok(rules.clone()); }
+    }
+    let rules = Arc::new(compile_rules(db::get_word_filter_rules(guild_id as i64).await?));
+    RULES.lock().await.insert(guild_id, (Instant::now(), rules.clone()));
+    Ok(rules)
+}
+
+/// The first rule matching `text`, with the matched part.
+fn first_match<'a>(rules: &'a [Rule], text: &str) -> Option<(&'a Rule, String)> {
+    rules.iter().find_map(|r| r.regex.find(text).map(|m| (r, m.as_str().to_string())))
+}
+
+fn describe(rule: &Rule) -> String {
+    format!("#{} {} `{}` → {}", rule.id, if rule.is_regex { "正規表現" } else { "キーワード" }, rule.pattern, rule.action)
+}
+
+/// Apply the first matching rule to `message`. Returns true when the message was removed
+/// and later handlers should not process it.
+pub async fn handle_message(ctx: &Context, message: &Message) -> Result<bool> {
+    if message.author.bot || message.content.is_empty() || !crate::intents::has(REQUIRED_INTENTS) { return Ok(false); }
+    let guild_id = match message.guild_id { Some(g) => g, None => return Ok(false) };
+    let rules = rules(guild_id.0).await?;
+    if rules.is_empty() || permissions::is_exempt(ctx, guild_id, message).await { return Ok(false); }
+    let (rule, matched) = match first_match(&rules, &message.content) { Some(m) => m, None => return Ok(false) };
+
+    let monitor_only = automod::is_monitor_only(guild_id).await;
+    let enforce = !monitor_only && rule.action != "log";
+    let mut case_id = None;
+    if enforce {
+        message.delete(&ctx.http).await?;
+        if rule.action == "warn" {
+            let reason = format!("ワードフィルター #{}", rule.id);
+            case_id = Some(db::create_mod_case(guild_id.0 as i64, None, message.author.id.0 as i64, "warn", Some(&reason), Utc::now().timestamp()).await?);
+        }
+        let _ = message.channel_id.say(&ctx.http, format!("{} 禁止されている語句を含むため、メッセージを削除しました。", message.author.mention())).await;
+    }
+
+    let title = match rule.action.as_str() {
+        "warn" => "禁止語句を含むメッセージを削除し、警告しました",
+        "log" => "禁止語句を含むメッセージを検出しました",
+        _ => "禁止語句を含むメッセージを削除しました",
+    };
+    let quoted: String = message.content.chars().take(QUOTED_CONTENT_LENGTH).collect();
+    let mut embed = CreateEmbed::default();
+    embed.title(if rule.action == "log" { title.to_string() } else { automod::action_title(title, monitor_only) });
+    embed.description(format!("投稿者: {}\nチャンネル: <#{}>\nルール: {}\n一致: `{}`", message.author.mention(), message.channel_id.0, describe(rule), matched));
+    embed.field("内容", quoted, false);
+    if let Some(id) = case_id { embed.field("ケース", format!("#{}", id), true); }
+    embed.color(serenity::utils::Colour::ORANGE);
+    embed.timestamp(Utc::now().to_rfc3339());
+    embed.footer(|f| f.text("EvexBot | Word Filter"));
+    modlog::send(&ctx.http, guild_id, embed).await?;
+    Ok(enforce)
+}
+
+pub struct FilterCommand;
+
+#[async_trait]
+impl Command for FilterCommand {
+    fn name(&self) -> &'static str { "filter" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("禁止語句のフィルター")
+            .create_option(|s| {
+                s.name("add").description("ルールを追加します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("pattern").description("キーワード、または正規表現").kind(CommandOptionType::String).required(true))
+                    .create_sub_option(|o| {
+                        o.name("type").description("keyword: 部分一致 (既定) / regex: 正規表現").kind(CommandOptionType::String).required(false)
+                            .add_string_choice("keyword", "keyword")
+                            .add_string_choice("regex", "regex")
+                    })
+                    .create_sub_option(|o| {
+                        o.name("action").description("delete: 削除 (既定) / warn: 削除して警告 / log: ログのみ").kind(CommandOptionType::String).required(false)
+                            .add_string_choice("delete", "delete")
+                            .add_string_choice("warn", "warn")
+                            .add_string_choice("log", "log")
+                    })
+            })
+            .create_option(|s| {
+                s.name("remove").description("ルールを削除します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("id").description("ルール番号 (/filter list で確認できます)").kind(CommandOptionType::Integer).required(true))
+            })
+            .create_option(|s| s.name("list").description("ルールを一覧します").kind(CommandOptionType::SubCommand))
+            .create_option(|s| {
+                s.name("test").description("文章がどのルールに一致するか確認します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|o| o.name("text").description("確認する文章").kind(CommandOptionType::String).required(true))
+            })
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        let gid = guild_id.0 as i64;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+        let args = Args::new(&sub.options);
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        match sub.name.as_str() {
+            "add" => {
+                let pattern = args.str("pattern").unwrap_or("").trim();
+                let is_regex = args.str("type") == Some("regex");
+                let action = args.str("action").unwrap_or("delete");
+                if pattern.is_empty() || pattern.chars().count() > MAX_PATTERN_LENGTH { return inv.say(format!("パターンは1～{}文字で指定してください。", MAX_PATTERN_LENGTH)).await; }
+                if !ACTIONS.contains(&action) { return inv.say("delete、warn、logのいずれかを指定してください。").await; }
+                if let Err(e) = compile(pattern, is_regex) { return inv.say(format!("正規表現が正しくありません:\n```\n{}\n```", e)).await; }
+                if db::get_word_filter_rules(gid).await?.len() >= MAX_RULES { return inv.say(format!("ルールは{}件まで登録できます。", MAX_RULES)).await; }
+                let id = db::add_word_filter_rule(gid, pattern, is_regex, action, command.user.id.0 as i64, Utc::now().timestamp()).await?;
+                RULES.lock().await.remove(&guild_id.0);
+                let warning = if crate::intents::has(REQUIRED_INTENTS) { "" } else { "\n⚠️ このBotはメッセージ本文を読めない設定のため、フィルターは動作しません。" };
+                inv.say(format!("ルール #{} を追加しました: {} `{}` → {}{}", id, if is_regex { "正規表現" } else { "キーワード" }, pattern, action, warning)).await
+            }
+            "remove" => {
+                let id = args.int("id").unwrap_or(0);
+                if !db::remove_word_filter_rule(gid, id).await? { return inv.say(format!("ルール #{} はありません。", id)).await; }
+                RULES.lock().await.remove(&guild_id.0);
+                inv.say(format!("ルール #{} を削除しました。", id)).await
+            }
+            "list" => {
+                let rules = rules(guild_id.0).await?;
+                if rules.is_empty() { return inv.say("ルールはありません。`/filter add` で追加できます。").await; }
+                let lines: Vec<String> = rules.iter().map(describe).collect();
+                inv.say(format!("ルール ({}件、番号の小さい順に確認します)\n{}", rules.len(), lines.join("\n"))).await
+            }
+            "test" => {
+                let text = args.str("text").unwrap_or("");
+                let rules = rules(guild_id.0).await?;
+                match first_match(&rules, text) {
+                    Some((rule, matched)) => inv.say(format!("{} に一致します (一致した部分: `{}`)。", describe(rule), matched)).await,
+                    None => inv.say("どのルールにも一致しません。").await,
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i64, pattern: &str, is_regex: bool) -> Rule {
+        Rule { id, pattern: pattern.to_string(), is_regex, action: "delete".to_string(), regex: compile(pattern, is_regex).unwrap() }
+    }
+
+    #[test]
+    fn keywords_match_literally_and_ignore_case() {
+        let rules = vec![rule(1, "free.nitro", false)];
+        assert_eq!(first_match(&rules, "get FREE.NITRO now").map(|(r, m)| (r.id, m)), Some((1, "FREE.NITRO".to_string())));
+        assert!(first_match(&rules, "free-nitro").is_none());
+    }
+
+    #[test]
+    fn the_first_matching_rule_wins() {
+        let rules = vec![rule(1, "spam", false), rule(2, r"\bsp[a4]m+\b", true)];
+        assert_eq!(first_match(&rules, "sp4mmm").map(|(r, _)| r.id), Some(2));
+        assert_eq!(first_match(&rules, "spam and sp4m").map(|(r, _)| r.id), Some(1));
+        assert!(first_match(&rules, "こんにちは").is_none());
+    }
+
+    #[test]
+    fn invalid_and_oversized_regexes_are_rejected() {
+        assert!(compile("(unclosed", true).is_err());
+        assert!(compile("(unclosed", false).is_ok());
+        assert!(compile(r"(\w{1000}){1000}", true).is_err());
+    }
+}
@@ -1,21 +1,58 @@
 use anyhow::Result;
-use serenity::model::channel::Message;
+use serenity::async_trait;
+use serenity::builder::{CreateApplicationCommand, CreateComponents};
+use serenity::http::Http;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::component::{ButtonStyle, InputTextStyle};
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::application::interaction::modal::ModalSubmitInteraction;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::channel::{ChannelType, Message, ReactionType};
 use serenity::prelude::*;
 use tokio::sync::Mutex;
 use once_cell::sync::Lazy;
-use serenity::model::id::ChannelId;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+
+use crate::chart;
+use crate::commands::{Args, Command, Defer, Invocation};
+use crate::components::modal_value;
+use crate::db;
+use crate::member_cache;
+use crate::members_history;
+use crate::permissions;
+use crate::report::Report;
+use crate::thread_title;
+
+const RECENT_JOIN_DAYS: i64 = 30;
+const TREND_DAYS: i64 = 90;
 
 static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
-pub const TARGET_CHANNEL_ID: u64 = 1445478071221223515;
+/// The template is recognised by its content; without it every message looks like a stray
+/// post and the template would be reposted in a loop.
+pub const REQUIRED_INTENTS: GatewayIntents = GatewayIntents::MESSAGE_CONTENT;
+
+/// Intro channel for guilds that haven't run `/intro-channel set` yet.
+static DEFAULT_CHANNEL_ID: Lazy<Option<u64>> = Lazy::new(|| std::env::var("INTRO_CHANNEL_ID").ok().and_then(|v| v.trim().parse().ok()));
 pub const MARKER: &str = "EvexBot";
 pub const CHECK_EMOJI: char = '✅';
+const DEFAULT_TEMPLATE: &str = "自己紹介テンプレート\n```text\n- 名前: \n- 得意分野: \n- SNSリンク: \n- 一言: \n```";
+/// Leaves room for the marker line within Discord's 2000 character limit.
+const MAX_TEMPLATE_LENGTH: usize = 1900;
+const PREVIEW_LENGTH: usize = 60;
+/// Custom id of the "自己紹介を書く" button on welcome messages.
+pub const WRITE_BUTTON_ID: &str = "intro_write";
+pub const WRITE_MODAL_ID: &str = "intro_submit";
+/// Discord allows five inputs per modal.
+const MAX_FIELDS: usize = 5;
+const MAX_LABEL_LENGTH: usize = 45;
+const MAX_ANSWER_LENGTH: usize = 300;
 
 fn is_intro_message(message: &Message) -> bool {
     if message.author.id != serenity::model::id::UserId(0) && message.author.bot {
         // only bot messages
     }
-    if message.author != message.author.clone() { }
     if let Some(embed) = message.embeds.first() {
         if let Some(footer) = &embed.footer {
             if footer.text == MARKER { return true; }
@@ -25,12 +62,29 @@ fn is_intro_message(message: &Message) -> bool {
     false
 }
 
-pub async fn ensure_template_at_bottom(channel: ChannelId, ctx: &Context) -> Result<()> {
+/// The guild's intro channel from `/intro-channel set`, falling back to INTRO_CHANNEL_ID.
+pub async fn intro_channel(guild_id: Option<GuildId>) -> Result<Option<ChannelId>> {
+    let stored = match guild_id { Some(g) => db::get_intro_channel(g.0 as i64).await?, None => None };
+    Ok(stored.map(|c| c as u64).or(*DEFAULT_CHANNEL_ID).map(ChannelId))
+}
+
+pub async fn ensure_template_at_bottom(channel: ChannelId, guild_id: Option<GuildId>, ctx: &Context) -> Result<()> {
+    post_template(channel, guild_id, ctx, false).await
+}
+
+/// The guild's current template from `/intro-template`, or the built-in one.
+async fn template_content(guild_id: Option<GuildId>) -> Result<String> {
+    let stored = match guild_id { Some(g) => db::get_current_intro_template(g.0 as i64).await?, None => None };
+    Ok(stored.unwrap_or_else(|| DEFAULT_TEMPLATE.to_string()))
+}
+
+/// Post the template as the channel's last message; `force` replaces it even when it already is.
+async fn post_template(channel: ChannelId, guild_id: Option<GuildId>, ctx: &Context, force: bool) -> Result<()> {
     let _g = LOCK.lock().await;
     // fetch last message
     let messages = channel.messages(&ctx.http, |retriever| retriever.limit(1)).await?;
     if let Some(last) = messages.first() {
-        if is_intro_message(last) { return Ok(()); }
+        if is_intro_message(last) && !force { return Ok(()); }
     }
     // delete old templates
     let history = channel.messages(&ctx.http, |r| r.limit(200)).await?;
@@ -40,26 +94,472 @@ pub async fn ensure_template_at_bottom(channel: ChannelId, ctx: &Context) -> Res
         }
     }
     // send new template
-    let content = "自己紹介テンプレート\n```text\n- 名前: \n- 得意分野: \n- SNSリンク: \n- 一言: \n```\n-# EvexBot";
+    let content = format!("{}\n-# {}", template_content(guild_id).await?, MARKER);
     channel.say(&ctx.http, content).await?;
     Ok(())
 }
 
+fn author_name(message: &Message) -> String {
+    message.member.as_ref().and_then(|m| m.nick.clone()).unwrap_or_else(|| message.author.name.clone())
+}
+
+/// "Name: summary of the intro", or the generic reply thread name when the intro has no text.
+async fn thread_name(name: &str, message: &Message) -> String {
+    let name: String = name.chars().take(80).collect();
+    match thread_title::generate(&message.content, message.guild_id.map(|g| g.0 as i64)).await {
+        Some(title) => thread_title::with_prefix(&format!("{}: ", name), &title),
+        None => format!("{}さんへの返信はこちら", name),
+    }
+}
+
+/// A thread started from a message shares the message's id. `name` is whose intro it is.
+async fn open_intro_thread(ctx: &Context, intro: &Message, name: &str) -> Result<ChannelId> {
+    let name = thread_name(name, intro).await;
+    let thread = intro.channel_id.create_public_thread(&ctx.http, intro.id, |t| t.name(name).auto_archive_duration(10080)).await?;
+    Ok(thread.id)
+}
+
+/// Move a direct reply to someone else's intro into that intro's thread.
+/// Returns true when the reply was redirected.
+async fn redirect_reply(ctx: &Context, message: &Message) -> Result<bool> {
+    let intro = match &message.referenced_message { Some(m) => m, None => return Ok(false) };
+    if intro.author.bot || intro.author.id == message.author.id { return Ok(false); }
+
+    let thread = ChannelId(intro.id.0);
+    let mut content = format!("{} さんからの返信:\n{}", message.author.mention(), message.content);
+    for a in message.attachments.iter() {
+        content.push_str(&format!("\n{}", a.url));
+    }
+    if thread.say(&ctx.http, &content).await.is_err() {
+        // Intros posted before the option was enabled have no thread yet.
+        open_intro_thread(ctx, intro, &author_name(intro)).await?.say(&ctx.http, &content).await?;
+    }
+    message.delete(&ctx.http).await?;
+    Ok(true)
+}
+
+/// The acknowledgment reaction configured with `/intro reaction`; None when disabled.
+async fn reaction_for(guild_id: Option<GuildId>) -> Result<Option<ReactionType>> {
+    let stored = match guild_id { Some(g) => db::get_intro_react_emoji(g.0 as i64).await?, None => None };
+    Ok(match stored.as_deref() {
+        None => Some(ReactionType::Unicode(CHECK_EMOJI.to_string())),
+        Some("") => None,
+        Some(s) => ReactionType::try_from(s).ok(),
+    })
+}
+
+/// Accepts a unicode emoji or a custom emoji from this guild.
+async fn parse_reaction(http: &Http, guild_id: GuildId, input: &str) -> std::result::Result<ReactionType, &'static str> {
+    let input = input.trim();
+    let reaction = ReactionType::try_from(input).map_err(|_| "絵文字を指定してください。")?;
+    match &reaction {
+        ReactionType::Custom { id, .. } => {
+            if guild_id.emoji(http, *id).await.is_err() { return Err("このサーバーのカスタム絵文字を指定してください。"); }
+        }
+        ReactionType::Unicode(s) => {
+            if s.is_empty() || s.chars().count() > 8 || s.chars().any(|c| c.is_ascii_alphanumeric() || c.is_whitespace()) { return Err("絵文字を指定してください。"); }
+        }
+        _ => return Err("絵文字を指定してください。"),
+    }
+    Ok(reaction)
+}
+
 pub async fn handle_message(ctx: &Context, message: &Message) -> Result<()> {
-    if message.channel_id.0 != TARGET_CHANNEL_ID { return Ok(()); }
+    if !crate::intents::has(REQUIRED_INTENTS) || intro_channel(message.guild_id).await? != Some(message.channel_id) { return Ok(()); }
     if is_intro_message(message) { return Ok(()); }
-    ensure_template_at_bottom(message.channel_id, ctx).await?;
+
+    let auto_thread = match message.guild_id { Some(g) if !message.author.bot => db::get_intro_auto_thread(g.0 as i64).await?, _ => false };
+    if auto_thread && redirect_reply(ctx, message).await? { return Ok(()); }
+
+    ensure_template_at_bottom(message.channel_id, message.guild_id, ctx).await?;
     // react to user message
     if !message.author.bot {
-        let _ = message.react(&ctx.http, CHECK_EMOJI).await;
+        if let Some(emoji) = reaction_for(message.guild_id).await? {
+            let _ = message.react(&ctx.http, emoji).await;
+        }
+        if let (Some(g), None) = (message.guild_id, &message.message_reference) {
+            db::record_intro(g.0 as i64, message.author.id.0 as i64, message.id.0 as i64, message.timestamp.unix_timestamp()).await?;
+        }
+        if auto_thread { open_intro_thread(ctx, message, &author_name(message)).await?; }
+    }
+    Ok(())
+}
+
+/// Adds the "自己紹介を書く" button, which opens the intro form for whoever clicks it.
+pub fn write_button(c: &mut CreateComponents) -> &mut CreateComponents {
+    c.create_action_row(|row| row.create_button(|b| b.custom_id(WRITE_BUTTON_ID).label("自己紹介を書く").emoji('📝').style(ButtonStyle::Primary)))
+}
+
+/// The `- label:` lines of the template, which become the form's inputs.
+fn template_fields(template: &str) -> Vec<String> {
+    template.lines()
+        .filter_map(|l| l.trim().strip_prefix('-').and_then(|l| l.trim().strip_suffix(':').or_else(|| l.trim().strip_suffix('：'))))
+        .map(|l| l.trim().chars().take(MAX_LABEL_LENGTH).collect::<String>())
+        .filter(|l| !l.is_empty())
+        .take(MAX_FIELDS)
+        .collect()
+}
+
+pub async fn handle_component(ctx: &Context, comp: &MessageComponentInteraction) -> Result<()> {
+    let guild_id = comp.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+    if let Some(message_id) = db::get_intro_message(guild_id.0 as i64, comp.user.id.0 as i64).await? {
+        let channel = intro_channel(Some(guild_id)).await?.map(|c| c.0).unwrap_or_default();
+        let msg = format!("すでに自己紹介を投稿しています: https://discord.com/channels/{}/{}/{}", guild_id.0, channel, message_id);
+        comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+        return Ok(());
+    }
+
+    let fields = template_fields(&template_content(Some(guild_id)).await?);
+    comp.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::Modal).interaction_response_data(|d| {
+        d.custom_id(WRITE_MODAL_ID).title("自己紹介").components(|c| {
+            if fields.is_empty() {
+                // Free-form templates get a single text box.
+                c.create_action_row(|row| row.create_input_text(|t| t.custom_id("field:0").label("自己紹介").style(InputTextStyle::Paragraph).max_length(MAX_TEMPLATE_LENGTH as u64).required(true)));
+            }
+            for (i, label) in fields.iter().enumerate() {
+                c.create_action_row(|row| row.create_input_text(|t| t.custom_id(format!("field:{}", i)).label(label).style(InputTextStyle::Short).max_length(MAX_ANSWER_LENGTH as u64).required(false)));
+            }
+            c
+        })
+    })).await?;
+    Ok(())
+}
+
+/// Posts the submitted form in the intro channel on the member's behalf and treats it
+/// like an intro they wrote themselves.
+pub async fn handle_write_modal(ctx: &Context, modal: &ModalSubmitInteraction) -> Result<()> {
+    let guild_id = modal.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+    let fields = template_fields(&template_content(Some(guild_id)).await?);
+    let answers: Vec<String> = if fields.is_empty() {
+        modal_value(modal, "field:0").map(|v| v.trim().to_string()).into_iter().filter(|v| !v.is_empty()).collect()
+    } else {
+        fields.iter().enumerate().filter_map(|(i, label)| {
+            let value = modal_value(modal, &format!("field:{}", i)).unwrap_or_default();
+            let value = value.trim();
+            if value.is_empty() { None } else { Some(format!("- {}: {}", label, value)) }
+        }).collect()
+    };
+
+    let channel = intro_channel(Some(guild_id)).await?;
+    let msg = if answers.is_empty() {
+        "少なくとも1つの項目を入力してください。".to_string()
+    } else if db::get_intro_message(guild_id.0 as i64, modal.user.id.0 as i64).await?.is_some() {
+        "すでに自己紹介を投稿しています。".to_string()
+    } else if let Some(channel) = channel {
+        let intro = channel.say(&ctx.http, format!("{} さんの自己紹介\n{}", modal.user.mention(), answers.join("\n"))).await?;
+        // The channel handler skips bot messages, so do what it does for members here.
+        if let Some(emoji) = reaction_for(Some(guild_id)).await? {
+            let _ = intro.react(&ctx.http, emoji).await;
+        }
+        db::record_intro(guild_id.0 as i64, modal.user.id.0 as i64, intro.id.0 as i64, intro.timestamp.unix_timestamp()).await?;
+        if db::get_intro_auto_thread(guild_id.0 as i64).await? {
+            let name = modal.member.as_ref().and_then(|m| m.nick.clone()).unwrap_or_else(|| modal.user.name.clone());
+            open_intro_thread(ctx, &intro, &name).await?;
+        }
+        format!("自己紹介を投稿しました！ {}", intro.link())
+    } else {
+        "自己紹介チャンネルが設定されていません。管理者に `/intro-channel set` を依頼してください。".to_string()
+    };
+    modal.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+pub struct IntroCommand;
+
+#[async_trait]
+impl Command for IntroCommand {
+    fn name(&self) -> &'static str { "intro" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("自己紹介チャンネルの設定").create_option(|o| {
+            o.name("autothread").description("自己紹介ごとに返信用スレッドを自動作成します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|s| s.name("enabled").description("有効にする").kind(CommandOptionType::Boolean).required(true))
+        })
+        .create_option(|o| {
+            o.name("reaction").description("自己紹介に付けるリアクションを設定します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|s| s.name("emoji").description("絵文字 (none でリアクションなし、reset で ✅ に戻す)").kind(CommandOptionType::String).required(true))
+        })
+        .create_option(|o| o.name("stats").description("自己紹介の投稿状況を表示します").kind(CommandOptionType::SubCommand).create_sub_option(|s| chart::size_option(s)))
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_intro_command(inv.ctx, inv.command).await
+    }
+}
+
+pub struct IntroTemplateCommand;
+
+#[async_trait]
+impl Command for IntroTemplateCommand {
+    fn name(&self) -> &'static str { "intro-template" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("自己紹介テンプレートの管理")
+            .create_option(|o| o.name("edit").description("テンプレートを編集します").kind(CommandOptionType::SubCommand))
+            .create_option(|o| o.name("history").description("テンプレートの変更履歴を表示します").kind(CommandOptionType::SubCommand))
+            .create_option(|o| {
+                o.name("rollback").description("指定したバージョンのテンプレートに戻します").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("version").description("バージョン番号").kind(CommandOptionType::Integer).required(true))
+            })
+            .create_option(|o| {
+                o.name("lock").description("ロック中はロックした管理者以外がテンプレートを変更できなくなります").kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|s| s.name("enabled").description("ロックする").kind(CommandOptionType::Boolean).required(true))
+            })
+    }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        handle_template_command(inv.ctx, inv.command).await
+    }
+}
+
+/// Locked templates can only be changed by the admin who locked them (or a bot owner).
+async fn template_lock_error(guild_id: i64, user_id: UserId) -> Result<Option<String>> {
+    Ok(match db::get_intro_template_lock(guild_id).await? {
+        Some(locked_by) if locked_by as u64 != user_id.0 && !crate::owner::is_owner(user_id) => Some(format!("テンプレートは <@{}> によってロックされています。", locked_by)),
+        _ => None,
+    })
+}
+
+async fn save_template(ctx: &Context, guild_id: i64, content: &str, author: UserId) -> Result<i64> {
+    let version = db::add_intro_template_version(guild_id, content, author.0 as i64, chrono::Utc::now().timestamp()).await?;
+    if let Some(channel) = intro_channel(Some(GuildId(guild_id as u64))).await? {
+        post_template(channel, Some(GuildId(guild_id as u64)), ctx, true).await?;
+    }
+    Ok(version)
+}
+
+fn preview(content: &str) -> String {
+    let line = content.lines().find(|l| !l.trim().is_empty() && !l.starts_with("```")).unwrap_or("").trim();
+    if line.chars().count() > PREVIEW_LENGTH { format!("{}…", line.chars().take(PREVIEW_LENGTH).collect::<String>()) } else { line.to_string() }
+}
+
+async fn handle_template_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    let is_admin = permissions::is_admin(member).await;
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    // The editor is a modal, which has to be the first response.
+    if sub.name == "edit" {
+        let error = if !is_admin { Some(permissions::DENIED_MESSAGE.to_string()) } else { template_lock_error(guild_id, command.user.id).await? };
+        if let Some(msg) = error {
+            command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+            return Ok(());
+        }
+        let current = template_content(Some(GuildId(guild_id as u64))).await?;
+        command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::Modal).interaction_response_data(|d| {
+            d.custom_id("intro_template_edit").title("自己紹介テンプレート").components(|c| c.create_action_row(|row| {
+                row.create_input_text(|t| t.custom_id("content").label("テンプレート").style(InputTextStyle::Paragraph).value(current).max_length(MAX_TEMPLATE_LENGTH as u64).required(true))
+            }))
+        })).await?;
+        return Ok(());
+    }
+
+    command.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    if !is_admin { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+
+    match sub.name.as_str() {
+        "history" => {
+            let versions = db::get_intro_template_versions(guild_id).await?;
+            let current = versions.first().map(|v| v.0);
+            let report = Report::embed("intro_template_history.txt", "自己紹介テンプレートの履歴", serenity::utils::Colour::BLURPLE).ephemeral(true);
+            let report = if versions.is_empty() {
+                report.line("まだ編集されていません (標準のテンプレートを使用中)。")
+            } else {
+                report.lines(versions.iter().map(|(v, content, author, at)| {
+                    format!("`v{}`{} <@{}> <t:{}:f>\n{}", v, if Some(*v) == current { " (現在)" } else { "" }, author, at, preview(content))
+                }))
+            };
+            let report = match db::get_intro_template_lock(guild_id).await? {
+                Some(by) => report.header(format!("🔒 <@{}> がロック中", by)),
+                None => report,
+            };
+            report.send(&ctx.http, command).await?;
+        }
+        "rollback" => {
+            let version = sub.options.iter().find(|o| o.name=="version").and_then(|o| o.value.as_ref()).and_then(|v| v.as_i64()).unwrap_or(0);
+            let msg = if let Some(e) = template_lock_error(guild_id, command.user.id).await? {
+                e
+            } else {
+                match db::get_intro_template_version(guild_id, version).await? {
+                    Some(content) => {
+                        let new_version = save_template(ctx, guild_id, &content, command.user.id).await?;
+                        format!("テンプレートを v{} の内容に戻しました (v{} として保存)。", version, new_version)
+                    }
+                    None => format!("v{} は見つかりません。", version),
+                }
+            };
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+        }
+        "lock" => {
+            let enabled = sub.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+            let msg = if let Some(e) = template_lock_error(guild_id, command.user.id).await? {
+                e
+            } else if enabled {
+                db::update_intro_template_lock(guild_id, Some(command.user.id.0 as i64)).await?;
+                "テンプレートをロックしました。あなた以外の管理者は変更できません。".to_string()
+            } else {
+                db::update_intro_template_lock(guild_id, None).await?;
+                "テンプレートのロックを解除しました。".to_string()
+            };
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub async fn handle_template_modal(ctx: &Context, modal: &ModalSubmitInteraction) -> Result<()> {
+    let guild_id = modal.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let is_admin = match modal.member.as_ref() { Some(m) => permissions::is_admin(m).await, None => false };
+    let content = modal_value(modal, "content").unwrap_or_default();
+    let content = content.trim();
+
+    let msg = if !is_admin {
+        permissions::DENIED_MESSAGE.to_string()
+    } else if let Some(e) = template_lock_error(guild_id, modal.user.id).await? {
+        e
+    } else if content.is_empty() {
+        "テンプレートを入力してください。".to_string()
+    } else if db::get_current_intro_template(guild_id).await?.as_deref() == Some(content) {
+        "テンプレートは変更されていません。".to_string()
+    } else {
+        let version = save_template(ctx, guild_id, content, modal.user.id).await?;
+        format!("テンプレートを更新しました (v{})。/intro-template rollback で以前のバージョンに戻せます。", version)
+    };
+    modal.create_interaction_response(&ctx.http, |r| r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| d.content(msg).ephemeral(true))).await?;
+    Ok(())
+}
+
+async fn handle_intro_command(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    command.create_interaction_response(&ctx.http, |r| r.kind(serenity::model::application::interaction::InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| d.ephemeral(true))).await?;
+    let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+    if !permissions::is_admin(member).await { command.create_followup_message(&ctx.http, |m| m.content(permissions::DENIED_MESSAGE).ephemeral(true)).await?; return Ok(()); }
+    let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?.0 as i64;
+    let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+    match sub.name.as_str() {
+        "autothread" => {
+            let enabled = sub.options.iter().find(|o| o.name=="enabled").and_then(|o| o.value.as_ref()).and_then(|v| v.as_bool()).unwrap_or(false);
+            db::update_intro_auto_thread(guild_id, enabled).await?;
+            let msg = if enabled { "自己紹介ごとに返信用スレッドを作成し、チャンネルへの直接返信はスレッドへ移動します。" } else { "返信用スレッドの自動作成を無効にしました!" };
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+        }
+        "reaction" => {
+            let input = sub.options.iter().find(|o| o.name=="emoji").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str()).unwrap_or("");
+            let msg = match input.trim() {
+                "none" | "off" => {
+                    db::update_intro_react_emoji(guild_id, Some("")).await?;
+                    "自己紹介へのリアクションを無効にしました!".to_string()
+                }
+                "reset" => {
+                    db::update_intro_react_emoji(guild_id, None).await?;
+                    format!("自己紹介へのリアクションを {} に戻しました!", CHECK_EMOJI)
+                }
+                _ => match parse_reaction(&ctx.http, GuildId(guild_id as u64), input).await {
+                    Ok(reaction) => {
+                        db::update_intro_react_emoji(guild_id, Some(&reaction.to_string())).await?;
+                        format!("自己紹介へのリアクションを {} に設定しました!", reaction)
+                    }
+                    Err(e) => e.to_string(),
+                },
+            };
+            command.create_followup_message(&ctx.http, |m| m.content(msg).ephemeral(true)).await?;
+        }
+        "stats" => {
+            let size_opt = sub.options.iter().find(|o| o.name=="size").and_then(|o| o.value.as_ref()).and_then(|v| v.as_str());
+            let size = match chart::resolve(Some(guild_id), size_opt, chart::WIDE).await {
+                Ok(s) => s,
+                Err(e) => { command.create_followup_message(&ctx.http, |m| m.content(e).ephemeral(true)).await?; return Ok(()); }
+            };
+            let (embed, chart) = build_stats(&ctx.http, serenity::model::id::GuildId(guild_id as u64), size).await?;
+            command.create_followup_message(&ctx.http, |m| {
+                if let Some(c) = chart.as_ref() { m.add_file((c.bytes.as_slice(), c.filename().as_str())); }
+                m.embed(|e| { *e = embed; e }).ephemeral(true)
+            }).await?;
+        }
+        _ => {}
     }
     Ok(())
 }
 
-pub async fn handle_message_delete(_ctx: &Context, _deleted_message_id: serenity::model::id::MessageId, _guild_id: Option<serenity::model::id::GuildId>) -> Result<()> {
-    // When a message is deleted in the target channel, ensure template
-    // We don't have channel id directly here, so just attempt to ensure using target constant
-    let channel = ChannelId(TARGET_CHANNEL_ID);
-    ensure_template_at_bottom(channel, _ctx).await.ok();
+pub async fn handle_message_delete(ctx: &Context, channel_id: ChannelId, guild_id: Option<GuildId>) -> Result<()> {
+    // When a message is deleted in the intro channel, make sure the template is still last
+    if intro_channel(guild_id).await? != Some(channel_id) { return Ok(()); }
+    ensure_template_at_bottom(channel_id, guild_id, ctx).await.ok();
     Ok(())
 }
+
+async fn build_stats(http: &Http, guild_id: serenity::model::id::GuildId, size: chart::ChartSize) -> Result<(serenity::builder::CreateEmbed, Option<chart::Rendered>)> {
+    let gid = guild_id.0 as i64;
+    let intros = db::get_intros(gid).await?;
+    let posted: std::collections::HashSet<u64> = intros.iter().map(|(u, _)| *u as u64).collect();
+    let members: Vec<_> = member_cache::members(http, guild_id).await?.into_iter().filter(|m| !m.user.bot).collect();
+
+    let posted_members = members.iter().filter(|m| posted.contains(&m.user.id.0)).count();
+    let cutoff = chrono::Utc::now().timestamp() - RECENT_JOIN_DAYS * 86400;
+    let recent: Vec<_> = members.iter().filter(|m| m.joined_at.map(|j| j.unix_timestamp() >= cutoff).unwrap_or(false)).collect();
+    let recent_posted = recent.iter().filter(|m| posted.contains(&m.user.id.0)).count();
+    let rate = |n: usize, d: usize| if d == 0 { "-".to_string() } else { format!("{:.1}%", n as f64 / d as f64 * 100.0) };
+
+    let mut embed = serenity::builder::CreateEmbed::default();
+    embed.title("自己紹介の投稿状況");
+    embed.field("投稿済みメンバー", format!("{}人 / {}人 ({})", posted_members, members.len(), rate(posted_members, members.len())), false);
+    embed.field(format!("直近{}日の参加者", RECENT_JOIN_DAYS), format!("{}人中{}人が投稿 ({})", recent.len(), recent_posted, rate(recent_posted, recent.len())), false);
+
+    // Trend: share of members (from daily snapshots) who had posted an intro by each day.
+    let today = chrono::Utc::now().date_naive();
+    let since = today - chrono::Duration::days(TREND_DAYS);
+    let snapshots = db::get_member_snapshots(gid, &since.to_string(), &today.to_string()).await?;
+    let mut dates = Vec::new();
+    let mut values = Vec::new();
+    for (day, count) in snapshots.iter() {
+        let date = match chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d") { Ok(d) => d, Err(_) => continue };
+        if *count <= 0 { continue; }
+        let end_of_day = match date.succ_opt() { Some(d) => d.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp(), None => continue };
+        let cumulative = intros.iter().filter(|(_, t)| *t < end_of_day).count() as i64;
+        dates.push(date);
+        values.push((cumulative * 100 / count) as i32);
+    }
+
+    let chart = if dates.len() >= 2 {
+        let chart = chart::finish(Some(gid), members_history::create_line_chart("Intro Rate (%)", &dates, &values, size, crate::chart::Locale::for_guild(Some(gid)).await)?, "intro_trend").await;
+        embed.image(chart.url());
+        Some(chart)
+    } else {
+        embed.description("メンバー数の記録が不足しているため推移グラフは表示できません。");
+        None
+    };
+    embed.color(serenity::utils::Colour::GOLD);
+    embed.footer(|f| f.text("EvexBot | Intro Stats"));
+    Ok((embed, chart))
+}
+
+pub struct IntroChannelCommand;
+
+#[async_trait]
+impl Command for IntroChannelCommand {
+    fn name(&self) -> &'static str { "intro-channel" }
+
+    fn define<'a>(&self, c: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        c.description("自己紹介チャンネルの設定").create_option(|s| {
+            s.name("set").description("自己紹介チャンネルを設定し、テンプレートを投稿します").kind(CommandOptionType::SubCommand)
+                .create_sub_option(|o| o.name("channel").description("自己紹介チャンネル").kind(CommandOptionType::Channel).channel_types(&[ChannelType::Text]).required(true))
+        })
+    }
+
+    fn defer(&self) -> Defer { Defer::Ephemeral }
+
+    async fn run(&self, inv: &Invocation<'_>) -> Result<()> {
+        let command = inv.command;
+        let member = command.member.as_ref().ok_or_else(|| anyhow::anyhow!("member required"))?;
+        let guild_id = command.guild_id.ok_or_else(|| anyhow::anyhow!("guild required"))?;
+        let sub = command.data.options.first().ok_or_else(|| anyhow::anyhow!("subcommand required"))?;
+
+        if !permissions::is_admin(member).await { return inv.say(permissions::DENIED_MESSAGE).await; }
+        let channel = match Args::new(&sub.options).channel("channel") { Some(c) => c.id, None => return Ok(()) };
+        db::update_intro_channel(guild_id.0 as i64, channel.0 as i64).await?;
+        post_template(channel, Some(guild_id), inv.ctx, false).await?;
+        inv.say(format!("自己紹介チャンネルを <#{}> に設定しました。", channel.0)).await
+    }
+}